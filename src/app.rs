@@ -1,4 +1,53 @@
-use crate::storage;
+use crate::autocorrect::{self, TableAction};
+use crate::bookmarks::{self, Bookmark, BookmarkAction};
+use crate::characters::{self, CharacterTable};
+use crate::comments;
+use crate::compile::{self, CompileTarget, Part, Separator};
+use crate::completion::{self, Completion};
+use crate::corkboard::{self, CorkboardAction};
+use crate::dialogs;
+use crate::diff;
+use crate::docx;
+use crate::editing::{self, EditCommand, EditKey, QuoteStyle};
+use crate::encoding::{DecodedText, LineEnding, TextEncoding};
+use crate::encryption;
+use crate::export::{self, Exporter};
+use crate::fdx;
+use crate::file_io::{FileRequest, FileResult, FileWorker, IoState};
+use crate::file_types;
+use crate::fonts;
+use crate::format::{self, DocumentFormat};
+use crate::formatted::{self, Block};
+use crate::git;
+use crate::goals::{self, DailyTally, GoalPeriod, Sprint, WritingSession};
+use crate::history::{History, HistoryLimits, Restored};
+use crate::incremental::ParsedDocument;
+use crate::inspector::{self, SceneDraft};
+use crate::instance;
+use crate::outline;
+use crate::parser::{
+    self, Comment, Direction, DocStats, DocumentStructure, Landmark, ParsedLine, ValidationIssue,
+};
+use crate::pdf;
+use crate::print;
+use crate::problems;
+use crate::profile::{self, ProfileImport};
+use crate::project::{self, OpenProject, ProjectAction};
+use crate::project_search::{self, ProjectSearch, SearchAction};
+use crate::read_aloud::{self, Progress, ReadAloud, ToolbarAction};
+use crate::readability;
+use crate::search::{self, FindState};
+use crate::settings::{self, EditorFont, Settings, Theme};
+use crate::snippets::{self, Snippet};
+use crate::statistics::{self, Breakdown, Report};
+use crate::storage::{self, BackupRetention, Storage};
+use crate::style::{self, StyleCheck};
+use crate::tabs::{self, DocumentTab, TabAction, TabLabel};
+use crate::templates::{self, Template};
+use crate::text_utils::{self, PunctuationStyle};
+use crate::theme;
+use crate::thesaurus::{self, Lookup, LookupKind, WordNet};
+use crate::toc::{self, References};
 /// FILE: src/app.rs
 ///
 /// This module contains our main App struct and implements the eframe::App trait.
@@ -10,6 +59,9 @@ use crate::storage;
 /// - impl blocks: Where we define methods on structs
 /// - Mutable references (&mut): Allowing safe modification of data
 /// - Arc<Mutex<T>>: Thread-safe shared ownership with interior mutability
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
 
@@ -17,28 +69,598 @@ use std::thread;
 // APP STRUCT - APPLICATION STATE
 // ============================================================================
 
+/// Seconds the text must sit unchanged before it is re-validated
+/// Validation runs on a pause in typing, not on every keystroke.
+const VALIDATION_DELAY: f64 = 0.5;
+
+/// Most matches the Go to prompt lists
+const GO_TO_LIST_LENGTH: usize = 10;
+
+/// Seconds the status bar reappears for in focus mode when the status
+/// message changes
+const STATUS_FLASH_SECONDS: f64 = 3.0;
+
+/// Minimum seconds between writes of today's word total to the progress
+/// history (it is also written on exit)
+const PROGRESS_SAVE_INTERVAL: f64 = 60.0;
+
+/// How many days the Progress History window shows
+const PROGRESS_HISTORY_DAYS: u64 = 30;
+
+/// How many days the status bar's sparkline shows
+const SPARKLINE_DAYS: usize = 14;
+
+/// Minimum seconds between copies of the changing text sent to the
+/// autosave thread (the thread checks its timer once a second anyway)
+const AUTOSAVE_COPY_INTERVAL: f64 = 1.0;
+
+/// Id source of the main editor's TextEdit
+/// Anything that reads the editor's cursor/selection state needs the same Id.
+const EDITOR_ID: &str = "main_editor";
+
+/// Id source of the second editor View → Split Editor shows
+const SECOND_EDITOR_ID: &str = "second_editor";
+
+/// Width of the editor's left margin, where bookmarks are marked (points)
+const BOOKMARK_GUTTER: f32 = 16.0;
+
+/// Id of the find bar's query field, so Ctrl+F can focus it
+const FIND_FIELD_ID: &str = "find_query";
+
+/// Sample text for the Editor Font window's preview
+const FONT_PREVIEW: &str =
+    "[CHAPTER: One]\n\nThe house stood at the end of the lane, its windows dark. \
+She had not been back in eleven years, and the gate still stuck.\n\n\"Anyone home?\" she called.";
+
+/// Something the user asked for that would throw away unsaved edits
+///
+/// When the document is dirty, the action is parked here while the
+/// "Save changes?" prompt is open, and carried out (or dropped) depending
+/// on the answer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PendingAction {
+    /// Close the window (File → Exit or the OS close button)
+    /// With several unsaved tabs, the prompt comes up for each in turn.
+    Exit,
+    /// Close the active tab (File → Close Tab, Ctrl+W, or its ×)
+    CloseTab,
+}
+
+/// How View → Split Editor divides the editor area
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EditorSplit {
+    /// The second editor to the right of the first
+    SideBySide,
+    /// The second editor below the first
+    TopAndBottom,
+}
+
+/// What the Version History window lists
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HistorySource {
+    /// Snapshots (File → Version History…)
+    Snapshots,
+    /// Backups made when saving (File → Restore from Backup…)
+    Backups,
+}
+
+/// What the user did with a named snapshot in the Snapshots panel
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SnapshotAction {
+    Restore,
+    Compare,
+    Delete,
+}
+
+/// Which document the app starts with (chosen on the command line)
+#[derive(Debug, Clone, PartialEq)]
+pub enum StartupDocument {
+    /// The last session's document, if session restore is on (no arguments)
+    Restore,
+    /// An empty, untitled document (`--new`)
+    Empty,
+    /// This file (`bookscript mychapter.bks`); the path is absolute
+    File(std::path::PathBuf),
+}
+
 /// The App struct holds all the state for our application.
 ///
 /// OWNERSHIP & THREADING:
-/// - `text_content` is wrapped in Arc<Mutex<String>>
-/// - Arc = "Atomic Reference Counted" = allows multiple owners
-/// - Mutex = "Mutual exclusion" = ensures only one thread accesses at a time
-/// - We need this because the autosave thread and GUI thread both access it
+/// - `text_content` is a plain String owned by the GUI thread
+/// - The autosave thread never touches it: sync_autosave() SENDS it a copy
+///   over a channel, at most once a second and only when it has changed
+/// - So the GUI never waits on a lock, however big the manuscript
 pub struct App {
     /// The text being edited by the user
-    /// Arc<Mutex<String>> allows both the GUI thread and autosave thread
-    /// to safely access the same string. When you want to read or write,
-    /// you call .lock() to get exclusive access.
-    text_content: Arc<Mutex<String>>,
+    text_content: String,
+
+    /// Every open document, in tab bar order. The active one's slot is a
+    /// placeholder: its document lives in the fields of App itself (see
+    /// tabs.rs), and is parked back in the slot when another tab is chosen.
+    tabs: Vec<DocumentTab>,
+
+    /// Index into `tabs` of the document in the editor
+    active_tab: usize,
 
     /// Path to the current project file
     /// Option<T> means "this might be Some(value) or None"
     /// We use None when no file is open yet
     current_file_path: Option<std::path::PathBuf>,
 
+    /// The current file's modification time when it was last loaded or
+    /// saved here (None for untitled documents)
+    /// A save first checks the file still has this time - if not, someone
+    /// else changed it and the save stops (see save_conflict).
+    disk_modified: Option<std::time::SystemTime>,
+
+    /// A save that stopped because the file changed on disk; the "File
+    /// changed on disk" prompt is open while this is set
+    save_conflict: Option<std::path::PathBuf>,
+
+    /// View → Read-only: the editor ignores typing, undo and restores
+    read_only: bool,
+
+    /// Line endings the current document had on disk, and gets saved with
+    /// (the editor itself always uses `\n`)
+    line_ending: LineEnding,
+
+    /// The encoding the current document was read in, and gets saved in
+    encoding: TextEncoding,
+
+    /// The password the current document is encrypted with on save (None
+    /// for an ordinary document)
+    password: Option<String>,
+
     /// Status message shown at the bottom of the window
     /// (e.g., "Autosaved at 14:23:45" or "File loaded successfully")
     status_message: String,
+
+    /// Format guessed from the file extension and/or content
+    /// Recomputed whenever a file is loaded or saved under a new name
+    detected_format: DocumentFormat,
+
+    /// Format explicitly chosen by the user in the status-bar dropdown
+    /// None means "trust detected_format". Persisted in the sidecar file.
+    format_override: Option<DocumentFormat>,
+
+    /// Whether the Tools → Recently deleted window is open
+    show_trash_window: bool,
+
+    /// Whether the Tools → Character report window is open
+    show_characters_window: bool,
+
+    /// The character report and its sort order
+    characters: CharacterTable,
+
+    /// The edit_generation `characters` was computed at
+    characters_generation: Option<u64>,
+
+    /// Whether the Tools → Statistics window is open, its report and which
+    /// breakdown its chart shows
+    show_statistics_window: bool,
+    statistics: Report,
+    statistics_breakdown: Breakdown,
+    /// The structure_key the report was built for
+    statistics_key: Option<(u64, bool, DocumentFormat)>,
+    /// Readability counts of the paragraphs and speeches the report was
+    /// last built from, so a rebuild only counts what changed
+    readability_cache: readability::Cache,
+
+    /// Whether the Tools → Style Check window is open, and its findings
+    show_style_window: bool,
+    style_check: StyleCheck,
+
+    /// The Define / Synonyms popup, and the word under the editor's last
+    /// right-click (what its menu would look up)
+    lookup: Option<Lookup>,
+    context_word: Option<Lookup>,
+    /// The WordNet data once it's loaded (Err: why it couldn't be), and
+    /// the thread loading it
+    wordnet: Option<Result<WordNet, String>>,
+    wordnet_loading: Option<Receiver<anyhow::Result<WordNet>>>,
+
+    /// Tools → Read Aloud in progress (its toolbar shows while Some)
+    read_aloud: Option<ReadAloud>,
+
+    /// Whether the View → Characters panel is showing, and the text of
+    /// its "New character" field
+    show_characters_panel: bool,
+    new_character_name: String,
+
+    /// Whether the View → Scene Inspector panel is showing
+    show_scene_inspector: bool,
+
+    /// Whether View → Corkboard shows the scenes as cards in place of the
+    /// editor
+    show_corkboard: bool,
+
+    /// Whether the View → Comments panel is showing, the comments it lists,
+    /// and the edit_generation they were found at
+    show_comments_panel: bool,
+    comments: Vec<Comment>,
+    comments_generation: Option<u64>,
+
+    /// Whether the View → Formatted Preview panel is showing, its blocks,
+    /// and the edit_generation they were built at
+    show_formatted_preview: bool,
+    formatted_blocks: Vec<Block>,
+    formatted_generation: Option<u64>,
+
+    /// Whether the View → Bookmarks panel is showing, and the
+    /// edit_generation the open document's bookmarks were last placed at (the
+    /// bookmarks themselves are kept in `session`)
+    show_bookmarks_panel: bool,
+    bookmarks_generation: Option<u64>,
+
+    /// The caret's scene as the inspector edits it, and whether one of its
+    /// fields had the keyboard last frame (the draft is then kept rather
+    /// than read back from the text)
+    scene_draft: Option<SceneDraft>,
+    scene_draft_editing: bool,
+
+    /// Whether the File → Version History window is open, and whether it
+    /// lists snapshots or save backups
+    show_snapshots_window: bool,
+    history_source: HistorySource,
+
+    /// Snapshots (or backups) of the current document listed in that
+    /// window, newest first, with their word counts
+    snapshot_entries: Vec<(storage::Snapshot, usize)>,
+
+    /// The snapshot selected for preview: its index in snapshot_entries
+    /// and its text
+    snapshot_preview: Option<(usize, String)>,
+
+    /// The name being typed in File → Take Snapshot… (None while that
+    /// prompt is closed)
+    snapshot_name: Option<String>,
+
+    /// Whether the View → Snapshots panel is showing, the open document's
+    /// named snapshots in the open project (newest first), and the project
+    /// and document they were read for
+    show_named_snapshots: bool,
+    named_snapshots: Vec<storage::NamedSnapshot>,
+    named_snapshots_for: Option<(std::path::PathBuf, std::path::PathBuf)>,
+
+    /// A version compared with the editor text in the Compare window: what
+    /// it is, and its text
+    comparison: Option<(String, String)>,
+
+    /// The differences between that version and the editor text, and the
+    /// edit_generation they were worked out at
+    comparison_diff: Option<(u64, diff::Diff)>,
+
+    /// Whether the View → Git panel is showing, what it last read of the
+    /// open project's repository (None if the project isn't in one), and
+    /// the project file it was read for
+    show_git_panel: bool,
+    git_status: Option<git::RepoStatus>,
+    git_status_for: Option<std::path::PathBuf>,
+
+    /// The message being typed in the Commit prompt (None while it's
+    /// closed)
+    commit_message: Option<String>,
+
+    /// A password-protected file waiting for its password: the file, the
+    /// password being typed, and whether the last one tried was wrong
+    unlock_prompt: Option<(std::path::PathBuf, String, bool)>,
+
+    /// File → Set Password… while it's open: the new password and the same
+    /// again, typed twice to catch a typo
+    password_prompt: Option<(String, String)>,
+
+    /// What the document in front is a read-only preview of, e.g.
+    /// "one.bks at 1a2b3c4" (a version from the Git panel); None for an
+    /// ordinary document
+    preview: Option<String>,
+
+    /// Snapshot of the trash contents shown in that window
+    /// Refreshed when the window opens and after every restore/purge
+    trash_entries: Vec<storage::TrashEntry>,
+
+    /// Words written since launch, across every document opened
+    writing_session: WritingSession,
+
+    /// Words written today, including earlier sessions
+    daily_tally: DailyTally,
+
+    /// Where daily totals are recorded (None if the data directory couldn't
+    /// be found - progress then isn't kept)
+    progress_path: Option<std::path::PathBuf>,
+
+    /// Documents for File → Open Recent, most recent first
+    recent_files: Vec<std::path::PathBuf>,
+
+    /// Where the recent files list is kept (None if the data directory
+    /// couldn't be found - the list then lasts for this session only)
+    recent_files_path: Option<std::path::PathBuf>,
+
+    /// The user's own templates for File → New from Template, and where
+    /// they're kept (None if the data directory couldn't be found)
+    user_templates: Vec<Template>,
+    templates_path: Option<std::path::PathBuf>,
+
+    /// The name being typed in Save as Template… (None while that prompt
+    /// is closed)
+    template_name: Option<String>,
+
+    /// The Insert menu's snippets, from the snippets file (the example's
+    /// if it couldn't be read)
+    snippets: Vec<Snippet>,
+
+    /// Today's total as last written to the history file, and when (egui
+    /// time) it was written
+    progress_recorded: (i64, f64),
+
+    /// Whether the Tools → Writing Goal window is open
+    show_goal_window: bool,
+
+    /// The character name list open under the caret, and where it's drawn
+    completion: Option<Completion>,
+    completion_anchor: egui::Pos2,
+
+    /// Where the list Esc closed started, so it stays closed until the
+    /// caret moves on to another word
+    completion_dismissed: Option<usize>,
+
+    /// The document's speakers, and the edit_generation they were found at
+    completion_names: Option<(u64, Vec<String>)>,
+
+    /// The sprint being run (Tools → Sprint), if any
+    sprint: Option<Sprint>,
+
+    /// Whether the Tools → Sprint window is open
+    show_sprint_window: bool,
+
+    /// Past sprints for that window, newest first, and where they're kept
+    /// (None if the data directory couldn't be found)
+    sprint_history: Vec<storage::SprintRecord>,
+    sprints_path: Option<std::path::PathBuf>,
+
+    /// Whether the Edit → Clean Up Document window is open
+    show_cleanup_window: bool,
+
+    /// Whether the File → Export → PDF window is open, and the title it
+    /// puts on the title page (suggested from the file name when opened)
+    show_pdf_window: bool,
+    pdf_title: String,
+
+    /// Whether the File → Print… window is open, with the pages it shows
+    /// and what they were laid out from (edit_generation, comments kept, title
+    /// and PDF options)
+    show_print_window: bool,
+    print_pages: Vec<pdf::Page>,
+    print_pages_for: Option<(u64, bool, String, pdf::PdfOptions)>,
+
+    /// The same for the File → Export → Word (DOCX) window
+    show_docx_window: bool,
+    docx_title: String,
+
+    /// Whether the Tools → Progress History window is open
+    show_progress_window: bool,
+
+    /// Days listed in that window and the status bar's sparkline, newest
+    /// first. Read at startup, kept current as today's total is recorded,
+    /// and re-read from the history file when the window opens.
+    progress_entries: Vec<storage::DailyProgress>,
+
+    /// User preferences (editing behaviors, ...)
+    settings: Settings,
+
+    /// The theme currently set on the egui context
+    /// Compared against settings.theme each frame, so a change from
+    /// Preferences or a profile import takes effect immediately. None
+    /// makes the next frame apply it again (View → Theme → Reload).
+    applied_theme: Option<Theme>,
+
+    /// The custom editor font file last handed to egui, and whether it
+    /// could be used (so a bad file is reported once, not every frame)
+    loaded_font: Option<(std::path::PathBuf, bool)>,
+
+    /// Whether the View → Editor Font window is open
+    show_font_window: bool,
+
+    /// Where preferences are saved (None if the data directory couldn't be
+    /// found - settings then last for this session only)
+    settings_path: Option<std::path::PathBuf>,
+
+    /// A profile that has been read and is waiting for the user to confirm
+    /// it in the import preview window
+    pending_import: Option<ProfileImport>,
+
+    /// The text's parsed lines and structure, re-parsed line by line as
+    /// the text changes rather than all at once (see parsed_lines())
+    parsed: ParsedDocument,
+
+    /// Chapters and scenes parsed from the current text, for the outline
+    structure: DocumentStructure,
+
+    /// Undo/redo steps for the current document (Ctrl+Z / Ctrl+Y)
+    history: History,
+
+    /// Goes up by one every time text_content changes (see text_edited)
+    /// Cached results (structure, statistics) compare against it to decide
+    /// whether they need recomputing, so an idle document costs nothing -
+    /// hashing the text every frame did cost, on a long novel.
+    edit_generation: u64,
+
+    /// Word/character counts for the whole document, and the edit_generation and
+    /// exclude-tags option they were computed with
+    stats: DocStats,
+    stats_key: Option<(u64, bool)>,
+
+    /// Counts for the editor's selection, keyed by edit_generation, selected char
+    /// range, and exclude-tags option (None when nothing is selected)
+    selection_stats: Option<((u64, usize, usize, bool), DocStats)>,
+
+    /// The caret's place for the status bar ("Line 12, Col 4 — Act I ›
+    /// ..."), keyed by edit_generation and caret position
+    caret_location: Option<((u64, usize), String)>,
+
+    /// The edit_generation `structure` was built at, the exclude-tags
+    /// option its word counts used, and the mode it was parsed in
+    structure_key: Option<(u64, bool, DocumentFormat)>,
+
+    /// Whether the outline sidebar is visible (View → Outline)
+    show_outline: bool,
+
+    /// The open project, if any; the sidebar shows its files instead of
+    /// just the outline
+    project: Option<OpenProject>,
+
+    /// Whether the Edit → Find in Project panel is showing, and its query,
+    /// results and background worker
+    show_project_search: bool,
+    project_search: ProjectSearch,
+
+    /// A Find in Project result waiting for its document to load: the
+    /// file, the 1-based line, and the match's byte range to select
+    pending_match: Option<(std::path::PathBuf, usize, Range<usize>)>,
+
+    /// Whether the File → Project Settings window is open
+    show_project_window: bool,
+
+    /// Whether the File → Compile window is open
+    show_compile_window: bool,
+
+    /// Focus mode (View → Focus mode, F11): no menu bar, status bar or
+    /// outline, and the text in a centered column
+    focus_mode: bool,
+
+    /// Whether the window was already full screen when focus mode started,
+    /// so leaving it doesn't take the window out of full screen too
+    fullscreen_before_focus: bool,
+
+    /// The status message as of the last frame, to notice when it changes
+    last_status: String,
+
+    /// Until when (egui time, seconds) the status bar stays visible in
+    /// focus mode after the message changed
+    status_visible_until: f64,
+
+    /// Tag problems found in the current text, shown in the Problems list
+    problems: Vec<ValidationIssue>,
+
+    /// The edit_generation `problems` was computed at
+    problems_generation: Option<u64>,
+
+    /// A newer edit_generation waiting to be validated, and when it was first
+    /// seen - validation waits until it has been unchanged for VALIDATION_DELAY
+    pending_validation: Option<(u64, f64)>,
+
+    /// The Edit → Go to (Ctrl+G) prompt's text; Some while it's open
+    go_to_query: Option<String>,
+
+    /// Which of the prompt's matches Enter goes to (Up/Down move it)
+    go_to_selected: usize,
+
+    /// The find bar's query, options and matches (kept while it's closed,
+    /// so reopening it searches for the same thing)
+    find: FindState,
+
+    /// Whether the find bar (Ctrl+F / Ctrl+H) is showing above the editor
+    show_find_bar: bool,
+
+    /// Move keyboard focus to the find field on the next frame
+    focus_find_field: bool,
+
+    /// A char range the editor should select and scroll to on the next
+    /// frame - set by Find Next/Previous, consumed by the central panel
+    pending_selection: Option<Range<usize>>,
+
+    /// A 1-based line number the editor should jump to on the next frame
+    /// Set by clicks in the outline, Problems list or character report,
+    /// consumed by the central panel
+    pending_jump: Option<usize>,
+
+    /// True when the text has been edited since the last save or load
+    /// Autosave writes to its own file, so it does NOT clear this flag.
+    dirty: bool,
+
+    /// An action waiting on the "Save changes?" prompt (None = no prompt)
+    unsaved_prompt: Option<PendingAction>,
+
+    /// Set once the user has agreed to close, so the next close request
+    /// from the OS is let through instead of being intercepted again
+    allow_close: bool,
+
+    /// The window title we last sent, so we only send a new one on change
+    window_title: String,
+
+    /// Settings and file path shared with the autosave thread
+    autosave_state: Arc<Mutex<storage::AutosaveState>>,
+
+    /// Results from the autosave thread, drained every frame
+    autosave_events: Receiver<storage::AutosaveEvent>,
+
+    /// Requests to the autosave thread (File → Autosave Now)
+    autosave_commands: Sender<storage::AutosaveCommand>,
+
+    /// An autosave failure shown in a banner until dismissed
+    autosave_warning: Option<String>,
+
+    /// The most recent autosave failure, so the same error repeating every
+    /// interval doesn't bring back a dismissed banner (cleared by a success)
+    last_autosave_error: Option<String>,
+
+    /// The edit_generation and file of the last copy sent to the autosave
+    /// thread, and when (egui time) it was sent
+    autosave_sent: Option<(u64, Option<std::path::PathBuf>)>,
+    autosave_sent_at: f64,
+
+    /// Whether the Edit → Preferences window is open
+    show_preferences: bool,
+
+    /// Where this run's session file lives (None if the data directory
+    /// couldn't be found - crash recovery is then simply unavailable)
+    session_path: Option<std::path::PathBuf>,
+
+    /// This run's session record, rewritten when the open document changes
+    /// and marked clean on exit
+    session: storage::SessionInfo,
+
+    /// Autosaves left behind by a crashed session, newest first
+    /// The recovery prompt offers them one at a time until the list is empty.
+    recovery_candidates: Vec<storage::RecoveryCandidate>,
+
+    /// Text of the first recovery candidate, once the user asks to see it
+    /// (Err holds why it couldn't be read)
+    recovery_preview: Option<Result<String, String>>,
+
+    /// The editor's cursor (char index) and scroll offset as of the last
+    /// frame, written to the session file on exit
+    editor_cursor: usize,
+    editor_scroll: f32,
+
+    /// Whether View → Split Editor shows a second editor on the same text,
+    /// and which way the area is divided
+    split_editor: Option<EditorSplit>,
+
+    /// Cursor and scroll offset to put the editor back to on the next
+    /// frame (set by session restore)
+    pending_view: Option<(Option<usize>, Option<f32>)>,
+
+    /// Session restore's cursor and scroll offset, held until the restored
+    /// document has finished loading
+    pending_restore: Option<(Option<usize>, Option<f32>)>,
+
+    /// Background thread that loads and saves documents
+    file_io: FileWorker,
+
+    /// Where the document lives - shared with the file worker and the
+    /// autosave thread
+    storage: Arc<dyn Storage>,
+
+    /// What copies of the app started since have handed over (see
+    /// instance.rs); None if this copy isn't listening for them
+    instance_requests: Option<Receiver<instance::Request>>,
+    /// Documents waiting to open, handed over or dropped on the window,
+    /// oldest first; they open one at a time as the file worker comes free
+    pending_opens: Vec<instance::Request>,
+
+    /// An action from the "Save changes?" prompt, waiting for its save to
+    /// finish (and dropped if the save fails)
+    after_save: Option<PendingAction>,
 }
 
 // ============================================================================
@@ -51,216 +673,7783 @@ impl App {
     /// `cc` (CreationContext) is provided by eframe and contains info about
     /// the rendering context, storage, and integration settings.
     ///
-    /// We mark it with underscore `_cc` to tell the compiler "we know we're
-    /// not using this parameter yet, but we might need it later."
-    pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        // Create a new empty String and wrap it in Arc<Mutex<>> for sharing
-        // Arc::new() creates the reference-counted pointer
-        // Mutex::new() creates the lock around the String
-        let text_content = Arc::new(Mutex::new(String::new()));
+    /// `settings` are the preferences main() read from disk before the
+    /// window was created. `storage` is where the document is loaded from,
+    /// saved to and autosaved to (storage::DiskStorage outside tests).
+    /// `server` answers copies of the app started later, if this is the
+    /// first (see instance.rs).
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        settings: Settings,
+        startup: StartupDocument,
+        storage: Arc<dyn Storage>,
+        server: Option<instance::Server>,
+    ) -> Self {
+        let text_content = String::new();
 
-        // Clone the Arc to create a second pointer to the same data
-        // This doesn't clone the String itself, just the pointer!
-        // Arc uses atomic reference counting to track how many pointers exist
-        let text_for_autosave = Arc::clone(&text_content);
+        // The thread also needs the settings and current file path, which
+        // change while it runs - so they get their own shared, locked struct
+        let autosave_state = Arc::new(Mutex::new(storage::AutosaveState::default()));
+        let state_for_autosave = Arc::clone(&autosave_state);
+        let storage_for_autosave = Arc::clone(&storage);
+
+        // Channels for everything else: commands go to the thread, results
+        // come back
+        let (autosave_commands, commands_for_autosave) = mpsc::channel();
+        let (events_for_autosave, autosave_events) = mpsc::channel();
 
         // --------------------------------------------------------------------
         // SPAWN AUTOSAVE THREAD
         // --------------------------------------------------------------------
         // thread::spawn creates a new OS thread that runs concurrently
         // The thread runs the closure we pass to it
-        // `move` keyword: the closure takes ownership of text_for_autosave
+        // `move` keyword: the closure takes ownership of storage_for_autosave,
+        // state_for_autosave and the thread's ends of the channels
         thread::spawn(move || {
             // This code runs in a separate thread, independent of the GUI
             // Call our autosave function (defined in storage.rs)
-            storage::autosave_thread(text_for_autosave);
+            storage::autosave_thread(
+                storage_for_autosave,
+                state_for_autosave,
+                commands_for_autosave,
+                events_for_autosave,
+            );
             // When this function returns, the thread exits
         });
 
+        // --------------------------------------------------------------------
+        // EMPTY OLD TRASH
+        // --------------------------------------------------------------------
+        // Files deleted more than 30 days ago are removed for good, and the
+        // first status message says so. A failure here only means the trash
+        // stays a little fuller, so we log it instead of bothering the user.
+        let startup_status = match storage::purge_expired_trash() {
+            Ok(0) => String::from("Ready"),
+            Ok(n) => format!("Permanently deleted {} file(s) trashed over 30 days ago", n),
+            Err(e) => {
+                eprintln!("Trash purge failed: {}", e);
+                String::from("Ready")
+            }
+        };
+
+        // --------------------------------------------------------------------
+        // CRASH RECOVERY
+        // --------------------------------------------------------------------
+        // Look at how the previous run ended BEFORE recording this one,
+        // since recording this one overwrites the session file.
+        let session_path = storage::get_session_path().ok();
+        let previous_session = session_path.as_deref().and_then(load_previous_session);
+        let recovery_candidates = previous_session
+            .as_ref()
+            .map(find_recovery_candidates)
+            .unwrap_or_default();
+
+        let session = storage::SessionInfo {
+            started_at: storage::unix_now(),
+            clean_exit: false,
+            ..Default::default()
+        };
+        if let Some(path) = &session_path {
+            if let Err(e) = storage::save_session(path, &session) {
+                eprintln!("Failed to record session: {}", e);
+            }
+        }
+
+        // --------------------------------------------------------------------
+        // WRITING PROGRESS
+        // --------------------------------------------------------------------
+        // Today's count continues from whatever earlier sessions recorded
+        let progress_path = storage::get_progress_path().ok();
+        let today = chrono::Local::now().date_naive();
+        let history = progress_path
+            .as_deref()
+            .map(|path| {
+                storage::load_progress(path).unwrap_or_else(|e| {
+                    eprintln!("Failed to read progress history: {}", e);
+                    Vec::new()
+                })
+            })
+            .unwrap_or_default();
+        let progress_entries = storage::recent_progress(&history, today, PROGRESS_HISTORY_DAYS);
+        let earlier_today = progress_entries.first().map_or(0, |d| d.words);
+
+        // --------------------------------------------------------------------
+        // RECENT FILES
+        // --------------------------------------------------------------------
+        let recent_files_path = storage::get_recent_files_path().ok();
+        let recent_files = recent_files_path
+            .as_deref()
+            .map(|path| {
+                storage::load_recent_files(path).unwrap_or_else(|e| {
+                    eprintln!("Failed to read recent files: {}", e);
+                    Vec::new()
+                })
+            })
+            .unwrap_or_default();
+
+        // --------------------------------------------------------------------
+        // USER TEMPLATES
+        // --------------------------------------------------------------------
+        let templates_path = storage::get_templates_path().ok();
+        let user_templates = templates_path
+            .as_deref()
+            .map(|path| {
+                storage::load_templates(path).unwrap_or_else(|e| {
+                    eprintln!("Failed to read templates: {}", e);
+                    Vec::new()
+                })
+            })
+            .unwrap_or_default();
+
+        // Ctrl+= / Ctrl+- / Ctrl+0 zoom the editor text only (see
+        // handle_shortcuts), so egui's own whole-UI zoom is turned off
+        cc.egui_ctx.options_mut(|o| o.zoom_with_keyboard = false);
+
         // --------------------------------------------------------------------
         // RETURN THE APP INSTANCE
         // --------------------------------------------------------------------
         // `Self` is shorthand for `App` when inside an impl block
         // This creates and returns a new App instance
-        Self {
+        let mut app = Self {
             text_content,
-            current_file_path: None,               // No file open initially
-            status_message: String::from("Ready"), // Initial status
+            current_file_path: None, // No file open initially
+            disk_modified: None,
+            save_conflict: None,
+            read_only: false,
+            line_ending: LineEnding::Lf,
+            encoding: TextEncoding::Utf8,
+            password: None,
+            status_message: startup_status, // Initial status
+            detected_format: DocumentFormat::BookScript, // New documents are BookScript
+            tabs: vec![DocumentTab::empty()],
+            active_tab: 0,
+            format_override: None,
+            show_trash_window: false,
+            show_characters_window: false,
+            characters: CharacterTable::default(),
+            characters_generation: None,
+            show_statistics_window: false,
+            statistics: Report::default(),
+            statistics_breakdown: Breakdown::default(),
+            statistics_key: None,
+            readability_cache: readability::Cache::default(),
+            show_style_window: false,
+            style_check: StyleCheck::default(),
+            lookup: None,
+            context_word: None,
+            wordnet: None,
+            wordnet_loading: None,
+            read_aloud: None,
+            show_characters_panel: false,
+            new_character_name: String::new(),
+            show_scene_inspector: false,
+            show_corkboard: false,
+            show_comments_panel: false,
+            comments: Vec::new(),
+            comments_generation: None,
+            show_formatted_preview: false,
+            formatted_blocks: Vec::new(),
+            formatted_generation: None,
+            show_bookmarks_panel: false,
+            bookmarks_generation: None,
+            scene_draft: None,
+            scene_draft_editing: false,
+            show_snapshots_window: false,
+            history_source: HistorySource::Snapshots,
+            snapshot_entries: Vec::new(),
+            snapshot_name: None,
+            show_named_snapshots: false,
+            named_snapshots: Vec::new(),
+            named_snapshots_for: None,
+            comparison: None,
+            comparison_diff: None,
+            show_git_panel: false,
+            git_status: None,
+            git_status_for: None,
+            commit_message: None,
+            unlock_prompt: None,
+            password_prompt: None,
+            preview: None,
+            snapshot_preview: None,
+            trash_entries: Vec::new(),
+            writing_session: WritingSession::default(),
+            daily_tally: DailyTally::new(today, earlier_today),
+            progress_path,
+            recent_files,
+            recent_files_path,
+            user_templates,
+            templates_path,
+            template_name: None,
+            snippets: snippets::defaults(),
+            progress_recorded: (earlier_today, 0.0),
+            show_goal_window: false,
+            completion: None,
+            completion_anchor: egui::Pos2::ZERO,
+            completion_dismissed: None,
+            completion_names: None,
+            sprint: None,
+            show_sprint_window: false,
+            sprint_history: Vec::new(),
+            sprints_path: storage::get_sprints_path().ok(),
+            show_cleanup_window: false,
+            show_pdf_window: false,
+            pdf_title: String::new(),
+            show_print_window: false,
+            print_pages: Vec::new(),
+            print_pages_for: None,
+            show_docx_window: false,
+            docx_title: String::new(),
+            show_progress_window: false,
+            progress_entries,
+            applied_theme: Some(settings.theme),
+            loaded_font: None,
+            show_font_window: false,
+            settings_path: storage::get_settings_path().ok(),
+            settings,
+            pending_import: None,
+            parsed: ParsedDocument::new("", DocumentFormat::BookScript),
+            structure: DocumentStructure::default(),
+            history: History::new("", HistoryLimits::default()),
+            edit_generation: 0,
+            stats: DocStats::default(),
+            stats_key: None,
+            selection_stats: None,
+            caret_location: None,
+            structure_key: None,
+            show_outline: true,
+            project: None,
+            show_project_search: false,
+            project_search: ProjectSearch::default(),
+            pending_match: None,
+            show_project_window: false,
+            show_compile_window: false,
+            focus_mode: false,
+            fullscreen_before_focus: false,
+            last_status: String::new(),
+            status_visible_until: 0.0,
+            problems: Vec::new(),
+            problems_generation: None,
+            pending_validation: None,
+            go_to_query: None,
+            go_to_selected: 0,
+            pending_jump: None,
+            find: FindState::default(),
+            show_find_bar: false,
+            focus_find_field: false,
+            pending_selection: None,
+            dirty: false,
+            unsaved_prompt: None,
+            allow_close: false,
+            window_title: String::new(),
+            autosave_state,
+            autosave_events,
+            autosave_commands,
+            autosave_warning: None,
+            last_autosave_error: None,
+            autosave_sent: None,
+            autosave_sent_at: 0.0,
+            show_preferences: false,
+            session_path,
+            session,
+            recovery_candidates,
+            recovery_preview: None,
+            editor_cursor: 0,
+            editor_scroll: 0.0,
+            split_editor: None,
+            pending_view: None,
+            pending_restore: None,
+            file_io: FileWorker::spawn(Arc::clone(&storage)),
+            storage,
+            instance_requests: server.map(|server| server.serve(cc.egui_ctx.clone())),
+            pending_opens: Vec::new(),
+            after_save: None,
+        };
+
+        app.load_snippets();
+
+        // Colors are applied here rather than waiting for the first frame,
+        // so the window never flashes the wrong theme
+        let visuals = app.theme_visuals();
+        cc.egui_ctx.set_visuals(visuals);
+
+        // --------------------------------------------------------------------
+        // SESSION RESTORE
+        // --------------------------------------------------------------------
+        // Reopen last time's document, unless the command line named a
+        // file or asked for an empty one. Crash recovery (above) may still
+        // offer a newer autosave on the first frame.
+        //
+        // A file from the command line goes through the normal load path,
+        // so a bad path ends up in the status bar rather than a crash.
+        match startup {
+            StartupDocument::File(path) => app.load_file(path),
+            StartupDocument::Empty => {}
+            StartupDocument::Restore => {
+                if app.settings.restore_session {
+                    if let Some(previous) = previous_session {
+                        app.restore_session(previous);
+                    }
+                }
+            }
         }
+
+        app
     }
 
-    /// Load a file from disk into the editor
+    /// Reopen the documents (and project) from the previous session, where
+    /// they were left
     ///
-    /// `&mut self` means this method borrows the App mutably
-    /// (it can modify the App's fields)
-    fn load_file(&mut self, path: std::path::PathBuf) {
-        // storage::load_text_file returns Result<String, anyhow::Error>
-        // We use pattern matching to handle both success and error cases
-        match storage::load_text_file(&path) {
-            // If loading succeeded, we get Ok(content)
-            Ok(content) => {
-                // Lock the mutex to get mutable access to the String
-                // `.lock()` returns a MutexGuard<String>
-                // `.unwrap()` panics if the lock is poisoned (very rare)
-                // The `*` dereferences the guard to get the String itself
-                *self.text_content.lock().unwrap() = content;
+    /// The document in front goes through the file worker like any other
+    /// load; the ones behind it are read straight into their tabs, since
+    /// nothing shows them until they're picked. Files that have since been
+    /// moved or deleted are left out with a note in the status bar - never
+    /// an error dialog. So are password-protected ones behind the front
+    /// tab: only the one in front asks for its password.
+    fn restore_session(&mut self, previous: storage::SessionInfo) {
+        if let Some(path) = previous.project.filter(|path| path.exists()) {
+            self.open_project(path);
+        }
 
-                // Update our state to remember which file is open
-                self.current_file_path = Some(path.clone());
+        // The tab list is empty in sessions written before there were tabs
+        let mut tabs = Vec::new();
+        let mut active = None;
+        let mut missing = 0;
+        let mut locked = 0;
+        for (index, entry) in previous.tabs.iter().enumerate() {
+            if Some(index) == previous.active_tab {
+                if entry.path.exists() {
+                    // A blank slot, filled in when the load lands
+                    active = Some(tabs.len());
+                    tabs.push(DocumentTab::empty());
+                } else {
+                    missing += 1;
+                }
+            } else {
+                match self.restored_tab(entry) {
+                    Some(tab) => tabs.push(tab),
+                    None if storage::is_password_protected(self.storage.as_ref(), &entry.path) => {
+                        locked += 1
+                    }
+                    None => missing += 1,
+                }
+            }
+        }
+        if !tabs.is_empty() {
+            // With nothing to load, the first tab comes to the front
+            self.active_tab = active.unwrap_or(0);
+            let front = std::mem::replace(&mut tabs[self.active_tab], DocumentTab::empty());
+            self.tabs = tabs;
+            self.unpark_document(front);
+        }
+        if missing > 0 {
+            self.status_message = format!(
+                "{} {} from last time no longer {}",
+                format_count(missing),
+                if missing == 1 { "file" } else { "files" },
+                if missing == 1 { "exists" } else { "exist" }
+            );
+        }
+        if locked > 0 {
+            self.status_message = format!(
+                "{} password-protected {} from last time {} reopened (open {} again to unlock)",
+                format_count(locked),
+                if locked == 1 { "file" } else { "files" },
+                if locked == 1 { "wasn't" } else { "weren't" },
+                if locked == 1 { "it" } else { "them" }
+            );
+        }
 
-                // Update status message for the user
-                self.status_message = format!("Loaded: {}", path.display());
+        let Some(path) = previous.document else {
+            return;
+        };
+        if !path.exists() {
+            self.status_message = format!("Last file no longer exists: {}", path.display());
+            return;
+        }
+        if previous.tabs.is_empty() || active.is_some() {
+            self.load_file(path);
+            self.pending_restore = Some((previous.cursor, previous.scroll_offset));
+        }
+    }
+
+    /// Read a document from the previous session into a parked tab
+    ///
+    /// None if it can't be read any more.
+    fn restored_tab(&self, entry: &storage::SessionTab) -> Option<DocumentTab> {
+        let DecodedText {
+            text,
+            encoding,
+            line_ending,
+        } = storage::load_document_from(self.storage.as_ref(), &entry.path).ok()?;
+        Some(DocumentTab {
+            history: History::new(&text, HistoryLimits::default()),
+            detected_format: format::detect_format(Some(&entry.path), &text),
+            // A broken sidecar just means no override, as on a normal load
+            format_override: storage::load_sidecar(&entry.path)
+                .ok()
+                .and_then(|meta| meta.format_override),
+            disk_modified: self.storage.modified(&entry.path),
+            line_ending,
+            encoding,
+            // A password-protected file can't be read without its password
+            password: None,
+            path: Some(entry.path.clone()),
+            dirty: false,
+            cursor: entry.cursor,
+            scroll: entry.scroll,
+            preview: None,
+            text,
+        })
+    }
+
+    /// The open documents as the session file records them, with the
+    /// caret and scroll position each is at now
+    fn session_tabs(&self) -> (Vec<storage::SessionTab>, Option<usize>) {
+        let mut tabs = Vec::new();
+        let mut active = None;
+        for (index, tab) in self.tabs.iter().enumerate() {
+            let (path, cursor, scroll) = if index == self.active_tab {
+                (
+                    self.current_file_path.as_ref(),
+                    self.editor_cursor,
+                    self.editor_scroll,
+                )
+            } else {
+                (tab.path.as_ref(), tab.cursor, tab.scroll)
+            };
+            let Some(path) = path else {
+                continue;
+            };
+            if index == self.active_tab {
+                active = Some(tabs.len());
             }
-            // If loading failed, we get Err(e) where e is the error
-            Err(e) => {
-                // Show the error to the user in the status bar
-                self.status_message = format!("Error loading file: {}", e);
+            tabs.push(storage::SessionTab {
+                path: path.clone(),
+                cursor,
+                scroll,
+            });
+        }
+        (tabs, active)
+    }
+
+    /// Keep the session file's record of the open documents and project up
+    /// to date
+    ///
+    /// Only writes when they actually changed (Open, Save As, switching or
+    /// closing tabs), so this costs a comparison per frame, not a file
+    /// write. Caret positions are only written on exit.
+    fn sync_session(&mut self) {
+        let project = self.project.as_ref().map(|open| &open.path);
+        let (tabs, active) = self.session_tabs();
+        let same_tabs = tabs.len() == self.session.tabs.len()
+            && tabs
+                .iter()
+                .zip(&self.session.tabs)
+                .all(|(now, recorded)| now.path == recorded.path);
+        if self.session.document == self.current_file_path
+            && self.session.project.as_ref() == project
+            && self.session.active_tab == active
+            && same_tabs
+        {
+            return;
+        }
+        self.session.document = self.current_file_path.clone();
+        self.session.project = project.cloned();
+        self.session.tabs = tabs;
+        self.session.active_tab = active;
+        self.write_session();
+    }
+
+    /// Write `self.session` to disk, logging (not showing) any failure
+    fn write_session(&self) {
+        if let Some(path) = &self.session_path {
+            if let Err(e) = storage::save_session(path, &self.session) {
+                eprintln!("Failed to record session: {}", e);
             }
         }
     }
 
-    /// Save the current text to a file on disk
-    fn save_file(&mut self, path: std::path::PathBuf) {
-        // Lock the mutex and clone the string contents
-        // We clone because we need to keep the lock time short
-        // (holding locks too long can cause performance issues)
-        let content = self.text_content.lock().unwrap().clone();
-
-        // Attempt to save the file
-        match storage::save_text_file(&path, &content) {
-            Ok(_) => {
-                // Update our state
-                self.current_file_path = Some(path.clone());
-                self.status_message = format!("Saved: {}", path.display());
+    /// Load a crashed session's autosave into the editor
+    ///
+    /// The text is marked dirty: it was never saved to the real document.
+    /// If the autosave belonged to a file, that path is restored too, so a
+    /// plain Save puts the draft back where it came from.
+    fn restore_autosave(&mut self, candidate: storage::RecoveryCandidate) {
+        match storage::load_document_from(self.storage.as_ref(), &candidate.autosave_path) {
+            Ok(DecodedText { text: content, .. }) => {
+                let format_path = candidate
+                    .document_path
+                    .as_deref()
+                    .unwrap_or(&candidate.autosave_path);
+                self.detected_format = format::detect_format(Some(format_path), &content);
+                self.format_override = candidate
+                    .document_path
+                    .as_ref()
+                    .and_then(|p| storage::load_sidecar(p).ok())
+                    .and_then(|meta| meta.format_override);
+
+                self.bank_progress();
+                self.history.reset(&content);
+                self.text_content = content;
+                self.text_edited();
+                // Restoring is a deliberate choice over the document's
+                // current contents, so its current time is the one to keep
+                self.disk_modified = candidate
+                    .document_path
+                    .as_deref()
+                    .and_then(|path| self.storage.modified(path));
+                self.current_file_path = candidate.document_path;
+                self.line_ending = LineEnding::Lf;
+                self.encoding = TextEncoding::Utf8;
+                self.password = None;
+                self.dirty = true;
+                self.status_message = format!(
+                    "Restored autosaved draft from {}",
+                    candidate.autosave_path.display()
+                );
             }
             Err(e) => {
-                self.status_message = format!("Error saving file: {}", e);
+                self.status_message = format!("Error restoring autosave: {}", e);
             }
         }
     }
-}
-
-// ============================================================================
-// TRAIT IMPLEMENTATION - eframe::App
-// ============================================================================
 
-/// Implement the eframe::App trait for our App struct
-///
-/// TRAITS are Rust's way of defining shared behavior (like interfaces).
-/// eframe requires us to implement the `update` method, which it calls
-/// every frame to rebuild the UI.
-impl eframe::App for App {
-    /// Called by eframe each frame to build the UI
-    ///
-    /// Parameters:
-    /// - `&mut self`: Mutable reference to our app (we can modify state)
-    /// - `ctx`: The egui Context, which provides access to all UI widgets
-    /// - `_frame`: Frame info (we don't use it, hence the underscore)
+    /// Draw the "Restore autosaved draft?" prompt for the first candidate
     ///
-    /// IMMEDIATE MODE GUI:
-    /// Unlike traditional GUI frameworks that maintain a tree of widgets,
-    /// egui rebuilds the entire UI from scratch every frame. This might
-    /// sound inefficient, but it's actually very fast and makes code simpler.
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // ====================================================================
-        // TOP PANEL - MENU BAR
-        // ====================================================================
-        // TopBottomPanel creates a bar at the top of the window
-        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
-            // `ui` is a Ui object that lets us add widgets
-            // It's passed to us by the closure
+    /// - Restore: load it; any other candidates are dropped (the editor can
+    ///   only hold one document, and they stay on disk regardless)
+    /// - Discard: move it to the trash so it isn't offered again, then ask
+    ///   about the next one
+    fn recovery_window(&mut self, ctx: &egui::Context) {
+        let Some(candidate) = self.recovery_candidates.first() else {
+            return;
+        };
 
-            // Create a horizontal menu bar
-            egui::menu::bar(ui, |ui| {
-                // "File" menu
-                ui.menu_button("File", |ui| {
-                    // "Open" button
-                    if ui.button("Open (.bks/.scr)").clicked() {
-                        // In a real app, you'd use a file picker dialog here
-                        // For now, we'll load a test file if it exists
-                        let test_path = std::path::PathBuf::from("test.bks");
-                        self.load_file(test_path);
-                    }
+        let mut restore = false;
+        let mut discard = false;
+        let mut preview = false;
+        let remaining = self.recovery_candidates.len();
 
-                    // "Save As" button
-                    if ui.button("Save As...").clicked() {
-                        // In a real app, you'd use a file picker dialog
-                        // For now, we'll save to a default location
-                        let save_path = std::path::PathBuf::from("output.bks");
-                        self.save_file(save_path);
-                    }
+        let name = candidate
+            .document_path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from("an untitled document"));
+        let age = format_age(storage::unix_now().saturating_sub(candidate.modified));
 
-                    // Separator line in the menu
-                    ui.separator();
+        egui::Window::new("Recover unsaved work")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label("BookScript Writer didn't shut down properly last time.");
+                ui.label(format!(
+                    "An autosaved draft of {} was found ({}).",
+                    name, age
+                ));
+                if remaining > 1 {
+                    ui.weak(format!("Draft 1 of {}", remaining));
+                }
 
-                    // "Exit" button
-                    if ui.button("Exit").clicked() {
-                        // ctx.send_viewport_cmd tells eframe to close the window
-                        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                match &self.recovery_preview {
+                    Some(Ok(text)) => {
+                        egui::ScrollArea::vertical()
+                            .id_salt("recovery_preview")
+                            .max_height(300.0)
+                            .show(ui, |ui| {
+                                // A &str (not &mut String) makes the TextEdit read-only
+                                ui.add(
+                                    egui::TextEdit::multiline(&mut text.as_str())
+                                        .desired_width(480.0)
+                                        .font(egui::TextStyle::Monospace),
+                                );
+                            });
                     }
-                });
+                    Some(Err(e)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, e);
+                    }
+                    None => {}
+                }
 
-                // "Help" menu
-                ui.menu_button("Help", |ui| {
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Restore autosaved draft").clicked() {
+                        restore = true;
+                    }
+                    if ui
+                        .add_enabled(
+                            self.recovery_preview.is_none(),
+                            egui::Button::new("Preview"),
+                        )
+                        .clicked()
+                    {
+                        preview = true;
+                    }
+                    if ui.button("Discard").clicked() {
+                        discard = true;
+                    }
+                });
+            });
+
+        if preview {
+            let path = &self.recovery_candidates[0].autosave_path;
+            self.recovery_preview = Some(
+                storage::load_document_from(self.storage.as_ref(), path)
+                    .map(|decoded| decoded.text)
+                    .map_err(|e| format!("Error reading autosave: {}", e)),
+            );
+        }
+        if restore || discard {
+            // The next candidate (if any) starts without a preview
+            self.recovery_preview = None;
+        }
+        if restore {
+            let candidate = self.recovery_candidates.remove(0);
+            self.recovery_candidates.clear();
+            self.restore_autosave(candidate);
+        } else if discard {
+            let candidate = self.recovery_candidates.remove(0);
+            self.status_message = match storage::trash_file(&candidate.autosave_path) {
+                Ok(_) => String::from("Autosaved draft moved to Recently deleted"),
+                Err(e) => format!("Error discarding autosave: {}", e),
+            };
+        }
+    }
+
+    /// Exchange information with the autosave thread
+    ///
+    /// Called once per frame. Copying the
+    /// settings every frame is cheap, and means no code path that changes
+    /// them (Preferences, profile import) can forget to tell the thread.
+    ///
+    /// The text is a different matter: it is only copied when it (or the
+    /// file it belongs to) has changed, and at most once per
+    /// AUTOSAVE_COPY_INTERVAL while typing continues.
+    fn sync_autosave(&mut self, now: f64) {
+        {
+            let mut state = self.autosave_state.lock().unwrap();
+            state.settings = self.settings.autosave;
+            state.snapshots = self.settings.snapshots;
+        }
+
+        let current = (self.edit_generation, self.current_file_path.clone());
+        if self.autosave_sent.as_ref() != Some(&current)
+            && now - self.autosave_sent_at >= AUTOSAVE_COPY_INTERVAL
+        {
+            self.send_document_to_autosave(now);
+        }
+
+        // try_recv never blocks: it returns Err as soon as the queue is empty
+        while let Ok(event) = self.autosave_events.try_recv() {
+            match event {
+                storage::AutosaveEvent::Saved { at, path } => {
+                    self.status_message = format!(
+                        "Autosaved to {} at {}",
+                        path.file_name().unwrap_or_default().to_string_lossy(),
+                        at.format("%H:%M:%S")
+                    );
+                    self.last_autosave_error = None;
+                }
+                storage::AutosaveEvent::Skipped(message) => {
+                    self.status_message = message;
+                }
+                storage::AutosaveEvent::Failed(message) => {
+                    self.status_message = message.clone();
+                    if self.last_autosave_error.as_ref() != Some(&message) {
+                        self.autosave_warning = Some(message.clone());
+                        self.last_autosave_error = Some(message);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Ask the autosave thread to save right away (File → Autosave Now)
+    fn autosave_now(&mut self, now: f64) {
+        if self.password.is_some() {
+            self.status_message =
+                String::from("Password-protected documents aren't autosaved - save with Ctrl+S");
+            return;
+        }
+        // Make sure the thread saves what's on screen, not a copy from up
+        // to a second ago
+        self.send_document_to_autosave(now);
+
+        // The thread only stops when the App is dropped, so this can't fail
+        // while the menu is on screen; if it somehow does, say so
+        if self
+            .autosave_commands
+            .send(storage::AutosaveCommand::SaveNow)
+            .is_err()
+        {
+            self.status_message = String::from("Autosave is not running");
+        }
+    }
+
+    /// Send the autosave thread a copy of the text and its file path
+    ///
+    /// A password-protected document is never written out unencrypted: the
+    /// thread gets an empty text instead, which it neither autosaves nor
+    /// snapshots.
+    fn send_document_to_autosave(&mut self, now: f64) {
+        let text = match self.password {
+            Some(_) => String::new(),
+            None => self.text_content.clone(),
+        };
+        let command = storage::AutosaveCommand::Document {
+            text,
+            file: self.current_file_path.clone(),
+        };
+        // A send only fails once the thread is gone; autosave_now reports that
+        let _ = self.autosave_commands.send(command);
+        self.autosave_sent = Some((self.edit_generation, self.current_file_path.clone()));
+        self.autosave_sent_at = now;
+    }
+
+    /// Draw the autosave failure banner under the menu bar, if there is one
+    ///
+    /// It doesn't block editing: the document is still in memory, and the
+    /// user may want to keep writing while they free up disk space.
+    fn autosave_warning_banner(&mut self, ctx: &egui::Context) {
+        let Some(message) = &self.autosave_warning else {
+            return;
+        };
+        let mut dismissed = false;
+
+        egui::TopBottomPanel::top("autosave_warning").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", message));
+                ui.label("Your work is not being backed up. Save it somewhere safe.");
+                if ui.button("Dismiss").clicked() {
+                    dismissed = true;
+                }
+            });
+        });
+
+        if dismissed {
+            self.autosave_warning = None;
+        }
+    }
+
+    /// Draw the Edit → Preferences window
+    fn preferences_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_preferences;
+        let mut table_action = None;
+
+        egui::Window::new("Preferences")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.heading("Autosave");
+                let autosave = &mut self.settings.autosave;
+                ui.checkbox(&mut autosave.enabled, "Autosave the document periodically");
+
+                // add_enabled_ui greys out everything inside when autosave is off
+                ui.add_enabled_ui(autosave.enabled, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Every");
+                        // DragValue: a number you can drag or type into;
+                        // range() clamps whatever the user enters
+                        ui.add(
+                            egui::DragValue::new(&mut autosave.interval_secs)
+                                .range(
+                                    storage::MIN_AUTOSAVE_INTERVAL_SECS
+                                        ..=storage::MAX_AUTOSAVE_INTERVAL_SECS,
+                                )
+                                .suffix(" s"),
+                        );
+                    });
+                });
+                ui.label("Open files autosave to \"<name>.autosave\" next to the file.");
+
+                ui.separator();
+                ui.heading("Backups");
+                let backups = &mut self.settings.backups;
+                ui.checkbox(
+                    &mut backups.enabled,
+                    "Keep a copy of the previous version when saving",
+                );
+                ui.add_enabled_ui(backups.enabled, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.radio_value(
+                            &mut backups.retention,
+                            BackupRetention::Count,
+                            "Keep the last",
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut backups.count)
+                                .range(1..=storage::MAX_BACKUP_COUNT),
+                        );
+                        ui.label("backups");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.radio_value(
+                            &mut backups.retention,
+                            BackupRetention::Days,
+                            "Keep backups for",
+                        );
+                        ui.add(
+                            egui::DragValue::new(&mut backups.days)
+                                .range(1..=storage::MAX_BACKUP_DAYS)
+                                .suffix(" days"),
+                        );
+                    });
+                });
+                ui.label(
+                    "Backups go to a \"backups\" folder next to the file; \
+                     File → Restore from Backup… brings one back.",
+                );
+
+                ui.separator();
+                ui.heading("Version history");
+                let snapshots = &mut self.settings.snapshots;
+                ui.checkbox(&mut snapshots.on_save, "Take a snapshot every time I save");
+                ui.horizontal(|ui| {
+                    ui.label("Also every");
+                    ui.add(
+                        egui::DragValue::new(&mut snapshots.interval_mins)
+                            .range(0..=storage::MAX_SNAPSHOT_INTERVAL_MINS)
+                            .suffix(" min"),
+                    );
+                    ui.label("while editing (0 = off)");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Keep the newest");
+                    ui.add(
+                        egui::DragValue::new(&mut snapshots.keep)
+                            .range(1..=storage::MAX_SNAPSHOTS_KEPT),
+                    );
+                    ui.label("snapshots per document");
+                });
+
+                ui.separator();
+                ui.heading("Word count");
+                let word_count = &mut self.settings.word_count;
+                ui.horizontal(|ui| {
+                    ui.label("Words per page");
+                    ui.add(egui::DragValue::new(&mut word_count.words_per_page).range(50..=1000));
+                });
+                ui.checkbox(
+                    &mut word_count.exclude_tags,
+                    "Leave tag lines like [CHAPTER: 1] out of the counts",
+                );
+
+                ui.separator();
+                ui.heading("Export");
+                ui.checkbox(
+                    &mut self.settings.export_comments,
+                    "Keep [[comments]] in exported and compiled files",
+                );
+
+                ui.separator();
+                ui.heading("Appearance");
+                ui.horizontal(|ui| {
+                    ui.label("Theme");
+                    for theme in Theme::ALL {
+                        ui.radio_value(&mut self.settings.theme, theme, theme.label());
+                    }
+                });
+                let editor = &mut self.settings.editor;
+                ui.horizontal(|ui| {
+                    ui.label("Editor font size");
+                    ui.add(
+                        egui::DragValue::new(&mut editor.font_size)
+                            .range(settings::MIN_FONT_SIZE..=settings::MAX_FONT_SIZE)
+                            .suffix(" pt"),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Editor font");
+                    ui.radio_value(&mut editor.font, EditorFont::Monospace, "Monospace");
+                    ui.radio_value(&mut editor.font, EditorFont::Proportional, "Proportional");
+                    if editor.custom_font.is_some() {
+                        ui.radio_value(&mut editor.font, EditorFont::Custom, "Custom");
+                    }
+                    if ui.button("More…").clicked() {
+                        self.show_font_window = true;
+                    }
+                });
+                let editor = &mut self.settings.editor;
+                ui.checkbox(&mut editor.word_wrap, "Wrap long lines");
+
+                ui.separator();
+                ui.heading("Focus mode");
+                let focus = &mut self.settings.focus;
+                ui.horizontal(|ui| {
+                    ui.label("Text column width");
+                    ui.add(
+                        egui::DragValue::new(&mut focus.column_width)
+                            .range(
+                                settings::MIN_FOCUS_COLUMN_WIDTH..=settings::MAX_FOCUS_COLUMN_WIDTH,
+                            )
+                            .suffix(" px"),
+                    );
+                });
+                ui.checkbox(
+                    &mut focus.typewriter_scrolling,
+                    "Typewriter scrolling: keep the current line in the middle",
+                );
+                ui.checkbox(&mut focus.full_screen, "Full screen");
+                ui.checkbox(
+                    &mut focus.dim_paragraphs,
+                    "Dim everything but the paragraph being written",
+                );
+
+                ui.separator();
+                ui.heading("Autocorrect");
+                table_action = autocorrect::show(ui, &mut self.settings.autocorrect);
+
+                ui.separator();
+                ui.heading("Startup");
+                ui.checkbox(
+                    &mut self.settings.restore_session,
+                    "Reopen the last documents and project where I left off",
+                );
+            });
+
+        match table_action {
+            Some(TableAction::Import) => self.import_autocorrect(),
+            Some(TableAction::Export) => self.export_autocorrect(),
+            None => {}
+        }
+
+        // Closing the window is when changes get written to disk
+        if self.show_preferences && !open {
+            self.save_settings();
+        }
+        self.show_preferences = open;
+    }
+
+    /// Merge a replacements file (autocorrect.rs) into the autocorrect table
+    fn import_autocorrect(&mut self) {
+        let Some(path) = dialogs::pick_file("Import Replacements", "Text", &["txt", "tsv"], None)
+        else {
+            return;
+        };
+        let imported =
+            storage::load_text_file(&path).and_then(|text| autocorrect::parse_table(&text));
+        self.status_message = match imported {
+            Ok(imported) => {
+                let changed =
+                    autocorrect::merge(&mut self.settings.autocorrect.replacements, imported);
+                self.save_settings();
+                format!("Imported: {} replacement(s) added or changed", changed)
+            }
+            Err(e) => format!("Error importing {}: {}", path.display(), e),
+        };
+    }
+
+    /// Write the autocorrect table to a replacements file
+    fn export_autocorrect(&mut self) {
+        let Some(path) = dialogs::save_file(
+            "Export Replacements",
+            "Text",
+            &["txt", "tsv"],
+            "replacements.txt",
+            None,
+        ) else {
+            return;
+        };
+        let table = autocorrect::to_table(&self.settings.autocorrect.replacements);
+        self.status_message =
+            match storage::save_text_file(&path, &table, storage::BackupSettings::OFF) {
+                Ok(()) => format!("Exported: {}", path.display()),
+                Err(e) => format!("Error exporting: {}", e),
+            };
+    }
+
+    /// Write the preferences to the settings file
+    fn save_settings(&mut self) {
+        let Some(path) = &self.settings_path else {
+            return;
+        };
+        if let Err(e) = storage::save_settings(path, &self.settings) {
+            self.status_message = format!("Error saving settings: {}", e);
+        }
+    }
+
+    /// Whether the editor can draw with the custom font: it loaded, and
+    /// egui has taken it on (set_fonts only takes effect on the next frame)
+    fn custom_font_ready(&self, ctx: &egui::Context) -> bool {
+        let editor = &self.settings.editor;
+        editor.font == EditorFont::Custom
+            && matches!(
+                (&self.loaded_font, &editor.custom_font),
+                (Some((loaded, true)), Some(wanted)) if loaded == wanted
+            )
+            && ctx.fonts(|f| f.families().contains(&fonts::custom_family()))
+    }
+
+    /// Draw the View → Editor Font window: family, size and spacing, with a
+    /// preview that changes as they do (and so does the editor)
+    fn font_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_font_window;
+        let custom_ready = self.custom_font_ready(ctx);
+        let mut choose_file = false;
+
+        egui::Window::new("Editor Font")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let editor = &mut self.settings.editor;
+                egui::Grid::new("editor_font")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Font:");
+                        ui.vertical(|ui| {
+                            ui.radio_value(&mut editor.font, EditorFont::Monospace, "Monospace");
+                            ui.radio_value(
+                                &mut editor.font,
+                                EditorFont::Proportional,
+                                "Proportional",
+                            );
+                            ui.horizontal(|ui| {
+                                ui.add_enabled_ui(editor.custom_font.is_some(), |ui| {
+                                    ui.radio_value(&mut editor.font, EditorFont::Custom, "");
+                                });
+                                let name = editor
+                                    .custom_font
+                                    .as_deref()
+                                    .and_then(|p| p.file_name())
+                                    .map(|n| n.to_string_lossy().into_owned())
+                                    .unwrap_or_else(|| "No font file".to_string());
+                                ui.label(name);
+                                if ui.button("Choose…").clicked() {
+                                    choose_file = true;
+                                }
+                            });
+                        });
+                        ui.end_row();
+
+                        ui.label("Size:");
+                        ui.add(
+                            egui::Slider::new(
+                                &mut editor.font_size,
+                                settings::MIN_FONT_SIZE..=settings::MAX_FONT_SIZE,
+                            )
+                            .step_by(1.0)
+                            .suffix(" pt"),
+                        );
+                        ui.end_row();
+
+                        ui.label("Line spacing:");
+                        ui.add(
+                            egui::Slider::new(
+                                &mut editor.line_spacing,
+                                settings::MIN_LINE_SPACING..=settings::MAX_LINE_SPACING,
+                            )
+                            .step_by(0.05)
+                            .suffix("×"),
+                        );
+                        ui.end_row();
+                    });
+                ui.checkbox(&mut editor.word_wrap, "Wrap long lines");
+
+                ui.separator();
+                ui.label("Preview:");
+                let font = editor.font_id(custom_ready);
+                let mut format = egui::TextFormat::simple(font.clone(), ui.visuals().text_color());
+                format.line_height = editor.line_height(ui.fonts(|f| f.row_height(&font)));
+                let mut job =
+                    egui::text::LayoutJob::single_section(FONT_PREVIEW.to_string(), format);
+                job.wrap.max_width = 380.0;
+                egui::Frame::canvas(ui.style()).show(ui, |ui| {
+                    ui.set_width(380.0);
+                    ui.label(job);
+                });
+            });
+
+        if choose_file {
+            let current = self.settings.editor.custom_font.clone();
+            if let Some(path) =
+                dialogs::pick_file("Choose Font", "Fonts", &["ttf", "otf"], current.as_deref())
+            {
+                self.settings.editor.custom_font = Some(path);
+                self.settings.editor.font = EditorFont::Custom;
+            }
+        }
+        // Closing the window is when changes get written to disk
+        if self.show_font_window && !open {
+            self.save_settings();
+        }
+        self.show_font_window = open;
+    }
+
+    /// Read the snippets file, keeping the snippets already loaded if it
+    /// can't be (reported in the status bar)
+    fn load_snippets(&mut self) -> bool {
+        match storage::get_snippets_path().and_then(|path| snippets::load(&path)) {
+            Ok(loaded) => {
+                self.snippets = loaded;
+                true
+            }
+            Err(e) => {
+                self.status_message = format!("Error loading snippets: {:#}", e);
+                false
+            }
+        }
+    }
+
+    /// Put a snippet in place of the main editor's selection (or at its
+    /// caret), as its own undo step
+    fn insert_snippet(&mut self, ctx: &egui::Context, index: usize) {
+        if !self.check_editable() {
+            return;
+        }
+        let Some(snippet) = self.snippets.get(index) else {
+            return;
+        };
+        let editor_id = egui::Id::new(EDITOR_ID);
+        let mut state = egui::TextEdit::load_state(ctx, editor_id).unwrap_or_default();
+        let end = self.text_content.chars().count();
+        let range = match state.cursor.char_range() {
+            Some(range) => {
+                let [start, stop] = range.sorted();
+                start.index.min(end)..stop.index.min(end)
+            }
+            None => {
+                let caret = self.editor_cursor.min(end);
+                caret..caret
+            }
+        };
+        let command = snippets::insert(snippet, range);
+
+        self.history.checkpoint(&self.text_content);
+        command.apply(&mut self.text_content);
+        self.history.checkpoint(&self.text_content);
+        self.dirty = true;
+        self.text_edited();
+
+        state
+            .cursor
+            .set_char_range(Some(egui::text::CCursorRange::one(
+                egui::text::CCursor::new(command.caret),
+            )));
+        state.store(ctx, editor_id);
+        ctx.memory_mut(|m| m.request_focus(editor_id));
+    }
+
+    /// The colors for the chosen theme
+    ///
+    /// A custom theme file that can't be used is reported in the status bar,
+    /// and the dark theme shown instead.
+    fn theme_visuals(&mut self) -> egui::Visuals {
+        if self.settings.theme != Theme::Custom {
+            return self.settings.theme.visuals();
+        }
+        match storage::get_theme_path().and_then(|path| theme::load_custom(&path)) {
+            Ok(visuals) => visuals,
+            Err(e) => {
+                self.status_message = format!("Error loading theme: {:#}", e);
+                Theme::Dark.visuals()
+            }
+        }
+    }
+
+    /// Bring the egui context in line with the current settings, and note
+    /// the window's size and position for the next launch
+    fn sync_appearance(&mut self, ctx: &egui::Context) {
+        if self.applied_theme != Some(self.settings.theme) {
+            let visuals = self.theme_visuals();
+            ctx.set_visuals(visuals);
+            self.applied_theme = Some(self.settings.theme);
+        }
+
+        // A custom font is loaded when it's chosen (or at startup), once
+        // per file
+        let editor = &self.settings.editor;
+        if let (EditorFont::Custom, Some(path)) = (editor.font, &editor.custom_font) {
+            if self.loaded_font.as_ref().map(|(loaded, _)| loaded) != Some(path) {
+                let path = path.clone();
+                let usable = match fonts::definitions_with(&path) {
+                    Ok(definitions) => {
+                        ctx.set_fonts(definitions);
+                        true
+                    }
+                    Err(e) => {
+                        self.status_message = format!("Error loading font: {}", e);
+                        false
+                    }
+                };
+                self.loaded_font = Some((path, usable));
+            }
+        }
+
+        // Minimized, maximized or full screen geometry isn't worth restoring
+        let (inner, outer, minimized, maximized) = ctx.input(|i| {
+            let viewport = i.viewport();
+            (
+                viewport.inner_rect,
+                viewport.outer_rect,
+                viewport.minimized.unwrap_or(false),
+                viewport.maximized.unwrap_or(false) || viewport.fullscreen.unwrap_or(false),
+            )
+        });
+        if !minimized && !maximized {
+            if let Some(inner) = inner {
+                self.settings.window.size = Some([inner.width(), inner.height()]);
+            }
+            if let Some(outer) = outer {
+                self.settings.window.position = Some([outer.min.x, outer.min.y]);
+            }
+        }
+    }
+
+    /// Enter or leave focus mode, taking the window in and out of full
+    /// screen if the settings ask for it
+    fn set_focus_mode(&mut self, ctx: &egui::Context, on: bool) {
+        if on == self.focus_mode {
+            return;
+        }
+        self.focus_mode = on;
+        if !self.settings.focus.full_screen {
+            return;
+        }
+        if on {
+            self.fullscreen_before_focus = ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(true));
+        } else {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(
+                self.fullscreen_before_focus,
+            ));
+        }
+    }
+
+    /// Note that text_content has changed, so everything worked out from
+    /// it is worked out again
+    ///
+    /// Every change to the text has to come through here: loading,
+    /// switching tabs, undo, replacing, restoring. The editor widgets bump
+    /// edit_generation directly, as they hold the text borrowed.
+    fn text_edited(&mut self) {
+        self.edit_generation += 1;
+    }
+
+    /// The current text, parsed in the document's mode
+    ///
+    /// Only the lines that changed since the last call are parsed again
+    /// (all of them after a change of mode).
+    fn parsed(&mut self) -> &ParsedDocument {
+        self.parsed.set_format(self.active_format());
+        self.parsed.update(&self.text_content);
+        &self.parsed
+    }
+
+    /// The current text's parsed lines
+    fn parsed_lines(&mut self) -> &[ParsedLine] {
+        self.parsed().lines()
+    }
+
+    /// Refresh the document structure and its word counts if the text
+    /// (or the exclude-tags option) changed since last time
+    fn refresh_structure(&mut self) {
+        // Comparing generations is free; even an incremental parse has to
+        // find what changed
+        let exclude_tags = self.settings.word_count.exclude_tags;
+        let key = (self.edit_generation, exclude_tags, self.active_format());
+        if self.structure_key != Some(key) {
+            self.structure = self.parsed().structure().clone();
+            parser::annotate_structure(&self.text_content, &mut self.structure, exclude_tags);
+            self.structure_key = Some(key);
+        }
+    }
+
+    /// Where the caret is: line and column, then the act, chapter and scene
+    /// it's in (see parser::location_at)
+    fn caret_location(&mut self) -> &str {
+        let key = (self.edit_generation, self.editor_cursor);
+        if self.caret_location.as_ref().map(|(k, _)| *k) != Some(key) {
+            let text = &self.text_content;
+            let line = editing::line_at_char(text, self.editor_cursor);
+            let column = self.editor_cursor - editing::line_start_char(text, line) + 1;
+            let mut location = format!("Line {}, Col {}", line, column);
+
+            // The incrementally parsed structure: refresh_structure's word
+            // counts would cost a pass over the whole text per keystroke
+            let path = parser::location_at(self.parsed().structure(), line);
+            if !path.is_empty() {
+                location.push_str(" — ");
+                location.push_str(&path.join(" › "));
+            }
+            self.caret_location = Some((key, location));
+        }
+        self.caret_location
+            .as_ref()
+            .map_or("", |(_, location)| location)
+    }
+
+    /// Re-validate the text once it has stopped changing for a moment
+    ///
+    /// Every edit bumps edit_generation and so restarts the wait; the list only
+    /// updates after a pause in typing.
+    fn refresh_problems(&mut self, now: f64) {
+        if self.problems_generation == Some(self.edit_generation) {
+            self.pending_validation = None;
+            return;
+        }
+
+        match self.pending_validation {
+            Some((generation, since)) if generation == self.edit_generation => {
+                if now - since >= VALIDATION_DELAY {
+                    self.problems = parser::validate(self.parsed_lines());
+                    self.problems_generation = Some(self.edit_generation);
+                    self.pending_validation = None;
+                }
+            }
+            _ => self.pending_validation = Some((self.edit_generation, now)),
+        }
+    }
+
+    /// Recount the whole document's words and characters if the text (or
+    /// the exclude-tags option) changed since the last count
+    fn refresh_document_stats(&mut self) -> DocStats {
+        let exclude_tags = self.settings.word_count.exclude_tags;
+        let key = (self.edit_generation, exclude_tags);
+        if self.stats_key != Some(key) {
+            self.stats = parser::count_stats(&self.text_content, exclude_tags);
+            self.stats_key = Some(key);
+        }
+        self.stats
+    }
+
+    /// Update the session and daily word counts, and write today's total
+    /// to the progress history every PROGRESS_SAVE_INTERVAL seconds
+    fn track_progress(&mut self, now: f64) {
+        // Switching exclude-tags changes the count without any writing, so
+        // it's treated like opening a different document
+        let counted_with = self.stats_key.map(|(_, exclude_tags)| exclude_tags);
+        let words = self.refresh_document_stats().words;
+        if counted_with.is_some_and(|c| c != self.settings.word_count.exclude_tags) {
+            self.writing_session.document_replaced();
+        }
+        self.writing_session.observe(words);
+        let delta = self.writing_session.delta();
+
+        // A session running past midnight closes off yesterday
+        let today = chrono::Local::now().date_naive();
+        if let Some((date, words)) = self.daily_tally.roll_over(today, delta) {
+            self.record_progress(date, words);
+            self.progress_recorded = (0, now);
+        }
+
+        let total = self.daily_tally.total(delta);
+        if total != self.progress_recorded.0
+            && now - self.progress_recorded.1 >= PROGRESS_SAVE_INTERVAL
+        {
+            self.record_progress(today, total);
+            self.progress_recorded = (total, now);
+        }
+    }
+
+    /// Keep what was written in the open document before another replaces
+    /// it, so the session count carries on instead of starting over
+    fn bank_progress(&mut self) {
+        let exclude_tags = self.settings.word_count.exclude_tags;
+        let words = parser::count_stats(&self.text_content, exclude_tags).words;
+        self.writing_session.observe(words);
+        self.writing_session.document_replaced();
+    }
+
+    /// Write one day's total to the progress history file
+    fn record_progress(&mut self, date: chrono::NaiveDate, words: i64) {
+        let Some(path) = &self.progress_path else {
+            return;
+        };
+        if let Err(e) = storage::record_progress(path, storage::DailyProgress { date, words }) {
+            self.status_message = format!("Error saving writing progress: {}", e);
+            return;
+        }
+        // A new day isn't listed yet: read the list again to make room
+        match self.progress_entries.iter_mut().find(|d| d.date == date) {
+            Some(entry) => entry.words = words,
+            None => self.refresh_progress_history(),
+        }
+    }
+
+    /// Up/Down, Enter and Esc for the character name list, taken out of
+    /// the input before the editor (or a shortcut) can act on them
+    fn handle_completion_keys(&mut self, ctx: &egui::Context) {
+        let editor_id = egui::Id::new(EDITOR_ID);
+        let Some(completion) = &mut self.completion else {
+            return;
+        };
+        if !ctx.memory(|m| m.has_focus(editor_id)) {
+            return;
+        }
+        let mut accept = None;
+        let mut dismiss = false;
+        ctx.input_mut(|i| {
+            i.events.retain(|event| {
+                let egui::Event::Key {
+                    key,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } = event
+                else {
+                    return true;
+                };
+                if !modifiers.is_none() {
+                    return true;
+                }
+                match key {
+                    egui::Key::ArrowDown => completion.move_selection(1),
+                    egui::Key::ArrowUp => completion.move_selection(-1),
+                    egui::Key::Enter => accept = Some(completion.selected),
+                    egui::Key::Escape => dismiss = true,
+                    _ => return true,
+                }
+                false
+            });
+        });
+        if dismiss {
+            self.completion_dismissed = Some(completion.start);
+            self.completion = None;
+        } else if let Some(index) = accept {
+            self.accept_completion(ctx, index);
+        }
+    }
+
+    /// Put the chosen name in place of what was typed
+    fn accept_completion(&mut self, ctx: &egui::Context, index: usize) {
+        let Some(completion) = self.completion.take() else {
+            return;
+        };
+        if !self.check_editable() {
+            return;
+        }
+        let command = completion.accept(index);
+        // Its own undo step, like a paste
+        self.history.checkpoint(&self.text_content);
+        command.apply(&mut self.text_content);
+        self.history.checkpoint(&self.text_content);
+        self.dirty = true;
+        self.text_edited();
+
+        let editor_id = egui::Id::new(EDITOR_ID);
+        let mut state = egui::TextEdit::load_state(ctx, editor_id).unwrap_or_default();
+        state
+            .cursor
+            .set_char_range(Some(egui::text::CCursorRange::one(
+                egui::text::CCursor::new(command.caret),
+            )));
+        state.store(ctx, editor_id);
+        // Clicking a name took the focus away from the editor
+        ctx.memory_mut(|m| m.request_focus(editor_id));
+    }
+
+    /// Open, update or close the character name list for the caret at
+    /// `caret` (None when the editor isn't being typed in), drawn at
+    /// `anchor`
+    fn update_completion(&mut self, ctx: &egui::Context, caret: Option<(usize, egui::Pos2)>) {
+        if caret.is_none() && self.completion.is_some() && completion::is_hovered(ctx) {
+            return;
+        }
+        let context = caret
+            .filter(|_| self.settings.structure_editing.complete_names && !self.locked())
+            .and_then(|(caret, anchor)| {
+                completion::context_at(&self.text_content, caret).map(|c| (c, caret, anchor))
+            });
+        let Some((context, caret, anchor)) = context else {
+            self.completion = None;
+            self.completion_dismissed = None;
+            return;
+        };
+        if self.completion_dismissed == Some(context.1) {
+            return;
+        }
+        self.completion_dismissed = None;
+
+        if self
+            .completion_names
+            .as_ref()
+            .map(|(generation, _)| *generation)
+            != Some(self.edit_generation)
+        {
+            let names = completion::known_names(self.parsed_lines());
+            self.completion_names = Some((self.edit_generation, names));
+        }
+        let names = self.completion_names.as_ref().map_or(&[][..], |(_, n)| n);
+        let mut found = completion::complete(context, caret, names);
+        // Keep the choice made with the arrow keys while the list stays
+        // the same
+        if let (Some(found), Some(old)) = (&mut found, &self.completion) {
+            if found.start == old.start && found.items == old.items {
+                found.selected = old.selected;
+            }
+        }
+        self.completion = found;
+        self.completion_anchor = anchor;
+    }
+
+    /// Start a sprint of `minutes` now
+    fn start_sprint(&mut self, minutes: u32, now: f64) {
+        let started_at = chrono::Local::now().naive_local();
+        self.sprint = Some(Sprint::start(
+            minutes,
+            now,
+            started_at,
+            self.writing_session.delta(),
+        ));
+        self.status_message = format!("Sprint started: {} min", minutes);
+    }
+
+    /// End the running sprint after `elapsed` seconds (time up, stopped,
+    /// or the app closing), record it and show the result
+    fn finish_sprint(&mut self, elapsed: f64) {
+        let Some(sprint) = self.sprint.take() else {
+            return;
+        };
+        let record = storage::SprintRecord {
+            started: sprint.started_at,
+            minutes: sprint.minutes,
+            seconds: elapsed.round() as u64,
+            words: sprint.words(self.writing_session.delta()),
+        };
+        self.status_message = format!(
+            "Sprint over: {} words in {}",
+            format_delta(record.words),
+            goals::format_clock(record.seconds as f64)
+        );
+        if let Some(path) = &self.sprints_path {
+            if let Err(e) = storage::record_sprint(path, record) {
+                self.status_message = format!("Error saving sprint: {}", e);
+            }
+        }
+        self.refresh_sprint_history();
+        self.show_sprint_window = true;
+    }
+
+    /// Called every frame: end the sprint when its time is up, and keep
+    /// the countdown ticking while it runs
+    fn track_sprint(&mut self, ctx: &egui::Context, now: f64) {
+        let Some(sprint) = self.sprint else {
+            return;
+        };
+        if sprint.is_over(now) {
+            self.finish_sprint(sprint.elapsed(now));
+        } else {
+            // egui only repaints on input; wake up for the next second
+            let to_next_second = sprint.remaining(now).fract().max(0.05);
+            ctx.request_repaint_after(std::time::Duration::from_secs_f64(to_next_second));
+        }
+    }
+
+    /// Re-read the sprint history for the Sprint window
+    fn refresh_sprint_history(&mut self) {
+        self.sprint_history = match self.sprints_path.as_deref().map(storage::load_sprints) {
+            Some(Ok(mut history)) => {
+                history.reverse();
+                history
+            }
+            Some(Err(e)) => {
+                self.status_message = format!("Error reading sprint history: {}", e);
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+    }
+
+    /// The manuscript's length and target: the open project's, or the open
+    /// document's against the Writing Goal window's target
+    ///
+    /// None when neither has a target.
+    fn manuscript_progress(&self) -> Option<(usize, usize)> {
+        if let Some(open) = &self.project {
+            let target = open.project.settings.target_words;
+            if target > 0 {
+                return Some((open.total_words(), target));
+            }
+        }
+        match self.settings.goal.manuscript_words {
+            0 => None,
+            target => Some((self.stats.words, target)),
+        }
+    }
+
+    /// Draw the status bar's sparkline: words written on each of the last
+    /// SPARKLINE_DAYS days as tiny bars, today's on the right
+    ///
+    /// Clicking it opens the Progress History window.
+    fn progress_sparkline(&mut self, ui: &mut egui::Ui) {
+        let today = self.daily_tally.date();
+        let live_today = self.daily_tally.total(self.writing_session.delta());
+        let days: Vec<i64> = self
+            .progress_entries
+            .iter()
+            .take(SPARKLINE_DAYS)
+            .map(|d| if d.date == today { live_today } else { d.words })
+            .rev()
+            .collect();
+        if days.is_empty() {
+            return;
+        }
+        let best = days.iter().copied().max().unwrap_or(0).max(1);
+
+        let bar_width = 4.0;
+        let size = egui::vec2(SPARKLINE_DAYS as f32 * bar_width, 14.0);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+        let color = ui.visuals().widgets.inactive.fg_stroke.color;
+        let painter = ui.painter_at(rect);
+        // Right-aligned, so today is always at the same spot
+        let left = rect.right() - days.len() as f32 * bar_width;
+        for (i, &words) in days.iter().enumerate() {
+            // Days with no writing still get a sliver, so the line reads
+            // as days rather than gaps
+            let height = (words.max(0) as f32 / best as f32 * rect.height()).max(1.0);
+            let x = left + i as f32 * bar_width;
+            let bar = egui::Rect::from_min_max(
+                egui::pos2(x, rect.bottom() - height),
+                egui::pos2(x + bar_width - 1.0, rect.bottom()),
+            );
+            painter.rect_filled(bar, 0.0, color);
+        }
+
+        let response = response.on_hover_text(format!(
+            "Words written per day, last {} days\nToday: {}",
+            days.len(),
+            format_delta(live_today)
+        ));
+        if response.clicked() {
+            self.refresh_progress_history();
+            self.show_progress_window = true;
+        }
+    }
+
+    /// Words counted towards the goal: this session's or today's
+    fn goal_progress(&self) -> i64 {
+        let delta = self.writing_session.delta();
+        match self.settings.goal.period {
+            GoalPeriod::Session => delta,
+            GoalPeriod::Daily => self.daily_tally.total(delta),
+        }
+    }
+
+    /// Recount words and characters if the text (or the options) changed,
+    /// and count the editor's selection if there is one
+    ///
+    /// Returns the whole document's counts and the selection's (None when
+    /// nothing is selected).
+    fn refresh_statistics(&mut self, ctx: &egui::Context) -> (DocStats, Option<DocStats>) {
+        let exclude_tags = self.settings.word_count.exclude_tags;
+        self.refresh_document_stats();
+        let text = &self.text_content;
+
+        // The selection lives in the TextEdit's saved state (char indices)
+        let selection = egui::TextEdit::load_state(ctx, egui::Id::new(EDITOR_ID))
+            .and_then(|state| state.cursor.char_range())
+            .filter(|range| range.primary != range.secondary)
+            .map(|range| {
+                let (a, b) = (range.primary.index, range.secondary.index);
+                (a.min(b), a.max(b))
+            });
+
+        let Some((start, end)) = selection else {
+            self.selection_stats = None;
+            return (self.stats, None);
+        };
+
+        let key = (self.edit_generation, start, end, exclude_tags);
+        match &self.selection_stats {
+            Some((cached_key, stats)) if *cached_key == key => (self.stats, Some(*stats)),
+            _ => {
+                let selected =
+                    &text[editing::char_to_byte(text, start)..editing::char_to_byte(text, end)];
+                let stats = parser::count_stats(selected, exclude_tags);
+                self.selection_stats = Some((key, stats));
+                (self.stats, Some(stats))
+            }
+        }
+    }
+
+    /// The format the rest of the app should use for the current document
+    ///
+    /// A user override always wins over detection.
+    fn active_format(&self) -> DocumentFormat {
+        self.format_override.unwrap_or(self.detected_format)
+    }
+
+    /// Change (or clear) the user's format override for this document
+    ///
+    /// If the document has been saved somewhere, the choice is written to
+    /// its sidecar so it survives closing and reopening the file.
+    fn set_format_override(&mut self, format_override: Option<DocumentFormat>) {
+        self.format_override = format_override;
+
+        if let Some(path) = &self.current_file_path {
+            let meta = storage::DocumentMeta { format_override };
+            if let Err(e) = storage::save_sidecar(path, &meta) {
+                self.status_message = format!("Error saving document settings: {}", e);
+                return;
+            }
+        }
+
+        // The caret's act/chapter/scene is cached by text and caret alone
+        self.caret_location = None;
+        self.status_message = format!("Mode: {}", self.active_format().label());
+    }
+
+    /// Draw the status-bar dropdown that shows and overrides the document mode
+    ///
+    /// The first entry is "Auto", which clears the override and goes back to
+    /// the detected format. The other entries pin a specific format.
+    fn format_selector(&mut self, ui: &mut egui::Ui) {
+        // Work on a copy so the closure below doesn't need to borrow self
+        let mut choice = self.format_override;
+
+        let selected_text = match choice {
+            Some(fmt) => fmt.label().to_string(),
+            None => format!("Auto ({})", self.detected_format.label()),
+        };
+
+        egui::ComboBox::from_id_salt("format_mode")
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(
+                    &mut choice,
+                    None,
+                    format!("Auto (detected: {})", self.detected_format.label()),
+                );
+                for fmt in DocumentFormat::ALL {
+                    ui.selectable_value(&mut choice, Some(fmt), fmt.label());
+                }
+            });
+
+        ui.label("Mode:");
+
+        // Only touch state (and the sidecar on disk) when the user actually
+        // picked something different
+        if choice != self.format_override {
+            self.set_format_override(choice);
+        }
+    }
+
+    /// Load a file from disk into the editor
+    ///
+    /// `&mut self` means this method borrows the App mutably
+    /// (it can modify the App's fields)
+    fn load_file(&mut self, path: std::path::PathBuf) {
+        // The worker reads the file; finish_load() picks up the result
+        if !self.file_io.submit(FileRequest::Load(path)) {
+            self.report_busy();
+        }
+    }
+
+    /// Apply a finished load: put the content in the editor, or report why
+    /// it couldn't be read
+    ///
+    /// `password` is the one a password-protected file was unlocked with;
+    /// the document is encrypted with it again on save.
+    fn finish_load(
+        &mut self,
+        path: std::path::PathBuf,
+        modified: Option<std::time::SystemTime>,
+        password: Option<String>,
+        result: anyhow::Result<DecodedText>,
+    ) {
+        // The load started by session restore gets its own status message
+        // and puts the cursor back once it lands
+        let restoring = self.pending_restore.take();
+
+        // storage::load_document returns Result<DecodedText, anyhow::Error>
+        // We use pattern matching to handle both success and error cases
+        match result {
+            // If loading succeeded, we get Ok(decoded)
+            Ok(decoded) => {
+                let DecodedText {
+                    text: content,
+                    encoding,
+                    line_ending,
+                } = decoded;
+
+                // A Final Draft file is converted before anything else, so
+                // one that can't be read leaves the open tabs alone
+                let is_fdx = path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("fdx"));
+                let fdx_import = if is_fdx {
+                    match fdx::import_fdx(&content) {
+                        Ok(converted) => Some(converted),
+                        Err(e) => {
+                            self.status_message =
+                                format!("Error importing Final Draft file: {}", e);
+                            return;
+                        }
+                    }
+                } else {
+                    None
+                };
+
+                // Imported screenplays count too: Open Recent imports again
+                self.remember_recent_file(&path);
+
+                // A file that's already open (Reload from disk) replaces
+                // itself; any other gets a tab of its own, unless the one in
+                // front is an untouched blank document
+                if let Some(index) = self.tab_with_path(&path) {
+                    if index != self.active_tab {
+                        self.activate_tab(index);
+                    }
+                } else if self.current_file_path.is_some()
+                    || self.dirty
+                    || !self.text_content.is_empty()
+                {
+                    self.new_tab();
+                }
+
+                // A file that isn't plain UTF-8 is saved back the same way,
+                // so say which it is
+                let converted = if encoding == TextEncoding::Utf8 {
+                    String::new()
+                } else {
+                    format!(" ({})", encoding)
+                };
+
+                // Fountain and Final Draft screenplays are converted to
+                // BookScript on the way in. The converted text has no file of
+                // its own yet, so it is opened untitled and dirty: Save asks
+                // where to put the .bks instead of overwriting the original.
+                if let Some(converted) = fdx_import {
+                    self.open_imported(&path, converted, "Final Draft");
+                    return;
+                }
+                if format::format_from_extension(&path) == Some(DocumentFormat::Fountain) {
+                    self.open_imported(&path, parser::import_fountain(&content), "Fountain");
+                    return;
+                }
+
+                // Work out what kind of document this is before the content
+                // is moved into the editor (detection borrows it)
+                self.detected_format = format::detect_format(Some(&path), &content);
+
+                // A new document starts a new history - undo must not bring
+                // back the previous file's text
+                self.bank_progress();
+                self.history.reset(&content);
+
+                self.text_content = content;
+                self.text_edited();
+
+                // Update our state to remember which file is open
+                self.current_file_path = Some(path.clone());
+                self.disk_modified = modified;
+                self.line_ending = line_ending;
+                self.encoding = encoding;
+                self.password = password;
+                self.dirty = false;
+
+                // Update status message for the user
+                self.status_message = format!("Loaded: {}{}", path.display(), converted);
+
+                // Pick up a format override saved by a previous session
+                // A broken sidecar shouldn't stop the document from opening,
+                // so we fall back to detection and mention it in the status bar
+                self.format_override = match storage::load_sidecar(&path) {
+                    Ok(meta) => meta.format_override,
+                    Err(e) => {
+                        self.status_message = format!("Loaded: {} ({})", path.display(), e);
+                        None
+                    }
+                };
+
+                if let Some(view) = restoring {
+                    self.status_message = format!("Restored: {}{}", path.display(), converted);
+                    self.pending_view = Some(view);
+                }
+                self.apply_pending_match();
+            }
+            // If loading failed, we get Err(e) where e is the error
+            Err(e) => {
+                // Show the error to the user in the status bar
+                self.status_message = format!("Error loading file: {}", e);
+                self.pending_match = None;
+            }
+        }
+    }
+
+    /// Open a screenplay converted to BookScript as a new, unsaved document
+    ///
+    /// `kind` names the original format in the status bar ("Fountain").
+    fn open_imported(&mut self, path: &std::path::Path, converted: String, kind: &str) {
+        self.detected_format = DocumentFormat::BookScript;
+        self.format_override = None;
+        self.bank_progress();
+        self.history.reset(&converted);
+        self.text_content = converted;
+        self.text_edited();
+
+        self.current_file_path = None;
+        self.disk_modified = None;
+        self.line_ending = LineEnding::Lf;
+        self.encoding = TextEncoding::Utf8;
+        self.password = None;
+        self.dirty = true;
+        self.status_message = format!(
+            "Imported {}: {} (use Save As to keep it as BookScript)",
+            kind,
+            path.display()
+        );
+    }
+
+    /// Save the current text to a file on disk
+    ///
+    /// The write happens on the worker thread; finish_save() handles the
+    /// outcome. Returns true if the save was started, false if another
+    /// file operation is still in flight.
+    fn save_file(&mut self, path: std::path::PathBuf) -> bool {
+        // The worker thread gets its own copy; editing carries on meanwhile
+        let content = self.text_content.clone();
+
+        // Only the open file's own time means anything; Save As to another
+        // path has nothing to compare against
+        let expected_modified = self
+            .disk_modified
+            .filter(|_| self.current_file_path.as_ref() == Some(&path));
+
+        // Version history keeps plain copies, so a password-protected
+        // document has none
+        let snapshots = self.settings.snapshots;
+        let snapshot = snapshots.on_save && self.password.is_none();
+        let request = FileRequest::Save {
+            path,
+            content,
+            line_ending: self.line_ending,
+            encoding: self.encoding,
+            password: self.password.clone(),
+            expected_modified,
+            backups: self.settings.backups,
+            snapshot_keep: snapshot.then_some(snapshots.keep),
+        };
+        if !self.file_io.submit(request) {
+            self.report_busy();
+            return false;
+        }
+        true
+    }
+
+    /// Apply a finished save
+    ///
+    /// Returns true if the file was written, so an action waiting on the
+    /// save (Exit, Close tab) knows whether it's safe to continue.
+    fn finish_save(
+        &mut self,
+        path: std::path::PathBuf,
+        content_hash: u64,
+        modified: Option<std::time::SystemTime>,
+        result: anyhow::Result<()>,
+    ) -> bool {
+        match result {
+            Ok(()) => {
+                // Saving under a new name may change the extension, so
+                // detection has to be redone for the new path
+                if self.current_file_path.as_ref() != Some(&path) {
+                    self.detected_format = format::detect_format(Some(&path), &self.text_content);
+                    self.remember_recent_file(&path);
+
+                    // Bookmarks go along to the new file
+                    let marks = self
+                        .current_file_path
+                        .as_ref()
+                        .and_then(|old| self.session.bookmarks.get(old))
+                        .cloned();
+                    if let Some(marks) = marks {
+                        self.session.bookmarks.insert(path.clone(), marks);
+                    }
+
+                    // Carry an explicit override over to the new location
+                    if self.format_override.is_some() {
+                        let meta = storage::DocumentMeta {
+                            format_override: self.format_override,
+                        };
+                        if let Err(e) = storage::save_sidecar(&path, &meta) {
+                            eprintln!("Could not write sidecar: {}", e);
+                        }
+                    }
+                }
+
+                // Update our state. Edits made while the save was in flight
+                // aren't in the file, so those keep the document dirty.
+                self.current_file_path = Some(path.clone());
+                self.disk_modified = modified;
+                self.dirty = storage::content_hash(&self.text_content) != content_hash;
+                self.status_message = format!("Saved: {}", path.display());
+                // A preview saved under a name of its own is an ordinary
+                // document from now on
+                self.preview = None;
+                true
+            }
+            Err(e) => {
+                self.status_message = format!("Error saving file: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Hand finished loads and saves back to the GUI
+    ///
+    /// Called at the top of every frame, before anything reads the text.
+    fn poll_file_io(&mut self, ctx: &egui::Context) {
+        while let Some(result) = self.file_io.poll() {
+            match result {
+                FileResult::Loaded {
+                    path,
+                    modified,
+                    password,
+                    result,
+                } => self.finish_load(path, modified, password, result),
+                FileResult::Locked {
+                    path,
+                    wrong_password,
+                } => self.unlock_prompt = Some((path, String::new(), wrong_password)),
+                FileResult::Saved {
+                    path,
+                    content_hash,
+                    modified,
+                    encoding,
+                    unencodable,
+                    result,
+                } => {
+                    let saved = self.finish_save(path.clone(), content_hash, modified, result);
+                    if saved {
+                        // The document now is what was written
+                        if let Some(c) = unencodable {
+                            self.status_message =
+                                format!("Saved as UTF-8: {} has no \"{}\"", self.encoding, c);
+                        }
+                        self.encoding = encoding;
+                        self.commit_on_save(&path);
+                    }
+                    // An action parked on "Save changes?" continues only
+                    // once the save has actually succeeded
+                    if let Some(action) = self.after_save.take() {
+                        if saved {
+                            self.perform(ctx, action);
+                        }
+                    }
+                }
+                // A parked action stays parked: it continues if the user
+                // chooses Overwrite or Save As in the prompt
+                FileResult::Conflict { path } => {
+                    self.status_message =
+                        format!("Not saved: {} was changed on disk", path.display());
+                    self.save_conflict = Some(path);
+                }
+            }
+        }
+    }
+
+    /// Tell the user a file operation is still running
+    fn report_busy(&mut self) {
+        if let Some(text) = self.file_io.state().status_text() {
+            self.status_message = format!("Please wait - {}", text);
+        }
+    }
+
+    /// File → Open: ask for a file and load it
+    ///
+    /// Cancelling the dialog leaves everything as it was.
+    fn open_with_dialog(&mut self, ctx: &egui::Context) {
+        if self.file_io.is_busy() {
+            self.report_busy();
+            return;
+        }
+        if let Some(path) = dialogs::pick_document(self.current_file_path.as_deref()) {
+            self.open_document(ctx, path);
+        }
+    }
+
+    /// Queue what copies of the app started since have handed over, and
+    /// come to the front for each
+    fn poll_instance_requests(&mut self, ctx: &egui::Context) {
+        let Some(requests) = &self.instance_requests else {
+            return;
+        };
+        for request in requests.try_iter() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+            ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+            self.pending_opens.push(request);
+        }
+    }
+
+    /// Queue the files just dropped on the window, in the order given
+    fn take_dropped_files(&mut self, ctx: &egui::Context) {
+        let dropped: Vec<std::path::PathBuf> = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .collect()
+        });
+        self.pending_opens
+            .extend(dropped.into_iter().map(instance::Request::Open));
+    }
+
+    /// Open the queued documents, each in a tab of its own (or its tab, if
+    /// it's open already), one at a time as the file worker comes free
+    fn open_pending(&mut self, ctx: &egui::Context) {
+        while !self.pending_opens.is_empty() && !self.file_io.is_busy() {
+            match self.pending_opens.remove(0) {
+                instance::Request::Open(path) => self.open_document(ctx, path),
+                instance::Request::New => self.new_tab(),
+                instance::Request::Show => {}
+            }
+        }
+    }
+
+    /// While files are dragged over the window, dim it and say that
+    /// dropping them opens them
+    fn file_drop_overlay(&self, ctx: &egui::Context) {
+        let count = ctx.input(|i| i.raw.hovered_files.len());
+        if count == 0 {
+            return;
+        }
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("file_drop_overlay"),
+        ));
+        let rect = ctx.screen_rect();
+        painter.rect_filled(rect, 0.0, egui::Color32::from_black_alpha(160));
+        let label = if count == 1 {
+            String::from("Drop to open")
+        } else {
+            format!("Drop to open {} files", format_count(count))
+        };
+        painter.text(
+            rect.center(),
+            egui::Align2::CENTER_CENTER,
+            label,
+            egui::FontId::proportional(24.0),
+            egui::Color32::WHITE,
+        );
+    }
+
+    /// Bring a document to the front: its tab if it's already open,
+    /// otherwise load it (into a new tab, see finish_load)
+    fn open_document(&mut self, ctx: &egui::Context, path: std::path::PathBuf) {
+        if path
+            .extension()
+            .is_some_and(|e| e == project::PROJECT_EXTENSION)
+        {
+            self.open_project(path);
+            return;
+        }
+        match self.tab_with_path(&path) {
+            Some(index) => self.switch_tab(ctx, index),
+            None => self.load_file(path),
+        }
+    }
+
+    // ========================================================================
+    // PROJECTS
+    // ========================================================================
+
+    /// File → New Project: create a project file, with the open document
+    /// as its first file if it has been saved
+    fn new_project(&mut self) {
+        let name = self
+            .current_file_path
+            .as_deref()
+            .and_then(|p| p.file_stem())
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "untitled".to_string());
+        let Some(path) = dialogs::save_file(
+            "New Project",
+            "BookScript project",
+            &[project::PROJECT_EXTENSION],
+            &format!("{}.{}", name, project::PROJECT_EXTENSION),
+            self.current_file_path.as_deref(),
+        ) else {
+            return;
+        };
+
+        let mut new = project::Project::default();
+        new.metadata.title = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let exclude_tags = self.settings.word_count.exclude_tags;
+        let mut open = OpenProject::new(path, new, exclude_tags);
+        if let Some(current) = &self.current_file_path {
+            open.add_file(current);
+        }
+        self.status_message = format!("Created project: {}", open.path.display());
+        self.project = Some(open);
+        self.show_outline = true;
+        self.write_project();
+    }
+
+    /// File → Open Project
+    fn open_project_with_dialog(&mut self) {
+        let current = self
+            .project
+            .as_ref()
+            .map(|open| open.path.as_path())
+            .or(self.current_file_path.as_deref());
+        if let Some(path) = dialogs::pick_file(
+            "Open Project",
+            "BookScript project",
+            &[project::PROJECT_EXTENSION],
+            current,
+        ) {
+            self.open_project(path);
+        }
+    }
+
+    /// Open a project file, replacing the open project (documents already
+    /// open stay open)
+    fn open_project(&mut self, path: std::path::PathBuf) {
+        match project::load_project(&path) {
+            Ok(loaded) => {
+                let exclude_tags = self.settings.word_count.exclude_tags;
+                let open = OpenProject::new(path, loaded, exclude_tags);
+                let files = open.project.files.len();
+                self.status_message = format!(
+                    "Opened project: {} ({} {})",
+                    open.title(),
+                    format_count(files),
+                    if files == 1 { "document" } else { "documents" }
+                );
+                // Read the files in the background now, so the first Find
+                // in Project doesn't have to
+                let paths = (0..files).map(|index| open.file_path(index)).collect();
+                self.project_search.index(paths);
+                self.project = Some(open);
+                self.show_outline = true;
+            }
+            Err(e) => self.status_message = format!("Error opening project: {}", e),
+        }
+    }
+
+    /// Edit → Find in Project (Ctrl+Shift+F): show the panel with its
+    /// query field focused, and search again with what's typed there
+    fn open_project_search(&mut self) {
+        if self.project.is_none() {
+            self.status_message = String::from("Open a project to search all its documents");
+            return;
+        }
+        self.show_project_search = true;
+        self.project_search.focus_field = true;
+        self.run_project_search();
+    }
+
+    /// Search the project's documents for the panel's query, the ones
+    /// open with unsaved changes as they are in the editor
+    fn run_project_search(&mut self) {
+        let Some(open) = &self.project else {
+            return;
+        };
+        let files = (0..open.project.files.len())
+            .map(|index| open.file_path(index))
+            .collect();
+        let mut unsaved = std::collections::HashMap::new();
+        if let Some(path) = self.current_file_path.clone().filter(|_| self.dirty) {
+            unsaved.insert(path, self.text_content.clone());
+        }
+        for tab in self.tabs.iter().filter(|tab| tab.dirty) {
+            if let Some(path) = &tab.path {
+                unsaved.insert(path.clone(), tab.text.clone());
+            }
+        }
+        self.project_search.start(files, unsaved);
+    }
+
+    /// Draw the Find in Project panel, taking in results as they arrive
+    fn project_search_panel(&mut self, ctx: &egui::Context) {
+        if self.project_search.poll() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+        let Some(open) = &self.project else {
+            self.show_project_search = false;
+            return;
+        };
+        let search = &mut self.project_search;
+        let action = egui::SidePanel::right("project_search_panel")
+            .resizable(true)
+            .default_width(380.0)
+            .show(ctx, |ui| {
+                project_search::show(ui, search, |path| open.entry_for(path))
+            })
+            .inner;
+
+        match action {
+            Some(SearchAction::Search) => self.run_project_search(),
+            Some(SearchAction::Open { path, line, range }) => {
+                self.open_search_result(ctx, path, line, range)
+            }
+            Some(SearchAction::Close) => self.show_project_search = false,
+            None => {}
+        }
+    }
+
+    /// Open a Find in Project result: its document, with the match selected
+    /// once it's in front (see apply_pending_match)
+    fn open_search_result(
+        &mut self,
+        ctx: &egui::Context,
+        path: std::path::PathBuf,
+        line: usize,
+        range: Range<usize>,
+    ) {
+        if self.file_io.is_busy() {
+            self.report_busy();
+            return;
+        }
+        if !path.exists() && self.tab_with_path(&path).is_none() {
+            self.status_message = format!("No longer exists: {}", path.display());
+            return;
+        }
+        self.pending_match = Some((path.clone(), line, range));
+        self.open_document(ctx, path);
+        self.apply_pending_match();
+    }
+
+    /// Select the waiting Find in Project match if its document is the one
+    /// in front now
+    ///
+    /// If the text has changed so the range no longer falls on characters,
+    /// the caret goes to the match's line instead.
+    fn apply_pending_match(&mut self) {
+        let Some((path, line, range)) = self.pending_match.take() else {
+            return;
+        };
+        if self.current_file_path.as_ref() != Some(&path) {
+            // Still loading: wait for finish_load
+            self.pending_match = Some((path, line, range));
+            return;
+        }
+        let text = &self.text_content;
+        let fits = range.end <= text.len()
+            && text.is_char_boundary(range.start)
+            && text.is_char_boundary(range.end);
+        if fits {
+            self.select_match(range);
+        } else {
+            self.pending_jump = Some(line);
+        }
+    }
+
+    /// File → Close Project: back to the plain outline
+    fn close_project(&mut self) {
+        // Keep what the settings and compile windows had changed
+        if self.show_project_window || self.show_compile_window {
+            self.write_project();
+        }
+        self.show_project_search = false;
+        if let Some(open) = self.project.take() {
+            self.status_message = format!("Closed project: {}", open.title());
+        }
+        self.show_project_window = false;
+        self.show_compile_window = false;
+    }
+
+    /// Write the open project's file after a change to it
+    fn write_project(&mut self) {
+        let Some(open) = &self.project else {
+            return;
+        };
+        if let Err(e) = project::save_project(&open.path, &open.project) {
+            self.status_message = format!("Error saving project: {}", e);
+        }
+    }
+
+    /// Keep the project's word counts in step with the word count setting
+    /// and the open document's text
+    fn refresh_project_counts(&mut self) {
+        let exclude_tags = self.settings.word_count.exclude_tags;
+        let words = self.refresh_document_stats().words;
+        let Some(open) = &mut self.project else {
+            return;
+        };
+        if open.exclude_tags != exclude_tags {
+            open.recount(exclude_tags);
+        }
+        let index = self
+            .current_file_path
+            .as_deref()
+            .and_then(|path| open.index_of(path));
+        if let Some(index) = index {
+            open.word_counts[index] = Some(words);
+        }
+    }
+
+    /// Carry out a click in the project sidebar
+    fn apply_project_action(&mut self, ctx: &egui::Context, action: ProjectAction) {
+        let Some(open) = &mut self.project else {
+            return;
+        };
+        match action {
+            ProjectAction::Open(index) => {
+                let path = open.file_path(index);
+                if self.file_io.is_busy() {
+                    self.report_busy();
+                } else if path.exists() {
+                    self.open_document(ctx, path);
+                } else {
+                    self.status_message = format!("No longer exists: {}", path.display());
+                }
+            }
+            ProjectAction::Jump(line) => self.pending_jump = Some(line),
+            ProjectAction::Move(index, offset) => {
+                open.move_file(index, offset);
+                self.write_project();
+            }
+            ProjectAction::Remove(index) => {
+                let path = open.file_path(index);
+                open.remove_file(index);
+                self.status_message = format!("Removed from project: {}", path.display());
+                self.write_project();
+            }
+            ProjectAction::AddCurrent => {
+                if let Some(path) = &self.current_file_path {
+                    open.add_file(path);
+                    self.write_project();
+                }
+            }
+            ProjectAction::NewFile => self.new_project_file(ctx),
+        }
+    }
+
+    /// Sidebar → New…: create an empty document next to the project file,
+    /// add it to the project and open it
+    ///
+    /// Picking a file that already exists adds it as it is, rather than
+    /// emptying it.
+    fn new_project_file(&mut self, ctx: &egui::Context) {
+        let Some(open) = &self.project else {
+            return;
+        };
+        let name = format!("chapter {}.bks", open.project.files.len() + 1);
+        let Some(path) = dialogs::save_file(
+            "New Document",
+            "BookScript",
+            &["bks", "scr"],
+            &name,
+            Some(&open.path),
+        ) else {
+            return;
+        };
+
+        if !path.exists() {
+            if let Err(e) = storage::save_text_file(&path, "", storage::BackupSettings::OFF) {
+                self.status_message = format!("Error creating document: {}", e);
+                return;
+            }
+        }
+        if let Some(open) = &mut self.project {
+            open.add_file(&path);
+        }
+        self.write_project();
+        self.open_document(ctx, path);
+    }
+
+    /// Draw the File → Project Settings window
+    fn project_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_project_window;
+        let Some(project) = &mut self.project else {
+            self.show_project_window = false;
+            return;
+        };
+
+        egui::Window::new("Project Settings")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(project.path.display().to_string());
+                ui.add_space(4.0);
+                let project = &mut project.project;
+                egui::Grid::new("project_settings")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Title:");
+                        ui.text_edit_singleline(&mut project.metadata.title);
+                        ui.end_row();
+                        ui.label("Author:");
+                        ui.text_edit_singleline(&mut project.metadata.author);
+                        ui.end_row();
+                        ui.label("Target:");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::DragValue::new(&mut project.settings.target_words)
+                                    .speed(100.0)
+                                    .range(0..=10_000_000),
+                            );
+                            ui.label("words (0 = none)");
+                        });
+                        ui.end_row();
+                    });
+            });
+
+        // Closing the window is when changes get written to disk
+        if self.show_project_window && !open {
+            self.write_project();
+        }
+        self.show_project_window = open;
+    }
+
+    /// Draw the File → Compile window: pick the documents, front matter,
+    /// separator and format, then where the result goes
+    fn compile_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_compile_window;
+        let mut compile = false;
+        let Some(project) = &mut self.project else {
+            self.show_compile_window = false;
+            return;
+        };
+
+        egui::Window::new("Compile")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                let files = &project.project.files;
+                let settings = &mut project.project.settings.compile;
+
+                ui.label("Include:");
+                if files.is_empty() {
+                    ui.weak("The project has no documents yet.");
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        for entry in files {
+                            let mut included = !settings.excluded.contains(entry);
+                            if ui.checkbox(&mut included, entry.as_str()).changed() {
+                                if included {
+                                    settings.excluded.retain(|e| e != entry);
+                                } else {
+                                    settings.excluded.push(entry.clone());
+                                }
+                            }
+                        }
+                    });
+                ui.separator();
+
+                ui.label("Front matter (BookScript, before the first document):");
+                ui.add(
+                    egui::TextEdit::multiline(&mut settings.front_matter)
+                        .desired_rows(3)
+                        .desired_width(f32::INFINITY),
+                );
+
+                egui::Grid::new("compile_options")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Between documents:");
+                        egui::ComboBox::from_id_salt("compile_separator")
+                            .selected_text(settings.separator.label())
+                            .show_ui(ui, |ui| {
+                                for separator in Separator::ALL {
+                                    ui.selectable_value(
+                                        &mut settings.separator,
+                                        separator,
+                                        separator.label(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+                        if settings.separator == Separator::Custom {
+                            ui.label("Separator line:");
+                            ui.text_edit_singleline(&mut settings.custom_separator);
+                            ui.end_row();
+                        }
+                        ui.label("Format:");
+                        egui::ComboBox::from_id_salt("compile_target")
+                            .selected_text(settings.target.label())
+                            .show_ui(ui, |ui| {
+                                for target in CompileTarget::ALL {
+                                    ui.selectable_value(
+                                        &mut settings.target,
+                                        target,
+                                        target.label(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+                    });
+                if matches!(settings.target, CompileTarget::Pdf | CompileTarget::Docx) {
+                    ui.weak("Uses the title page options from its own Export window.");
+                }
+
+                ui.add_space(8.0);
+                let any = files.iter().any(|entry| !settings.excluded.contains(entry));
+                if ui.add_enabled(any, egui::Button::new("Compile…")).clicked() {
+                    compile = true;
+                }
+            });
+
+        if compile && self.compile_project() {
+            open = false;
+        }
+        // Closing the window is when changes get written to disk
+        if self.show_compile_window && !open {
+            self.write_project();
+        }
+        self.show_compile_window = open;
+    }
+
+    /// Put the project's included documents together and export them;
+    /// returns false if the file dialog was cancelled
+    ///
+    /// Open documents are compiled as they are in the editor, saved or not.
+    fn compile_project(&mut self) -> bool {
+        let Some(project) = &self.project else {
+            return false;
+        };
+        let settings = &project.project.settings.compile;
+        let target = settings.target;
+        let title = project.title();
+
+        let mut parts = Vec::new();
+        for (index, entry) in project.project.files.iter().enumerate() {
+            if settings.excluded.contains(entry) {
+                continue;
+            }
+            let path = project.file_path(index);
+            let open_text = if self.current_file_path.as_deref() == Some(path.as_path()) {
+                Some(self.text_content.clone())
+            } else {
+                self.tabs
+                    .iter()
+                    .enumerate()
+                    .find(|(i, tab)| *i != self.active_tab && tab.path.as_ref() == Some(&path))
+                    .map(|(_, tab)| tab.text.clone())
+            };
+            let text = match open_text {
+                Some(text) => text,
+                None => match storage::load_text_file(&path) {
+                    Ok(text) => text,
+                    Err(e) => {
+                        self.status_message = format!("Error compiling: {}", e);
+                        return false;
+                    }
+                },
+            };
+            let name = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            // A document's table of contents gives its own lines or pages,
+            // which aren't where things are in the manuscript
+            let text = toc::remove(&text).into_owned();
+            parts.push(Part { name, text });
+        }
+        let mut text = compile::assemble(&parts, settings);
+        if !self.settings.export_comments {
+            text = parser::strip_comments(&text).into_owned();
+        }
+
+        // The project's author, if it names one, goes on the title pages
+        let mut pdf_options = self.settings.pdf.clone();
+        let mut docx_options = self.settings.docx.clone();
+        let author = project.project.metadata.author.trim();
+        if !author.is_empty() {
+            pdf_options.author = author.to_string();
+            docx_options.author = author.to_string();
+        }
+
+        let Some(path) = dialogs::save_file(
+            &format!("Compile as {}", target.label()),
+            target.label(),
+            &[target.extension()],
+            &format!("{}.{}", title, target.extension()),
+            Some(&project.path),
+        ) else {
+            return false;
+        };
+
+        let result =
+            compile::render(target, &text, &title, &pdf_options, &docx_options).and_then(|bytes| {
+                storage::save_binary_file(&path, &bytes)?;
+                Ok(bytes.len())
+            });
+        self.status_message = match result {
+            Ok(size) => format!(
+                "Compiled {} {}: {} ({})",
+                format_count(parts.len()),
+                if parts.len() == 1 {
+                    "document"
+                } else {
+                    "documents"
+                },
+                path.display(),
+                format_size(size as u64)
+            ),
+            Err(e) => format!("Error compiling: {}", e),
+        };
+        true
+    }
+
+    /// File → Open Recent → a file
+    ///
+    /// A file that has been moved or deleted since is dropped from the list
+    /// instead of failing to load.
+    fn open_recent(&mut self, ctx: &egui::Context, path: std::path::PathBuf) {
+        if self.file_io.is_busy() {
+            self.report_busy();
+            return;
+        }
+        if !path.exists() {
+            self.status_message = format!("No longer exists: {}", path.display());
+            self.recent_files.retain(|recent| *recent != path);
+            self.write_recent_files();
+            return;
+        }
+        self.open_document(ctx, path);
+    }
+
+    /// Put a document at the top of File → Open Recent
+    fn remember_recent_file(&mut self, path: &std::path::Path) {
+        if self.recent_files.first().map(|p| p.as_path()) == Some(path) {
+            return;
+        }
+        storage::push_recent_file(&mut self.recent_files, path);
+        self.write_recent_files();
+    }
+
+    /// Write the recent files list, logging (not showing) any failure
+    fn write_recent_files(&self) {
+        if let Some(path) = &self.recent_files_path {
+            if let Err(e) = storage::save_recent_files(path, &self.recent_files) {
+                eprintln!("Failed to record recent files: {}", e);
+            }
+        }
+    }
+
+    /// The tab that has `path` open, if any
+    fn tab_with_path(&self, path: &std::path::Path) -> Option<usize> {
+        if self.current_file_path.as_deref() == Some(path) {
+            return Some(self.active_tab);
+        }
+        self.tabs
+            .iter()
+            .enumerate()
+            .find(|(index, tab)| *index != self.active_tab && tab.path.as_deref() == Some(path))
+            .map(|(index, _)| index)
+    }
+
+    /// The first tab with unsaved changes, if any
+    fn first_dirty_tab(&self) -> Option<usize> {
+        (0..self.tabs.len()).find(|&index| {
+            if index == self.active_tab {
+                self.dirty
+            } else {
+                self.tabs[index].dirty
+            }
+        })
+    }
+
+    /// Title, dirty marker and path for each tab, in order
+    fn tab_labels(&self) -> Vec<TabLabel> {
+        let title = |path: &Option<std::path::PathBuf>| {
+            path.as_ref()
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| String::from("Untitled"))
+        };
+        self.tabs
+            .iter()
+            .enumerate()
+            .map(|(index, tab)| {
+                if index == self.active_tab {
+                    TabLabel {
+                        title: self.document_name(),
+                        dirty: self.dirty,
+                        path: self.current_file_path.clone(),
+                    }
+                } else {
+                    TabLabel {
+                        title: tab.preview.clone().unwrap_or_else(|| title(&tab.path)),
+                        dirty: tab.dirty,
+                        path: tab.path.clone(),
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Bring another tab to the front (clicking it, Ctrl+Tab)
+    ///
+    /// Not while a load or save is running or a prompt is open: their
+    /// results belong to the document that's in front now. Unsaved edits
+    /// in the tab being left get an autosave copy straight away, since
+    /// autosave only ever looks at the document in front.
+    fn switch_tab(&mut self, ctx: &egui::Context, index: usize) {
+        if index == self.active_tab || index >= self.tabs.len() {
+            return;
+        }
+        if self.file_io.is_busy() {
+            self.report_busy();
+            return;
+        }
+        if self.unsaved_prompt.is_some() || self.save_conflict.is_some() {
+            return;
+        }
+        if self.dirty {
+            self.autosave_now(ctx.input(|i| i.time));
+        }
+        self.activate_tab(index);
+    }
+
+    /// Park the document in front and take out the one in tab `index`
+    fn activate_tab(&mut self, index: usize) {
+        self.bank_progress();
+        let parked = self.park_document();
+        self.tabs[self.active_tab] = parked;
+        let tab = std::mem::replace(&mut self.tabs[index], DocumentTab::empty());
+        self.active_tab = index;
+        self.unpark_document(tab);
+    }
+
+    /// File → New: open an empty, untitled document in a tab of its own
+    fn new_tab(&mut self) {
+        self.tabs.push(DocumentTab::empty());
+        self.activate_tab(self.tabs.len() - 1);
+    }
+
+    /// File → New from Template → a template: a new, untitled document in
+    /// a tab of its own, starting with the template's text and mode
+    fn new_from_template(&mut self, template: Template) {
+        self.new_tab();
+        self.history.reset(&template.text);
+        self.text_content = template.text;
+        self.text_edited();
+        self.detected_format = format::detect_format(None, &self.text_content);
+        self.format_override = template.format;
+        self.status_message = format!("New document from template \"{}\"", template.name);
+    }
+
+    /// File → New from Template → Save as Template…: ask for a name,
+    /// suggesting the document's
+    fn open_template_prompt(&mut self) {
+        let name = self
+            .current_file_path
+            .as_deref()
+            .and_then(|p| p.file_stem())
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.template_name = Some(name);
+    }
+
+    /// Draw the Save as Template prompt: a name, then Enter
+    fn template_prompt(&mut self, ctx: &egui::Context) {
+        let Some(mut name) = self.template_name.take() else {
+            return;
+        };
+        let mut save = false;
+        let mut open = true;
+
+        egui::Window::new("Save as Template")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 60.0))
+            .show(ctx, |ui| {
+                ui.label("File → New from Template will offer this document's text and mode.");
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut name)
+                        .hint_text("Name, e.g. \"Mystery novel\"")
+                        .desired_width(300.0),
+                );
+                response.request_focus();
+                let taken = self
+                    .user_templates
+                    .iter()
+                    .any(|t| t.name.eq_ignore_ascii_case(name.trim()));
+                if taken {
+                    ui.weak("Replaces your template of that name.");
+                }
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    save = true;
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Save Template").clicked() {
+                        save = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        open = false;
+                    }
+                });
+            });
+
+        if save && !name.trim().is_empty() {
+            self.save_as_template(name.trim());
+            open = false;
+        }
+        if open {
+            self.template_name = Some(name);
+        }
+    }
+
+    /// Keep the document's text and mode as a user template called `name`
+    fn save_as_template(&mut self, name: &str) {
+        let template = Template {
+            name: name.to_string(),
+            format: self.format_override,
+            text: self.text_content.clone(),
+        };
+        let replaced = templates::add(&mut self.user_templates, template);
+        self.status_message = match self.write_templates() {
+            Ok(()) if replaced => format!("Template \"{}\" replaced", name),
+            Ok(()) => format!("Saved template \"{}\"", name),
+            Err(e) => format!("Error saving template: {}", e),
+        };
+    }
+
+    /// Remove user template `index`
+    fn delete_template(&mut self, index: usize) {
+        let template = self.user_templates.remove(index);
+        self.status_message = match self.write_templates() {
+            Ok(()) => format!("Deleted template \"{}\"", template.name),
+            Err(e) => format!("Error deleting template: {}", e),
+        };
+    }
+
+    /// Write the user's templates to the data directory
+    fn write_templates(&self) -> anyhow::Result<()> {
+        let path = self
+            .templates_path
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Could not determine user data directory"))?;
+        storage::save_templates(path, &self.user_templates)
+    }
+
+    /// Close the tab in front without saving
+    ///
+    /// The tab to its right (or else its left) takes its place. Closing the
+    /// last tab leaves one empty, untitled document, as at startup.
+    fn discard_active_tab(&mut self) {
+        self.bank_progress();
+        let next = if self.tabs.len() == 1 {
+            DocumentTab::empty()
+        } else {
+            self.tabs.remove(self.active_tab);
+            self.active_tab = self.active_tab.min(self.tabs.len() - 1);
+            std::mem::replace(&mut self.tabs[self.active_tab], DocumentTab::empty())
+        };
+        self.unpark_document(next);
+    }
+
+    /// Move the document in front out of App's fields
+    fn park_document(&mut self) -> DocumentTab {
+        DocumentTab {
+            text: std::mem::take(&mut self.text_content),
+            path: self.current_file_path.take(),
+            dirty: std::mem::take(&mut self.dirty),
+            history: std::mem::replace(
+                &mut self.history,
+                History::new("", HistoryLimits::default()),
+            ),
+            disk_modified: self.disk_modified.take(),
+            line_ending: self.line_ending,
+            encoding: self.encoding,
+            password: self.password.take(),
+            detected_format: self.detected_format,
+            format_override: self.format_override.take(),
+            cursor: self.editor_cursor,
+            scroll: self.editor_scroll,
+            preview: self.preview.take(),
+        }
+    }
+
+    /// Put a parked document in App's fields, with the caret and scroll
+    /// position it had
+    fn unpark_document(&mut self, tab: DocumentTab) {
+        self.text_content = tab.text;
+        self.text_edited();
+        self.current_file_path = tab.path;
+        self.dirty = tab.dirty;
+        self.history = tab.history;
+        self.disk_modified = tab.disk_modified;
+        self.line_ending = tab.line_ending;
+        self.encoding = tab.encoding;
+        self.password = tab.password;
+        self.detected_format = tab.detected_format;
+        self.format_override = tab.format_override;
+        self.editor_cursor = tab.cursor;
+        self.editor_scroll = tab.scroll;
+        self.preview = tab.preview;
+
+        // Anything waiting to move the caret was meant for the other document
+        self.pending_jump = None;
+        self.pending_selection = None;
+        self.pending_view = Some((Some(tab.cursor), Some(tab.scroll)));
+        self.find.current = None;
+        if self.show_snapshots_window {
+            self.refresh_snapshots();
+        }
+    }
+
+    /// Carry out a click in the tab bar
+    fn apply_tab_action(&mut self, ctx: &egui::Context, action: TabAction) {
+        match action {
+            TabAction::Select(index) => self.switch_tab(ctx, index),
+            TabAction::Close(index) => {
+                self.switch_tab(ctx, index);
+                if self.active_tab == index {
+                    self.request(ctx, PendingAction::CloseTab);
+                }
+            }
+            TabAction::Move { from, to } => {
+                let tab = self.tabs.remove(from);
+                self.tabs.insert(to, tab);
+                self.active_tab = tabs::active_after_move(self.active_tab, from, to);
+            }
+            TabAction::New => self.new_tab(),
+        }
+    }
+
+    /// Ctrl+Tab / Ctrl+Shift+Tab: the next or previous tab, wrapping around
+    fn cycle_tab(&mut self, ctx: &egui::Context, forward: bool) {
+        let count = self.tabs.len();
+        let index = if forward {
+            (self.active_tab + 1) % count
+        } else {
+            (self.active_tab + count - 1) % count
+        };
+        self.switch_tab(ctx, index);
+    }
+
+    /// File → Save: write to the current file, or ask where if there isn't one
+    ///
+    /// Returns true if a save was started; false if the Save As dialog was
+    /// cancelled or another file operation is still running.
+    fn save(&mut self) -> bool {
+        match self.current_file_path.clone() {
+            Some(path) => self.save_file(path),
+            None => self.save_as(),
+        }
+    }
+
+    /// File → Save As: always ask where to save
+    ///
+    /// Returns true if a save was started (see save()).
+    fn save_as(&mut self) -> bool {
+        if self.file_io.is_busy() {
+            self.report_busy();
+            return false;
+        }
+        let encrypted = self.password.is_some();
+        match dialogs::save_document_as(self.current_file_path.as_deref(), encrypted) {
+            Some(path) => self.save_file(path),
+            None => false,
+        }
+    }
+
+    /// Run `action`, or ask about unsaved changes first if there are any
+    fn request(&mut self, ctx: &egui::Context, action: PendingAction) {
+        match action {
+            PendingAction::Exit => self.perform(ctx, action),
+            PendingAction::CloseTab if self.dirty => self.unsaved_prompt = Some(action),
+            PendingAction::CloseTab => self.perform(ctx, action),
+        }
+    }
+
+    /// Carry out an action, having asked about the active document
+    ///
+    /// Exiting still stops at each other tab with unsaved changes: it is
+    /// brought to the front and the prompt asked again, so after a Save the
+    /// next one comes up, until none are left.
+    fn perform(&mut self, ctx: &egui::Context, action: PendingAction) {
+        match action {
+            PendingAction::Exit => match self.first_dirty_tab() {
+                Some(index) => {
+                    self.switch_tab(ctx, index);
+                    self.unsaved_prompt = Some(PendingAction::Exit);
+                }
+                None => {
+                    self.allow_close = true;
+                    // ctx.send_viewport_cmd tells eframe to close the window
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                }
+            },
+            PendingAction::CloseTab => self.discard_active_tab(),
+        }
+    }
+
+    /// Draw the "Save changes?" prompt for a parked action
+    ///
+    /// - Save: normal save (Save As if untitled); continue only if it worked
+    /// - Discard: continue without saving
+    /// - Cancel: forget the action entirely
+    fn unsaved_changes_window(&mut self, ctx: &egui::Context) {
+        let Some(action) = self.unsaved_prompt else {
+            return;
+        };
+
+        #[derive(PartialEq)]
+        enum Choice {
+            Save,
+            Discard,
+            Cancel,
+        }
+        let mut choice = None;
+
+        let name = self.document_name();
+        egui::Window::new("Unsaved changes")
+            .collapsible(false)
+            .resizable(false)
+            // Pin the window to the middle of the screen, like a modal dialog
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(format!("Save changes to \"{}\" before continuing?", name));
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        choice = Some(Choice::Save);
+                    }
+                    if ui.button("Discard").clicked() {
+                        choice = Some(Choice::Discard);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        choice = Some(Choice::Cancel);
+                    }
+                });
+            });
+
+        match choice {
+            Some(Choice::Save) => {
+                self.unsaved_prompt = None;
+                // The action runs when the save finishes (see poll_file_io)
+                if self.save() {
+                    self.after_save = Some(action);
+                }
+            }
+            Some(Choice::Discard) => {
+                // Discarding closes the tab either way; when exiting, the
+                // next unsaved tab (if any) is asked about after it
+                self.unsaved_prompt = None;
+                self.discard_active_tab();
+                if action == PendingAction::Exit {
+                    self.perform(ctx, action);
+                }
+            }
+            Some(Choice::Cancel) => self.unsaved_prompt = None,
+            None => {}
+        }
+    }
+
+    /// Draw the "File changed on disk" prompt after a save was stopped
+    ///
+    /// - Overwrite: save anyway, replacing the other version
+    /// - Reload from disk: throw away the edits here and load theirs
+    /// - Save As…: keep both, by putting this version somewhere else
+    /// - Compare: open the Compare window on the version on disk, to see
+    ///   what changed there and revert any of it into the editor; nothing is
+    ///   saved (the next save asks again)
+    /// - Cancel: do nothing for now (the next save asks again)
+    ///
+    /// An action waiting on the save (Exit, Close tab) goes ahead after Overwrite
+    /// or Save As succeed, and is dropped otherwise.
+    fn save_conflict_window(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.save_conflict.clone() else {
+            return;
+        };
+
+        #[derive(PartialEq)]
+        enum Choice {
+            Overwrite,
+            Reload,
+            SaveAs,
+            Compare,
+            Cancel,
+        }
+        let mut choice = None;
+
+        egui::Window::new("File changed on disk")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "\"{}\" was changed by another program since it was opened here.",
+                    path.display()
+                ));
+                ui.label("Saving now would overwrite those changes.");
+                ui.add_space(8.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Overwrite").clicked() {
+                        choice = Some(Choice::Overwrite);
+                    }
+                    if ui.button("Reload from disk").clicked() {
+                        choice = Some(Choice::Reload);
+                    }
+                    if ui.button("Save As…").clicked() {
+                        choice = Some(Choice::SaveAs);
+                    }
+                    if ui.button("Compare").clicked() {
+                        choice = Some(Choice::Compare);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        choice = Some(Choice::Cancel);
+                    }
+                });
+            });
+
+        let Some(choice) = choice else {
+            return;
+        };
+        self.save_conflict = None;
+        let started = match choice {
+            Choice::Overwrite => {
+                // Accept the version on disk as seen, so this save's check
+                // passes (unless it changes yet again in the meantime)
+                self.disk_modified = self.storage.modified(&path);
+                self.save_file(path)
+            }
+            Choice::SaveAs => self.save_as(),
+            Choice::Reload => {
+                self.load_file(path);
+                false
+            }
+            Choice::Compare => {
+                match storage::load_document_from(self.storage.as_ref(), &path) {
+                    Ok(on_disk) => {
+                        self.comparison = Some((String::from("On disk"), on_disk.text));
+                        self.comparison_diff = None;
+                    }
+                    Err(e) => self.status_message = format!("Error reading file: {}", e),
+                }
+                false
+            }
+            Choice::Cancel => false,
+        };
+        if !started {
+            self.after_save = None;
+        }
+    }
+
+    /// File name shown in the title bar and prompts ("Untitled" if unsaved)
+    fn document_name(&self) -> String {
+        if let Some(preview) = &self.preview {
+            return preview.clone();
+        }
+        self.current_file_path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from("Untitled"))
+    }
+
+    /// Keep the window title in sync: "*chapter1.bks — BookScript Writer"
+    ///
+    /// The leading * marks unsaved changes, and "[read-only]" after the name
+    /// that View → Read-only is on. The command is only sent when the title
+    /// actually changes, not every frame.
+    fn update_window_title(&mut self, ctx: &egui::Context) {
+        let marker = if self.dirty { "*" } else { "" };
+        let read_only = if self.locked() { " [read-only]" } else { "" };
+        let title = format!(
+            "{}{}{} — BookScript Writer",
+            marker,
+            self.document_name(),
+            read_only
+        );
+
+        if title != self.window_title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.window_title = title;
+        }
+    }
+
+    /// Check for application-wide keyboard shortcuts
+    ///
+    /// consume_shortcut() both checks for the key combination AND removes it
+    /// from the input queue, so the text editor won't also see it.
+    /// COMMAND means Ctrl on Windows/Linux and Cmd on macOS.
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        use egui::{Key, KeyboardShortcut, Modifiers};
+
+        let save_as = KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::S);
+        let save = KeyboardShortcut::new(Modifiers::COMMAND, Key::S);
+        let open = KeyboardShortcut::new(Modifiers::COMMAND, Key::O);
+
+        // Ctrl+Shift+S must be checked BEFORE Ctrl+S: egui's matching ignores
+        // extra Shift, so the Ctrl+S check would swallow it otherwise
+        if ctx.input_mut(|i| i.consume_shortcut(&save_as)) {
+            self.save_as();
+        } else if ctx.input_mut(|i| i.consume_shortcut(&save)) {
+            self.save();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&open)) {
+            self.open_with_dialog(ctx);
+        }
+        let print = KeyboardShortcut::new(Modifiers::COMMAND, Key::P);
+        if ctx.input_mut(|i| i.consume_shortcut(&print)) {
+            self.open_print_window();
+        }
+
+        // Tabs. Ctrl+Shift+Tab before Ctrl+Tab, for the same reason as Save As
+        let new_tab = KeyboardShortcut::new(Modifiers::COMMAND, Key::N);
+        let close_tab = KeyboardShortcut::new(Modifiers::COMMAND, Key::W);
+        let previous_tab = KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::Tab);
+        let next_tab = KeyboardShortcut::new(Modifiers::COMMAND, Key::Tab);
+        if ctx.input_mut(|i| i.consume_shortcut(&new_tab)) {
+            self.new_tab();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&close_tab)) && !self.file_io.is_busy() {
+            self.request(ctx, PendingAction::CloseTab);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&previous_tab)) {
+            self.cycle_tab(ctx, false);
+        } else if ctx.input_mut(|i| i.consume_shortcut(&next_tab)) {
+            self.cycle_tab(ctx, true);
+        }
+
+        // Undo/redo are consumed here so the TextEdit's own (shallow) undo
+        // never sees them. Same Shift ordering as Save As above.
+        let redo_shift = KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z);
+        let undo = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+        let redo = KeyboardShortcut::new(Modifiers::COMMAND, Key::Y);
+
+        if ctx.input_mut(|i| i.consume_shortcut(&redo_shift) || i.consume_shortcut(&redo)) {
+            self.redo(ctx);
+        } else if ctx.input_mut(|i| i.consume_shortcut(&undo)) {
+            self.undo(ctx);
+        }
+
+        // Navigation: chapters with Ctrl, scenes with Alt
+        let jumps = [
+            (
+                Modifiers::COMMAND,
+                Key::PageDown,
+                Landmark::Chapter,
+                Direction::Next,
+            ),
+            (
+                Modifiers::COMMAND,
+                Key::PageUp,
+                Landmark::Chapter,
+                Direction::Previous,
+            ),
+            (
+                Modifiers::ALT,
+                Key::PageDown,
+                Landmark::Scene,
+                Direction::Next,
+            ),
+            (
+                Modifiers::ALT,
+                Key::PageUp,
+                Landmark::Scene,
+                Direction::Previous,
+            ),
+        ];
+        for (modifiers, key, landmark, direction) in jumps {
+            let shortcut = KeyboardShortcut::new(modifiers, key);
+            if ctx.input_mut(|i| i.consume_shortcut(&shortcut)) {
+                self.jump_to_landmark(landmark, direction);
+            }
+        }
+        let go_to = KeyboardShortcut::new(Modifiers::COMMAND, Key::G);
+        if ctx.input_mut(|i| i.consume_shortcut(&go_to)) {
+            self.go_to_query.get_or_insert_with(String::new);
+        }
+
+        // Bookmarks. Shift+F2 before F2, as with Save As above.
+        let bookmark = KeyboardShortcut::new(Modifiers::COMMAND, Key::B);
+        if ctx.input_mut(|i| i.consume_shortcut(&bookmark)) {
+            let line = editing::line_at_char(&self.text_content, self.editor_cursor);
+            self.toggle_bookmark(line);
+        }
+        if ctx.input_mut(|i| i.consume_key(Modifiers::SHIFT, Key::F2)) {
+            self.jump_to_bookmark(false);
+        } else if ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::F2)) {
+            self.jump_to_bookmark(true);
+        }
+
+        // Find and replace. Shift+F3 before F3, as with Save As above.
+        // Ctrl+Shift+F first: Ctrl+F would take it too
+        let find_in_project = KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::F);
+        if ctx.input_mut(|i| i.consume_shortcut(&find_in_project)) {
+            self.open_project_search();
+        }
+        let find = KeyboardShortcut::new(Modifiers::COMMAND, Key::F);
+        let replace = KeyboardShortcut::new(Modifiers::COMMAND, Key::H);
+        if ctx.input_mut(|i| i.consume_shortcut(&find)) {
+            self.open_find_bar(false);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&replace)) {
+            self.open_find_bar(true);
+        }
+        if ctx.input_mut(|i| i.consume_key(Modifiers::SHIFT, Key::F3)) {
+            self.find_step(false);
+        } else if ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::F3)) {
+            self.find_step(true);
+        }
+
+        // Esc closes the Go to prompt first, if it's open, then the
+        // find bar
+        if self.go_to_query.is_some()
+            && ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape))
+        {
+            self.go_to_query = None;
+        }
+        if self.show_find_bar && ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape)) {
+            self.close_find_bar(ctx);
+        }
+        if self.show_project_search
+            && ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape))
+        {
+            self.show_project_search = false;
+        }
+
+        // Focus mode: F11 toggles, Esc only leaves (so Esc keeps working
+        // normally everywhere else)
+        if ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::F11)) {
+            self.set_focus_mode(ctx, !self.focus_mode);
+        }
+        if self.focus_mode && ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape)) {
+            self.set_focus_mode(ctx, false);
+        }
+
+        // Editor zoom. "=" and "+" share a key on most layouts, and which
+        // one arrives depends on Shift, so both zoom in.
+        let zoom_in = KeyboardShortcut::new(Modifiers::COMMAND, Key::Equals);
+        let zoom_in_plus = KeyboardShortcut::new(Modifiers::COMMAND, Key::Plus);
+        let zoom_out = KeyboardShortcut::new(Modifiers::COMMAND, Key::Minus);
+        let zoom_reset = KeyboardShortcut::new(Modifiers::COMMAND, Key::Num0);
+
+        if ctx.input_mut(|i| i.consume_shortcut(&zoom_in) || i.consume_shortcut(&zoom_in_plus)) {
+            self.zoom(settings::FONT_SIZE_STEP);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&zoom_out)) {
+            self.zoom(-settings::FONT_SIZE_STEP);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&zoom_reset)) {
+            self.settings.editor.font_size = settings::DEFAULT_FONT_SIZE;
+        }
+    }
+
+    /// The open document's bookmarks, in line order
+    fn bookmarks(&self) -> &[Bookmark] {
+        self.current_file_path
+            .as_ref()
+            .and_then(|path| self.session.bookmarks.get(path))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Bookmark `line` of the open document, or remove its bookmark
+    ///
+    /// Bookmarks are kept by file, so an untitled document has to be saved
+    /// first.
+    fn toggle_bookmark(&mut self, line: usize) {
+        let Some(path) = self.current_file_path.clone() else {
+            self.status_message = String::from("Save the document to bookmark lines in it");
+            return;
+        };
+        let marks = self.session.bookmarks.entry(path.clone()).or_default();
+        let added = bookmarks::toggle(marks, &self.text_content, line);
+        if marks.is_empty() {
+            self.session.bookmarks.remove(&path);
+        }
+        self.write_session();
+        self.status_message = if added {
+            format!("Bookmarked line {}", line)
+        } else {
+            format!("Removed the bookmark on line {}", line)
+        };
+    }
+
+    /// Move the caret to the next or previous bookmark (F2 / Shift+F2),
+    /// wrapping around at the ends
+    fn jump_to_bookmark(&mut self, forward: bool) {
+        let current = editing::line_at_char(&self.text_content, self.editor_cursor);
+        match bookmarks::next(self.bookmarks(), current, forward) {
+            Some(line) => self.pending_jump = Some(line),
+            None => self.status_message = String::from("No bookmarks (Ctrl+B adds one)"),
+        }
+    }
+
+    /// Move the open document's bookmarks along with their lines if the
+    /// text changed since last time (see bookmarks::reanchor)
+    ///
+    /// The new places reach the session file with its next write.
+    fn refresh_bookmarks(&mut self) {
+        if self.bookmarks_generation == Some(self.edit_generation) {
+            return;
+        }
+        self.bookmarks_generation = Some(self.edit_generation);
+        let marks = self
+            .current_file_path
+            .as_ref()
+            .and_then(|path| self.session.bookmarks.get_mut(path));
+        if let Some(marks) = marks {
+            bookmarks::reanchor(marks, &self.text_content);
+        }
+    }
+
+    /// Move the caret to the next or previous chapter or scene tag
+    ///
+    /// At either end nothing moves, and the status bar says why.
+    fn jump_to_landmark(&mut self, landmark: Landmark, direction: Direction) {
+        let current = editing::line_at_char(&self.text_content, self.editor_cursor);
+        let target = parser::find_landmark(self.parsed_lines(), current, landmark, direction);
+
+        match target {
+            Some(line) => self.pending_jump = Some(line),
+            None => {
+                self.status_message = String::from(match (landmark, direction) {
+                    (Landmark::Chapter, Direction::Next) => "No more chapters",
+                    (Landmark::Chapter, Direction::Previous) => "No earlier chapters",
+                    (Landmark::Scene, Direction::Next) => "No more scenes",
+                    (Landmark::Scene, Direction::Previous) => "No earlier scenes",
+                });
+            }
+        }
+    }
+
+    /// Draw the Go to prompt: a line number or part of an act, chapter or
+    /// scene name, with what it matches listed underneath
+    ///
+    /// Up/Down pick from the list and Enter jumps to the picked one; a
+    /// click on one jumps straight there. A query that matches nothing
+    /// leaves the prompt open with a note in the status bar, so it can be
+    /// corrected.
+    fn go_to_window(&mut self, ctx: &egui::Context) {
+        let Some(mut query) = self.go_to_query.take() else {
+            return;
+        };
+        self.refresh_structure();
+        let targets = parser::go_to_targets(&query, self.parsed.lines(), &self.structure);
+        let shown = targets.len().min(GO_TO_LIST_LENGTH);
+        // Taken before the TextEdit sees them, which would move the caret
+        let (down, up) = ctx.input_mut(|i| {
+            (
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+            )
+        });
+        if down && self.go_to_selected + 1 < shown {
+            self.go_to_selected += 1;
+        }
+        if up {
+            self.go_to_selected = self.go_to_selected.saturating_sub(1);
+        }
+        self.go_to_selected = self.go_to_selected.min(shown.saturating_sub(1));
+
+        let mut go = false;
+        let mut clicked = None;
+        let mut open = true;
+
+        egui::Window::new("Go to")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 60.0))
+            .show(ctx, |ui| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut query)
+                        .hint_text("Line number, or part of a chapter or scene name")
+                        .desired_width(320.0),
+                );
+                // The prompt exists to be typed into
+                response.request_focus();
+                if response.changed() {
+                    self.go_to_selected = 0;
+                }
+                // Enter makes a single-line TextEdit give up focus
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    go = true;
+                }
+                for (index, target) in targets.iter().take(shown).enumerate() {
+                    let selected = index == self.go_to_selected;
+                    if ui.selectable_label(selected, &target.label).clicked() {
+                        clicked = Some(target.line);
+                    }
+                }
+                if targets.len() > shown {
+                    ui.weak(format!("and {} more", format_count(targets.len() - shown)));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Go").clicked() {
+                        go = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        open = false;
+                    }
+                });
+            });
+
+        let target = clicked.or_else(|| {
+            go.then(|| targets.get(self.go_to_selected).map(|t| t.line))
+                .flatten()
+        });
+        match target {
+            Some(line) => {
+                self.pending_jump = Some(line);
+                open = false;
+            }
+            None if go => {
+                self.status_message =
+                    format!("No line, chapter or scene matches \"{}\"", query.trim());
+            }
+            None => {}
+        }
+
+        if open {
+            self.go_to_query = Some(query);
+        } else {
+            self.go_to_selected = 0;
+        }
+    }
+
+    /// Grow (positive `delta`) or shrink the editor text, within limits
+    fn zoom(&mut self, delta: f32) {
+        let editor = &mut self.settings.editor;
+        editor.font_size =
+            (editor.font_size + delta).clamp(settings::MIN_FONT_SIZE, settings::MAX_FONT_SIZE);
+    }
+
+    /// Show the find bar with the cursor in its query field; with
+    /// `replace`, the Replace row too (Ctrl+H)
+    fn open_find_bar(&mut self, replace: bool) {
+        self.show_find_bar = true;
+        self.find.show_replace = replace;
+        self.focus_find_field = true;
+    }
+
+    /// Hide the find bar and give the keyboard back to the editor
+    fn close_find_bar(&mut self, ctx: &egui::Context) {
+        self.show_find_bar = false;
+        ctx.memory_mut(|m| m.request_focus(egui::Id::new(EDITOR_ID)));
+    }
+
+    /// Bring the find matches up to date with the text as it is right now
+    fn refresh_find(&mut self) {
+        self.find.refresh(&self.text_content, self.edit_generation);
+    }
+
+    /// Edit → Find Next (F3) / Find Previous (Shift+F3)
+    ///
+    /// Searches from the current match if the caret is still at its end,
+    /// and from the caret otherwise, wrapping around at either end. With
+    /// nothing to search for it just opens the find bar.
+    fn find_step(&mut self, forward: bool) {
+        if self.find.query.is_empty() {
+            self.open_find_bar(false);
+            return;
+        }
+        self.refresh_find();
+
+        let caret = editing::char_to_byte(&self.text_content, self.editor_cursor);
+        let found = match self.find.current_match() {
+            Some(current) if current.end == caret && forward => self.find.select_next(current.end),
+            Some(current) if current.end == caret => self.find.select_previous(current.start),
+            _ if forward => self.find.select_next(caret),
+            _ => self.find.select_previous(caret),
+        };
+
+        match found {
+            Some(range) => self.select_match(range),
+            None => self.status_message = self.find.summary(),
+        }
+    }
+
+    /// Select a match (a byte range) in the editor on the next frame
+    ///
+    /// The caret goes to the end of the match, which is how find_step
+    /// recognizes it as still selected.
+    fn select_match(&mut self, range: Range<usize>) {
+        let text = &self.text_content;
+        let start = editing::byte_to_char(text, range.start);
+        let end = start + text[range].chars().count();
+        self.pending_selection = Some(start..end);
+        self.editor_cursor = end;
+    }
+
+    /// The find bar's Replace button
+    ///
+    /// Replaces the current match if it's still selected and moves on to
+    /// the next one; otherwise it only finds, so the user sees what is
+    /// about to change before it does.
+    fn replace_current(&mut self) {
+        if !self.check_editable() {
+            return;
+        }
+        self.refresh_find();
+        let caret = editing::char_to_byte(&self.text_content, self.editor_cursor);
+        let Some(range) = self.find.current_match().filter(|m| m.end == caret) else {
+            self.find_step(true);
+            return;
+        };
+        let Ok(regex) = search::build_regex(&self.find.query, self.find.options) else {
+            return;
+        };
+
+        let text = &mut self.text_content;
+        let replacement = search::replacement_for(
+            text,
+            range.clone(),
+            &regex,
+            &self.find.replacement,
+            self.find.options,
+        );
+        self.history.checkpoint(text);
+        text.replace_range(range.clone(), &replacement);
+        self.history.checkpoint(text);
+        self.dirty = true;
+        self.text_edited();
+
+        // Carry on after the replacement, so replacing "a" with "aa"
+        // doesn't find its own output
+        let after = range.start + replacement.len();
+        self.find.current = None;
+        self.refresh_find();
+        match self.find.select_next(after) {
+            Some(next) => self.select_match(next),
+            None => {
+                let caret = editing::byte_to_char(&self.text_content, after);
+                self.pending_selection = Some(caret..caret);
+                self.editor_cursor = caret;
+            }
+        }
+    }
+
+    /// The find bar's Replace All button: one undo step for all of it
+    fn replace_all(&mut self) {
+        if !self.check_editable() || self.find.query.is_empty() {
+            return;
+        }
+        let regex = match search::build_regex(&self.find.query, self.find.options) {
+            Ok(regex) => regex,
+            Err(_) => {
+                self.status_message = self.find.summary();
+                return;
+            }
+        };
+
+        let text = &mut self.text_content;
+        let (replaced, count) =
+            search::replace_all(text, &regex, &self.find.replacement, self.find.options);
+        if count == 0 {
+            self.status_message = String::from("No matches");
+            return;
+        }
+
+        self.history.checkpoint(text);
+        *text = replaced;
+        self.history.checkpoint(text);
+
+        self.dirty = true;
+        self.text_edited();
+        self.find.current = None;
+        self.status_message = format!(
+            "Replaced {} {}",
+            format_count(count),
+            if count == 1 { "match" } else { "matches" }
+        );
+    }
+
+    /// Draw the find bar (Ctrl+F), with the Replace row under it for
+    /// Ctrl+H, between the menu bar and the editor
+    fn find_bar(&mut self, ctx: &egui::Context) {
+        self.refresh_find();
+
+        let options_before = self.find.options;
+        let mut query_changed = false;
+        let mut step = None;
+        let mut replace_one = false;
+        let mut replace_all = false;
+        let mut close = false;
+        let editable = !self.locked() && !matches!(self.file_io.state(), IoState::Loading(_));
+
+        egui::TopBottomPanel::top("find_bar").show(ctx, |ui| {
+            // Both rows start their fields at the same x
+            let label_width = 60.0;
+
+            ui.horizontal(|ui| {
+                ui.add_sized([label_width, 18.0], egui::Label::new("Find:"));
+                let field = ui.add(
+                    egui::TextEdit::singleline(&mut self.find.query)
+                        .id(egui::Id::new(FIND_FIELD_ID))
+                        .desired_width(240.0),
+                );
+                query_changed = field.changed();
+                if std::mem::take(&mut self.focus_find_field) {
+                    field.request_focus();
+                }
+                // A single-line TextEdit gives up focus on Enter; take it
+                // back so Enter can be pressed again for the next match
+                if field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    step = Some(!ui.input(|i| i.modifiers.shift));
+                    self.focus_find_field = true;
+                }
+
+                ui.checkbox(&mut self.find.options.case_sensitive, "Match case");
+                ui.checkbox(&mut self.find.options.whole_word, "Whole word");
+                ui.checkbox(&mut self.find.options.regex, "Regex");
+
+                if ui
+                    .button("Previous")
+                    .on_hover_text("Shift+F3 or Shift+Enter")
+                    .clicked()
+                {
+                    step = Some(false);
+                }
+                if ui.button("Next").on_hover_text("F3 or Enter").clicked() {
+                    step = Some(true);
+                }
+
+                if self.find.error.is_some() {
+                    ui.colored_label(ui.visuals().error_fg_color, self.find.summary());
+                } else if !self.find.query.is_empty() {
+                    ui.label(self.find.summary());
+                }
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("Close").on_hover_text("Esc").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+            if self.find.show_replace {
+                ui.horizontal(|ui| {
+                    ui.add_sized([label_width, 18.0], egui::Label::new("Replace:"));
+                    let field =
+                        egui::TextEdit::singleline(&mut self.find.replacement).desired_width(240.0);
+                    // In regex mode `$1` etc. bring back parts of the match
+                    let field = if self.find.options.regex {
+                        field.hint_text("$1 = group 1")
+                    } else {
+                        field
+                    };
+                    ui.add(field);
+
+                    if ui
+                        .add_enabled(editable, egui::Button::new("Replace"))
+                        .clicked()
+                    {
+                        replace_one = true;
+                    }
+                    if ui
+                        .add_enabled(editable, egui::Button::new("Replace All"))
+                        .clicked()
+                    {
+                        replace_all = true;
+                    }
+                });
+            }
+        });
+
+        // Search as you type: a new query or option moves straight to the
+        // first match from the caret
+        if query_changed || self.find.options != options_before {
+            self.find.current = None;
+            self.refresh_find();
+            let caret = editing::char_to_byte(&self.text_content, self.editor_cursor);
+            if let Some(range) = self.find.select_next(caret) {
+                self.select_match(range);
+            }
+        }
+
+        if let Some(forward) = step {
+            self.find_step(forward);
+        }
+        if replace_one {
+            self.replace_current();
+        }
+        if replace_all {
+            self.replace_all();
+        }
+        if close {
+            self.close_find_bar(ctx);
+        }
+    }
+
+    /// Whether the text can't be changed: View → Read-only is on, or the
+    /// document is a preview of an earlier version
+    fn locked(&self) -> bool {
+        self.read_only || self.preview.is_some()
+    }
+
+    /// Can the text be changed? If not, says why
+    fn check_editable(&mut self) -> bool {
+        if self.preview.is_some() {
+            self.status_message = String::from("A preview of an earlier version can't be edited");
+        } else if self.read_only {
+            self.status_message = String::from("Read-only (View → Read-only to edit)");
+        }
+        !self.locked()
+    }
+
+    /// Edit → Undo
+    fn undo(&mut self, ctx: &egui::Context) {
+        if !self.check_editable() {
+            return;
+        }
+        let restored = self.history.undo(&self.text_content);
+        match restored {
+            Some(restored) => self.apply_restored(ctx, restored),
+            None => self.status_message = String::from("Nothing to undo"),
+        }
+    }
+
+    /// Edit → Redo
+    fn redo(&mut self, ctx: &egui::Context) {
+        if !self.check_editable() {
+            return;
+        }
+        let restored = self.history.redo(&self.text_content);
+        match restored {
+            Some(restored) => self.apply_restored(ctx, restored),
+            None => self.status_message = String::from("Nothing to redo"),
+        }
+    }
+
+    /// Put text from the history into the editor and move the caret to
+    /// where the change happened
+    fn apply_restored(&mut self, ctx: &egui::Context, restored: Restored) {
+        // The TextEdit cursor counts chars, the history counts bytes
+        let caret = restored.text[..restored.caret].chars().count();
+        self.text_content = restored.text;
+        self.dirty = true;
+        self.text_edited();
+
+        let editor_id = egui::Id::new(EDITOR_ID);
+        let mut state = egui::TextEdit::load_state(ctx, editor_id).unwrap_or_default();
+        let cursor = egui::text::CCursor::new(caret);
+        state
+            .cursor
+            .set_char_range(Some(egui::text::CCursorRange::one(cursor)));
+        state.store(ctx, editor_id);
+    }
+
+    /// Carry out a File → Export entry: write the file, or open the
+    /// export's window first for PDF and Word
+    fn start_export(&mut self, exporter: Exporter) {
+        match exporter {
+            Exporter::Markdown => self.export_as(DocumentFormat::Markdown),
+            Exporter::PlainText => self.export_as(DocumentFormat::Plain),
+            Exporter::Html => self.export_html(),
+            Exporter::Fountain => self.export_as(DocumentFormat::Fountain),
+            Exporter::FinalDraft => self.export_fdx(),
+            Exporter::Pdf => {
+                self.pdf_title = self.suggested_title();
+                self.show_pdf_window = true;
+            }
+            Exporter::Docx => {
+                self.docx_title = self.suggested_title();
+                self.show_docx_window = true;
+            }
+        }
+    }
+
+    /// File → Export: convert the document and write it to a file of the
+    /// user's choosing
+    ///
+    /// The open document itself is untouched - no change to the current
+    /// path, dirty flag, or history.
+    fn export_as(&mut self, target: DocumentFormat) {
+        let Some(path) = self.pick_export_path(target.label(), target.default_extension()) else {
+            return;
+        };
+
+        let output = {
+            let text = &self.export_text();
+            match target {
+                DocumentFormat::Markdown => export::export_markdown(text),
+                DocumentFormat::Fountain => export::export_fountain(text),
+                _ => export::export_plaintext(text),
+            }
+        };
+        self.status_message =
+            match storage::save_text_file(&path, &output, storage::BackupSettings::OFF) {
+                Ok(()) => format!("Exported: {}", path.display()),
+                Err(e) => format!("Error exporting: {}", e),
+            };
+    }
+
+    /// File → Export → HTML: a standalone page for reading in a browser
+    ///
+    /// The page is titled after the document's file name (or its first
+    /// chapter, for an untitled document).
+    fn export_html(&mut self) {
+        let Some(path) = self.pick_export_path("HTML", "html") else {
+            return;
+        };
+
+        let title = self
+            .current_file_path
+            .as_deref()
+            .and_then(|p| p.file_stem())
+            .map(|s| s.to_string_lossy().into_owned());
+        let export = {
+            let text = &self.export_text_without_contents();
+            export::export_html(text, title.as_deref())
+        };
+        self.status_message =
+            match storage::save_text_file(&path, &export.html, storage::BackupSettings::OFF) {
+                Ok(()) => format!(
+                    "Exported: {} ({}, {} {})",
+                    path.display(),
+                    format_size(export.html.len() as u64),
+                    format_count(export.chapters),
+                    if export.chapters == 1 {
+                        "chapter"
+                    } else {
+                        "chapters"
+                    }
+                ),
+                Err(e) => format!("Error exporting: {}", e),
+            };
+    }
+
+    /// File → Export → Final Draft: the script as an .fdx file
+    fn export_fdx(&mut self) {
+        let Some(path) = self.pick_export_path("Final Draft", "fdx") else {
+            return;
+        };
+        let xml = fdx::export_fdx(&self.export_text());
+        self.status_message =
+            match storage::save_text_file(&path, &xml, storage::BackupSettings::OFF) {
+                Ok(()) => format!("Exported: {}", path.display()),
+                Err(e) => format!("Error exporting: {}", e),
+            };
+    }
+
+    /// Draw the File → Export → PDF window: fill in the title page, then
+    /// pick where the PDF goes
+    fn pdf_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_pdf_window;
+        let mut export = false;
+
+        egui::Window::new("Export PDF")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let options = &mut self.settings.pdf;
+                ui.checkbox(&mut options.title_page, "Start with a title page");
+                ui.add_enabled_ui(options.title_page, |ui| {
+                    egui::Grid::new("pdf_title_page")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("Title:");
+                            ui.text_edit_singleline(&mut self.pdf_title);
+                            ui.end_row();
+                            ui.label("Credit:");
+                            ui.text_edit_singleline(&mut options.credit);
+                            ui.end_row();
+                            ui.label("Author:");
+                            ui.text_edit_singleline(&mut options.author);
+                            ui.end_row();
+                            ui.label("Draft:");
+                            ui.text_edit_singleline(&mut options.draft);
+                            ui.end_row();
+                            ui.label("Contact:");
+                            ui.add(egui::TextEdit::multiline(&mut options.contact).desired_rows(3));
+                            ui.end_row();
+                        });
+                });
+                ui.label("Set in 12 pt Courier on US Letter, with screenplay margins.");
+                ui.add_space(8.0);
+                if ui.button("Export…").clicked() {
+                    export = true;
+                }
+            });
+
+        if export && self.export_pdf() {
+            open = false;
+        }
+        if self.show_pdf_window && !open {
+            self.save_settings();
+        }
+        self.show_pdf_window = open;
+    }
+
+    /// Write the PDF; returns false if the file dialog was cancelled
+    fn export_pdf(&mut self) -> bool {
+        let Some(path) = self.pick_export_path("PDF", "pdf") else {
+            return false;
+        };
+
+        let title = self.pdf_document_title();
+        let result =
+            pdf::export_pdf(&self.export_text(), &title, &self.settings.pdf).and_then(|export| {
+                storage::save_binary_file(&path, &export.bytes)?;
+                Ok(export)
+            });
+        self.status_message = match result {
+            Ok(export) => format!(
+                "Exported: {} ({}, {} {})",
+                path.display(),
+                format_size(export.bytes.len() as u64),
+                format_count(export.pages),
+                if export.pages == 1 { "page" } else { "pages" }
+            ),
+            Err(e) => format!("Error exporting: {}", e),
+        };
+        true
+    }
+
+    /// The title on the PDF's title page: the one typed in, or the
+    /// document's name
+    fn pdf_document_title(&self) -> String {
+        match self.pdf_title.trim() {
+            "" => self.document_name(),
+            title => title.to_string(),
+        }
+    }
+
+    /// File → Print… (Ctrl+P): open the print preview
+    fn open_print_window(&mut self) {
+        self.pdf_title = self.suggested_title();
+        self.show_print_window = true;
+    }
+
+    /// Draw the File → Print… window: the pages as the PDF export lays them
+    /// out, and a Print button
+    fn print_window(&mut self, ctx: &egui::Context) {
+        let title = self.pdf_document_title();
+        let key = (
+            self.edit_generation,
+            self.settings.export_comments,
+            title.clone(),
+            self.settings.pdf.clone(),
+        );
+        if self.print_pages_for.as_ref() != Some(&key) {
+            self.print_pages = pdf::layout(&self.export_text(), &title, &self.settings.pdf);
+            self.print_pages_for = Some(key);
+        }
+
+        let mut open = self.show_print_window;
+        let mut print = false;
+        egui::Window::new("Print")
+            .open(&mut open)
+            .collapsible(false)
+            .default_size([660.0, 760.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Print").clicked() {
+                        print = true;
+                    }
+                    ui.checkbox(&mut self.settings.pdf.title_page, "Title page")
+                        .on_hover_text("Its details are set in File → Export → PDF (screenplay)…");
+                });
+                ui.weak("12 pt Courier on US Letter, to the default printer. The blue box is the margins.");
+                ui.separator();
+                print::show(ui, &self.print_pages, self.settings.pdf.title_page);
+            });
+
+        if print {
+            self.print_document(&title);
+        }
+        if self.show_print_window && !open {
+            self.save_settings();
+            // The pages can be long; nothing needs them until it opens again
+            self.print_pages = Vec::new();
+            self.print_pages_for = None;
+        }
+        self.show_print_window = open;
+    }
+
+    /// Render the document as the PDF export would and send it to the
+    /// printer
+    fn print_document(&mut self, title: &str) {
+        let result = pdf::export_pdf(&self.export_text(), title, &self.settings.pdf)
+            .and_then(|export| print::print_pdf(&export.bytes, title).map(|()| export.pages));
+        self.status_message = match result {
+            Ok(pages) => format!(
+                "Sent {} {} to the printer",
+                format_count(pages),
+                if pages == 1 { "page" } else { "pages" }
+            ),
+            Err(e) => format!("Error printing: {}", e),
+        };
+    }
+
+    /// Draw the File → Export → Word (DOCX) window: fill in the cover page
+    /// and header, then pick where the file goes
+    fn docx_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_docx_window;
+        let mut export = false;
+
+        egui::Window::new("Export Word Document")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let options = &mut self.settings.docx;
+                egui::Grid::new("docx_manuscript")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Title:");
+                        ui.text_edit_singleline(&mut self.docx_title);
+                        ui.end_row();
+                        ui.label("Author:");
+                        ui.text_edit_singleline(&mut options.author);
+                        ui.end_row();
+                    });
+                ui.checkbox(&mut options.cover_page, "Start with a cover page");
+                ui.add_enabled_ui(options.cover_page, |ui| {
+                    ui.label("Contact details:");
+                    ui.add(egui::TextEdit::multiline(&mut options.contact).desired_rows(4));
+                });
+                ui.label("Standard manuscript format: 12 pt Times New Roman, double spaced.");
+                ui.add_space(8.0);
+                if ui.button("Export…").clicked() {
+                    export = true;
+                }
+            });
+
+        if export && self.export_docx() {
+            open = false;
+        }
+        if self.show_docx_window && !open {
+            self.save_settings();
+        }
+        self.show_docx_window = open;
+    }
+
+    /// Write the Word document; returns false if the file dialog was
+    /// cancelled
+    fn export_docx(&mut self) -> bool {
+        let Some(path) = self.pick_export_path("Word", "docx") else {
+            return false;
+        };
+
+        let title = match self.docx_title.trim() {
+            "" => self.document_name(),
+            title => title.to_string(),
+        };
+        let result =
+            docx::export_docx(&self.export_text(), &title, &self.settings.docx).and_then(|bytes| {
+                storage::save_binary_file(&path, &bytes)?;
+                Ok(bytes.len())
+            });
+        self.status_message = match result {
+            Ok(size) => format!(
+                "Exported: {} ({})",
+                path.display(),
+                format_size(size as u64)
+            ),
+            Err(e) => format!("Error exporting: {}", e),
+        };
+        true
+    }
+
+    /// The title the export windows suggest: the file name without its
+    /// extension, or nothing for an untitled document
+    fn suggested_title(&self) -> String {
+        self.current_file_path
+            .as_deref()
+            .and_then(|p| p.file_stem())
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// The document as the exporters get it: converted to BookScript from
+    /// its mode (a Fountain or Markdown document's headings become tags),
+    /// and without its comments unless Preferences → Export keeps them
+    fn export_text(&self) -> Cow<'_, str> {
+        self.prepare_export(&self.text_content)
+    }
+
+    /// export_text without its Tools → Table of Contents list, for an
+    /// exporter that makes linked contents of its own
+    fn export_text_without_contents(&self) -> Cow<'_, str> {
+        // Taken out first: the list is found by its comment markers
+        match toc::remove(&self.text_content) {
+            Cow::Borrowed(_) => self.export_text(),
+            Cow::Owned(text) => Cow::Owned(self.prepare_export(&text).into_owned()),
+        }
+    }
+
+    /// `text` made ready for the exporters (see export_text)
+    fn prepare_export<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let converted = parser::parser_for(self.active_format()).to_bookscript(text);
+        if self.settings.export_comments {
+            return converted;
+        }
+        match converted {
+            Cow::Borrowed(text) => parser::strip_comments(text),
+            Cow::Owned(text) => Cow::Owned(parser::strip_comments(&text).into_owned()),
+        }
+    }
+
+    /// Ask where an export should go, suggesting "<document name>.<extension>"
+    /// next to the open document
+    fn pick_export_path(&self, label: &str, extension: &str) -> Option<std::path::PathBuf> {
+        let stem = self
+            .current_file_path
+            .as_deref()
+            .and_then(|p| p.file_stem())
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "untitled".to_string());
+
+        dialogs::save_file(
+            &format!("Export as {}", label),
+            label,
+            &[extension],
+            &format!("{}.{}", stem, extension),
+            self.current_file_path.as_deref(),
+        )
+    }
+
+    /// Write the current preferences to a profile file
+    fn export_profile(&mut self, path: std::path::PathBuf) {
+        let result = profile::export_profile(&self.settings)
+            .and_then(|json| storage::save_text_file(&path, &json, storage::BackupSettings::OFF));
+        self.status_message = match result {
+            Ok(()) => format!("Profile exported: {}", path.display()),
+            Err(e) => format!("Error exporting profile: {}", e),
+        };
+    }
+
+    /// Tools → Register file types (see file_types.rs)
+    fn register_file_types(&mut self) {
+        self.status_message = match file_types::register() {
+            Ok(message) => message,
+            Err(e) => format!("Error registering file types: {:#}", e),
+        };
+    }
+
+    /// Read a profile file and open the import preview
+    ///
+    /// Nothing is applied until the user confirms in the preview window.
+    fn import_profile(&mut self, path: std::path::PathBuf) {
+        let result = storage::load_text_file(&path)
+            .and_then(|json| profile::prepare_import(&json, &self.settings));
+        match result {
+            Ok(import) => self.pending_import = Some(import),
+            Err(e) => self.status_message = format!("Error importing profile: {}", e),
+        }
+    }
+
+    /// Draw the import preview: what will change, what was skipped, and
+    /// Apply / Cancel buttons
+    fn import_preview_window(&mut self, ctx: &egui::Context) {
+        let Some(import) = &self.pending_import else {
+            return;
+        };
+
+        let mut apply = false;
+        let mut cancel = false;
+
+        egui::Window::new("Import profile")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                if import.changes.is_empty() {
+                    ui.label("The profile matches your current settings.");
+                } else {
+                    ui.label("These settings will change:");
+                    for change in &import.changes {
+                        ui.label(format!("  • {}", change));
+                    }
+                }
+
+                if !import.skipped.is_empty() {
+                    ui.separator();
+                    ui.label("These entries can't be used on this machine and will be skipped:");
+                    for skipped in &import.skipped {
+                        ui.label(format!("  • {}", skipped));
+                    }
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    // add_enabled greys the button out when there's nothing to apply
+                    if ui
+                        .add_enabled(!import.changes.is_empty(), egui::Button::new("Apply"))
+                        .clicked()
+                    {
+                        apply = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+
+        if cancel {
+            self.pending_import = None;
+        }
+
+        if apply {
+            // take() moves the value out of the Option, leaving None behind
+            let Some(import) = self.pending_import.take() else {
+                return;
+            };
+
+            // Back up the current profile FIRST - if that fails, don't apply
+            match profile::write_backup(&self.settings) {
+                Ok(backup) => {
+                    self.settings = import.settings;
+                    self.save_settings();
+                    self.status_message = format!(
+                        "Profile imported ({} changed, {} skipped). Previous profile saved to {}",
+                        import.changes.len(),
+                        import.skipped.len(),
+                        backup.display()
+                    );
+                }
+                Err(e) => {
+                    self.status_message =
+                        format!("Import cancelled, could not back up profile: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Recompute the character report if the text has changed since it
+    /// was last built
+    fn refresh_characters(&mut self) {
+        if self.characters_generation != Some(self.edit_generation) {
+            let report = parser::character_report(self.parsed_lines());
+            self.characters.set_rows(report);
+            self.characters_generation = Some(self.edit_generation);
+        }
+    }
+
+    /// Find the document's comments again if the text changed since last
+    /// time
+    fn refresh_comments(&mut self) {
+        if self.comments_generation != Some(self.edit_generation) {
+            self.comments = parser::find_comments(self.parsed_lines());
+            self.comments_generation = Some(self.edit_generation);
+        }
+    }
+
+    /// Rebuild the View → Formatted Preview blocks if the text changed
+    fn refresh_formatted_blocks(&mut self) {
+        if self.formatted_generation != Some(self.edit_generation) {
+            self.formatted_blocks = formatted::blocks(self.parsed_lines());
+            self.formatted_generation = Some(self.edit_generation);
+        }
+    }
+
+    /// Rebuild the Tools → Statistics report if the text (or the
+    /// exclude-tags option) changed
+    fn refresh_statistics_report(&mut self) {
+        self.refresh_structure();
+        if self.statistics_key != self.structure_key {
+            self.refresh_formatted_blocks();
+            self.statistics = statistics::report(
+                &self.structure,
+                &self.formatted_blocks,
+                &mut self.readability_cache,
+            );
+            self.statistics_key = self.structure_key;
+        }
+    }
+
+    /// Draw the Scene Inspector panel, writing the scene's tag again when
+    /// a field changes
+    fn scene_inspector(&mut self, ctx: &egui::Context) {
+        self.refresh_structure();
+        self.refresh_characters();
+        if !self.scene_draft_editing {
+            let line = editing::line_at_char(&self.text_content, self.editor_cursor);
+            self.scene_draft = self
+                .structure
+                .scenes
+                .iter()
+                .find(|s| s.line_start <= line && line <= s.line_end)
+                .map(SceneDraft::of);
+        }
+
+        let names: Vec<String> = self
+            .characters
+            .rows
+            .iter()
+            .map(|r| r.name.clone())
+            .collect();
+        let output = egui::SidePanel::right("scene_inspector")
+            .resizable(true)
+            .default_width(240.0)
+            .show(ctx, |ui| {
+                ui.heading("Scene");
+                ui.separator();
+                inspector::show(ui, self.scene_draft.as_mut(), &names)
+            })
+            .inner;
+        self.scene_draft_editing = output.editing;
+
+        let Some(draft) = self.scene_draft.as_ref().filter(|_| output.changed) else {
+            return;
+        };
+        let (line, tag) = (draft.line, draft.tag());
+        if !self.check_editable() {
+            return;
+        }
+        let Some(range) = editing::line_byte_range(&self.text_content, line) else {
+            return;
+        };
+        if self.text_content[range.clone()] != tag {
+            self.text_content.replace_range(range, &tag);
+            self.dirty = true;
+            self.text_edited();
+            self.history.edited(ctx.input(|i| i.time));
+        }
+    }
+
+    /// Draw View → Corkboard in the central panel and apply what was done
+    /// there
+    fn corkboard(&mut self, ui: &mut egui::Ui) {
+        self.refresh_structure();
+        match corkboard::show(ui, &self.structure) {
+            Some(CorkboardAction::Move(from, to)) => {
+                if !self.check_editable() {
+                    return;
+                }
+                let Some(moved) =
+                    corkboard::move_scene(&self.text_content, &self.structure, from, to)
+                else {
+                    return;
+                };
+                self.history.checkpoint(&self.text_content);
+                self.text_content = moved;
+                self.history.checkpoint(&self.text_content);
+                self.dirty = true;
+                self.text_edited();
+                self.status_message = format!(
+                    "Moved \"{}\" to scene {}",
+                    self.structure.scenes[from].description,
+                    to + 1
+                );
+            }
+            Some(CorkboardAction::Open(line)) => {
+                self.show_corkboard = false;
+                self.pending_jump = Some(line);
+            }
+            None => {}
+        }
+    }
+
+    /// Draw the Tools → Character report window
+    ///
+    /// Clicking a name jumps to their first cue.
+    fn characters_window(&mut self, ctx: &egui::Context) {
+        self.refresh_characters();
+
+        let mut open = self.show_characters_window;
+        let mut export = false;
+        egui::Window::new("Character report")
+            .open(&mut open)
+            .default_width(560.0)
+            .show(ctx, |ui| {
+                if let Some(line) = characters::show(ui, &mut self.characters) {
+                    self.pending_jump = Some(line);
+                }
+                ui.separator();
+                export = ui
+                    .add_enabled(
+                        !self.characters.rows.is_empty(),
+                        egui::Button::new("Export CSV…"),
+                    )
+                    .on_hover_text("Save the table, in its current order, for a spreadsheet")
+                    .clicked();
+            });
+        self.show_characters_window = open;
+        if export {
+            self.export_character_csv();
+        }
+    }
+
+    /// Save the character report as a CSV file next to the document
+    fn export_character_csv(&mut self) {
+        let stem = self
+            .current_file_path
+            .as_deref()
+            .and_then(|p| p.file_stem())
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "untitled".to_string());
+        let Some(path) = dialogs::save_file(
+            "Export Character Report",
+            "CSV",
+            &["csv"],
+            &format!("{} characters.csv", stem),
+            self.current_file_path.as_deref(),
+        ) else {
+            return;
+        };
+
+        let csv = characters::to_csv(&self.characters);
+        self.status_message =
+            match storage::save_text_file(&path, &csv, storage::BackupSettings::OFF) {
+                Ok(()) => format!(
+                    "Exported: {} ({} characters)",
+                    path.display(),
+                    format_count(self.characters.rows.len())
+                ),
+                Err(e) => format!("Error exporting: {}", e),
+            };
+    }
+
+    /// Draw the Tools → Statistics window
+    ///
+    /// Clicking a chapter or scene in the chart jumps to it.
+    fn statistics_window(&mut self, ctx: &egui::Context) {
+        self.refresh_statistics_report();
+
+        let mut open = self.show_statistics_window;
+        egui::Window::new("Statistics")
+            .open(&mut open)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                let breakdown = &mut self.statistics_breakdown;
+                if let Some(line) = statistics::show(ui, &self.statistics, breakdown) {
+                    self.pending_jump = Some(line);
+                }
+            });
+        self.show_statistics_window = open;
+    }
+
+    /// Draw the Tools → Style Check window
+    ///
+    /// Clicking an occurrence selects it in the editor. The options are
+    /// saved with the other preferences when the window closes.
+    fn style_window(&mut self, ctx: &egui::Context) {
+        self.parsed();
+        self.style_check.refresh(
+            &self.text_content,
+            self.edit_generation,
+            self.parsed.lines(),
+            &self.settings.style,
+        );
+
+        let mut open = self.show_style_window;
+        let mut clicked = None;
+        egui::Window::new("Style check")
+            .open(&mut open)
+            .default_width(460.0)
+            .show(ctx, |ui| {
+                clicked = style::show(ui, &mut self.style_check, &mut self.settings.style);
+            });
+        if let Some(range) = clicked {
+            self.select_match(range);
+        }
+
+        if self.show_style_window && !open {
+            self.style_check.close();
+            self.save_settings();
+        }
+        self.show_style_window = open;
+    }
+
+    /// Draw the Define / Synonyms popup for a right-clicked word
+    ///
+    /// WordNet is loaded in the background the first time; clicking a
+    /// synonym replaces the word with it.
+    fn lookup_window(&mut self, ctx: &egui::Context) {
+        self.load_wordnet();
+        let Some(lookup) = &mut self.lookup else {
+            return;
+        };
+
+        let mut open = true;
+        let mut picked = None;
+        let mut choose_folder = false;
+        egui::Window::new(format!("“{}”", lookup.word))
+            .id(egui::Id::new("lookup_window"))
+            .open(&mut open)
+            .collapsible(false)
+            .default_pos(lookup.pos)
+            .default_width(360.0)
+            .show(ctx, |ui| match &self.wordnet {
+                Some(Ok(wordnet)) => {
+                    picked = thesaurus::show(ui, lookup, wordnet);
+                    if wordnet.is_small() {
+                        ui.separator();
+                        ui.label(
+                            egui::RichText::new(
+                                "Using the small built-in dictionary. For every word, \
+                                 download WordNet 3.x from wordnet.princeton.edu and \
+                                 choose its folder.",
+                            )
+                            .weak(),
+                        );
+                        choose_folder = ui.button("Choose WordNet Folder…").clicked();
+                    }
+                }
+                Some(Err(e)) => {
+                    ui.label(e);
+                    ui.label(
+                        "Define and Synonyms need WordNet's data files. Download \
+                         WordNet 3.x from wordnet.princeton.edu and choose its folder.",
+                    );
+                    choose_folder = ui.button("Choose WordNet Folder…").clicked();
+                }
+                None => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Loading the dictionary…");
+                    });
+                }
+            });
+
+        if let Some(word) = picked {
+            self.replace_looked_up(ctx, word);
+        } else if !open {
+            self.lookup = None;
+        }
+        if choose_folder {
+            let current = self.settings.wordnet_dir.clone();
+            if let Some(dir) = dialogs::pick_folder("Choose WordNet Folder", current.as_deref()) {
+                self.settings.wordnet_dir = Some(dir);
+                self.save_settings();
+                self.wordnet = None;
+            }
+        }
+    }
+
+    /// Start loading WordNet if it isn't loaded or loading, and pick up the
+    /// result once the thread has it
+    fn load_wordnet(&mut self) {
+        if let Some(receiver) = &self.wordnet_loading {
+            // try_recv never blocks; Empty means still loading
+            match receiver.try_recv() {
+                Ok(result) => {
+                    self.wordnet = Some(result.map_err(|e| format!("{:#}", e)));
+                    self.wordnet_loading = None;
+                }
+                Err(mpsc::TryRecvError::Empty) => {}
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.wordnet = Some(Err(String::from("Loading the dictionary failed")));
+                    self.wordnet_loading = None;
+                }
+            }
+            return;
+        }
+        if self.wordnet.is_some() {
+            return;
+        }
+        // Only the default folder falls back to the built-in selection; a
+        // chosen one with nothing in it should say so
+        let (dir, chosen) = match self.settings.wordnet_dir.clone() {
+            Some(dir) => (Some(dir), true),
+            None => (storage::get_wordnet_dir().ok(), false),
+        };
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let result = match dir {
+                Some(dir) if chosen || thesaurus::has_data(&dir) => thesaurus::load(&dir),
+                _ => Ok(thesaurus::load_small()),
+            };
+            // The popup may be gone by the time this finishes; that's fine
+            let _ = sender.send(result);
+        });
+        self.wordnet_loading = Some(receiver);
+    }
+
+    /// Put `word` in place of the looked-up word, as its own undo step
+    fn replace_looked_up(&mut self, ctx: &egui::Context, word: String) {
+        let Some(lookup) = self.lookup.take() else {
+            return;
+        };
+        if !self.check_editable() {
+            return;
+        }
+        let current: String = self
+            .text_content
+            .chars()
+            .skip(lookup.range.start)
+            .take(lookup.range.len())
+            .collect();
+        if current != lookup.word {
+            self.status_message = format!("“{}” has changed since it was looked up", lookup.word);
+            return;
+        }
+
+        let command = EditCommand {
+            caret: lookup.range.start + word.chars().count(),
+            range: lookup.range,
+            insert: word,
+        };
+        self.history.checkpoint(&self.text_content);
+        command.apply(&mut self.text_content);
+        self.history.checkpoint(&self.text_content);
+        self.dirty = true;
+        self.text_edited();
+
+        let editor_id = egui::Id::new(EDITOR_ID);
+        let mut state = egui::TextEdit::load_state(ctx, editor_id).unwrap_or_default();
+        state
+            .cursor
+            .set_char_range(Some(egui::text::CCursorRange::one(
+                egui::text::CCursor::new(command.caret),
+            )));
+        state.store(ctx, editor_id);
+        ctx.memory_mut(|m| m.request_focus(editor_id));
+    }
+
+    /// Tools → Read Aloud: start reading the selection, or else the scene
+    /// (failing that the chapter, failing that the document) the caret is in
+    fn start_read_aloud(&mut self, ctx: &egui::Context) {
+        self.refresh_structure();
+        let text = &self.text_content;
+        let selection = egui::TextEdit::load_state(ctx, egui::Id::new(EDITOR_ID))
+            .and_then(|state| state.cursor.char_range())
+            .filter(|range| range.primary != range.secondary)
+            .map(|range| {
+                let (a, b) = (range.primary.index, range.secondary.index);
+                editing::char_to_byte(text, a.min(b))..editing::char_to_byte(text, a.max(b))
+            });
+
+        let line = editing::line_at_char(text, self.editor_cursor);
+        let lines = |start: usize, end: usize| {
+            let start = editing::line_byte_range(text, start).map_or(0, |r| r.start);
+            let end = editing::line_byte_range(text, end).map_or(text.len(), |r| r.end);
+            start..end
+        };
+        let scene = self
+            .structure
+            .scenes
+            .iter()
+            .find(|s| (s.line_start..=s.line_end).contains(&line));
+        let chapter = self
+            .structure
+            .chapters
+            .iter()
+            .find(|c| (c.line_start..=c.line_end).contains(&line));
+        let (range, what) = if let Some(range) = selection {
+            (range, String::from("the selection"))
+        } else if let Some(scene) = scene {
+            (
+                lines(scene.line_start, scene.line_end),
+                format!("scene “{}”", scene.description),
+            )
+        } else if let Some(chapter) = chapter {
+            (
+                lines(chapter.line_start, chapter.line_end),
+                format!("chapter “{}”", chapter.title),
+            )
+        } else {
+            (0..text.len(), String::from("the document"))
+        };
+
+        let words_per_minute = self.settings.read_aloud.words_per_minute;
+        let generation = self.edit_generation;
+        self.status_message = match ReadAloud::start(text, range, generation, words_per_minute) {
+            Ok(Some(reader)) => {
+                self.read_aloud = Some(reader);
+                format!("Reading {} aloud", what)
+            }
+            Ok(None) => format!("Nothing to read in {}", what),
+            Err(e) => format!("Error: {}", e),
+        };
+    }
+
+    /// Stop reading aloud and close the toolbar; the speed is saved
+    fn stop_read_aloud(&mut self) {
+        // Dropping the reader stops the speech program
+        self.read_aloud = None;
+        self.save_settings();
+    }
+
+    /// Draw the Read Aloud toolbar above the editor, and move the reading
+    /// on when a sentence is done
+    fn read_aloud_bar(&mut self, ctx: &egui::Context) {
+        let Some(reader) = &mut self.read_aloud else {
+            return;
+        };
+        let words_per_minute = self.settings.read_aloud.words_per_minute;
+        let progress = reader.poll(words_per_minute);
+
+        let action = egui::TopBottomPanel::top("read_aloud_bar")
+            .show(ctx, |ui| {
+                read_aloud::toolbar(ui, reader, &mut self.settings.read_aloud)
+            })
+            .inner;
+        let result = match action {
+            Some(ToolbarAction::Play) => reader.play(words_per_minute),
+            Some(ToolbarAction::Pause) => {
+                reader.pause();
+                Ok(())
+            }
+            Some(ToolbarAction::Skip(by)) => reader.skip(by, words_per_minute),
+            Some(ToolbarAction::Stop) | None => Ok(()),
+        };
+
+        match (progress, result) {
+            (Err(e), _) | (_, Err(e)) => {
+                self.status_message = format!("Error reading aloud: {}", e);
+                self.stop_read_aloud();
+            }
+            (Ok(Progress::Finished), _) => {
+                self.status_message = String::from("Finished reading aloud");
+                self.stop_read_aloud();
+            }
+            _ if action == Some(ToolbarAction::Stop) => self.stop_read_aloud(),
+            // The speech program gives no events; look again shortly
+            _ => ctx.request_repaint_after(std::time::Duration::from_millis(100)),
+        }
+    }
+
+    /// Draw the Tools → Writing Goal window
+    ///
+    /// The goal is saved with the other preferences when the window closes.
+    fn goal_window(&mut self, ctx: &egui::Context) {
+        let written = self.goal_progress();
+        let mut open = self.show_goal_window;
+
+        egui::Window::new("Writing goal")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let goal = &mut self.settings.goal;
+                ui.horizontal(|ui| {
+                    ui.label("Write");
+                    ui.add(
+                        egui::DragValue::new(&mut goal.words)
+                            .range(0..=goals::MAX_GOAL_WORDS)
+                            .suffix(" words"),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut goal.period, GoalPeriod::Session, "this session");
+                    ui.radio_value(&mut goal.period, GoalPeriod::Daily, "today");
+                });
+                ui.label("0 words turns the goal off.");
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Manuscript target");
+                    ui.add(
+                        egui::DragValue::new(&mut goal.manuscript_words)
+                            .range(0..=goals::MAX_MANUSCRIPT_WORDS)
+                            .speed(100)
+                            .suffix(" words"),
+                    );
+                });
+                ui.label("The open project's own target is used instead, if it has one.");
+
+                ui.separator();
+                let period = match goal.period {
+                    GoalPeriod::Session => "this session",
+                    GoalPeriod::Daily => "today",
+                };
+                match goal.fraction(written) {
+                    Some(fraction) => {
+                        ui.add(egui::ProgressBar::new(fraction).show_percentage());
+                        ui.label(format!(
+                            "{} of {} words {}",
+                            format_delta(written),
+                            format_count(goal.words),
+                            period
+                        ));
+                    }
+                    None => {
+                        ui.label(format!("{} words {}", format_delta(written), period));
+                    }
+                }
+            });
+
+        if self.show_goal_window && !open {
+            self.save_settings();
+        }
+        self.show_goal_window = open;
+    }
+
+    /// Draw the Tools → Sprint window: start a timed sprint, watch it run,
+    /// and see how it (and earlier ones) went
+    fn sprint_window(&mut self, ctx: &egui::Context) {
+        let now = ctx.input(|i| i.time);
+        let mut open = self.show_sprint_window;
+        let mut start = None;
+        let mut stop = false;
+
+        egui::Window::new("Sprint")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                if let Some(sprint) = &self.sprint {
+                    let elapsed = sprint.elapsed(now) / (f64::from(sprint.minutes) * 60.0);
+                    ui.heading(goals::format_clock(sprint.remaining(now)));
+                    ui.add(egui::ProgressBar::new(elapsed as f32));
+                    ui.label(format!(
+                        "{} words so far",
+                        format_delta(sprint.words(self.writing_session.delta()))
+                    ));
+                    if ui.button("Stop").clicked() {
+                        stop = true;
+                    }
+                } else {
+                    ui.label("Write as much as you can before the time runs out.");
+                    ui.horizontal(|ui| {
+                        for minutes in [15, 25] {
+                            if ui.button(format!("{} minutes", minutes)).clicked() {
+                                start = Some(minutes);
+                            }
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        let minutes = &mut self.settings.goal.sprint_minutes;
+                        ui.add(
+                            egui::DragValue::new(minutes)
+                                .range(goals::MIN_SPRINT_MINUTES..=goals::MAX_SPRINT_MINUTES)
+                                .suffix(" minutes"),
+                        );
+                        if ui.button("Start").clicked() {
+                            start = Some(*minutes);
+                        }
+                    });
+                }
+
+                if self.sprint_history.is_empty() {
+                    return;
+                }
+                ui.separator();
+                if self.sprint.is_none() {
+                    let last = &self.sprint_history[0];
+                    ui.label(format!(
+                        "Last sprint: {} words in {} ({:.0} words/min)",
+                        format_delta(last.words),
+                        goals::format_clock(last.seconds as f64),
+                        goals::words_per_minute(last.words, last.seconds)
+                    ));
+                }
+                if let Some(best) = self.sprint_history.iter().max_by(|a, b| {
+                    goals::words_per_minute(a.words, a.seconds)
+                        .total_cmp(&goals::words_per_minute(b.words, b.seconds))
+                }) {
+                    ui.weak(format!(
+                        "Best: {:.0} words/min ({} words, {})",
+                        goals::words_per_minute(best.words, best.seconds),
+                        format_delta(best.words),
+                        best.started.format("%Y-%m-%d")
+                    ));
+                }
+
+                ui.add_space(4.0);
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("sprint_history")
+                            .num_columns(3)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for sprint in &self.sprint_history {
+                                    ui.label(
+                                        sprint.started.format("%a %Y-%m-%d %H:%M").to_string(),
+                                    );
+                                    ui.label(goals::format_clock(sprint.seconds as f64));
+                                    ui.label(format!("{} words", format_delta(sprint.words)));
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+
+        if let Some(minutes) = start {
+            self.start_sprint(minutes, now);
+        }
+        if let Some(sprint) = self.sprint.filter(|_| stop) {
+            self.finish_sprint(sprint.elapsed(now));
+        }
+        if self.show_sprint_window && !open {
+            self.save_settings();
+        }
+        self.show_sprint_window = open;
+    }
+
+    /// Draw the Edit → Clean Up Document window: pick the fixes, then apply
+    /// them to the whole document as a single undo step
+    fn cleanup_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_cleanup_window;
+        let mut apply = false;
+
+        egui::Window::new("Clean up document")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let options = &mut self.settings.cleanup;
+                ui.checkbox(
+                    &mut options.normalize_punctuation,
+                    "Make quotes, dashes and ellipses consistent",
+                );
+                ui.add_enabled_ui(options.normalize_punctuation, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add_space(20.0);
+                        ui.radio_value(
+                            &mut options.punctuation_style,
+                            PunctuationStyle::Smart,
+                            "Smart (“ ” ‘ ’ — …)",
+                        );
+                        ui.radio_value(
+                            &mut options.punctuation_style,
+                            PunctuationStyle::Straight,
+                            "Straight (\" ' -- ...)",
+                        );
+                    });
+                });
+                ui.checkbox(
+                    &mut options.normalize_spaces,
+                    "Replace non-breaking spaces with normal spaces",
+                );
+                ui.checkbox(
+                    &mut options.collapse_blank_lines,
+                    "Collapse runs of blank lines into one",
+                );
+                ui.checkbox(
+                    &mut options.strip_trailing_whitespace,
+                    "Remove trailing spaces",
+                );
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut options.tabs_to_spaces, "Convert tabs to spaces, width");
+                    ui.add_enabled(
+                        options.tabs_to_spaces,
+                        egui::DragValue::new(&mut options.tab_width)
+                            .range(1..=text_utils::MAX_TAB_WIDTH),
+                    );
+                });
+                ui.label("Tag lines and character cues keep their punctuation.");
+
+                ui.separator();
+                ui.checkbox(&mut options.clean_on_paste, "Also clean up pasted text");
+                ui.add_space(8.0);
+                if ui.button("Clean up").clicked() {
+                    apply = true;
+                }
+            });
+
+        if apply {
+            self.clean_up_document();
+            open = false;
+        }
+        if self.show_cleanup_window && !open {
+            self.save_settings();
+        }
+        self.show_cleanup_window = open;
+    }
+
+    /// Tools → Table of Contents: list the document's acts, chapters and
+    /// scenes at the caret, or update the list it already has (see toc)
+    fn insert_table_of_contents(&mut self, references: References) {
+        if !self.check_editable() {
+            return;
+        }
+        let line = editing::line_at_char(&self.text_content, self.editor_cursor) - 1;
+        let Some(updated) = toc::update(&self.text_content, line, references) else {
+            self.status_message = String::from("No acts, chapters or scenes to list");
+            return;
+        };
+        if updated == self.text_content {
+            self.status_message = String::from("Table of contents is up to date");
+            return;
+        }
+
+        let entries = toc::find(&updated).map_or(0, |range| range.len() - 3);
+        self.history.checkpoint(&self.text_content);
+        self.text_content = updated;
+        self.history.checkpoint(&self.text_content);
+        self.dirty = true;
+        self.text_edited();
+        self.status_message = format!(
+            "Table of contents: {} {}",
+            format_count(entries),
+            if entries == 1 { "entry" } else { "entries" }
+        );
+    }
+
+    /// Apply the Clean Up Document fixes to the whole text
+    fn clean_up_document(&mut self) {
+        if !self.check_editable() {
+            return;
+        }
+        let text = &mut self.text_content;
+        let cleaned = text_utils::clean_up(text, &self.settings.cleanup);
+        if cleaned.lines_changed == 0 {
+            self.status_message = String::from("Nothing to clean up");
+            return;
+        }
+
+        // One undo step takes the whole cleanup back
+        self.history.checkpoint(text);
+        *text = cleaned.text;
+        self.history.checkpoint(text);
+
+        self.dirty = true;
+        self.text_edited();
+        self.status_message = format!(
+            "Cleaned: {} {} changed",
+            format_count(cleaned.lines_changed),
+            if cleaned.lines_changed == 1 {
+                "line"
+            } else {
+                "lines"
+            }
+        );
+    }
+
+    /// Re-read the progress history for the Progress History window
+    ///
+    /// Today's row comes from the running count rather than the file, which
+    /// can be up to PROGRESS_SAVE_INTERVAL behind.
+    fn refresh_progress_history(&mut self) {
+        let history = match self.progress_path.as_deref().map(storage::load_progress) {
+            Some(Ok(history)) => history,
+            Some(Err(e)) => {
+                self.status_message = format!("Error reading writing progress: {}", e);
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+        let today = self.daily_tally.date();
+        self.progress_entries = storage::recent_progress(&history, today, PROGRESS_HISTORY_DAYS);
+        if let Some(entry) = self.progress_entries.first_mut() {
+            entry.words = self.daily_tally.total(self.writing_session.delta());
+        }
+    }
+
+    /// Draw the Tools → Progress History window: one row per day, with a
+    /// bar scaled to the best day shown
+    fn progress_window(&mut self, ctx: &egui::Context) {
+        let best = self
+            .progress_entries
+            .iter()
+            .map(|d| d.words)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        let mut open = self.show_progress_window;
+        egui::Window::new("Progress history")
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                if ui.button("Refresh").clicked() {
+                    self.refresh_progress_history();
+                }
+                ui.separator();
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("progress_history")
+                        .num_columns(3)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for day in &self.progress_entries {
+                                ui.label(day.date.format("%a %Y-%m-%d").to_string());
+                                ui.label(format_delta(day.words));
+                                ui.add(
+                                    egui::ProgressBar::new(day.words.max(0) as f32 / best as f32)
+                                        .desired_width(160.0),
+                                );
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+        self.show_progress_window = open;
+    }
+
+    /// Re-read the current document's snapshots (or backups) for the
+    /// Version History window
+    fn refresh_snapshots(&mut self) {
+        self.snapshot_preview = None;
+        let listed = match self.history_source {
+            HistorySource::Snapshots => storage::snapshot_dir(self.current_file_path.as_deref())
+                .and_then(|dir| storage::list_snapshots_in(&dir)),
+            // An untitled document has never been saved, so has no backups
+            HistorySource::Backups => Ok(self
+                .current_file_path
+                .as_deref()
+                .map(storage::list_backups)
+                .unwrap_or_default()),
+        };
+        match listed {
+            Ok(snapshots) => {
+                // An unreadable snapshot still gets listed, with 0 words
+                self.snapshot_entries = snapshots
+                    .into_iter()
+                    .map(|snapshot| {
+                        let words = self
+                            .load_snapshot(&snapshot)
+                            .map(|text| parser::count_stats(&text, false).words)
+                            .unwrap_or(0);
+                        (snapshot, words)
+                    })
+                    .collect();
+            }
+            Err(e) => {
+                self.snapshot_entries.clear();
+                self.status_message = format!("Error reading version history: {}", e);
+            }
+        }
+    }
+
+    /// A listed snapshot's text; a backup of a password-protected document
+    /// is decrypted with its password
+    fn load_snapshot(&self, snapshot: &storage::Snapshot) -> anyhow::Result<String> {
+        if snapshot.backup {
+            storage::load_backup(&snapshot.path, self.password.as_deref())
+        } else {
+            storage::load_text_file(&snapshot.path)
+        }
+    }
+
+    /// Draw the File → Version History (or Restore from Backup) window
+    ///
+    /// The list on the left selects a snapshot; its text is shown read-only
+    /// on the right, with a button to bring it back into the editor.
+    fn snapshots_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_snapshots_window;
+        let mut select: Option<usize> = None;
+        let mut restore = false;
+        let mut compare = false;
+        let title = match self.history_source {
+            HistorySource::Snapshots => "Version History",
+            HistorySource::Backups => "Restore from Backup",
+        };
+
+        // The id keeps the window's position when the title changes
+        egui::Window::new(title)
+            .id(egui::Id::new("version_history"))
+            .open(&mut open)
+            .default_width(720.0)
+            .default_height(420.0)
+            .show(ctx, |ui| {
+                if self.snapshot_entries.is_empty() {
+                    match self.history_source {
+                        HistorySource::Snapshots => {
+                            ui.label("No snapshots of this document yet.");
+                            ui.label("They are taken when you save and every few minutes while you write.");
+                        }
+                        HistorySource::Backups => {
+                            ui.label("No backups of this document yet.");
+                            ui.label("Saving keeps the previous version in a \"backups\" folder next to the file (Preferences → Backups).");
+                        }
+                    }
+                    return;
+                }
+
+                ui.horizontal_top(|ui| {
+                    egui::ScrollArea::vertical()
+                        .id_salt("snapshot_list")
+                        .max_width(240.0)
+                        .max_height(360.0)
+                        .show(ui, |ui| {
+                            let selected = self.snapshot_preview.as_ref().map(|(i, _)| *i);
+                            for (index, (snapshot, words)) in
+                                self.snapshot_entries.iter().enumerate()
+                            {
+                                let label = format!(
+                                    "{} · {} words",
+                                    format_timestamp(snapshot.created),
+                                    format_count(*words)
+                                );
+                                if ui
+                                    .selectable_label(selected == Some(index), label)
+                                    .clicked()
+                                {
+                                    select = Some(index);
+                                }
+                            }
+                        });
+
+                    ui.separator();
+
+                    ui.vertical(|ui| match &self.snapshot_preview {
+                        Some((_, text)) => {
+                            ui.horizontal(|ui| {
+                                if ui.button("Restore this version").clicked() {
+                                    restore = true;
+                                }
+                                if ui.button("Compare with current").clicked() {
+                                    compare = true;
+                                }
+                            });
+                            ui.label("Restoring replaces the editor text; the file on disk is unchanged until you save.");
+                            egui::ScrollArea::vertical()
+                                .id_salt("snapshot_preview")
+                                .max_height(320.0)
+                                .show(ui, |ui| {
+                                    // A &str (not &mut String) makes the TextEdit read-only
+                                    ui.add(
+                                        egui::TextEdit::multiline(&mut text.as_str())
+                                            .desired_width(f32::INFINITY)
+                                            .font(egui::TextStyle::Monospace),
+                                    );
+                                });
+                        }
+                        None => {
+                            ui.label("Select a snapshot to preview it.");
+                        }
+                    });
+                });
+            });
+
+        self.show_snapshots_window = open;
+
+        if let Some(index) = select {
+            match self.load_snapshot(&self.snapshot_entries[index].0) {
+                Ok(text) => self.snapshot_preview = Some((index, text)),
+                Err(e) => self.status_message = format!("Error reading snapshot: {}", e),
+            }
+        }
+
+        if compare {
+            if let Some((index, text)) = &self.snapshot_preview {
+                let snapshot = &self.snapshot_entries[*index].0;
+                let kind = if snapshot.backup { "Backup" } else { "Version" };
+                let title = format!("{} from {}", kind, format_timestamp(snapshot.created));
+                self.comparison = Some((title, text.clone()));
+                self.comparison_diff = None;
+            }
+        }
+
+        if restore && self.check_editable() {
+            if let Some((index, text)) = self.snapshot_preview.take() {
+                let snapshot = &self.snapshot_entries[index].0;
+                let kind = if snapshot.backup { "backup" } else { "version" };
+                let created = snapshot.created;
+                let current = &mut self.text_content;
+                // Undo can take the editor back to what it held before
+                self.history.checkpoint(current);
+                *current = text;
+                self.history.checkpoint(current);
+
+                self.dirty = true;
+                self.text_edited();
+                self.show_snapshots_window = false;
+                self.status_message =
+                    format!("Restored {} from {}", kind, format_timestamp(created));
+            }
+        }
+    }
+
+    /// Where the open document's named snapshots go: the open project's
+    /// snapshot folder, and the document as the project names it
+    ///
+    /// None (with the reason in the status bar) without a project, for an
+    /// untitled document, or for a password-protected one (a snapshot is a
+    /// plain copy).
+    fn named_snapshot_target(&mut self) -> Option<(std::path::PathBuf, String)> {
+        if self.password.is_some() {
+            self.status_message =
+                String::from("Snapshots aren't kept for password-protected documents");
+            return None;
+        }
+        let Some(project) = &self.project else {
+            self.status_message =
+                String::from("Snapshots are kept with a project (File → Open Project…)");
+            return None;
+        };
+        let Some(path) = &self.current_file_path else {
+            self.status_message = String::from("Save the document before taking a snapshot");
+            return None;
+        };
+        Some((
+            storage::named_snapshot_dir(&project.path),
+            project.entry_for(path),
+        ))
+    }
+
+    /// File → Take Snapshot…: ask for a name, suggesting the date
+    fn open_snapshot_prompt(&mut self) {
+        if self.named_snapshot_target().is_some() {
+            let now = chrono::Local::now().format("%Y-%m-%d %H:%M");
+            self.snapshot_name = Some(format!("Snapshot {}", now));
+        }
+    }
+
+    /// Draw the Take Snapshot prompt: a name, then Enter
+    fn snapshot_prompt(&mut self, ctx: &egui::Context) {
+        let Some(mut name) = self.snapshot_name.take() else {
+            return;
+        };
+        let mut take = false;
+        let mut open = true;
+
+        egui::Window::new("Take Snapshot")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 60.0))
+            .show(ctx, |ui| {
+                ui.label(
+                    "A named copy of this document, kept with the project until you delete it.",
+                );
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut name)
+                        .hint_text("Name, e.g. \"First draft done\"")
+                        .desired_width(300.0),
+                );
+                response.request_focus();
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    take = true;
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Take Snapshot").clicked() {
+                        take = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        open = false;
+                    }
+                });
+            });
+
+        if take && !name.trim().is_empty() {
+            self.take_named_snapshot(name.trim());
+            open = false;
+        }
+        if open {
+            self.snapshot_name = Some(name);
+        }
+    }
+
+    /// Copy the editor text into the project's snapshot folder as `name`
+    fn take_named_snapshot(&mut self, name: &str) {
+        let Some((dir, document)) = self.named_snapshot_target() else {
+            return;
+        };
+        let result = storage::take_named_snapshot(
+            &dir,
+            &document,
+            name,
+            &self.text_content,
+            storage::unix_now(),
+        );
+        self.status_message = match result {
+            Ok(_) => format!("Took snapshot \"{}\"", name),
+            Err(e) => format!("Error taking snapshot: {}", e),
+        };
+        // Read again on the panel's next frame
+        self.named_snapshots_for = None;
+    }
+
+    /// Re-read the open document's named snapshots if the project or the
+    /// document changed (or a snapshot was taken or deleted)
+    fn refresh_named_snapshots(&mut self) {
+        let key = self
+            .project
+            .as_ref()
+            .map(|p| p.path.clone())
+            .zip(self.current_file_path.clone());
+        if key.is_some() && self.named_snapshots_for == key {
+            return;
+        }
+        self.named_snapshots.clear();
+        self.named_snapshots_for = key;
+        let (Some(project), Some(path)) = (&self.project, &self.current_file_path) else {
+            return;
+        };
+        let document = project.entry_for(path);
+        match storage::list_named_snapshots(&storage::named_snapshot_dir(&project.path)) {
+            Ok(snapshots) => {
+                self.named_snapshots = snapshots
+                    .into_iter()
+                    .rev()
+                    .filter(|s| s.document == document)
+                    .collect();
+            }
+            Err(e) => self.status_message = format!("Error reading snapshots: {}", e),
+        }
+    }
+
+    /// Draw the View → Snapshots panel: the open document's named
+    /// snapshots, each with Restore, Compare and Delete
+    fn named_snapshots_panel(&mut self, ctx: &egui::Context) {
+        self.refresh_named_snapshots();
+
+        let mut action: Option<(usize, SnapshotAction)> = None;
+        let mut take = false;
+        egui::SidePanel::right("named_snapshots_panel")
+            .resizable(true)
+            .default_width(240.0)
+            .show(ctx, |ui| {
+                ui.heading("Snapshots");
+                if ui.button("Take Snapshot…").clicked() {
+                    take = true;
+                }
+                ui.separator();
+                if self.project.is_none() {
+                    ui.label("Snapshots are kept with a project.");
+                    return;
+                }
+                if self.named_snapshots.is_empty() {
+                    ui.label("No snapshots of this document yet.");
+                    ui.weak("Take one at a milestone - a finished draft, before a big rewrite.");
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .auto_shrink(false)
+                    .show(ui, |ui| {
+                        for (index, snapshot) in self.named_snapshots.iter().enumerate() {
+                            ui.strong(&snapshot.name);
+                            ui.weak(format_timestamp(snapshot.created));
+                            ui.horizontal(|ui| {
+                                let buttons = [
+                                    ("Restore", SnapshotAction::Restore),
+                                    ("Compare", SnapshotAction::Compare),
+                                    ("Delete", SnapshotAction::Delete),
+                                ];
+                                for (label, clicked) in buttons {
+                                    if ui.small_button(label).clicked() {
+                                        action = Some((index, clicked));
+                                    }
+                                }
+                            });
+                            ui.add_space(6.0);
+                        }
+                    });
+            });
+
+        if take {
+            self.open_snapshot_prompt();
+        }
+        let Some((index, action)) = action else {
+            return;
+        };
+        let Some(project) = &self.project else {
+            return;
+        };
+        let dir = storage::named_snapshot_dir(&project.path);
+        let snapshot = self.named_snapshots[index].clone();
+        if action == SnapshotAction::Delete {
+            self.status_message = match storage::delete_named_snapshot(&dir, &snapshot) {
+                Ok(()) => format!("Deleted snapshot \"{}\" (it's in the trash)", snapshot.name),
+                Err(e) => format!("Error deleting snapshot: {}", e),
+            };
+            self.named_snapshots_for = None;
+            return;
+        }
+
+        let text = match storage::load_named_snapshot(&dir, &snapshot) {
+            Ok(text) => text,
+            Err(e) => {
+                self.status_message = format!("Error reading snapshot: {}", e);
+                return;
+            }
+        };
+        if action == SnapshotAction::Compare {
+            let title = format!(
+                "\"{}\" ({})",
+                snapshot.name,
+                format_timestamp(snapshot.created)
+            );
+            self.comparison = Some((title, text));
+            self.comparison_diff = None;
+        } else if self.check_editable() {
+            // Undo can take the editor back to what it held before
+            self.history.checkpoint(&self.text_content);
+            self.text_content = text;
+            self.history.checkpoint(&self.text_content);
+            self.dirty = true;
+            self.text_edited();
+            self.status_message = format!("Restored snapshot \"{}\"", snapshot.name);
+        }
+    }
+
+    /// Draw the Compare window: what changed between a version (from
+    /// comparison) and the editor text, with a Revert button per hunk
+    fn compare_window(&mut self, ctx: &egui::Context) {
+        let Some((title, text)) = &self.comparison else {
+            return;
+        };
+        // Worked out again only when the editor text changes
+        if self
+            .comparison_diff
+            .as_ref()
+            .map(|(generation, _)| *generation)
+            != Some(self.edit_generation)
+        {
+            let diff = diff::diff(text, &self.text_content);
+            self.comparison_diff = Some((self.edit_generation, diff));
+        }
+        let Some((_, diff)) = &self.comparison_diff else {
+            return;
+        };
+        let mut open = true;
+        let mut revert = None;
+
+        egui::Window::new("Compare")
+            .open(&mut open)
+            .default_width(720.0)
+            .default_height(480.0)
+            .show(ctx, |ui| {
+                let (added, removed) = diff.counts();
+                ui.horizontal(|ui| {
+                    ui.strong(title);
+                    ui.label("→ Now");
+                    ui.weak(format!(
+                        "{} lines added, {} removed",
+                        format_count(added),
+                        format_count(removed)
+                    ));
+                });
+                ui.separator();
+                revert = diff::show(ui, diff, !self.locked());
+            });
+
+        let reverted = revert.map(|index| {
+            let hunk = &diff.hunks[index];
+            let label = diff::hunk_label(hunk);
+            (diff::revert(text, &self.text_content, hunk), label)
+        });
+        if let Some((reverted, label)) = reverted {
+            if self.check_editable() {
+                // Undo can take the editor back to what it held before
+                self.history.checkpoint(&self.text_content);
+                self.text_content = reverted;
+                self.history.checkpoint(&self.text_content);
+                self.dirty = true;
+                self.text_edited();
+                self.status_message = format!("Reverted change: {}", label);
+            }
+        }
+
+        if !open {
+            self.comparison = None;
+            self.comparison_diff = None;
+        }
+    }
+
+    /// Re-read the open project's repository for the Git panel if the
+    /// project changed (or something was committed or saved)
+    fn refresh_git(&mut self) {
+        let key = self.project.as_ref().map(|p| p.path.clone());
+        if key.is_some() && self.git_status_for == key {
+            return;
+        }
+        self.git_status = None;
+        self.git_status_for = key;
+        let Some(project) = &self.project else {
+            return;
+        };
+        match git::status(project.dir()) {
+            Ok(status) => self.git_status = status,
+            Err(e) => self.status_message = format!("Error reading Git history: {}", e),
+        }
+    }
+
+    /// Draw the View → Git panel: the project's changes and commits, with
+    /// each project document viewable as it was in a commit
+    fn git_panel(&mut self, ctx: &egui::Context) {
+        self.refresh_git();
+
+        let mut init = false;
+        let mut commit = false;
+        let mut view: Option<(usize, usize)> = None;
+        let mut settings_changed = false;
+        egui::SidePanel::right("git_panel")
+            .resizable(true)
+            .default_width(260.0)
+            .show(ctx, |ui| {
+                ui.heading("Git");
+                let Some(project) = &mut self.project else {
+                    ui.label("Git history is kept for a project.");
+                    return;
+                };
+                let Some(status) = &self.git_status else {
+                    ui.label("This project isn't in a Git repository.");
+                    if ui.button("Create Repository").clicked() {
+                        init = true;
+                    }
+                    ui.weak("Makes the project folder a Git repository, to commit its files to.");
+                    return;
+                };
+
+                ui.horizontal(|ui| {
+                    if ui.button("Commit…").clicked() {
+                        commit = true;
+                    }
+                    match status.changed {
+                        0 => ui.weak("No changes"),
+                        1 => ui.weak("1 changed file"),
+                        n => ui.weak(format!("{} changed files", format_count(n))),
+                    };
+                });
+                settings_changed = ui
+                    .checkbox(
+                        &mut project.project.settings.git_commit_on_save,
+                        "Commit on save",
+                    )
+                    .changed();
+                ui.separator();
+
+                if status.commits.is_empty() {
+                    ui.label("No commits yet.");
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .auto_shrink(false)
+                    .show(ui, |ui| {
+                        for (index, info) in status.commits.iter().enumerate() {
+                            ui.strong(&info.summary);
+                            let time = u64::try_from(info.time).unwrap_or(0);
+                            ui.weak(format!(
+                                "{} · {} · {}",
+                                info.short_id(),
+                                info.author,
+                                format_timestamp(time)
+                            ));
+                            ui.menu_button("View…", |ui| {
+                                for (file, entry) in project.project.files.iter().enumerate() {
+                                    if ui.button(entry).clicked() {
+                                        ui.close_menu();
+                                        view = Some((index, file));
+                                    }
+                                }
+                            });
+                            ui.add_space(6.0);
+                        }
+                    });
+            });
+
+        if settings_changed {
+            self.write_project();
+        }
+        if init {
+            if let Some(project) = &self.project {
+                self.status_message = match git::init(project.dir()) {
+                    Ok(()) => format!("Created a Git repository in {}", project.dir().display()),
+                    Err(e) => format!("Error creating repository: {}", e),
+                };
+                self.git_status_for = None;
+            }
+        }
+        if commit {
+            self.open_commit_prompt();
+        }
+        if let Some((index, file)) = view {
+            self.view_committed_version(index, file);
+        }
+    }
+
+    /// Open a project document as it was in a commit, read-only in a tab
+    /// of its own
+    ///
+    /// `commit` indexes the Git panel's list, `file` the project's files.
+    fn view_committed_version(&mut self, commit: usize, file: usize) {
+        let (Some(project), Some(status)) = (&self.project, &self.git_status) else {
+            return;
+        };
+        let info = &status.commits[commit];
+        let path = project.file_path(file);
+        let text = match git::file_at(project.dir(), &info.id, &path) {
+            Ok(text) => text,
+            Err(e) => {
+                self.status_message = format!("Can't show that version: {}", e);
+                return;
+            }
+        };
+        let name = path.file_name().map_or_else(
+            || project.project.files[file].clone(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let label = format!("{} at {}", name, info.short_id());
+        let summary = info.summary.clone();
+
+        self.new_tab();
+        self.detected_format = format::detect_format(Some(&path), &text);
+        self.history.reset(&text);
+        self.text_content = text;
+        self.text_edited();
+        self.status_message = format!("{} (\"{}\"), read-only", label, summary);
+        self.preview = Some(label);
+    }
+
+    /// Draw the password prompt for a password-protected file being opened
+    fn unlock_prompt(&mut self, ctx: &egui::Context) {
+        let Some((path, mut password, wrong)) = self.unlock_prompt.take() else {
+            return;
+        };
+        let mut unlock = false;
+        let mut open = true;
+
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        egui::Window::new("Password")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 60.0))
+            .show(ctx, |ui| {
+                ui.label(format!("{} is password-protected.", name));
+                if wrong {
+                    ui.colored_label(
+                        ui.visuals().error_fg_color,
+                        "Wrong password (or the file is damaged). Try again.",
+                    );
+                }
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut password)
+                        .password(true)
+                        .hint_text("Password")
+                        .desired_width(280.0),
+                );
+                response.request_focus();
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    unlock = true;
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Open").clicked() {
+                        unlock = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        open = false;
+                    }
+                });
+            });
+
+        if unlock && !password.is_empty() {
+            let request = FileRequest::Unlock {
+                path: path.clone(),
+                password: password.clone(),
+            };
+            if self.file_io.submit(request) {
+                return;
+            }
+            self.report_busy();
+        }
+        if open {
+            self.unlock_prompt = Some((path, password, wrong));
+        } else {
+            // A session restore waiting on this file won't get it
+            self.pending_restore = None;
+            self.status_message = format!("Not opened: {}", path.display());
+        }
+    }
+
+    /// Draw the Set Password prompt
+    ///
+    /// Setting a password saves the document encrypted, under a `.bksx`
+    /// name (Save As suggests one unless it already has it). Removing it
+    /// saves the plain document again, by Save As from a `.bksx` file.
+    fn password_prompt(&mut self, ctx: &egui::Context) {
+        let Some((mut password, mut again)) = self.password_prompt.take() else {
+            return;
+        };
+        let mut set = false;
+        let mut remove = false;
+        let mut open = true;
+        let protected = self.password.is_some();
+
+        let title = if protected {
+            "Change Password"
+        } else {
+            "Set Password"
+        };
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 60.0))
+            .show(ctx, |ui| {
+                ui.label("The document is saved encrypted, and opening it asks for this password.");
+                ui.label("A forgotten password can't be recovered.");
+                ui.weak("Autosave and version history are off for password-protected documents.");
+                ui.add_space(4.0);
+
+                let first = ui.add(
+                    egui::TextEdit::singleline(&mut password)
+                        .password(true)
+                        .hint_text("Password")
+                        .desired_width(280.0),
+                );
+                if !first.has_focus() && password.is_empty() {
+                    first.request_focus();
+                }
+                let second = ui.add(
+                    egui::TextEdit::singleline(&mut again)
+                        .password(true)
+                        .hint_text("The same again")
+                        .desired_width(280.0),
+                );
+                let matching = !password.is_empty() && password == again;
+                if !again.is_empty() && !matching {
+                    ui.colored_label(ui.visuals().error_fg_color, "The passwords don't match");
+                }
+                if matching && second.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter))
+                {
+                    set = true;
+                }
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(matching, egui::Button::new(title)).clicked() {
+                        set = true;
+                    }
+                    if protected && ui.button("Remove Password").clicked() {
+                        remove = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        open = false;
+                    }
+                });
+            });
+
+        if set {
+            self.password = Some(password);
+            self.save_protection_change("Password set");
+        } else if remove {
+            self.password = None;
+            self.save_protection_change("Password removed");
+        } else if open {
+            self.password_prompt = Some((password, again));
+        }
+    }
+
+    /// Save after a password was set or removed, under a name with the
+    /// right extension (Save As offers one when the current name doesn't
+    /// have it)
+    fn save_protection_change(&mut self, what: &str) {
+        // The file on disk no longer matches how the document will be saved
+        self.dirty = true;
+        let encrypted = self.password.is_some();
+        let named_right = self.current_file_path.as_ref().is_some_and(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == encryption::EXTENSION)
+                == encrypted
+        });
+        let started = if named_right {
+            self.save()
+        } else {
+            self.save_as()
+        };
+        if !started {
+            self.status_message = format!("{} - it takes effect when the document is saved", what);
+        }
+    }
+
+    /// File → Commit to Git…: ask for a commit message
+    fn open_commit_prompt(&mut self) {
+        let Some(project) = &self.project else {
+            self.status_message = String::from("Git history is kept for a project");
+            return;
+        };
+        if !git::is_repository(project.dir()) {
+            self.status_message =
+                String::from("The project isn't in a Git repository (View → Git to create one)");
+            return;
+        }
+        self.commit_message = Some(String::new());
+    }
+
+    /// Draw the Commit prompt: a message, then Enter
+    fn commit_prompt(&mut self, ctx: &egui::Context) {
+        let Some(mut message) = self.commit_message.take() else {
+            return;
+        };
+        let mut commit = false;
+        let mut open = true;
+
+        egui::Window::new("Commit to Git")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 60.0))
+            .show(ctx, |ui| {
+                ui.label(
+                    "Commits the project folder's saved files. Unsaved changes aren't included.",
+                );
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut message)
+                        .hint_text("What changed, e.g. \"Rewrote chapter 3\"")
+                        .desired_width(320.0),
+                );
+                response.request_focus();
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    commit = true;
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Commit").clicked() {
+                        commit = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        open = false;
+                    }
+                });
+            });
+
+        if commit && !message.trim().is_empty() {
+            self.commit_project(message.trim());
+            open = false;
+        }
+        if open {
+            self.commit_message = Some(message);
+        }
+    }
+
+    /// Commit the project folder with `message`
+    fn commit_project(&mut self, message: &str) {
+        let Some(project) = &self.project else {
+            return;
+        };
+        self.status_message = match git::commit_all(project.dir(), message) {
+            Ok(Some(id)) => format!("Committed {}: {}", id, message),
+            Ok(None) => String::from("Nothing to commit - the project's files are unchanged"),
+            Err(e) => format!("Error committing: {}", e),
+        };
+        self.git_status_for = None;
+    }
+
+    /// After a save: commit the project folder if `path` is one of the
+    /// project's documents and Commit on save is on
+    fn commit_on_save(&mut self, path: &std::path::Path) {
+        let Some(project) = &self.project else {
+            return;
+        };
+        // The change count is out of date either way
+        self.git_status_for = None;
+        if !project.project.settings.git_commit_on_save || project.index_of(path).is_none() {
+            return;
+        }
+        let message = format!("Save {}", project.entry_for(path));
+        match git::commit_all(project.dir(), &message) {
+            Ok(Some(id)) => self
+                .status_message
+                .push_str(&format!(" · committed {}", id)),
+            Ok(None) => {}
+            Err(e) => self.status_message = format!("Saved, but not committed: {}", e),
+        }
+    }
+
+    /// Re-read the trash folder into `trash_entries`
+    fn refresh_trash(&mut self) {
+        match storage::list_trash() {
+            Ok(entries) => self.trash_entries = entries,
+            Err(e) => {
+                self.trash_entries.clear();
+                self.status_message = format!("Error reading trash: {}", e);
+            }
+        }
+    }
+
+    /// Draw the Tools → Recently deleted window
+    ///
+    /// Each row shows where the file came from and how long ago it was
+    /// deleted, with buttons to restore it or delete it permanently.
+    fn trash_window(&mut self, ctx: &egui::Context) {
+        // egui::Window::open() takes a &mut bool and flips it to false when
+        // the user clicks the window's close button
+        let mut open = self.show_trash_window;
+
+        // Actions are collected during drawing and applied afterwards, because
+        // we can't modify trash_entries while iterating over it
+        let mut to_restore: Option<storage::TrashEntry> = None;
+        let mut to_purge: Vec<storage::TrashEntry> = Vec::new();
+
+        egui::Window::new("Recently deleted")
+            .open(&mut open)
+            .default_width(520.0)
+            .show(ctx, |ui| {
+                if self.trash_entries.is_empty() {
+                    ui.label("Nothing has been deleted recently.");
+                    return;
+                }
+
+                ui.label(format!(
+                    "Deleted files are kept for {} days.",
+                    storage::TRASH_RETENTION.as_secs() / (24 * 60 * 60)
+                ));
+                ui.separator();
+
+                let now = storage::unix_now();
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for entry in &self.trash_entries {
+                            ui.horizontal(|ui| {
+                                if ui.button("Restore").clicked() {
+                                    to_restore = Some(entry.clone());
+                                }
+                                if ui.button("Delete forever").clicked() {
+                                    to_purge.push(entry.clone());
+                                }
+                                ui.label(format_age(now.saturating_sub(entry.deleted_at)));
+                                ui.label(entry.original_path.display().to_string());
+                            });
+                        }
+                    });
+
+                ui.separator();
+                if ui.button("Empty trash").clicked() {
+                    to_purge = self.trash_entries.clone();
+                }
+            });
+
+        self.show_trash_window = open;
+
+        if let Some(entry) = to_restore {
+            match storage::restore_from_trash(&entry) {
+                Ok(path) => self.status_message = format!("Restored: {}", path.display()),
+                Err(e) => self.status_message = format!("Error restoring file: {}", e),
+            }
+            self.refresh_trash();
+        }
+
+        if !to_purge.is_empty() {
+            let count = to_purge.len();
+            for entry in &to_purge {
+                if let Err(e) = storage::purge_trash_entry(entry) {
+                    self.status_message = format!("Error deleting file: {}", e);
+                    self.refresh_trash();
+                    return;
+                }
+            }
+            self.status_message = format!("Permanently deleted {} file(s)", count);
+            self.refresh_trash();
+        }
+    }
+
+    /// Draw the top menu bar (File / Edit / View / Tools / Help)
+    fn menu_bar(&mut self, ctx: &egui::Context) {
+        // TopBottomPanel creates a bar at the top of the window
+        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+            // `ui` is a Ui object that lets us add widgets
+            // It's passed to us by the closure
+
+            // Create a horizontal menu bar
+            egui::menu::bar(ui, |ui| {
+                // "File" menu
+                ui.menu_button("File", |ui| {
+                    // Open/Save are greyed out while a load or save is running
+                    let busy = self.file_io.is_busy();
+
+                    if ui
+                        .add(egui::Button::new("New").shortcut_text("Ctrl+N"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.new_tab();
+                    }
+                    ui.menu_button("New from Template", |ui| {
+                        let mut picked = None;
+                        for template in templates::built_in() {
+                            if ui.button(&template.name).clicked() {
+                                picked = Some(template);
+                            }
+                        }
+                        if !self.user_templates.is_empty() {
+                            ui.separator();
+                        }
+                        let mut deleted = None;
+                        for (index, template) in self.user_templates.iter().enumerate() {
+                            let response = ui
+                                .button(&template.name)
+                                .on_hover_text("Right-click to delete");
+                            if response.clicked() {
+                                picked = Some(template.clone());
+                            }
+                            response.context_menu(|ui| {
+                                if ui.button("Delete Template").clicked() {
+                                    deleted = Some(index);
+                                    ui.close_menu();
+                                }
+                            });
+                        }
+                        ui.separator();
+                        if ui.button("Save as Template…").clicked() {
+                            ui.close_menu();
+                            self.open_template_prompt();
+                        }
+
+                        if let Some(template) = picked {
+                            ui.close_menu();
+                            self.new_from_template(template);
+                        }
+                        if let Some(index) = deleted {
+                            self.delete_template(index);
+                        }
+                    });
+
+                    // "Open" button
+                    // shortcut_text shows the key combination on the right
+                    // (it's only a label - the keys are handled in handle_shortcuts)
+                    if ui
+                        .add_enabled(!busy, egui::Button::new("Open…").shortcut_text("Ctrl+O"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.open_with_dialog(ctx);
+                    }
+
+                    // Files that no longer exist are greyed out (and
+                    // open_recent copes with one that vanishes after the
+                    // menu was drawn)
+                    ui.add_enabled_ui(!busy, |ui| {
+                        ui.menu_button("Open Recent", |ui| {
+                            let mut picked = None;
+                            for path in &self.recent_files {
+                                let name = path.file_name().map_or_else(
+                                    || path.display().to_string(),
+                                    |name| name.to_string_lossy().into_owned(),
+                                );
+                                let response = ui
+                                    .add_enabled(path.exists(), egui::Button::new(name))
+                                    .on_hover_text(path.display().to_string())
+                                    .on_disabled_hover_text(format!(
+                                        "{} (no longer exists)",
+                                        path.display()
+                                    ));
+                                if response.clicked() {
+                                    picked = Some(path.clone());
+                                }
+                            }
+                            if self.recent_files.is_empty() {
+                                ui.label("No recent files");
+                            }
+
+                            ui.separator();
+                            if ui
+                                .add_enabled(
+                                    !self.recent_files.is_empty(),
+                                    egui::Button::new("Clear Recent"),
+                                )
+                                .clicked()
+                            {
+                                ui.close_menu();
+                                self.recent_files.clear();
+                                self.write_recent_files();
+                            }
+
+                            if let Some(path) = picked {
+                                ui.close_menu();
+                                self.open_recent(ctx, path);
+                            }
+                        });
+                    });
+
+                    ui.separator();
+                    if ui.button("New Project…").clicked() {
+                        ui.close_menu();
+                        self.new_project();
+                    }
+                    if ui.button("Open Project…").clicked() {
+                        ui.close_menu();
+                        self.open_project_with_dialog();
+                    }
+                    let has_project = self.project.is_some();
+                    if ui
+                        .add_enabled(has_project, egui::Button::new("Project Settings…"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.show_project_window = true;
+                    }
+                    if ui
+                        .add_enabled(has_project, egui::Button::new("Compile…"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.show_compile_window = true;
+                    }
+                    if ui
+                        .add_enabled(has_project, egui::Button::new("Close Project"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.close_project();
+                    }
+                    ui.separator();
+
+                    // "Save" button
+                    if ui
+                        .add_enabled(!busy, egui::Button::new("Save").shortcut_text("Ctrl+S"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.save();
+                    }
+
+                    // "Save As" button
+                    if ui
+                        .add_enabled(
+                            !busy,
+                            egui::Button::new("Save As…").shortcut_text("Ctrl+Shift+S"),
+                        )
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.save_as();
+                    }
+                    if ui
+                        .add(egui::Button::new("Print…").shortcut_text("Ctrl+P"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.open_print_window();
+                    }
+                    if ui
+                        .add_enabled(!busy, egui::Button::new("Set Password…"))
+                        .on_hover_text("Save the document encrypted (.bksx)")
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.password_prompt = Some((String::new(), String::new()));
+                    }
+
+                    // "Export" submenu - nested menu_button opens to the side
+                    // The exporters suited to the document's mode come
+                    // first, set apart from the rest
+                    ui.menu_button("Export", |ui| {
+                        let suggested = Exporter::suggested_for(self.active_format());
+                        let rest = Exporter::ALL.iter().filter(|e| !suggested.contains(e));
+                        let mut picked = None;
+                        for exporter in suggested {
+                            if ui.button(exporter.label()).clicked() {
+                                picked = Some(*exporter);
+                            }
+                        }
+                        if !suggested.is_empty() {
+                            ui.separator();
+                        }
+                        for exporter in rest {
+                            if ui.button(exporter.label()).clicked() {
+                                picked = Some(*exporter);
+                            }
+                        }
+                        if let Some(exporter) = picked {
+                            ui.close_menu();
+                            self.start_export(exporter);
+                        }
+                    });
+
+                    if ui
+                        .button("Autosave Now")
+                        .on_hover_text("Write the autosave copy immediately")
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.autosave_now(ui.input(|i| i.time));
+                    }
+
+                    if ui.button("Version History…").clicked() {
+                        ui.close_menu();
+                        self.history_source = HistorySource::Snapshots;
+                        self.refresh_snapshots();
+                        self.show_snapshots_window = true;
+                    }
+                    if ui.button("Restore from Backup…").clicked() {
+                        ui.close_menu();
+                        self.history_source = HistorySource::Backups;
+                        self.refresh_snapshots();
+                        self.show_snapshots_window = true;
+                    }
+                    if ui.button("Take Snapshot…").clicked() {
+                        ui.close_menu();
+                        self.open_snapshot_prompt();
+                    }
+                    if ui
+                        .add_enabled(has_project, egui::Button::new("Commit to Git…"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.open_commit_prompt();
+                    }
+
+                    // Separator line in the menu
+                    ui.separator();
+
+                    if ui
+                        .add_enabled(
+                            !busy,
+                            egui::Button::new("Close Tab").shortcut_text("Ctrl+W"),
+                        )
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.request(ctx, PendingAction::CloseTab);
+                    }
+
+                    // "Exit" button
+                    if ui.button("Exit").clicked() {
+                        ui.close_menu();
+                        self.request(ctx, PendingAction::Exit);
+                    }
+                });
+
+                // "Edit" menu
+                ui.menu_button("Edit", |ui| {
+                    let (can_undo, can_redo) = {
+                        let text = &self.text_content;
+                        (self.history.can_undo(text), self.history.can_redo(text))
+                    };
+                    if ui
+                        .add_enabled(can_undo, egui::Button::new("Undo").shortcut_text("Ctrl+Z"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.undo(ctx);
+                    }
+                    if ui
+                        .add_enabled(can_redo, egui::Button::new("Redo").shortcut_text("Ctrl+Y"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.redo(ctx);
+                    }
+
+                    ui.separator();
+
+                    if ui
+                        .add(egui::Button::new("Find…").shortcut_text("Ctrl+F"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.open_find_bar(false);
+                    }
+                    if ui
+                        .add(egui::Button::new("Replace…").shortcut_text("Ctrl+H"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.open_find_bar(true);
+                    }
+                    if ui
+                        .add_enabled(
+                            self.project.is_some(),
+                            egui::Button::new("Find in Project…").shortcut_text("Ctrl+Shift+F"),
+                        )
+                        .on_disabled_hover_text("Open a project to search all its documents")
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.open_project_search();
+                    }
+                    if ui
+                        .add(egui::Button::new("Find next").shortcut_text("F3"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.find_step(true);
+                    }
+                    if ui
+                        .add(egui::Button::new("Find previous").shortcut_text("Shift+F3"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.find_step(false);
+                    }
+
+                    ui.separator();
+
+                    if ui
+                        .add(egui::Button::new("Go to…").shortcut_text("Ctrl+G"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.go_to_query = Some(String::new());
+                    }
+                    let jumps = [
+                        (
+                            "Next chapter",
+                            "Ctrl+PgDn",
+                            Landmark::Chapter,
+                            Direction::Next,
+                        ),
+                        (
+                            "Previous chapter",
+                            "Ctrl+PgUp",
+                            Landmark::Chapter,
+                            Direction::Previous,
+                        ),
+                        ("Next scene", "Alt+PgDn", Landmark::Scene, Direction::Next),
+                        (
+                            "Previous scene",
+                            "Alt+PgUp",
+                            Landmark::Scene,
+                            Direction::Previous,
+                        ),
+                    ];
+                    for (label, shortcut, landmark, direction) in jumps {
+                        if ui
+                            .add(egui::Button::new(label).shortcut_text(shortcut))
+                            .clicked()
+                        {
+                            ui.close_menu();
+                            self.jump_to_landmark(landmark, direction);
+                        }
+                    }
+
+                    ui.separator();
+
+                    if ui
+                        .add(egui::Button::new("Toggle bookmark").shortcut_text("Ctrl+B"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        let line = editing::line_at_char(&self.text_content, self.editor_cursor);
+                        self.toggle_bookmark(line);
+                    }
+                    if ui
+                        .add(egui::Button::new("Next bookmark").shortcut_text("F2"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.jump_to_bookmark(true);
+                    }
+                    if ui
+                        .add(egui::Button::new("Previous bookmark").shortcut_text("Shift+F2"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.jump_to_bookmark(false);
+                    }
+
+                    ui.separator();
+
+                    if ui.button("Clean Up Document…").clicked() {
+                        ui.close_menu();
+                        self.show_cleanup_window = true;
+                    }
+
+                    ui.separator();
+
+                    // ui.checkbox flips the bool it's given when clicked
+                    let opts = &mut self.settings.structure_editing;
+                    ui.checkbox(&mut opts.smart_enter, "Smart Enter inside tags");
+                    ui.checkbox(&mut opts.auto_pair_brackets, "Auto-pair brackets");
+                    ui.checkbox(&mut opts.space_after_colon, "Space after tag colon");
+                    ui.checkbox(&mut opts.complete_names, "Complete character names");
+
+                    ui.separator();
+
+                    ui.checkbox(&mut opts.smart_typography, "Smart typography")
+                        .on_hover_text(
+                            "Curly quotes, -- to an em dash and ... to an ellipsis as you type",
+                        );
+                    ui.add_enabled_ui(opts.smart_typography, |ui| {
+                        ui.menu_button("Quote Style", |ui| {
+                            for style in QuoteStyle::ALL {
+                                ui.radio_value(&mut opts.quote_style, style, style.label());
+                            }
+                        });
+                        ui.checkbox(&mut opts.plain_in_brackets, "Not inside [tags]");
+                    });
+
+                    ui.separator();
+
+                    if ui.button("Preferences…").clicked() {
+                        self.show_preferences = true;
+                        ui.close_menu();
+                    }
+                });
+
+                // "Insert" menu
+                ui.menu_button("Insert", |ui| {
+                    let mut chosen = None;
+                    for (index, snippet) in self.snippets.iter().enumerate() {
+                        let mut button = egui::Button::new(&snippet.name);
+                        if !snippet.trigger.is_empty() {
+                            button = button.shortcut_text(format!("{}⇥", snippet.trigger));
+                        }
+                        if ui.add_enabled(!self.locked(), button).clicked() {
+                            chosen = Some(index);
+                        }
+                    }
+                    if let Some(index) = chosen {
+                        ui.close_menu();
+                        self.insert_snippet(ctx, index);
+                    }
+                    if self.snippets.is_empty() {
+                        ui.weak("No snippets");
+                    }
+
+                    ui.separator();
+                    if ui
+                        .button("Reload Snippets")
+                        .on_hover_text("Pick up changes made to snippets.toml")
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        if self.load_snippets() {
+                            self.status_message =
+                                format!("Loaded {} snippet(s)", self.snippets.len());
+                        }
+                    }
+                    if let Ok(path) = storage::get_snippets_path() {
+                        ui.weak(path.display().to_string());
+                    }
+                });
+
+                // "View" menu
+                ui.menu_button("View", |ui| {
+                    ui.menu_button("Theme", |ui| {
+                        let before = self.settings.theme;
+                        for theme in Theme::ALL {
+                            ui.radio_value(&mut self.settings.theme, theme, theme.label());
+                        }
+                        if self.settings.theme != before {
+                            ui.close_menu();
+                            self.save_settings();
+                        }
+                        ui.separator();
+                        if ui
+                            .add_enabled(
+                                self.settings.theme == Theme::Custom,
+                                egui::Button::new("Reload Theme File"),
+                            )
+                            .on_hover_text("Pick up changes made to theme.json")
+                            .clicked()
+                        {
+                            ui.close_menu();
+                            self.applied_theme = None;
+                        }
+                        if let Ok(path) = storage::get_theme_path() {
+                            ui.weak(path.display().to_string());
+                        }
+                    });
+                    ui.checkbox(&mut self.show_outline, "Outline");
+                    if ui
+                        .checkbox(&mut self.show_characters_panel, "Characters")
+                        .changed()
+                        && !self.show_characters_panel
+                    {
+                        // Notes still being typed are written as it closes
+                        self.write_project();
+                    }
+                    ui.checkbox(&mut self.show_scene_inspector, "Scene Inspector");
+                    ui.checkbox(&mut self.show_comments_panel, "Comments");
+                    ui.checkbox(&mut self.show_bookmarks_panel, "Bookmarks");
+                    ui.checkbox(&mut self.show_named_snapshots, "Snapshots");
+                    ui.checkbox(&mut self.show_git_panel, "Git");
+                    ui.checkbox(&mut self.show_corkboard, "Corkboard");
+                    ui.menu_button("Split Editor", |ui| {
+                        let before = self.split_editor;
+                        ui.radio_value(&mut self.split_editor, None, "Off");
+                        ui.radio_value(
+                            &mut self.split_editor,
+                            Some(EditorSplit::SideBySide),
+                            "Side by Side",
+                        );
+                        ui.radio_value(
+                            &mut self.split_editor,
+                            Some(EditorSplit::TopAndBottom),
+                            "Top and Bottom",
+                        );
+                        if self.split_editor != before {
+                            ui.close_menu();
+                        }
+                    });
+                    ui.checkbox(&mut self.show_formatted_preview, "Formatted Preview");
+                    ui.checkbox(&mut self.read_only, "Read-only");
+                    if ui
+                        .add(egui::Button::new("Focus mode").shortcut_text("F11"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.set_focus_mode(ctx, true);
+                    }
+
+                    ui.separator();
+
+                    // Editor text size - the shortcuts are only labels here,
+                    // handle_shortcuts does the work
+                    let editor = &mut self.settings.editor;
+                    ui.add(
+                        egui::Slider::new(
+                            &mut editor.font_size,
+                            settings::MIN_FONT_SIZE..=settings::MAX_FONT_SIZE,
+                        )
+                        .step_by(1.0)
+                        .text("Text size"),
+                    );
+                    ui.radio_value(&mut editor.font, EditorFont::Monospace, "Monospace font");
+                    ui.radio_value(
+                        &mut editor.font,
+                        EditorFont::Proportional,
+                        "Proportional font",
+                    );
+                    if editor.custom_font.is_some() {
+                        ui.radio_value(&mut editor.font, EditorFont::Custom, "Custom font");
+                    }
+                    if ui.button("Editor Font…").clicked() {
+                        ui.close_menu();
+                        self.show_font_window = true;
+                    }
+                    if ui
+                        .add(egui::Button::new("Zoom in").shortcut_text("Ctrl+="))
+                        .clicked()
+                    {
+                        self.zoom(settings::FONT_SIZE_STEP);
+                    }
+                    if ui
+                        .add(egui::Button::new("Zoom out").shortcut_text("Ctrl+-"))
+                        .clicked()
+                    {
+                        self.zoom(-settings::FONT_SIZE_STEP);
+                    }
+                    if ui
+                        .add(egui::Button::new("Reset zoom").shortcut_text("Ctrl+0"))
+                        .clicked()
+                    {
+                        self.settings.editor.font_size = settings::DEFAULT_FONT_SIZE;
+                    }
+                });
+
+                // "Tools" menu
+                ui.menu_button("Tools", |ui| {
+                    if ui.button("Character report…").clicked() {
+                        self.show_characters_window = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Statistics…").clicked() {
+                        self.show_statistics_window = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Style Check…").clicked() {
+                        self.show_style_window = true;
+                        ui.close_menu();
+                    }
+                    let reading = self.read_aloud.is_some();
+                    let label = if reading {
+                        "Stop Reading Aloud"
+                    } else {
+                        "Read Aloud"
+                    };
+                    if ui
+                        .button(label)
+                        .on_hover_text("Reads the selection, or the scene the caret is in")
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        if reading {
+                            self.stop_read_aloud();
+                        } else {
+                            self.start_read_aloud(ctx);
+                        }
+                    }
+                    ui.menu_button("Table of Contents", |ui| {
+                        let hover = "Lists the acts, chapters and scenes at the caret, \
+                                     or brings the list already in the document up to date";
+                        if ui
+                            .button("With Line Numbers")
+                            .on_hover_text(hover)
+                            .clicked()
+                        {
+                            self.insert_table_of_contents(References::Lines);
+                            ui.close_menu();
+                        }
+                        if ui
+                            .button("With Page Numbers")
+                            .on_hover_text(hover)
+                            .clicked()
+                        {
+                            self.insert_table_of_contents(References::Pages);
+                            ui.close_menu();
+                        }
+                    });
+
+                    ui.separator();
+
+                    if ui.button("Writing goal…").clicked() {
+                        self.show_goal_window = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Progress history…").clicked() {
+                        self.refresh_progress_history();
+                        self.show_progress_window = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Sprint…").clicked() {
+                        self.refresh_sprint_history();
+                        self.show_sprint_window = true;
+                        ui.close_menu();
+                    }
+
+                    ui.separator();
+
+                    if ui.button("Recently deleted…").clicked() {
+                        self.refresh_trash();
+                        self.show_trash_window = true;
+                        // close_menu() collapses the dropdown after a click
+                        ui.close_menu();
+                    }
+
+                    ui.separator();
+
+                    if ui.button("Export profile…").clicked() {
+                        ui.close_menu();
+                        if let Some(path) = dialogs::save_file(
+                            "Export profile",
+                            "Profile",
+                            &["json"],
+                            profile::PROFILE_FILE_NAME,
+                            None,
+                        ) {
+                            self.export_profile(path);
+                        }
+                    }
+                    if ui.button("Import profile…").clicked() {
+                        ui.close_menu();
+                        if let Some(path) =
+                            dialogs::pick_file("Import profile", "Profile", &["json"], None)
+                        {
+                            self.import_profile(path);
+                        }
+                    }
+
+                    ui.separator();
+
+                    if ui
+                        .button("Register file types")
+                        .on_hover_text(
+                            "Makes .bks files (and .scr, outside Windows) open in \
+                             this copy of the app from the file manager",
+                        )
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        self.register_file_types();
+                    }
+                });
+
+                // "Help" menu
+                ui.menu_button("Help", |ui| {
                     if ui.button("About").clicked() {
                         self.status_message =
                             String::from("BookScript Writer v0.1.0 - A simple writing app");
                     }
                 });
-            });
-        });
+            });
+        });
+    }
+
+    /// Draw the bottom panel: Problems list and status bar
+    fn status_bar(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
+            // Add some padding around the status message
+            ui.add_space(4.0);
+
+            // Collapsible list of tag problems; clicking one jumps to its line
+            let header = format!("Problems ({})", self.problems.len());
+            egui::CollapsingHeader::new(header)
+                .id_salt("problems_panel")
+                .default_open(false)
+                .show(ui, |ui| {
+                    if let Some(line) = problems::show(ui, &self.problems) {
+                        self.pending_jump = Some(line);
+                    }
+                });
+
+            // Display the status message
+            ui.horizontal(|ui| {
+                ui.label("Status:");
+                // An in-flight load or save takes over the message until it ends
+                match self.file_io.state().status_text() {
+                    Some(progress) => ui.label(progress),
+                    None => ui.label(&self.status_message),
+                };
+
+                // Right-aligned document mode selector and counts
+                // right_to_left layout places widgets starting from the right edge,
+                // so the first one added ends up rightmost
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    self.format_selector(ui);
+                    ui.separator();
+
+                    ui.label(problems::summary(&self.problems));
+                    ui.separator();
+
+                    // The whole document's counts stay put while a
+                    // selection's counts appear to their left
+                    let (stats, selection) = self.refresh_statistics(ctx);
+                    let pages = stats.pages(self.settings.word_count.words_per_page);
+                    ui.label(format!(
+                        "{} words · {} chars ({} without spaces) · ~{:.1} pages",
+                        format_count(stats.words),
+                        format_count(stats.characters),
+                        format_count(stats.characters_no_spaces),
+                        pages,
+                    ));
+                    ui.separator();
+                    if let Some(selected) = selection {
+                        ui.label(format!(
+                            "Selection: {} words · {} chars",
+                            format_count(selected.words),
+                            format_count(selected.characters),
+                        ));
+                        ui.separator();
+                    }
+
+                    if let Some((words, target)) = self.manuscript_progress() {
+                        ui.add(
+                            egui::ProgressBar::new(goals::manuscript_fraction(words, target))
+                                .desired_width(120.0)
+                                .text(format!(
+                                    "{} / {}",
+                                    format_count(words),
+                                    format_count(target)
+                                )),
+                        )
+                        .on_hover_text("Manuscript length");
+                        ui.separator();
+                    }
+
+                    let written = self.goal_progress();
+                    if let Some(fraction) = self.settings.goal.fraction(written) {
+                        ui.add(egui::ProgressBar::new(fraction).desired_width(120.0).text(
+                            format!(
+                                "{} / {}",
+                                format_delta(written),
+                                format_count(self.settings.goal.words)
+                            ),
+                        ));
+                    }
+                    self.progress_sparkline(ui);
+                    ui.label(format!(
+                        "{} words this session",
+                        format_delta(self.writing_session.delta())
+                    ));
+
+                    if let Some(sprint) = &self.sprint {
+                        ui.separator();
+                        let now = ui.input(|i| i.time);
+                        let label = format!(
+                            "Sprint {} · {} words",
+                            goals::format_clock(sprint.remaining(now)),
+                            format_delta(sprint.words(self.writing_session.delta()))
+                        );
+                        if ui.link(label).clicked() {
+                            self.show_sprint_window = true;
+                        }
+                    }
+
+                    // Leftmost of the group, next to the status message
+                    ui.separator();
+                    ui.label(self.caret_location());
+                });
+            });
+
+            ui.add_space(4.0);
+        });
+    }
+}
+
+// ============================================================================
+// TRAIT IMPLEMENTATION - eframe::App
+// ============================================================================
+
+/// Implement the eframe::App trait for our App struct
+///
+/// TRAITS are Rust's way of defining shared behavior (like interfaces).
+/// eframe requires us to implement the `update` method, which it calls
+/// every frame to rebuild the UI.
+impl eframe::App for App {
+    /// Called by eframe each frame to build the UI
+    ///
+    /// Parameters:
+    /// - `&mut self`: Mutable reference to our app (we can modify state)
+    /// - `ctx`: The egui Context, which provides access to all UI widgets
+    /// - `_frame`: Frame info (we don't use it, hence the underscore)
+    ///
+    /// IMMEDIATE MODE GUI:
+    /// Unlike traditional GUI frameworks that maintain a tree of widgets,
+    /// egui rebuilds the entire UI from scratch every frame. This might
+    /// sound inefficient, but it's actually very fast and makes code simpler.
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Keyboard shortcuts are checked before any widget gets a chance
+        // to react to the keys
+        self.poll_file_io(ctx);
+        self.poll_instance_requests(ctx);
+        self.take_dropped_files(ctx);
+        self.open_pending(ctx);
+        self.handle_completion_keys(ctx);
+        self.handle_shortcuts(ctx);
+        self.sync_appearance(ctx);
+        self.sync_session();
+        self.refresh_bookmarks();
+        self.sync_autosave(ctx.input(|i| i.time));
+
+        // --------------------------------------------------------------------
+        // INTERCEPT WINDOW CLOSE
+        // --------------------------------------------------------------------
+        // The OS close button (or Alt+F4, Cmd+Q, ...) arrives as a close
+        // request. With unsaved changes we cancel it and ask first; once the
+        // user has answered, allow_close lets the next request through.
+        if ctx.input(|i| i.viewport().close_requested())
+            && self.first_dirty_tab().is_some()
+            && !self.allow_close
+        {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            if self.unsaved_prompt.is_none() {
+                self.perform(ctx, PendingAction::Exit);
+            }
+        }
+
+        // ====================================================================
+        // TOP PANEL - MENU BAR
+        // ====================================================================
+        // Hidden in focus mode - F11 or Esc brings it back
+        if !self.focus_mode {
+            self.menu_bar(ctx);
+        }
+        // Shown even in focus mode: a failing autosave shouldn't go unseen
+        self.autosave_warning_banner(ctx);
+        if !self.focus_mode {
+            let labels = self.tab_labels();
+            let action = egui::TopBottomPanel::top("tab_bar")
+                .show(ctx, |ui| tabs::tab_bar(ui, &labels, self.active_tab))
+                .inner;
+            if let Some(action) = action {
+                self.apply_tab_action(ctx, action);
+            }
+        }
+        if self.show_find_bar {
+            self.find_bar(ctx);
+        }
+        if self.read_aloud.is_some() {
+            self.read_aloud_bar(ctx);
+        }
+
+        // ====================================================================
+        // BOTTOM PANEL - STATUS BAR
+        // ====================================================================
+        let now = ctx.input(|i| i.time);
+        self.refresh_problems(now);
+        self.track_progress(now);
+        self.track_sprint(ctx, now);
+
+        // In focus mode the status bar stays hidden, except for a few
+        // seconds after the status message changes
+        if self.status_message != self.last_status {
+            self.last_status = self.status_message.clone();
+            self.status_visible_until = now + STATUS_FLASH_SECONDS;
+        }
+        if !self.focus_mode {
+            self.status_bar(ctx);
+        } else if now < self.status_visible_until {
+            self.status_bar(ctx);
+            // egui only repaints on input; wake up to hide the bar again
+            ctx.request_repaint_after(std::time::Duration::from_secs_f64(
+                self.status_visible_until - now,
+            ));
+        }
+
+        // ====================================================================
+        // LEFT PANEL - OUTLINE
+        // ====================================================================
+        // Side panels must be added before the CentralPanel, which takes
+        // whatever space is left over
+        if self.show_outline && !self.focus_mode {
+            self.refresh_structure();
+            self.refresh_project_counts();
+
+            egui::SidePanel::left("outline_panel")
+                .resizable(true)
+                .default_width(220.0)
+                .show(ctx, |ui| match &self.project {
+                    Some(open) => {
+                        ui.heading(open.title());
+                        let active = self.current_file_path.as_deref();
+                        if let Some(action) = project::show(ui, open, active, &self.structure) {
+                            self.apply_project_action(ctx, action);
+                        }
+                    }
+                    None => {
+                        ui.heading("Outline");
+                        ui.separator();
+                        if let Some(line) = outline::show(ui, &self.structure) {
+                            self.pending_jump = Some(line);
+                        }
+                    }
+                });
+        }
 
         // ====================================================================
-        // BOTTOM PANEL - STATUS BAR
+        // RIGHT PANEL - CHARACTERS
         // ====================================================================
-        egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
-            // Add some padding around the status message
-            ui.add_space(4.0);
+        if self.show_characters_panel && !self.focus_mode {
+            self.refresh_characters();
 
-            // Display the status message
-            ui.horizontal(|ui| {
-                ui.label("Status:");
-                ui.label(&self.status_message);
-            });
+            let output = egui::SidePanel::right("characters_panel")
+                .resizable(true)
+                .default_width(240.0)
+                .show(ctx, |ui| {
+                    ui.heading("Characters");
+                    let profiles = self.project.as_mut().map(|p| &mut p.project.characters);
+                    characters::panel(
+                        ui,
+                        &self.characters.rows,
+                        profiles,
+                        &mut self.new_character_name,
+                    )
+                })
+                .inner;
+            if let Some(line) = output.jump {
+                self.pending_jump = Some(line);
+            }
+            if output.save {
+                self.write_project();
+            }
+        }
 
-            ui.add_space(4.0);
-        });
+        // ====================================================================
+        // RIGHT PANEL - SCENE INSPECTOR
+        // ====================================================================
+        if self.show_scene_inspector && !self.focus_mode {
+            self.scene_inspector(ctx);
+        }
+
+        // ====================================================================
+        // RIGHT PANEL - COMMENTS
+        // ====================================================================
+        if self.show_comments_panel && !self.focus_mode {
+            self.refresh_comments();
+            let clicked = egui::SidePanel::right("comments_panel")
+                .resizable(true)
+                .default_width(220.0)
+                .show(ctx, |ui| {
+                    ui.heading("Comments");
+                    ui.separator();
+                    comments::show(ui, &self.comments)
+                })
+                .inner;
+            if let Some(line) = clicked {
+                self.pending_jump = Some(line);
+            }
+        }
+
+        // ====================================================================
+        // RIGHT PANEL - BOOKMARKS
+        // ====================================================================
+        if self.show_bookmarks_panel && !self.focus_mode {
+            let action = egui::SidePanel::right("bookmarks_panel")
+                .resizable(true)
+                .default_width(220.0)
+                .show(ctx, |ui| {
+                    ui.heading("Bookmarks");
+                    ui.separator();
+                    bookmarks::show(ui, self.bookmarks())
+                })
+                .inner;
+            match action {
+                Some(BookmarkAction::Jump(line)) => self.pending_jump = Some(line),
+                Some(BookmarkAction::Remove(index)) => {
+                    let line = self.bookmarks()[index].line;
+                    self.toggle_bookmark(line);
+                }
+                None => {}
+            }
+        }
+
+        // ====================================================================
+        // RIGHT PANEL - NAMED SNAPSHOTS
+        // ====================================================================
+        if self.show_named_snapshots && !self.focus_mode {
+            self.named_snapshots_panel(ctx);
+        }
+
+        // ====================================================================
+        // RIGHT PANEL - GIT
+        // ====================================================================
+        if self.show_git_panel && !self.focus_mode {
+            self.git_panel(ctx);
+        }
+
+        // ====================================================================
+        // RIGHT PANEL - FIND IN PROJECT
+        // ====================================================================
+        if self.show_project_search && !self.focus_mode {
+            self.project_search_panel(ctx);
+        }
+
+        // ====================================================================
+        // RIGHT PANEL - FORMATTED PREVIEW
+        // ====================================================================
+        // Added last, so it sits right next to the editor
+        if self.show_formatted_preview && !self.focus_mode {
+            self.refresh_formatted_blocks();
+            let clicked = egui::SidePanel::right("formatted_preview_panel")
+                .resizable(true)
+                .default_width(420.0)
+                .show(ctx, |ui| {
+                    ui.heading("Preview");
+                    ui.separator();
+                    formatted::show(ui, &self.formatted_blocks)
+                })
+                .inner;
+            if let Some(line) = clicked {
+                self.pending_jump = Some(line);
+            }
+        }
 
         // ====================================================================
         // CENTRAL PANEL - TEXT EDITOR
         // ====================================================================
         // CentralPanel fills all remaining space after top/bottom panels
+        //
+        // The caret and where it is on screen, for the character name list
+        let mut completion_caret = None;
         egui::CentralPanel::default().show(ctx, |ui| {
-            // Lock the mutex to get access to the text content
-            // `.lock()` blocks until we can acquire the lock
-            // `.unwrap()` panics if the mutex is poisoned
-            let mut text = self.text_content.lock().unwrap();
+            if self.show_corkboard {
+                self.corkboard(ui);
+                return;
+            }
+
+            // Borrow the text mutably for the rest of the panel - only this
+            // one field, so other fields of self stay usable alongside it
+            let custom_font_ready = self.custom_font_ready(ui.ctx());
+            let bookmark_lines: Vec<usize> = self.bookmarks().iter().map(|b| b.line).collect();
+            let locked = self.locked();
+            let text = &mut self.text_content;
+
+            // A fixed Id lets us read and write the editor's cursor state
+            // from outside the widget (egui keeps it in ctx memory)
+            let editor_id = egui::Id::new(EDITOR_ID);
+
+            // Focus mode narrows the editor to a centered column; normally
+            // it takes the whole width
+            let focus = self.focus_mode.then_some(self.settings.focus);
+
+            // The split view's second editor (never in focus mode)
+            let second_id = egui::Id::new(SECOND_EDITOR_ID);
+            let split = self.split_editor.filter(|_| focus.is_none());
+            let editor_ids = if split.is_some() {
+                &[editor_id, second_id][..]
+            } else {
+                &[editor_id][..]
+            };
+
+            // Seconds since the app started - the history uses it to group
+            // keystrokes into undo steps
+            let now = ui.input(|i| i.time);
+
+            // A paste is its own undo step: snapshot what came before it now,
+            // and what it produced after the TextEdit has applied it
+            let pasting = ui
+                .ctx()
+                .memory(|m| editor_ids.iter().any(|&id| m.has_focus(id)))
+                && ui.input(|i| i.events.iter().any(|e| matches!(e, egui::Event::Paste(_))));
+            if pasting {
+                self.history.checkpoint(text);
+                if self.settings.cleanup.clean_on_paste {
+                    clean_pasted_text(ui.ctx(), &self.settings.cleanup);
+                }
+            }
+
+            // Give structure-aware editing first look at this frame's key
+            // presses, before the TextEdit below consumes them
+            for &id in editor_ids {
+                if !locked
+                    && apply_structure_editing(
+                        ui.ctx(),
+                        id,
+                        text,
+                        &self.settings,
+                        &self.snippets,
+                        &mut self.history,
+                    )
+                {
+                    self.dirty = true;
+                    self.edit_generation += 1;
+                    self.history.edited(now);
+                }
+            }
+
+            // A jump requested by the outline or the Problems list: move the caret to the start
+            // of the target line now, and scroll to it once the TextEdit
+            // has been laid out below
+            let jump_to = self.pending_jump.take().map(|line| {
+                let index = editing::line_start_char(text, line);
+                let mut state = egui::TextEdit::load_state(ui.ctx(), editor_id).unwrap_or_default();
+                let cursor = egui::text::CCursor::new(index);
+                state
+                    .cursor
+                    .set_char_range(Some(egui::text::CCursorRange::one(cursor)));
+                state.store(ui.ctx(), editor_id);
+                ui.ctx().memory_mut(|m| m.request_focus(editor_id));
+                cursor
+            });
+
+            // A match found by the find bar: select it, and scroll to it
+            // below like a jump
+            let select_to = self.pending_selection.take().map(|range| {
+                let mut state = egui::TextEdit::load_state(ui.ctx(), editor_id).unwrap_or_default();
+                let end = egui::text::CCursor::new(range.end);
+                state
+                    .cursor
+                    .set_char_range(Some(egui::text::CCursorRange::two(
+                        egui::text::CCursor::new(range.start),
+                        end,
+                    )));
+                state.store(ui.ctx(), editor_id);
+                end
+            });
+
+            // Find bar matches to highlight, the current one more strongly;
+            // while reading aloud, the sentence being read instead
+            let reading = self
+                .read_aloud
+                .as_ref()
+                .and_then(|r| r.highlight(self.edit_generation));
+            let highlights: &[Range<usize>] = match &reading {
+                Some(sentence) => std::slice::from_ref(sentence),
+                None if self.show_find_bar => &self.find.matches,
+                None => &[],
+            };
+            let current_match = reading.clone().or_else(|| self.find.current_match());
+            // Scroll to each sentence as reading gets to it
+            let read_to = reading
+                .clone()
+                .filter(|_| self.read_aloud.as_mut().is_some_and(|r| r.take_moved()))
+                .map(|sentence| {
+                    egui::text::CCursor::new(editing::byte_to_char(text, sentence.start))
+                });
+
+            // Typing into a document that's about to be replaced by a load
+            // would silently lose the keystrokes, so the editor is locked
+            // (as it is in read-only mode)
+            let loading = matches!(self.file_io.state(), IoState::Loading(_));
+
+            // The editor's own font, independent of the menus and panels
+            let font = self.settings.editor.font_id(custom_font_ready);
+            let line_height = self
+                .settings
+                .editor
+                .line_height(ui.fonts(|f| f.row_height(&font)));
+            let word_wrap = self.settings.editor.word_wrap;
+
+            // Without wrapping, long lines scroll sideways instead
+            let mut scroll_area = if word_wrap {
+                egui::ScrollArea::vertical()
+            } else {
+                egui::ScrollArea::both()
+            };
+
+            // Session restore: put the cursor back where it was and start
+            // the scroll area at the saved offset
+            if let Some((cursor, offset)) = self.pending_view.take() {
+                if let Some(index) = cursor {
+                    let index = index.min(text.chars().count());
+                    let mut state =
+                        egui::TextEdit::load_state(ui.ctx(), editor_id).unwrap_or_default();
+                    state
+                        .cursor
+                        .set_char_range(Some(egui::text::CCursorRange::one(
+                            egui::text::CCursor::new(index),
+                        )));
+                    state.store(ui.ctx(), editor_id);
+                    ui.ctx().memory_mut(|m| m.request_focus(editor_id));
+                }
+                if let Some(offset) = offset {
+                    scroll_area = scroll_area.vertical_scroll_offset(offset);
+                }
+            }
+
+            // The paragraph the caret was in last frame stays bright when
+            // focus mode dims the rest
+            let lit_paragraph = focus
+                .filter(|f| f.dim_paragraphs)
+                .map(|_| paragraph_at(text, self.editor_cursor));
+
+            // A layouter decides how the TextEdit breaks lines: at the
+            // available width, or (wrap_width = infinity) never
+            let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                let mut format = egui::TextFormat::simple(font.clone(), ui.visuals().text_color());
+                format.line_height = line_height;
+                let mut job = highlighted_job(
+                    text,
+                    format,
+                    highlights,
+                    current_match.as_ref(),
+                    ui.visuals(),
+                );
+                color_comments(&mut job, text, theme::comment_color(ui.visuals()));
+                if let Some(lit) = &lit_paragraph {
+                    dim_outside(&mut job, lit);
+                }
+                job.wrap.max_width = if word_wrap { wrap_width } else { f32::INFINITY };
+                job.wrap.break_anywhere = false;
+                ui.fonts(|f| f.layout_job(job))
+            };
+
+            // Split view: a second editor on the same text, in a panel
+            // taking half the area to start with. It has its own caret and
+            // scroll position; its edits go into the same undo history, and
+            // whichever editor has focus is "the caret" for the status bar
+            // and commands. Jumps and find still go to the first editor.
+            if let Some(split) = split {
+                let mut second_editor = |ui: &mut egui::Ui| {
+                    let scroll_area = if word_wrap {
+                        egui::ScrollArea::vertical()
+                    } else {
+                        egui::ScrollArea::both()
+                    };
+                    scroll_area
+                        .id_salt("second_editor_scroll")
+                        .auto_shrink(false)
+                        .show(ui, |ui| {
+                            let output = egui::TextEdit::multiline(&mut *text)
+                                .id(second_id)
+                                .desired_width(f32::INFINITY)
+                                .lock_focus(true)
+                                .font(font.clone())
+                                .layouter(&mut layouter)
+                                .margin(egui::Margin {
+                                    left: BOOKMARK_GUTTER,
+                                    right: 4.0,
+                                    top: 2.0,
+                                    bottom: 2.0,
+                                })
+                                .interactive(!loading && !locked)
+                                .show(ui);
+                            paint_bookmarks(ui, &output, &bookmark_lines);
+                            if output.response.secondary_clicked() {
+                                self.context_word = word_under_pointer(&output, text);
+                            }
+                            if let Some(kind) =
+                                editor_context_menu(&output.response, self.context_word.as_ref())
+                            {
+                                self.lookup =
+                                    self.context_word.take().map(|l| Lookup { kind, ..l });
+                            }
+
+                            if output.response.changed() {
+                                self.dirty = true;
+                                self.edit_generation += 1;
+                                if pasting {
+                                    self.history.checkpoint(text);
+                                } else {
+                                    self.history.edited(now);
+                                }
+                            }
+                            if let Some(range) =
+                                output.cursor_range.filter(|_| output.response.has_focus())
+                            {
+                                self.editor_cursor = range.primary.ccursor.index;
+                            }
+                        });
+                };
+                match split {
+                    EditorSplit::SideBySide => {
+                        egui::SidePanel::right("second_editor_side_panel")
+                            .resizable(true)
+                            .default_width(ui.available_width() / 2.0)
+                            .show_inside(ui, |ui| second_editor(ui));
+                    }
+                    EditorSplit::TopAndBottom => {
+                        egui::TopBottomPanel::bottom("second_editor_bottom_panel")
+                            .resizable(true)
+                            .default_height(ui.available_height() / 2.0)
+                            .show_inside(ui, |ui| second_editor(ui));
+                    }
+                }
+            }
 
             // Create a scrollable area that fills the available space
-            egui::ScrollArea::vertical().show(ui, |ui| {
+            let scroll_output = scroll_area.show(ui, |ui| {
                 // TextEdit::multiline creates a text editor widget
                 //
-                // `&mut *text` explanation:
-                // - `text` is a MutexGuard<String>
-                // - `*text` dereferences it to get &String
-                // - `&mut *text` creates a mutable reference &mut String
+                // `&mut *text` reborrows the &mut String, so `text` can still
+                // be used after the editor is done with it
                 //
-                // This is how we modify the string through the mutex guard
-                ui.add(
-                    egui::TextEdit::multiline(&mut *text)
-                        // Make the editor fill all available space
-                        .desired_width(f32::INFINITY)
-                        .desired_rows(30)
-                        // Use a monospace font (good for code/writing)
-                        .font(egui::TextStyle::Monospace), // Show line numbers? (commented out for now)
-                                                           // .code_editor()
-                );
-            });
+                // .show() (instead of ui.add) gives us the laid-out galley,
+                // which we need to find where a line ends up on screen
+                //
+                // The editor is built once; focus mode only changes its
+                // width and the layout it sits in
+                let editor = egui::TextEdit::multiline(&mut *text)
+                    .id(editor_id)
+                    // Fill all available space, or the focus mode column
+                    .desired_width(focus.map_or(f32::INFINITY, |f| f.column_width))
+                    .desired_rows(30)
+                    // Tab indents or expands a snippet rather than moving
+                    // the focus to the next widget
+                    .lock_focus(true)
+                    // The family and size chosen in View / Preferences
+                    .font(font.clone())
+                    .layouter(&mut layouter)
+                    // The left margin is the gutter bookmarks are marked in
+                    .margin(egui::Margin {
+                        left: BOOKMARK_GUTTER,
+                        right: 4.0,
+                        top: 2.0,
+                        bottom: 2.0,
+                    })
+                    .interactive(!loading && !locked);
+                // Show line numbers? (commented out for now)
+                // .code_editor()
+
+                let output = if focus.is_some() {
+                    // top_down(Center) puts the column in the middle, leaving
+                    // equal margins either side
+                    ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+                        editor.show(ui)
+                    })
+                    .inner
+                } else {
+                    editor.show(ui)
+                };
+
+                paint_bookmarks(ui, &output, &bookmark_lines);
 
-            // The MutexGuard is automatically dropped here (goes out of scope)
-            // This releases the lock so other threads can access the text
+                // Right-click: Define / Synonyms for the word under the pointer
+                if output.response.secondary_clicked() {
+                    self.context_word = word_under_pointer(&output, text);
+                }
+                if let Some(kind) =
+                    editor_context_menu(&output.response, self.context_word.as_ref())
+                {
+                    self.lookup = self.context_word.take().map(|l| Lookup { kind, ..l });
+                }
+
+                // changed() is true on any frame where the user edited the text
+                if output.response.changed() {
+                    self.dirty = true;
+                    self.edit_generation += 1;
+                    if pasting {
+                        self.history.checkpoint(text);
+                    } else {
+                        self.history.edited(now);
+                    }
+                }
+                self.history.tick(text, now);
+
+                // Remembered for the session file (cursor_range is None
+                // while the editor doesn't have focus - keep the last one)
+                let previous_cursor = self.editor_cursor;
+                if let Some(range) = output.cursor_range {
+                    self.editor_cursor = range.primary.ccursor.index;
+                }
+                if let Some(range) = output
+                    .cursor_range
+                    .filter(|r| output.response.has_focus() && r.primary == r.secondary)
+                {
+                    let rect = output
+                        .galley
+                        .pos_from_ccursor(range.primary.ccursor)
+                        .translate(output.galley_pos.to_vec2());
+                    completion_caret = Some((range.primary.ccursor.index, rect.left_bottom()));
+                }
+                // The dimming was laid out for the old caret position
+                if lit_paragraph.is_some() && self.editor_cursor != previous_cursor {
+                    ui.ctx().request_repaint();
+                }
+
+                // Typewriter scrolling: whenever the caret moves, bring its
+                // line back to the middle of the window
+                if focus.is_some_and(|f| f.typewriter_scrolling) {
+                    // Room below the text, or the last lines could never
+                    // scroll up to the middle
+                    ui.add_space(ui.clip_rect().height() / 2.0);
+
+                    let moved = output.response.changed() || self.editor_cursor != previous_cursor;
+                    if let Some(range) = output.cursor_range.filter(|_| moved) {
+                        let rect = output
+                            .galley
+                            .pos_from_ccursor(range.primary.ccursor)
+                            .translate(output.galley_pos.to_vec2());
+                        ui.scroll_to_rect(rect, Some(egui::Align::Center));
+                    }
+                }
+
+                if let Some(cursor) = jump_to {
+                    // pos_from_ccursor gives the caret rectangle relative to
+                    // the galley; translate it to screen space and scroll there
+                    let rect = output
+                        .galley
+                        .pos_from_ccursor(cursor)
+                        .translate(output.galley_pos.to_vec2());
+                    ui.scroll_to_rect(rect, Some(egui::Align::TOP));
+                }
+                if let Some(cursor) = select_to.or(read_to) {
+                    let rect = output
+                        .galley
+                        .pos_from_ccursor(cursor)
+                        .translate(output.galley_pos.to_vec2());
+                    ui.scroll_to_rect(rect, Some(egui::Align::Center));
+                }
+            });
+            self.editor_scroll = scroll_output.state.offset.y;
         });
 
+        self.update_completion(ctx, completion_caret);
+        if let Some(completion) = &self.completion {
+            if let Some(index) = completion::show(ctx, self.completion_anchor, completion) {
+                self.accept_completion(ctx, index);
+            }
+        }
+
+        // ====================================================================
+        // FLOATING WINDOWS
+        // ====================================================================
+        // Windows float above the panels, so they're drawn after them
+        if self.show_trash_window {
+            self.trash_window(ctx);
+        }
+        if self.show_snapshots_window {
+            self.snapshots_window(ctx);
+        }
+        if self.show_characters_window {
+            self.characters_window(ctx);
+        }
+        if self.show_statistics_window {
+            self.statistics_window(ctx);
+        }
+        if self.show_style_window {
+            self.style_window(ctx);
+        }
+        if self.lookup.is_some() {
+            self.lookup_window(ctx);
+        }
+        if self.show_sprint_window {
+            self.sprint_window(ctx);
+        }
+        if self.show_goal_window {
+            self.goal_window(ctx);
+        }
+        if self.show_cleanup_window {
+            self.cleanup_window(ctx);
+        }
+        if self.show_print_window {
+            self.print_window(ctx);
+        }
+        if self.show_pdf_window {
+            self.pdf_window(ctx);
+        }
+        if self.show_project_window {
+            self.project_window(ctx);
+        }
+        if self.show_font_window {
+            self.font_window(ctx);
+        }
+        if self.show_compile_window {
+            self.compile_window(ctx);
+        }
+        if self.show_docx_window {
+            self.docx_window(ctx);
+        }
+        if self.show_progress_window {
+            self.progress_window(ctx);
+        }
+        if self.pending_import.is_some() {
+            self.import_preview_window(ctx);
+        }
+        if self.show_preferences {
+            self.preferences_window(ctx);
+        }
+        if self.go_to_query.is_some() {
+            self.go_to_window(ctx);
+        }
+        if self.snapshot_name.is_some() {
+            self.snapshot_prompt(ctx);
+        }
+        if self.template_name.is_some() {
+            self.template_prompt(ctx);
+        }
+        if self.comparison.is_some() {
+            self.compare_window(ctx);
+        }
+        if self.unlock_prompt.is_some() {
+            self.unlock_prompt(ctx);
+        }
+        if self.password_prompt.is_some() {
+            self.password_prompt(ctx);
+        }
+        if self.commit_message.is_some() {
+            self.commit_prompt(ctx);
+        }
+        if self.save_conflict.is_some() {
+            self.save_conflict_window(ctx);
+        }
+        if self.unsaved_prompt.is_some() {
+            self.unsaved_changes_window(ctx);
+        }
+        if !self.recovery_candidates.is_empty() {
+            self.recovery_window(ctx);
+        }
+        self.file_drop_overlay(ctx);
+
+        self.update_window_title(ctx);
+
         // ====================================================================
         // CONTINUOUS RENDERING
         // ====================================================================
@@ -269,6 +8458,481 @@ impl eframe::App for App {
         // This is useful for animations or background updates like autosave
         ctx.request_repaint();
     }
+
+    /// Called by eframe once, just before the app shuts down normally
+    ///
+    /// Marking the session clean is what tells the next launch there is
+    /// nothing to recover. After a crash this never runs, so the flag stays
+    /// false.
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.session.clean_exit = true;
+        self.session.cursor = Some(self.editor_cursor);
+        self.session.scroll_offset = Some(self.editor_scroll);
+        (self.session.tabs, self.session.active_tab) = self.session_tabs();
+        self.write_session();
+        // Also records the window geometry noted during the last frame
+        self.save_settings();
+        // The project settings and compile windows, and the characters
+        // panel, may still hold unwritten changes
+        if self.show_project_window || self.show_compile_window || self.show_characters_panel {
+            self.write_project();
+        }
+
+        // A sprint cut short by closing still counts
+        if let Some(sprint) = self.sprint {
+            self.finish_sprint(sprint.elapsed_by_clock(chrono::Local::now().naive_local()));
+        }
+
+        let total = self.daily_tally.total(self.writing_session.delta());
+        if total != self.progress_recorded.0 {
+            self.record_progress(self.daily_tally.date(), total);
+        }
+    }
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+/// Run this frame's key presses through editing::handle_key
+///
+/// If a key gets special treatment, we apply the edit ourselves, move the
+/// caret, and REMOVE the event from egui's input queue so the TextEdit never
+/// sees it. Only one key per frame is handled; the rest pass through.
+///
+/// Tab after a snippet's trigger puts the snippet in its place (see
+/// snippets.rs); any other Tab is left to the TextEdit, which indents.
+///
+/// Returns true if the text was changed.
+fn apply_structure_editing(
+    ctx: &egui::Context,
+    editor_id: egui::Id,
+    text: &mut String,
+    settings: &Settings,
+    snippets: &[Snippet],
+    history: &mut History,
+) -> bool {
+    let options = &settings.structure_editing;
+    // Only intercept keys meant for the editor
+    if !ctx.memory(|m| m.has_focus(editor_id)) {
+        return false;
+    }
+
+    let Some(mut state) = egui::TextEdit::load_state(ctx, editor_id) else {
+        return false;
+    };
+    let Some(range) = state.cursor.char_range() else {
+        return false;
+    };
+    // With a selection, typing replaces it - leave that to the TextEdit
+    if range.primary != range.secondary {
+        return false;
+    }
+    let caret = range.primary.index;
+
+    // Find the first event we might want to handle, remembering its
+    // position in the queue so we can remove exactly that one
+    let found = ctx.input(|i| {
+        i.events.iter().enumerate().find_map(|(index, event)| {
+            let key = match event {
+                egui::Event::Key {
+                    key: egui::Key::Enter,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } if modifiers.is_none() => EditKey::Enter,
+                egui::Event::Key {
+                    key: egui::Key::Backspace,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } if modifiers.is_none() => EditKey::Backspace,
+                egui::Event::Key {
+                    key: egui::Key::Tab,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } if modifiers.is_none() => {
+                    return snippets::expand(text, caret, snippets)
+                        .map(|command| (index, command, None));
+                }
+                egui::Event::Text(typed) if typed.chars().count() == 1 => {
+                    EditKey::Char(typed.chars().next()?)
+                }
+                _ => return None,
+            };
+            // A word the autocorrect table has, just finished by this key,
+            // is corrected first; the key itself is handled afterwards
+            let finishes_word = match key {
+                EditKey::Enter => true,
+                EditKey::Char(c) => autocorrect::ends_word(c),
+                EditKey::Backspace => false,
+            };
+            if finishes_word {
+                if let Some(correction) = autocorrect::correct(text, caret, &settings.autocorrect) {
+                    return Some((index, correction, Some(key)));
+                }
+            }
+            editing::handle_key(text, caret, key, options).map(|command| (index, command, None))
+        })
+    });
+
+    let Some((index, command, then)) = found else {
+        return false;
+    };
+
+    ctx.input_mut(|i| {
+        i.events.remove(index);
+    });
+    let caret = match then {
+        None => {
+            command.apply(text);
+            command.caret
+        }
+        // An autocorrection is an undo step of its own, so one undo brings
+        // back the word as it was typed
+        Some(key) => {
+            history.checkpoint(text);
+            command.apply(text);
+            let typed = autocorrect::type_after(text, command.caret, key, options);
+            typed.apply(text);
+            history.checkpoint(text);
+            typed.caret
+        }
+    };
+
+    let cursor = egui::text::CCursor::new(caret);
+    state
+        .cursor
+        .set_char_range(Some(egui::text::CCursorRange::one(cursor)));
+    state.store(ctx, editor_id);
+    true
+}
+
+/// The word under the pointer in an editor just right-clicked, to look up
+fn word_under_pointer(output: &egui::text_edit::TextEditOutput, text: &str) -> Option<Lookup> {
+    let pos = output.response.interact_pointer_pos()?;
+    let cursor = output.galley.cursor_from_pos(pos - output.galley_pos);
+    let range = thesaurus::word_at(text, cursor.ccursor.index)?;
+    Some(Lookup {
+        kind: LookupKind::Define,
+        word: text.chars().skip(range.start).take(range.len()).collect(),
+        range,
+        pos,
+    })
+}
+
+/// The editor's right-click menu: what to look up `word` in, if anything
+/// was picked
+fn editor_context_menu(response: &egui::Response, word: Option<&Lookup>) -> Option<LookupKind> {
+    let mut picked = None;
+    response.context_menu(|ui| {
+        let Some(word) = word else {
+            ui.label("No word here");
+            return;
+        };
+        if ui.button(format!("Define “{}”", word.word)).clicked() {
+            picked = Some(LookupKind::Define);
+            ui.close_menu();
+        }
+        if ui.button(format!("Synonyms for “{}”", word.word)).clicked() {
+            picked = Some(LookupKind::Synonyms);
+            ui.close_menu();
+        }
+    });
+    picked
+}
+
+/// Lay out the editor's text in one format, with find matches given a
+/// background: `current` in the selection color, the rest in a fainter one
+///
+/// The matches were found in the text as it was at the start of the frame;
+/// if the TextEdit has just changed it, any that no longer fit are skipped
+/// until the next frame finds them again.
+fn highlighted_job(
+    text: &str,
+    format: egui::TextFormat,
+    matches: &[Range<usize>],
+    current: Option<&Range<usize>>,
+    visuals: &egui::Visuals,
+) -> egui::text::LayoutJob {
+    let mut job = egui::text::LayoutJob::default();
+    let mut copied_to = 0;
+
+    for range in matches {
+        let fits = range.start >= copied_to
+            && range.end <= text.len()
+            && text.is_char_boundary(range.start)
+            && text.is_char_boundary(range.end);
+        if !fits {
+            continue;
+        }
+        let background = if Some(range) == current {
+            visuals.selection.bg_fill
+        } else {
+            visuals.selection.bg_fill.gamma_multiply(0.4)
+        };
+        if copied_to < range.start {
+            job.append(&text[copied_to..range.start], 0.0, format.clone());
+        }
+        job.append(
+            &text[range.clone()],
+            0.0,
+            egui::TextFormat {
+                background,
+                ..format.clone()
+            },
+        );
+        copied_to = range.end;
+    }
+    job.append(&text[copied_to..], 0.0, format);
+
+    job
+}
+
+/// Byte range of the paragraph holding the character at `char_index`
+///
+/// Paragraphs are runs of non-blank lines, as in the exports. On a blank
+/// line the range is just that line.
+fn paragraph_at(text: &str, char_index: usize) -> Range<usize> {
+    let caret = text
+        .char_indices()
+        .nth(char_index)
+        .map_or(text.len(), |(i, _)| i);
+
+    let mut start = 0;
+    let mut in_paragraph = false;
+    for line in text.split_inclusive('\n') {
+        let line_start = line.as_ptr() as usize - text.as_ptr() as usize;
+        let line_end = line_start + line.len();
+        let blank = line.trim().is_empty();
+        if blank {
+            if in_paragraph && caret < line_start {
+                return start..line_start;
+            }
+            in_paragraph = false;
+            if caret >= line_start && caret < line_end {
+                return line_start..line_end;
+            }
+        } else if !in_paragraph {
+            in_paragraph = true;
+            start = line_start;
+        }
+    }
+    if in_paragraph {
+        start..text.len()
+    } else {
+        text.len()..text.len()
+    }
+}
+
+/// Mark the bookmarked lines (1-based, in order) in the editor's gutter
+///
+/// A line is marked on its first row, when wrapping spreads it over
+/// several.
+fn paint_bookmarks(ui: &egui::Ui, output: &egui::text_edit::TextEditOutput, lines: &[usize]) {
+    if lines.is_empty() {
+        return;
+    }
+    let color = ui.visuals().selection.stroke.color;
+    let x = output.galley_pos.x - BOOKMARK_GUTTER / 2.0;
+    let mut line = 1;
+    let mut first_row = true;
+    for row in &output.galley.rows {
+        if first_row && lines.binary_search(&line).is_ok() {
+            let y = output.galley_pos.y + row.rect.center().y;
+            ui.painter()
+                .circle_filled(egui::pos2(x, y), BOOKMARK_GUTTER / 4.0, color);
+        }
+        first_row = row.ends_with_newline;
+        if row.ends_with_newline {
+            line += 1;
+        }
+    }
+}
+
+/// Draw the `[[...]]` comments in `text` (laid out as `job`) in `color`,
+/// in italics
+///
+/// Sections are split at the edges of each comment, as in dim_outside.
+fn color_comments(job: &mut egui::text::LayoutJob, text: &str, color: egui::Color32) {
+    let comments = parser::comment_ranges(text);
+    if comments.is_empty() {
+        return;
+    }
+    let mut sections = Vec::with_capacity(job.sections.len() + 2 * comments.len());
+    for section in job.sections.drain(..) {
+        let range = section.byte_range.clone();
+        let cuts = comments
+            .iter()
+            .flat_map(|c| [c.start, c.end])
+            .filter(|&cut| cut > range.start && cut < range.end);
+        let mut from = range.start;
+        for to in cuts.chain([range.end]) {
+            let mut piece = egui::text::LayoutSection {
+                byte_range: from..to,
+                leading_space: if from == range.start {
+                    section.leading_space
+                } else {
+                    0.0
+                },
+                format: section.format.clone(),
+            };
+            if comments.iter().any(|c| c.start <= from && from < c.end) {
+                piece.format.color = color;
+                piece.format.italics = true;
+            }
+            sections.push(piece);
+            from = to;
+        }
+    }
+    job.sections = sections;
+}
+
+/// Fade the text of `job` outside `lit` (a byte range), for focus mode
+///
+/// Sections that cross the edge of the range are split there.
+fn dim_outside(job: &mut egui::text::LayoutJob, lit: &Range<usize>) {
+    let mut sections = Vec::with_capacity(job.sections.len() + 2);
+    for section in job.sections.drain(..) {
+        let range = section.byte_range.clone();
+        let cuts = [lit.start, lit.end]
+            .into_iter()
+            .filter(|&cut| cut > range.start && cut < range.end);
+        let mut from = range.start;
+        for to in cuts.chain([range.end]) {
+            let mut piece = egui::text::LayoutSection {
+                byte_range: from..to,
+                // Only the first piece keeps the leading space
+                leading_space: if from == range.start {
+                    section.leading_space
+                } else {
+                    0.0
+                },
+                format: section.format.clone(),
+            };
+            if from < lit.start || from >= lit.end {
+                piece.format.color = piece.format.color.gamma_multiply(0.3);
+            }
+            sections.push(piece);
+            from = to;
+        }
+    }
+    job.sections = sections;
+}
+
+/// Run this frame's pasted text through text_utils::clean_up before the
+/// TextEdit inserts it
+///
+/// Like apply_structure_editing, this works on egui's input queue: the
+/// Paste event's text is replaced in place, so the TextEdit pastes the
+/// cleaned version with its usual caret and selection handling.
+fn clean_pasted_text(ctx: &egui::Context, options: &text_utils::CleanupOptions) {
+    ctx.input_mut(|i| {
+        for event in &mut i.events {
+            if let egui::Event::Paste(pasted) = event {
+                *pasted = text_utils::clean_up(pasted, options).text;
+            }
+        }
+    });
+}
+
+/// Read the previous run's session file
+///
+/// A missing file (first launch) or a malformed one both mean "no previous
+/// session": nothing is restored or offered, and the app starts normally.
+fn load_previous_session(session_path: &std::path::Path) -> Option<storage::SessionInfo> {
+    match storage::load_session(session_path) {
+        Ok(previous) => previous,
+        Err(e) => {
+            eprintln!("Failed to read previous session: {}", e);
+            None
+        }
+    }
+}
+
+/// List the autosaves from the previous session worth offering
+///
+/// Any problem looking for them just means nothing is offered - the user
+/// can still find the autosave files on disk.
+fn find_recovery_candidates(previous: &storage::SessionInfo) -> Vec<storage::RecoveryCandidate> {
+    match storage::get_autosave_dir() {
+        Ok(dir) => storage::find_recovery_candidates(previous, &dir),
+        Err(e) => {
+            eprintln!("Failed to check for autosaves: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Format Unix seconds as local date and time: "2025-10-26 14:05"
+fn format_timestamp(secs: u64) -> String {
+    chrono::DateTime::from_timestamp(secs as i64, 0)
+        .map(|t| {
+            t.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_else(|| secs.to_string())
+}
+
+/// Format a count with thousands separators: 1234567 → "1,234,567"
+pub(crate) fn format_count(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        // A comma goes before every group of three digits counted from the right
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// A file size in bytes, KB or MB: 512 → "512 bytes", 20480 → "20.0 KB"
+fn format_size(bytes: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = 1024 * KB;
+    if bytes < KB {
+        format!("{} bytes", bytes)
+    } else if bytes < MB {
+        format!("{:.1} KB", bytes as f64 / KB as f64)
+    } else {
+        format!("{:.1} MB", bytes as f64 / MB as f64)
+    }
+}
+
+/// A signed word count with its sign and thousands separators: "+1,234",
+/// "-56", "0"
+fn format_delta(n: i64) -> String {
+    let magnitude = format_count(n.unsigned_abs() as usize);
+    match n.signum() {
+        1 => format!("+{}", magnitude),
+        -1 => format!("-{}", magnitude),
+        _ => magnitude,
+    }
+}
+
+/// Turn a number of seconds into a short "how long ago" string
+///
+/// 45 → "just now", 600 → "10 min ago", 7200 → "2 h ago", 259200 → "3 days ago"
+fn format_age(seconds: u64) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+
+    if seconds < MINUTE {
+        String::from("just now")
+    } else if seconds < HOUR {
+        format!("{} min ago", seconds / MINUTE)
+    } else if seconds < DAY {
+        format!("{} h ago", seconds / HOUR)
+    } else if seconds < 2 * DAY {
+        String::from("1 day ago")
+    } else {
+        format!("{} days ago", seconds / DAY)
+    }
 }
 
 // ============================================================================
@@ -0,0 +1,433 @@
+//! FILE: src/autocorrect.rs
+//!
+//! Autocorrect: the user's table of replacements ("teh" → "the", "MC" → the
+//! protagonist's name), applied as a word is finished.
+//!
+//! A word is finished by typing a space, Enter or punctuation after it. If
+//! the word just before the caret is in the table, it's replaced before
+//! the typed character goes in. App makes the correction an undo step of
+//! its own, so one Ctrl+Z brings back what was typed.
+//!
+//! MATCHING:
+//! An entry matches the word exactly. An all-lowercase entry also matches
+//! the word capitalized or in capitals, and the replacement follows suit:
+//! with "teh" → "the", "Teh" becomes "The" and "TEH" becomes "THE". An
+//! entry with capitals in it ("MC") matches only as written.
+//!
+//! THE TABLE AS A FILE:
+//! Preferences can export the table and import one: a text file with one
+//! replacement per line, the typed word and its replacement separated by a
+//! tab. Blank lines and lines starting with `#` are skipped. Imported
+//! entries replace the ones with the same typed word and add the rest.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Returning an editing::EditCommand, the same as other typing behaviors
+//! - Building an error message that names the line it's about
+
+use crate::editing::{self, EditCommand, EditKey, StructureEditing};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// One entry of the table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Replacement {
+    /// The word as typed
+    pub from: String,
+    /// What it's replaced with
+    pub to: String,
+}
+
+/// Whether autocorrect is on, and its table (saved in Settings)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutocorrectOptions {
+    pub enabled: bool,
+    pub replacements: Vec<Replacement>,
+}
+
+impl Default for AutocorrectOptions {
+    /// On, with a few common typos
+    fn default() -> Self {
+        let pairs = [
+            ("teh", "the"),
+            ("hte", "the"),
+            ("adn", "and"),
+            ("taht", "that"),
+            ("recieve", "receive"),
+            ("wierd", "weird"),
+            ("seperate", "separate"),
+            ("definately", "definitely"),
+            ("occured", "occurred"),
+            ("untill", "until"),
+        ];
+        Self {
+            enabled: true,
+            replacements: pairs
+                .iter()
+                .map(|(from, to)| Replacement {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Does typing `c` finish the word before it?
+pub fn ends_word(c: char) -> bool {
+    c.is_whitespace()
+        || matches!(
+            c,
+            '.' | ',' | ';' | ':' | '!' | '?' | ')' | ']' | '}' | '"' | '”' | '»' | '…' | '—'
+        )
+}
+
+/// The correction of the word just before char index `caret`, if the
+/// table has it
+///
+/// The edit replaces the word only; the character that finished it is
+/// left for the caller to put in.
+pub fn correct(text: &str, caret: usize, options: &AutocorrectOptions) -> Option<EditCommand> {
+    if !options.enabled {
+        return None;
+    }
+    let before = &text[..editing::char_to_byte(text, caret)];
+    // The word: back to the last space, less any opening punctuation
+    // ("“teh" is "teh")
+    let start = before.rfind(char::is_whitespace).map_or(0, |i| {
+        i + before[i..].chars().next().map_or(1, char::len_utf8)
+    });
+    let word = before[start..].trim_start_matches(|c: char| !c.is_alphanumeric());
+    if word.is_empty() {
+        return None;
+    }
+
+    let to = options
+        .replacements
+        .iter()
+        .find_map(|r| replacement_for(word, r))?;
+    let length = word.chars().count();
+    Some(EditCommand {
+        range: caret - length..caret,
+        caret: caret - length + to.chars().count(),
+        insert: to,
+    })
+}
+
+/// The key that finished a corrected word, typed after the correction:
+/// as structure editing handles it, or else as its own character
+pub fn type_after(
+    text: &str,
+    caret: usize,
+    key: EditKey,
+    options: &StructureEditing,
+) -> EditCommand {
+    editing::handle_key(text, caret, key, options).unwrap_or_else(|| {
+        let insert = match key {
+            EditKey::Char(c) => c.to_string(),
+            EditKey::Enter => String::from("\n"),
+            EditKey::Backspace => {
+                unreachable!("Backspace finishes no word, so never follows a correction")
+            }
+        };
+        EditCommand {
+            range: caret..caret,
+            caret: caret + 1,
+            insert,
+        }
+    })
+}
+
+/// What `word` becomes under `replacement` (see module docs), if it matches
+fn replacement_for(word: &str, replacement: &Replacement) -> Option<String> {
+    let from = replacement.from.trim();
+    if from.is_empty() {
+        return None;
+    }
+    if word == from {
+        return Some(replacement.to.clone());
+    }
+    if from.chars().any(char::is_uppercase) || !word.eq_ignore_ascii_case(from) {
+        return None;
+    }
+    let capitalized = word.chars().next().is_some_and(char::is_uppercase);
+    capitalized.then(|| match_case(word, &replacement.to))
+}
+
+/// `to`, in capitals if `word` is, or capitalized if `word` is
+///
+/// A single capital letter ("I", "A") counts as capitalized, not capitals.
+pub fn match_case(word: &str, to: &str) -> String {
+    let all_caps = word.chars().count() > 1 && !word.chars().any(char::is_lowercase);
+    if all_caps {
+        return to.to_uppercase();
+    }
+    let mut chars = to.chars();
+    match (word.chars().next(), chars.next()) {
+        (Some(initial), Some(first)) if initial.is_uppercase() => {
+            first.to_uppercase().chain(chars).collect()
+        }
+        _ => to.to_string(),
+    }
+}
+
+/// The table as a file (see module docs)
+pub fn to_table(replacements: &[Replacement]) -> String {
+    let mut out = String::from("# Autocorrect replacements: typed word, a tab, replacement\n");
+    for r in replacements {
+        if !r.from.trim().is_empty() {
+            out.push_str(&format!("{}\t{}\n", r.from.trim(), r.to));
+        }
+    }
+    out
+}
+
+/// Read a table file (see module docs)
+///
+/// Err naming the first line that isn't a word, a tab and a replacement.
+pub fn parse_table(text: &str) -> Result<Vec<Replacement>> {
+    let mut replacements = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((from, to)) = line.split_once('\t') else {
+            bail!(
+                "Line {}: expected the typed word, a tab, then its replacement",
+                index + 1
+            );
+        };
+        let from = from.trim();
+        if from.is_empty() || from.contains(char::is_whitespace) {
+            bail!("Line {}: the typed word must be a single word", index + 1);
+        }
+        replacements.push(Replacement {
+            from: from.to_string(),
+            to: to.trim().to_string(),
+        });
+    }
+    Ok(replacements)
+}
+
+/// Add `imported` to `replacements`, replacing entries for the same typed
+/// word; returns how many entries were added or changed
+pub fn merge(replacements: &mut Vec<Replacement>, imported: Vec<Replacement>) -> usize {
+    let mut changed = 0;
+    for new in imported {
+        match replacements.iter_mut().find(|r| r.from.trim() == new.from) {
+            Some(existing) if existing.to == new.to => {}
+            Some(existing) => {
+                existing.to = new.to;
+                changed += 1;
+            }
+            None => {
+                replacements.push(new);
+                changed += 1;
+            }
+        }
+    }
+    changed
+}
+
+/// What the Preferences table asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableAction {
+    Import,
+    Export,
+}
+
+/// Draw the Preferences section: the on/off switch and the table
+pub fn show(ui: &mut egui::Ui, options: &mut AutocorrectOptions) -> Option<TableAction> {
+    let mut action = None;
+    ui.checkbox(&mut options.enabled, "Correct words as I type");
+    egui::CollapsingHeader::new(format!("Replacements ({})", options.replacements.len()))
+        .id_salt("autocorrect_replacements")
+        .show(ui, |ui| {
+            let mut remove = None;
+            egui::ScrollArea::vertical()
+                .max_height(180.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("autocorrect_table")
+                        .num_columns(3)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Typed");
+                            ui.strong("Replace with");
+                            ui.end_row();
+                            for (index, r) in options.replacements.iter_mut().enumerate() {
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut r.from).desired_width(100.0),
+                                );
+                                ui.add(egui::TextEdit::singleline(&mut r.to).desired_width(180.0));
+                                if ui.small_button("🗑").on_hover_text("Remove").clicked() {
+                                    remove = Some(index);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+            if let Some(index) = remove {
+                options.replacements.remove(index);
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Add").clicked() {
+                    options.replacements.push(Replacement {
+                        from: String::new(),
+                        to: String::new(),
+                    });
+                }
+                if ui.button("Import…").clicked() {
+                    action = Some(TableAction::Import);
+                }
+                if ui
+                    .add_enabled(
+                        !options.replacements.is_empty(),
+                        egui::Button::new("Export…"),
+                    )
+                    .clicked()
+                {
+                    action = Some(TableAction::Export);
+                }
+            });
+        });
+    action
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Type `key` at the end of `text` the way the editor does: the
+    /// correction (if any), then the key; the text afterwards, and the caret
+    fn finish(text: &str, key: EditKey, options: &AutocorrectOptions) -> (String, usize) {
+        let mut text = text.to_string();
+        let mut caret = text.chars().count();
+        if let Some(correction) = correct(&text, caret, options) {
+            correction.apply(&mut text);
+            caret = correction.caret;
+        }
+        let typed = type_after(&text, caret, key, &StructureEditing::default());
+        typed.apply(&mut text);
+        (text, typed.caret)
+    }
+
+    fn table(pairs: &[(&str, &str)]) -> AutocorrectOptions {
+        AutocorrectOptions {
+            enabled: true,
+            replacements: pairs
+                .iter()
+                .map(|(from, to)| Replacement {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn the_finishing_key_follows_the_correction() {
+        let options = AutocorrectOptions::default();
+        assert_eq!(
+            finish("I saw teh", EditKey::Char(' '), &options),
+            ("I saw the ".into(), 10)
+        );
+        assert_eq!(
+            finish("I saw teh", EditKey::Char('.'), &options),
+            ("I saw the.".into(), 10)
+        );
+        // A real line break, not a backslash and an n
+        assert_eq!(
+            finish("I saw teh", EditKey::Enter, &options),
+            ("I saw the\n".into(), 10)
+        );
+    }
+
+    #[test]
+    fn the_caret_lands_after_a_longer_replacement() {
+        let options = table(&[("MC", "Rosalind")]);
+        let command = correct("Then MC", 7, &options).unwrap();
+        assert_eq!(command.range, 5..7);
+        assert_eq!(command.insert, "Rosalind");
+        assert_eq!(command.caret, 13);
+        assert_eq!(
+            finish("Then MC", EditKey::Char(','), &options).0,
+            "Then Rosalind,"
+        );
+    }
+
+    #[test]
+    fn case_follows_the_typed_word() {
+        let options = AutocorrectOptions::default();
+        assert_eq!(finish("Teh", EditKey::Char(' '), &options).0, "The ");
+        assert_eq!(finish("TEH", EditKey::Char(' '), &options).0, "THE ");
+        // An entry with capitals matches only as written
+        let options = table(&[("MC", "Rosalind")]);
+        assert!(correct("mc", 2, &options).is_none());
+        assert!(correct("Mc", 2, &options).is_none());
+        assert_eq!(match_case("I", "we"), "We");
+    }
+
+    #[test]
+    fn only_the_whole_word_is_corrected() {
+        let options = AutocorrectOptions::default();
+        assert!(correct("tehran", 6, &options).is_none());
+        assert!(correct("steh", 4, &options).is_none());
+        // Opening punctuation isn't part of the word
+        assert_eq!(finish("“teh", EditKey::Char(' '), &options).0, "“the ");
+        // Not a word before the caret
+        assert!(correct("teh ", 4, &options).is_none());
+        assert!(correct("", 0, &options).is_none());
+        let off = AutocorrectOptions {
+            enabled: false,
+            ..options
+        };
+        assert!(correct("teh", 3, &off).is_none());
+    }
+
+    #[test]
+    fn ends_word_keys() {
+        for c in [' ', '\t', '.', ',', '!', '?', ')', '”', '…', '—'] {
+            assert!(ends_word(c), "{c:?}");
+        }
+        for c in ['a', '\'', '-', '(', '['] {
+            assert!(!ends_word(c), "{c:?}");
+        }
+    }
+
+    #[test]
+    fn the_table_file_round_trips() {
+        let options = table(&[("teh", "the"), ("MC", "Rosalind Vale")]);
+        let file = to_table(&options.replacements);
+        assert_eq!(
+            file,
+            "# Autocorrect replacements: typed word, a tab, replacement\n\
+             teh\tthe\nMC\tRosalind Vale\n"
+        );
+        assert_eq!(parse_table(&file).unwrap(), options.replacements);
+    }
+
+    #[test]
+    fn a_bad_table_line_is_named() {
+        let error = parse_table("# header\n\nteh\tthe\nno tab here\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Line 4: expected the typed word, a tab, then its replacement"
+        );
+        let error = parse_table("two words\tx\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Line 1: the typed word must be a single word"
+        );
+    }
+
+    #[test]
+    fn merging_replaces_and_adds() {
+        let mut replacements = table(&[("teh", "the"), ("MC", "Ann")]).replacements;
+        let imported = table(&[("teh", "the"), ("MC", "Rosalind"), ("adn", "and")]).replacements;
+        assert_eq!(merge(&mut replacements, imported), 2);
+        assert_eq!(
+            replacements,
+            table(&[("teh", "the"), ("MC", "Rosalind"), ("adn", "and")]).replacements
+        );
+    }
+}
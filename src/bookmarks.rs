@@ -0,0 +1,227 @@
+//! FILE: src/bookmarks.rs
+//!
+//! Bookmarks: lines marked to come back to (Ctrl+B), shown in the editor's
+//! gutter, visited in turn with F2 / Shift+F2 and listed in View →
+//! Bookmarks.
+//!
+//! A bookmark remembers its line's number AND its text. Line numbers shift
+//! as lines are added or removed above, so whenever the text changes each
+//! bookmark looks for its text again (reanchor) - at its old line first,
+//! then ever further away. Only when the line itself was edited does it
+//! stay put by number and take on the new text.
+//!
+//! Bookmarks are kept per document file in the session file
+//! (storage::SessionInfo::bookmarks), so they're back when the document is
+//! reopened, even if it was changed elsewhere in the meantime.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - sort_by_key and dedup_by_key for keeping a list tidy
+//! - Searching outward from a point with two iterators zipped together
+
+use serde::{Deserialize, Serialize};
+
+/// A bookmarked line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bookmark {
+    /// 1-based line number
+    pub line: usize,
+    /// The line's text when it was last found (trailing whitespace trimmed)
+    pub text: String,
+}
+
+/// What the user did in the Bookmarks panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookmarkAction {
+    /// Go to this 1-based line
+    Jump(usize),
+    /// Remove the bookmark at this index
+    Remove(usize),
+}
+
+/// The lines of `text` as bookmarks compare them
+fn lines(text: &str) -> Vec<&str> {
+    text.lines().map(str::trim_end).collect()
+}
+
+/// Add a bookmark on `line`, or remove the one that's there
+///
+/// Returns true if one was added. The list stays sorted by line.
+pub fn toggle(bookmarks: &mut Vec<Bookmark>, text: &str, line: usize) -> bool {
+    if let Some(index) = bookmarks.iter().position(|b| b.line == line) {
+        bookmarks.remove(index);
+        return false;
+    }
+    let text = lines(text).get(line - 1).copied().unwrap_or("").to_string();
+    bookmarks.push(Bookmark { line, text });
+    bookmarks.sort_by_key(|b| b.line);
+    true
+}
+
+/// Move each bookmark to where its line is in `text` now (see module docs)
+///
+/// Bookmarks that end up on the same line are merged.
+pub fn reanchor(bookmarks: &mut Vec<Bookmark>, text: &str) {
+    let lines = lines(text);
+
+    for bookmark in bookmarks.iter_mut() {
+        let line = bookmark.line.clamp(1, lines.len().max(1));
+        let found = nearest_line(&lines, line - 1, &bookmark.text);
+        match found {
+            Some(index) => bookmark.line = index + 1,
+            // The line itself was edited (or the document got shorter)
+            None => {
+                bookmark.line = line;
+                bookmark.text = lines.get(line - 1).copied().unwrap_or("").to_string();
+            }
+        }
+    }
+    bookmarks.sort_by_key(|b| b.line);
+    bookmarks.dedup_by_key(|b| b.line);
+}
+
+/// Index of the line equal to `wanted` nearest to `start`, looking at
+/// `start` itself, then one below, one above, two below, ...
+fn nearest_line(lines: &[&str], start: usize, wanted: &str) -> Option<usize> {
+    if lines.get(start) == Some(&wanted) {
+        return Some(start);
+    }
+    let below = (start + 1..lines.len())
+        .map(Some)
+        .chain(std::iter::repeat(None));
+    let above = (0..start.min(lines.len()))
+        .rev()
+        .map(Some)
+        .chain(std::iter::repeat(None));
+    below
+        .zip(above)
+        .take_while(|pair| *pair != (None, None))
+        .flat_map(|(down, up)| [down, up])
+        .flatten()
+        .find(|&index| lines[index] == wanted)
+}
+
+/// The bookmarked line after (`forward`) or before `line`, wrapping around
+/// at either end
+pub fn next(bookmarks: &[Bookmark], line: usize, forward: bool) -> Option<usize> {
+    let (first, last) = (bookmarks.first()?, bookmarks.last()?);
+    let found = if forward {
+        bookmarks.iter().find(|b| b.line > line).unwrap_or(first)
+    } else {
+        bookmarks
+            .iter()
+            .rev()
+            .find(|b| b.line < line)
+            .unwrap_or(last)
+    };
+    Some(found.line)
+}
+
+/// Draw the bookmark list
+pub fn show(ui: &mut egui::Ui, bookmarks: &[Bookmark]) -> Option<BookmarkAction> {
+    if bookmarks.is_empty() {
+        ui.label("No bookmarks.");
+        ui.weak("Ctrl+B marks the line the caret is on.");
+        return None;
+    }
+
+    let mut action = None;
+    egui::ScrollArea::vertical()
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            for (index, bookmark) in bookmarks.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.small_button("×").on_hover_text("Remove").clicked() {
+                        action = Some(BookmarkAction::Remove(index));
+                    }
+                    let text = match bookmark.text.trim() {
+                        "" => "(blank line)",
+                        text => text,
+                    };
+                    let label = format!("{}: {}", bookmark.line, text);
+                    let label = egui::SelectableLabel::new(false, label);
+                    if ui.add(label).clicked() {
+                        action = Some(BookmarkAction::Jump(bookmark.line));
+                    }
+                });
+            }
+        });
+    action
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marks(list: &[(usize, &str)]) -> Vec<Bookmark> {
+        list.iter()
+            .map(|&(line, text)| Bookmark {
+                line,
+                text: text.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn toggling_adds_in_order_then_removes() {
+        let text = "one\ntwo  \nthree\n";
+        let mut bookmarks = Vec::new();
+        assert!(toggle(&mut bookmarks, text, 3));
+        assert!(toggle(&mut bookmarks, text, 2));
+        assert!(toggle(&mut bookmarks, text, 9));
+        assert_eq!(bookmarks, marks(&[(2, "two"), (3, "three"), (9, "")]));
+        assert!(!toggle(&mut bookmarks, text, 3));
+        assert_eq!(bookmarks, marks(&[(2, "two"), (9, "")]));
+    }
+
+    #[test]
+    fn bookmarks_follow_their_lines() {
+        let mut bookmarks = marks(&[(2, "two"), (3, "three")]);
+        reanchor(&mut bookmarks, "new\nnew\none\ntwo\nthree\n");
+        assert_eq!(bookmarks, marks(&[(4, "two"), (5, "three")]));
+        reanchor(&mut bookmarks, "two\nthree\n");
+        assert_eq!(bookmarks, marks(&[(1, "two"), (2, "three")]));
+    }
+
+    #[test]
+    fn an_edited_line_keeps_its_bookmark_by_number() {
+        let mut bookmarks = marks(&[(2, "two")]);
+        reanchor(&mut bookmarks, "one\ntwo, edited\nthree\n");
+        assert_eq!(bookmarks, marks(&[(2, "two, edited")]));
+        // A shorter document pulls it up to the last line
+        let mut bookmarks = marks(&[(5, "five")]);
+        reanchor(&mut bookmarks, "one\ntwo\n");
+        assert_eq!(bookmarks, marks(&[(2, "two")]));
+        let mut bookmarks = marks(&[(5, "five")]);
+        reanchor(&mut bookmarks, "");
+        assert_eq!(bookmarks, marks(&[(1, "")]));
+    }
+
+    #[test]
+    fn the_nearest_copy_of_a_line_wins() {
+        let lines = ["x", "a", "x", "b", "x", "c"];
+        assert_eq!(nearest_line(&lines, 2, "x"), Some(2));
+        // One below is looked at before one above
+        assert_eq!(nearest_line(&lines, 3, "x"), Some(4));
+        assert_eq!(nearest_line(&lines, 5, "x"), Some(4));
+        assert_eq!(nearest_line(&lines, 9, "a"), Some(1));
+        assert_eq!(nearest_line(&lines, 0, "z"), None);
+    }
+
+    #[test]
+    fn bookmarks_on_one_line_merge() {
+        let mut bookmarks = marks(&[(1, "gone"), (2, "two")]);
+        reanchor(&mut bookmarks, "two\n");
+        assert_eq!(bookmarks, marks(&[(1, "two")]));
+    }
+
+    #[test]
+    fn next_and_previous_wrap_around() {
+        let bookmarks = marks(&[(3, "c"), (7, "g"), (12, "l")]);
+        assert_eq!(next(&bookmarks, 1, true), Some(3));
+        assert_eq!(next(&bookmarks, 3, true), Some(7));
+        assert_eq!(next(&bookmarks, 12, true), Some(3));
+        assert_eq!(next(&bookmarks, 7, false), Some(3));
+        assert_eq!(next(&bookmarks, 3, false), Some(12));
+        assert_eq!(next(&[], 3, true), None);
+    }
+}
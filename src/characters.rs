@@ -0,0 +1,378 @@
+//! FILE: src/characters.rs
+//!
+//! The Tools → Character report window: a sortable table of who speaks,
+//! which can be saved as CSV (to_csv) for a spreadsheet.
+//! And the View → Characters panel: each character with the scenes they
+//! speak in, and (with a project open) a description and notes.
+//!
+//! Like outline.rs, this module only DRAWS. App computes the report with
+//! parser::character_report, keeps the sort order in a CharacterTable, and
+//! gets back the line the user clicked so it can jump there. The panel
+//! edits the project's CharacterProfiles in place; App writes the project.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - sort_by with a comparator chosen at runtime
+//! - std::cmp::Ordering::reverse() for descending order
+//! - egui::Grid for simple tables
+
+use crate::parser::{self, CharacterStats};
+use crate::project::CharacterProfile;
+use std::cmp::Ordering;
+
+/// Columns the table can be sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortColumn {
+    Name,
+    Speeches,
+    Lines,
+    Words,
+    FirstLine,
+}
+
+/// The report rows plus how they're currently sorted
+#[derive(Debug, Clone)]
+pub struct CharacterTable {
+    pub rows: Vec<CharacterStats>,
+    sort: SortColumn,
+    descending: bool,
+}
+
+impl Default for CharacterTable {
+    /// Biggest speaking parts first
+    fn default() -> Self {
+        Self {
+            rows: Vec::new(),
+            sort: SortColumn::Words,
+            descending: true,
+        }
+    }
+}
+
+impl CharacterTable {
+    /// Replace the rows, keeping the current sort order
+    pub fn set_rows(&mut self, rows: Vec<CharacterStats>) {
+        self.rows = rows;
+        self.apply_sort();
+    }
+
+    /// Sort by `column`; clicking the current column again flips direction
+    fn sort_by(&mut self, column: SortColumn) {
+        if self.sort == column {
+            self.descending = !self.descending;
+        } else {
+            self.sort = column;
+            // Numbers read best biggest-first, names and lines A→Z / top-down
+            self.descending = matches!(
+                column,
+                SortColumn::Speeches | SortColumn::Lines | SortColumn::Words
+            );
+        }
+        self.apply_sort();
+    }
+
+    fn apply_sort(&mut self) {
+        let column = self.sort;
+        let descending = self.descending;
+        self.rows.sort_by(|a, b| {
+            let order = compare(column, a, b);
+            if descending {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+    }
+}
+
+/// Order two rows by `column`, falling back to name so ties are stable
+fn compare(column: SortColumn, a: &CharacterStats, b: &CharacterStats) -> Ordering {
+    let primary = match column {
+        SortColumn::Name => Ordering::Equal,
+        SortColumn::Speeches => a.speeches.cmp(&b.speeches),
+        SortColumn::Lines => a.dialogue_lines.cmp(&b.dialogue_lines),
+        SortColumn::Words => a.dialogue_words.cmp(&b.dialogue_words),
+        SortColumn::FirstLine => a.first_line.cmp(&b.first_line),
+    };
+    primary.then_with(|| a.name.cmp(&b.name))
+}
+
+/// Draw the table
+///
+/// Returns the 1-based line of the first appearance of the character the
+/// user clicked, if any.
+pub fn show(ui: &mut egui::Ui, table: &mut CharacterTable) -> Option<usize> {
+    if table.rows.is_empty() {
+        ui.label("No characters found.");
+        ui.label("Character cues are ALL-CAPS names above dialogue, or [CHARACTER: NAME] tags.");
+        return None;
+    }
+
+    let mut clicked = None;
+    let mut sort = None;
+
+    egui::ScrollArea::vertical()
+        .max_height(400.0)
+        .show(ui, |ui| {
+            egui::Grid::new("character_report")
+                .striped(true)
+                .num_columns(6)
+                .show(ui, |ui| {
+                    for (label, column) in [
+                        ("Character", SortColumn::Name),
+                        ("Speeches", SortColumn::Speeches),
+                        ("Lines", SortColumn::Lines),
+                        ("Words", SortColumn::Words),
+                        ("First line", SortColumn::FirstLine),
+                    ] {
+                        let arrow = match (table.sort == column, table.descending) {
+                            (false, _) => "",
+                            (true, true) => " ⏷",
+                            (true, false) => " ⏶",
+                        };
+                        if ui.button(format!("{}{}", label, arrow)).clicked() {
+                            sort = Some(column);
+                        }
+                    }
+                    ui.label("Appears in");
+                    ui.end_row();
+
+                    for row in &table.rows {
+                        if ui.selectable_label(false, &row.name).clicked() {
+                            clicked = Some(row.first_line);
+                        }
+                        ui.label(row.speeches.to_string());
+                        ui.label(row.dialogue_lines.to_string());
+                        ui.label(row.dialogue_words.to_string());
+                        ui.label(row.first_line.to_string());
+                        ui.label(appears_in(row));
+                        ui.end_row();
+                    }
+                });
+        });
+
+    if let Some(column) = sort {
+        table.sort_by(column);
+    }
+    clicked
+}
+
+/// The table as CSV, in its current order: one row per character, with
+/// the chapters and scenes they speak in each joined with "; "
+///
+/// Fields are quoted when they hold a comma, quote or line break (RFC 4180),
+/// and lines end in CRLF, which spreadsheets read everywhere.
+pub fn to_csv(table: &CharacterTable) -> String {
+    let mut out = String::from("Character,Speeches,Lines,Words,First line,Chapters,Scenes\r\n");
+    for row in &table.rows {
+        let scenes: Vec<&str> = row.scenes.iter().map(|s| s.description.as_str()).collect();
+        let fields = [
+            row.name.clone(),
+            row.speeches.to_string(),
+            row.dialogue_lines.to_string(),
+            row.dialogue_words.to_string(),
+            row.first_line.to_string(),
+            row.chapters.join("; "),
+            scenes.join("; "),
+        ];
+        let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&fields.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// One CSV field, quoted if it needs to be
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// "Chapter 1, Chapter 2 · 3 scenes"
+fn appears_in(row: &CharacterStats) -> String {
+    let chapters = row.chapters.join(", ");
+    let scenes = match row.scenes.len() {
+        0 => String::new(),
+        1 => String::from("1 scene"),
+        n => format!("{} scenes", n),
+    };
+    match (chapters.is_empty(), scenes.is_empty()) {
+        (true, true) => String::from("-"),
+        (false, true) => chapters,
+        (true, false) => scenes,
+        (false, false) => format!("{} · {}", chapters, scenes),
+    }
+}
+
+// ============================================================================
+// CHARACTERS PANEL
+// ============================================================================
+
+/// What happened in the Characters panel this frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PanelOutput {
+    /// 1-based line of the scene (or first cue) clicked
+    pub jump: Option<usize>,
+    /// The profiles changed and the edit is finished (a field lost focus, a
+    /// character was added or removed), so it's time to write them
+    pub save: bool,
+}
+
+/// Draw the panel
+///
+/// `rows` are the open document's speakers; `profiles` the project's notes
+/// (None without a project, when only the speakers are listed). Characters
+/// with notes who don't speak in this document are listed after the rest.
+/// `new_name` holds the "Add" field's text between frames.
+pub fn panel(
+    ui: &mut egui::Ui,
+    rows: &[CharacterStats],
+    mut profiles: Option<&mut Vec<CharacterProfile>>,
+    new_name: &mut String,
+) -> PanelOutput {
+    let mut output = PanelOutput::default();
+
+    // Order of first appearance reads like a cast list
+    let mut speakers: Vec<&CharacterStats> = rows.iter().collect();
+    speakers.sort_by_key(|row| row.first_line);
+    let absent: Vec<String> = profiles
+        .as_deref()
+        .map(|profiles| {
+            profiles
+                .iter()
+                .filter(|p| !rows.iter().any(|row| row.name == p.name))
+                .map(|p| p.name.clone())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    match profiles.as_deref_mut() {
+        Some(profiles) => {
+            ui.horizontal(|ui| {
+                let field = ui.add(
+                    egui::TextEdit::singleline(new_name)
+                        .hint_text("New character")
+                        .desired_width(120.0),
+                );
+                let entered = field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                let name = parser::normalize_cue(new_name);
+                let known = rows.iter().any(|row| row.name == name)
+                    || profiles.iter().any(|p| p.name == name);
+                if (ui
+                    .add_enabled(!name.is_empty() && !known, egui::Button::new("Add"))
+                    .clicked()
+                    || entered)
+                    && !name.is_empty()
+                    && !known
+                {
+                    profiles.push(CharacterProfile {
+                        name,
+                        ..CharacterProfile::default()
+                    });
+                    new_name.clear();
+                    output.save = true;
+                }
+            });
+        }
+        None => {
+            ui.weak("Open a project to keep descriptions and notes.");
+        }
+    }
+    ui.separator();
+
+    if speakers.is_empty() && absent.is_empty() {
+        ui.label("No characters found.");
+        return output;
+    }
+
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        for row in speakers {
+            let speeches = match row.speeches {
+                1 => String::from("1 speech"),
+                n => format!("{} speeches", n),
+            };
+            egui::CollapsingHeader::new(format!("{} ({})", row.name, speeches))
+                .id_salt(("character", &row.name))
+                .show(ui, |ui| {
+                    if let Some(profiles) = profiles.as_deref_mut() {
+                        profile_fields(ui, profiles, &row.name, &mut output);
+                    }
+                    if ui
+                        .link(format!("First cue: line {}", row.first_line))
+                        .clicked()
+                    {
+                        output.jump = Some(row.first_line);
+                    }
+                    if !row.scenes.is_empty() {
+                        ui.label("Appears in:");
+                        for scene in &row.scenes {
+                            if ui.link(&scene.description).clicked() {
+                                output.jump = Some(scene.line);
+                            }
+                        }
+                    }
+                });
+        }
+
+        if absent.is_empty() {
+            return;
+        }
+        ui.separator();
+        ui.weak("Not in this document:");
+        for name in absent {
+            egui::CollapsingHeader::new(&name)
+                .id_salt(("character", &name))
+                .show(ui, |ui| {
+                    let Some(profiles) = profiles.as_deref_mut() else {
+                        return;
+                    };
+                    profile_fields(ui, profiles, &name, &mut output);
+                    if ui.small_button("Remove").clicked() {
+                        profiles.retain(|p| p.name != name);
+                        output.save = true;
+                    }
+                });
+        }
+    });
+
+    output
+}
+
+/// The description and notes fields for `name`
+///
+/// A character without a profile gets one once something is typed.
+fn profile_fields(
+    ui: &mut egui::Ui,
+    profiles: &mut Vec<CharacterProfile>,
+    name: &str,
+    output: &mut PanelOutput,
+) {
+    let mut scratch = CharacterProfile::default();
+    let index = profiles.iter().position(|p| p.name == name);
+    let profile = match index {
+        Some(index) => &mut profiles[index],
+        None => &mut scratch,
+    };
+
+    let description = ui.add(
+        egui::TextEdit::singleline(&mut profile.description)
+            .hint_text("Description")
+            .desired_width(f32::INFINITY),
+    );
+    let notes = ui.add(
+        egui::TextEdit::multiline(&mut profile.notes)
+            .hint_text("Notes")
+            .desired_rows(3)
+            .desired_width(f32::INFINITY),
+    );
+    if description.lost_focus() || notes.lost_focus() {
+        output.save = true;
+    }
+
+    if index.is_none() && (description.changed() || notes.changed()) {
+        scratch.name = name.to_string();
+        profiles.push(scratch);
+    }
+}
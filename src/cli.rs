@@ -0,0 +1,511 @@
+//! FILE: src/cli.rs
+//!
+//! Headless commands: work on a document from the command line without
+//! opening a window, so conversions and builds can be scripted.
+//!
+//!   bookscript export FILE [--format FORMAT] [-o OUTPUT] [--watch]
+//!   bookscript outline FILE
+//!   bookscript stats FILE
+//!
+//! - export: the document through one of the exporters (the ones File →
+//!   Compile offers). FORMAT is the output's extension - txt, md, html,
+//!   fountain, fdx, pdf, docx or epub. Without --format it's taken from OUTPUT's
+//!   extension; without -o the output goes next to FILE, named after it.
+//!   `-o -` writes it to stdout. With --watch it keeps running and
+//!   exports again every time FILE changes (see watch()), for a preview
+//!   that keeps up with editing in another program.
+//! - outline: the acts, chapters and scenes, as the outline sidebar shows
+//!   them, with their line numbers and word counts
+//! - stats: the totals of Tools → Statistics, plus characters and pages
+//!
+//! They share the app's code all the way: a document is read as File →
+//! Open reads it (Fountain and Final Draft files are converted to
+//! BookScript), and the saved preferences apply - keeping comments in
+//! exports, the PDF and Word options, the word-count options. A
+//! password-protected document can't be used.
+//!
+//! Results go to stdout and errors to stderr. The exit status is 0 on
+//! success, 1 if the command failed and 2 if the arguments weren't
+//! understood, as for the window's own arguments.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Returning an exit status instead of letting main() return
+//! - std::io::Write on stdout, for output that isn't text
+//! - Polling a file's modification time with thread::sleep
+
+use crate::compile::{self, CompileTarget};
+use crate::fdx;
+use crate::format;
+use crate::formatted;
+use crate::outline;
+use crate::parser;
+use crate::readability;
+use crate::settings::Settings;
+use crate::statistics;
+use crate::storage;
+use crate::toc;
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// The subcommands, as typed
+const COMMANDS: [&str; 3] = ["export", "outline", "stats"];
+
+/// How often `export --watch` looks at the input's modification time
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// What parse() says when it can't tell the export format
+const NO_FORMAT: &str = "give --format, or an output file name with an extension";
+
+/// A headless command and what it works on
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    Export {
+        input: PathBuf,
+        target: CompileTarget,
+        /// None for stdout
+        output: Option<PathBuf>,
+        /// Keep exporting as the input changes
+        watch: bool,
+    },
+    Outline(PathBuf),
+    Stats(PathBuf),
+}
+
+/// Run the subcommand `args` name, if the first of them names one
+///
+/// Returns the exit status to end with; None if there's no subcommand and
+/// the window should open as usual.
+pub fn run(args: &[String], settings: impl FnOnce() -> Settings) -> Option<i32> {
+    let name = args.first().filter(|a| COMMANDS.contains(&a.as_str()))?;
+    let command = match parse(name, &args[1..]) {
+        Ok(Some(command)) => command,
+        Ok(None) => {
+            let _ = write_stdout(format!("{}\n", crate::USAGE).as_bytes());
+            return Some(0);
+        }
+        Err(message) => {
+            eprintln!("bookscript {}: {}\n\n{}", name, message, crate::USAGE);
+            return Some(2);
+        }
+    };
+    match execute(command, &settings()) {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("bookscript {}: {:#}", name, e);
+            Some(1)
+        }
+    }
+}
+
+/// Work out the command from the arguments after its name
+///
+/// Ok(None) for --help; Err with a message for anything not understood.
+/// As for the window's arguments, `--` ends the flags.
+fn parse(name: &str, args: &[String]) -> Result<Option<Command>, String> {
+    let mut input: Option<PathBuf> = None;
+    let mut format: Option<String> = None;
+    let mut output: Option<String> = None;
+    let mut watch = false;
+    let mut flags_done = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--" if !flags_done => flags_done = true,
+            "-h" | "--help" if !flags_done => return Ok(None),
+            "--format" | "-f" if !flags_done && name == "export" => {
+                let value = args.next().ok_or("--format needs a format")?;
+                format = Some(value.clone());
+            }
+            "-o" | "--output" if !flags_done && name == "export" => {
+                let value = args.next().ok_or("-o needs a file name")?;
+                output = Some(value.clone());
+            }
+            "-w" | "--watch" if !flags_done && name == "export" => watch = true,
+            flag if !flags_done && flag.starts_with('-') && flag != "-" => {
+                return Err(format!("unknown option: {}", flag));
+            }
+            _ if input.is_some() => {
+                return Err(format!("only one file can be given (got {})", arg));
+            }
+            _ => input = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let input = input.ok_or("no file given")?;
+    match name {
+        "outline" => return Ok(Some(Command::Outline(input))),
+        "stats" => return Ok(Some(Command::Stats(input))),
+        _ => {}
+    }
+
+    let named = match (&format, output.as_deref()) {
+        (Some(format), _) => format.clone(),
+        (None, Some(path)) if path != "-" => Path::new(path)
+            .extension()
+            .map(|e| e.to_string_lossy().into_owned())
+            .ok_or(NO_FORMAT)?,
+        (None, _) => return Err(NO_FORMAT.to_string()),
+    };
+    let target = CompileTarget::from_extension(&named).ok_or_else(|| {
+        format!(
+            "unknown format: {} (use txt, md, html, fountain, fdx, pdf, docx or epub)",
+            named
+        )
+    })?;
+    let output = match output.as_deref() {
+        Some("-") => None,
+        Some(path) => Some(PathBuf::from(path)),
+        None => Some(input.with_extension(target.extension())),
+    };
+    if watch && output.is_none() {
+        return Err("--watch needs an output file, not stdout".to_string());
+    }
+    Ok(Some(Command::Export {
+        input,
+        target,
+        output,
+        watch,
+    }))
+}
+
+/// Carry out the command
+fn execute(command: Command, settings: &Settings) -> Result<()> {
+    match command {
+        Command::Export {
+            input,
+            target,
+            output: Some(output),
+            watch: true,
+        } => watch(&input, target, &output, settings),
+        Command::Export {
+            input,
+            target,
+            output,
+            watch: _,
+        } => {
+            export(&input, target, output.as_deref(), settings)?;
+            if let Some(output) = output {
+                eprintln!("Exported: {}", output.display());
+            }
+            Ok(())
+        }
+        Command::Outline(input) => {
+            let text = read_document(&input)?;
+            write_stdout(outline::to_text(&structure(&text, settings)).as_bytes())
+        }
+        Command::Stats(input) => {
+            let text = read_document(&input)?;
+            write_stdout(stats(&text, settings).as_bytes())
+        }
+    }
+}
+
+/// The document at `path` as BookScript text, converted the way File →
+/// Open converts it, or from the mode the status bar shows for it (a .txt
+/// that holds Fountain, say)
+fn read_document(path: &Path) -> Result<String> {
+    let text = storage::load_text_file(path)?;
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("fdx"))
+    {
+        return fdx::import_fdx(&text).context("Can't import the Final Draft file");
+    }
+    let mode = storage::load_sidecar(path)
+        .ok()
+        .and_then(|meta| meta.format_override)
+        .unwrap_or_else(|| format::detect_format(Some(path), &text));
+    Ok(parser::parser_for(mode).to_bookscript(&text).into_owned())
+}
+
+/// `bookscript export`: render the document and write it out
+///
+/// The text goes to the exporter as File → Export gives it: comments taken
+/// out unless Preferences keeps them, and for HTML and EPUB (which make
+/// linked contents of their own) without the Tools → Table of Contents
+/// list. The
+/// file name without its extension is the title.
+fn export(
+    input: &Path,
+    target: CompileTarget,
+    output: Option<&Path>,
+    settings: &Settings,
+) -> Result<()> {
+    if let Some(output) = output {
+        let same = std::fs::canonicalize(output)
+            .is_ok_and(|o| std::fs::canonicalize(input).is_ok_and(|i| i == o));
+        if same {
+            bail!(
+                "{} is the document being exported; give the output another name with -o",
+                input.display()
+            );
+        }
+    }
+
+    let mut text = read_document(input)?;
+    // HTML and EPUB make linked contents of their own
+    if matches!(target, CompileTarget::Html | CompileTarget::Epub) {
+        text = toc::remove(&text).into_owned();
+    }
+    if !settings.export_comments {
+        text = parser::strip_comments(&text).into_owned();
+    }
+    let title = input
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let bytes = compile::render(target, &text, &title, &settings.pdf, &settings.docx)?;
+
+    match output {
+        Some(output) => storage::save_binary_file(output, &bytes),
+        None => write_stdout(&bytes),
+    }
+}
+
+/// `bookscript export --watch`: export now, then again whenever the
+/// input's modification time changes, until stopped with Ctrl+C
+///
+/// An export that fails is reported and watching goes on - the file may
+/// have been caught half-written, and the save that finishes it changes
+/// the time again. While the file is missing (some editors save by
+/// deleting and renaming) nothing happens.
+fn watch(input: &Path, target: CompileTarget, output: &Path, settings: &Settings) -> Result<()> {
+    let mut last =
+        storage::modified_time(input).with_context(|| format!("Can't read {}", input.display()))?;
+    eprintln!("Watching {} for changes (Ctrl+C to stop)", input.display());
+    loop {
+        let time = chrono::Local::now().format("%H:%M:%S");
+        match export(input, target, Some(output), settings) {
+            Ok(()) => eprintln!("{} Exported: {}", time, output.display()),
+            Err(e) => eprintln!("{} Error exporting: {:#}", time, e),
+        }
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            match storage::modified_time(input) {
+                Some(modified) if modified != last => {
+                    last = modified;
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Write to stdout; a reader that stopped early (`| head`) isn't an error
+fn write_stdout(bytes: &[u8]) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    match stdout.write_all(bytes).and_then(|()| stdout.flush()) {
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// The document's structure with its word counts
+fn structure(text: &str, settings: &Settings) -> parser::DocumentStructure {
+    let mut structure = parser::extract_structure(&parser::parse_document(text));
+    parser::annotate_structure(text, &mut structure, settings.word_count.exclude_tags);
+    structure
+}
+
+/// `bookscript stats`: the counts, one "Label: value" a line
+fn stats(text: &str, settings: &Settings) -> String {
+    let counts = parser::count_stats(text, settings.word_count.exclude_tags);
+    let blocks = formatted::blocks(&parser::parse_document(text));
+    let report = statistics::report(
+        &structure(text, settings),
+        &blocks,
+        &mut readability::Cache::default(),
+    );
+
+    let mut rows = statistics::totals(&report);
+    let pages = format!(
+        "{:.1} (at {} words a page)",
+        counts.pages(settings.word_count.words_per_page),
+        settings.word_count.words_per_page
+    );
+    rows.insert(1, ("Characters", counts.characters.to_string()));
+    rows.insert(
+        2,
+        (
+            "Characters without spaces",
+            counts.characters_no_spaces.to_string(),
+        ),
+    );
+    rows.insert(3, ("Pages", pages));
+    rows.iter()
+        .map(|(label, value)| format!("{}: {}\n", label, value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(name: &str, args: &[&str]) -> Result<Option<Command>, String> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        parse(name, &args)
+    }
+
+    fn error(name: &str, args: &[&str]) -> String {
+        parsed(name, args).unwrap_err()
+    }
+
+    fn export_to(input: &str, target: CompileTarget, output: Option<&str>) -> Command {
+        Command::Export {
+            input: PathBuf::from(input),
+            target,
+            output: output.map(PathBuf::from),
+            watch: false,
+        }
+    }
+
+    #[test]
+    fn arguments_that_are_not_understood() {
+        assert_eq!(error("export", &[]), "no file given");
+        assert_eq!(
+            error("stats", &["a.bks", "b.bks"]),
+            "only one file can be given (got b.bks)"
+        );
+        assert_eq!(
+            error("export", &["a.bks", "--colour"]),
+            "unknown option: --colour"
+        );
+        // Export's options belong to export alone
+        assert_eq!(
+            error("outline", &["-o", "x.txt", "a.bks"]),
+            "unknown option: -o"
+        );
+        assert_eq!(
+            error("export", &["a.bks", "--format"]),
+            "--format needs a format"
+        );
+        assert_eq!(error("export", &["a.bks", "-o"]), "-o needs a file name");
+        assert_eq!(error("export", &["a.bks"]), NO_FORMAT);
+        assert_eq!(error("export", &["a.bks", "-o", "out"]), NO_FORMAT);
+        assert_eq!(error("export", &["a.bks", "-o", "-"]), NO_FORMAT);
+        assert_eq!(
+            error("export", &["a.bks", "-f", "rtf"]),
+            "unknown format: rtf (use txt, md, html, fountain, fdx, pdf, docx or epub)"
+        );
+        assert_eq!(
+            error("export", &["a.bks", "-f", "pdf", "-o", "-", "--watch"]),
+            "--watch needs an output file, not stdout"
+        );
+    }
+
+    #[test]
+    fn export_arguments() {
+        assert_eq!(
+            parsed("export", &["a.bks", "--format", "PDF"]),
+            Ok(Some(export_to("a.bks", CompileTarget::Pdf, Some("a.pdf"))))
+        );
+        assert_eq!(
+            parsed("export", &["-o", "out/book.markdown", "a.bks"]),
+            Ok(Some(export_to(
+                "a.bks",
+                CompileTarget::Markdown,
+                Some("out/book.markdown")
+            )))
+        );
+        // --format wins over the output's extension
+        assert_eq!(
+            parsed("export", &["a.bks", "-f", "txt", "-o", "notes.log"]),
+            Ok(Some(export_to(
+                "a.bks",
+                CompileTarget::PlainText,
+                Some("notes.log")
+            )))
+        );
+        assert_eq!(
+            parsed("export", &["a.bks", "-f", "html", "-o", "-"]),
+            Ok(Some(export_to("a.bks", CompileTarget::Html, None)))
+        );
+        assert_eq!(
+            parsed("export", &["--watch", "a.bks", "-f", "epub"]),
+            Ok(Some(Command::Export {
+                input: PathBuf::from("a.bks"),
+                target: CompileTarget::Epub,
+                output: Some(PathBuf::from("a.epub")),
+                watch: true,
+            }))
+        );
+    }
+
+    #[test]
+    fn files_after_the_end_of_flags() {
+        assert_eq!(
+            parsed("outline", &["--", "-draft.bks"]),
+            Ok(Some(Command::Outline(PathBuf::from("-draft.bks"))))
+        );
+        assert_eq!(
+            parsed("stats", &["--", "--help"]),
+            Ok(Some(Command::Stats(PathBuf::from("--help"))))
+        );
+        assert_eq!(parsed("stats", &["a.bks", "--help"]), Ok(None));
+        assert_eq!(
+            error("stats", &["--", "a.bks", "--"]),
+            "only one file can be given (got --)"
+        );
+    }
+
+    #[test]
+    fn only_subcommands_are_run() {
+        let args = |list: &[&str]| list.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        let settings = || panic!("no settings needed");
+        assert_eq!(run(&args(&["novel.bks"]), settings), None);
+        assert_eq!(run(&[], settings), None);
+        assert_eq!(run(&args(&["stats"]), settings), Some(2));
+        assert_eq!(run(&args(&["export", "--help"]), settings), Some(0));
+    }
+
+    #[test]
+    fn export_writes_the_output_and_refuses_the_input() {
+        let dir = std::env::temp_dir().join(format!("bookscript-test-{}-cli", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("novel.bks");
+        std::fs::write(
+            &input,
+            "[[contents]]\nContents\nOne, line 5\n[[/contents]]\n[CHAPTER: One]\nIt began. [[note: fix]]\n",
+        )
+        .unwrap();
+        let settings = Settings::default();
+
+        let output = dir.join("novel.txt");
+        export(&input, CompileTarget::PlainText, Some(&output), &settings).unwrap();
+        let text = std::fs::read_to_string(&output).unwrap();
+        assert!(text.contains("One, line 5"));
+        assert!(!text.contains("fix"));
+
+        let output = dir.join("novel.html");
+        export(&input, CompileTarget::Html, Some(&output), &settings).unwrap();
+        assert!(!std::fs::read_to_string(&output).unwrap().contains("line 5"));
+
+        let error = export(&input, CompileTarget::PlainText, Some(&input), &settings).unwrap_err();
+        assert!(error.to_string().contains("is the document being exported"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stats_lines() {
+        let settings = Settings::default();
+        let text = stats("[CHAPTER: One]\nIt began here.\n", &settings);
+        let labels: Vec<&str> = text.lines().map(|l| l.split(':').next().unwrap()).collect();
+        assert_eq!(
+            labels[..5],
+            [
+                "Words",
+                "Characters",
+                "Characters without spaces",
+                "Pages",
+                "Chapters"
+            ]
+        );
+        assert!(text.contains("Chapters: 1\n"));
+    }
+}
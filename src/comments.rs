@@ -0,0 +1,45 @@
+//! FILE: src/comments.rs
+//!
+//! View → Comments: every `[[note: ...]]` comment in the document, in a
+//! panel beside the editor.
+//!
+//! Like problems.rs, this module only DRAWS. App finds the comments (with
+//! parser::find_comments), passes them in, and gets back the line the user
+//! clicked so it can move the editor there.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Returning Option<T> from UI code to report user actions
+
+use crate::parser::Comment;
+
+/// Draw the comment list
+///
+/// Returns the 1-based line number of the comment the user clicked, if any.
+pub fn show(ui: &mut egui::Ui, comments: &[Comment]) -> Option<usize> {
+    if comments.is_empty() {
+        ui.label("No comments.");
+        ui.weak("Write [[note: ...]] anywhere in the text to add one.");
+        return None;
+    }
+
+    let mut clicked = None;
+
+    egui::ScrollArea::vertical()
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            for comment in comments {
+                ui.weak(format!("Line {}", comment.line));
+                let text = match comment.text.as_str() {
+                    "" => "(empty)",
+                    text => text,
+                };
+                let label = egui::SelectableLabel::new(false, text);
+                if ui.add(label).clicked() {
+                    clicked = Some(comment.line);
+                }
+                ui.add_space(4.0);
+            }
+        });
+
+    clicked
+}
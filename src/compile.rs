@@ -0,0 +1,362 @@
+//! FILE: src/compile.rs
+//!
+//! File → Compile: a project's documents put together into one manuscript
+//! and sent through one of the exporters.
+//!
+//! Compiling happens in two steps:
+//! 1. assemble: the included documents, in project order, joined into one
+//!    BookScript text - front matter first, a separator between documents
+//! 2. render: that text through the chosen exporter, giving the bytes of
+//!    the output file
+//!
+//! Reading the documents is App's job, because a document that is open has
+//! its latest text in the editor (or a tab), not on disk.
+//!
+//! What to include and how is saved with the project (ProjectSettings), so
+//! compiling again gives the same result.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Associated constants listing every variant of an enum
+//! - One function dispatching to several exporters with different return
+//!   types, unified as bytes
+
+use crate::docx::{self, DocxOptions};
+use crate::epub;
+use crate::export::{self, structural_tag};
+use crate::fdx;
+use crate::parser::TagType;
+use crate::pdf::{self, PdfOptions};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+// ============================================================================
+// OPTIONS
+// ============================================================================
+
+/// How the project is compiled, saved in the project file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompileSettings {
+    /// Documents left out, as listed in Project::files (so a document added
+    /// later is included until unticked)
+    pub excluded: Vec<String>,
+    /// What goes between one document and the next
+    pub separator: Separator,
+    /// The line used by Separator::Custom
+    pub custom_separator: String,
+    /// BookScript text put before the first document (a dedication, an
+    /// epigraph, ...)
+    pub front_matter: String,
+    /// Which exporter the result goes through
+    pub target: CompileTarget,
+}
+
+/// What goes between documents
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Separator {
+    /// Just a blank line: the documents carry their own chapter tags
+    #[default]
+    BlankLine,
+    /// A `[CHAPTER: ...]` tag named after the file, for documents that
+    /// don't start with an act or chapter tag of their own
+    ChapterFromFileName,
+    /// The custom separator line, with a blank line either side
+    Custom,
+}
+
+impl Separator {
+    pub const ALL: [Separator; 3] = [
+        Separator::BlankLine,
+        Separator::ChapterFromFileName,
+        Separator::Custom,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Separator::BlankLine => "Blank line",
+            Separator::ChapterFromFileName => "Chapter named after the file",
+            Separator::Custom => "Custom line",
+        }
+    }
+}
+
+/// The exporters a project can be compiled with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompileTarget {
+    PlainText,
+    Markdown,
+    Html,
+    Fountain,
+    FinalDraft,
+    Pdf,
+    #[default]
+    Docx,
+    Epub,
+}
+
+impl CompileTarget {
+    /// Every target, in the order the Compile window lists them
+    pub const ALL: [CompileTarget; 8] = [
+        CompileTarget::Docx,
+        CompileTarget::Pdf,
+        CompileTarget::Epub,
+        CompileTarget::PlainText,
+        CompileTarget::Markdown,
+        CompileTarget::Html,
+        CompileTarget::Fountain,
+        CompileTarget::FinalDraft,
+    ];
+
+    /// Name for the window and the save dialog's filter
+    pub fn label(&self) -> &'static str {
+        match self {
+            CompileTarget::PlainText => "Plain text manuscript",
+            CompileTarget::Markdown => "Markdown",
+            CompileTarget::Html => "HTML",
+            CompileTarget::Fountain => "Fountain",
+            CompileTarget::FinalDraft => "Final Draft (FDX)",
+            CompileTarget::Pdf => "PDF (screenplay)",
+            CompileTarget::Docx => "Word (DOCX)",
+            CompileTarget::Epub => "EPUB (e-book)",
+        }
+    }
+
+    /// The target whose output has `extension` (any case), as `bookscript
+    /// export --format` takes it; "text" and "markdown" work too
+    pub fn from_extension(extension: &str) -> Option<CompileTarget> {
+        let extension = extension.to_ascii_lowercase();
+        let extension = match extension.as_str() {
+            "text" => "txt",
+            "markdown" => "md",
+            other => other,
+        };
+        Self::ALL.into_iter().find(|t| t.extension() == extension)
+    }
+
+    /// File extension of the output
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CompileTarget::PlainText => "txt",
+            CompileTarget::Markdown => "md",
+            CompileTarget::Html => "html",
+            CompileTarget::Fountain => "fountain",
+            CompileTarget::FinalDraft => "fdx",
+            CompileTarget::Pdf => "pdf",
+            CompileTarget::Docx => "docx",
+            CompileTarget::Epub => "epub",
+        }
+    }
+}
+
+// ============================================================================
+// ASSEMBLING
+// ============================================================================
+
+/// One document to compile: its name (the file name without extension)
+/// and its text
+#[derive(Debug, Clone)]
+pub struct Part {
+    pub name: String,
+    pub text: String,
+}
+
+/// Join the documents into one BookScript text
+pub fn assemble(parts: &[Part], settings: &CompileSettings) -> String {
+    let mut out = String::new();
+
+    let front_matter = settings.front_matter.trim_end();
+    if !front_matter.trim().is_empty() {
+        out.push_str(front_matter);
+        out.push('\n');
+    }
+
+    // Empty documents are skipped, separator and all
+    let mut first = true;
+    for part in parts {
+        // Blank lines at either end would double up with the separator's
+        let text = part.text.trim_end().trim_start_matches(['\n', '\r']);
+        if text.is_empty() {
+            continue;
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        match settings.separator {
+            Separator::BlankLine => {}
+            Separator::ChapterFromFileName => {
+                if !starts_with_heading(text) {
+                    out.push_str(&format!("[CHAPTER: {}]\n\n", part.name));
+                }
+            }
+            Separator::Custom => {
+                let line = settings.custom_separator.trim();
+                // Between documents only: not above the first one
+                if !first && !line.is_empty() {
+                    out.push_str(line);
+                    out.push_str("\n\n");
+                }
+            }
+        }
+        out.push_str(text);
+        out.push('\n');
+        first = false;
+    }
+
+    out
+}
+
+/// Does the first non-blank line open an act or a chapter?
+fn starts_with_heading(text: &str) -> bool {
+    text.lines()
+        .find(|line| !line.trim().is_empty())
+        .and_then(structural_tag)
+        .is_some_and(|tag| matches!(tag, TagType::Act(_) | TagType::Chapter(_)))
+}
+
+// ============================================================================
+// RENDERING
+// ============================================================================
+
+/// Run the compiled text through the target's exporter
+///
+/// `title` goes on the HTML page, the PDF title page, the Word cover
+/// page and header, and the EPUB's metadata; the PDF and Word options are
+/// the ones their own export windows save. EPUB has no window of its own:
+/// it takes the author from the Word options.
+pub fn render(
+    target: CompileTarget,
+    text: &str,
+    title: &str,
+    pdf_options: &PdfOptions,
+    docx_options: &DocxOptions,
+) -> Result<Vec<u8>> {
+    let bytes = match target {
+        CompileTarget::PlainText => export::export_plaintext(text).into_bytes(),
+        CompileTarget::Markdown => export::export_markdown(text).into_bytes(),
+        CompileTarget::Html => export::export_html(text, Some(title)).html.into_bytes(),
+        CompileTarget::Fountain => export::export_fountain(text).into_bytes(),
+        CompileTarget::FinalDraft => fdx::export_fdx(text).into_bytes(),
+        CompileTarget::Pdf => pdf::export_pdf(text, title, pdf_options)?.bytes,
+        CompileTarget::Docx => docx::export_docx(text, title, docx_options)?,
+        CompileTarget::Epub => epub::export_epub(text, title, &docx_options.author)?,
+    };
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts() -> Vec<Part> {
+        [
+            ("Opening", "\n\nThe sea was calm.\n\n\n"),
+            ("Empty", "  \n\n"),
+            ("Storm", "[CHAPTER: The Storm]\nRain.\r\n"),
+            ("Calm", "  [ACT: II]\nQuiet.\n"),
+            ("Ending", "It ended."),
+        ]
+        .into_iter()
+        .map(|(name, text)| Part {
+            name: name.to_string(),
+            text: text.to_string(),
+        })
+        .collect()
+    }
+
+    fn settings(separator: Separator) -> CompileSettings {
+        CompileSettings {
+            separator,
+            custom_separator: String::from("  * * *  "),
+            ..CompileSettings::default()
+        }
+    }
+
+    #[test]
+    fn documents_are_joined_in_order_by_a_blank_line() {
+        assert_eq!(
+            assemble(&parts(), &settings(Separator::BlankLine)),
+            "The sea was calm.\n\n[CHAPTER: The Storm]\nRain.\n\n  [ACT: II]\nQuiet.\n\nIt ended.\n"
+        );
+    }
+
+    #[test]
+    fn chapters_are_named_after_files_without_their_own() {
+        assert_eq!(
+            assemble(&parts(), &settings(Separator::ChapterFromFileName)),
+            "[CHAPTER: Opening]\n\nThe sea was calm.\n\n\
+             [CHAPTER: The Storm]\nRain.\n\n\
+             \x20 [ACT: II]\nQuiet.\n\n\
+             [CHAPTER: Ending]\n\nIt ended.\n"
+        );
+    }
+
+    #[test]
+    fn a_custom_line_goes_between_documents_only() {
+        assert_eq!(
+            assemble(&parts(), &settings(Separator::Custom)),
+            "The sea was calm.\n\n* * *\n\n[CHAPTER: The Storm]\nRain.\n\n\
+             * * *\n\n  [ACT: II]\nQuiet.\n\n* * *\n\nIt ended.\n"
+        );
+        // A blank custom line is no separator at all
+        let mut blank = settings(Separator::Custom);
+        blank.custom_separator = String::from("  ");
+        assert_eq!(
+            assemble(&parts(), &blank),
+            assemble(&parts(), &settings(Separator::BlankLine))
+        );
+    }
+
+    #[test]
+    fn front_matter_comes_first() {
+        let mut with_front = settings(Separator::Custom);
+        with_front.front_matter = String::from("For Ann.\n\n\n");
+        let text = assemble(&parts()[..1], &with_front);
+        assert_eq!(text, "For Ann.\n\nThe sea was calm.\n");
+        // Nothing at all to compile
+        assert_eq!(assemble(&[], &settings(Separator::BlankLine)), "");
+    }
+
+    #[test]
+    fn targets_round_trip_through_their_extensions() {
+        for target in CompileTarget::ALL {
+            assert_eq!(
+                CompileTarget::from_extension(target.extension()),
+                Some(target)
+            );
+        }
+        assert_eq!(
+            CompileTarget::from_extension("Markdown"),
+            Some(CompileTarget::Markdown)
+        );
+        assert_eq!(
+            CompileTarget::from_extension("TEXT"),
+            Some(CompileTarget::PlainText)
+        );
+        assert_eq!(CompileTarget::from_extension("odt"), None);
+    }
+
+    #[test]
+    fn every_target_renders() {
+        let text = assemble(&parts(), &settings(Separator::ChapterFromFileName));
+        for target in CompileTarget::ALL {
+            let bytes = render(
+                target,
+                &text,
+                "Tides",
+                &PdfOptions::default(),
+                &DocxOptions::default(),
+            )
+            .unwrap();
+            assert!(!bytes.is_empty(), "{:?}", target);
+        }
+        let plain = render(
+            CompileTarget::PlainText,
+            &text,
+            "Tides",
+            &PdfOptions::default(),
+            &DocxOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(plain, export::export_plaintext(&text).into_bytes());
+    }
+}
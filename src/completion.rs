@@ -0,0 +1,283 @@
+//! FILE: src/completion.rs
+//!
+//! Character name completion in the editor, so a name is spelled the same
+//! way every time it comes up.
+//!
+//! A list of known names pops up under the caret:
+//! - on an ALL-CAPS line where a cue goes (after a blank line), once two
+//!   capitals have been typed: "MA|" offers MARGARET and MARTIN
+//! - after `@` anywhere in a line: "said @ma|" offers the same two, and
+//!   replaces the `@ma` with the name picked
+//!
+//! Up/Down choose, Enter (or a click) accepts, Esc closes the list until the caret
+//! leaves the word. The names are the document's speakers (parser::cue_name),
+//! so a character is offered once they've spoken.
+//!
+//! Like editing.rs, finding and applying a completion is pure text work;
+//! App feeds it the caret and the keys, and this module draws the list.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - rfind / char_indices for scanning back from the caret
+//! - Reusing editing::EditCommand to describe the edit
+
+use crate::editing::{self, EditCommand};
+use crate::parser::{self, ParsedLine};
+
+/// Capitals needed on a cue line before names are offered (a lone "I" or
+/// "A" starting a sentence shouldn't pop anything up)
+const MIN_CUE_PREFIX: usize = 2;
+
+/// Most names listed at once
+const MAX_ITEMS: usize = 8;
+
+/// What opened the list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// An ALL-CAPS line in a cue's place
+    Cue,
+    /// An `@` before the caret
+    At,
+}
+
+/// An open completion list
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub trigger: Trigger,
+    /// Char index where the replaced text starts (the `@`, or the first
+    /// letter of the cue)
+    pub start: usize,
+    /// Char index of the caret, where the replaced text ends
+    pub end: usize,
+    /// The names on offer, best first
+    pub items: Vec<String>,
+    /// Which one Enter would pick
+    pub selected: usize,
+}
+
+impl Completion {
+    /// Move the selection down (1) or up (-1), wrapping around
+    pub fn move_selection(&mut self, offset: isize) {
+        let count = self.items.len() as isize;
+        self.selected = (self.selected as isize + offset).rem_euclid(count) as usize;
+    }
+
+    /// The edit that puts `items[index]` in place of the typed text
+    pub fn accept(&self, index: usize) -> EditCommand {
+        let name = self.items[index].clone();
+        EditCommand {
+            caret: self.start + name.chars().count(),
+            range: self.start..self.end,
+            insert: name,
+        }
+    }
+}
+
+/// Every speaker in the document, normalized ("HERO (V.O.)" → "HERO"), in
+/// order of first appearance
+pub fn known_names(parsed: &[ParsedLine]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (i, line) in parsed.iter().enumerate() {
+        let next = parsed.get(i + 1).map(|l| l.text.as_str());
+        if let Some(name) = parser::cue_name(line, next) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Where the text before `caret` could be completed, and how: the
+/// trigger, the char index the typed part starts at, and that part
+/// (upper-cased, without the `@`)
+///
+/// Cheap enough to run every frame; the names are only gathered once this
+/// finds something.
+pub fn context_at(text: &str, caret: usize) -> Option<(Trigger, usize, String)> {
+    let caret_byte = editing::char_to_byte(text, caret);
+    let line_start = text[..caret_byte].rfind('\n').map_or(0, |i| i + 1);
+    let before = &text[line_start..caret_byte];
+    let after = text[caret_byte..].split('\n').next().unwrap_or("");
+
+    // `@` wins: it's asked for explicitly
+    if let Some(at) = before.rfind('@') {
+        let typed = &before[at + 1..];
+        if typed.chars().all(is_name_char) {
+            let start = editing::byte_to_char(text, line_start + at);
+            return Some((Trigger::At, start, typed.to_uppercase()));
+        }
+    }
+
+    // A cue goes on a line of its own, after a blank line (or first in
+    // the document), and is typed at the end of that line
+    let typed = before.trim_start();
+    let letters = typed.chars().filter(|c| c.is_alphabetic()).count();
+    let above_is_blank = text[..line_start]
+        .strip_suffix('\n')
+        .is_none_or(|above| above.rsplit('\n').next().unwrap_or("").trim().is_empty());
+    if letters >= MIN_CUE_PREFIX
+        && after.trim().is_empty()
+        && above_is_blank
+        && typed.chars().all(is_name_char)
+        && !typed.chars().any(char::is_lowercase)
+    {
+        let start = editing::byte_to_char(text, caret_byte - typed.len());
+        return Some((Trigger::Cue, start, typed.to_string()));
+    }
+
+    None
+}
+
+/// Letters and what else appears in names ("O'BRIEN", "MARY-ANN", "DR. NO")
+fn is_name_char(c: char) -> bool {
+    c.is_alphabetic() || matches!(c, ' ' | '.' | '\'' | '-')
+}
+
+/// The completion list for `context` (from context_at), or None if no
+/// known name fits
+pub fn complete(
+    context: (Trigger, usize, String),
+    caret: usize,
+    names: &[String],
+) -> Option<Completion> {
+    let (trigger, start, typed) = context;
+    let items: Vec<String> = names
+        .iter()
+        // On a cue line, the name already typed in full needs no offer
+        .filter(|name| name.starts_with(&typed) && !(trigger == Trigger::Cue && **name == typed))
+        .take(MAX_ITEMS)
+        .cloned()
+        .collect();
+    if items.is_empty() {
+        return None;
+    }
+    Some(Completion {
+        trigger,
+        start,
+        end: caret,
+        items,
+        selected: 0,
+    })
+}
+
+/// Id of the list's Area
+fn area_id() -> egui::Id {
+    egui::Id::new("character_completion")
+}
+
+/// Is the mouse over the list? (Pressing on it takes the focus away from
+/// the editor, but mustn't close it before the click lands.)
+pub fn is_hovered(ctx: &egui::Context) -> bool {
+    let rect = ctx.memory(|m| m.area_rect(area_id()));
+    let pointer = ctx.input(|i| i.pointer.hover_pos());
+    matches!((rect, pointer), (Some(rect), Some(pos)) if rect.contains(pos))
+}
+
+/// Draw the list with its top left corner at `anchor` (under the caret)
+///
+/// Returns the index of the name clicked, if any.
+pub fn show(ctx: &egui::Context, anchor: egui::Pos2, completion: &Completion) -> Option<usize> {
+    let mut clicked = None;
+    egui::Area::new(area_id())
+        .fixed_pos(anchor)
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                for (index, name) in completion.items.iter().enumerate() {
+                    if ui
+                        .selectable_label(index == completion.selected, name)
+                        .clicked()
+                    {
+                        clicked = Some(index);
+                    }
+                }
+            });
+        });
+    clicked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// context_at with the caret at `|`
+    fn at_caret(text: &str) -> Option<(Trigger, usize, String)> {
+        let caret = editing::byte_to_char(text, text.find('|').unwrap());
+        context_at(&text.replacen('|', "", 1), caret)
+    }
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn known_names_are_the_speakers_in_order() {
+        let text = "MARGARET\nHello.\n\nMARTIN (V.O.)\nHi.\n\nMARGARET\nAgain.\n\nTHE END\n";
+        assert_eq!(
+            known_names(&parser::parse_document(text)),
+            ["MARGARET", "MARTIN"]
+        );
+    }
+
+    #[test]
+    fn a_cue_line_offers_names_after_two_capitals() {
+        assert_eq!(
+            at_caret("Intro.\n\nMA|"),
+            Some((Trigger::Cue, 8, String::from("MA")))
+        );
+        assert_eq!(
+            at_caret("  O'B|\nnext"),
+            Some((Trigger::Cue, 2, String::from("O'B")))
+        );
+        // One capital, no blank line above, lowercase, or text after the caret
+        assert_eq!(at_caret("Intro.\n\nM|"), None);
+        assert_eq!(at_caret("Intro.\nMA|"), None);
+        assert_eq!(at_caret("Intro.\n\nMa|"), None);
+        assert_eq!(at_caret("Intro.\n\nMA|RGE"), None);
+    }
+
+    #[test]
+    fn an_at_offers_names_anywhere() {
+        assert_eq!(
+            at_caret("She said “@ma|"),
+            Some((Trigger::At, 10, String::from("MA")))
+        );
+        assert_eq!(at_caret("@|"), Some((Trigger::At, 0, String::new())));
+        // The @ is for this word only
+        assert_eq!(at_caret("mail me@home, then|"), None);
+    }
+
+    #[test]
+    fn completing_and_accepting() {
+        let known = names(&["MARGARET", "MARTIN", "ANN"]);
+        let text = "She said @ma";
+        let mut completion = complete(at_caret("She said @ma|").unwrap(), 12, &known).unwrap();
+        assert_eq!(completion.items, ["MARGARET", "MARTIN"]);
+
+        completion.move_selection(-1);
+        assert_eq!(completion.selected, 1);
+        completion.move_selection(1);
+        assert_eq!(completion.selected, 0);
+
+        let command = completion.accept(1);
+        let mut text = text.to_string();
+        command.apply(&mut text);
+        assert_eq!(text, "She said MARTIN");
+        assert_eq!(command.caret, 15);
+
+        assert!(complete((Trigger::At, 0, String::from("Z")), 1, &known).is_none());
+    }
+
+    #[test]
+    fn a_cue_typed_in_full_is_not_offered_again() {
+        let known = names(&["ANN", "ANNA"]);
+        let completion = complete((Trigger::Cue, 0, String::from("ANN")), 3, &known).unwrap();
+        assert_eq!(completion.items, ["ANNA"]);
+        let completion = complete((Trigger::At, 0, String::from("ANN")), 4, &known).unwrap();
+        assert_eq!(completion.items, ["ANN", "ANNA"]);
+
+        let many: Vec<String> = (0..20).map(|i| format!("AB{i}")).collect();
+        let completion = complete((Trigger::Cue, 0, String::from("AB")), 2, &many).unwrap();
+        assert_eq!(completion.items.len(), MAX_ITEMS);
+    }
+}
@@ -0,0 +1,176 @@
+//! FILE: src/corkboard.rs
+//!
+//! View → Corkboard: the document's scenes as index cards on a grid, in
+//! place of the editor.
+//!
+//! Each card shows the scene's description, status, point of view,
+//! synopsis (see parser::SceneMeta) and length. Dragging a card onto
+//! another moves the scene there - in the text itself, which is what
+//! move_scene does.
+//!
+//! MOVING SCENES:
+//! Each scene occupies a "slot": the lines from its tag up to the next
+//! scene, chapter or act tag. Reordering keeps the slots where they are and
+//! deals the scenes out to them in the new order, so chapter and act tags
+//! stay put and a scene dragged past one simply changes chapter. The blank
+//! lines at the end of a slot belong to the slot, not the scene, so the
+//! spacing between scenes doesn't change either.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - egui's drag and drop (dnd_drag_source / dnd_drop_zone) with a typed
+//!   payload
+//! - str::split_inclusive to cut text into lines without losing the line
+//!   breaks
+
+use crate::app::format_count;
+use crate::parser::{DocumentStructure, Scene};
+
+/// Width of a card (points)
+const CARD_WIDTH: f32 = 220.0;
+
+/// Most characters of the synopsis shown on a card
+const SYNOPSIS_PREVIEW: usize = 240;
+
+/// What the user did on the corkboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorkboardAction {
+    /// Move the scene at the first index so it ends up at the second
+    Move(usize, usize),
+    /// Go to this 1-based line in the editor
+    Open(usize),
+}
+
+/// Draw the cards
+///
+/// Nothing changes here - App applies the returned action.
+pub fn show(ui: &mut egui::Ui, structure: &DocumentStructure) -> Option<CorkboardAction> {
+    if structure.scenes.is_empty() {
+        ui.label("No scenes yet.");
+        ui.label("Add [SCENE: ...] tags, and each scene becomes a card here.");
+        return None;
+    }
+
+    let mut action = None;
+    egui::ScrollArea::vertical()
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                ui.spacing_mut().item_spacing = egui::vec2(12.0, 12.0);
+                for (index, scene) in structure.scenes.iter().enumerate() {
+                    let frame = egui::Frame::default().inner_margin(10.0).rounding(6.0);
+                    let (card, dropped) = ui.dnd_drop_zone::<usize, _>(frame, |ui| {
+                        let id = egui::Id::new(("scene_card", index));
+                        ui.dnd_drag_source(id, index, |ui| card(ui, scene)).inner
+                    });
+                    if let Some(from) = dropped {
+                        if *from != index {
+                            action = Some(CorkboardAction::Move(*from, index));
+                        }
+                    }
+                    if card.inner {
+                        action = Some(CorkboardAction::Open(scene.line_start));
+                    }
+                }
+            });
+        });
+    action
+}
+
+/// One card's contents; returns true if "Go to" was clicked
+fn card(ui: &mut egui::Ui, scene: &Scene) -> bool {
+    ui.set_width(CARD_WIDTH);
+    ui.set_min_height(120.0);
+    let mut open = false;
+
+    ui.vertical(|ui| {
+        ui.horizontal(|ui| {
+            ui.strong(&scene.description);
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                open = ui.small_button("Go to").clicked();
+            });
+        });
+
+        let mut details = Vec::new();
+        if let Some(status) = scene.meta.status {
+            details.push(status.key().to_string());
+        }
+        if !scene.meta.pov.is_empty() {
+            details.push(format!("POV {}", scene.meta.pov));
+        }
+        details.push(format!("{} words", format_count(scene.word_count)));
+        ui.weak(details.join(" · "));
+        ui.separator();
+
+        match scene.meta.synopsis.as_str() {
+            "" => {
+                ui.weak("No synopsis");
+            }
+            synopsis => {
+                let mut preview: String = synopsis.chars().take(SYNOPSIS_PREVIEW).collect();
+                if preview.len() < synopsis.len() {
+                    preview.push('…');
+                }
+                ui.label(preview);
+            }
+        }
+    });
+    open
+}
+
+/// The text with the scene at `from` moved so it becomes scene number `to`
+/// (see module docs)
+///
+/// `structure` must have been built from this same text. None if either
+/// index is out of range or they're the same.
+pub fn move_scene(
+    text: &str,
+    structure: &DocumentStructure,
+    from: usize,
+    to: usize,
+) -> Option<String> {
+    let scenes = &structure.scenes;
+    if from == to || from >= scenes.len() || to >= scenes.len() {
+        return None;
+    }
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+
+    // Each slot: its scene's lines without trailing blank lines, and those
+    // blank lines
+    let slots: Vec<(String, String)> = scenes
+        .iter()
+        .map(|scene| {
+            let slot = &lines[scene.line_start - 1..scene.line_end.min(lines.len())];
+            let content_len = slot
+                .iter()
+                .rposition(|line| !line.trim().is_empty())
+                .map_or(0, |i| i + 1);
+            let mut content = slot[..content_len].concat();
+            // The last line of the document may not have a line break
+            if !content.ends_with('\n') {
+                content.push('\n');
+            }
+            (content, slot[content_len..].concat())
+        })
+        .collect();
+
+    let mut order: Vec<usize> = (0..scenes.len()).collect();
+    let moved = order.remove(from);
+    order.insert(to, moved);
+
+    let mut out = String::with_capacity(text.len() + 1);
+    let mut next_line = 1;
+    for (slot, scene) in scenes.iter().enumerate() {
+        out.push_str(&lines[next_line - 1..scene.line_start - 1].concat());
+        out.push_str(&slots[order[slot]].0);
+        out.push_str(&slots[slot].1);
+        next_line = scene.line_end + 1;
+    }
+    if next_line <= lines.len() {
+        out.push_str(&lines[next_line - 1..].concat());
+    }
+    // Keep a document without a final line break that way
+    if !text.ends_with('\n') && out.ends_with('\n') {
+        out.pop();
+    }
+    Some(out)
+}
@@ -0,0 +1,176 @@
+//! FILE: src/dialogs.rs
+//!
+//! Native file picker dialogs (Open / Save As) via the `rfd` crate.
+//!
+//! Every function here blocks until the user picks a file or cancels, and
+//! returns `Option<PathBuf>`: None means "cancelled", which callers treat as
+//! "do nothing" - not as an error.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Builder pattern (rfd::FileDialog::new().add_filter(...).pick_file())
+//! - Option combinators: and_then, map, or_else
+
+use crate::encryption;
+use std::path::{Path, PathBuf};
+
+/// Extensions shown by the "BookScript" filter (`.bksx` is
+/// password-protected)
+const BOOKSCRIPT_EXTENSIONS: [&str; 3] = ["bks", "bksx", "scr"];
+
+/// Extensions shown by the "Fountain" filter (converted on open)
+const FOUNTAIN_EXTENSIONS: [&str; 2] = ["fountain", "spmd"];
+
+/// Extensions shown by the "Final Draft" filter (converted on open)
+const FINAL_DRAFT_EXTENSIONS: [&str; 1] = ["fdx"];
+
+/// Extension added to bare names typed into Save As
+const DEFAULT_EXTENSION: &str = "bks";
+
+/// Show an Open dialog for BookScript documents (or Fountain and Final
+/// Draft screenplays)
+///
+/// Starts in the folder of the currently open file, or the user's
+/// documents folder when nothing is open.
+pub fn pick_document(current: Option<&Path>) -> Option<PathBuf> {
+    let mut dialog = rfd::FileDialog::new()
+        .set_title("Open")
+        .add_filter("BookScript", &BOOKSCRIPT_EXTENSIONS)
+        .add_filter("Fountain", &FOUNTAIN_EXTENSIONS)
+        .add_filter("Final Draft", &FINAL_DRAFT_EXTENSIONS)
+        .add_filter("All files", &["*"]);
+
+    if let Some(dir) = start_directory(current) {
+        dialog = dialog.set_directory(dir);
+    }
+
+    dialog.pick_file()
+}
+
+/// Show a Save As dialog for BookScript documents
+///
+/// The file name is pre-filled with the current file's name (or
+/// "untitled.bks"), and a bare name like "chapter1" comes back as
+/// "chapter1.bks". An `encrypted` document gets `.bksx` instead, and one
+/// whose password was removed goes from `.bksx` back to `.bks`.
+pub fn save_document_as(current: Option<&Path>, encrypted: bool) -> Option<PathBuf> {
+    let extension = if encrypted {
+        encryption::EXTENSION
+    } else {
+        DEFAULT_EXTENSION
+    };
+    let file_name = current
+        .and_then(|p| p.file_name())
+        .map(|name| {
+            let name = Path::new(name);
+            let protected = name
+                .extension()
+                .is_some_and(|ext| ext == encryption::EXTENSION);
+            if encrypted || protected {
+                name.with_extension(extension)
+            } else {
+                name.to_path_buf()
+            }
+        })
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| format!("untitled.{}", extension));
+
+    let mut dialog = rfd::FileDialog::new()
+        .set_title("Save As")
+        .set_file_name(file_name);
+    dialog = if encrypted {
+        dialog.add_filter("Password-protected BookScript", &[encryption::EXTENSION])
+    } else {
+        dialog.add_filter("BookScript", &BOOKSCRIPT_EXTENSIONS)
+    };
+
+    if let Some(dir) = start_directory(current) {
+        dialog = dialog.set_directory(dir);
+    }
+
+    dialog
+        .save_file()
+        .map(|path| with_default_extension(path, extension))
+}
+
+/// Show an Open dialog for an arbitrary file type
+///
+/// `filter_name` / `extensions` describe the single filter offered
+/// (e.g. "Profile", ["json"]).
+pub fn pick_file(
+    title: &str,
+    filter_name: &str,
+    extensions: &[&str],
+    current: Option<&Path>,
+) -> Option<PathBuf> {
+    let mut dialog = rfd::FileDialog::new()
+        .set_title(title)
+        .add_filter(filter_name, extensions);
+
+    if let Some(dir) = start_directory(current) {
+        dialog = dialog.set_directory(dir);
+    }
+
+    dialog.pick_file()
+}
+
+/// Show a dialog for choosing a folder
+pub fn pick_folder(title: &str, current: Option<&Path>) -> Option<PathBuf> {
+    let mut dialog = rfd::FileDialog::new().set_title(title);
+    if let Some(dir) = current.filter(|dir| dir.is_dir()) {
+        dialog = dialog.set_directory(dir);
+    }
+    dialog.pick_folder()
+}
+
+/// Show a Save dialog for an arbitrary file type
+///
+/// The first entry of `extensions` is appended when the user types a name
+/// without one.
+pub fn save_file(
+    title: &str,
+    filter_name: &str,
+    extensions: &[&str],
+    file_name: &str,
+    current: Option<&Path>,
+) -> Option<PathBuf> {
+    let mut dialog = rfd::FileDialog::new()
+        .set_title(title)
+        .add_filter(filter_name, extensions)
+        .set_file_name(file_name);
+
+    if let Some(dir) = start_directory(current) {
+        dialog = dialog.set_directory(dir);
+    }
+
+    let default_extension = extensions.first().copied().unwrap_or(DEFAULT_EXTENSION);
+    dialog
+        .save_file()
+        .map(|path| with_default_extension(path, default_extension))
+}
+
+/// Folder a dialog should open in
+///
+/// 1. The folder of the current file, if there is one
+/// 2. Otherwise the user's Documents folder
+/// 3. Otherwise None (the dialog picks its own default)
+pub fn start_directory(current: Option<&Path>) -> Option<PathBuf> {
+    current
+        .and_then(|p| p.parent())
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+        .or_else(|| {
+            directories::UserDirs::new().and_then(|dirs| dirs.document_dir().map(Path::to_path_buf))
+        })
+}
+
+/// Add `extension` to a path that doesn't have one
+///
+/// "notes" → "notes.bks", but "notes.txt" stays "notes.txt" - if the user
+/// typed an extension, they meant it.
+pub fn with_default_extension(path: PathBuf, extension: &str) -> PathBuf {
+    if path.extension().is_some() {
+        path
+    } else {
+        path.with_extension(extension)
+    }
+}
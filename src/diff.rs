@@ -0,0 +1,463 @@
+//! FILE: src/diff.rs
+//!
+//! Line-by-line differences between an earlier version of a document (a
+//! named snapshot, a version-history snapshot or a save backup) and the
+//! editor text, for the Compare window.
+//!
+//! The differences come from Myers' algorithm: the shortest list of line
+//! removals and additions that turns the old text into the new one. Runs of
+//! changed lines form hunks, and a hunk can be reverted on its own - its
+//! new lines swapped back for the old ones - leaving every other change in
+//! place.
+//!
+//! Lines are compared with their line breaks (split_inclusive), so putting
+//! the hunks back together always gives the exact text.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - isize indexes for an algorithm that walks diagonals either side of 0
+//! - Keeping a trace of earlier states to walk back through them
+
+use std::ops::Range;
+
+/// Unchanged lines shown either side of a hunk
+const CONTEXT_LINES: usize = 3;
+
+/// Most changed lines worked out one by one (see shortest_edit)
+const MAX_EDITS: isize = 2000;
+
+/// What happened to a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineChange {
+    Same,
+    /// Only in the old text
+    Removed,
+    /// Only in the new text
+    Added,
+}
+
+/// One line of the comparison, in the order they're shown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub change: LineChange,
+    /// The line without its line break
+    pub text: String,
+    /// 1-based line number in the old and the new text (None for a line
+    /// that's only in the other one)
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+}
+
+/// A run of changed lines: 0-based line index ranges in the old and the
+/// new text (either may be empty) and the span of `Diff::lines` it covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+    pub lines: Range<usize>,
+}
+
+/// Every line of both texts, with the hunks among them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diff {
+    pub lines: Vec<DiffLine>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl Diff {
+    /// Lines added and removed in all
+    pub fn counts(&self) -> (usize, usize) {
+        self.hunks.iter().fold((0, 0), |(added, removed), hunk| {
+            (added + hunk.new.len(), removed + hunk.old.len())
+        })
+    }
+}
+
+/// Compare `old` with `new`, line by line
+pub fn diff(old: &str, new: &str) -> Diff {
+    let a: Vec<&str> = old.split_inclusive('\n').collect();
+    let b: Vec<&str> = new.split_inclusive('\n').collect();
+
+    // Most comparisons are a few edits in a long text: the shared start and
+    // end are matched here, and only the middle goes through Myers
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let middle = shortest_edit(&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let changes = std::iter::repeat_n(LineChange::Same, prefix)
+        .chain(middle)
+        .chain(std::iter::repeat_n(LineChange::Same, suffix));
+
+    let mut result = Diff::default();
+    let (mut i, mut j) = (0, 0);
+    let mut open: Option<Hunk> = None;
+    for change in changes {
+        let (text, old_line, new_line) = match change {
+            LineChange::Same => (a[i], Some(i + 1), Some(j + 1)),
+            LineChange::Removed => (a[i], Some(i + 1), None),
+            LineChange::Added => (b[j], None, Some(j + 1)),
+        };
+        if change == LineChange::Same {
+            if let Some(hunk) = open.take() {
+                result.hunks.push(hunk);
+            }
+        } else {
+            let index = result.lines.len();
+            let hunk = open.get_or_insert(Hunk {
+                old: i..i,
+                new: j..j,
+                lines: index..index,
+            });
+            hunk.lines.end = index + 1;
+        }
+        if change != LineChange::Added {
+            i += 1;
+        }
+        if change != LineChange::Removed {
+            j += 1;
+        }
+        if let Some(hunk) = &mut open {
+            hunk.old.end = i;
+            hunk.new.end = j;
+        }
+        result.lines.push(DiffLine {
+            change,
+            text: text.strip_suffix('\n').unwrap_or(text).to_string(),
+            old_line,
+            new_line,
+        });
+    }
+    result.hunks.extend(open);
+    result
+}
+
+/// The fewest removals and additions turning `a` into `b` (Myers), as a
+/// change per line in order
+///
+/// Past MAX_EDITS changed lines, the lines are simply all removed and all
+/// added again: the trace kept for walking back grows with the square of
+/// the number of changes.
+fn shortest_edit(a: &[&str], b: &[&str]) -> Vec<LineChange> {
+    if a.is_empty() || b.is_empty() {
+        return replace_all(a, b);
+    }
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    // v[k + max]: how far along `a` the furthest path on diagonal k
+    // (x - y = k) has got
+    let index = |k: isize| (k + max) as usize;
+    let mut v = vec![0isize; 2 * max as usize + 2];
+    // trace[d]: the diagonals -d to d + 1 of v before the d-th round, all
+    // that round reads
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max.min(MAX_EDITS) {
+        trace.push(v[index(-d)..index(d) + 2].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let down = k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]);
+            let mut x = if down {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+        if d == MAX_EDITS {
+            return replace_all(a, b);
+        }
+    }
+
+    // Walk back from the end, one round at a time
+    let mut changes = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| v[(k + d) as usize];
+        let k = x - y;
+        let down = k == -d || (k != d && at(k - 1) < at(k + 1));
+        let previous_k = if down { k + 1 } else { k - 1 };
+        let previous_x = at(previous_k);
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            changes.push(LineChange::Same);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            changes.push(if down {
+                LineChange::Added
+            } else {
+                LineChange::Removed
+            });
+        }
+        (x, y) = (previous_x, previous_y);
+    }
+    changes.reverse();
+    changes
+}
+
+/// Every line of `a` removed, then every line of `b` added
+fn replace_all(a: &[&str], b: &[&str]) -> Vec<LineChange> {
+    let removed = std::iter::repeat_n(LineChange::Removed, a.len());
+    removed
+        .chain(std::iter::repeat_n(LineChange::Added, b.len()))
+        .collect()
+}
+
+/// `new` with one hunk put back as it was in `old`
+///
+/// `hunk` must come from diff(old, new).
+pub fn revert(old: &str, new: &str, hunk: &Hunk) -> String {
+    let a: Vec<&str> = old.split_inclusive('\n').collect();
+    let b: Vec<&str> = new.split_inclusive('\n').collect();
+    let mut out = String::with_capacity(new.len());
+    out.extend(b[..hunk.new.start].iter().copied());
+    out.extend(a[hunk.old.clone()].iter().copied());
+    out.extend(b[hunk.new.end..].iter().copied());
+    out
+}
+
+/// Draw the differences: each hunk with a few unchanged lines around it,
+/// removed lines red and added lines green
+///
+/// Returns the index of the hunk whose Revert button was clicked, if any.
+pub fn show(ui: &mut egui::Ui, diff: &Diff, can_revert: bool) -> Option<usize> {
+    if diff.hunks.is_empty() {
+        ui.label("No differences.");
+        return None;
+    }
+
+    let (removed_fill, added_fill) = if ui.visuals().dark_mode {
+        (
+            egui::Color32::from_rgb(0x5a, 0x22, 0x22),
+            egui::Color32::from_rgb(0x1f, 0x4a, 0x26),
+        )
+    } else {
+        (
+            egui::Color32::from_rgb(0xfb, 0xd4, 0xd4),
+            egui::Color32::from_rgb(0xd4, 0xf4, 0xd8),
+        )
+    };
+
+    let mut revert = None;
+    egui::ScrollArea::vertical()
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            for (index, hunk) in diff.hunks.iter().enumerate() {
+                let from = hunk.lines.start.saturating_sub(CONTEXT_LINES);
+                let to = (hunk.lines.end + CONTEXT_LINES).min(diff.lines.len());
+
+                ui.horizontal(|ui| {
+                    ui.strong(hunk_label(hunk));
+                    if ui
+                        .add_enabled(can_revert, egui::Button::new("Revert").small())
+                        .on_hover_text("Put these lines back as they were")
+                        .clicked()
+                    {
+                        revert = Some(index);
+                    }
+                });
+                for line in &diff.lines[from..to] {
+                    let (sign, fill) = match line.change {
+                        LineChange::Same => (' ', egui::Color32::TRANSPARENT),
+                        LineChange::Removed => ('-', removed_fill),
+                        LineChange::Added => ('+', added_fill),
+                    };
+                    let text = egui::RichText::new(format!("{} {}", sign, line.text))
+                        .monospace()
+                        .background_color(fill);
+                    ui.label(text);
+                }
+                ui.separator();
+            }
+        });
+    revert
+}
+
+/// "Lines 12–14" (of the current text), or where lines were removed
+pub fn hunk_label(hunk: &Hunk) -> String {
+    match hunk.new.len() {
+        0 if hunk.new.start == 0 => "Removed at the start".to_string(),
+        0 => format!("Removed after line {}", hunk.new.start),
+        1 => format!("Line {}", hunk.new.start + 1),
+        _ => format!("Lines {}–{}", hunk.new.start + 1, hunk.new.end),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each line as "-text", "+text" or " text"
+    fn marked(diff: &Diff) -> Vec<String> {
+        diff.lines
+            .iter()
+            .map(|line| {
+                let mark = match line.change {
+                    LineChange::Same => ' ',
+                    LineChange::Removed => '-',
+                    LineChange::Added => '+',
+                };
+                format!("{}{}", mark, line.text)
+            })
+            .collect()
+    }
+
+    /// Reverting every hunk, last first, must give back `old` exactly
+    fn assert_reverts_to_old(old: &str, new: &str) {
+        let diff = diff(old, new);
+        let mut text = new.to_string();
+        for hunk in diff.hunks.iter().rev() {
+            text = revert(old, &text, hunk);
+        }
+        assert_eq!(text, old);
+    }
+
+    #[test]
+    fn identical_texts_have_no_hunks() {
+        let text = "One\nTwo\nThree\n";
+        let same = diff(text, text);
+        assert!(same.hunks.is_empty());
+        assert_eq!(same.counts(), (0, 0));
+        assert_eq!(marked(&same), [" One", " Two", " Three"]);
+        assert_eq!(same.lines[2].old_line, Some(3));
+        assert_eq!(same.lines[2].new_line, Some(3));
+        assert_eq!(diff("", ""), Diff::default());
+    }
+
+    #[test]
+    fn an_insertion_is_one_added_hunk() {
+        let old = "One\nTwo\nThree\n";
+        let new = "One\nTwo\nNew A\nNew B\nThree\n";
+        let inserted = diff(old, new);
+        assert_eq!(
+            marked(&inserted),
+            [" One", " Two", "+New A", "+New B", " Three"]
+        );
+        assert_eq!(
+            inserted.hunks,
+            [Hunk {
+                old: 2..2,
+                new: 2..4,
+                lines: 2..4
+            }]
+        );
+        assert_eq!(inserted.counts(), (2, 0));
+        assert_eq!(inserted.lines[4].old_line, Some(3));
+        assert_eq!(inserted.lines[4].new_line, Some(5));
+        assert_eq!(hunk_label(&inserted.hunks[0]), "Lines 3–4");
+        assert_reverts_to_old(old, new);
+        assert_reverts_to_old("", new);
+    }
+
+    #[test]
+    fn a_deletion_is_one_removed_hunk() {
+        let old = "One\nTwo\nThree\n";
+        let deleted = diff(old, "Two\nThree\n");
+        assert_eq!(marked(&deleted), ["-One", " Two", " Three"]);
+        assert_eq!(deleted.hunks[0].old, 0..1);
+        assert_eq!(deleted.hunks[0].new, 0..0);
+        assert_eq!(deleted.counts(), (0, 1));
+        assert_eq!(hunk_label(&deleted.hunks[0]), "Removed at the start");
+
+        let middle = diff(old, "One\nThree\n");
+        assert_eq!(hunk_label(&middle.hunks[0]), "Removed after line 1");
+        assert_reverts_to_old(old, "One\nThree\n");
+        assert_reverts_to_old(old, "");
+    }
+
+    #[test]
+    fn a_missing_final_line_break_is_a_change() {
+        let old = "One\nTwo\n";
+        let new = "One\nTwo";
+        let changed = diff(old, new);
+        // Shown the same, but the line differs by its break
+        assert_eq!(marked(&changed), [" One", "-Two", "+Two"]);
+        assert_eq!(changed.counts(), (1, 1));
+        assert_eq!(revert(old, new, &changed.hunks[0]), old);
+        assert_reverts_to_old(new, old);
+    }
+
+    #[test]
+    fn the_fewest_changes_are_found() {
+        let old = "a\nb\nc\na\nb\nb\na\n";
+        let new = "c\nb\na\nb\na\nc\n";
+        // Myers' own example: five edits is the least there is
+        let changes = diff(old, new);
+        let (added, removed) = changes.counts();
+        assert_eq!(added + removed, 5);
+        assert_reverts_to_old(old, new);
+    }
+
+    #[test]
+    fn reverting_one_hunk_keeps_the_others() {
+        let old = "Title\nOne\nTwo\nThree\nFour\nFive\nSix\nSeven\nEight\nEnd\n";
+        let new = "Title\nOne!\nTwo\nThree\nFour\nFive\nSix\nSeven\nEnd\nCoda\n";
+        let changes = diff(old, new);
+        assert_eq!(changes.hunks.len(), 3);
+
+        for (index, hunk) in changes.hunks.iter().enumerate() {
+            let reverted = revert(old, new, hunk);
+            let left = diff(old, &reverted);
+            // The reverted hunk is gone; the others are exactly as they were
+            let others: Vec<&Hunk> = changes
+                .hunks
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != index)
+                .map(|(_, h)| h)
+                .collect();
+            assert_eq!(left.hunks.len(), 2);
+            for (kept, before) in left.hunks.iter().zip(others) {
+                assert_eq!(kept.old, before.old);
+                assert_eq!(
+                    marked(&left)[kept.lines.clone()],
+                    marked(&changes)[before.lines.clone()]
+                );
+            }
+        }
+        assert_eq!(
+            revert(old, new, &changes.hunks[0]),
+            "Title\nOne\nTwo\nThree\nFour\nFive\nSix\nSeven\nEnd\nCoda\n"
+        );
+    }
+
+    #[test]
+    fn too_many_changes_become_one_replacement() {
+        // Every line changed except one in the middle, which Myers would
+        // keep; past MAX_EDITS it's replaced with the rest
+        let lines = |prefix: &str| -> String {
+            let half = (MAX_EDITS as usize + 200) / 2;
+            let mut text = String::new();
+            for i in 0..half {
+                text.push_str(&format!("{}{}\n", prefix, i));
+            }
+            text.push_str("shared\n");
+            for i in half..2 * half {
+                text.push_str(&format!("{}{}\n", prefix, i));
+            }
+            text
+        };
+        let (old, new) = (lines("old "), lines("new "));
+        let replaced = diff(&old, &new);
+        assert_eq!(replaced.hunks.len(), 1);
+        let count = old.lines().count();
+        assert_eq!(replaced.counts(), (count, count));
+        assert_reverts_to_old(&old, &new);
+
+        // Under the limit, the shared line is kept
+        let kept = diff("a\nshared\nb\n", "c\nshared\nd\n");
+        assert_eq!(kept.hunks.len(), 2);
+    }
+}
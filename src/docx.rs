@@ -0,0 +1,436 @@
+//! FILE: src/docx.rs
+//!
+//! File → Export → Word (DOCX): the document in standard manuscript format,
+//! the way agents and publishers ask for submissions.
+//!
+//! STANDARD MANUSCRIPT FORMAT:
+//! - 12 pt Times New Roman, double spaced, 1" margins on US Letter
+//! - Paragraphs indented half an inch, with no space between them
+//! - Each chapter starts on a new page, its title centered a third of the
+//!   way down
+//! - Scene breaks are a centered `#` (the scene's description isn't
+//!   printed, as in the plain-text export)
+//! - A header on every page, top right: "Surname / Title / 7"
+//! - Optionally a cover page: contact details top left, the word count
+//!   top right, title and byline in the middle, and no header
+//! - "END" after the last line
+//!
+//! A .docx file is a zip archive of XML files (Office Open XML). The
+//! smallest set Word accepts is built here by hand: the content types, the
+//! package relationships, the document, its styles and its header.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Writing a zip archive into memory (zip::ZipWriter over a Cursor<Vec<u8>>)
+//! - Building XML by hand, with escaping
+
+use crate::app::format_count;
+use crate::export::{escape_html, structural_tag};
+use crate::parser::{self, TagType};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+
+// ============================================================================
+// OPTIONS
+// ============================================================================
+
+/// What the cover page and header say about the author, saved with the
+/// settings
+///
+/// The title belongs to the document, so the export window suggests one
+/// from the file name each time instead.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocxOptions {
+    /// Start with a cover page
+    pub cover_page: bool,
+    /// The author's name; its last word goes in the page header
+    pub author: String,
+    /// Name, address, phone, email: top left of the cover page, one item
+    /// per line
+    pub contact: String,
+}
+
+impl Default for DocxOptions {
+    fn default() -> Self {
+        Self {
+            cover_page: true,
+            author: String::new(),
+            contact: String::new(),
+        }
+    }
+}
+
+// ============================================================================
+// EXPORT
+// ============================================================================
+
+/// Build a .docx file for `text`
+///
+/// Err only if the zip archive can't be written, which in memory means
+/// something is badly wrong.
+pub fn export_docx(text: &str, title: &str, options: &DocxOptions) -> Result<Vec<u8>> {
+    let document = document_xml(text, title, options);
+    let header = header_xml(title, &options.author);
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let deflated = SimpleFileOptions::default();
+    for (name, content) in [
+        ("[Content_Types].xml", CONTENT_TYPES),
+        ("_rels/.rels", PACKAGE_RELATIONSHIPS),
+        ("word/_rels/document.xml.rels", DOCUMENT_RELATIONSHIPS),
+        ("word/styles.xml", STYLES),
+        ("word/document.xml", document.as_str()),
+        ("word/header1.xml", header.as_str()),
+    ] {
+        zip.start_file(name, deflated)?;
+        zip.write_all(content.as_bytes())?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// word/document.xml: the cover page and the manuscript itself
+fn document_xml(text: &str, title: &str, options: &DocxOptions) -> String {
+    let mut body = Body::default();
+
+    if options.cover_page {
+        cover_page(&mut body, text, title, options);
+    }
+
+    // True once prose has been written since the last heading, so a scene
+    // break right under a chapter title is left out
+    let mut prose_since_heading = false;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        // Bare cues and stage directions are prose here, as in the other
+        // manuscript exports; malformed tags are printed as they are
+        match structural_tag(line) {
+            Some(TagType::Act(title)) => {
+                body.chapter_title(&format!("ACT {}", title.to_uppercase()));
+                prose_since_heading = false;
+            }
+            Some(TagType::Chapter(title)) => {
+                body.chapter_title(&title);
+                prose_since_heading = false;
+            }
+            Some(TagType::Scene(_)) => {
+                if prose_since_heading {
+                    body.centered("#");
+                }
+                prose_since_heading = false;
+            }
+            Some(TagType::Character(name)) => {
+                body.paragraph(&name.to_uppercase());
+                prose_since_heading = true;
+            }
+            _ => {
+                body.paragraph(trimmed);
+                prose_since_heading = true;
+            }
+        }
+    }
+    body.centered("END");
+
+    // titlePg gives the first page its own (empty) header, which keeps the
+    // running header off the cover page
+    let title_page = if options.cover_page {
+        "<w:titlePg/>"
+    } else {
+        ""
+    };
+    format!(
+        "{XML_DECLARATION}<w:document {NAMESPACES}><w:body>{}\
+         <w:sectPr><w:headerReference w:type=\"default\" r:id=\"rIdHeader\"/>\
+         <w:pgSz w:w=\"12240\" w:h=\"15840\"/>\
+         <w:pgMar w:top=\"1440\" w:right=\"1440\" w:bottom=\"1440\" w:left=\"1440\" \
+         w:header=\"720\" w:footer=\"720\" w:gutter=\"0\"/>{}</w:sectPr>\
+         </w:body></w:document>",
+        body.xml, title_page
+    )
+}
+
+/// Contact details and word count at the top, title and byline halfway down
+fn cover_page(body: &mut Body, text: &str, title: &str, options: &DocxOptions) {
+    for line in options.contact.lines().map(str::trim) {
+        if !line.is_empty() {
+            body.raw_paragraph(SINGLE_SPACED, line);
+        }
+    }
+    // Manuscripts round the count to the nearest hundred
+    let words = parser::count_stats(text, true).words;
+    let rounded = (words + 50) / 100 * 100;
+    body.raw_paragraph(
+        &format!("{SINGLE_SPACED}<w:jc w:val=\"right\"/>"),
+        &format!("about {} words", format_count(rounded.max(100))),
+    );
+
+    body.raw_paragraph(
+        "<w:spacing w:before=\"4320\"/><w:ind w:firstLine=\"0\"/><w:jc w:val=\"center\"/>",
+        &title.to_uppercase(),
+    );
+    let author = options.author.trim();
+    if !author.is_empty() {
+        body.centered(&format!("by {}", author));
+    }
+    body.page_break();
+}
+
+/// word/header1.xml: "Surname / Title / page number", right-aligned
+fn header_xml(title: &str, author: &str) -> String {
+    let surname = author.split_whitespace().last();
+    let label = match surname {
+        Some(surname) => format!("{} / {} / ", surname, title),
+        None => format!("{} / ", title),
+    };
+    format!(
+        "{XML_DECLARATION}<w:hdr {NAMESPACES}><w:p><w:pPr>\
+         <w:spacing w:line=\"240\" w:lineRule=\"auto\"/><w:ind w:firstLine=\"0\"/><w:jc w:val=\"right\"/></w:pPr>\
+         <w:r><w:t xml:space=\"preserve\">{}</w:t></w:r>\
+         <w:fldSimple w:instr=\"PAGE\"><w:r><w:t>1</w:t></w:r></w:fldSimple></w:p></w:hdr>",
+        escape_html(&label)
+    )
+}
+
+/// Collects the body's paragraphs
+#[derive(Default)]
+struct Body {
+    xml: String,
+    /// Nothing has been written since the last page break (or at all), so a
+    /// chapter doesn't need a break of its own
+    at_page_start: bool,
+    /// Something has been written
+    started: bool,
+}
+
+impl Body {
+    /// A normal, indented, double-spaced paragraph (the Normal style)
+    fn paragraph(&mut self, text: &str) {
+        self.raw_paragraph("", text);
+    }
+
+    /// A centered paragraph with no indent (scene breaks, END, bylines)
+    fn centered(&mut self, text: &str) {
+        self.raw_paragraph("<w:ind w:firstLine=\"0\"/><w:jc w:val=\"center\"/>", text);
+    }
+
+    /// A chapter title: on a new page, a third of the way down
+    fn chapter_title(&mut self, text: &str) {
+        if self.started && !self.at_page_start {
+            self.page_break();
+        }
+        self.raw_paragraph(
+            "<w:spacing w:before=\"2880\"/><w:ind w:firstLine=\"0\"/><w:jc w:val=\"center\"/>",
+            text,
+        );
+    }
+
+    /// A paragraph with these paragraph properties (`<w:pPr>` contents)
+    fn raw_paragraph(&mut self, properties: &str, text: &str) {
+        self.xml.push_str("<w:p>");
+        if !properties.is_empty() {
+            self.xml.push_str(&format!("<w:pPr>{}</w:pPr>", properties));
+        }
+        self.xml.push_str(&format!(
+            "<w:r><w:t xml:space=\"preserve\">{}</w:t></w:r></w:p>",
+            escape_html(text)
+        ));
+        self.started = true;
+        self.at_page_start = false;
+    }
+
+    fn page_break(&mut self) {
+        self.xml
+            .push_str("<w:p><w:r><w:br w:type=\"page\"/></w:r></w:p>");
+        self.at_page_start = true;
+    }
+}
+
+// ============================================================================
+// FIXED PARTS OF THE PACKAGE
+// ============================================================================
+
+const XML_DECLARATION: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n";
+
+const NAMESPACES: &str =
+    "xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\" \
+     xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\"";
+
+/// Paragraph properties for the cover page's single-spaced lines
+const SINGLE_SPACED: &str =
+    "<w:spacing w:line=\"240\" w:lineRule=\"auto\"/><w:ind w:firstLine=\"0\"/>";
+
+const CONTENT_TYPES: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>
+<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+<Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+<Default Extension=\"xml\" ContentType=\"application/xml\"/>\
+<Override PartName=\"/word/document.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml\"/>\
+<Override PartName=\"/word/styles.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml\"/>\
+<Override PartName=\"/word/header1.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.header+xml\"/>\
+</Types>";
+
+const PACKAGE_RELATIONSHIPS: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>
+<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+<Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument\" Target=\"word/document.xml\"/>\
+</Relationships>";
+
+const DOCUMENT_RELATIONSHIPS: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>
+<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+<Relationship Id=\"rIdStyles\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles\" Target=\"styles.xml\"/>\
+<Relationship Id=\"rIdHeader\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/header\" Target=\"header1.xml\"/>\
+</Relationships>";
+
+/// Times New Roman 12 pt (sizes are in half-points), double spaced (line
+/// spacing is in 240ths of a line), half-inch first-line indent (720 twips)
+const STYLES: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>
+<w:styles xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\">\
+<w:docDefaults>\
+<w:rPrDefault><w:rPr><w:rFonts w:ascii=\"Times New Roman\" w:hAnsi=\"Times New Roman\" w:cs=\"Times New Roman\"/>\
+<w:sz w:val=\"24\"/><w:szCs w:val=\"24\"/></w:rPr></w:rPrDefault>\
+<w:pPrDefault><w:pPr><w:spacing w:before=\"0\" w:after=\"0\" w:line=\"480\" w:lineRule=\"auto\"/>\
+<w:ind w:firstLine=\"720\"/></w:pPr></w:pPrDefault>\
+</w:docDefaults>\
+<w:style w:type=\"paragraph\" w:default=\"1\" w:styleId=\"Normal\"><w:name w:val=\"Normal\"/></w:style>\
+</w:styles>";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn no_cover() -> DocxOptions {
+        DocxOptions {
+            cover_page: false,
+            ..DocxOptions::default()
+        }
+    }
+
+    /// What's between `<w:body>` and the section properties
+    fn body(text: &str, title: &str, options: &DocxOptions) -> String {
+        let xml = document_xml(text, title, options);
+        let start = xml.find("<w:body>").unwrap() + "<w:body>".len();
+        let end = xml.find("<w:sectPr>").unwrap();
+        xml[start..end].to_string()
+    }
+
+    fn run(text: &str) -> String {
+        format!("<w:r><w:t xml:space=\"preserve\">{}</w:t></w:r>", text)
+    }
+
+    const CENTERED: &str = "<w:pPr><w:ind w:firstLine=\"0\"/><w:jc w:val=\"center\"/></w:pPr>";
+    const CHAPTER: &str = "<w:pPr><w:spacing w:before=\"2880\"/><w:ind w:firstLine=\"0\"/>\
+                           <w:jc w:val=\"center\"/></w:pPr>";
+    const PAGE_BREAK: &str = "<w:p><w:r><w:br w:type=\"page\"/></w:r></w:p>";
+
+    #[test]
+    fn the_body_follows_manuscript_format() {
+        let text = "[CHAPTER: One]\n[SCENE: Beach]\nShe ran & hid.\n\n[SCENE: Cave]\n\
+                    <Dark> it was.\n[CHAPTER: Two]\nHERO\nEnd \"here\".\n";
+        let expected = [
+            // The first chapter needs no page break; its first scene no `#`
+            format!("<w:p>{}{}</w:p>", CHAPTER, run("One")),
+            format!("<w:p>{}</w:p>", run("She ran &amp; hid.")),
+            format!("<w:p>{}{}</w:p>", CENTERED, run("#")),
+            format!("<w:p>{}</w:p>", run("&lt;Dark&gt; it was.")),
+            PAGE_BREAK.to_string(),
+            format!("<w:p>{}{}</w:p>", CHAPTER, run("Two")),
+            // A cue is an ordinary paragraph
+            format!("<w:p>{}</w:p>", run("HERO")),
+            format!("<w:p>{}</w:p>", run("End &quot;here&quot;.")),
+            format!("<w:p>{}{}</w:p>", CENTERED, run("END")),
+        ];
+        assert_eq!(body(text, "Book", &no_cover()), expected.concat());
+    }
+
+    #[test]
+    fn acts_and_malformed_tags() {
+        let text = "[ACT: one]\n[CHAPTER: 1]\n[SCENE: Beach\nText\n";
+        let expected = [
+            format!("<w:p>{}{}</w:p>", CHAPTER, run("ACT ONE")),
+            // An act has a title page of its own
+            PAGE_BREAK.to_string(),
+            format!("<w:p>{}{}</w:p>", CHAPTER, run("1")),
+            format!("<w:p>{}</w:p>", run("[SCENE: Beach")),
+            format!("<w:p>{}</w:p>", run("Text")),
+            format!("<w:p>{}{}</w:p>", CENTERED, run("END")),
+        ];
+        assert_eq!(body(text, "Book", &no_cover()), expected.concat());
+    }
+
+    #[test]
+    fn the_cover_page_has_contact_count_title_and_byline() {
+        let options = DocxOptions {
+            cover_page: true,
+            author: String::from("Ann  Smith "),
+            contact: String::from("Ann Smith\n\n  ann@example.com  \n"),
+        };
+        let text = "word ".repeat(1_260);
+        let body = body(&text, "Tides & Time", &options);
+        let single = "<w:spacing w:line=\"240\" w:lineRule=\"auto\"/><w:ind w:firstLine=\"0\"/>";
+        let expected = [
+            format!("<w:p><w:pPr>{}</w:pPr>{}</w:p>", single, run("Ann Smith")),
+            format!(
+                "<w:p><w:pPr>{}</w:pPr>{}</w:p>",
+                single,
+                run("ann@example.com")
+            ),
+            format!(
+                "<w:p><w:pPr>{}<w:jc w:val=\"right\"/></w:pPr>{}</w:p>",
+                single,
+                run("about 1,300 words")
+            ),
+            format!(
+                "<w:p><w:pPr><w:spacing w:before=\"4320\"/><w:ind w:firstLine=\"0\"/>\
+                 <w:jc w:val=\"center\"/></w:pPr>{}</w:p>",
+                run("TIDES &amp; TIME")
+            ),
+            format!("<w:p>{}{}</w:p>", CENTERED, run("by Ann  Smith")),
+            PAGE_BREAK.to_string(),
+        ];
+        assert!(body.starts_with(&expected.concat()), "{}", body);
+        // The cover page has no running header
+        assert!(document_xml(&text, "T", &options).contains("<w:titlePg/>"));
+        assert!(!document_xml(&text, "T", &no_cover()).contains("<w:titlePg/>"));
+    }
+
+    #[test]
+    fn the_header_has_surname_title_and_page() {
+        let header = header_xml("Tides & Time", "Ann Smith");
+        assert!(header.contains(&run("Smith / Tides &amp; Time / ")));
+        assert!(header.contains("<w:fldSimple w:instr=\"PAGE\">"));
+        assert!(header_xml("Tides", " ").contains(&run("Tides / ")));
+    }
+
+    #[test]
+    fn the_package_holds_well_formed_parts() {
+        let bytes = export_docx(
+            "[CHAPTER: <One>]\nText & more\n",
+            "T",
+            &DocxOptions::default(),
+        )
+        .unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let mut names = Vec::new();
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index).unwrap();
+            let mut xml = String::new();
+            file.read_to_string(&mut xml).unwrap();
+            assert!(roxmltree::Document::parse(&xml).is_ok(), "{}", file.name());
+            names.push(file.name().to_string());
+        }
+        assert_eq!(
+            names,
+            [
+                "[Content_Types].xml",
+                "_rels/.rels",
+                "word/_rels/document.xml.rels",
+                "word/styles.xml",
+                "word/document.xml",
+                "word/header1.xml",
+            ]
+        );
+    }
+}
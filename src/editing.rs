@@ -0,0 +1,627 @@
+//! FILE: src/editing.rs
+//!
+//! Structure-aware key handling for the editor.
+//!
+//! egui's TextEdit knows nothing about `[SCENE: Beach]` tags, so pressing
+//! Enter with the caret inside one splits the tag across two lines and the
+//! parser no longer recognizes it. This module decides - BEFORE the TextEdit
+//! sees the key - whether a key press should be handled specially, and if so,
+//! what edit to make instead.
+//!
+//! The decision function is pure (text + caret + key in, edit out) and knows
+//! nothing about egui. App is responsible for feeding it keys and applying
+//! the result.
+//!
+//! The same hook does smart typography, when it's switched on: straight
+//! quotes become curly ones in the chosen language's style, `--` an em dash
+//! and `...` an ellipsis, as they're typed. Tags and comments are left as
+//! typed unless that's switched off too - the parser reads them exactly.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Enums with data (`EditKey::Char(char)`)
+//! - `Range<usize>` for describing spans of text
+//! - Byte indices vs char indices in UTF-8 strings
+//! - `let ... else` for early returns
+
+use crate::parser;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+// ============================================================================
+// OPTIONS
+// ============================================================================
+
+/// Which structure-aware behaviors are switched on
+///
+/// Each behavior can be toggled independently from the Edit menu.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StructureEditing {
+    /// Enter inside `[...]` jumps past the `]` to a new line
+    pub smart_enter: bool,
+
+    /// Typing `[` inserts `[]`, Backspace on `[|]` removes both, and typing
+    /// `]` in front of an existing `]` steps over it
+    pub auto_pair_brackets: bool,
+
+    /// Typing `:` right after a known tag name inserts `: `
+    pub space_after_colon: bool,
+
+    /// Offer known character names on cue lines and after `@`
+    /// (see completion.rs)
+    pub complete_names: bool,
+
+    /// Typing `"` or `'` puts in a curly quote, `--` an em dash and `...`
+    /// an ellipsis
+    pub smart_typography: bool,
+
+    /// Which curly quotes smart typography puts in
+    pub quote_style: QuoteStyle,
+
+    /// Leave what's typed inside `[...]` (tags and comments) as typed
+    pub plain_in_brackets: bool,
+}
+
+impl Default for StructureEditing {
+    fn default() -> Self {
+        Self {
+            smart_enter: true,
+            auto_pair_brackets: true,
+            space_after_colon: true,
+            complete_names: true,
+            smart_typography: false,
+            quote_style: QuoteStyle::default(),
+            plain_in_brackets: true,
+        }
+    }
+}
+
+/// The quotation marks of a language, for smart typography
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuoteStyle {
+    /// “double” and ‘single’
+    #[default]
+    English,
+    /// „double“ and ‚single‘
+    German,
+    /// «double» and ‹single› (no spaces are added inside them)
+    French,
+    /// ”double” and ’single’
+    Swedish,
+}
+
+impl QuoteStyle {
+    pub const ALL: [QuoteStyle; 4] = [
+        QuoteStyle::English,
+        QuoteStyle::German,
+        QuoteStyle::French,
+        QuoteStyle::Swedish,
+    ];
+
+    /// Name for the Edit menu
+    pub fn label(self) -> &'static str {
+        match self {
+            QuoteStyle::English => "English “…” ‘…’",
+            QuoteStyle::German => "German „…“ ‚…‘",
+            QuoteStyle::French => "French «…» ‹…›",
+            QuoteStyle::Swedish => "Swedish ”…” ’…’",
+        }
+    }
+
+    /// Opening and closing double marks, then opening and closing single
+    fn marks(self) -> [char; 4] {
+        match self {
+            QuoteStyle::English => ['\u{201C}', '\u{201D}', '\u{2018}', '\u{2019}'],
+            QuoteStyle::German => ['\u{201E}', '\u{201C}', '\u{201A}', '\u{2018}'],
+            QuoteStyle::French => ['\u{00AB}', '\u{00BB}', '\u{2039}', '\u{203A}'],
+            QuoteStyle::Swedish => ['\u{201D}', '\u{201D}', '\u{2019}', '\u{2019}'],
+        }
+    }
+}
+
+// ============================================================================
+// INPUT AND OUTPUT TYPES
+// ============================================================================
+
+/// The key presses this module cares about
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditKey {
+    Enter,
+    Backspace,
+    /// A typed character (from egui's Event::Text)
+    Char(char),
+}
+
+/// An edit to apply instead of the TextEdit's default behavior
+///
+/// All positions are CHAR indices (not byte indices), matching egui's
+/// CCursor. "Replace `range` with `insert`, then put the caret at `caret`."
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditCommand {
+    pub range: Range<usize>,
+    pub insert: String,
+    pub caret: usize,
+}
+
+impl EditCommand {
+    /// Apply the edit to `text` in place
+    pub fn apply(&self, text: &mut String) {
+        let start = char_to_byte(text, self.range.start);
+        let end = char_to_byte(text, self.range.end);
+        text.replace_range(start..end, &self.insert);
+    }
+}
+
+// ============================================================================
+// THE DECISION FUNCTION
+// ============================================================================
+
+/// Decide whether `key`, pressed with the caret at char index `caret`,
+/// needs structure-aware handling
+///
+/// Returns None when the TextEdit should handle the key normally.
+///
+/// EXAMPLES (| marks the caret):
+///   "[SCENE: Be|ach]"  + Enter      → "[SCENE: Beach]\n|"
+///   "[SCENE: Beach|"   + Enter      → "[SCENE: Beach]\n|"
+///   "[|]"              + Backspace  → "|"
+///   "foo |"            + '['        → "foo [|]"
+///   "[SCENE|"          + ':'        → "[SCENE: |"
+pub fn handle_key(
+    text: &str,
+    caret: usize,
+    key: EditKey,
+    options: &StructureEditing,
+) -> Option<EditCommand> {
+    match key {
+        EditKey::Enter if options.smart_enter => smart_enter(text, caret),
+        EditKey::Backspace if options.auto_pair_brackets => backspace_pair(text, caret),
+        EditKey::Char('[') if options.auto_pair_brackets => open_pair(text, caret),
+        EditKey::Char(']') if options.auto_pair_brackets => step_over_close(text, caret),
+        EditKey::Char(':') if options.space_after_colon => colon_space(text, caret),
+        EditKey::Char(c @ ('"' | '\'' | '-' | '.')) if options.smart_typography => {
+            typography(text, caret, c, options)
+        }
+        _ => None,
+    }
+}
+
+// ============================================================================
+// TAG SPAN UNDER THE CARET
+// ============================================================================
+
+/// Where the bracket pair around the caret sits on the current line
+///
+/// Char indices into the whole text. `close` is None for an unclosed tag.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TagSpan {
+    open: usize,
+    close: Option<usize>,
+    line_end: usize,
+}
+
+/// Find the `[` ... `]` span the caret is inside, if any
+///
+/// Tags never span lines, so we only look at the caret's own line: the
+/// nearest `[` to the left (with no `]` between it and the caret) and the
+/// first `]` to the right.
+fn tag_span_at(text: &str, caret: usize) -> Option<TagSpan> {
+    let chars: Vec<char> = text.chars().collect();
+    let caret = caret.min(chars.len());
+
+    // Walk left from the caret to the start of the line
+    let mut open = None;
+    let mut i = caret;
+    while i > 0 {
+        i -= 1;
+        match chars[i] {
+            '\n' | ']' => break,
+            '[' => {
+                open = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let open = open?;
+
+    // Walk right from the caret to the end of the line
+    let mut close = None;
+    let mut line_end = chars.len();
+    for (j, &c) in chars.iter().enumerate().skip(caret) {
+        if c == '\n' {
+            line_end = j;
+            break;
+        }
+        if c == ']' && close.is_none() {
+            close = Some(j);
+        }
+    }
+
+    Some(TagSpan {
+        open,
+        close,
+        line_end,
+    })
+}
+
+/// The tag name typed so far between `[` and the caret, e.g. "SCENE"
+/// for "[SCENE|", or None if that text isn't a bare word
+fn tag_name_before_caret(text: &str, span: &TagSpan, caret: usize) -> Option<String> {
+    let name: String = text
+        .chars()
+        .skip(span.open + 1)
+        .take(caret - span.open - 1)
+        .collect();
+    let trimmed = name.trim();
+    if trimmed.is_empty() || !trimmed.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some(trimmed.to_string())
+}
+
+// ============================================================================
+// INDIVIDUAL BEHAVIORS
+// ============================================================================
+
+fn smart_enter(text: &str, caret: usize) -> Option<EditCommand> {
+    let span = tag_span_at(text, caret)?;
+
+    match span.close {
+        // Closed tag: leave it alone and open a new line after the `]`
+        Some(close) => Some(EditCommand {
+            range: close + 1..close + 1,
+            insert: String::from("\n"),
+            caret: close + 2,
+        }),
+        // Unclosed tag: only intervene if it's really a tag ("[SCENE: Be"),
+        // not prose that happens to contain a bracket ("[sic")
+        None => {
+            let value: String = text
+                .chars()
+                .skip(span.open + 1)
+                .take(span.line_end - span.open - 1)
+                .collect();
+            let (name, _) = value.split_once(':')?;
+            if !parser::is_known_tag_name(name.trim()) {
+                return None;
+            }
+            // Close the tag at the end of the line, then start a new line
+            Some(EditCommand {
+                range: span.line_end..span.line_end,
+                insert: String::from("]\n"),
+                caret: span.line_end + 2,
+            })
+        }
+    }
+}
+
+fn backspace_pair(text: &str, caret: usize) -> Option<EditCommand> {
+    if caret == 0 {
+        return None;
+    }
+    let mut around = text.chars().skip(caret - 1);
+    if around.next() == Some('[') && around.next() == Some(']') {
+        Some(EditCommand {
+            range: caret - 1..caret + 1,
+            insert: String::new(),
+            caret: caret - 1,
+        })
+    } else {
+        None
+    }
+}
+
+fn open_pair(text: &str, caret: usize) -> Option<EditCommand> {
+    // Only pair when nothing is glued to the right of the caret, otherwise
+    // wrapping existing text in brackets ("[word") would be impossible
+    let next = text.chars().nth(caret);
+    if next.is_some_and(|c| !c.is_whitespace()) {
+        return None;
+    }
+    Some(EditCommand {
+        range: caret..caret,
+        insert: String::from("[]"),
+        caret: caret + 1,
+    })
+}
+
+fn step_over_close(text: &str, caret: usize) -> Option<EditCommand> {
+    if text.chars().nth(caret) == Some(']') {
+        Some(EditCommand {
+            range: caret..caret,
+            insert: String::new(),
+            caret: caret + 1,
+        })
+    } else {
+        None
+    }
+}
+
+fn colon_space(text: &str, caret: usize) -> Option<EditCommand> {
+    let span = tag_span_at(text, caret)?;
+    let name = tag_name_before_caret(text, &span, caret)?;
+    if !parser::is_known_tag_name(&name) {
+        return None;
+    }
+    // Don't double up if there's already a space after the caret
+    if text.chars().nth(caret) == Some(' ') {
+        return None;
+    }
+    Some(EditCommand {
+        range: caret..caret,
+        insert: String::from(": "),
+        caret: caret + 2,
+    })
+}
+
+/// Smart typography: the curly quote for a typed `"` or `'`, and a dash
+/// or ellipsis once a `--` or `...` is complete
+///
+/// A quote opens at the start of a line or after a space, an opening
+/// bracket, a dash or an opening quote, and closes anywhere else. A `'`
+/// closing a single quote opened earlier on the line is its closing mark;
+/// any other `'` after a letter is an apostrophe (’ in every style).
+///
+/// Exactly two hyphens become an em dash and exactly three dots an
+/// ellipsis, as in text_utils::smarten_punctuation: a third hyphen turns
+/// the dash back into `---` (a scene break), a fourth dot the ellipsis
+/// into `....`.
+fn typography(
+    text: &str,
+    caret: usize,
+    typed: char,
+    options: &StructureEditing,
+) -> Option<EditCommand> {
+    if options.plain_in_brackets && tag_span_at(text, caret).is_some() {
+        return None;
+    }
+    let before = &text[..char_to_byte(text, caret)];
+    let mut earlier = before.chars().rev();
+    let (last, second, third) = (earlier.next(), earlier.next(), earlier.next());
+
+    // Replace the `count` chars before the caret (and the typed one)
+    let replace = |count: usize, insert: &str| EditCommand {
+        range: caret - count..caret,
+        insert: insert.to_string(),
+        caret: caret - count + insert.chars().count(),
+    };
+
+    match typed {
+        '"' | '\'' => {
+            let [open_double, close_double, open_single, close_single] =
+                options.quote_style.marks();
+            let opening = last.is_none_or(|p| {
+                p.is_whitespace()
+                    || matches!(p, '(' | '[' | '{' | '\u{2014}' | '\u{2013}')
+                    || p == open_double
+                    || p == open_single
+            });
+            let line = &before[before.rfind('\n').map_or(0, |i| i + 1)..];
+            let single_open = line
+                .rfind(open_single)
+                .is_some_and(|at| !line[at..].contains(close_single));
+            let mark = match (typed, opening) {
+                ('"', true) => open_double,
+                ('"', false) => close_double,
+                (_, true) => open_single,
+                (_, false) if single_open => close_single,
+                (_, false) if last.is_some_and(char::is_alphanumeric) => '\u{2019}',
+                (_, false) => close_single,
+            };
+            Some(replace(0, &mark.to_string()))
+        }
+        '-' => match (last, second) {
+            (Some('-'), second) if second != Some('-') => Some(replace(1, "\u{2014}")),
+            (Some('\u{2014}'), _) => Some(replace(1, "---")),
+            _ => None,
+        },
+        '.' => match (last, second, third) {
+            (Some('.'), Some('.'), third) if third != Some('.') => Some(replace(2, "\u{2026}")),
+            (Some('\u{2026}'), ..) => Some(replace(1, "....")),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// ============================================================================
+// HELPERS
+// ============================================================================
+
+/// Convert a char index into a byte index
+///
+/// Rust strings are UTF-8, where a character can take 1 to 4 bytes, so the
+/// 5th character doesn't necessarily start at byte 5. egui counts cursor
+/// positions in chars; slicing a String needs bytes.
+pub fn char_to_byte(text: &str, char_index: usize) -> usize {
+    text.char_indices()
+        .nth(char_index)
+        .map(|(byte, _)| byte)
+        .unwrap_or(text.len())
+}
+
+/// Convert a byte index (on a char boundary) into a char index
+///
+/// The inverse of char_to_byte: search matches are byte ranges, but the
+/// editor's selection is counted in chars.
+pub fn byte_to_char(text: &str, byte_index: usize) -> usize {
+    text[..byte_index].chars().count()
+}
+
+/// The 1-based line number a char index is on
+///
+/// The inverse of line_start_char; indexes past the end are on the last line.
+pub fn line_at_char(text: &str, char_index: usize) -> usize {
+    1 + text.chars().take(char_index).filter(|&c| c == '\n').count()
+}
+
+/// Byte range of a 1-based line, without its line break
+///
+/// None if the text has fewer lines.
+pub fn line_byte_range(text: &str, line_number: usize) -> Option<Range<usize>> {
+    let mut start = 0;
+    for (index, line) in text.split('\n').enumerate() {
+        if index + 1 == line_number {
+            let end = start + line.strip_suffix('\r').map_or(line.len(), str::len);
+            return Some(start..end);
+        }
+        start += line.len() + 1;
+    }
+    None
+}
+
+/// Char index of the first character on a 1-based line number
+///
+/// Line numbers past the end of the text clamp to the end of the text.
+/// Used to move the caret when jumping to a chapter, scene, or line.
+pub fn line_start_char(text: &str, line_number: usize) -> usize {
+    if line_number <= 1 {
+        return 0;
+    }
+    let mut line = 1;
+    for (index, c) in text.chars().enumerate() {
+        if c == '\n' {
+            line += 1;
+            if line == line_number {
+                return index + 1;
+            }
+        }
+    }
+    text.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Press `key` in `text`, where `|` marks the caret; the text after the
+    /// edit (caret marked the same way), or None if the key is left alone
+    fn press(text: &str, key: EditKey, options: &StructureEditing) -> Option<String> {
+        let caret = byte_to_char(text, text.find('|').unwrap());
+        let mut text = text.replacen('|', "", 1);
+        let command = handle_key(&text, caret, key, options)?;
+        command.apply(&mut text);
+        text.insert(char_to_byte(&text, command.caret), '|');
+        Some(text)
+    }
+
+    fn on(text: &str, key: EditKey) -> Option<String> {
+        press(text, key, &StructureEditing::default())
+    }
+
+    #[test]
+    fn enter_inside_a_tag_moves_past_it() {
+        assert_eq!(
+            on("[SCENE: Be|ach]\nText", EditKey::Enter).as_deref(),
+            Some("[SCENE: Beach]\n|\nText")
+        );
+        assert_eq!(
+            on("[SCENE: Beach|", EditKey::Enter).as_deref(),
+            Some("[SCENE: Beach]\n|")
+        );
+        assert_eq!(
+            on("Intro\n[chapter: Tw|o", EditKey::Enter).as_deref(),
+            Some("Intro\n[chapter: Two]\n|")
+        );
+    }
+
+    #[test]
+    fn enter_outside_a_tag_or_in_bracketed_prose_is_left_alone() {
+        assert_eq!(on("[SCENE: Beach]|", EditKey::Enter), None);
+        assert_eq!(on("Plain te|xt", EditKey::Enter), None);
+        assert_eq!(on("He said [si|c", EditKey::Enter), None);
+        assert_eq!(on("[NOTE: unknow|n", EditKey::Enter), None);
+        // The bracket on the previous line doesn't count
+        assert_eq!(on("[SCENE: x\nne|xt", EditKey::Enter), None);
+    }
+
+    #[test]
+    fn brackets_pair_and_unpair() {
+        assert_eq!(on("foo |", EditKey::Char('[')).as_deref(), Some("foo [|]"));
+        assert_eq!(on("|word", EditKey::Char('[')), None);
+        assert_eq!(on("[|]", EditKey::Backspace).as_deref(), Some("|"));
+        assert_eq!(on("[x|]", EditKey::Backspace), None);
+        assert_eq!(on("|", EditKey::Backspace), None);
+        assert_eq!(
+            on("[SCENE: Beach|]", EditKey::Char(']')).as_deref(),
+            Some("[SCENE: Beach]|")
+        );
+        assert_eq!(on("[SCENE: Beach|", EditKey::Char(']')), None);
+    }
+
+    #[test]
+    fn a_colon_after_a_known_tag_name_gets_a_space() {
+        assert_eq!(
+            on("[SCENE|]", EditKey::Char(':')).as_deref(),
+            Some("[SCENE: |]")
+        );
+        assert_eq!(
+            on("[ act |", EditKey::Char(':')).as_deref(),
+            Some("[ act : |")
+        );
+        assert_eq!(on("[NOTE|", EditKey::Char(':')), None);
+        assert_eq!(on("Time|", EditKey::Char(':')), None);
+        assert_eq!(on("[SCENE| Beach]", EditKey::Char(':')), None);
+    }
+
+    #[test]
+    fn each_behavior_can_be_switched_off() {
+        let off = StructureEditing {
+            smart_enter: false,
+            auto_pair_brackets: false,
+            space_after_colon: false,
+            ..StructureEditing::default()
+        };
+        assert_eq!(press("[SCENE: Be|ach]", EditKey::Enter, &off), None);
+        assert_eq!(press("[|]", EditKey::Backspace, &off), None);
+        assert_eq!(press("|", EditKey::Char('['), &off), None);
+        assert_eq!(press("[SCENE|", EditKey::Char(':'), &off), None);
+    }
+
+    #[test]
+    fn smart_typography_curls_quotes_and_joins_dashes() {
+        let smart = StructureEditing {
+            smart_typography: true,
+            ..StructureEditing::default()
+        };
+        let typed = |text: &str, c: char| press(text, EditKey::Char(c), &smart);
+        assert_eq!(
+            typed("She said |", '"').as_deref(),
+            Some("She said \u{201C}|")
+        );
+        assert_eq!(
+            typed("\u{201C}Hi|", '"').as_deref(),
+            Some("\u{201C}Hi\u{201D}|")
+        );
+        assert_eq!(typed("don|", '\'').as_deref(), Some("don\u{2019}|"));
+        assert_eq!(typed("wait-|", '-').as_deref(), Some("wait\u{2014}|"));
+        assert_eq!(typed("\u{2014}|", '-').as_deref(), Some("---|"));
+        assert_eq!(typed("so..|", '.').as_deref(), Some("so\u{2026}|"));
+        // Left as typed inside tags, and when switched off
+        assert_eq!(typed("[SCENE: |", '"'), None);
+        assert_eq!(on("She said |", EditKey::Char('"')), None);
+
+        let german = StructureEditing {
+            quote_style: QuoteStyle::German,
+            ..smart
+        };
+        assert_eq!(
+            press("|", EditKey::Char('"'), &german).as_deref(),
+            Some("\u{201E}|")
+        );
+    }
+
+    #[test]
+    fn line_helpers_count_chars_not_bytes() {
+        let text = "é\r\nzwei\ndrei";
+        assert_eq!(char_to_byte(text, 1), 2);
+        assert_eq!(byte_to_char(text, 2), 1);
+        assert_eq!(line_at_char(text, 3), 2);
+        assert_eq!(line_byte_range(text, 1), Some(0..2));
+        assert_eq!(line_byte_range(text, 3), Some(9..13));
+        assert_eq!(line_byte_range(text, 4), None);
+        assert_eq!(line_start_char(text, 2), 3);
+        assert_eq!(line_start_char(text, 9), text.chars().count());
+    }
+}
@@ -0,0 +1,327 @@
+//! FILE: src/encoding.rs
+//!
+//! Turning a file's raw bytes into the editor's text, whatever wrote them.
+//!
+//! Rust strings are always UTF-8, and fs::read_to_string refuses anything
+//! else. Older drafts are often Windows-1252 (Word and Notepad on Windows
+//! before UTF-8 was the default) or UTF-16 with a byte order mark. decode()
+//! accepts all of these, says which one it found, and normalizes line
+//! endings to `\n` so the rest of the app only ever sees one kind.
+//!
+//! A document is saved back in the encoding it was read in, with the line
+//! ending style it had (TextEncoding::encode, LineEnding::apply), so a
+//! Windows-1252 CRLF file stays Windows-1252 CRLF. New documents are UTF-8
+//! with `\n`. Text that a legacy encoding can't hold (an emoji typed into
+//! a Windows-1252 file) makes that save fall back to UTF-8.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Byte slices (&[u8]) and slice patterns (`[0xEF, 0xBB, 0xBF, rest @ ..]`)
+//! - char::decode_utf16 for UTF-16 code units
+//! - Cow<str>: borrowing when nothing needs to change, allocating when it does
+
+use std::borrow::Cow;
+use std::fmt;
+
+/// The encoding a file turned out to be in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextEncoding {
+    /// Plain UTF-8 (includes pure ASCII)
+    #[default]
+    Utf8,
+    /// UTF-8 starting with a byte order mark, which is dropped
+    Utf8Bom,
+    /// UTF-16, little-endian, with a BOM
+    Utf16Le,
+    /// UTF-16, big-endian, with a BOM
+    Utf16Be,
+    /// Not valid UTF-8: read as Windows-1252 (a superset of Latin-1)
+    Windows1252,
+}
+
+impl TextEncoding {
+    /// The bytes of `text` in this encoding, with the byte order mark if it
+    /// has one
+    ///
+    /// Err holds the first character Windows-1252 has no byte for; the
+    /// Unicode encodings take anything.
+    pub fn encode(self, text: &str) -> Result<Cow<'_, [u8]>, char> {
+        Ok(match self {
+            TextEncoding::Utf8 => Cow::Borrowed(text.as_bytes()),
+            TextEncoding::Utf8Bom => {
+                let mut bytes = vec![0xEF, 0xBB, 0xBF];
+                bytes.extend_from_slice(text.as_bytes());
+                Cow::Owned(bytes)
+            }
+            TextEncoding::Utf16Le => Cow::Owned(encode_utf16(text, u16::to_le_bytes)),
+            TextEncoding::Utf16Be => Cow::Owned(encode_utf16(text, u16::to_be_bytes)),
+            TextEncoding::Windows1252 => Cow::Owned(
+                text.chars()
+                    .map(|c| windows_1252_byte(c).ok_or(c))
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TextEncoding::Utf8 => "UTF-8",
+            TextEncoding::Utf8Bom => "UTF-8 with BOM",
+            TextEncoding::Utf16Le => "UTF-16 LE",
+            TextEncoding::Utf16Be => "UTF-16 BE",
+            TextEncoding::Windows1252 => "Windows-1252",
+        })
+    }
+}
+
+/// How lines were separated in a file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n` (Linux, macOS, and the editor's own text)
+    #[default]
+    Lf,
+    /// `\r\n` (Windows)
+    CrLf,
+}
+
+impl LineEnding {
+    /// Convert editor text (always `\n`) to this line ending for writing
+    pub fn apply<'a>(self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            LineEnding::Lf => Cow::Borrowed(text),
+            LineEnding::CrLf => Cow::Owned(text.replace('\n', "\r\n")),
+        }
+    }
+}
+
+/// A file's text, ready for the editor, and what it looked like on disk
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedText {
+    /// The text, with `\n` line endings and no BOM
+    pub text: String,
+    pub encoding: TextEncoding,
+    /// CrLf if the file used `\r\n` anywhere, so saving can keep it
+    pub line_ending: LineEnding,
+}
+
+/// Decode a file's bytes
+///
+/// DETECTION ORDER:
+/// 1. A byte order mark settles it: UTF-8, UTF-16 LE or UTF-16 BE
+/// 2. Otherwise, bytes that are valid UTF-8 are UTF-8
+/// 3. Anything else is read as Windows-1252, which maps every byte to some
+///    character, so decoding never fails
+///
+/// Broken UTF-16 (an odd byte count, unpaired surrogates) decodes with
+/// U+FFFD in place of the bad units rather than failing.
+pub fn decode(bytes: &[u8]) -> DecodedText {
+    let (raw, encoding) = match bytes {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => match utf8_or_windows_1252(rest) {
+            (text, TextEncoding::Utf8) => (text, TextEncoding::Utf8Bom),
+            // A UTF-8 BOM in front of bytes that aren't UTF-8 lied
+            (text, encoding) => (text, encoding),
+        },
+        [0xFF, 0xFE, rest @ ..] => (
+            decode_utf16(rest, u16::from_le_bytes),
+            TextEncoding::Utf16Le,
+        ),
+        [0xFE, 0xFF, rest @ ..] => (
+            decode_utf16(rest, u16::from_be_bytes),
+            TextEncoding::Utf16Be,
+        ),
+        _ => utf8_or_windows_1252(bytes),
+    };
+
+    let line_ending = if raw.contains("\r\n") {
+        LineEnding::CrLf
+    } else {
+        LineEnding::Lf
+    };
+
+    DecodedText {
+        text: normalize_line_endings(&raw),
+        encoding,
+        line_ending,
+    }
+}
+
+/// Replace `\r\n` and lone `\r` (classic Mac OS) with `\n`
+pub fn normalize_line_endings(text: &str) -> String {
+    if !text.contains('\r') {
+        return text.to_string();
+    }
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// UTF-8 if the bytes are valid UTF-8, Windows-1252 otherwise
+fn utf8_or_windows_1252(bytes: &[u8]) -> (String, TextEncoding) {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => (text.to_string(), TextEncoding::Utf8),
+        Err(_) => (
+            bytes.iter().map(|&b| windows_1252_char(b)).collect(),
+            TextEncoding::Windows1252,
+        ),
+    }
+}
+
+/// Decode UTF-16 with the given byte order
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let units = bytes.chunks(2).map(|pair| match pair {
+        [a, b] => from_bytes([*a, *b]),
+        // A dangling last byte can't be a whole code unit
+        _ => 0xFFFD,
+    });
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// Encode UTF-16 with the given byte order, starting with its byte order
+/// mark
+fn encode_utf16(text: &str, to_bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+    std::iter::once(0xFEFF)
+        .chain(text.encode_utf16())
+        .flat_map(to_bytes)
+        .collect()
+}
+
+/// Windows-1252 characters for bytes 0x80-0x9F, where it differs from
+/// Latin-1 (the five unassigned bytes keep their Latin-1 control codes)
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/// The Windows-1252 byte for a char, if it has one (the reverse of
+/// windows_1252_char)
+fn windows_1252_byte(c: char) -> Option<u8> {
+    match c as u32 {
+        0x00..=0x7F | 0xA0..=0xFF => Some(c as u8),
+        _ => WINDOWS_1252_HIGH
+            .iter()
+            .position(|&high| high == c)
+            .map(|index| 0x80 + index as u8),
+    }
+}
+
+/// One Windows-1252 byte as a char
+///
+/// Outside 0x80-0x9F, Windows-1252 and Latin-1 agree with Unicode's first
+/// 256 code points, so the byte value IS the code point.
+fn windows_1252_char(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => WINDOWS_1252_HIGH[(byte - 0x80) as usize],
+        _ => byte as char,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoded(text: &str, encoding: TextEncoding, line_ending: LineEnding) -> DecodedText {
+        DecodedText {
+            text: text.to_string(),
+            encoding,
+            line_ending,
+        }
+    }
+
+    #[test]
+    fn plain_utf8_is_taken_as_is() {
+        assert_eq!(
+            decode("Caf\u{e9} \u{2014} ok\n".as_bytes()),
+            decoded(
+                "Caf\u{e9} \u{2014} ok\n",
+                TextEncoding::Utf8,
+                LineEnding::Lf
+            )
+        );
+        assert_eq!(decode(b""), decoded("", TextEncoding::Utf8, LineEnding::Lf));
+    }
+
+    #[test]
+    fn a_utf8_bom_is_dropped_and_remembered() {
+        assert_eq!(
+            decode(b"\xEF\xBB\xBFHi\r\n"),
+            decoded("Hi\n", TextEncoding::Utf8Bom, LineEnding::CrLf)
+        );
+        // A BOM in front of bytes that aren't UTF-8 doesn't make them UTF-8
+        assert_eq!(
+            decode(b"\xEF\xBB\xBFna\xEFve").encoding,
+            TextEncoding::Windows1252
+        );
+    }
+
+    #[test]
+    fn utf16_follows_its_bom() {
+        // "Hé\n" plus U+1F600, which needs a surrogate pair
+        let le = b"\xFF\xFEH\x00\xE9\x00\n\x00\x3D\xD8\x00\xDE";
+        let be = b"\xFE\xFF\x00H\x00\xE9\x00\n\xD8\x3D\xDE\x00";
+        assert_eq!(
+            decode(le),
+            decoded("H\u{e9}\n\u{1F600}", TextEncoding::Utf16Le, LineEnding::Lf)
+        );
+        assert_eq!(
+            decode(be),
+            decoded("H\u{e9}\n\u{1F600}", TextEncoding::Utf16Be, LineEnding::Lf)
+        );
+        // A dangling byte and an unpaired surrogate don't fail the load
+        assert_eq!(decode(b"\xFF\xFEA\x00\x00\xD8B").text, "A\u{FFFD}\u{FFFD}");
+    }
+
+    #[test]
+    fn anything_else_is_windows_1252() {
+        // Curly quotes, a euro sign and Latin-1 letters from Word on Windows
+        let bytes = b"\x93Caf\xE9\x94 costs \x80 5\r\nna\xEFve";
+        assert_eq!(
+            decode(bytes),
+            decoded(
+                "\u{201C}Caf\u{e9}\u{201D} costs \u{20AC} 5\nna\u{ef}ve",
+                TextEncoding::Windows1252,
+                LineEnding::CrLf
+            )
+        );
+    }
+
+    #[test]
+    fn mixed_line_endings_all_become_newlines() {
+        let mixed = decode(b"one\r\ntwo\nthree\rfour\r\n");
+        assert_eq!(mixed.text, "one\ntwo\nthree\nfour\n");
+        // Any \r\n at all means the file is saved back with \r\n
+        assert_eq!(mixed.line_ending, LineEnding::CrLf);
+
+        let classic_mac = decode(b"one\rtwo\r");
+        assert_eq!(classic_mac.text, "one\ntwo\n");
+        assert_eq!(classic_mac.line_ending, LineEnding::Lf);
+    }
+
+    #[test]
+    fn encoding_reverses_decoding() {
+        let text = "\u{201C}Caf\u{e9}\u{201D}\nsecond line";
+        for encoding in [
+            TextEncoding::Utf8,
+            TextEncoding::Utf8Bom,
+            TextEncoding::Utf16Le,
+            TextEncoding::Utf16Be,
+            TextEncoding::Windows1252,
+        ] {
+            let crlf = LineEnding::CrLf.apply(text);
+            let bytes = encoding.encode(&crlf).unwrap();
+            assert_eq!(
+                decode(&bytes),
+                decoded(text, encoding, LineEnding::CrLf),
+                "{}",
+                encoding
+            );
+        }
+        assert_eq!(
+            TextEncoding::Windows1252.encode("ok \u{1F600}"),
+            Err('\u{1F600}')
+        );
+        assert_eq!(LineEnding::Lf.apply("a\nb"), "a\nb");
+    }
+}
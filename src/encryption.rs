@@ -0,0 +1,251 @@
+//! FILE: src/encryption.rs
+//!
+//! Password-protected documents (`.bksx`): the file's bytes, encrypted
+//! with a key made from a password.
+//!
+//! The password goes through Argon2id, which is deliberately slow and
+//! memory-hungry so guessing passwords is expensive, to give a 256-bit key.
+//! The document is then encrypted with AES-256-GCM. GCM also checks the
+//! result on the way back: a wrong password (or a damaged file) fails
+//! cleanly instead of giving garbage text.
+//!
+//! FILE LAYOUT:
+//!
+//! ```text
+//! "BKSX" 0x01                 magic and format version
+//! memory, passes, lanes       Argon2 settings, u32 little-endian each
+//! salt (16 bytes)             random for every save
+//! nonce (12 bytes)            random for every save
+//! ciphertext + tag            the encrypted file bytes
+//! ```
+//!
+//! Everything before the ciphertext is authenticated too, so the Argon2
+//! settings can't be weakened without the file failing to open.
+//!
+//! What gets encrypted is exactly what would have been written without a
+//! password (encoding and line endings included), so a document decrypts
+//! to the same bytes encoding::decode would have read.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Fixed-size arrays from slices with try_into
+//! - Result<Option<T>> to tell "wrong password" apart from "not readable"
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{anyhow, bail, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+
+/// Extension for password-protected documents
+pub const EXTENSION: &str = "bksx";
+
+/// Start of every encrypted file, with the format version last
+const MAGIC: &[u8; 5] = b"BKSX\x01";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + 12 + SALT_LEN + NONCE_LEN;
+
+/// Most Argon2 memory (KiB) a file may ask for: a damaged or hostile
+/// header mustn't make opening it take gigabytes
+const MAX_MEMORY_KIB: u32 = 1 << 20;
+
+/// Most Argon2 passes a file may ask for
+const MAX_PASSES: u32 = 64;
+
+/// Whether `bytes` are an encrypted document
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// The 256-bit key for `password`
+fn derive_key(password: &str, salt: &[u8], params: Params) -> Result<Key<Aes256Gcm>> {
+    let mut key = Key::<Aes256Gcm>::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive the key: {}", e))?;
+    Ok(key)
+}
+
+/// Encrypt a file's bytes with `password`
+pub fn encrypt(plain: &[u8], password: &str) -> Result<Vec<u8>> {
+    encrypt_with(plain, password, Params::DEFAULT)
+}
+
+/// encrypt, with the Argon2 settings given (tests use cheap ones)
+fn encrypt_with(plain: &[u8], password: &str, params: Params) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    let mut out = Vec::with_capacity(HEADER_LEN + plain.len() + 16);
+    out.extend_from_slice(MAGIC);
+    for value in [params.m_cost(), params.t_cost(), params.p_cost()] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+
+    let key = derive_key(password, &salt, params)?;
+    let payload = Payload {
+        msg: plain,
+        aad: &out,
+    };
+    let encrypted = Aes256Gcm::new(&key)
+        .encrypt(&nonce, payload)
+        .map_err(|_| anyhow!("Encryption failed"))?;
+    out.extend_from_slice(&encrypted);
+    Ok(out)
+}
+
+/// Decrypt an encrypted document with `password`
+///
+/// Ok(None) if the password is wrong - or the file was changed or damaged
+/// after it was written, which GCM can't tell apart. Err if it isn't an
+/// encrypted document this version can read.
+pub fn decrypt(bytes: &[u8], password: &str) -> Result<Option<Vec<u8>>> {
+    if !is_encrypted(bytes) {
+        bail!("Not a password-protected document");
+    }
+    if bytes.len() < HEADER_LEN {
+        bail!("The file is too short to be a password-protected document");
+    }
+    let (header, encrypted) = bytes.split_at(HEADER_LEN);
+
+    let number = |index: usize| {
+        let at = MAGIC.len() + 4 * index;
+        u32::from_le_bytes(header[at..at + 4].try_into().unwrap())
+    };
+    let (memory, passes, lanes) = (number(0), number(1), number(2));
+    if memory > MAX_MEMORY_KIB || passes > MAX_PASSES {
+        bail!("The file asks for unreasonable key settings");
+    }
+    let params = Params::new(memory, passes, lanes, None)
+        .map_err(|e| anyhow!("The file's key settings are invalid: {}", e))?;
+
+    let salt = &header[MAGIC.len() + 12..MAGIC.len() + 12 + SALT_LEN];
+    let nonce = Nonce::from_slice(&header[HEADER_LEN - NONCE_LEN..]);
+    let key = derive_key(password, salt, params)?;
+    let payload = Payload {
+        msg: encrypted,
+        aad: header,
+    };
+    Ok(Aes256Gcm::new(&key).decrypt(nonce, payload).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &[u8] = b"[CHAPTER: One]\r\nIt was a dark and stormy night.";
+
+    /// The least work Argon2 allows, so each test takes milliseconds
+    fn small_params() -> Params {
+        Params::new(8, 1, 1, None).unwrap()
+    }
+
+    fn encrypted() -> Vec<u8> {
+        encrypt_with(TEXT, "correct horse", small_params()).unwrap()
+    }
+
+    /// Byte offset of header field `index` (0 memory, 1 passes, 2 lanes)
+    fn field(index: usize) -> usize {
+        MAGIC.len() + 4 * index
+    }
+
+    #[test]
+    fn decrypting_gives_back_the_bytes() {
+        let bytes = encrypted();
+        assert!(is_encrypted(&bytes));
+        assert_eq!(bytes.len(), HEADER_LEN + TEXT.len() + 16);
+        assert_eq!(
+            decrypt(&bytes, "correct horse").unwrap().as_deref(),
+            Some(TEXT)
+        );
+        // The settings used are the ones written in the header
+        assert_eq!(bytes[field(0)..field(1)], 8u32.to_le_bytes());
+
+        let empty = encrypt_with(b"", "pw", small_params()).unwrap();
+        assert_eq!(decrypt(&empty, "pw").unwrap().as_deref(), Some(&b""[..]));
+    }
+
+    #[test]
+    fn every_save_gets_its_own_salt_and_nonce() {
+        let (one, two) = (encrypted(), encrypted());
+        assert_ne!(one[field(3)..HEADER_LEN], two[field(3)..HEADER_LEN]);
+        assert_ne!(one[HEADER_LEN..], two[HEADER_LEN..]);
+    }
+
+    #[test]
+    fn a_wrong_password_is_none() {
+        let bytes = encrypted();
+        assert_eq!(decrypt(&bytes, "wrong horse").unwrap(), None);
+        assert_eq!(decrypt(&bytes, "").unwrap(), None);
+        assert_eq!(decrypt(&bytes, "Correct horse").unwrap(), None);
+    }
+
+    #[test]
+    fn a_changed_header_or_body_is_none() {
+        let original = encrypted();
+        // One more pass, a salt byte, a nonce byte, a ciphertext byte and
+        // the tag's last byte
+        for at in [
+            field(1),
+            field(3),
+            HEADER_LEN - 1,
+            HEADER_LEN,
+            original.len() - 1,
+        ] {
+            let mut bytes = original.clone();
+            bytes[at] ^= 0x02;
+            assert_eq!(decrypt(&bytes, "correct horse").unwrap(), None, "{}", at);
+        }
+    }
+
+    #[test]
+    fn unreadable_files_are_errors() {
+        let bytes = encrypted();
+        // Bad magic, or another format version
+        let mut wrong = bytes.clone();
+        wrong[0] = b'X';
+        assert!(decrypt(&wrong, "correct horse").is_err());
+        let mut version = bytes.clone();
+        version[MAGIC.len() - 1] = 2;
+        assert!(decrypt(&version, "correct horse").is_err());
+        assert!(decrypt(TEXT, "correct horse").is_err());
+        // Cut off inside the header
+        assert!(decrypt(&bytes[..HEADER_LEN - 1], "correct horse").is_err());
+        assert!(decrypt(MAGIC, "correct horse").is_err());
+        // Lanes of 0 aren't valid Argon2 settings
+        let mut lanes = bytes.clone();
+        lanes[field(2)..field(3)].copy_from_slice(&0u32.to_le_bytes());
+        assert!(decrypt(&lanes, "correct horse").is_err());
+    }
+
+    #[test]
+    fn a_truncated_body_is_none() {
+        // Header intact, the encrypted part cut short: it fails to check
+        let bytes = encrypted();
+        assert_eq!(
+            decrypt(&bytes[..bytes.len() - 1], "correct horse").unwrap(),
+            None
+        );
+        assert_eq!(
+            decrypt(&bytes[..HEADER_LEN], "correct horse").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn costly_key_settings_are_refused() {
+        let bytes = encrypted();
+        let mut memory = bytes.clone();
+        memory[field(0)..field(1)].copy_from_slice(&(MAX_MEMORY_KIB + 1).to_le_bytes());
+        let error = decrypt(&memory, "correct horse").unwrap_err();
+        assert!(error.to_string().contains("unreasonable"));
+
+        let mut passes = bytes;
+        passes[field(1)..field(2)].copy_from_slice(&(MAX_PASSES + 1).to_le_bytes());
+        assert!(decrypt(&passes, "correct horse").is_err());
+    }
+}
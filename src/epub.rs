@@ -0,0 +1,514 @@
+//! FILE: src/epub.rs
+//!
+//! Compiling to EPUB: the manuscript as an e-book that beta readers can
+//! open on a phone, tablet or e-reader.
+//!
+//! An .epub file is a zip archive of XHTML pages plus a few files that
+//! describe them (EPUB 3):
+//! - `mimetype`: the text "application/epub+zip", first in the archive and
+//!   not compressed, so a reader can recognize the file from its first bytes
+//! - `META-INF/container.xml`: where the package document is
+//! - `OEBPS/content.opf`: the package document - title, author, language,
+//!   every file in the book, and the reading order (the spine)
+//! - `OEBPS/nav.xhtml`: the table of contents - every act and chapter,
+//!   with its scenes under it. Readers show it in their menu, and it is
+//!   also a Contents page in the book, after any front matter
+//! - `OEBPS/toc.ncx`: the same contents for older (EPUB 2) readers
+//! - `OEBPS/section-N.xhtml`: the text, one file per act and chapter, so a
+//!   reader starts each on a new page; anything above the first act or
+//!   chapter gets a file of its own
+//!
+//! The pages are built with the HTML export's body writer (see export.rs),
+//! so headings, cues and paragraphs look the same in both, and all text is
+//! escaped the same way.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Choosing a compression method per zip entry (Stored vs Deflated)
+//! - Inline format arguments naming a constant (`{XML_DECLARATION}`)
+//! - std::mem::replace: finish one section and start the next in one step
+
+use crate::export::{escape_html, HtmlBody, HTML_STYLE};
+use crate::parser::{self, TagType};
+use crate::storage;
+use anyhow::Result;
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::CompressionMethod;
+
+/// One XHTML file of the book
+struct Section {
+    /// The act or chapter heading it starts with (the book's title for the
+    /// text above the first one)
+    title: String,
+    /// The page's `<body>` contents
+    body: String,
+    /// The scenes that start on this page: their number (for the
+    /// `scene-N` anchor) and description
+    scenes: Vec<(usize, String)>,
+    /// The text above the first act or chapter
+    front_matter: bool,
+}
+
+impl Section {
+    /// File name of the `index`th section (0-based), inside OEBPS
+    fn file_name(index: usize) -> String {
+        format!("section-{}.xhtml", index + 1)
+    }
+}
+
+/// Build an .epub file for `text`
+///
+/// `title` and `author` (which may be empty) go in the book's metadata,
+/// where libraries and e-readers list it. Err only if the zip archive
+/// can't be written.
+pub fn export_epub(text: &str, title: &str, author: &str) -> Result<Vec<u8>> {
+    let sections = split_sections(text, title);
+    let identifier = book_identifier(title, author);
+    let modified = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default();
+
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+
+    let mut files = vec![
+        (
+            String::from("META-INF/container.xml"),
+            CONTAINER.to_string(),
+        ),
+        (
+            String::from("OEBPS/content.opf"),
+            package_document(&sections, &identifier, title, author, &modified),
+        ),
+        (
+            String::from("OEBPS/nav.xhtml"),
+            navigation(&sections, title),
+        ),
+        (
+            String::from("OEBPS/toc.ncx"),
+            ncx(&sections, &identifier, title),
+        ),
+        (String::from("OEBPS/style.css"), HTML_STYLE.to_string()),
+    ];
+    for (index, section) in sections.iter().enumerate() {
+        files.push((
+            format!("OEBPS/{}", Section::file_name(index)),
+            xhtml_page(&section.title, &section.body),
+        ));
+    }
+    for (name, content) in files {
+        zip.start_file(name, deflated)?;
+        zip.write_all(content.as_bytes())?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Cut the document into a section per act and chapter
+///
+/// A book with nothing in it still gets one (empty) section: the spine
+/// can't be empty.
+fn split_sections(text: &str, book_title: &str) -> Vec<Section> {
+    let mut sections = Vec::new();
+    let mut body = HtmlBody::default();
+    let mut current = Section {
+        title: book_title.to_string(),
+        body: String::new(),
+        scenes: Vec::new(),
+        front_matter: true,
+    };
+    // Numbered the way HtmlBody numbers their anchors
+    let mut scene_count = 0;
+
+    for line in &parser::parse_document(text) {
+        let heading = match &line.tag {
+            Some(TagType::Act(act)) => Some(format!("Act {}", act)),
+            Some(TagType::Chapter(chapter)) => Some(chapter.clone()),
+            Some(TagType::Scene(description)) => {
+                scene_count += 1;
+                current.scenes.push((scene_count, description.clone()));
+                None
+            }
+            _ => None,
+        };
+        if let Some(heading) = heading {
+            let next = Section {
+                title: heading,
+                body: String::new(),
+                scenes: Vec::new(),
+                front_matter: false,
+            };
+            let mut finished = std::mem::replace(&mut current, next);
+            finished.body = body.take();
+            // Blank lines above the first heading don't make a section
+            if !finished.body.is_empty() {
+                sections.push(finished);
+            }
+        }
+        body.line(line);
+    }
+
+    current.body = body.take();
+    if !current.body.is_empty() || sections.is_empty() {
+        sections.push(current);
+    }
+    sections
+}
+
+/// Where the Contents page goes in the reading order: after the front
+/// matter if there is any, otherwise first
+fn contents_position(sections: &[Section]) -> usize {
+    usize::from(sections.first().is_some_and(|s| s.front_matter))
+}
+
+/// The book's identifier, for the package document and the NCX
+///
+/// It only has to stay the same when the same book is compiled again, so
+/// readers treat a new draft as an update.
+fn book_identifier(title: &str, author: &str) -> String {
+    format!(
+        "urn:bookscript:{:016x}",
+        storage::content_hash(&format!("{}\n{}", title, author))
+    )
+}
+
+/// OEBPS/content.opf: metadata, manifest and spine
+fn package_document(
+    sections: &[Section],
+    identifier: &str,
+    title: &str,
+    author: &str,
+    modified: &str,
+) -> String {
+    let creator = match author.trim() {
+        "" => String::new(),
+        author => format!("<dc:creator>{}</dc:creator>\n", escape_html(author)),
+    };
+
+    let mut manifest = String::new();
+    let mut spine = String::new();
+    for index in 0..sections.len() {
+        if index == contents_position(sections) {
+            spine.push_str("<itemref idref=\"nav\"/>\n");
+        }
+        let id = format!("section-{}", index + 1);
+        manifest.push_str(&format!(
+            "<item id=\"{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+            id,
+            Section::file_name(index)
+        ));
+        spine.push_str(&format!("<itemref idref=\"{}\"/>\n", id));
+    }
+
+    format!(
+        "{XML_DECLARATION}<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" \
+         unique-identifier=\"book-id\">\n\
+         <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+         <dc:identifier id=\"book-id\">{}</dc:identifier>\n\
+         <dc:title>{}</dc:title>\n\
+         {}<dc:language>en</dc:language>\n\
+         <meta property=\"dcterms:modified\">{}</meta>\n\
+         </metadata>\n\
+         <manifest>\n\
+         <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+         <item id=\"ncx\" href=\"toc.ncx\" media-type=\"application/x-dtbncx+xml\"/>\n\
+         <item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n\
+         {}</manifest>\n\
+         <spine toc=\"ncx\">\n{}</spine>\n\
+         </package>\n",
+        identifier,
+        escape_html(title),
+        creator,
+        modified,
+        manifest,
+        spine
+    )
+}
+
+/// OEBPS/nav.xhtml: one entry per section, in reading order, with its
+/// scenes in a list of their own
+fn navigation(sections: &[Section], title: &str) -> String {
+    let mut items = String::new();
+    for (index, section) in sections.iter().enumerate() {
+        let file = Section::file_name(index);
+        items.push_str(&format!(
+            "<li><a href=\"{}\">{}</a>",
+            file,
+            escape_html(&section.title)
+        ));
+        if !section.scenes.is_empty() {
+            items.push_str("\n<ol>\n");
+            for (number, description) in &section.scenes {
+                items.push_str(&format!(
+                    "<li><a href=\"{}#scene-{}\">{}</a></li>\n",
+                    file,
+                    number,
+                    escape_html(description)
+                ));
+            }
+            items.push_str("</ol>\n");
+        }
+        items.push_str("</li>\n");
+    }
+    let body = format!(
+        "<nav epub:type=\"toc\" id=\"toc\">\n<h1>Contents</h1>\n<ol>\n{}</ol>\n</nav>\n",
+        items
+    );
+    xhtml_page(title, &body)
+}
+
+/// OEBPS/toc.ncx: the contents again, in EPUB 2's format
+///
+/// Every entry needs a playOrder: its position in the reading order,
+/// counting scenes.
+fn ncx(sections: &[Section], identifier: &str, title: &str) -> String {
+    let mut points = String::new();
+    let mut order = 0;
+    let mut point = |points: &mut String, label: &str, src: &str| {
+        order += 1;
+        points.push_str(&format!(
+            "<navPoint id=\"point-{0}\" playOrder=\"{0}\"><navLabel><text>{1}</text></navLabel>\
+             <content src=\"{2}\"/>",
+            order,
+            escape_html(label),
+            src
+        ));
+    };
+    for (index, section) in sections.iter().enumerate() {
+        let file = Section::file_name(index);
+        point(&mut points, &section.title, &file);
+        points.push('\n');
+        for (number, description) in &section.scenes {
+            point(
+                &mut points,
+                description,
+                &format!("{}#scene-{}", file, number),
+            );
+            points.push_str("</navPoint>\n");
+        }
+        points.push_str("</navPoint>\n");
+    }
+
+    format!(
+        "{XML_DECLARATION}<ncx xmlns=\"http://www.daisy.org/z3986/2005/ncx/\" version=\"2005-1\">\n\
+         <head><meta name=\"dtb:uid\" content=\"{}\"/></head>\n\
+         <docTitle><text>{}</text></docTitle>\n\
+         <navMap>\n{}</navMap>\n\
+         </ncx>\n",
+        escape_html(identifier),
+        escape_html(title),
+        points
+    )
+}
+
+/// A complete XHTML page around `body`
+fn xhtml_page(title: &str, body: &str) -> String {
+    format!(
+        "{XML_DECLARATION}<!DOCTYPE html>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" \
+         lang=\"en\" xml:lang=\"en\">\n\
+         <head>\n<meta charset=\"utf-8\"/>\n<title>{}</title>\n\
+         <link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>\n</head>\n\
+         <body>\n{}</body>\n</html>\n",
+        escape_html(title),
+        body
+    )
+}
+
+// ============================================================================
+// FIXED PARTS OF THE PACKAGE
+// ============================================================================
+
+const XML_DECLARATION: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+
+const CONTAINER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\
+<rootfiles><rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/></rootfiles>\
+</container>";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    /// The archive's entries, in order, with their text
+    fn unzip(bytes: Vec<u8>) -> Vec<(String, String)> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        (0..archive.len())
+            .map(|index| {
+                let mut file = archive.by_index(index).unwrap();
+                let mut text = String::new();
+                file.read_to_string(&mut text).unwrap();
+                (file.name().to_string(), text)
+            })
+            .collect()
+    }
+
+    fn entry<'a>(files: &'a [(String, String)], name: &str) -> &'a str {
+        &files.iter().find(|(n, _)| n == name).unwrap().1
+    }
+
+    /// The reading order: the spine's idrefs
+    fn spine(files: &[(String, String)]) -> Vec<&str> {
+        entry(files, "OEBPS/content.opf")
+            .lines()
+            .filter_map(|line| line.strip_prefix("<itemref idref=\""))
+            .filter_map(|rest| rest.strip_suffix("\"/>"))
+            .collect()
+    }
+
+    #[test]
+    fn the_mimetype_comes_first_and_uncompressed() {
+        let bytes = export_epub("Text", "Book", "").unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let first = archive.by_index(0).unwrap();
+        assert_eq!(first.name(), "mimetype");
+        assert_eq!(first.compression(), CompressionMethod::Stored);
+        drop(first);
+        let mut mimetype = String::new();
+        archive
+            .by_name("mimetype")
+            .unwrap()
+            .read_to_string(&mut mimetype)
+            .unwrap();
+        assert_eq!(mimetype, "application/epub+zip");
+        assert!(archive.by_name("META-INF/container.xml").is_ok());
+    }
+
+    #[test]
+    fn each_act_and_chapter_gets_its_own_page_in_reading_order() {
+        let text = "A dedication.\n\n[ACT: I]\n[CHAPTER: One]\nFirst.\n[SCENE: Dock]\n\
+                    Rain.\n[CHAPTER: Two]\nSecond.";
+        let files = unzip(export_epub(text, "My Book", "Ann Lee").unwrap());
+        let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "mimetype",
+                "META-INF/container.xml",
+                "OEBPS/content.opf",
+                "OEBPS/nav.xhtml",
+                "OEBPS/toc.ncx",
+                "OEBPS/style.css",
+                "OEBPS/section-1.xhtml",
+                "OEBPS/section-2.xhtml",
+                "OEBPS/section-3.xhtml",
+                "OEBPS/section-4.xhtml",
+            ]
+        );
+
+        assert!(entry(&files, "OEBPS/section-1.xhtml").contains("<p>A dedication.</p>"));
+        assert!(entry(&files, "OEBPS/section-2.xhtml").contains("<h1 class=\"act\">Act I</h1>"));
+        let one = entry(&files, "OEBPS/section-3.xhtml");
+        assert!(one.contains("<title>One</title>"));
+        assert!(one.contains("<h1 id=\"chapter-1\">One</h1>"));
+        assert!(one.contains("<h2 id=\"scene-1\">Dock</h2>"));
+        // Anchor ids carry on from page to page
+        let two = entry(&files, "OEBPS/section-4.xhtml");
+        assert!(two.contains("<h1 id=\"chapter-2\">Two</h1>"));
+
+        let opf = entry(&files, "OEBPS/content.opf");
+        assert!(opf.contains("<dc:title>My Book</dc:title>"));
+        assert!(opf.contains("<dc:creator>Ann Lee</dc:creator>"));
+        // The Contents page comes after the dedication
+        assert_eq!(
+            spine(&files),
+            ["section-1", "nav", "section-2", "section-3", "section-4"]
+        );
+    }
+
+    #[test]
+    fn the_contents_list_chapters_with_their_scenes() {
+        let text = "[CHAPTER: One]\n[SCENE: Dock]\nRain.\n[SCENE: Ship & sea]\nWaves.\n\
+                    [CHAPTER: Two]\nSecond.\n[SCENE: Home]\nDry.";
+        let files = unzip(export_epub(text, "Book", "").unwrap());
+
+        let nav = entry(&files, "OEBPS/nav.xhtml");
+        assert!(nav.contains(
+            "<nav epub:type=\"toc\" id=\"toc\">\n<h1>Contents</h1>\n<ol>\n\
+             <li><a href=\"section-1.xhtml\">One</a>\n<ol>\n\
+             <li><a href=\"section-1.xhtml#scene-1\">Dock</a></li>\n\
+             <li><a href=\"section-1.xhtml#scene-2\">Ship &amp; sea</a></li>\n\
+             </ol>\n</li>\n\
+             <li><a href=\"section-2.xhtml\">Two</a>\n<ol>\n\
+             <li><a href=\"section-2.xhtml#scene-3\">Home</a></li>\n\
+             </ol>\n</li>\n\
+             </ol>\n</nav>"
+        ));
+        // The anchors the contents link to are on those pages
+        assert!(entry(&files, "OEBPS/section-2.xhtml").contains("<h2 id=\"scene-3\">Home</h2>"));
+
+        // No front matter: the Contents page comes first
+        assert_eq!(spine(&files), ["nav", "section-1", "section-2"]);
+        let opf = entry(&files, "OEBPS/content.opf");
+        assert!(opf.contains("properties=\"nav\""));
+        assert!(opf.contains("<spine toc=\"ncx\">"));
+
+        let ncx = entry(&files, "OEBPS/toc.ncx");
+        let orders: Vec<&str> = ncx
+            .split("playOrder=\"")
+            .skip(1)
+            .filter_map(|rest| rest.split('"').next())
+            .collect();
+        assert_eq!(orders, ["1", "2", "3", "4", "5"]);
+        assert!(ncx.contains(
+            "<text>Ship &amp; sea</text></navLabel><content src=\"section-1.xhtml#scene-2\"/>"
+        ));
+        let uid = format!("content=\"{}\"", book_identifier("Book", ""));
+        assert!(ncx.contains(&uid));
+        assert!(opf.contains(&book_identifier("Book", "")));
+    }
+
+    #[test]
+    fn every_xml_file_is_well_formed() {
+        let text = "[CHAPTER: Tom & Jerry]\nHERO\n(beat)\n<Hi> \"there\"\nagain\n\n[SCENE: A < B]";
+        let files = unzip(export_epub(text, "T&J", "O'Neil").unwrap());
+        for (name, content) in &files {
+            if name.ends_with(".xhtml")
+                || name.ends_with(".opf")
+                || name.ends_with(".ncx")
+                || name.ends_with(".xml")
+            {
+                let options = roxmltree::ParsingOptions {
+                    allow_dtd: true,
+                    ..roxmltree::ParsingOptions::default()
+                };
+                if let Err(e) = roxmltree::Document::parse_with_options(content, options) {
+                    panic!("{} isn't well-formed: {}\n{}", name, e, content);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn text_and_metadata_are_escaped() {
+        let files = unzip(
+            export_epub(
+                "[CHAPTER: <b>Bold</b>]\nTom & \"Jerry\"\nline two",
+                "A < B",
+                "",
+            )
+            .unwrap(),
+        );
+        let opf = entry(&files, "OEBPS/content.opf");
+        assert!(opf.contains("<dc:title>A &lt; B</dc:title>"));
+        // No author: no creator element at all
+        assert!(!opf.contains("dc:creator"));
+        let page = entry(&files, "OEBPS/section-1.xhtml");
+        assert!(page.contains("<title>&lt;b&gt;Bold&lt;/b&gt;</title>"));
+        assert!(page.contains("<p>Tom &amp; &quot;Jerry&quot;<br/>\nline two</p>"));
+    }
+
+    #[test]
+    fn an_empty_book_still_has_a_page() {
+        let files = unzip(export_epub("\n\n", "Empty", "").unwrap());
+        assert!(files
+            .iter()
+            .any(|(name, _)| name == "OEBPS/section-1.xhtml"));
+        assert!(!files
+            .iter()
+            .any(|(name, _)| name == "OEBPS/section-2.xhtml"));
+        assert!(entry(&files, "OEBPS/nav.xhtml").contains(">Empty</a>"));
+    }
+}
@@ -0,0 +1,749 @@
+//! FILE: src/export.rs
+//!
+//! Convert a BookScript document into formats other programs can read.
+//!
+//! The tag syntax (`[CHAPTER: X]`, `[SCENE: Y]`, ...) means nothing outside
+//! this app, so exporting replaces tags with the target format's own way of
+//! marking structure. Everything else - prose, dialogue cues, stage
+//! directions - passes through untouched.
+//!
+//! MAPPING TABLE:
+//!
+//! | Input              | Markdown                  | Plain text              |
+//! |--------------------|---------------------------|-------------------------|
+//! | `[ACT: I]`         | `# I`                     | `ACT I` (centered)      |
+//! | `[CHAPTER: X]`     | `# X` (`## X` with acts)  | `X` in capitals         |
+//! | `[SCENE: Y]`       | `## Y` (`### Y` with acts)| `#` scene break         |
+//! | `[CHARACTER: Ann]` | `**ANN**`                 | `ANN`                   |
+//! | malformed tag      | unchanged                 | unchanged               |
+//! | anything else      | unchanged                 | unchanged               |
+//!
+//! Headings always get a blank line before and after them, which Markdown
+//! needs and which reads better in plain text too.
+//!
+//! HTML export (export_html) goes further: it builds a standalone page for
+//! beta readers, with a linked table of contents, `<h1>` chapters, `<h2>`
+//! scenes, `<p>` paragraphs and styled character cues. All document text is
+//! HTML-escaped.
+//!
+//! Fountain export (export_fountain) targets other screenwriting tools. It
+//! is the reverse of parser::import_fountain: what it writes, the importer
+//! reads back as the same document.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Reusing the parser instead of re-matching tags with new regexes
+//! - A small builder struct that owns the output String
+
+use crate::format::DocumentFormat;
+use crate::parser::{self, TagType};
+
+/// The entries of File → Export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exporter {
+    Markdown,
+    PlainText,
+    Html,
+    Fountain,
+    FinalDraft,
+    Pdf,
+    Docx,
+}
+
+impl Exporter {
+    /// Every exporter, in menu order
+    pub const ALL: [Exporter; 7] = [
+        Exporter::Markdown,
+        Exporter::PlainText,
+        Exporter::Html,
+        Exporter::Fountain,
+        Exporter::FinalDraft,
+        Exporter::Pdf,
+        Exporter::Docx,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Exporter::Markdown => "Markdown…",
+            Exporter::PlainText => "Plain text manuscript…",
+            Exporter::Html => "HTML…",
+            Exporter::Fountain => "Fountain…",
+            Exporter::FinalDraft => "Final Draft (FDX)…",
+            Exporter::Pdf => "PDF (screenplay)…",
+            Exporter::Docx => "Word (DOCX)…",
+        }
+    }
+
+    /// The exporters a document in `format` most likely goes out as, which
+    /// the menu offers first: screenplay formats for Fountain, reading
+    /// formats for Markdown. A BookScript document can go anywhere.
+    pub fn suggested_for(format: DocumentFormat) -> &'static [Exporter] {
+        match format {
+            DocumentFormat::BookScript => &[],
+            DocumentFormat::Fountain => &[Exporter::Fountain, Exporter::FinalDraft, Exporter::Pdf],
+            DocumentFormat::Markdown => &[Exporter::Markdown, Exporter::Html, Exporter::Docx],
+            DocumentFormat::Plain => &[Exporter::PlainText, Exporter::Docx],
+        }
+    }
+}
+
+/// Width plain-text headings and scene breaks are centered in
+/// (a standard manuscript line is about 60 characters)
+const PLAIN_LINE_WIDTH: usize = 60;
+
+/// Convert a document to Markdown
+pub fn export_markdown(text: &str) -> String {
+    // With acts present, acts take the top heading level and everything
+    // below moves down one
+    let has_acts = has_acts(text);
+    let chapter_level = if has_acts { "##" } else { "#" };
+    let scene_level = if has_acts { "###" } else { "##" };
+
+    let mut out = Output::default();
+    for line in text.lines() {
+        match structural_tag(line) {
+            Some(TagType::Act(title)) => out.heading(&format!("# {}", title)),
+            Some(TagType::Chapter(title)) => out.heading(&format!("{} {}", chapter_level, title)),
+            Some(TagType::Scene(description)) => {
+                out.heading(&format!("{} {}", scene_level, description))
+            }
+            Some(TagType::Character(name)) => out.line(&format!("**{}**", name.to_uppercase())),
+            _ => out.line(line),
+        }
+    }
+    out.finish()
+}
+
+/// Convert a document to plain text with manuscript-style spacing
+///
+/// Chapter titles are set in capitals, and a centered `#` marks a scene
+/// break - except directly under a heading, where a break would be noise.
+pub fn export_plaintext(text: &str) -> String {
+    let mut out = Output::default();
+    // True once prose has been written since the last heading
+    let mut prose_since_heading = false;
+
+    for line in text.lines() {
+        match structural_tag(line) {
+            Some(TagType::Act(title)) => {
+                out.heading(&centered(&format!("ACT {}", title.to_uppercase())));
+                prose_since_heading = false;
+            }
+            Some(TagType::Chapter(title)) => {
+                out.heading(&title.to_uppercase());
+                prose_since_heading = false;
+            }
+            Some(TagType::Scene(_)) => {
+                if prose_since_heading {
+                    out.heading(&centered("#"));
+                }
+                prose_since_heading = false;
+            }
+            Some(TagType::Character(name)) => {
+                out.line(&name.to_uppercase());
+                prose_since_heading = true;
+            }
+            _ => {
+                out.line(line);
+                if !line.trim().is_empty() {
+                    prose_since_heading = true;
+                }
+            }
+        }
+    }
+    out.finish()
+}
+
+// ============================================================================
+// FOUNTAIN
+// ============================================================================
+
+/// Characters that mark a Fountain line as something other than action
+/// when they start it (`.` only counts before a letter or digit)
+const FOUNTAIN_MARKERS: [char; 7] = ['#', '=', '>', '@', '!', '~', '.'];
+
+/// Convert a document to Fountain screenplay markup
+///
+/// MAPPING:
+/// - `[ACT: I]` → `# Act I`; `[CHAPTER: X]` → `# X` (`## X` with acts)
+/// - `[SCENE: INT. BEACH - DAY]` → `INT. BEACH - DAY`; a heading Fountain
+///   wouldn't recognize is forced with `.`: `[SCENE: Beach]` → `.Beach`
+/// - cues (bare or `[CHARACTER: Ann]`) → `ANN`, with a blank line above;
+///   a cue Fountain wouldn't recognize is forced with `@`
+/// - ordinary lines Fountain would mistake for something else - a scene
+///   heading, a cue, a line starting with one of its markers - are forced
+///   to action with `!`
+///
+/// Dialogue, parentheticals, transitions (`CUT TO:`) and malformed tags
+/// pass through. Importing the result gives back the same document, except
+/// that `[CHARACTER: Ann]` comes back as a bare cue.
+pub fn export_fountain(text: &str) -> String {
+    let parsed = parser::parse_document(text);
+    let chapter_level = if has_acts(text) { "##" } else { "#" };
+
+    let mut out = Output::default();
+    for (i, line) in parsed.iter().enumerate() {
+        let next = parsed.get(i + 1).map(|line| line.text.as_str());
+        match structural_tag(&line.text) {
+            Some(TagType::Act(title)) => out.heading(&format!("# Act {}", title)),
+            Some(TagType::Chapter(title)) => out.heading(&format!("{} {}", chapter_level, title)),
+            Some(TagType::Scene(heading)) => {
+                if parser::is_fountain_scene_heading(&heading) {
+                    out.heading(&heading)
+                } else {
+                    out.heading(&format!(".{}", heading))
+                }
+            }
+            _ => match (&line.tag, parser::cue_name(line, next)) {
+                (Some(TagType::Character(cue)), Some(_)) => {
+                    let cue = cue.trim().to_uppercase();
+                    if parser::is_character_cue(&cue) {
+                        out.separated(&cue);
+                    } else {
+                        out.separated(&format!("@{}", cue));
+                    }
+                }
+                _ => {
+                    let after_blank = out.at_break();
+                    out.line(&fountain_action(&line.text, after_blank, next));
+                }
+            },
+        }
+    }
+    out.finish()
+}
+
+/// An ordinary line, with `!` in front if Fountain would otherwise read it
+/// as something else
+fn fountain_action(line: &str, after_blank: bool, next: Option<&str>) -> String {
+    let trimmed = line.trim();
+    let mut chars = trimmed.chars();
+    let marker = match chars.next() {
+        Some('.') => chars.next().is_some_and(char::is_alphanumeric),
+        Some(c) => FOUNTAIN_MARKERS.contains(&c),
+        None => return line.to_string(),
+    };
+
+    // Fountain takes any all-capitals line between a blank line and a
+    // non-blank one for a cue, and the parser is pickier (see cue_name)
+    let has_letters = trimmed.chars().any(char::is_alphabetic);
+    let all_caps = has_letters && !trimmed.chars().any(char::is_lowercase);
+    let next_has_text = next.is_some_and(|next| !next.trim().is_empty());
+    let looks_like_cue = after_blank && all_caps && next_has_text;
+
+    let looks_like_heading = after_blank && parser::is_fountain_scene_heading(trimmed);
+
+    if marker || looks_like_cue || looks_like_heading {
+        format!("!{}", line)
+    } else {
+        line.to_string()
+    }
+}
+
+// ============================================================================
+// HTML
+// ============================================================================
+
+/// A standalone HTML page, plus what the status bar reports about it
+#[derive(Debug, Clone, PartialEq)]
+pub struct HtmlExport {
+    pub html: String,
+    /// Number of chapters in the page
+    pub chapters: usize,
+}
+
+/// Styles embedded in every exported page, so it needs no other files
+/// (EPUB export puts the same rules in its stylesheet)
+pub(crate) const HTML_STYLE: &str = "\
+body { max-width: 40em; margin: 2em auto; padding: 0 1em; font-family: Georgia, serif; line-height: 1.6; }
+.title { font-size: 2.2em; text-align: center; margin: 1em 0; }
+nav.toc { border-bottom: 1px solid #ccc; margin-bottom: 2em; padding-bottom: 1em; }
+nav.toc ol ol { list-style: none; padding-left: 1.5em; }
+h1 { margin-top: 2.5em; }
+h1.act { text-align: center; text-transform: uppercase; letter-spacing: 0.1em; }
+.cue { font-variant: small-caps; font-weight: bold; margin: 1em 0 0 3em; }
+.cue + p { margin-top: 0; margin-left: 3em; }
+.direction { font-style: italic; margin-left: 3em; }
+";
+
+/// Convert a document to a standalone HTML page
+///
+/// The page title is `file_title` (the document's file name) when given,
+/// otherwise the first chapter's title, otherwise "Untitled".
+///
+/// LAYOUT:
+/// - Table of contents: every chapter, with its scenes nested under it,
+///   linking to `#chapter-N` / `#scene-N` (numbered in document order)
+/// - `[ACT: I]` → `<h1 class="act">Act I`, `[CHAPTER: X]` → `<h1>`,
+///   `[SCENE: Y]` → `<h2>`
+/// - Character cues (bare or `[CHARACTER: Ann]`) → `<p class="cue">`;
+///   stage directions → `<p class="direction">`
+/// - Other lines make paragraphs: blank lines separate them, and lines
+///   within a paragraph are joined with `<br/>`
+/// - Malformed tags are shown as ordinary text
+pub fn export_html(text: &str, file_title: Option<&str>) -> HtmlExport {
+    let parsed = parser::parse_document(text);
+    let structure = parser::extract_structure(&parsed);
+
+    let title = file_title
+        .map(str::to_string)
+        .or_else(|| structure.chapters.first().map(|c| c.title.clone()))
+        .unwrap_or_else(|| String::from("Untitled"));
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape_html(&title)));
+    html.push_str(&format!("<style>\n{}</style>\n", HTML_STYLE));
+    html.push_str("</head>\n<body>\n");
+    html.push_str(&format!(
+        "<header><p class=\"title\">{}</p></header>\n",
+        escape_html(&title)
+    ));
+
+    html_contents(&mut html, &structure);
+
+    html.push_str("<main>\n");
+    let mut body = HtmlBody::default();
+    for line in &parsed {
+        body.line(line);
+    }
+    html.push_str(&body.finish());
+    html.push_str("</main>\n</body>\n</html>\n");
+
+    HtmlExport {
+        html,
+        chapters: structure.chapters.len(),
+    }
+}
+
+/// Write the table of contents (nothing if there are no chapters or scenes)
+///
+/// Scenes before the first chapter are listed at the top level.
+fn html_contents(html: &mut String, structure: &parser::DocumentStructure) {
+    if structure.chapters.is_empty() && structure.scenes.is_empty() {
+        return;
+    }
+
+    let scene_item = |html: &mut String, index: usize, scene: &parser::Scene| {
+        html.push_str(&format!(
+            "<li><a href=\"#scene-{}\">{}</a></li>\n",
+            index + 1,
+            escape_html(&scene.description)
+        ));
+    };
+
+    html.push_str("<nav class=\"toc\">\n<p><strong>Contents</strong></p>\n<ol>\n");
+    for (index, scene) in structure.scenes.iter().enumerate() {
+        if scene.line_start
+            < structure
+                .chapters
+                .first()
+                .map_or(usize::MAX, |c| c.line_start)
+        {
+            scene_item(html, index, scene);
+        }
+    }
+    for (index, chapter) in structure.chapters.iter().enumerate() {
+        html.push_str(&format!(
+            "<li><a href=\"#chapter-{}\">{}</a>",
+            index + 1,
+            escape_html(&chapter.title)
+        ));
+        // A chapter's scenes are the ones that start inside its line range
+        let mut scenes = structure
+            .scenes
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| (chapter.line_start..=chapter.line_end).contains(&s.line_start))
+            .peekable();
+        if scenes.peek().is_some() {
+            html.push_str("\n<ol>\n");
+            for (index, scene) in scenes {
+                scene_item(html, index, scene);
+            }
+            html.push_str("</ol>\n");
+        }
+        html.push_str("</li>\n");
+    }
+    html.push_str("</ol>\n</nav>\n");
+}
+
+/// Builds the page body line by line, grouping prose into paragraphs
+///
+/// The markup is also valid XHTML (`<br/>`), so EPUB export builds its
+/// chapter files with it too.
+#[derive(Default)]
+pub(crate) struct HtmlBody {
+    html: String,
+    /// Escaped lines of the paragraph being collected
+    paragraph: Vec<String>,
+    /// Chapters and scenes written so far, for their anchor ids
+    chapters: usize,
+    scenes: usize,
+}
+
+impl HtmlBody {
+    pub(crate) fn line(&mut self, line: &parser::ParsedLine) {
+        // Bracket tags and bare cues/directions both come from the parser;
+        // Unknown (malformed) tags are just text
+        match &line.tag {
+            Some(TagType::Act(title)) => {
+                self.block(&format!(
+                    "<h1 class=\"act\">Act {}</h1>",
+                    escape_html(title)
+                ));
+            }
+            Some(TagType::Chapter(title)) => {
+                self.chapters += 1;
+                self.block(&format!(
+                    "<h1 id=\"chapter-{}\">{}</h1>",
+                    self.chapters,
+                    escape_html(title)
+                ));
+            }
+            Some(TagType::Scene(description)) => {
+                self.scenes += 1;
+                self.block(&format!(
+                    "<h2 id=\"scene-{}\">{}</h2>",
+                    self.scenes,
+                    escape_html(description)
+                ));
+            }
+            Some(TagType::Character(name)) => {
+                self.block(&format!(
+                    "<p class=\"cue\">{}</p>",
+                    escape_html(&name.to_uppercase())
+                ));
+            }
+            Some(TagType::Action(_)) => {
+                self.block(&format!(
+                    "<p class=\"direction\">{}</p>",
+                    escape_html(line.text.trim())
+                ));
+            }
+            Some(TagType::Unknown(_)) | None => {
+                if line.text.trim().is_empty() {
+                    self.end_paragraph();
+                } else {
+                    self.paragraph.push(escape_html(line.text.trim()));
+                }
+            }
+        }
+    }
+
+    /// Write a heading or other single-line block, ending any paragraph
+    fn block(&mut self, block: &str) {
+        self.end_paragraph();
+        self.html.push_str(block);
+        self.html.push('\n');
+    }
+
+    fn end_paragraph(&mut self) {
+        if self.paragraph.is_empty() {
+            return;
+        }
+        self.html.push_str("<p>");
+        self.html.push_str(&self.paragraph.join("<br/>\n"));
+        self.html.push_str("</p>\n");
+        self.paragraph.clear();
+    }
+
+    fn finish(mut self) -> String {
+        self.end_paragraph();
+        self.html
+    }
+
+    /// Everything written so far, leaving the body empty but still
+    /// counting chapters and scenes, so anchor ids stay unique across the
+    /// pieces
+    pub(crate) fn take(&mut self) -> String {
+        self.end_paragraph();
+        std::mem::take(&mut self.html)
+    }
+}
+
+/// Escape the characters HTML gives a meaning to
+///
+/// Quotes are escaped too, so the result is safe inside attribute values.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// ============================================================================
+// HELPERS
+// ============================================================================
+
+/// The tag on this line, if it is a well-formed BRACKET tag
+///
+/// Bare character cues ("HERO") and stage directions are tags to the
+/// parser but plain text to an exporter, so they return None. So do
+/// malformed tags (TagType::Unknown), which are passed through verbatim.
+pub(crate) fn structural_tag(line: &str) -> Option<TagType> {
+    if !line.trim_start().starts_with('[') {
+        return None;
+    }
+    match parser::parse_line(line, 0).tag {
+        Some(TagType::Unknown(_)) | Some(TagType::Action(_)) => None,
+        tag => tag,
+    }
+}
+
+/// Does the document contain any [ACT: ...] tags?
+fn has_acts(text: &str) -> bool {
+    text.lines()
+        .any(|line| matches!(structural_tag(line), Some(TagType::Act(_))))
+}
+
+/// Pad `text` on the left so it sits in the middle of a manuscript line
+fn centered(text: &str) -> String {
+    let width = text.chars().count();
+    let padding = PLAIN_LINE_WIDTH.saturating_sub(width) / 2;
+    format!("{}{}", " ".repeat(padding), text)
+}
+
+/// Collects output lines, keeping blank lines around headings tidy
+#[derive(Default)]
+struct Output {
+    text: String,
+    /// The last line written was blank
+    at_blank: bool,
+    /// A heading was just written; a blank line must follow it
+    after_heading: bool,
+}
+
+impl Output {
+    /// Write one line as-is
+    fn line(&mut self, line: &str) {
+        let blank = line.trim().is_empty();
+        if self.after_heading && !blank {
+            self.push("");
+        }
+        self.after_heading = false;
+        self.push(line);
+    }
+
+    /// Write a line with a blank line above it (except at the very top)
+    fn separated(&mut self, line: &str) {
+        if !self.at_break() {
+            self.push("");
+        }
+        self.line(line);
+    }
+
+    /// Will the next line have a blank line (or nothing) above it?
+    fn at_break(&self) -> bool {
+        self.text.is_empty() || self.at_blank || self.after_heading
+    }
+
+    /// Write a heading with a blank line before and after it
+    ///
+    /// A heading at the very top of the output gets no blank line above it.
+    fn heading(&mut self, heading: &str) {
+        if !self.text.is_empty() && !self.at_blank {
+            self.push("");
+        }
+        self.push(heading);
+        self.after_heading = true;
+    }
+
+    fn push(&mut self, line: &str) {
+        self.text.push_str(line);
+        self.text.push('\n');
+        self.at_blank = line.trim().is_empty();
+    }
+
+    fn finish(self) -> String {
+        self.text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_maps_each_tag_to_a_heading() {
+        let text = "[CHAPTER: The Beginning]\nShe ran.\n[SCENE: Beach]\n[CHARACTER: Ann]\nHello.";
+        assert_eq!(
+            export_markdown(text),
+            "# The Beginning\n\nShe ran.\n\n## Beach\n\n**ANN**\nHello.\n"
+        );
+    }
+
+    #[test]
+    fn markdown_headings_move_down_a_level_under_acts() {
+        let text = "[ACT: I]\n[CHAPTER: One]\n[SCENE: Beach]\nText";
+        assert_eq!(
+            export_markdown(text),
+            "# I\n\n## One\n\n### Beach\n\nText\n"
+        );
+    }
+
+    #[test]
+    fn plain_text_sets_headings_and_scene_breaks() {
+        let text = "[ACT: i]\n[CHAPTER: One]\n[SCENE: Beach]\nShe ran.\n[SCENE: Cave]\nDark.";
+        let out = export_plaintext(text);
+        assert_eq!(
+            out,
+            format!(
+                "{}\n\nONE\n\nShe ran.\n\n{}\n\nDark.\n",
+                centered("ACT I"),
+                centered("#")
+            )
+        );
+        // The first scene directly under its chapter gets no break
+        assert_eq!(out.matches('#').count(), 1);
+    }
+
+    #[test]
+    fn malformed_tags_and_prose_pass_through_verbatim() {
+        let text = "[SCENE: Beach\n[CHAPTR: 1]\n  Indented prose.\nHERO\n(beat)";
+        assert_eq!(export_markdown(text), format!("{}\n", text));
+        assert_eq!(export_plaintext(text), format!("{}\n", text));
+    }
+
+    #[test]
+    fn a_document_without_tags_is_unchanged() {
+        let text = "Just prose.\n\nA second paragraph.";
+        assert_eq!(export_markdown(text), format!("{}\n", text));
+        assert_eq!(export_plaintext(text), format!("{}\n", text));
+        assert_eq!(export_markdown(""), "");
+        assert_eq!(export_plaintext(""), "");
+    }
+
+    #[test]
+    fn html_golden_page() {
+        let text = "[CHAPTER: One]\n\
+                    [SCENE: Dock]\n\
+                    The boat left.\n\
+                    It rained.\n\
+                    \n\
+                    ANN\n\
+                    (quietly)\n\
+                    Goodbye.\n\
+                    [CHAPTER: Two]\n\
+                    The end.";
+        let export = export_html(text, Some("Draft"));
+        assert_eq!(export.chapters, 2);
+        let expected = format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+             <title>Draft</title>\n\
+             <style>\n{}</style>\n\
+             </head>\n<body>\n\
+             <header><p class=\"title\">Draft</p></header>\n\
+             <nav class=\"toc\">\n<p><strong>Contents</strong></p>\n<ol>\n\
+             <li><a href=\"#chapter-1\">One</a>\n<ol>\n\
+             <li><a href=\"#scene-1\">Dock</a></li>\n\
+             </ol>\n</li>\n\
+             <li><a href=\"#chapter-2\">Two</a></li>\n\
+             </ol>\n</nav>\n\
+             <main>\n\
+             <h1 id=\"chapter-1\">One</h1>\n\
+             <h2 id=\"scene-1\">Dock</h2>\n\
+             <p>The boat left.<br/>\nIt rained.</p>\n\
+             <p class=\"cue\">ANN</p>\n\
+             <p class=\"direction\">(quietly)</p>\n\
+             <p>Goodbye.</p>\n\
+             <h1 id=\"chapter-2\">Two</h1>\n\
+             <p>The end.</p>\n\
+             </main>\n</body>\n</html>\n",
+            HTML_STYLE
+        );
+        assert_eq!(export.html, expected);
+    }
+
+    #[test]
+    fn html_escapes_text_titles_and_tags() {
+        let text = "[CHAPTER: <script>alert(1)</script>]\n\
+                    <script>alert(\"x\")</script> & 'more'\n\
+                    [SCENE: A < B]\n\
+                    [CHAPTR: <b>typo</b>]";
+        let html = export_html(text, None).html;
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("<b>"));
+        assert!(html.contains("<title>&lt;script&gt;alert(1)&lt;/script&gt;</title>"));
+        assert!(html.contains(
+            "<p>&lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt; &amp; &#39;more&#39;</p>"
+        ));
+        assert!(html.contains("<h2 id=\"scene-1\">A &lt; B</h2>"));
+        assert!(html.contains("[CHAPTR: &lt;b&gt;typo&lt;/b&gt;]</p>"));
+        // The file name is escaped as well
+        let named = export_html("Text", Some("a&b <i>")).html;
+        assert!(named.contains("<title>a&amp;b &lt;i&gt;</title>"));
+    }
+
+    #[test]
+    fn html_without_structure_has_no_contents() {
+        let export = export_html("Just one line", None);
+        assert_eq!(export.chapters, 0);
+        assert!(!export.html.contains("<nav"));
+        assert!(export.html.contains("<title>Untitled</title>"));
+        assert!(export
+            .html
+            .contains("<main>\n<p>Just one line</p>\n</main>"));
+    }
+
+    /// Export to Fountain and import the result again
+    fn fountain_round_trip(text: &str) -> String {
+        parser::import_fountain(&export_fountain(text))
+    }
+
+    #[test]
+    fn fountain_maps_headings_cues_and_forced_elements() {
+        let text = "[ACT: I]\n[CHAPTER: Arrival]\n[SCENE: INT. DOCK - NIGHT]\n\
+                    Rain.\n\nANN\n(quietly)\nGo.\n\n[SCENE: Flashback]\n\n\
+                    [CHARACTER: Mr. Lee]\nStay.\n\nINT. is short for interior.\n";
+        assert_eq!(
+            export_fountain(text),
+            "# Act I\n\n## Arrival\n\nINT. DOCK - NIGHT\n\n\
+             Rain.\n\nANN\n(quietly)\nGo.\n\n.Flashback\n\n\
+             MR. LEE\nStay.\n\n!INT. is short for interior.\n"
+        );
+    }
+
+    #[test]
+    fn fountain_round_trips_a_screenplay() {
+        let text = "[ACT: I]\n\n\
+                    [CHAPTER: Arrival]\n\n\
+                    [SCENE: INT. DOCK - NIGHT]\n\n\
+                    Rain hammers the pier.\n\n\
+                    ANN (V.O.)\n\
+                    (quietly)\n\
+                    We should go.\n\n\
+                    BOB\n\
+                    Not yet.\n\n\
+                    CUT TO:\n\n\
+                    [SCENE: Flashback]\n\n\
+                    THE HOUSE IS EMPTY\n\
+                    and has been for years.\n\n\
+                    #hashtag at the start\n\
+                    .Dotted line\n";
+        assert_eq!(fountain_round_trip(text), text);
+    }
+
+    #[test]
+    fn fountain_round_trip_turns_explicit_cues_bare() {
+        let text = "[CHARACTER: Ann]\nHello.\n";
+        assert_eq!(fountain_round_trip(text), "ANN\nHello.\n");
+    }
+
+    #[test]
+    fn fountain_round_trip_keeps_books_without_acts() {
+        let text = "[CHAPTER: One]\n\nIt began.\n\n[CHAPTER: Two]\n\nIt ended.\n";
+        assert_eq!(export_fountain(text).lines().next(), Some("# One"));
+        assert_eq!(fountain_round_trip(text), text);
+        assert_eq!(fountain_round_trip(""), "");
+    }
+}
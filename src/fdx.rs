@@ -0,0 +1,322 @@
+//! FILE: src/fdx.rs
+//!
+//! Final Draft (.fdx) files: exporting to them and importing from them.
+//!
+//! FDX is XML. The script is a flat list of `<Paragraph Type="...">`
+//! elements inside `<Content>`, each holding one or more `<Text>` runs (a
+//! new run starts wherever the styling changes). There are no blank lines:
+//! Final Draft spaces paragraphs by their type.
+//!
+//! MAPPING TABLE:
+//!
+//! | BookScript                  | FDX paragraph type | Back to BookScript   |
+//! |-----------------------------|--------------------|----------------------|
+//! | `[SCENE: INT. HOUSE]`       | Scene Heading      | `[SCENE: ...]`       |
+//! | cue (`ANN`, `[CHARACTER:]`) | Character          | `ANN`                |
+//! | line in a speech            | Dialogue           | the line             |
+//! | `(beat)` in a speech        | Parenthetical      | `(beat)`             |
+//! | `CUT TO:`                   | Transition         | `CUT TO:`            |
+//! | `[ACT: I]`                  | New Act            | `[ACT: I]`           |
+//! | `[CHAPTER: X]`              | General            | `[CHAPTER: X]`       |
+//! | anything else               | Action             | the line             |
+//!
+//! Chapters aren't a screenplay idea, so they travel as the tag itself in a
+//! General paragraph; Final Draft shows it as text, and it comes back as a
+//! chapter. Paragraph types not in the table (Shot, Cast List, ...) are read
+//! as action, and End of Act paragraphs are dropped.
+//!
+//! Writing needs nothing more than string formatting with escaping;
+//! reading uses the roxmltree crate.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Building XML by hand, with escaping
+//! - Walking a read-only XML tree (roxmltree) with iterator adaptors
+
+use crate::export::escape_html;
+use crate::parser::{self, ParsedLine, TagType};
+use anyhow::{anyhow, bail, Result};
+
+// ============================================================================
+// EXPORT
+// ============================================================================
+
+/// Convert a document to a Final Draft file
+pub fn export_fdx(text: &str) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\" ?>\n");
+    xml.push_str("<FinalDraft DocumentType=\"Script\" Template=\"No\" Version=\"5\">\n");
+    xml.push_str("  <Content>\n");
+    for (kind, text) in paragraphs(&parser::parse_document(text)) {
+        // Text content and attribute values need the same escaping as HTML
+        xml.push_str(&format!(
+            "    <Paragraph Type=\"{}\">\n      <Text>{}</Text>\n    </Paragraph>\n",
+            kind,
+            escape_html(&text)
+        ));
+    }
+    xml.push_str("  </Content>\n");
+    xml.push_str("</FinalDraft>\n");
+    xml
+}
+
+/// Each non-blank line as a (paragraph type, text) pair
+///
+/// A speech runs from a cue to the next blank line or structural tag, as
+/// in the parser.
+fn paragraphs(parsed: &[ParsedLine]) -> Vec<(&'static str, String)> {
+    let mut paragraphs = Vec::new();
+    let mut in_speech = false;
+
+    for (i, line) in parsed.iter().enumerate() {
+        let trimmed = line.text.trim();
+        if trimmed.is_empty() {
+            in_speech = false;
+            continue;
+        }
+
+        let next = parsed.get(i + 1).map(|line| line.text.as_str());
+        let paragraph = match &line.tag {
+            Some(TagType::Act(title)) => ("New Act", format!("ACT {}", title)),
+            Some(TagType::Chapter(_)) => ("General", trimmed.to_string()),
+            Some(TagType::Scene(heading)) => ("Scene Heading", heading.to_uppercase()),
+            Some(TagType::Character(cue)) if parser::cue_name(line, next).is_some() => {
+                ("Character", cue.trim().to_uppercase())
+            }
+            Some(TagType::Action(_)) if in_speech => ("Parenthetical", trimmed.to_string()),
+            Some(TagType::Unknown(_)) => ("Action", trimmed.to_string()),
+            _ if in_speech => ("Dialogue", trimmed.to_string()),
+            _ if is_transition(trimmed) => ("Transition", trimmed.to_string()),
+            _ => ("Action", trimmed.to_string()),
+        };
+
+        in_speech = matches!(paragraph.0, "Character" | "Parenthetical" | "Dialogue");
+        paragraphs.push(paragraph);
+    }
+
+    paragraphs
+}
+
+/// An all-capitals line ending in "TO:" ("CUT TO:", "DISSOLVE TO:")
+fn is_transition(line: &str) -> bool {
+    line.ends_with("TO:") && !line.chars().any(char::is_lowercase)
+}
+
+// ============================================================================
+// IMPORT
+// ============================================================================
+
+/// Convert a Final Draft file's XML into BookScript text
+///
+/// Err if the text isn't XML, or isn't a Final Draft document.
+pub fn import_fdx(xml: &str) -> Result<String> {
+    let document =
+        roxmltree::Document::parse(xml).map_err(|e| anyhow!("Not a valid FDX file: {}", e))?;
+    let root = document.root_element();
+    if !root.has_tag_name("FinalDraft") {
+        bail!(
+            "Not a Final Draft file (the root element is <{}>)",
+            root.tag_name().name()
+        );
+    }
+    let Some(content) = root.children().find(|node| node.has_tag_name("Content")) else {
+        bail!("The Final Draft file has no script content");
+    };
+
+    let mut out = Output::default();
+    for paragraph in content
+        .children()
+        .filter(|node| node.has_tag_name("Paragraph"))
+    {
+        let kind = paragraph.attribute("Type").unwrap_or("Action");
+        // A paragraph's text is split into runs wherever its style changes
+        let text: String = paragraph
+            .children()
+            .filter(|node| node.has_tag_name("Text"))
+            .filter_map(|node| node.text())
+            .collect();
+        // Line breaks inside a paragraph would split it into several lines
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if text.is_empty() {
+            continue;
+        }
+
+        match kind {
+            "Scene Heading" => out.block(&format!("[SCENE: {}]", text)),
+            "New Act" => {
+                let title = text
+                    .strip_prefix("ACT ")
+                    .or_else(|| text.strip_prefix("Act "))
+                    .unwrap_or(&text);
+                out.block(&format!("[ACT: {}]", title));
+            }
+            "End of Act" => {}
+            "Character" => {
+                let cue = text.to_uppercase();
+                if parser::is_character_cue(&cue) {
+                    out.block(&cue);
+                } else {
+                    out.block(&format!("[CHARACTER: {}]", text));
+                }
+                out.in_speech = true;
+            }
+            "Dialogue" | "Parenthetical" if out.in_speech => {
+                let line = if kind == "Parenthetical" && !text.starts_with('(') {
+                    format!("({})", text)
+                } else {
+                    text
+                };
+                out.speech(&line);
+            }
+            _ => out.block(&text),
+        }
+    }
+
+    Ok(out.text)
+}
+
+/// Collects BookScript lines, with a blank line between paragraphs except
+/// inside a speech
+#[derive(Default)]
+struct Output {
+    text: String,
+    /// The last line written was a cue or part of its speech
+    in_speech: bool,
+}
+
+impl Output {
+    /// A line that starts something new: a blank line goes above it
+    fn block(&mut self, line: &str) {
+        if !self.text.is_empty() {
+            self.text.push('\n');
+        }
+        self.text.push_str(line);
+        self.text.push('\n');
+        self.in_speech = false;
+    }
+
+    /// A line of the current speech, right under the one before it
+    fn speech(&mut self, line: &str) {
+        self.text.push_str(line);
+        self.text.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &str = "\
+[ACT: I]
+
+[CHAPTER: Morning]
+
+[SCENE: int. kitchen - day]
+
+Ann pours coffee.
+
+ANN
+(quietly)
+Is it ready?
+
+BOB (V.O.)
+Not yet.
+
+CUT TO:
+";
+
+    #[test]
+    fn each_line_gets_its_paragraph_type() {
+        let paragraphs = paragraphs(&parser::parse_document(SCRIPT));
+        let expected = [
+            ("New Act", "ACT I"),
+            ("General", "[CHAPTER: Morning]"),
+            ("Scene Heading", "INT. KITCHEN - DAY"),
+            ("Action", "Ann pours coffee."),
+            ("Character", "ANN"),
+            ("Parenthetical", "(quietly)"),
+            ("Dialogue", "Is it ready?"),
+            ("Character", "BOB (V.O.)"),
+            ("Dialogue", "Not yet."),
+            ("Transition", "CUT TO:"),
+        ];
+        let paragraphs: Vec<(&str, &str)> = paragraphs
+            .iter()
+            .map(|(kind, text)| (*kind, text.as_str()))
+            .collect();
+        assert_eq!(paragraphs, expected);
+    }
+
+    #[test]
+    fn speeches_end_at_a_blank_line() {
+        let paragraphs = paragraphs(&parser::parse_document(
+            "ANN\nHello.\n\n(The door opens.)\nCut to: the hall\n[SCENE: \n",
+        ));
+        let kinds: Vec<&str> = paragraphs.iter().map(|(kind, _)| *kind).collect();
+        // A parenthetical outside a speech, a lower-case "to:", and a
+        // malformed tag are all action
+        assert_eq!(
+            kinds,
+            ["Character", "Dialogue", "Action", "Action", "Action"]
+        );
+    }
+
+    #[test]
+    fn export_writes_escaped_xml() {
+        let xml = export_fdx("[SCENE: Tom & Jerry's <house>]\n\nShe says \"hi\" & leaves.\n");
+        assert_eq!(
+            xml,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\" ?>\n\
+             <FinalDraft DocumentType=\"Script\" Template=\"No\" Version=\"5\">\n\
+             \x20 <Content>\n\
+             \x20   <Paragraph Type=\"Scene Heading\">\n\
+             \x20     <Text>TOM &amp; JERRY&#39;S &lt;HOUSE&gt;</Text>\n\
+             \x20   </Paragraph>\n\
+             \x20   <Paragraph Type=\"Action\">\n\
+             \x20     <Text>She says &quot;hi&quot; &amp; leaves.</Text>\n\
+             \x20   </Paragraph>\n\
+             \x20 </Content>\n\
+             </FinalDraft>\n"
+        );
+        // Final Draft (and any XML reader) can read it back
+        assert!(roxmltree::Document::parse(&xml).is_ok());
+    }
+
+    #[test]
+    fn import_reads_each_paragraph_type() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<FinalDraft DocumentType="Script" Version="5">
+  <Content>
+    <Paragraph Type="New Act"><Text>ACT II</Text></Paragraph>
+    <Paragraph Type="Scene Heading"><Text>INT. HALL</Text><Text> - NIGHT</Text></Paragraph>
+    <Paragraph Type="Action"><Text>Rain   falls.</Text></Paragraph>
+    <Paragraph Type="Character"><Text>Ann</Text></Paragraph>
+    <Paragraph Type="Parenthetical"><Text>whispering</Text></Paragraph>
+    <Paragraph Type="Dialogue"><Text>Who &amp; why?</Text></Paragraph>
+    <Paragraph Type="End of Act"><Text>END OF ACT II</Text></Paragraph>
+    <Paragraph Type="Shot"><Text>CLOSE ON the door.</Text></Paragraph>
+    <Paragraph Type="Dialogue"><Text>Stray line.</Text></Paragraph>
+    <Paragraph Type="Action"><Text></Text></Paragraph>
+  </Content>
+</FinalDraft>"#;
+        assert_eq!(
+            import_fdx(xml).unwrap(),
+            "[ACT: II]\n\n[SCENE: INT. HALL - NIGHT]\n\nRain falls.\n\nANN\n(whispering)\nWho & why?\n\nCLOSE ON the door.\n\nStray line.\n"
+        );
+    }
+
+    #[test]
+    fn a_script_survives_export_and_import() {
+        let imported = import_fdx(&export_fdx(SCRIPT)).unwrap();
+        assert_eq!(
+            imported,
+            "[ACT: I]\n\n[CHAPTER: Morning]\n\n[SCENE: INT. KITCHEN - DAY]\n\nAnn pours coffee.\n\nANN\n(quietly)\nIs it ready?\n\nBOB (V.O.)\nNot yet.\n\nCUT TO:\n"
+        );
+    }
+
+    #[test]
+    fn other_files_are_refused() {
+        assert!(import_fdx("not xml").is_err());
+        assert!(import_fdx("<html><body/></html>").is_err());
+        assert!(import_fdx("<FinalDraft><TitlePage/></FinalDraft>").is_err());
+    }
+}
@@ -0,0 +1,500 @@
+//! FILE: src/file_io.rs
+//!
+//! Document loading and saving on a background thread.
+//!
+//! Reading a 10 MB manuscript from a network drive can take seconds, and
+//! update() runs on the GUI thread - doing the I/O there freezes the window.
+//! Instead, App hands a FileRequest to the FileWorker, which runs the
+//! Storage call on its own thread and sends a FileResult back over a
+//! channel. App drains the channel at the top of every frame.
+//!
+//! A password-protected document (encryption.rs) comes back from Load as
+//! Locked; App asks for the password and sends Unlock. Decrypting (and
+//! encrypting on save) happens here too - the key derivation is slow on
+//! purpose, far too slow for the GUI thread.
+//!
+//! Only one operation runs at a time. IoState tracks which one, so the
+//! status bar can say "Loading…" and the menus can refuse to start a second
+//! operation on top of the first. None of this touches egui.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - std::sync::mpsc channels: one thread sends, another receives
+//! - try_recv(): checking a channel without blocking
+//! - Enums as a small state machine
+
+use crate::encoding::{self, DecodedText, LineEnding, TextEncoding};
+use crate::encryption;
+use crate::storage::{self, BackupSettings, SaveCheck, Storage};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::SystemTime;
+
+// ============================================================================
+// MESSAGES
+// ============================================================================
+
+/// Work sent from the GUI thread to the worker
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileRequest {
+    /// Read a document
+    Load(PathBuf),
+    /// Read a password-protected document
+    Unlock { path: PathBuf, password: String },
+    /// Write a document, backing up the version it replaces as `backups`
+    /// says (see storage::save_text_file)
+    ///
+    /// `content` has `\n` line endings; it is written with `line_ending`,
+    /// in `encoding` (or UTF-8 if that encoding can't hold it), and then
+    /// encrypted if there's a `password`.
+    /// With `snapshot_keep` set, a successful save is also recorded in the
+    /// document's version history, which is pruned to that many entries.
+    ///
+    /// `expected_modified` is the file's modification time when it was last
+    /// loaded or saved; if the file has changed since, nothing is written
+    /// and the result is a Conflict (None skips the check).
+    Save {
+        path: PathBuf,
+        content: String,
+        line_ending: LineEnding,
+        encoding: TextEncoding,
+        password: Option<String>,
+        expected_modified: Option<SystemTime>,
+        backups: BackupSettings,
+        snapshot_keep: Option<usize>,
+    },
+}
+
+impl FileRequest {
+    /// The file this request reads or writes
+    pub fn path(&self) -> &Path {
+        match self {
+            FileRequest::Load(path) => path,
+            FileRequest::Unlock { path, .. } => path,
+            FileRequest::Save { path, .. } => path,
+        }
+    }
+}
+
+/// The outcome of a FileRequest, sent back to the GUI thread
+#[derive(Debug)]
+pub enum FileResult {
+    /// The text of the file (with its encoding and line endings), or why
+    /// it couldn't be read
+    ///
+    /// `modified` is the file's modification time as of the read, for the
+    /// next save's external change check. `password` is the one it was
+    /// unlocked with (None if it isn't password-protected).
+    Loaded {
+        path: PathBuf,
+        modified: Option<SystemTime>,
+        password: Option<String>,
+        result: Result<DecodedText>,
+    },
+    /// The file is password-protected: it needs an Unlock, or another one if
+    /// `wrong_password` (the one given didn't open it)
+    Locked { path: PathBuf, wrong_password: bool },
+    /// Whether the save worked
+    ///
+    /// `content_hash` identifies the text that was written, so the GUI can
+    /// tell whether the user kept typing while the save was in flight.
+    /// `modified` is the file's new modification time. `encoding` is the
+    /// one it was written in, and `unencodable` the character that made it
+    /// fall back to UTF-8 if the requested one couldn't be used.
+    Saved {
+        path: PathBuf,
+        content_hash: u64,
+        modified: Option<SystemTime>,
+        encoding: TextEncoding,
+        unencodable: Option<char>,
+        result: Result<()>,
+    },
+    /// Nothing was written: the file changed on disk since it was last
+    /// loaded or saved, and saving would have overwritten that
+    Conflict { path: PathBuf },
+}
+
+// ============================================================================
+// STATE MACHINE
+// ============================================================================
+
+/// What the worker is doing right now
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum IoState {
+    /// Nothing in flight; new requests are accepted
+    #[default]
+    Idle,
+    /// Reading this file
+    Loading(PathBuf),
+    /// Writing this file
+    Saving(PathBuf),
+}
+
+impl IoState {
+    /// Is an operation in flight?
+    pub fn is_busy(&self) -> bool {
+        !matches!(self, IoState::Idle)
+    }
+
+    /// Move to Loading/Saving for `request`
+    ///
+    /// Returns false (and changes nothing) if something is already in
+    /// flight - overlapping operations could race on the same file.
+    pub fn start(&mut self, request: &FileRequest) -> bool {
+        if self.is_busy() {
+            return false;
+        }
+        let path = request.path().to_path_buf();
+        *self = match request {
+            FileRequest::Load(_) | FileRequest::Unlock { .. } => IoState::Loading(path),
+            FileRequest::Save { .. } => IoState::Saving(path),
+        };
+        true
+    }
+
+    /// Back to Idle once a result has arrived
+    pub fn finish(&mut self) {
+        *self = IoState::Idle;
+    }
+
+    /// Status bar text while busy: "Loading… chapter1.bks"
+    pub fn status_text(&self) -> Option<String> {
+        let (verb, path) = match self {
+            IoState::Idle => return None,
+            IoState::Loading(path) => ("Loading…", path),
+            IoState::Saving(path) => ("Saving…", path),
+        };
+        Some(format!("{} {}", verb, path.display()))
+    }
+}
+
+// ============================================================================
+// WORKER
+// ============================================================================
+
+/// Owns the worker thread and both ends of the conversation with it
+pub struct FileWorker {
+    requests: Sender<FileRequest>,
+    results: Receiver<FileResult>,
+    state: IoState,
+}
+
+impl FileWorker {
+    /// Start the worker thread, reading and writing through `storage`
+    ///
+    /// The thread lives until the FileWorker is dropped: dropping the
+    /// request Sender makes its recv() fail, which ends its loop.
+    pub fn spawn(storage: Arc<dyn Storage>) -> Self {
+        let (request_tx, request_rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+
+        thread::spawn(move || worker_loop(storage, request_rx, result_tx));
+
+        Self {
+            requests: request_tx,
+            results: result_rx,
+            state: IoState::Idle,
+        }
+    }
+
+    /// Current state, for the status bar and for enabling menu items
+    pub fn state(&self) -> &IoState {
+        &self.state
+    }
+
+    /// Is an operation in flight?
+    pub fn is_busy(&self) -> bool {
+        self.state.is_busy()
+    }
+
+    /// Hand a request to the worker
+    ///
+    /// Returns false if another operation is still in flight (the request
+    /// is dropped) or the worker thread has gone away.
+    pub fn submit(&mut self, request: FileRequest) -> bool {
+        let mut next = self.state.clone();
+        if !next.start(&request) {
+            return false;
+        }
+        if self.requests.send(request).is_err() {
+            return false;
+        }
+        self.state = next;
+        true
+    }
+
+    /// Take the finished result, if there is one, without waiting
+    pub fn poll(&mut self) -> Option<FileResult> {
+        match self.results.try_recv() {
+            Ok(result) => {
+                self.state.finish();
+                Some(result)
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                // The worker panicked mid-operation; don't stay "busy" forever
+                self.state.finish();
+                None
+            }
+        }
+    }
+}
+
+/// The worker thread: perform each request and report back
+fn worker_loop(
+    storage: Arc<dyn Storage>,
+    requests: Receiver<FileRequest>,
+    results: Sender<FileResult>,
+) {
+    // recv() blocks until a request arrives, and fails once the GUI side
+    // has been dropped - that's the signal to exit
+    while let Ok(request) = requests.recv() {
+        let result = perform(storage.as_ref(), request);
+        if results.send(result).is_err() {
+            break;
+        }
+    }
+}
+
+/// Run one request (on whichever thread calls it)
+///
+/// Version-history snapshots are always written to the data directory on
+/// disk, whatever `storage` is; leave `snapshot_keep` unset to skip them.
+fn perform(storage: &dyn Storage, request: FileRequest) -> FileResult {
+    match request {
+        FileRequest::Load(path) => load(storage, path, None),
+        FileRequest::Unlock { path, password } => load(storage, path, Some(password)),
+        FileRequest::Save {
+            path,
+            content,
+            line_ending,
+            encoding,
+            password,
+            expected_modified,
+            backups,
+            snapshot_keep,
+        } => {
+            let current = storage.modified(&path);
+            if storage::check_for_external_change(expected_modified, current) == SaveCheck::Conflict
+            {
+                return FileResult::Conflict { path };
+            }
+
+            let on_disk = line_ending.apply(&content);
+            let (bytes, encoding, unencodable) = match encoding.encode(&on_disk) {
+                Ok(bytes) => (bytes, encoding, None),
+                Err(c) => (
+                    std::borrow::Cow::Borrowed(on_disk.as_bytes()),
+                    TextEncoding::Utf8,
+                    Some(c),
+                ),
+            };
+            let result = match &password {
+                Some(password) => encryption::encrypt(&bytes, password)
+                    .and_then(|encrypted| storage.write(&path, &encrypted, backups)),
+                None => storage.write(&path, &bytes, backups),
+            };
+
+            // A failed snapshot doesn't make the save itself a failure
+            if let (Ok(()), Some(keep)) = (&result, snapshot_keep) {
+                if let Err(e) = storage::take_snapshot(Some(&path), &content, keep) {
+                    eprintln!("Snapshot failed: {}", e);
+                }
+            }
+
+            FileResult::Saved {
+                modified: storage.modified(&path),
+                path,
+                content_hash: storage::content_hash(&content),
+                encoding,
+                unencodable,
+                result,
+            }
+        }
+    }
+}
+
+/// Read `path`, decrypting it with `password` if it's password-protected
+fn load(storage: &dyn Storage, path: PathBuf, password: Option<String>) -> FileResult {
+    // The time is taken BEFORE reading: if the file changes during the
+    // read, the next save sees a newer time and stops, rather than the
+    // change slipping through unnoticed
+    let modified = storage.modified(&path);
+    let bytes = match storage.read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return FileResult::Loaded {
+                path,
+                modified,
+                password: None,
+                result: Err(e),
+            }
+        }
+    };
+    if !encryption::is_encrypted(&bytes) {
+        return FileResult::Loaded {
+            path,
+            modified,
+            password: None,
+            result: Ok(encoding::decode(&bytes)),
+        };
+    }
+
+    let Some(password) = password else {
+        return FileResult::Locked {
+            path,
+            wrong_password: false,
+        };
+    };
+    match encryption::decrypt(&bytes, &password) {
+        Ok(Some(plain)) => FileResult::Loaded {
+            path,
+            modified,
+            password: Some(password),
+            result: Ok(encoding::decode(&plain)),
+        },
+        Ok(None) => FileResult::Locked {
+            path,
+            wrong_password: true,
+        },
+        Err(e) => FileResult::Loaded {
+            path,
+            modified,
+            password: None,
+            result: Err(e),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStorage;
+    use std::time::Duration;
+
+    fn save_request(path: &str, content: &str) -> FileRequest {
+        FileRequest::Save {
+            path: PathBuf::from(path),
+            content: content.to_string(),
+            line_ending: LineEnding::Lf,
+            encoding: TextEncoding::Utf8,
+            password: None,
+            expected_modified: None,
+            backups: BackupSettings::OFF,
+            snapshot_keep: None,
+        }
+    }
+
+    /// Wait (briefly) for the worker's next result
+    fn wait(worker: &mut FileWorker) -> FileResult {
+        for _ in 0..500 {
+            if let Some(result) = worker.poll() {
+                return result;
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+        panic!("the worker never answered");
+    }
+
+    #[test]
+    fn the_state_follows_the_request_and_refuses_overlaps() {
+        let mut state = IoState::default();
+        assert!(!state.is_busy());
+        assert_eq!(state.status_text(), None);
+
+        assert!(state.start(&FileRequest::Load(PathBuf::from("a.bks"))));
+        assert_eq!(state, IoState::Loading(PathBuf::from("a.bks")));
+        assert_eq!(state.status_text().as_deref(), Some("Loading… a.bks"));
+
+        // A second operation can't start until the first has finished
+        assert!(!state.start(&save_request("a.bks", "text")));
+        assert_eq!(state, IoState::Loading(PathBuf::from("a.bks")));
+
+        state.finish();
+        assert!(state.start(&save_request("b.bks", "text")));
+        assert_eq!(state.status_text().as_deref(), Some("Saving… b.bks"));
+        state.finish();
+
+        let unlock = FileRequest::Unlock {
+            path: PathBuf::from("c.bksx"),
+            password: String::from("secret"),
+        };
+        assert_eq!(unlock.path(), Path::new("c.bksx"));
+        assert!(state.start(&unlock));
+        assert_eq!(state, IoState::Loading(PathBuf::from("c.bksx")));
+    }
+
+    #[test]
+    fn the_worker_saves_then_loads_through_its_storage() {
+        let storage = Arc::new(InMemoryStorage::new());
+        let mut worker = FileWorker::spawn(storage.clone());
+
+        assert!(worker.submit(save_request("/doc.bks", "Hello")));
+        assert!(worker.is_busy());
+        assert!(!worker.submit(FileRequest::Load(PathBuf::from("/doc.bks"))));
+        match wait(&mut worker) {
+            FileResult::Saved {
+                path,
+                content_hash,
+                result,
+                ..
+            } => {
+                assert_eq!(path, PathBuf::from("/doc.bks"));
+                assert_eq!(content_hash, storage::content_hash("Hello"));
+                assert!(result.is_ok());
+            }
+            other => panic!("expected Saved, got {:?}", other),
+        }
+        assert!(!worker.is_busy());
+        assert_eq!(
+            storage.text(Path::new("/doc.bks")).as_deref(),
+            Some("Hello")
+        );
+
+        assert!(worker.submit(FileRequest::Load(PathBuf::from("/doc.bks"))));
+        match wait(&mut worker) {
+            FileResult::Loaded { result, .. } => assert_eq!(result.unwrap().text, "Hello"),
+            other => panic!("expected Loaded, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn errors_come_back_as_results() {
+        let mut worker = FileWorker::spawn(Arc::new(InMemoryStorage::new()));
+        assert!(worker.submit(FileRequest::Load(PathBuf::from("/missing.bks"))));
+        match wait(&mut worker) {
+            FileResult::Loaded { result, .. } => {
+                let message = result.unwrap_err().to_string();
+                assert!(message.contains("/missing.bks"), "{}", message);
+            }
+            other => panic!("expected Loaded, got {:?}", other),
+        }
+        assert_eq!(worker.state(), &IoState::Idle);
+    }
+
+    #[test]
+    fn a_save_over_a_file_changed_elsewhere_is_a_conflict() {
+        let storage = InMemoryStorage::new();
+        storage.insert("/doc.bks", "theirs");
+        let loaded_at = storage.modified(Path::new("/doc.bks"));
+        storage.insert("/doc.bks", "changed again");
+
+        let mut request = save_request("/doc.bks", "mine");
+        if let FileRequest::Save {
+            expected_modified, ..
+        } = &mut request
+        {
+            *expected_modified = loaded_at;
+        }
+        assert!(matches!(
+            perform(&storage, request),
+            FileResult::Conflict { .. }
+        ));
+        assert_eq!(
+            storage.text(Path::new("/doc.bks")).as_deref(),
+            Some("changed again")
+        );
+    }
+}
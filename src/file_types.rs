@@ -0,0 +1,341 @@
+//! FILE: src/file_types.rs
+//!
+//! Tools → Register file types: make this copy of the app the one that
+//! opens .bks and .scr files, so they open from the file manager with a
+//! double-click or Open With.
+//!
+//! Each system is told to start the app with the file's path as its
+//! argument, as `bookscript FILE` would. A copy already running is handed
+//! the file (see instance.rs), so a double-click opens it in a new tab
+//! there instead of starting a second window.
+//!
+//! WHAT EACH SYSTEM GETS:
+//! - Linux and the rest: a `text/x-bookscript` MIME type for the
+//!   extensions (`<data home>/mime/packages/bookscript.xml`), a menu entry
+//!   that opens it (`<data home>/applications/bookscript.desktop`), and
+//!   `xdg-mime` told to make that entry the default. `<data home>` is
+//!   usually ~/.local/share.
+//! - Windows: a `BookScript.Document` file type under
+//!   HKEY_CURRENT_USER\Software\Classes whose Open command starts the app,
+//!   with .bks pointing at it and listing it under Open With. A program the
+//!   user has picked for .bks in Explorer stays their choice. .scr isn't
+//!   registered on Windows: it's the screen saver extension there, and
+//!   taking it over would stop screen savers from running.
+//! - macOS: there, a double-clicked file is sent to an app bundle as an
+//!   Apple Event, not as an argument, and eframe doesn't pass those on. So
+//!   a small AppleScript app, ~/Applications/BookScript Writer.app, is made
+//!   to receive them and start this one with each file's path. Its
+//!   Info.plist claims the extensions, and Launch Services is told about
+//!   it.
+//!
+//! Everything is the user's own, so no administrator rights are needed.
+//! Registering again (after moving the app, say) points everything at the
+//! new location.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - cfg!() picking a platform inside ordinary code, as in print.rs
+//! - Quoting a path for a shell, an AppleScript string and a .desktop file
+
+use crate::storage;
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// The extensions registered (.scr not on Windows; see module docs)
+const EXTENSIONS: [&str; 2] = ["bks", "scr"];
+
+/// What file managers call the files
+const TYPE_NAME: &str = "BookScript document";
+
+/// The files' MIME type on Linux
+const MIME_TYPE: &str = "text/x-bookscript";
+
+/// Name of the Linux menu entry's file
+const DESKTOP_FILE: &str = "bookscript.desktop";
+
+/// The file type's name in the Windows registry
+const PROG_ID: &str = "BookScript.Document";
+
+/// The macOS opener app, in ~/Applications, and its bundle identifier
+const OPENER_NAME: &str = "BookScript Writer.app";
+const OPENER_ID: &str = "com.BookScript.BookScript.Opener";
+
+/// Register the file types for the running executable (see module docs)
+///
+/// Returns a line for the status bar saying what was registered.
+pub fn register() -> Result<String> {
+    let exe = std::env::current_exe().context("Can't tell where the app is")?;
+    let extensions: &[&str] = if cfg!(windows) {
+        &EXTENSIONS[..1]
+    } else {
+        &EXTENSIONS
+    };
+
+    if cfg!(windows) {
+        register_windows(&exe)?;
+    } else if cfg!(target_os = "macos") {
+        register_macos(&exe)?;
+    } else {
+        register_linux(&exe, extensions)?;
+    }
+
+    let names: Vec<String> = extensions.iter().map(|e| format!(".{}", e)).collect();
+    Ok(format!(
+        "{} files now open with {}",
+        names.join(" and "),
+        exe.display()
+    ))
+}
+
+/// The MIME type, the menu entry, and the entry made the default
+fn register_linux(exe: &Path, extensions: &[&str]) -> Result<()> {
+    let dirs = directories::BaseDirs::new().context("Could not determine the home folder")?;
+    let data = dirs.data_dir();
+    let exe = exe
+        .to_str()
+        .context("The app's path isn't valid UTF-8, which a .desktop file needs")?;
+
+    let globs: String = extensions
+        .iter()
+        .map(|e| format!("    <glob pattern=\"*.{}\"/>\n", e))
+        .collect();
+    let mime = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <mime-info xmlns=\"http://www.freedesktop.org/standards/shared-mime-info\">\n\
+         \x20 <mime-type type=\"{}\">\n\
+         \x20   <sub-class-of type=\"text/plain\"/>\n\
+         \x20   <comment>{}</comment>\n\
+         {}\
+         \x20 </mime-type>\n\
+         </mime-info>\n",
+        MIME_TYPE, TYPE_NAME, globs
+    );
+    let desktop = format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=BookScript Writer\n\
+         Comment=Write books and scripts in BookScript\n\
+         Exec={} %f\n\
+         Terminal=false\n\
+         Categories=Office;TextEditor;\n\
+         MimeType={};\n",
+        desktop_exec(exe),
+        MIME_TYPE
+    );
+
+    let mime_dir = data.join("mime");
+    let applications = data.join("applications");
+    storage::save_text_file(
+        mime_dir.join("packages").join("bookscript.xml"),
+        &mime,
+        storage::BackupSettings::OFF,
+    )?;
+    storage::save_text_file(
+        applications.join(DESKTOP_FILE),
+        &desktop,
+        storage::BackupSettings::OFF,
+    )?;
+
+    run(Command::new("update-mime-database").arg(&mime_dir))?;
+    // Only a cache: menus find the entry without it
+    let _ = run(Command::new("update-desktop-database").arg(&applications));
+    run(Command::new("xdg-mime").args(["default", DESKTOP_FILE, MIME_TYPE]))
+        .context("The file type is registered, but the app couldn't be made its default")
+}
+
+/// The file type and its Open command, and .bks pointing at it
+fn register_windows(exe: &Path) -> Result<()> {
+    let classes = r"HKCU\Software\Classes";
+    let prog = format!(r"{}\{}", classes, PROG_ID);
+    let open = format!("\"{}\" \"%1\"", exe.display());
+    for extension in &EXTENSIONS[..1] {
+        let key = format!(r"{}\.{}", classes, extension);
+        reg_add(&key, None, Some(PROG_ID))?;
+        reg_add(&format!(r"{}\OpenWithProgids", key), Some(PROG_ID), None)?;
+    }
+    reg_add(&prog, None, Some(TYPE_NAME))?;
+    reg_add(&format!(r"{}\shell\open\command", prog), None, Some(&open))
+}
+
+/// Set a registry value with `reg add`: the key's default value when
+/// `name` is None, and an empty REG_NONE value when `data` is
+fn reg_add(key: &str, name: Option<&str>, data: Option<&str>) -> Result<()> {
+    let mut reg = Command::new("reg");
+    reg.args(["add", key]);
+    match name {
+        Some(name) => reg.args(["/v", name]),
+        None => reg.arg("/ve"),
+    };
+    match data {
+        Some(data) => reg.args(["/d", data]),
+        None => reg.args(["/t", "REG_NONE"]),
+    };
+    run(reg.arg("/f"))
+}
+
+/// The opener app: made with osacompile, given the document types in its
+/// Info.plist, and registered with Launch Services
+fn register_macos(exe: &Path) -> Result<()> {
+    let dirs = directories::BaseDirs::new().context("Could not determine the home folder")?;
+    let app = dirs.home_dir().join("Applications").join(OPENER_NAME);
+    let plist = app.join("Contents").join("Info.plist");
+    if app.exists() {
+        // Only ever replace an opener made here before
+        if !fs::read_to_string(&plist).is_ok_and(|p| p.contains(OPENER_ID)) {
+            bail!(
+                "{} already exists and wasn't made by BookScript; move it away first",
+                app.display()
+            );
+        }
+        fs::remove_dir_all(&app).with_context(|| format!("Failed to replace {}", app.display()))?;
+    }
+    fs::create_dir_all(dirs.home_dir().join("Applications"))?;
+
+    // Started on its own it starts the app; given files, it starts the app
+    // with each one. The trailing & keeps `do shell script` from waiting
+    // for the app to quit.
+    let exe = exe.to_str().context("The app's path isn't valid UTF-8")?;
+    let command = applescript_string(&shell_quote(exe));
+    let script = format!(
+        "on run\n\
+         do shell script {0} & \" > /dev/null 2>&1 &\"\n\
+         end run\n\
+         on open theFiles\n\
+         repeat with theFile in theFiles\n\
+         do shell script {0} & \" \" & quoted form of POSIX path of theFile & \" > /dev/null 2>&1 &\"\n\
+         end repeat\n\
+         end open",
+        command
+    );
+    let mut osacompile = Command::new("osacompile");
+    osacompile.arg("-o").arg(&app);
+    for line in script.lines() {
+        osacompile.args(["-e", line]);
+    }
+    run(&mut osacompile)?;
+
+    let types = serde_json::json!([{
+        "CFBundleTypeName": TYPE_NAME,
+        "CFBundleTypeRole": "Editor",
+        "LSHandlerRank": "Owner",
+        "CFBundleTypeExtensions": EXTENSIONS,
+    }]);
+    run(Command::new("plutil")
+        .args(["-replace", "CFBundleIdentifier", "-string", OPENER_ID])
+        .arg(&plist))?;
+    run(Command::new("plutil")
+        .args([
+            "-replace",
+            "CFBundleDocumentTypes",
+            "-json",
+            &types.to_string(),
+        ])
+        .arg(&plist))?;
+    run(Command::new(
+        "/System/Library/Frameworks/CoreServices.framework/Frameworks/\
+         LaunchServices.framework/Support/lsregister",
+    )
+    .arg("-f")
+    .arg(&app))
+}
+
+/// Run a program to completion, turning a failure into an error with what
+/// it printed
+fn run(command: &mut Command) -> Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .output()
+        .map_err(|e| anyhow!("Couldn't run {}: {}", program, e))?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        match message.trim() {
+            "" => bail!("{} failed ({})", program, output.status),
+            message => bail!("{}: {}", program, message),
+        }
+    }
+    Ok(())
+}
+
+/// A path as the program in a .desktop file's Exec line: double-quoted,
+/// with the characters the spec reserves escaped, then with backslashes
+/// and % doubled as every value and field code there needs
+fn desktop_exec(path: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in path.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted.replace('\\', "\\\\").replace('%', "%%")
+}
+
+/// `text` as one word for sh
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// `text` as an AppleScript string literal
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', r"\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn desktop_exec_quotes_and_escapes() {
+        assert_eq!(
+            desktop_exec("/opt/My Apps/bookscript"),
+            r#""/opt/My Apps/bookscript""#
+        );
+        // Reserved characters get a backslash, then every backslash is
+        // doubled for the value
+        assert_eq!(desktop_exec("/a$b"), r#""/a\\$b""#);
+        assert_eq!(desktop_exec(r#"/say "hi""#), r#""/say \\"hi\\"""#);
+        assert_eq!(desktop_exec(r"/back\slash"), r#""/back\\\\slash""#);
+        assert_eq!(desktop_exec("/100%/app"), r#""/100%%/app""#);
+    }
+
+    #[test]
+    fn shell_quote_makes_one_word() {
+        assert_eq!(
+            shell_quote("/Applications/My App"),
+            "'/Applications/My App'"
+        );
+        assert_eq!(shell_quote("/it's"), r"'/it'\''s'");
+        assert_eq!(shell_quote("$HOME `x`"), "'$HOME `x`'");
+    }
+
+    #[test]
+    fn applescript_strings_escape_quotes_and_backslashes() {
+        assert_eq!(applescript_string("plain"), r#""plain""#);
+        assert_eq!(
+            applescript_string(r#"say "hi" \ bye"#),
+            r#""say \"hi\" \\ bye""#
+        );
+        // A shell-quoted path goes in whole
+        assert_eq!(
+            applescript_string(&shell_quote("/it's")),
+            r#""'/it'\\''s'""#
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_failed_command_says_why() {
+        assert!(run(&mut Command::new("true")).is_ok());
+        let error =
+            run(Command::new("sh").args(["-c", "echo 'no such type' >&2; exit 3"])).unwrap_err();
+        assert_eq!(error.to_string(), "sh: no such type");
+        let error = run(Command::new("sh").args(["-c", "exit 2"])).unwrap_err();
+        assert_eq!(error.to_string(), "sh failed (exit status: 2)");
+        let error = run(&mut Command::new("bookscript-no-such-program")).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Couldn't run bookscript-no-such-program: "));
+    }
+}
@@ -0,0 +1,53 @@
+//! FILE: src/fonts.rs
+//!
+//! A font file of the user's own for the editor text (View → Editor Font).
+//!
+//! egui draws with the fonts in its FontDefinitions: a few built in, each
+//! listed under a family (Proportional, Monospace). A custom font is added
+//! to those under a family of its own, CUSTOM_FONT, with the built-in fonts
+//! behind it for any character it doesn't have. Menus and panels keep
+//! egui's fonts; only the editor asks for the custom family.
+//!
+//! egui panics when asked to draw with a font file it can't read, or with a
+//! family it hasn't been given - so a file is checked here before it is
+//! handed over, and App only asks for the family once egui has it.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Using a trait's methods (ab_glyph::Font) on a type that implements it
+
+use ab_glyph::Font;
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::Path;
+
+/// Name of the custom font, as font data and as a family
+pub const CUSTOM_FONT: &str = "editor-custom";
+
+/// The family the editor draws with when the font is EditorFont::Custom
+pub fn custom_family() -> egui::FontFamily {
+    egui::FontFamily::Name(CUSTOM_FONT.into())
+}
+
+/// egui's own fonts plus the font file at `path`, ready for ctx.set_fonts
+///
+/// Err if the file can't be read or isn't a TrueType/OpenType font egui
+/// can draw with.
+pub fn definitions_with(path: &Path) -> Result<egui::FontDefinitions> {
+    let bytes = std::fs::read(path).context(format!("Failed to read font: {}", path.display()))?;
+    let font = ab_glyph::FontRef::try_from_slice(&bytes)
+        .map_err(|_| anyhow!("Not a TrueType or OpenType font: {}", path.display()))?;
+    // egui's own limits (16..=16384 units per em)
+    if font.units_per_em().is_none() {
+        bail!("Unsupported font: {}", path.display());
+    }
+
+    let mut fonts = egui::FontDefinitions::default();
+    fonts
+        .font_data
+        .insert(CUSTOM_FONT.to_string(), egui::FontData::from_owned(bytes));
+    let mut family = vec![CUSTOM_FONT.to_string()];
+    if let Some(fallbacks) = fonts.families.get(&egui::FontFamily::Proportional) {
+        family.extend(fallbacks.iter().cloned());
+    }
+    fonts.families.insert(custom_family(), family);
+    Ok(fonts)
+}
@@ -0,0 +1,292 @@
+/// FILE: src/format.rs
+///
+/// This module figures out WHAT KIND of document is open: a BookScript file
+/// with `[CHAPTER: X]` tags, a Fountain screenplay, a Markdown manuscript, or
+/// plain prose. The answer decides how the rest of the app interprets the text.
+///
+/// RUST CONCEPTS DEMONSTRATED:
+/// - Fieldless enums with `impl` blocks (methods on enum values)
+/// - Associated constants (`DocumentFormat::ALL`)
+/// - `Option<T>` chaining with `.and_then()` and `.or_else()`
+/// - Iterator adaptors: `.take()`, `.filter()`, `.count()`
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// ============================================================================
+// DOCUMENT FORMAT ENUM
+// ============================================================================
+
+/// The formats the editor knows how to interpret
+///
+/// `Copy` is derived because the enum carries no data - copying it is as
+/// cheap as copying an integer, so we can pass it around by value freely.
+///
+/// `Serialize`/`Deserialize` let the format be written to the per-document
+/// sidecar file when the user overrides the detected mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DocumentFormat {
+    /// Native format: `[CHAPTER: X]`, `[SCENE: Beach]`, `[ACT: I]` tags
+    BookScript,
+
+    /// Fountain screenplay markup: `INT. BEACH - DAY` sluglines, cues, etc.
+    Fountain,
+
+    /// Markdown manuscript: `# Chapter` / `## Scene` headings
+    Markdown,
+
+    /// Plain prose with no structural markup at all
+    Plain,
+}
+
+impl DocumentFormat {
+    /// Every format, in the order shown in the status-bar dropdown
+    pub const ALL: [DocumentFormat; 4] = [
+        DocumentFormat::BookScript,
+        DocumentFormat::Fountain,
+        DocumentFormat::Markdown,
+        DocumentFormat::Plain,
+    ];
+
+    /// Human-readable name for menus and the status bar
+    pub fn label(&self) -> &'static str {
+        match self {
+            DocumentFormat::BookScript => "BookScript tags",
+            DocumentFormat::Fountain => "Fountain",
+            DocumentFormat::Markdown => "Markdown",
+            DocumentFormat::Plain => "Plain",
+        }
+    }
+
+    /// The file extension an exporter should suggest for this format
+    pub fn default_extension(&self) -> &'static str {
+        match self {
+            DocumentFormat::BookScript => "bks",
+            DocumentFormat::Fountain => "fountain",
+            DocumentFormat::Markdown => "md",
+            DocumentFormat::Plain => "txt",
+        }
+    }
+}
+
+// ============================================================================
+// DETECTION
+// ============================================================================
+
+/// How many lines of content we peek at when sniffing the format
+///
+/// Structural markers almost always appear near the top of a manuscript,
+/// so there's no need to scan a whole novel to make a decision.
+const SNIFF_LINE_LIMIT: usize = 200;
+
+/// Map a file extension to a format, if the extension is unambiguous
+///
+/// Returns None for extensions like `.txt` (or no extension at all) that
+/// could hold anything - those need a look at the content instead.
+pub fn format_from_extension(path: &Path) -> Option<DocumentFormat> {
+    // extension() gives us an &OsStr; to_str() fails for non-UTF-8 names,
+    // which we simply treat as "unknown extension"
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+
+    match ext.as_str() {
+        "bks" | "bksx" | "scr" => Some(DocumentFormat::BookScript),
+        "fountain" | "spmd" => Some(DocumentFormat::Fountain),
+        "md" | "markdown" => Some(DocumentFormat::Markdown),
+        // "txt" and everything else is ambiguous
+        _ => None,
+    }
+}
+
+/// Guess the format by looking at the text itself
+///
+/// HEURISTIC:
+/// Each format has a tell-tale line shape. We count how many of the first
+/// SNIFF_LINE_LIMIT lines match each shape and pick the format with the
+/// most hits. No hits at all means the document is plain prose.
+///
+/// Ties are broken in the order BookScript > Fountain > Markdown, since a
+/// bracket tag is the least likely to appear by accident.
+pub fn sniff_content(content: &str) -> DocumentFormat {
+    let lines: Vec<&str> = content
+        .lines()
+        .take(SNIFF_LINE_LIMIT)
+        .map(str::trim)
+        .collect();
+
+    let tag_hits = lines.iter().filter(|l| looks_like_bracket_tag(l)).count();
+    let slug_hits = lines.iter().filter(|l| looks_like_slugline(l)).count();
+    let heading_hits = lines
+        .iter()
+        .filter(|l| looks_like_markdown_heading(l))
+        .count();
+
+    if tag_hits == 0 && slug_hits == 0 && heading_hits == 0 {
+        return DocumentFormat::Plain;
+    }
+
+    if tag_hits >= slug_hits && tag_hits >= heading_hits {
+        DocumentFormat::BookScript
+    } else if slug_hits >= heading_hits {
+        DocumentFormat::Fountain
+    } else {
+        DocumentFormat::Markdown
+    }
+}
+
+/// Detect the format of a document from its path and content
+///
+/// The extension wins when it's unambiguous (a `.bks` file is BookScript even
+/// if it's still empty). Otherwise - `.txt`, unknown extensions, or an
+/// unsaved buffer with no path - we fall back to sniffing the content.
+pub fn detect_format(path: Option<&Path>, content: &str) -> DocumentFormat {
+    path.and_then(format_from_extension)
+        .unwrap_or_else(|| sniff_content(content))
+}
+
+// ============================================================================
+// LINE SHAPE PREDICATES
+// ============================================================================
+// Each predicate expects an already-trimmed line.
+
+/// `[CHAPTER: 1]`, `[SCENE: Beach]` - an opening bracket, an alphabetic tag
+/// name, then a colon
+fn looks_like_bracket_tag(line: &str) -> bool {
+    let Some(rest) = line.strip_prefix('[') else {
+        return false;
+    };
+    let Some((name, _value)) = rest.split_once(':') else {
+        return false;
+    };
+    let name = name.trim();
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+/// `INT. BEACH - DAY`, `EXT HOUSE`, `INT./EXT. CAR`, `I/E. CAR`, or a forced
+/// scene heading like `.FLASHBACK`
+fn looks_like_slugline(line: &str) -> bool {
+    let upper = line.to_ascii_uppercase();
+    const PREFIXES: [&str; 6] = ["INT.", "EXT.", "INT/EXT", "INT./EXT.", "I/E", "EST."];
+    if PREFIXES.iter().any(|p| upper.starts_with(p)) {
+        return true;
+    }
+    // "INT " / "EXT " without the period are also common in the wild
+    if upper.starts_with("INT ") || upper.starts_with("EXT ") {
+        return true;
+    }
+    // Forced scene heading: a single leading period followed by a letter
+    // ("..." ellipses at the start of prose must not count)
+    let mut chars = line.chars();
+    chars.next() == Some('.') && chars.next().is_some_and(|c| c.is_alphabetic())
+}
+
+/// `# Heading` through `###### Heading` - hashes followed by a space
+fn looks_like_markdown_heading(line: &str) -> bool {
+    let hashes = line.chars().take_while(|&c| c == '#').count();
+    (1..=6).contains(&hashes) && line[hashes..].starts_with(' ')
+}
+
+// ============================================================================
+// WHY EXTENSION FIRST, CONTENT SECOND?
+// ============================================================================
+//
+// An explicit extension is the user telling us what the file is, so it
+// should win. But `.txt` is a catch-all: screenwriters often save Fountain
+// as .txt, and some people keep Markdown drafts the same way. Peeking at the
+// content handles those cases, and the status-bar dropdown lets the user
+// correct us when the heuristic still guesses wrong. That correction is
+// stored in the document's sidecar file so it sticks across sessions.
+//
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unambiguous_extensions_win_over_content() {
+        let fountain = "INT. BEACH - DAY\n\nHERO\nHello.\n";
+        assert_eq!(
+            detect_format(Some(Path::new("draft.bks")), fountain),
+            DocumentFormat::BookScript
+        );
+        assert_eq!(
+            detect_format(Some(Path::new("DRAFT.MD")), "[CHAPTER: 1]\n"),
+            DocumentFormat::Markdown
+        );
+        assert_eq!(
+            detect_format(Some(Path::new("a.fountain")), ""),
+            DocumentFormat::Fountain
+        );
+        assert_eq!(
+            format_from_extension(Path::new("old.scr")),
+            Some(DocumentFormat::BookScript)
+        );
+    }
+
+    #[test]
+    fn ambiguous_extensions_and_no_path_sniff_the_content() {
+        assert_eq!(format_from_extension(Path::new("notes.txt")), None);
+        assert_eq!(format_from_extension(Path::new("README")), None);
+        assert_eq!(
+            detect_format(Some(Path::new("script.txt")), "EXT. HOUSE - NIGHT\n"),
+            DocumentFormat::Fountain
+        );
+        assert_eq!(
+            detect_format(None, "# Chapter One\n\nIt began.\n"),
+            DocumentFormat::Markdown
+        );
+    }
+
+    #[test]
+    fn each_format_is_recognised_by_its_line_shapes() {
+        assert_eq!(
+            sniff_content("[CHAPTER: 1]\n[ scene : Beach ]\nText.\n"),
+            DocumentFormat::BookScript
+        );
+        assert_eq!(
+            sniff_content("INT./EXT. CAR\n\nI/E. TRAIN\n\n.FLASHBACK\n"),
+            DocumentFormat::Fountain
+        );
+        assert_eq!(
+            sniff_content("int beach\nSome action.\n"),
+            DocumentFormat::Fountain
+        );
+        assert_eq!(
+            sniff_content("# One\n\n### Deep heading\n"),
+            DocumentFormat::Markdown
+        );
+        assert_eq!(
+            sniff_content("Just prose.\nMore prose.\n"),
+            DocumentFormat::Plain
+        );
+        assert_eq!(sniff_content(""), DocumentFormat::Plain);
+    }
+
+    #[test]
+    fn near_misses_are_not_structure() {
+        // A hashtag, an ellipsis, a bracketed aside and a word starting INT
+        let text = "#nospace\n...and then\n[aside without colon]\nINTERIOR design\n";
+        assert_eq!(sniff_content(text), DocumentFormat::Plain);
+        // Tag names are letters only
+        assert_eq!(sniff_content("[NOTE 2: x]\n"), DocumentFormat::Plain);
+    }
+
+    #[test]
+    fn the_most_hits_win_and_ties_prefer_bookscript_then_fountain() {
+        assert_eq!(
+            sniff_content("# A\n# B\n[CHAPTER: 1]\n"),
+            DocumentFormat::Markdown
+        );
+        assert_eq!(
+            sniff_content("# A\n[CHAPTER: 1]\n"),
+            DocumentFormat::BookScript
+        );
+        assert_eq!(sniff_content("# A\nINT. HOUSE\n"), DocumentFormat::Fountain);
+    }
+
+    #[test]
+    fn only_the_first_lines_are_sniffed() {
+        let mut text = "prose\n".repeat(SNIFF_LINE_LIMIT);
+        text.push_str("[CHAPTER: Late]\n");
+        assert_eq!(sniff_content(&text), DocumentFormat::Plain);
+    }
+}
@@ -0,0 +1,163 @@
+//! FILE: src/formatted.rs
+//!
+//! View → Formatted Preview: the document as a reader sees it, in a panel
+//! beside the editor - act, chapter and scene headings styled, speeches
+//! indented under their cue, stage directions in italics, comments left
+//! out.
+//!
+//! App builds the blocks from the parsed lines (blocks()) whenever the text
+//! changes, so the preview keeps up with typing, and show() draws them.
+//! Each block knows the line it came from: clicking one moves the editor
+//! there, the way the outline and comment list do.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Grouping consecutive lines into blocks with a "current block" Option
+//! - Child UIs to indent and narrow wrapped text
+
+use crate::parser::{self, ParsedLine, TagType};
+
+/// Widest the text column is drawn, so lines stay readable in a wide panel
+const MAX_TEXT_WIDTH: f32 = 640.0;
+
+/// What a block is, which decides how it's drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockKind {
+    Act,
+    Chapter,
+    Scene,
+    /// A character cue, shown in capitals above its speech
+    Cue,
+    /// A "(beat)" inside a speech
+    Parenthetical,
+    Dialogue,
+    /// A "(stage direction)" line outside any speech
+    Direction,
+    /// Prose: consecutive lines up to a blank line
+    Paragraph,
+}
+
+/// One heading, cue or paragraph of the preview
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub kind: BlockKind,
+    /// Lines joined with '\n', comments taken out
+    pub text: String,
+    /// 1-based line the block starts on
+    pub line: usize,
+}
+
+/// The document's blocks, in order
+///
+/// Speeches follow the same rules as the PDF: a cue's speech runs to the
+/// next blank line or tag, and parenthesized lines in it are
+/// parentheticals. A line holding nothing but comments is skipped without
+/// splitting its paragraph, as in an export.
+pub fn blocks(parsed: &[ParsedLine]) -> Vec<Block> {
+    let mut blocks: Vec<Block> = Vec::new();
+    // The block later lines may still be added to (a paragraph or some
+    // dialogue), ended by a blank line or anything else
+    let mut open: Option<Block> = None;
+    let mut in_speech = false;
+
+    for (i, line) in parsed.iter().enumerate() {
+        let visible = parser::strip_comments(&line.text);
+        let text = visible.trim();
+        if text.is_empty() {
+            if line.text.trim().is_empty() {
+                blocks.extend(open.take());
+                in_speech = false;
+            }
+            continue;
+        }
+
+        let next = parsed.get(i + 1).map(|line| line.text.as_str());
+        let (kind, text) = match &line.tag {
+            Some(TagType::Act(title)) => (BlockKind::Act, format!("Act {}", title)),
+            Some(TagType::Chapter(title)) => (BlockKind::Chapter, title.clone()),
+            Some(TagType::Scene(description)) => (BlockKind::Scene, description.clone()),
+            Some(TagType::Character(cue)) if parser::cue_name(line, next).is_some() => {
+                (BlockKind::Cue, cue.trim().to_uppercase())
+            }
+            Some(TagType::Action(_)) if in_speech => (BlockKind::Parenthetical, text.to_string()),
+            Some(TagType::Action(_)) => (BlockKind::Direction, text.to_string()),
+            _ if in_speech => (BlockKind::Dialogue, text.to_string()),
+            _ => (BlockKind::Paragraph, text.to_string()),
+        };
+
+        match &mut open {
+            Some(block) if block.kind == kind => {
+                block.text.push('\n');
+                block.text.push_str(&text);
+            }
+            _ => {
+                blocks.extend(open.take());
+                let block = Block {
+                    kind,
+                    text,
+                    line: line.line_number,
+                };
+                match kind {
+                    BlockKind::Paragraph | BlockKind::Dialogue => open = Some(block),
+                    _ => blocks.push(block),
+                }
+            }
+        }
+        in_speech = matches!(
+            kind,
+            BlockKind::Cue | BlockKind::Parenthetical | BlockKind::Dialogue
+        );
+    }
+
+    blocks.extend(open);
+    blocks
+}
+
+/// Draw the preview
+///
+/// Returns the 1-based line of the block the user clicked, if any.
+pub fn show(ui: &mut egui::Ui, blocks: &[Block]) -> Option<usize> {
+    if blocks.is_empty() {
+        ui.label("Nothing to preview.");
+        return None;
+    }
+
+    let mut clicked = None;
+    egui::ScrollArea::vertical()
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            let width = ui.available_width().min(MAX_TEXT_WIDTH);
+            for block in blocks {
+                if draw_block(ui, block, width).clicked() {
+                    clicked = Some(block.line);
+                }
+            }
+        });
+    clicked
+}
+
+/// One block, with the space that goes above it
+fn draw_block(ui: &mut egui::Ui, block: &Block, width: f32) -> egui::Response {
+    let text = egui::RichText::new(&block.text);
+    // Left indent and width as fractions of the text column
+    let (space, text, indent, share) = match block.kind {
+        BlockKind::Act => (24.0, text.size(24.0).strong(), 0.0, 1.0),
+        BlockKind::Chapter => (20.0, text.size(20.0).strong(), 0.0, 1.0),
+        BlockKind::Scene => (14.0, text.size(16.0).strong().italics(), 0.0, 1.0),
+        BlockKind::Cue => (10.0, text.strong(), 0.4, 0.6),
+        BlockKind::Parenthetical => (0.0, text.italics(), 0.3, 0.45),
+        BlockKind::Dialogue => (0.0, text, 0.2, 0.6),
+        BlockKind::Direction => (10.0, text.italics().weak(), 0.1, 0.8),
+        BlockKind::Paragraph => (10.0, text, 0.0, 1.0),
+    };
+
+    ui.add_space(space);
+    ui.horizontal(|ui| {
+        ui.add_space(width * indent);
+        ui.vertical(|ui| {
+            ui.set_max_width(width * share);
+            ui.add(egui::Label::new(text).sense(egui::Sense::click()))
+        })
+        .inner
+    })
+    .inner
+}
@@ -0,0 +1,215 @@
+//! FILE: src/git.rs
+//!
+//! Git history for a project (View → Git): put the project folder in a
+//! repository, commit its files with a message (or on every save), list
+//! the commits, and read a file as it was in one of them.
+//!
+//! Git is optional. Nothing here runs unless the project folder is in a
+//! repository or the user creates one, and the repository is an ordinary
+//! one - the `git` command line and other tools work on it as usual.
+//!
+//! ONLY THE PROJECT FOLDER:
+//! The project folder may be inside a bigger repository (a folder of
+//! several books, say). Commits made here only take in files under the
+//! project folder, and the change count only counts those, so nothing
+//! else in that repository is committed by surprise.
+//!
+//! Everything goes through libgit2 (the git2 crate); no `git` program needs
+//! to be installed.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Wrapping a C library's types so they don't leak into the rest of the app
+//! - Option::iter to turn "maybe a parent" into a slice of parents
+
+use crate::encoding;
+use crate::encryption;
+use anyhow::{Context, Result};
+use git2::{IndexAddOption, Repository, Signature, Sort, StatusOptions};
+use std::path::{Path, PathBuf};
+
+/// Most commits listed in the history
+pub const MAX_HISTORY: usize = 200;
+
+/// Lines written to a new repository's `.gitignore`: save backups are
+/// copies of files that are already committed
+const GITIGNORE: &str = "# Backups made when saving\nbackups/\n";
+
+/// One commit, as the history lists it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitInfo {
+    /// Full hex id
+    pub id: String,
+    /// First line of the message
+    pub summary: String,
+    pub author: String,
+    /// Unix seconds
+    pub time: i64,
+}
+
+impl CommitInfo {
+    /// The first 7 characters of the id, as git shows it
+    pub fn short_id(&self) -> &str {
+        &self.id[..7.min(self.id.len())]
+    }
+}
+
+/// What the project folder's repository holds
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepoStatus {
+    /// Files under the project folder changed, added or deleted since the
+    /// last commit
+    pub changed: usize,
+    /// Newest first, at most MAX_HISTORY
+    pub commits: Vec<CommitInfo>,
+}
+
+/// The repository `dir` is in, if any
+fn open(dir: &Path) -> Option<Repository> {
+    Repository::discover(dir)
+        .ok()
+        .filter(|repo| !repo.is_bare())
+}
+
+/// `path` relative to the repository's working folder, with `/`
+/// separators
+fn relative(repo: &Repository, path: &Path) -> Result<String> {
+    let workdir = repo
+        .workdir()
+        .context("The repository has no working folder")?
+        .canonicalize()?;
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("Can't find {}", path.display()))?;
+    let relative = path
+        .strip_prefix(&workdir)
+        .with_context(|| format!("{} is outside the repository", path.display()))?;
+    let parts: Vec<_> = relative
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect();
+    Ok(parts.join("/"))
+}
+
+/// The pathspec matching everything under `dir`
+fn pathspec(repo: &Repository, dir: &Path) -> Result<String> {
+    let relative = relative(repo, dir)?;
+    Ok(if relative.is_empty() {
+        String::from("*")
+    } else {
+        relative
+    })
+}
+
+/// Whether `dir` is in a repository
+pub fn is_repository(dir: &Path) -> bool {
+    open(dir).is_some()
+}
+
+/// Make `dir` a repository, with a `.gitignore` for save backups unless it
+/// already has one
+pub fn init(dir: &Path) -> Result<()> {
+    Repository::init(dir)
+        .with_context(|| format!("Failed to create a repository in {}", dir.display()))?;
+    let ignore = dir.join(".gitignore");
+    if !ignore.exists() {
+        std::fs::write(&ignore, GITIGNORE).context("Failed to write .gitignore")?;
+    }
+    Ok(())
+}
+
+/// Commit every change under `dir` with `message`
+///
+/// Returns the new commit's short id, or None if nothing had changed. The
+/// author is git's configured user, or "BookScript Writer" when there
+/// isn't one.
+pub fn commit_all(dir: &Path, message: &str) -> Result<Option<String>> {
+    let repo = open(dir).context("The project isn't in a Git repository")?;
+    let spec = pathspec(&repo, dir)?;
+
+    let mut index = repo.index()?;
+    index.add_all([spec.as_str()], IndexAddOption::DEFAULT, None)?;
+    // add_all doesn't notice deleted files; update_all does
+    index.update_all([spec.as_str()], None)?;
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+
+    // An unborn HEAD (a new repository) has no commit yet
+    let parent = match repo.head() {
+        Ok(head) => Some(head.peel_to_commit()?),
+        Err(_) => None,
+    };
+    let unchanged = match &parent {
+        Some(parent) => parent.tree_id() == tree.id(),
+        None => tree.is_empty(),
+    };
+    if unchanged {
+        return Ok(None);
+    }
+
+    let signature = repo
+        .signature()
+        .or_else(|_| Signature::now("BookScript Writer", "writer@localhost"))?;
+    let parents: Vec<_> = parent.iter().collect();
+    let id = repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )?;
+    Ok(Some(id.to_string()[..7].to_string()))
+}
+
+/// Changes and commits for the repository `dir` is in, or None if it isn't
+/// in one
+pub fn status(dir: &Path) -> Result<Option<RepoStatus>> {
+    let Some(repo) = open(dir) else {
+        return Ok(None);
+    };
+    let spec = pathspec(&repo, dir)?;
+
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .pathspec(&spec);
+    let changed = repo.statuses(Some(&mut options))?.len();
+
+    let mut commits = Vec::new();
+    // A new repository has nothing to walk yet
+    if repo.head().is_ok() {
+        let mut walk = repo.revwalk()?;
+        walk.push_head()?;
+        walk.set_sorting(Sort::TIME)?;
+        for id in walk.take(MAX_HISTORY) {
+            let commit = repo.find_commit(id?)?;
+            commits.push(CommitInfo {
+                id: commit.id().to_string(),
+                summary: commit.summary().unwrap_or("").to_string(),
+                author: commit.author().name().unwrap_or("").to_string(),
+                time: commit.time().seconds(),
+            });
+        }
+    }
+    Ok(Some(RepoStatus { changed, commits }))
+}
+
+/// The text of `path` as it was in commit `id`
+///
+/// Decoded like a file being opened (encoding::decode). Err if the file
+/// wasn't in that commit, or is password-protected there.
+pub fn file_at(dir: &Path, id: &str, path: &Path) -> Result<String> {
+    let repo = open(dir).context("The project isn't in a Git repository")?;
+    let commit = repo.find_commit(git2::Oid::from_str(id)?)?;
+    let relative = PathBuf::from(relative(&repo, path)?);
+    let entry = commit
+        .tree()?
+        .get_path(&relative)
+        .with_context(|| format!("{} isn't in that commit", relative.display()))?;
+    let blob = entry.to_object(&repo)?.peel_to_blob()?;
+    if encryption::is_encrypted(blob.content()) {
+        anyhow::bail!("{} is password-protected", relative.display());
+    }
+    Ok(encoding::decode(blob.content()).text)
+}
@@ -0,0 +1,359 @@
+//! FILE: src/goals.rs
+//!
+//! Writing goals: how many words were written this session and today, how
+//! far the manuscript is towards its target length, and timed sprints.
+//!
+//! "Words written" is the change in the document's word count, not the
+//! number of words typed - deleting a paragraph counts against the total,
+//! so the session delta can go negative. Opening another file must not
+//! count that file's existing words as written (or the old file's words as
+//! deleted), so the count is banked and restarted from the new document.
+//!
+//! Daily totals carry over between sessions: the app starts with whatever
+//! was recorded for today, and a session that runs past midnight starts a
+//! new day. Writing the totals to disk is storage.rs's job; nothing here
+//! touches egui or the file system.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Signed arithmetic (i64) on unsigned counts (usize)
+//! - Option for "not known yet"
+//! - chrono::NaiveDate: a calendar date without a time zone
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+// ============================================================================
+// GOAL SETTING
+// ============================================================================
+
+/// What the goal counts against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GoalPeriod {
+    /// Words written since the app started
+    #[default]
+    Session,
+    /// Words written today, across sessions
+    Daily,
+}
+
+/// The goal set in Tools → Writing Goal
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WritingGoal {
+    /// Target number of words (0 = no goal)
+    pub words: usize,
+    pub period: GoalPeriod,
+    /// Length the finished manuscript should have (0 = no target)
+    ///
+    /// A project's own target (ProjectSettings::target_words) takes its
+    /// place while the project is open.
+    pub manuscript_words: usize,
+    /// Length of a custom sprint (Tools → Sprint), in minutes
+    pub sprint_minutes: u32,
+}
+
+impl Default for WritingGoal {
+    fn default() -> Self {
+        Self {
+            words: 0,
+            period: GoalPeriod::Session,
+            manuscript_words: 0,
+            sprint_minutes: 45,
+        }
+    }
+}
+
+/// Largest goal the dialog accepts
+pub const MAX_GOAL_WORDS: usize = 100_000;
+
+/// Largest manuscript target the dialog accepts
+pub const MAX_MANUSCRIPT_WORDS: usize = 2_000_000;
+
+impl WritingGoal {
+    /// How far `written` is towards the goal, from 0.0 to 1.0
+    ///
+    /// None when no goal is set. A negative count is no progress.
+    pub fn fraction(&self, written: i64) -> Option<f32> {
+        if self.words == 0 {
+            return None;
+        }
+        Some((written.max(0) as f32 / self.words as f32).min(1.0))
+    }
+}
+
+/// Shortest and longest sprint the Sprint window offers
+pub const MIN_SPRINT_MINUTES: u32 = 1;
+pub const MAX_SPRINT_MINUTES: u32 = 240;
+
+/// How far a manuscript of `words` is towards `target`, from 0.0 to 1.0
+pub fn manuscript_fraction(words: usize, target: usize) -> f32 {
+    (words as f32 / target.max(1) as f32).min(1.0)
+}
+
+// ============================================================================
+// SESSION COUNTER
+// ============================================================================
+
+/// Words written since the app started
+#[derive(Debug, Clone, Default)]
+pub struct WritingSession {
+    /// Words written in documents that have since been replaced
+    banked: i64,
+    /// The open document's word count when it was opened (None until the
+    /// first count after opening)
+    baseline: Option<usize>,
+    /// The open document's latest word count
+    current: usize,
+}
+
+impl WritingSession {
+    /// Record the open document's current word count
+    ///
+    /// The first count after startup or document_replaced() becomes the
+    /// baseline that later counts are measured against.
+    pub fn observe(&mut self, words: usize) {
+        self.baseline.get_or_insert(words);
+        self.current = words;
+    }
+
+    /// Another document is about to take the editor's place
+    ///
+    /// What was written in the old one is kept; the new one gets its own
+    /// baseline on the next observe().
+    pub fn document_replaced(&mut self) {
+        self.banked = self.delta();
+        self.baseline = None;
+        self.current = 0;
+    }
+
+    /// Words written this session (negative if more were deleted)
+    pub fn delta(&self) -> i64 {
+        let baseline = self.baseline.unwrap_or(self.current);
+        self.banked + self.current as i64 - baseline as i64
+    }
+}
+
+// ============================================================================
+// DAILY TOTAL
+// ============================================================================
+
+/// Words written today, including earlier sessions
+#[derive(Debug, Clone)]
+pub struct DailyTally {
+    /// The day being counted
+    date: NaiveDate,
+    /// Words recorded for `date` before the session delta below
+    earlier: i64,
+    /// The session delta when counting for `date` began
+    session_start: i64,
+}
+
+impl DailyTally {
+    /// Start counting `date`, which already has `earlier` words recorded
+    pub fn new(date: NaiveDate, earlier: i64) -> Self {
+        Self {
+            date,
+            earlier,
+            session_start: 0,
+        }
+    }
+
+    /// The day being counted
+    pub fn date(&self) -> NaiveDate {
+        self.date
+    }
+
+    /// Words written on the current day, given the session's delta
+    pub fn total(&self, session_delta: i64) -> i64 {
+        self.earlier + session_delta - self.session_start
+    }
+
+    /// Move on to `today` if the date has changed
+    ///
+    /// Returns the finished day and its total, which should be recorded
+    /// before it's forgotten. Words written from here on count for `today`.
+    pub fn roll_over(&mut self, today: NaiveDate, session_delta: i64) -> Option<(NaiveDate, i64)> {
+        if today == self.date {
+            return None;
+        }
+        let finished = (self.date, self.total(session_delta));
+        *self = Self {
+            date: today,
+            earlier: 0,
+            session_start: session_delta,
+        };
+        Some(finished)
+    }
+}
+
+// ============================================================================
+// SPRINTS
+// ============================================================================
+
+/// A timed burst of writing: words written against the clock
+///
+/// Times are egui's (seconds since the app started), so a sprint doesn't
+/// outlive the session. Words are counted from the session delta, which
+/// already carries over when another document is opened mid-sprint.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprint {
+    /// How long the sprint runs, in minutes
+    pub minutes: u32,
+    /// When it started, on the clock (for the history file)
+    pub started_at: chrono::NaiveDateTime,
+    /// When it started, in egui time
+    started: f64,
+    /// The session delta when it started
+    start_delta: i64,
+}
+
+impl Sprint {
+    /// Start a sprint of `minutes` at `now` (egui time) and `started_at`
+    /// (the clock), given the session's delta
+    pub fn start(
+        minutes: u32,
+        now: f64,
+        started_at: chrono::NaiveDateTime,
+        session_delta: i64,
+    ) -> Self {
+        Self {
+            minutes,
+            started_at,
+            started: now,
+            start_delta: session_delta,
+        }
+    }
+
+    /// Seconds run so far (never more than the sprint's length)
+    pub fn elapsed(&self, now: f64) -> f64 {
+        (now - self.started).clamp(0.0, self.length())
+    }
+
+    /// Seconds run so far by the clock, for when egui's time isn't at
+    /// hand (the app closing)
+    pub fn elapsed_by_clock(&self, now: chrono::NaiveDateTime) -> f64 {
+        let seconds = (now - self.started_at).num_milliseconds() as f64 / 1000.0;
+        seconds.clamp(0.0, self.length())
+    }
+
+    /// Seconds left to run
+    pub fn remaining(&self, now: f64) -> f64 {
+        self.length() - self.elapsed(now)
+    }
+
+    /// Whether the time is up
+    pub fn is_over(&self, now: f64) -> bool {
+        self.remaining(now) <= 0.0
+    }
+
+    /// Words written since the sprint started
+    pub fn words(&self, session_delta: i64) -> i64 {
+        session_delta - self.start_delta
+    }
+
+    /// The sprint's length in seconds
+    fn length(&self) -> f64 {
+        f64::from(self.minutes) * 60.0
+    }
+}
+
+/// Words per minute over `seconds`, or 0 for a sprint stopped at once
+pub fn words_per_minute(words: i64, seconds: u64) -> f64 {
+    if seconds == 0 {
+        return 0.0;
+    }
+    words as f64 * 60.0 / seconds as f64
+}
+
+/// "mm:ss" for a number of seconds (rounded up, so the clock shows 0:00
+/// only when time is up)
+pub fn format_clock(seconds: f64) -> String {
+    let seconds = seconds.max(0.0).ceil() as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 11, day).unwrap()
+    }
+
+    #[test]
+    fn the_first_count_is_the_baseline() {
+        let mut session = WritingSession::default();
+        assert_eq!(session.delta(), 0);
+        session.observe(1000);
+        assert_eq!(session.delta(), 0);
+        session.observe(1250);
+        assert_eq!(session.delta(), 250);
+    }
+
+    #[test]
+    fn deleting_makes_the_delta_negative() {
+        let mut session = WritingSession::default();
+        session.observe(1000);
+        session.observe(1100);
+        session.observe(700);
+        assert_eq!(session.delta(), -300);
+    }
+
+    #[test]
+    fn opening_another_document_keeps_the_count() {
+        let mut session = WritingSession::default();
+        session.observe(1000);
+        session.observe(1200);
+        session.document_replaced();
+        // The new file's own words aren't written this session...
+        session.observe(50_000);
+        assert_eq!(session.delta(), 200);
+        // ...but what's written in it is
+        session.observe(49_900);
+        assert_eq!(session.delta(), 100);
+        session.document_replaced();
+        session.document_replaced();
+        assert_eq!(session.delta(), 100);
+    }
+
+    #[test]
+    fn the_daily_total_adds_earlier_sessions_and_rolls_over_at_midnight() {
+        let mut tally = DailyTally::new(date(1), 400);
+        assert_eq!(tally.total(0), 400);
+        assert_eq!(tally.total(150), 550);
+        assert_eq!(tally.roll_over(date(1), 150), None);
+
+        assert_eq!(tally.roll_over(date(2), 150), Some((date(1), 550)));
+        assert_eq!(tally.date(), date(2));
+        // Only what's written after midnight counts for the new day
+        assert_eq!(tally.total(150), 0);
+        assert_eq!(tally.total(100), -50);
+    }
+
+    #[test]
+    fn goal_fraction_ignores_negative_counts_and_stops_at_one() {
+        let goal = WritingGoal {
+            words: 500,
+            ..WritingGoal::default()
+        };
+        assert_eq!(goal.fraction(-20), Some(0.0));
+        assert_eq!(goal.fraction(250), Some(0.5));
+        assert_eq!(goal.fraction(900), Some(1.0));
+        assert_eq!(WritingGoal::default().fraction(900), None);
+    }
+
+    #[test]
+    fn sprints_count_from_their_start() {
+        let started_at = date(1).and_hms_opt(9, 0, 0).unwrap();
+        let sprint = Sprint::start(10, 100.0, started_at, 40);
+        assert_eq!(sprint.words(65), 25);
+        assert_eq!(sprint.elapsed(50.0), 0.0);
+        assert_eq!(sprint.remaining(400.0), 300.0);
+        assert!(!sprint.is_over(699.0));
+        assert!(sprint.is_over(700.0));
+        assert_eq!(sprint.elapsed(5000.0), 600.0);
+        assert_eq!(format_clock(59.2), "1:00");
+        assert_eq!(words_per_minute(300, 600), 30.0);
+        assert_eq!(words_per_minute(300, 0), 0.0);
+    }
+}
@@ -0,0 +1,411 @@
+//! FILE: src/history.rs
+//!
+//! Undo/redo history for the whole document.
+//!
+//! egui's TextEdit has its own undo, but it is shallow and is thrown away
+//! whenever the widget's state is reset. This module keeps a separate,
+//! longer history that knows nothing about egui: the app tells it when the
+//! text was edited and hands it the current text, and it decides when to
+//! take a snapshot.
+//!
+//! HOW SNAPSHOTS ARE STORED:
+//! Storing the full text for every step would use megabytes per minute on a
+//! novel-length document. Instead each step stores only the region that
+//! changed: the bytes before it and after it are shared with the text on
+//! either side, so we just trim the common prefix and suffix.
+//!
+//!   before: "The cat sat."        after: "The black cat sat."
+//!   Change { start: 4, old: "", new: "black " }
+//!
+//! WHEN SNAPSHOTS ARE TAKEN (coalescing):
+//! Typing "hello" is one undo step, not five. Edits are grouped until the
+//! user pauses for IDLE_SECONDS, or until a burst has gone on for
+//! MAX_BURST_SECONDS (so a long unbroken typing session is still split up).
+//! Paste, load, and undo/redo itself force a snapshot immediately.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - VecDeque: a queue with cheap removal at BOTH ends (oldest entries are
+//!   evicted from the front, new ones pushed at the back)
+//! - Passing time in as a parameter to keep logic testable
+
+use std::collections::VecDeque;
+
+/// Pause in typing (seconds) after which the edits so far become one step
+pub const IDLE_SECONDS: f64 = 0.5;
+
+/// Longest run of continuous typing (seconds) merged into one step
+pub const MAX_BURST_SECONDS: f64 = 5.0;
+
+/// How much history to keep before the oldest steps are forgotten
+#[derive(Debug, Clone, Copy)]
+pub struct HistoryLimits {
+    /// Maximum number of undo steps
+    pub max_entries: usize,
+    /// Maximum total size of the stored changes, in bytes
+    pub max_bytes: usize,
+}
+
+impl Default for HistoryLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 500,
+            max_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// One undoable step: at byte `start`, `old` was replaced by `new`
+#[derive(Debug, Clone, PartialEq)]
+struct Change {
+    start: usize,
+    old: String,
+    new: String,
+}
+
+impl Change {
+    /// Work out the smallest change that turns `before` into `after`
+    fn between(before: &str, after: &str) -> Self {
+        // Common prefix, stepped back to a char boundary so slicing is safe
+        let mut prefix = before
+            .bytes()
+            .zip(after.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !before.is_char_boundary(prefix) || !after.is_char_boundary(prefix) {
+            prefix -= 1;
+        }
+
+        // Common suffix of what's left (it must not overlap the prefix)
+        let max_suffix = (before.len() - prefix).min(after.len() - prefix);
+        let mut suffix = before
+            .bytes()
+            .rev()
+            .zip(after.bytes().rev())
+            .take(max_suffix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !before.is_char_boundary(before.len() - suffix)
+            || !after.is_char_boundary(after.len() - suffix)
+        {
+            suffix -= 1;
+        }
+
+        Change {
+            start: prefix,
+            old: before[prefix..before.len() - suffix].to_string(),
+            new: after[prefix..after.len() - suffix].to_string(),
+        }
+    }
+
+    /// Bytes of memory this step holds on to
+    fn size(&self) -> usize {
+        self.old.len() + self.new.len()
+    }
+
+    /// Turn the "after" text back into the "before" text
+    fn revert(&self, text: &mut String) {
+        text.replace_range(self.start..self.start + self.new.len(), &self.old);
+    }
+
+    /// Turn the "before" text into the "after" text
+    fn reapply(&self, text: &mut String) {
+        text.replace_range(self.start..self.start + self.old.len(), &self.new);
+    }
+}
+
+/// The text an undo/redo produced, plus where to put the caret
+#[derive(Debug, Clone, PartialEq)]
+pub struct Restored {
+    pub text: String,
+    /// Byte offset just after the restored region
+    pub caret: usize,
+}
+
+/// Undo/redo stacks for one document
+#[derive(Debug)]
+pub struct History {
+    /// The text as of the most recent snapshot
+    current: String,
+    /// Steps that lead back in time; the newest is at the back
+    undo: VecDeque<Change>,
+    /// Steps undone so far; the most recently undone is at the back
+    redo: Vec<Change>,
+    /// Total size of everything in `undo`
+    undo_bytes: usize,
+    /// (time of first edit, time of latest edit) since the last snapshot
+    pending: Option<(f64, f64)>,
+    limits: HistoryLimits,
+}
+
+impl History {
+    /// Start a history whose starting point is `text`
+    pub fn new(text: &str, limits: HistoryLimits) -> Self {
+        Self {
+            current: text.to_string(),
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            undo_bytes: 0,
+            pending: None,
+            limits,
+        }
+    }
+
+    /// Forget everything and start again from `text`
+    ///
+    /// Used when a different document is loaded: undo must never bring back
+    /// the previous document's content.
+    pub fn reset(&mut self, text: &str) {
+        *self = Self::new(text, self.limits);
+    }
+
+    /// Note that the user edited the text at time `now` (in seconds)
+    ///
+    /// The snapshot is taken later by tick() or checkpoint().
+    pub fn edited(&mut self, now: f64) {
+        self.pending = match self.pending {
+            Some((first, _)) => Some((first, now)),
+            None => Some((now, now)),
+        };
+    }
+
+    /// Take a snapshot if typing has paused or the burst has gone on long
+    /// enough. Call once per frame with the current text.
+    pub fn tick(&mut self, text: &str, now: f64) {
+        if let Some((first, last)) = self.pending {
+            if now - last >= IDLE_SECONDS || now - first >= MAX_BURST_SECONDS {
+                self.checkpoint(text);
+            }
+        }
+    }
+
+    /// Take a snapshot right now (before a paste, after a paste, ...)
+    ///
+    /// Does nothing if the text hasn't changed since the last snapshot.
+    /// A new step makes the redo stack meaningless, so it is cleared.
+    pub fn checkpoint(&mut self, text: &str) {
+        self.pending = None;
+        if text == self.current {
+            return;
+        }
+
+        let change = Change::between(&self.current, text);
+        self.undo_bytes += change.size();
+        self.undo.push_back(change);
+        self.redo.clear();
+        self.current = text.to_string();
+        self.evict();
+    }
+
+    /// Step back one snapshot
+    ///
+    /// `text` is the editor's text right now; any edits not yet snapshotted
+    /// are captured first so they can be redone. Returns None when there is
+    /// nothing to undo.
+    pub fn undo(&mut self, text: &str) -> Option<Restored> {
+        self.checkpoint(text);
+
+        let change = self.undo.pop_back()?;
+        self.undo_bytes -= change.size();
+        change.revert(&mut self.current);
+
+        let restored = Restored {
+            text: self.current.clone(),
+            caret: change.start + change.old.len(),
+        };
+        self.redo.push(change);
+        Some(restored)
+    }
+
+    /// Step forward one snapshot (after an undo)
+    ///
+    /// Returns None when there is nothing to redo - including when the
+    /// user has typed since undoing, which starts a new branch of history.
+    pub fn redo(&mut self, text: &str) -> Option<Restored> {
+        self.checkpoint(text);
+
+        let change = self.redo.pop()?;
+        change.reapply(&mut self.current);
+
+        let restored = Restored {
+            text: self.current.clone(),
+            caret: change.start + change.new.len(),
+        };
+        self.undo_bytes += change.size();
+        self.undo.push_back(change);
+        self.evict();
+        Some(restored)
+    }
+
+    /// Is there anything to undo? (`text` = the editor's text right now)
+    pub fn can_undo(&self, text: &str) -> bool {
+        !self.undo.is_empty() || text != self.current
+    }
+
+    /// Is there anything to redo? (`text` = the editor's text right now)
+    pub fn can_redo(&self, text: &str) -> bool {
+        !self.redo.is_empty() && text == self.current
+    }
+
+    /// Drop the oldest steps until both limits are respected
+    fn evict(&mut self) {
+        while self.undo.len() > self.limits.max_entries || self.undo_bytes > self.limits.max_bytes {
+            match self.undo.pop_front() {
+                Some(oldest) => self.undo_bytes -= oldest.size(),
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(text: &str) -> History {
+        History::new(text, HistoryLimits::default())
+    }
+
+    #[test]
+    fn a_change_is_only_the_region_that_differs() {
+        assert_eq!(
+            Change::between("The cat sat.", "The black cat sat."),
+            Change {
+                start: 4,
+                old: String::new(),
+                new: "black ".into(),
+            }
+        );
+        // Shared bytes inside a multi-byte char don't split it
+        let change = Change::between("café", "cafè");
+        assert_eq!((change.old.as_str(), change.new.as_str()), ("é", "è"));
+        let change = Change::between("aaa", "aa");
+        assert_eq!(
+            (change.start, change.old.len(), change.new.len()),
+            (2, 1, 0)
+        );
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_snapshots() {
+        let mut h = history("one");
+        h.checkpoint("one two");
+        h.checkpoint("one two three");
+
+        let back = h.undo("one two three").unwrap();
+        assert_eq!(
+            back,
+            Restored {
+                text: "one two".into(),
+                caret: 7
+            }
+        );
+        assert_eq!(h.undo("one two").unwrap().text, "one");
+        assert_eq!(h.undo("one"), None);
+
+        assert_eq!(h.redo("one").unwrap().text, "one two");
+        let forward = h.redo("one two").unwrap();
+        assert_eq!(
+            forward,
+            Restored {
+                text: "one two three".into(),
+                caret: 13
+            }
+        );
+        assert_eq!(h.redo("one two three"), None);
+    }
+
+    #[test]
+    fn unsnapshotted_edits_are_captured_before_undoing() {
+        let mut h = history("a");
+        let back = h.undo("ab").unwrap();
+        assert_eq!(back.text, "a");
+        assert_eq!(h.redo("a").unwrap().text, "ab");
+    }
+
+    #[test]
+    fn typing_after_an_undo_drops_the_redo_steps() {
+        let mut h = history("a");
+        h.checkpoint("ab");
+        h.undo("ab").unwrap();
+        assert!(h.can_redo("a"));
+        assert!(!h.can_redo("ax"));
+        h.checkpoint("ax");
+        assert_eq!(h.redo("ax"), None);
+    }
+
+    #[test]
+    fn unchanged_text_takes_no_snapshot() {
+        let mut h = history("same");
+        h.checkpoint("same");
+        assert!(!h.can_undo("same"));
+        assert_eq!(h.undo("same"), None);
+    }
+
+    #[test]
+    fn edits_coalesce_until_a_pause_or_a_long_burst() {
+        let mut h = history("");
+        for (i, text) in ["h", "he", "hel"].iter().enumerate() {
+            let now = i as f64 * 0.1;
+            h.edited(now);
+            h.tick(text, now);
+        }
+        // Still typing: nothing snapshotted yet
+        h.tick("hel", 0.3);
+        assert_eq!(h.undo.len(), 0);
+        // A pause makes the three keystrokes one step
+        h.tick("hel", 0.3 + IDLE_SECONDS);
+        assert_eq!(h.undo.len(), 1);
+
+        // Typing without a pause is still split after MAX_BURST_SECONDS
+        let mut now = 10.0;
+        let mut text = String::from("hel");
+        while now < 10.0 + MAX_BURST_SECONDS + 1.0 {
+            text.push('x');
+            h.edited(now);
+            h.tick(&text, now);
+            now += 0.2;
+        }
+        assert_eq!(h.undo.len(), 2);
+    }
+
+    #[test]
+    fn the_oldest_steps_are_evicted_past_either_limit() {
+        let mut h = History::new(
+            "",
+            HistoryLimits {
+                max_entries: 2,
+                max_bytes: 1024,
+            },
+        );
+        h.checkpoint("a");
+        h.checkpoint("ab");
+        h.checkpoint("abc");
+        assert_eq!(h.undo("abc").unwrap().text, "ab");
+        assert_eq!(h.undo("ab").unwrap().text, "a");
+        assert_eq!(h.undo("a"), None);
+
+        let mut h = History::new(
+            "",
+            HistoryLimits {
+                max_entries: 100,
+                max_bytes: 10,
+            },
+        );
+        h.checkpoint("12345");
+        h.checkpoint("12345678");
+        // 5 + 3 bytes fit; another 4 pushes the first step out
+        assert_eq!(h.undo_bytes, 8);
+        h.checkpoint("123456789012");
+        assert_eq!(h.undo_bytes, 7);
+        assert_eq!(h.undo.len(), 2);
+    }
+
+    #[test]
+    fn reset_forgets_the_previous_document() {
+        let mut h = history("old document");
+        h.checkpoint("old document, edited");
+        h.reset("new document");
+        assert!(!h.can_undo("new document"));
+        assert_eq!(h.undo("new document"), None);
+    }
+}
@@ -0,0 +1,487 @@
+//! FILE: src/incremental.rs
+//!
+//! Keeping the parsed document up to date without re-parsing all of it.
+//!
+//! parser::parse_document runs the tag regexes over every line, so calling
+//! it after each keystroke costs time in proportion to the whole document.
+//! A keystroke only changes one line, though (a paste or a replace, a few).
+//! ParsedDocument keeps the parsed lines and the structure from last time,
+//! and apply() re-parses just the lines a TextChange touches:
+//!
+//! 1. Find the old lines the replaced range falls on
+//! 2. Parse the lines that now stand in their place
+//! 3. Splice them in and renumber the lines after them
+//! 4. If no act, chapter or scene tag was added or removed, shift the
+//!    structure's line ranges in place; otherwise rebuild it from the
+//!    parsed lines (still no regex work)
+//!
+//! Callers that don't know what changed (the editor widget edits the
+//! String directly) use update(), which works the change out by comparing
+//! the new text with the old - a plain byte comparison, far cheaper than
+//! parsing.
+//!
+//! lines() and structure() always match what the format's DocumentParser
+//! and extract_structure would produce for the same text. Changing the
+//! format (the status-bar mode) parses everything again.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Range<usize> for byte ranges, and String::replace_range
+//! - Vec::splice to swap a run of elements for a different number of new ones
+//! - slice::partition_point: binary search with a predicate
+
+use crate::format::DocumentFormat;
+use crate::parser::{self, DocumentParser, DocumentStructure, ParsedLine, TagType};
+use std::ops::Range;
+
+// ============================================================================
+// CHANGES
+// ============================================================================
+
+/// One edit: the bytes in `range` of the old text were replaced by `new_text`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChange {
+    pub range: Range<usize>,
+    pub new_text: String,
+}
+
+impl TextChange {
+    /// The smallest single change that turns `old` into `new`
+    ///
+    /// Everything outside the common start and common end counts as
+    /// changed, so two edits far apart become one change spanning both.
+    /// None if the texts are the same.
+    pub fn between(old: &str, new: &str) -> Option<Self> {
+        if old == new {
+            return None;
+        }
+
+        let mut prefix = old
+            .bytes()
+            .zip(new.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        // Both texts share these bytes, so a char boundary in one is a
+        // char boundary in the other
+        while !old.is_char_boundary(prefix) {
+            prefix -= 1;
+        }
+
+        // The common end may not reach back into the common start
+        let max_suffix = old.len().min(new.len()) - prefix;
+        let mut suffix = old
+            .bytes()
+            .rev()
+            .zip(new.bytes().rev())
+            .take(max_suffix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        while !old.is_char_boundary(old.len() - suffix) {
+            suffix -= 1;
+        }
+
+        Some(Self {
+            range: prefix..old.len() - suffix,
+            new_text: new[prefix..new.len() - suffix].to_string(),
+        })
+    }
+}
+
+// ============================================================================
+// PARSED DOCUMENT
+// ============================================================================
+
+/// A document's parsed lines and structure, kept in step with its text
+///
+/// Internally every `\n`-separated piece of the text is a line, including
+/// the empty piece after a final newline. str::lines() (and so
+/// parse_document) doesn't count that one, which lines() allows for.
+#[derive(Debug, Clone)]
+pub struct ParsedDocument {
+    /// The text as of the last change
+    text: String,
+    /// Byte offset where each line starts (always at least one entry)
+    line_starts: Vec<usize>,
+    /// One entry per line in line_starts
+    lines: Vec<ParsedLine>,
+    structure: DocumentStructure,
+    /// How the lines are read
+    format: DocumentFormat,
+}
+
+impl ParsedDocument {
+    /// Parse `text`, written in `format`, from scratch
+    pub fn new(text: &str, format: DocumentFormat) -> Self {
+        let mut document = Self {
+            text: text.to_string(),
+            line_starts: line_starts(text, 0),
+            lines: Vec::new(),
+            structure: DocumentStructure::default(),
+            format,
+        };
+        document.lines = parse_lines(text, 1, false, document.parser());
+        document.structure = parser::extract_structure(document.lines());
+        document
+    }
+
+    fn parser(&self) -> &'static dyn DocumentParser {
+        parser::parser_for(self.format)
+    }
+
+    /// Read the text as `format` from now on, parsing it all again if
+    /// that's a change
+    ///
+    /// Returns false (and does nothing) if the format is the same.
+    pub fn set_format(&mut self, format: DocumentFormat) -> bool {
+        if format == self.format {
+            return false;
+        }
+        *self = Self::new(&self.text, format);
+        true
+    }
+
+    /// The parsed lines, as parse_document would return them
+    pub fn lines(&self) -> &[ParsedLine] {
+        match self.lines.split_last() {
+            Some((last, rest)) if last.text.is_empty() => rest,
+            _ => &self.lines,
+        }
+    }
+
+    /// Acts, chapters and scenes, as extract_structure would return them
+    ///
+    /// Word counts aren't filled in; that's annotate_structure's job.
+    pub fn structure(&self) -> &DocumentStructure {
+        &self.structure
+    }
+
+    /// Bring everything up to date with `text`
+    ///
+    /// Returns false (and does nothing) if the text hasn't changed.
+    pub fn update(&mut self, text: &str) -> bool {
+        match TextChange::between(&self.text, text) {
+            Some(change) => {
+                self.apply(&change);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Apply one edit, re-parsing only the lines it touches
+    ///
+    /// `change.range` must lie on char boundaries of the current text.
+    pub fn apply(&mut self, change: &TextChange) {
+        let old_last = self.lines().len();
+
+        // The old lines the change falls on (indices into self.lines)
+        let first = self.line_index(change.range.start);
+        let last = self.line_index(change.range.end);
+        let start = self.line_starts[first];
+        let old_end = self.line_end(last);
+
+        self.text
+            .replace_range(change.range.clone(), &change.new_text);
+        let byte_delta = change.new_text.len() as isize - change.range.len() as isize;
+        let new_end = (old_end as isize + byte_delta) as usize;
+
+        // Parse what now stands where those lines were (the last of them
+        // ends at a `\n` unless it's the end of the text)
+        let followed_by_newline = new_end < self.text.len();
+        let replaced = parse_lines(
+            &self.text[start..new_end],
+            first + 1,
+            followed_by_newline,
+            self.parser(),
+        );
+        let removed = last - first + 1;
+        let line_delta = replaced.len() as isize - removed as isize;
+        let structural_change = self.lines[first..=last]
+            .iter()
+            .chain(&replaced)
+            .any(is_structural);
+
+        let new_starts = line_starts(&self.text[start..new_end], start);
+        self.line_starts.splice(first..=last, new_starts);
+        self.lines.splice(first..=last, replaced);
+
+        // Lines after the change only moved
+        let after = (first as isize + removed as isize + line_delta) as usize;
+        for offset in &mut self.line_starts[after..] {
+            *offset = (*offset as isize + byte_delta) as usize;
+        }
+        for line in &mut self.lines[after..] {
+            line.line_number = (line.line_number as isize + line_delta) as usize;
+        }
+
+        if structural_change {
+            self.structure = parser::extract_structure(self.lines());
+        } else {
+            let new_last = self.lines().len();
+            shift_structure(
+                &mut self.structure,
+                last + 1,
+                line_delta,
+                old_last,
+                new_last,
+            );
+        }
+    }
+
+    /// Index of the line containing byte `offset` (a line's `\n` belongs
+    /// to it, and the end of the text to the last line)
+    fn line_index(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset) - 1
+    }
+
+    /// Byte offset just past the end of line `index`, before its `\n`
+    fn line_end(&self, index: usize) -> usize {
+        self.line_starts
+            .get(index + 1)
+            .map_or(self.text.len(), |next| next - 1)
+    }
+}
+
+// ============================================================================
+// HELPERS
+// ============================================================================
+
+/// Parse each `\n`-separated piece of `text` with `parser`, numbering from
+/// `first_number`
+///
+/// A `\r` before a `\n` is dropped, as str::lines() drops it.
+/// `followed_by_newline` says whether the last piece has a `\n` after it
+/// in the full text.
+fn parse_lines(
+    text: &str,
+    first_number: usize,
+    followed_by_newline: bool,
+    parser: &dyn DocumentParser,
+) -> Vec<ParsedLine> {
+    let pieces: Vec<&str> = text.split('\n').collect();
+    let count = pieces.len();
+    pieces
+        .into_iter()
+        .enumerate()
+        .map(|(i, piece)| {
+            let piece = if i + 1 < count || followed_by_newline {
+                piece.strip_suffix('\r').unwrap_or(piece)
+            } else {
+                piece
+            };
+            parser.parse_line(piece, first_number + i)
+        })
+        .collect()
+}
+
+/// Where each `\n`-separated piece of `text` starts, plus `base`
+fn line_starts(text: &str, base: usize) -> Vec<usize> {
+    std::iter::once(base)
+        .chain(text.match_indices('\n').map(|(i, _)| base + i + 1))
+        .collect()
+}
+
+/// Does this line start or end an act, chapter or scene?
+fn is_structural(line: &ParsedLine) -> bool {
+    matches!(
+        line.tag,
+        Some(TagType::Act(_) | TagType::Chapter(_) | TagType::Scene(_))
+    )
+}
+
+/// Move line ranges after an edit that added or removed no structural tags
+///
+/// `last_edited` is the last edited line (1-based, old numbering); it and
+/// the lines after it have moved by `delta`. Nothing can start
+/// among the edited lines, since only tags start entries, and nothing can
+/// end before the last of them, since an entry ends the line before a tag.
+/// The one exception is whatever runs to the end of the document, whose
+/// last line is `old_last` before and `new_last` after.
+fn shift_structure(
+    structure: &mut DocumentStructure,
+    last_edited: usize,
+    delta: isize,
+    old_last: usize,
+    new_last: usize,
+) {
+    let shift = |line: &mut usize| {
+        if *line >= last_edited {
+            *line = (*line as isize + delta) as usize;
+        }
+    };
+    let shift_end = |line: &mut usize| {
+        if *line == old_last {
+            *line = new_last;
+        } else {
+            shift(line);
+        }
+    };
+
+    for act in &mut structure.acts {
+        shift(&mut act.line_start);
+        shift_end(&mut act.line_end);
+    }
+    for chapter in &mut structure.chapters {
+        shift(&mut chapter.line_start);
+        shift_end(&mut chapter.line_end);
+    }
+    for scene in &mut structure.scenes {
+        shift(&mut scene.line_start);
+        shift_end(&mut scene.line_end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "\
+[ACT: I]
+[CHAPTER: One]
+[SCENE: Beach]
+The sea was calm.
+
+HERO
+What a day.
+[SCENE: Cave]
+Dark inside.
+[CHAPTER: Two]
+The end.
+";
+
+    /// The incremental result must be what a full parse gives
+    fn assert_matches_full_parse(parsed: &ParsedDocument, text: &str) {
+        let full = parser::parse_document(text);
+        assert_eq!(
+            format!("{:?}", parsed.lines()),
+            format!("{:?}", full),
+            "lines of {:?}",
+            text
+        );
+        assert_eq!(
+            format!("{:?}", parsed.structure()),
+            format!("{:?}", parser::extract_structure(&full)),
+            "structure of {:?}",
+            text
+        );
+    }
+
+    /// Parse TEXT, update it to `edited`, and check it
+    fn check_edit(edited: &str) {
+        let mut parsed = ParsedDocument::new(TEXT, DocumentFormat::BookScript);
+        assert!(parsed.update(edited));
+        assert_matches_full_parse(&parsed, edited);
+        assert!(!parsed.update(edited));
+        // And back again
+        assert!(parsed.update(TEXT));
+        assert_matches_full_parse(&parsed, TEXT);
+    }
+
+    #[test]
+    fn edits_inside_a_line_keep_the_structure_in_step() {
+        check_edit(&TEXT.replacen("calm", "rough and grey", 1));
+        check_edit(&TEXT.replacen("What a day.", "", 1));
+        check_edit(&TEXT.replacen("[SCENE: Cave]", "[SCENE: Deep Cave]", 1));
+        check_edit(&TEXT.replacen("The end.\n", "The end.", 1));
+    }
+
+    #[test]
+    fn adding_and_removing_tags() {
+        // New tags of every level, mid-chapter and at the ends
+        check_edit(&TEXT.replacen("Dark inside.\n", "Dark inside.\n[SCENE: Pier]\n", 1));
+        check_edit(&TEXT.replacen("The sea", "[CHAPTER: Half]\nThe sea", 1));
+        check_edit(&format!("{}[ACT: II]\nMore.\n", TEXT));
+        check_edit(&format!("[CHAPTER: Prologue]\n{}", TEXT));
+        // Tags removed
+        check_edit(&TEXT.replacen("[SCENE: Beach]\n", "", 1));
+        check_edit(&TEXT.replacen("[ACT: I]\n", "", 1));
+        check_edit(&TEXT.replacen("[CHAPTER: Two]\nThe end.\n", "", 1));
+        // A tag turned into text, and text into a tag, in place
+        check_edit(&TEXT.replacen("[SCENE: Cave]", "SCENE: Cave", 1));
+        check_edit(&TEXT.replacen("Dark inside.", "[SCENE: Inside]", 1));
+        // A cue that stops being one
+        check_edit(&TEXT.replacen("HERO\n", "HERO said\n", 1));
+    }
+
+    #[test]
+    fn splitting_and_joining_lines() {
+        // Splitting text, and splitting a tag off the line before it
+        check_edit(&TEXT.replacen("sea was", "sea\nwas", 1));
+        check_edit(&TEXT.replacen("calm.\n", "calm. [SCENE: Pier]\n", 1));
+        check_edit(&TEXT.replacen("calm.", "calm.\n[SCENE: Pier]", 1));
+        // Joining text lines, a tag onto text, and two tags
+        check_edit(&TEXT.replacen("Dark inside.\n[CHAPTER", "Dark inside.[CHAPTER", 1));
+        check_edit(&TEXT.replacen("HERO\nWhat", "HERO What", 1));
+        check_edit(&TEXT.replacen("[ACT: I]\n[CHAPTER: One]", "[ACT: I][CHAPTER: One]", 1));
+        // Several lines at once, across chapters
+        check_edit(&TEXT.replacen("Dark inside.\n[CHAPTER: Two]\nThe", "The", 1));
+        // Blank lines and Windows line breaks
+        check_edit(&TEXT.replacen("\n\n", "\n\n\n\n", 1));
+        check_edit(&TEXT.replace('\n', "\r\n"));
+    }
+
+    #[test]
+    fn typing_one_character_at_a_time() {
+        let mut parsed = ParsedDocument::new("", DocumentFormat::BookScript);
+        let mut text = String::new();
+        for c in TEXT.chars() {
+            text.push(c);
+            parsed.update(&text);
+            assert_matches_full_parse(&parsed, &text);
+        }
+        // Then deleting from the front
+        while !text.is_empty() {
+            text.remove(0);
+            parsed.update(&text);
+            assert_matches_full_parse(&parsed, &text);
+        }
+    }
+
+    #[test]
+    fn the_smallest_change_is_found() {
+        assert_eq!(TextChange::between("same", "same"), None);
+        assert_eq!(
+            TextChange::between("The cat sat", "The bat sat"),
+            Some(TextChange {
+                range: 4..5,
+                new_text: String::from("b")
+            })
+        );
+        // Repeated letters: the common end doesn't overlap the start
+        assert_eq!(
+            TextChange::between("aaa", "aaaa"),
+            Some(TextChange {
+                range: 3..3,
+                new_text: String::from("a")
+            })
+        );
+        // Never splits a character
+        assert_eq!(
+            TextChange::between("café", "cafè"),
+            Some(TextChange {
+                range: 3..5,
+                new_text: String::from("è")
+            })
+        );
+    }
+
+    #[test]
+    fn changing_the_format_reparses_every_line() {
+        let text = "# One\n[CHAPTER: Two]\nText";
+        let mut parsed = ParsedDocument::new(text, DocumentFormat::BookScript);
+        let chapters = |p: &ParsedDocument| -> Vec<String> {
+            p.structure()
+                .chapters
+                .iter()
+                .map(|c| c.title.clone())
+                .collect()
+        };
+        assert_eq!(chapters(&parsed), ["Two"]);
+
+        assert!(parsed.set_format(DocumentFormat::Markdown));
+        assert_eq!(chapters(&parsed), ["One"]);
+        assert!(!parsed.set_format(DocumentFormat::Markdown));
+
+        assert!(parsed.set_format(DocumentFormat::Plain));
+        assert!(chapters(&parsed).is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+//! FILE: src/inspector.rs
+//!
+//! The View → Scene Inspector panel: the description, status, point of
+//! view and synopsis of the scene the caret is in.
+//!
+//! The metadata lives in the scene's tag (see parser::SceneMeta), so it
+//! moves with the scene when the text is rearranged and needs no file of
+//! its own. Editing a field here writes the tag line again.
+//!
+//! The panel edits a SceneDraft rather than the tag directly: the tag
+//! trims what it holds ("They " comes back as "They"), so while a field is
+//! being typed in, App keeps the draft instead of reading it back from the
+//! text.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - egui::ComboBox for choosing from a short list
+//! - Option<&mut T> for "maybe something to edit"
+
+use crate::parser::{self, Scene, SceneMeta, SceneStatus};
+
+/// The scene being inspected, as the panel edits it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneDraft {
+    /// 1-based line of the scene's tag
+    pub line: usize,
+    pub description: String,
+    pub meta: SceneMeta,
+}
+
+impl SceneDraft {
+    pub fn of(scene: &Scene) -> Self {
+        Self {
+            line: scene.line_start,
+            description: scene.description.clone(),
+            meta: scene.meta.clone(),
+        }
+    }
+
+    /// The tag line the draft is written as
+    pub fn tag(&self) -> String {
+        parser::scene_tag(&self.description, &self.meta)
+    }
+}
+
+/// What happened in the panel this frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InspectorOutput {
+    /// The draft changed: write its tag
+    pub changed: bool,
+    /// A text field has the keyboard, so the draft mustn't be replaced
+    pub editing: bool,
+}
+
+/// Draw the panel for `draft` (None when the caret isn't in a scene)
+///
+/// `names` are offered for the point of view.
+pub fn show(
+    ui: &mut egui::Ui,
+    draft: Option<&mut SceneDraft>,
+    names: &[String],
+) -> InspectorOutput {
+    let mut output = InspectorOutput::default();
+    let Some(draft) = draft else {
+        ui.label("The caret isn't in a scene.");
+        ui.weak("Scenes start at [SCENE: ...] tags.");
+        return output;
+    };
+    ui.weak(format!("Line {}", draft.line));
+
+    // The text fields, to see afterwards whether any changed or has focus
+    let mut fields = Vec::new();
+
+    egui::Grid::new("scene_inspector")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Scene:");
+            fields.push(ui.text_edit_singleline(&mut draft.description));
+            ui.end_row();
+
+            ui.label("Status:");
+            let before = draft.meta.status;
+            egui::ComboBox::from_id_salt("scene_status")
+                .selected_text(draft.meta.status.map_or("-", |s| s.key()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut draft.meta.status, None, "-");
+                    for status in SceneStatus::ALL {
+                        ui.selectable_value(&mut draft.meta.status, Some(status), status.key());
+                    }
+                });
+            if draft.meta.status != before {
+                output.changed = true;
+            }
+            ui.end_row();
+
+            ui.label("POV:");
+            ui.horizontal(|ui| {
+                fields.push(
+                    ui.add(egui::TextEdit::singleline(&mut draft.meta.pov).desired_width(120.0)),
+                );
+                ui.add_enabled_ui(!names.is_empty(), |ui| {
+                    ui.menu_button("▾", |ui| {
+                        for name in names {
+                            if ui.button(name).clicked() {
+                                draft.meta.pov = name.clone();
+                                output.changed = true;
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                });
+            });
+            ui.end_row();
+        });
+
+    ui.label("Synopsis:");
+    fields.push(
+        ui.add(
+            egui::TextEdit::multiline(&mut draft.meta.synopsis)
+                .desired_rows(5)
+                .desired_width(f32::INFINITY),
+        ),
+    );
+
+    for response in &fields {
+        output.changed |= response.changed();
+        output.editing |= response.has_focus();
+    }
+    output
+}
@@ -0,0 +1,407 @@
+//! FILE: src/instance.rs
+//!
+//! One copy of the app at a time. Two copies would autosave, and write the
+//! session and settings files, over each other. So starting the app while
+//! it's already running hands the command line to the running copy
+//! instead: that copy opens the file (or a new document) and comes to the
+//! front, and the new process exits without opening a window.
+//!
+//! HOW THE COPIES FIND EACH OTHER:
+//! The running copy listens on a TCP port of 127.0.0.1 - the loopback
+//! address, which nothing outside this computer can reach - and writes the
+//! port and a random key to `<data dir>/instance.lock`. A new copy reads
+//! that, connects, and sends the key and its request as one line of JSON;
+//! the running copy answers "ok". The lock file is readable by the user
+//! alone, so other users' processes can't learn the key.
+//!
+//! With no lock file, nothing answering at its port (the app crashed and
+//! left it behind) or no answer in time (the app is hung), the new copy
+//! takes over: it listens itself and writes a lock file of its own. The
+//! lock file is removed when the app closes. A copy that can't listen at
+//! all runs on its own, as before.
+//!
+//! Copies started at the same moment (a file manager opening several
+//! files at once starts one per file) can't both win: the lock file is
+//! written under another name and hard-linked into place, which fails if
+//! it's there already, so it appears complete and only once. A copy that
+//! loses goes back to handing over to the one that won.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - std::net: TcpListener, TcpStream and connect_timeout
+//! - A thread blocked in accept() for the life of the app, waking the GUI
+//!   with Context::request_repaint
+
+use crate::app::StartupDocument;
+use crate::storage;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// How long either side waits for the other
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Longest message read, in bytes (a path is far shorter)
+const MAX_MESSAGE: u64 = 64 * 1024;
+
+/// How many times claim() tries before running alone, when other copies
+/// keep claiming the lock file first
+const CLAIM_ATTEMPTS: usize = 3;
+
+/// What a new copy asks the running one to do
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Request {
+    /// Open this file (`bookscript FILE`); the path is absolute
+    Open(PathBuf),
+    /// Start a new, empty document (`bookscript --new`)
+    New,
+    /// Just come to the front (no arguments)
+    Show,
+}
+
+impl From<&StartupDocument> for Request {
+    fn from(startup: &StartupDocument) -> Self {
+        match startup {
+            StartupDocument::File(path) => Request::Open(path.clone()),
+            StartupDocument::Empty => Request::New,
+            StartupDocument::Restore => Request::Show,
+        }
+    }
+}
+
+/// What the lock file holds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct LockInfo {
+    port: u16,
+    key: String,
+}
+
+/// What a new copy sends, as one line of JSON
+#[derive(Debug, Serialize, Deserialize)]
+struct Message {
+    key: String,
+    request: Request,
+}
+
+/// How claim() went
+pub enum Claim {
+    /// The running copy took the request; this one should exit
+    Handed,
+    /// This is the only copy. The server goes to App, which answers other
+    /// copies through it; main() releases the lock as the app closes.
+    First { server: Server, lock: Lock },
+    /// Other copies couldn't be checked for; run anyway
+    Alone,
+}
+
+/// Hand `startup` to the running copy if there is one, or become the
+/// running copy (see module docs)
+pub fn claim(startup: &StartupDocument) -> Claim {
+    let Ok(path) = storage::get_instance_lock_path() else {
+        return Claim::Alone;
+    };
+    let request = Request::from(startup);
+    for _ in 0..CLAIM_ATTEMPTS {
+        let found = fs::read_to_string(&path).ok();
+        if let Some(info) = found.as_deref().and_then(parse_lock) {
+            if hand_over(&info, &request).is_ok() {
+                return Claim::Handed;
+            }
+        }
+        match listen(&path, found.as_deref()) {
+            Ok(Some(claim)) => return claim,
+            // Another copy claimed it first
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("Not checking for other copies of the app: {:#}", e);
+                return Claim::Alone;
+            }
+        }
+    }
+    eprintln!("Not checking for other copies of the app: the lock file keeps changing");
+    Claim::Alone
+}
+
+/// The lock file's contents, if it's there and readable
+fn read_lock(path: &Path) -> Option<LockInfo> {
+    parse_lock(&fs::read_to_string(path).ok()?)
+}
+
+fn parse_lock(contents: &str) -> Option<LockInfo> {
+    serde_json::from_str(contents).ok()
+}
+
+/// Send `request` to the copy `info` names; Ok once it has said "ok"
+fn hand_over(info: &LockInfo, request: &Request) -> Result<()> {
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, info.port));
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let message = Message {
+        key: info.key.clone(),
+        request: request.clone(),
+    };
+    writeln!(stream, "{}", serde_json::to_string(&message)?)?;
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer)?;
+    if answer.trim() != "ok" {
+        bail!("Unexpected answer: {}", answer.trim());
+    }
+    Ok(())
+}
+
+/// Start listening and write the lock file naming this copy, in place of
+/// `stale` - the lock file as claim() found it, if it was there
+///
+/// Ok(None) if another copy wrote a lock file of its own first.
+fn listen(path: &Path, stale: Option<&str>) -> Result<Option<Claim>> {
+    if let Some(stale) = stale {
+        if fs::read_to_string(path).ok().as_deref() != Some(stale) {
+            return Ok(None);
+        }
+        let _ = fs::remove_file(path);
+    }
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let mut key = [0u8; 16];
+    OsRng.fill_bytes(&mut key);
+    let info = LockInfo {
+        port: listener.local_addr()?.port(),
+        key: key.iter().map(|b| format!("{:02x}", b)).collect(),
+    };
+    if !write_lock(path, &info)? {
+        return Ok(None);
+    }
+    Ok(Some(Claim::First {
+        server: Server {
+            listener,
+            key: info.key.clone(),
+        },
+        lock: Lock {
+            path: path.to_path_buf(),
+            info,
+        },
+    }))
+}
+
+/// Write the lock file unless there is one already (see module docs),
+/// readable by the user alone on Unix (elsewhere the data folder is
+/// already private to the user)
+///
+/// Returns false if there was one.
+fn write_lock(path: &Path, info: &LockInfo) -> Result<bool> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp = path.with_extension(format!("lock.{}", info.key));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let written = options.open(&temp).and_then(|mut file| {
+        file.write_all(serde_json::to_string(info)?.as_bytes())?;
+        file.sync_all()
+    });
+    let linked = written.and_then(|()| fs::hard_link(&temp, path));
+    let _ = fs::remove_file(&temp);
+    match linked {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e).with_context(|| format!("Failed to write {}", path.display())),
+    }
+}
+
+/// The running copy's end: where other copies' requests arrive
+pub struct Server {
+    listener: TcpListener,
+    key: String,
+}
+
+impl Server {
+    /// Answer other copies on a thread of its own
+    ///
+    /// Their requests come out of the Receiver, and each one wakes `ctx`
+    /// so that a frame runs to handle it.
+    pub fn serve(self, ctx: egui::Context) -> Receiver<Request> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in self.listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                if let Some(request) = self.receive(stream) {
+                    if sender.send(request).is_err() {
+                        return;
+                    }
+                    ctx.request_repaint();
+                }
+            }
+        });
+        receiver
+    }
+
+    /// Read one message, answering "ok" if it has the key
+    fn receive(&self, mut stream: TcpStream) -> Option<Request> {
+        stream.set_read_timeout(Some(TIMEOUT)).ok()?;
+        let mut line = String::new();
+        BufReader::new((&stream).take(MAX_MESSAGE))
+            .read_line(&mut line)
+            .ok()?;
+        let message: Message = serde_json::from_str(&line).ok()?;
+        if message.key != self.key {
+            return None;
+        }
+        stream.write_all(b"ok\n").ok()?;
+        Some(message.request)
+    }
+}
+
+/// The lock file this copy wrote
+pub struct Lock {
+    path: PathBuf,
+    info: LockInfo,
+}
+
+impl Lock {
+    /// Remove the lock file as the app closes, unless another copy has
+    /// taken it over since (this one was hung, say)
+    pub fn release(&self) {
+        if read_lock(&self.path).as_ref() == Some(&self.info) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_lock(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "bookscript-test-{}-instance-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("instance.lock")
+    }
+
+    fn claimed(path: &Path, stale: Option<&str>) -> (Server, Lock) {
+        match listen(path, stale).unwrap() {
+            Some(Claim::First { server, lock }) => (server, lock),
+            _ => panic!("expected to claim {}", path.display()),
+        }
+    }
+
+    /// Hand `request` to the copy `info` names, `server` answering on a
+    /// thread; what the server took in, and whether the hand-over worked
+    fn exchange(server: Server, info: &LockInfo, request: &Request) -> (Option<Request>, bool) {
+        let answering = thread::spawn(move || {
+            let (stream, _) = server.listener.accept().unwrap();
+            server.receive(stream)
+        });
+        let handed = hand_over(info, request).is_ok();
+        (answering.join().unwrap(), handed)
+    }
+
+    #[test]
+    fn the_lock_file_is_written_once() {
+        let path = scratch_lock("once");
+        let (_server, lock) = claimed(&path, None);
+        assert_eq!(read_lock(&path), Some(lock.info.clone()));
+        assert_eq!(lock.info.key.len(), 32);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        // A second copy finds it there
+        assert!(matches!(listen(&path, None), Ok(None)));
+        // The temporary file it was written as is gone
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn a_stale_lock_is_replaced_unless_it_changed() {
+        let path = scratch_lock("stale");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "{\"port\":1,\"key\":\"old\"}").unwrap();
+        assert!(matches!(listen(&path, Some("something else")), Ok(None)));
+
+        let (_server, lock) = claimed(&path, Some("{\"port\":1,\"key\":\"old\"}"));
+        assert_ne!(lock.info.key, "old");
+        assert_eq!(read_lock(&path), Some(lock.info.clone()));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn a_request_with_the_key_is_handed_over() {
+        let path = scratch_lock("hand");
+        let (server, lock) = claimed(&path, None);
+        let request = Request::Open(PathBuf::from("/books/novel.bks"));
+        assert_eq!(
+            exchange(server, &lock.info, &request),
+            (Some(request), true)
+        );
+
+        let (server, lock) = claimed(&scratch_lock("wrong-key"), None);
+        let wrong = LockInfo {
+            key: String::from("not the key"),
+            ..lock.info.clone()
+        };
+        assert_eq!(exchange(server, &wrong, &Request::Show), (None, false));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        fs::remove_dir_all(lock.path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn nothing_listening_is_an_error() {
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let info = LockInfo {
+            port,
+            key: String::from("k"),
+        };
+        assert!(hand_over(&info, &Request::New).is_err());
+    }
+
+    #[test]
+    fn release_leaves_a_lock_taken_over_since() {
+        let path = scratch_lock("release");
+        let (_server, lock) = claimed(&path, None);
+        fs::write(&path, "{\"port\":2,\"key\":\"newer\"}").unwrap();
+        lock.release();
+        assert!(path.exists());
+
+        fs::remove_file(&path).unwrap();
+        let (_server, lock) = claimed(&path, None);
+        lock.release();
+        assert!(!path.exists());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn startup_documents_become_requests() {
+        let path = PathBuf::from("/books/novel.bks");
+        assert_eq!(
+            Request::from(&StartupDocument::File(path.clone())),
+            Request::Open(path)
+        );
+        assert_eq!(Request::from(&StartupDocument::Empty), Request::New);
+        assert_eq!(Request::from(&StartupDocument::Restore), Request::Show);
+        assert_eq!(parse_lock("not json"), None);
+    }
+}
@@ -1,13 +1,14 @@
-/// FILE: src/main.rs
-///
-/// This is the entry point of our application. When you run `cargo run`, execution
-/// starts at the `main()` function below.
-///
-/// RUST CONCEPTS DEMONSTRATED:
-/// - Module system: Using `mod` to declare modules from other files
-/// - Result<T, E>: Rust's type for operations that can succeed (Ok) or fail (Err)
-/// - Error propagation: Using `?` operator to bubble up errors
-/// - NativeOptions: Configuration struct for the eframe window
+//! FILE: src/main.rs
+//!
+//! This is the entry point of our application. When you run `cargo run`, execution
+//! starts at the `main()` function below.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Module system: Using `mod` to declare modules from other files
+//! - Result<T, E>: Rust's type for operations that can succeed (Ok) or fail (Err)
+//! - Error propagation: Using `?` operator to bubble up errors
+//! - NativeOptions: Configuration struct for the eframe window
+//! - std::env::args: Reading command-line arguments
 
 // ============================================================================
 // MODULE DECLARATIONS
@@ -16,12 +17,112 @@
 // - `mod app` → looks for src/app.rs
 // - `mod storage` → looks for src/storage.rs
 // - `mod parser` → looks for src/parser.rs
+// - `mod format` → looks for src/format.rs
+// - `mod editing` → looks for src/editing.rs
+// - `mod settings` → looks for src/settings.rs
+// - `mod profile` → looks for src/profile.rs
+// - `mod outline` → looks for src/outline.rs
+// - `mod dialogs` → looks for src/dialogs.rs
+// - `mod history` → looks for src/history.rs
+// - `mod export` → looks for src/export.rs
+// - `mod problems` → looks for src/problems.rs
+// - `mod file_io` → looks for src/file_io.rs
+// - `mod characters` → looks for src/characters.rs
+// - `mod goals` → looks for src/goals.rs
+// - `mod encoding` → looks for src/encoding.rs
+// - `mod text_utils` → looks for src/text_utils.rs
+// - `mod incremental` → looks for src/incremental.rs
+// - `mod search` → looks for src/search.rs
+// - `mod pdf` → looks for src/pdf.rs
+// - `mod fdx` → looks for src/fdx.rs
+// - `mod docx` → looks for src/docx.rs
+// - `mod project` → looks for src/project.rs
+// - `mod compile` → looks for src/compile.rs
+// - `mod theme` → looks for src/theme.rs
+// - `mod fonts` → looks for src/fonts.rs
+// - `mod tabs` → looks for src/tabs.rs
+// - `mod completion` → looks for src/completion.rs
+// - `mod inspector` → looks for src/inspector.rs
+// - `mod corkboard` → looks for src/corkboard.rs
+// - `mod comments` → looks for src/comments.rs
+// - `mod bookmarks` → looks for src/bookmarks.rs
+// - `mod diff` → looks for src/diff.rs
+// - `mod git` → looks for src/git.rs
+// - `mod encryption` → looks for src/encryption.rs
+// - `mod print` → looks for src/print.rs
+// - `mod toc` → looks for src/toc.rs
+// - `mod formatted` → looks for src/formatted.rs
+// - `mod templates` → looks for src/templates.rs
+// - `mod snippets` → looks for src/snippets.rs
+// - `mod statistics` → looks for src/statistics.rs
+// - `mod readability` → looks for src/readability.rs
+// - `mod style` → looks for src/style.rs
+// - `mod autocorrect` → looks for src/autocorrect.rs
+// - `mod thesaurus` → looks for src/thesaurus.rs
+// - `mod read_aloud` → looks for src/read_aloud.rs
+// - `mod project_search` → looks for src/project_search.rs
+// - `mod search_index` → looks for src/search_index.rs
+// - `mod cli` → looks for src/cli.rs
+// - `mod instance` → looks for src/instance.rs
+// - `mod file_types` → looks for src/file_types.rs
 //
 // This keeps our code organized and maintainable.
 
 mod app;
-mod storage;
+mod autocorrect;
+mod bookmarks;
+mod characters;
+mod cli;
+mod comments;
+mod compile;
+mod completion;
+mod corkboard;
+mod dialogs;
+mod diff;
+mod docx;
+mod editing;
+mod encoding;
+mod encryption;
+mod epub;
+mod export;
+mod fdx;
+mod file_io;
+mod file_types;
+mod fonts;
+mod format;
+mod formatted;
+mod git;
+mod goals;
+mod history;
+mod incremental;
+mod inspector;
+mod instance;
+mod outline;
 mod parser;
+mod pdf;
+mod print;
+mod problems;
+mod profile;
+mod project;
+mod project_search;
+mod read_aloud;
+mod readability;
+mod search;
+mod search_index;
+mod settings;
+mod snippets;
+mod statistics;
+mod storage;
+mod style;
+mod tabs;
+mod templates;
+mod text_utils;
+mod theme;
+mod thesaurus;
+mod toc;
+
+use app::StartupDocument;
+use std::sync::Arc;
 
 // ============================================================================
 // MAIN FUNCTION - PROGRAM ENTRY POINT
@@ -36,21 +137,65 @@ mod parser;
 /// The `-> Result<(), eframe::Error>` syntax is Rust's way of saying
 /// "this function might fail, and if it does, here's the error type."
 fn main() -> Result<(), eframe::Error> {
+    // ------------------------------------------------------------------------
+    // COMMAND LINE
+    // ------------------------------------------------------------------------
+    // Checked first: a bad argument should print usage and exit before any
+    // window appears. env::args() includes the program name, hence skip(1).
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // `bookscript export ...` and the other subcommands (cli.rs) do their
+    // work and exit without ever opening a window
+    if let Some(status) = cli::run(&args, load_settings) {
+        std::process::exit(status);
+    }
+
+    let startup = match parse_args(args.into_iter()) {
+        Ok(Some(startup)) => startup,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Err(message) => {
+            eprintln!("bookscript: {}\n\n{}", message, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    // ------------------------------------------------------------------------
+    // ONE COPY AT A TIME
+    // ------------------------------------------------------------------------
+    // If the app is already running, it opens this document and comes to
+    // the front, and this process is done (see instance.rs)
+    let (server, lock) = match instance::claim(&startup) {
+        instance::Claim::Handed => return Ok(()),
+        instance::Claim::First { server, lock } => (Some(server), Some(lock)),
+        instance::Claim::Alone => (None, None),
+    };
+
     // ------------------------------------------------------------------------
     // WINDOW CONFIGURATION
     // ------------------------------------------------------------------------
     // NativeOptions is a struct that configures our application window.
     // We use a struct initialization syntax with named fields.
-    let options = eframe::NativeOptions {
-        // viewport_builder configures the initial window appearance
-        viewport: egui::ViewportBuilder::default()
-            // Set the initial window size to 1024x768 pixels
-            .with_inner_size([1024.0, 768.0])
-            // Set the minimum window size to prevent it from being too small
-            .with_min_inner_size([400.0, 300.0])
-            // Set the window title that appears in the title bar
-            .with_title("BookScript Writer"),
+    // Preferences are read before the window exists so it can reopen at the
+    // size and position it had last time
+    let settings = load_settings();
 
+    // viewport_builder configures the initial window appearance
+    let mut viewport = egui::ViewportBuilder::default()
+        // The last size, or 1024x768 pixels the first time
+        .with_inner_size(settings.window.size.unwrap_or([1024.0, 768.0]))
+        // Set the minimum window size to prevent it from being too small
+        .with_min_inner_size([400.0, 300.0])
+        // Set the window title that appears in the title bar
+        .with_title("BookScript Writer");
+    if let Some(position) = settings.window.position {
+        viewport = viewport.with_position(position);
+    }
+
+    let options = eframe::NativeOptions {
+        viewport,
         // Use default values for all other NativeOptions fields
         ..Default::default()
     };
@@ -72,7 +217,7 @@ fn main() -> Result<(), eframe::Error> {
     // Box::new allocates our app on the heap (not the stack) and gives
     // eframe ownership of it. eframe will keep the app alive until the
     // window is closed.
-    eframe::run_native(
+    let result = eframe::run_native(
         "BookScript Writer",
         options,
         // This closure is called once when the app starts
@@ -81,11 +226,98 @@ fn main() -> Result<(), eframe::Error> {
             // Create and return our App instance
             // `Ok(Box::new(...))` means "successfully created the app"
             // The ? operator would propagate any errors from App::new()
-            Ok(Box::new(app::App::new(cc)))
+            Ok(Box::new(app::App::new(
+                cc,
+                settings,
+                startup,
+                Arc::new(storage::DiskStorage),
+                server,
+            )))
         }),
-    )
-    // The `?` operator here means: "if run_native returns an error, return
-    // that error from main() immediately. Otherwise, continue."
+    );
+
+    // The window has closed: the next copy to start is the first again
+    if let Some(lock) = lock {
+        lock.release();
+    }
+    result
+}
+
+/// Command-line help, printed for --help and after a bad argument
+const USAGE: &str = "\
+Usage: bookscript [--new] [FILE]
+       bookscript export FILE [--format FORMAT] [-o OUTPUT] [--watch]
+       bookscript outline FILE
+       bookscript stats FILE
+
+  FILE       open this document instead of the last session's
+  --new      start with an empty document, ignoring the last session
+  -h, --help show this message
+
+Without opening a window:
+  export     convert FILE to FORMAT: txt, md, html, fountain, fdx, pdf,
+             docx or epub (taken from OUTPUT's extension if not given). Written
+             next to FILE unless -o names the output; -o - is stdout.
+             --watch keeps running, exporting again whenever FILE changes
+  outline    list FILE's acts, chapters and scenes with their line numbers
+             and word counts
+  stats      print FILE's word, page and readability counts
+
+To open a document named like a command, put -- before it.";
+
+/// Work out which document to start with from the command-line arguments
+///
+/// Returns Ok(None) for --help, and Err with a message for anything that
+/// isn't understood: unknown flags, more than one file, or --new together
+/// with a file. A `--` argument ends the flags, for file names starting
+/// with `-`.
+///
+/// A relative path is made absolute here, against the directory the app
+/// was started from, before anything else gets a chance to change it.
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Option<StartupDocument>, String> {
+    let mut new = false;
+    let mut file: Option<String> = None;
+    let mut flags_done = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "--" if !flags_done => flags_done = true,
+            "-h" | "--help" if !flags_done => return Ok(None),
+            "--new" if !flags_done => new = true,
+            flag if !flags_done && flag.starts_with('-') && flag != "-" => {
+                return Err(format!("unknown option: {}", flag));
+            }
+            _ if file.is_some() => {
+                return Err(format!(
+                    "only one file can be opened at a time (got {})",
+                    arg
+                ));
+            }
+            _ => file = Some(arg),
+        }
+    }
+
+    match (new, file) {
+        (true, Some(_)) => Err("--new can't be combined with a file".to_string()),
+        (true, None) => Ok(Some(StartupDocument::Empty)),
+        (false, None) => Ok(Some(StartupDocument::Restore)),
+        (false, Some(file)) => std::path::absolute(&file)
+            .map(|path| Some(StartupDocument::File(path)))
+            .map_err(|e| format!("can't resolve {}: {}", file, e)),
+    }
+}
+
+/// Read the saved preferences, falling back to defaults
+///
+/// A missing or corrupt settings file must never stop the app from
+/// starting, so errors are logged and the defaults used instead.
+fn load_settings() -> settings::Settings {
+    storage::get_settings_path()
+        .and_then(|path| storage::load_settings(&path))
+        .unwrap_or_else(|e| {
+            eprintln!("Using default settings: {}", e);
+            settings::Settings::default()
+        })
 }
 
 // ============================================================================
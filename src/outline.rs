@@ -0,0 +1,213 @@
+//! FILE: src/outline.rs
+//!
+//! The outline sidebar: a tree of the document's acts, chapters and scenes.
+//!
+//! This module only DRAWS the outline. It doesn't own any state - App passes
+//! in the current DocumentStructure and gets back the line the user clicked
+//! (if any), then decides what to do with it. to_text() writes the same
+//! tree out as text, for `bookscript outline`.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Returning Option<T> from UI code to report user actions
+//! - Iterator filtering to group flat lists into a tree
+//! - egui::collapsing_header::CollapsingState for custom collapsible headers
+
+use crate::app::format_count;
+use crate::parser::{Chapter, DocumentStructure, Scene};
+use std::ops::RangeInclusive;
+
+/// Draw the outline tree
+///
+/// Returns the 1-based line number of the entry the user clicked, if any.
+///
+/// Acts and chapters are collapsible headers: acts hold the chapters in
+/// their line range, chapters hold their scenes. Chapters and scenes that
+/// come before the first act (or in a document with no acts) sit at the top
+/// level, and scenes outside every chapter are listed on their own. Every
+/// chapter and scene shows its word count (see parser::annotate_structure).
+pub fn show(ui: &mut egui::Ui, structure: &DocumentStructure) -> Option<usize> {
+    if structure.acts.is_empty() && structure.chapters.is_empty() && structure.scenes.is_empty() {
+        ui.label("No structure found.");
+        ui.label("Add [CHAPTER: ...] or [SCENE: ...] tags to build an outline.");
+        return None;
+    }
+
+    let mut clicked = None;
+    egui::ScrollArea::vertical().show(ui, |ui| tree(ui, structure, &mut clicked));
+    clicked
+}
+
+/// Draw the tree itself, without a scroll area of its own (the project
+/// sidebar nests it under the open file's entry)
+///
+/// Sets `clicked` to the 1-based line number of the entry clicked, if any.
+pub fn tree(ui: &mut egui::Ui, structure: &DocumentStructure, clicked: &mut Option<usize>) {
+    // Front matter (and text between an act tag and its first chapter)
+    // belongs to no chapter, but still counts towards the total
+    if !structure.chapters.is_empty() && structure.unchaptered_words > 0 {
+        ui.weak(format!(
+            "Outside chapters: {} words",
+            format_count(structure.unchaptered_words)
+        ));
+    }
+
+    // Everything before the first act
+    let first_act = structure.acts.first().map_or(usize::MAX, |a| a.line_start);
+    entries(ui, structure, 1..=first_act.saturating_sub(1), clicked);
+
+    for (index, act) in structure.acts.iter().enumerate() {
+        let id = ui.make_persistent_id(("outline_act", index));
+        egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, true)
+            .show_header(ui, |ui| {
+                if ui
+                    .selectable_label(false, format!("Act: {}", act.title))
+                    .clicked()
+                {
+                    *clicked = Some(act.line_start);
+                }
+            })
+            .body(|ui| {
+                entries(ui, structure, act.line_start..=act.line_end, clicked);
+            });
+    }
+}
+
+/// Draw the chapters and loose scenes that start within `lines`
+fn entries(
+    ui: &mut egui::Ui,
+    structure: &DocumentStructure,
+    lines: RangeInclusive<usize>,
+    clicked: &mut Option<usize>,
+) {
+    // Scenes not inside any chapter's line range
+    for scene in structure
+        .scenes
+        .iter()
+        .filter(|s| lines.contains(&s.line_start))
+        .filter(|s| !structure.chapters.iter().any(|c| contains(c, s)))
+    {
+        if scene_label(ui, scene) {
+            *clicked = Some(scene.line_start);
+        }
+    }
+
+    for (index, chapter) in structure
+        .chapters
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| lines.contains(&c.line_start))
+    {
+        // Each header needs a unique, stable Id so egui can remember
+        // whether it's open. The index keeps duplicate titles apart.
+        let id = ui.make_persistent_id(("outline_chapter", index));
+
+        egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, true)
+            .show_header(ui, |ui| {
+                // A selectable label, not the header itself, does the
+                // jumping - so the arrow still just opens and closes
+                if ui.selectable_label(false, chapter_text(chapter)).clicked() {
+                    *clicked = Some(chapter.line_start);
+                }
+            })
+            .body(|ui| {
+                for scene in structure.scenes.iter().filter(|s| contains(chapter, s)) {
+                    if scene_label(ui, scene) {
+                        *clicked = Some(scene.line_start);
+                    }
+                }
+            });
+    }
+}
+
+/// One clickable scene row; returns true when clicked
+///
+/// Hovering shows the scene's synopsis (see parser::SceneMeta).
+fn scene_label(ui: &mut egui::Ui, scene: &Scene) -> bool {
+    let response = ui.selectable_label(false, scene_text(scene));
+    let response = match scene.meta.synopsis.as_str() {
+        "" => response,
+        synopsis => response.on_hover_text(synopsis),
+    };
+    response.clicked()
+}
+
+/// "Scene: Beach (1,200 words)", with the scene's status after the count
+fn scene_text(scene: &Scene) -> String {
+    let mut label = format!(
+        "Scene: {} ({} words)",
+        scene.description,
+        format_count(scene.word_count)
+    );
+    if let Some(status) = scene.meta.status {
+        label.push_str(&format!(" · {}", status.key()));
+    }
+    label
+}
+
+/// "Chapter: Arrival (3,400 words)"
+fn chapter_text(chapter: &Chapter) -> String {
+    format!(
+        "Chapter: {} ({} words)",
+        chapter.title,
+        format_count(chapter.word_count)
+    )
+}
+
+/// The tree as text, one entry a line, indented as the sidebar nests them
+/// and with each entry's line number in a column on the left
+pub fn to_text(structure: &DocumentStructure) -> String {
+    let mut out = String::new();
+    if !structure.chapters.is_empty() && structure.unchaptered_words > 0 {
+        out.push_str(&format!(
+            "Outside chapters: {} words\n",
+            format_count(structure.unchaptered_words)
+        ));
+    }
+    let first_act = structure.acts.first().map_or(usize::MAX, |a| a.line_start);
+    text_entries(&mut out, structure, 1..=first_act.saturating_sub(1), 0);
+    for act in &structure.acts {
+        text_row(&mut out, act.line_start, 0, &format!("Act: {}", act.title));
+        text_entries(&mut out, structure, act.line_start..=act.line_end, 1);
+    }
+    out
+}
+
+/// entries(), as text
+fn text_entries(
+    out: &mut String,
+    structure: &DocumentStructure,
+    lines: RangeInclusive<usize>,
+    depth: usize,
+) {
+    for scene in structure
+        .scenes
+        .iter()
+        .filter(|s| lines.contains(&s.line_start))
+        .filter(|s| !structure.chapters.iter().any(|c| contains(c, s)))
+    {
+        text_row(out, scene.line_start, depth, &scene_text(scene));
+    }
+    for chapter in structure
+        .chapters
+        .iter()
+        .filter(|c| lines.contains(&c.line_start))
+    {
+        text_row(out, chapter.line_start, depth, &chapter_text(chapter));
+        for scene in structure.scenes.iter().filter(|s| contains(chapter, s)) {
+            text_row(out, scene.line_start, depth + 1, &scene_text(scene));
+        }
+    }
+}
+
+/// One line of to_text()
+fn text_row(out: &mut String, line: usize, depth: usize, label: &str) {
+    out.push_str(&format!("{:>6}  {}{}\n", line, "  ".repeat(depth), label));
+}
+
+/// Is the scene inside the chapter's line range?
+///
+/// Grouping by line range (rather than by `parent_chapter` title) keeps two
+/// chapters with the same title from sharing each other's scenes.
+fn contains(chapter: &Chapter, scene: &Scene) -> bool {
+    scene.line_start >= chapter.line_start && scene.line_start <= chapter.line_end
+}
@@ -1,50 +1,66 @@
-/// FILE: src/parser.rs
-///
-/// This is a PLACEHOLDER module for future parsing functionality.
-///
-/// PLANNED FEATURES:
-/// - Parse screenplay/script tags like [CHAPTER: X] and [SCENE: Beach]
-/// - Extract document structure (chapters, scenes, acts)
-/// - Validate tag syntax
-/// - Generate table of contents or outline
-///
-/// RUST CONCEPTS WE'LL USE:
-/// - Regex: For pattern matching tags
-/// - Enums: To represent different tag types
-/// - Pattern matching: To handle different parse cases
-/// - Iterators: To process lines of text efficiently
+//! FILE: src/parser.rs
+//!
+//! This module turns raw document text into structured information.
+//!
+//! FEATURES:
+//! - Parse screenplay/script tags like [CHAPTER: X] and [SCENE: Beach]
+//! - Recognize ALL-CAPS character cues and parenthetical stage directions
+//! - Extract document structure (acts, chapters, scenes)
+//! - Read scene metadata from the tag: `[SCENE: Beach | status=draft]`
+//! - Find `[[note: ...]]` comments, and take them out of the text
+//! - Convert Fountain screenplays into BookScript tags
+//! - Validate tags (malformed, unknown, empty, duplicate chapters)
+//! - Report who speaks, how much, and where (character_report)
+//! - Read Fountain, Markdown and plain documents in their own mode
+//!   (DocumentParser), as the status-bar mode selector picks
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Regex: For pattern matching tags
+//! - Enums: To represent different tag types
+//! - Pattern matching: To handle different parse cases
+//! - Iterators: To process lines of text efficiently
+//! - LazyLock: Compiling a regex once and reusing it everywhere
+//! - A trait with default methods, used through `&'static dyn DocumentParser`
+
+use crate::format::DocumentFormat;
+use regex::Regex;
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::LazyLock;
 
 // ============================================================================
-// FUTURE DATA STRUCTURES
+// DATA STRUCTURES
 // ============================================================================
 
-// When we implement this module, we'll probably define types like:
-
 /// Represents different types of screenplay tags
 ///
 /// ENUMS in Rust are powerful - each variant can hold different data!
 /// This is more powerful than enums in C or Java.
 #[derive(Debug, Clone, PartialEq)]
-#[allow(dead_code)] // Suppress "unused" warnings for this placeholder
+#[allow(dead_code)] // Not every variant is consumed by the GUI yet
 pub enum TagType {
     /// A chapter marker: [CHAPTER: 1]
     /// The String holds the chapter name/number
     Chapter(String),
 
     /// A scene marker: [SCENE: Beach]
-    /// The String holds the scene description
+    /// The String holds the scene description, without any metadata
+    /// after a `|` (see SceneMeta)
     Scene(String),
 
     /// An act marker: [ACT: I]
     Act(String),
 
     /// A character name (for dialogue)
+    /// Either `[CHARACTER: HERO]` or a bare ALL-CAPS cue line like `HERO (V.O.)`
     Character(String),
 
     /// Stage direction or action
+    /// A line wrapped entirely in parentheses: `(She slams the door.)`
     Action(String),
 
     /// Unrecognized or malformed tag
+    /// Holds the raw line so callers can show it in a warning
     Unknown(String),
 }
 
@@ -63,34 +79,179 @@ pub struct ParsedLine {
 }
 
 // ============================================================================
-// FUTURE PARSING FUNCTIONS
+// KNOWN TAG NAMES
+// ============================================================================
+
+/// Tag keywords that map onto TagType variants, in upper case
+///
+/// `[CHAPTER: X]`, `[SCENE: X]`, `[ACT: X]` and `[CHARACTER: X]` are the
+/// bracket tags the app understands. The editor uses this list to decide
+/// when a `[` starts a real tag rather than ordinary bracketed prose.
+pub const TAG_NAMES: [&str; 4] = ["CHAPTER", "SCENE", "ACT", "CHARACTER"];
+
+/// Check whether `name` is one of TAG_NAMES (ignoring case)
+pub fn is_known_tag_name(name: &str) -> bool {
+    TAG_NAMES
+        .iter()
+        .any(|known| known.eq_ignore_ascii_case(name))
+}
+
+// ============================================================================
+// REGEX PATTERNS
+// ============================================================================
+// LazyLock compiles each regex the first time it's used and then keeps it
+// for the rest of the program. Compiling a regex is far more expensive than
+// running it, and parse_line() is called once per line of the document.
+
+/// A well-formed tag: `[NAME: value]` filling the whole (trimmed) line
+///
+/// - `(?i)` is NOT used here: the name is matched as any letters and
+///   compared case-insensitively afterwards, so unknown names can be reported
+/// - The pattern is anchored at both ends (`^...$`), so the lazy `(.*?)`
+///   still has to run to the LAST `]` on the line. That keeps nested
+///   brackets inside the value: `[SCENE: The [old] house]`
+static TAG_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\[\s*([A-Za-z]+)\s*:\s*(.*?)\s*\]$").unwrap());
+
+/// Anything that looks like a tag: `[WORD:`, `[WORD]` or `[WORD` filling the
+/// line, or a known tag name after the `[` whatever follows it. Lines
+/// matching this but not TAG_RE are malformed tags; `[sic] the letter...`
+/// is prose.
+static TAG_LIKE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"^\[\s*(?:[A-Za-z]+\s*(?::|\]$|$)|(?i:{})\b)",
+        TAG_NAMES.join("|")
+    ))
+    .unwrap()
+});
+
+/// A character cue: upper-case words, optionally followed by an extension
+/// in parentheses - `HERO`, `DR. SMITH`, `MARY-ANN (V.O.)`, `O'BRIEN (CONT'D)`
+///
+/// The apostrophe can be curly (`O’BRIEN`), as smart typography types it.
+static CUE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[A-Z][A-Z0-9 .'’\-]*(\s*\([A-Za-z0-9 .'’\-]+\))?$").unwrap());
+
+/// Longest line we'll still consider a character cue
+/// Cues are names; a long all-caps line is shouting or a heading.
+const MAX_CUE_LENGTH: usize = 40;
+
+// ============================================================================
+// PARSING FUNCTIONS
 // ============================================================================
 
 /// Parse a single line and extract any tags
 ///
-/// PLANNED ALGORITHM:
+/// ALGORITHM:
 /// 1. Check if line matches tag pattern: [TAGNAME: value]
 /// 2. Extract the tag name and value
-/// 3. Match against known tag types
+/// 3. Match against known tag types (case-insensitive)
 /// 4. Return appropriate TagType variant
+/// 5. Otherwise, check for character cues and parenthetical directions
 ///
 /// EXAMPLE INPUT/OUTPUT:
 ///   Input: "[CHAPTER: The Beginning]"
 ///   Output: Some(TagType::Chapter("The Beginning".to_string()))
 ///
+///   Input: "[scene :  Beach ]"
+///   Output: Some(TagType::Scene("Beach".to_string()))
+///
+///   Input: "[CHAPTR: 1]" or "[SCENE: ]" or "[SCENE: Beach"
+///   Output: Some(TagType::Unknown(<the raw line>))
+///
 ///   Input: "Just regular text here."
 ///   Output: None
-#[allow(dead_code)]
 pub fn parse_line(line: &str, line_number: usize) -> ParsedLine {
-    // For now, just return a ParsedLine with no tag
-    // In the future, we'll implement regex matching here
     ParsedLine {
         line_number,
         text: line.to_string(),
-        tag: None, // TODO: Implement tag detection
+        tag: detect_tag(line),
     }
 }
 
+/// The classification logic behind parse_line()
+///
+/// Comments don't count: a line holding only a comment is plain text, and
+/// `HERO [[note: rename]]` is still a cue.
+fn detect_tag(line: &str) -> Option<TagType> {
+    let visible = without_comments(line);
+    let trimmed = visible.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    // ------------------------------------------------------------------------
+    // BRACKET TAGS
+    // ------------------------------------------------------------------------
+    if let Some(caps) = TAG_RE.captures(trimmed) {
+        // Group 1 is the name, group 2 the value (already trimmed by the regex)
+        let name = caps[1].to_ascii_uppercase();
+        let value = caps[2].to_string();
+
+        if value.is_empty() {
+            return Some(TagType::Unknown(line.to_string()));
+        }
+
+        return Some(match name.as_str() {
+            "CHAPTER" => TagType::Chapter(value),
+            "SCENE" => {
+                let description = split_scene_value(&value).0;
+                if description.is_empty() {
+                    return Some(TagType::Unknown(line.to_string()));
+                }
+                TagType::Scene(description.to_string())
+            }
+            "ACT" => TagType::Act(value),
+            "CHARACTER" => TagType::Character(value),
+            _ => TagType::Unknown(line.to_string()),
+        });
+    }
+
+    // Started like a tag but didn't match the full pattern:
+    // missing `]`, missing `:`, or trailing text after the `]`
+    if TAG_LIKE_RE.is_match(trimmed) {
+        return Some(TagType::Unknown(line.to_string()));
+    }
+
+    // ------------------------------------------------------------------------
+    // STAGE DIRECTIONS
+    // ------------------------------------------------------------------------
+    if trimmed.len() > 2 && trimmed.starts_with('(') && trimmed.ends_with(')') {
+        let inner = trimmed[1..trimmed.len() - 1].trim();
+        return Some(TagType::Action(inner.to_string()));
+    }
+
+    // ------------------------------------------------------------------------
+    // CHARACTER CUES
+    // ------------------------------------------------------------------------
+    if is_character_cue(trimmed) {
+        return Some(TagType::Character(trimmed.to_string()));
+    }
+
+    None
+}
+
+/// Does this (trimmed) line look like an ALL-CAPS character cue?
+///
+/// Rules out the all-caps lines that AREN'T names:
+/// - scene headings ("INT. BEACH - DAY")
+/// - transitions, which end in a colon ("CUT TO:")
+/// - long lines (shouting, headings)
+/// - lines without at least two letters ("I", "1984")
+pub fn is_character_cue(line: &str) -> bool {
+    if line.len() > MAX_CUE_LENGTH || !CUE_RE.is_match(line) {
+        return false;
+    }
+
+    let upper_letters = line.chars().filter(|c| c.is_ascii_uppercase()).count();
+    if upper_letters < 2 {
+        return false;
+    }
+
+    const SLUG_PREFIXES: [&str; 5] = ["INT.", "EXT.", "INT ", "EXT ", "EST."];
+    !SLUG_PREFIXES.iter().any(|p| line.starts_with(p))
+}
+
 /// Parse an entire document and return all parsed lines
 ///
 /// PLANNED ALGORITHM:
@@ -104,7 +265,6 @@ pub fn parse_line(line: &str, line_number: usize) -> ParsedLine {
 ///       .enumerate()       // Add line numbers: (index, line)
 ///       .map(|(i, line)| parse_line(line, i))  // Transform each line
 ///       .collect()         // Gather into Vec
-#[allow(dead_code)]
 pub fn parse_document(text: &str) -> Vec<ParsedLine> {
     text.lines()
         .enumerate()
@@ -112,12 +272,144 @@ pub fn parse_document(text: &str) -> Vec<ParsedLine> {
         .collect()
 }
 
-/// Extract document structure (chapters, scenes, etc.)
+// ============================================================================
+// DOCUMENT PARSERS
+// ============================================================================
+// The status-bar mode (format.rs) decides how a document's lines are read.
+// Each mode has a DocumentParser, and whatever the mode, the lines come out
+// as BookScript TagTypes - so the outline, statistics, Go to and the
+// exporters need no mode-specific code of their own.
+
+/// Reads a document written in one DocumentFormat
+///
+/// Lines are parsed one at a time, with no look at their neighbours, so
+/// incremental.rs can re-parse just the lines an edit touches.
+pub trait DocumentParser: Sync {
+    /// Parse one line (without its line ending)
+    fn parse_line(&self, line: &str, line_number: usize) -> ParsedLine;
+
+    /// The document as BookScript text, for the exporters (which all read
+    /// BookScript tags)
+    fn to_bookscript<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        Cow::Borrowed(text)
+    }
+}
+
+/// `[CHAPTER: X]` tags and cues: parse_line itself
+pub struct BookScriptParser;
+
+/// Fountain markup read the way import_fountain converts it
 ///
-/// This would analyze ParsedLine results and build a hierarchical structure
+/// A line is looked at on its own, so a scene heading doesn't need the
+/// blank line before it that import_fountain asks for.
+pub struct FountainParser;
+
+/// `# Chapter` and `## Scene` headings; nothing else is a tag
+pub struct MarkdownParser;
+
+/// Prose with no markup: no line is a tag
+pub struct PlainParser;
+
+impl DocumentParser for BookScriptParser {
+    fn parse_line(&self, line: &str, line_number: usize) -> ParsedLine {
+        parse_line(line, line_number)
+    }
+}
+
+impl DocumentParser for FountainParser {
+    fn parse_line(&self, line: &str, line_number: usize) -> ParsedLine {
+        let trimmed = line.trim();
+        let tag = if trimmed.is_empty() {
+            None
+        } else {
+            match convert_fountain_line(trimmed, true) {
+                // Brackets Fountain left alone are prose, not tags
+                Some(converted) if converted == trimmed && trimmed.starts_with('[') => None,
+                Some(converted) => detect_tag(&converted),
+                None => None,
+            }
+        };
+        ParsedLine {
+            line_number,
+            text: line.to_string(),
+            tag,
+        }
+    }
+
+    fn to_bookscript<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        Cow::Owned(import_fountain(text))
+    }
+}
+
+impl DocumentParser for MarkdownParser {
+    fn parse_line(&self, line: &str, line_number: usize) -> ParsedLine {
+        ParsedLine {
+            line_number,
+            text: line.to_string(),
+            tag: markdown_heading(line).map(|(level, title)| match level {
+                1 => TagType::Chapter(title.to_string()),
+                _ => TagType::Scene(title.to_string()),
+            }),
+        }
+    }
+
+    fn to_bookscript<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if !text.lines().any(|line| markdown_heading(line).is_some()) {
+            return Cow::Borrowed(text);
+        }
+        let mut out = String::with_capacity(text.len());
+        for line in text.split_inclusive('\n') {
+            let body = line.trim_end_matches(['\r', '\n']);
+            match markdown_heading(body) {
+                Some((1, title)) => out.push_str(&format!("[CHAPTER: {}]", title)),
+                Some((_, title)) => out.push_str(&format!("[SCENE: {}]", title)),
+                None => out.push_str(body),
+            }
+            out.push_str(&line[body.len()..]);
+        }
+        Cow::Owned(out)
+    }
+}
+
+impl DocumentParser for PlainParser {
+    fn parse_line(&self, line: &str, line_number: usize) -> ParsedLine {
+        ParsedLine {
+            line_number,
+            text: line.to_string(),
+            tag: None,
+        }
+    }
+}
+
+/// The parser for documents in `format`
+pub fn parser_for(format: DocumentFormat) -> &'static dyn DocumentParser {
+    match format {
+        DocumentFormat::BookScript => &BookScriptParser,
+        DocumentFormat::Fountain => &FountainParser,
+        DocumentFormat::Markdown => &MarkdownParser,
+        DocumentFormat::Plain => &PlainParser,
+    }
+}
+
+/// A `#` (chapter) or `##` (scene) heading: its level and title, without
+/// any closing hashes. Deeper headings are ordinary text.
+fn markdown_heading(line: &str) -> Option<(usize, &str)> {
+    let trimmed = line.trim();
+    let level = trimmed.chars().take_while(|&c| c == '#').count();
+    let rest = &trimmed[level..];
+    if !(1..=2).contains(&level) || !rest.starts_with(' ') {
+        return None;
+    }
+    let title = rest.trim().trim_end_matches('#').trim_end();
+    (!title.is_empty()).then_some((level, title))
+}
+
+/// Extract document structure (acts, chapters, scenes)
+///
+/// Walks the parsed lines once and builds a hierarchical structure
 /// representing the document's organization.
 ///
-/// PLANNED STRUCTURE:
+/// STRUCTURE:
 /// - Document
 ///   - Act I
 ///     - Chapter 1: "The Beginning"
@@ -126,21 +418,177 @@ pub fn parse_document(text: &str) -> Vec<ParsedLine> {
 ///     - Chapter 2: "The Journey"
 ///   - Act II
 ///     - ...
-#[allow(dead_code)]
-pub fn extract_structure(_parsed_lines: &[ParsedLine]) -> DocumentStructure {
-    // Placeholder implementation
-    DocumentStructure {
-        chapters: Vec::new(),
-        scenes: Vec::new(),
+///
+/// The three levels are stored as flat lists (in document order) with
+/// parent links, which is easy to build in one pass and easy to turn back
+/// into a tree: a chapter's `parent_act` and a scene's `parent_chapter`
+/// and `parent_act` name the entries they belong to.
+///
+/// LINE RANGES (1-based, inclusive):
+/// - An act runs until the line before the next act tag
+/// - A chapter runs until the line before the next chapter OR act tag
+/// - A scene runs until the line before the next scene, chapter, or act tag
+/// - Whatever is still open at the end runs to the last line
+///
+/// Scenes before the first chapter get `parent_chapter: None`, and chapters
+/// and scenes before the first act get `parent_act: None`. A scene directly
+/// under an act (`[ACT: I]` then `[SCENE: ...]`) gets the act but no
+/// chapter. Duplicate titles are kept as separate entries.
+pub fn extract_structure(parsed_lines: &[ParsedLine]) -> DocumentStructure {
+    let mut structure = DocumentStructure::default();
+
+    // Indices (into the Vecs above) of the entries whose end line we don't
+    // know yet. Each one is closed when a tag of the same or higher level
+    // shows up.
+    let mut open_act: Option<usize> = None;
+    let mut open_chapter: Option<usize> = None;
+    let mut open_scene: Option<usize> = None;
+
+    for line in parsed_lines {
+        let n = line.line_number;
+
+        match &line.tag {
+            Some(TagType::Act(title)) => {
+                close(&mut structure.scenes, &mut open_scene, n, |s| {
+                    &mut s.line_end
+                });
+                close(&mut structure.chapters, &mut open_chapter, n, |c| {
+                    &mut c.line_end
+                });
+                close(&mut structure.acts, &mut open_act, n, |a| &mut a.line_end);
+
+                structure.acts.push(Act {
+                    title: title.clone(),
+                    line_start: n,
+                    line_end: n,
+                });
+                open_act = Some(structure.acts.len() - 1);
+            }
+            Some(TagType::Chapter(title)) => {
+                close(&mut structure.scenes, &mut open_scene, n, |s| {
+                    &mut s.line_end
+                });
+                close(&mut structure.chapters, &mut open_chapter, n, |c| {
+                    &mut c.line_end
+                });
+
+                structure.chapters.push(Chapter {
+                    title: title.clone(),
+                    line_start: n,
+                    line_end: n,
+                    parent_act: open_act.map(|i| structure.acts[i].title.clone()),
+                    word_count: 0,
+                });
+                open_chapter = Some(structure.chapters.len() - 1);
+            }
+            Some(TagType::Scene(description)) => {
+                close(&mut structure.scenes, &mut open_scene, n, |s| {
+                    &mut s.line_end
+                });
+
+                structure.scenes.push(Scene {
+                    description: description.clone(),
+                    meta: scene_meta_of_line(&line.text).0,
+                    line_start: n,
+                    line_end: n,
+                    parent_chapter: open_chapter.map(|i| structure.chapters[i].title.clone()),
+                    parent_act: open_act.map(|i| structure.acts[i].title.clone()),
+                    word_count: 0,
+                });
+                open_scene = Some(structure.scenes.len() - 1);
+            }
+            _ => {}
+        }
+    }
+
+    // Everything still open runs to the end of the document
+    let last_line = parsed_lines.last().map_or(0, |l| l.line_number);
+    if let Some(i) = open_scene {
+        structure.scenes[i].line_end = last_line;
+    }
+    if let Some(i) = open_chapter {
+        structure.chapters[i].line_end = last_line;
+    }
+    if let Some(i) = open_act {
+        structure.acts[i].line_end = last_line;
+    }
+
+    structure
+}
+
+/// Close the currently open entry (if any) so it ends just before `line`
+///
+/// `end_of` picks the line_end field out of the entry. Taking a closure lets
+/// this one helper work for acts, chapters, and scenes alike.
+fn close<T>(
+    entries: &mut [T],
+    open: &mut Option<usize>,
+    line: usize,
+    end_of: impl Fn(&mut T) -> &mut usize,
+) {
+    // take() returns the old value and leaves None behind
+    if let Some(i) = open.take() {
+        // The entry started on an earlier line, so line - 1 never underflows
+        // and never ends before the entry's own tag line
+        *end_of(&mut entries[i]) = line - 1;
+    }
+}
+
+/// Fill in the word counts of a structure built from `text`
+/// (`structure` must come from extract_structure on this same text)
+///
+/// Each chapter and scene gets the words on the lines of its range, and
+/// `unchaptered_words` gets the words on lines outside every chapter (front
+/// matter, and text between an act tag and its first chapter). Lines are
+/// counted by the same rules as count_stats, so with the same
+/// `exclude_tags` the chapter counts plus `unchaptered_words` add up to the
+/// document's word count. With `exclude_tags`, the tag lines themselves
+/// (`[CHAPTER: ...]`, `[SCENE: ...]`) aren't counted.
+///
+/// extract_structure stays a pass over parsed lines; this second pass needs
+/// the raw text, and is only worth running when something shows the counts.
+pub fn annotate_structure(text: &str, structure: &mut DocumentStructure, exclude_tags: bool) {
+    // words_before[n] = words on lines 1..=n, so any line range's count is
+    // one subtraction: words_before[end] - words_before[start - 1]
+    let mut words_before = vec![0];
+    let mut total = 0;
+    for line in text.lines() {
+        let line = without_comments(line);
+        if !(exclude_tags && is_tag_line(&line)) {
+            total += line_words(&line);
+        }
+        words_before.push(total);
+    }
+    // Line ranges are 1-based and inclusive, and never start before line 1
+    let words_in = |start: usize, end: usize| words_before[end] - words_before[start - 1];
+
+    for chapter in &mut structure.chapters {
+        chapter.word_count = words_in(chapter.line_start, chapter.line_end);
+    }
+    for scene in &mut structure.scenes {
+        scene.word_count = words_in(scene.line_start, scene.line_end);
     }
+    let chaptered: usize = structure.chapters.iter().map(|c| c.word_count).sum();
+    structure.unchaptered_words = total - chaptered;
 }
 
 /// Represents the hierarchical structure of a document
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 #[allow(dead_code)]
 pub struct DocumentStructure {
+    pub acts: Vec<Act>,
     pub chapters: Vec<Chapter>,
     pub scenes: Vec<Scene>,
+    /// Words outside every chapter (set by annotate_structure)
+    pub unchaptered_words: usize,
+}
+
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct Act {
+    pub title: String,
+    pub line_start: usize,
+    pub line_end: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -149,78 +597,1017 @@ pub struct Chapter {
     pub title: String,
     pub line_start: usize,
     pub line_end: usize,
+    /// Title of the act this chapter belongs to (None before the first act)
+    pub parent_act: Option<String>,
+    /// Words in the chapter, its scenes included (set by annotate_structure)
+    pub word_count: usize,
 }
 
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct Scene {
     pub description: String,
+    /// Status, POV and synopsis from the tag
+    pub meta: SceneMeta,
     pub line_start: usize,
     pub line_end: usize,
     pub parent_chapter: Option<String>,
+    /// Title of the act this scene belongs to (None before the first act)
+    pub parent_act: Option<String>,
+    /// Words in the scene (set by annotate_structure)
+    pub word_count: usize,
 }
 
 // ============================================================================
-// IMPLEMENTATION PLAN
+// SCENE METADATA
 // ============================================================================
 //
-// When we're ready to implement this module, here's the roadmap:
-//
-// 1. ADD DEPENDENCIES to Cargo.toml:
-//    regex = "1.10"  // For pattern matching
-//
-// 2. WRITE TAG REGEX PATTERNS:
-//    const CHAPTER_PATTERN: &str = r"\[CHAPTER:\s*(.+?)\]";
-//    const SCENE_PATTERN: &str = r"\[SCENE:\s*(.+?)\]";
-//    etc.
+// A scene tag can carry fields after its description, each after a `|`:
 //
-// 3. IMPLEMENT parse_line():
-//    - Use regex::Regex::new() to compile patterns
-//    - Use regex.captures() to extract tag values
-//    - Match against tag types and return appropriate TagType
-//
-// 4. IMPLEMENT extract_structure():
-//    - Iterate through parsed lines
-//    - When we find a Chapter tag, create a new Chapter
-//    - When we find a Scene tag, add it to the current Chapter
-//    - Build the hierarchical structure
-//
-// 5. INTEGRATE WITH GUI (app.rs):
-//    - Parse the document when it's loaded
-//    - Display structure in a sidebar (chapters/scenes outline)
-//    - Allow clicking to jump to specific sections
-//    - Highlight syntax in the text editor
-//
-// 6. ADD VALIDATION:
-//    - Check for malformed tags
-//    - Warn about missing closing brackets
-//    - Detect duplicate chapter/scene names
+//   [SCENE: Beach | status=draft | pov=ANN | synopsis=They meet at last]
 //
+// The description alone is the scene's name everywhere (outline, exports);
+// the fields are for planning and never exported. Keys are case-insensitive
+// and any may be left out. A value can't contain `|`.
+
+/// How finished a scene is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneStatus {
+    Draft,
+    Revised,
+    Final,
+}
+
+impl SceneStatus {
+    pub const ALL: [SceneStatus; 3] =
+        [SceneStatus::Draft, SceneStatus::Revised, SceneStatus::Final];
+
+    /// As written in the tag
+    pub fn key(&self) -> &'static str {
+        match self {
+            SceneStatus::Draft => "draft",
+            SceneStatus::Revised => "revised",
+            SceneStatus::Final => "final",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<SceneStatus> {
+        SceneStatus::ALL
+            .into_iter()
+            .find(|status| status.key().eq_ignore_ascii_case(key))
+    }
+}
+
+/// The fields after a scene tag's description
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SceneMeta {
+    pub status: Option<SceneStatus>,
+    /// Whose point of view the scene is told from (empty = not given)
+    pub pov: String,
+    /// What happens, in a sentence or two (empty = not given)
+    pub synopsis: String,
+}
+
+/// Split a scene tag's value into its description and the raw fields
+fn split_scene_value(value: &str) -> (&str, Vec<&str>) {
+    let mut parts = value.split('|').map(str::trim);
+    let description = parts.next().unwrap_or("");
+    (description, parts.filter(|part| !part.is_empty()).collect())
+}
+
+/// The metadata of a scene tag line, and a message for each field that
+/// couldn't be read (for validate)
+///
+/// A line that isn't a scene tag has no metadata.
+pub fn scene_meta_of_line(line: &str) -> (SceneMeta, Vec<String>) {
+    let mut meta = SceneMeta::default();
+    let mut problems = Vec::new();
+    let visible = without_comments(line);
+    let Some(caps) = TAG_RE.captures(visible.trim()) else {
+        return (meta, problems);
+    };
+    if !caps[1].eq_ignore_ascii_case("SCENE") {
+        return (meta, problems);
+    }
+
+    for field in split_scene_value(&caps[2]).1 {
+        let Some((key, value)) = field.split_once('=') else {
+            problems.push(format!(
+                "Scene field \"{}\" needs a value (key=value)",
+                field
+            ));
+            continue;
+        };
+        let value = value.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "status" => match SceneStatus::from_key(value) {
+                Some(status) => meta.status = Some(status),
+                None => problems.push(format!(
+                    "Unknown scene status \"{}\" (expected draft, revised, final)",
+                    value
+                )),
+            },
+            "pov" => meta.pov = value.to_string(),
+            "synopsis" => meta.synopsis = value.to_string(),
+            other => problems.push(format!(
+                "Unknown scene field \"{}\" (expected status, pov, synopsis)",
+                other
+            )),
+        }
+    }
+    (meta, problems)
+}
+
+/// Write a scene tag with its metadata
+///
+/// `|` can't appear in a value and a tag is one line, so both are replaced
+/// in the text written.
+pub fn scene_tag(description: &str, meta: &SceneMeta) -> String {
+    let clean = |value: &str| {
+        value
+            .replace('|', "/")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mut tag = format!("[SCENE: {}", clean(description));
+    if let Some(status) = meta.status {
+        tag.push_str(&format!(" | status={}", status.key()));
+    }
+    for (key, value) in [("pov", &meta.pov), ("synopsis", &meta.synopsis)] {
+        let value = clean(value);
+        if !value.is_empty() {
+            tag.push_str(&format!(" | {}={}", key, value));
+        }
+    }
+    tag.push(']');
+    tag
+}
+
+// ============================================================================
+// NAVIGATION
+// ============================================================================
+
+/// Which kind of tag a next/previous jump stops at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Landmark {
+    Chapter,
+    Scene,
+}
+
+/// Which way a next/previous jump goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Next,
+    Previous,
+}
+
+/// Find the line of the next (or previous) chapter or scene tag, counting
+/// from the 1-based `current_line`
+///
+/// The current line itself never matches, so repeating a jump from a tag
+/// line moves on to the one after. There is no wrap-around: past the last
+/// tag (or with none at all) the result is None, and the caller says so.
+pub fn find_landmark(
+    parsed_lines: &[ParsedLine],
+    current_line: usize,
+    landmark: Landmark,
+    direction: Direction,
+) -> Option<usize> {
+    let is_landmark = |line: &&ParsedLine| {
+        matches!(
+            (&line.tag, landmark),
+            (Some(TagType::Chapter(_)), Landmark::Chapter)
+                | (Some(TagType::Scene(_)), Landmark::Scene)
+        )
+    };
+
+    match direction {
+        Direction::Next => parsed_lines
+            .iter()
+            .filter(is_landmark)
+            .find(|line| line.line_number > current_line),
+        Direction::Previous => parsed_lines
+            .iter()
+            .rev()
+            .filter(is_landmark)
+            .find(|line| line.line_number < current_line),
+    }
+    .map(|line| line.line_number)
+}
+
+/// A place Go to can jump to: a line number typed in, or an act, chapter
+/// or scene whose name matches what was typed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoToTarget {
+    /// 1-based line
+    pub line: usize,
+    /// What the list shows: "Line 12", "Chapter: The Journey",
+    /// "Scene: Beach (The Journey)"
+    pub label: String,
+}
+
+/// Everything a Go to entry could mean, best first
+///
+/// - A number is a line number, clamped to the document (0 means line 1)
+/// - Anything else is matched against act, chapter and scene names,
+///   ignoring case (see fuzzy_score): "journ" finds "The Journey", and so
+///   does "tjy". Better matches come first; equally good ones in the
+///   order they appear in the document
+///
+/// Empty for an empty query or a name nothing matches.
+pub fn go_to_targets(
+    query: &str,
+    parsed_lines: &[ParsedLine],
+    structure: &DocumentStructure,
+) -> Vec<GoToTarget> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    if let Ok(line) = query.parse::<usize>() {
+        let last = parsed_lines.last().map_or(1, |l| l.line_number);
+        let line = line.clamp(1, last.max(1));
+        return vec![GoToTarget {
+            line,
+            label: format!("Line {}", line),
+        }];
+    }
+
+    let acts = structure
+        .acts
+        .iter()
+        .map(|act| (&act.title, act.line_start, format!("Act: {}", act.title)));
+    let chapters = structure.chapters.iter().map(|chapter| {
+        let label = format!("Chapter: {}", chapter.title);
+        (&chapter.title, chapter.line_start, label)
+    });
+    let scenes = structure.scenes.iter().map(|scene| {
+        let label = match &scene.parent_chapter {
+            Some(chapter) => format!("Scene: {} ({})", scene.description, chapter),
+            None => format!("Scene: {}", scene.description),
+        };
+        (&scene.description, scene.line_start, label)
+    });
+
+    let mut scored: Vec<(i64, GoToTarget)> = acts
+        .chain(chapters)
+        .chain(scenes)
+        .filter_map(|(name, line, label)| {
+            let score = fuzzy_score(query, name)?;
+            Some((score, GoToTarget { line, label }))
+        })
+        .collect();
+    scored.sort_by_key(|(score, target)| (std::cmp::Reverse(*score), target.line));
+    scored.into_iter().map(|(_, target)| target).collect()
+}
+
+/// Where a 1-based line is in the document, outermost first: "Act II",
+/// the chapter's title, "Scene: Cave"
+///
+/// Each is the act, chapter or scene whose lines include `line`, so a line
+/// above the first act has no act, and so on; empty above the first tag.
+pub fn location_at(structure: &DocumentStructure, line: usize) -> Vec<String> {
+    let contains = |start: usize, end: usize| start <= line && line <= end;
+    let act = structure
+        .acts
+        .iter()
+        .find(|act| contains(act.line_start, act.line_end))
+        .map(|act| format!("Act {}", act.title));
+    let chapter = structure
+        .chapters
+        .iter()
+        .find(|chapter| contains(chapter.line_start, chapter.line_end))
+        .map(|chapter| chapter.title.clone());
+    let scene = structure
+        .scenes
+        .iter()
+        .find(|scene| contains(scene.line_start, scene.line_end))
+        .map(|scene| format!("Scene: {}", scene.description));
+    [act, chapter, scene].into_iter().flatten().collect()
+}
+
+/// How well `query` matches `name`, ignoring case; None if it doesn't
+///
+/// The query's characters (spaces aside) must all appear in the name, in
+/// order. A name starting with the query scores highest, then one
+/// containing it; otherwise letters that follow on from each other and
+/// letters starting a word score more, and gaps less.
+fn fuzzy_score(query: &str, name: &str) -> Option<i64> {
+    let query = query.to_lowercase();
+    let name_lower = name.to_lowercase();
+    // Shorter names first when everything else is equal
+    let length_penalty = name_lower.chars().count() as i64;
+
+    if name_lower.starts_with(&query) {
+        return Some(3000 - length_penalty);
+    }
+    if name_lower.contains(&query) {
+        return Some(2000 - length_penalty);
+    }
+
+    let name: Vec<char> = name_lower.chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in query.chars().filter(|c| !c.is_whitespace()) {
+        let found = next + name[next..].iter().position(|&c| c == wanted)?;
+        let word_start = found == 0 || !name[found - 1].is_alphanumeric();
+        score += 10;
+        if word_start {
+            score += 20;
+        }
+        match previous {
+            Some(previous) if previous + 1 == found => score += 15,
+            Some(previous) => score -= (found - previous - 1) as i64,
+            None => score -= found as i64,
+        }
+        previous = Some(found);
+        next = found + 1;
+    }
+    Some(score.min(1999) - length_penalty)
+}
+
+// ============================================================================
+// VALIDATION
 // ============================================================================
 
+/// How serious a validation issue is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The line was meant to be a tag but can't be read as one
+    Error,
+    /// The tag parses, but probably isn't what the writer meant
+    Warning,
+}
+
+/// One problem found by validate()
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    /// 1-based line the problem is on
+    pub line_number: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Check parsed lines for tag mistakes
+///
+/// ERRORS (the tag can't be read):
+/// - `[SCENE: Beach` - missing closing bracket
+/// - `[Scene Beach` - any other line that opens a bracket and never
+///   closes it
+/// - `[SCENE] Beach`, `[SCENE: Beach] extra` - any other malformed tag
+///
+/// A line that only starts with bracketed prose (`[sic] the letter...`)
+/// isn't a tag, so nothing is reported for it (see TAG_LIKE_RE).
+///
+/// WARNINGS (the tag reads, but looks wrong):
+/// - `[SCENE: ]` - empty value
+/// - `[CHAPTR: 1]` - unknown tag name
+/// - a chapter title used more than once (reported on every repeat)
+/// - a scene before the first chapter, unless it's in an act (scenes
+///   straight under acts are how plays and screenplays are laid out)
+/// - a chapter with nothing in it before the next chapter or act (blank
+///   lines and comments don't count)
+/// - `[SCENE: Beach | mood=dark]` - a scene field or status that isn't
+///   known (see SceneMeta)
+/// - `[[note: ...` - a comment without its closing `]]`, which would end up
+///   in the text
+///
+/// Issues come back in line order.
+pub fn validate(parsed: &[ParsedLine]) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    // Chapter title -> line it first appeared on
+    let mut chapter_lines: std::collections::HashMap<&str, usize> =
+        std::collections::HashMap::new();
+    // The chapter being read: its title and line, and whether anything
+    // has been written in it yet
+    let mut open_chapter: Option<(&str, usize, bool)> = None;
+    let mut empty_chapters = Vec::new();
+    let mut seen_act = false;
+
+    for line in parsed {
+        let n = line.line_number;
+        let visible = without_comments(&line.text);
+        let visible = visible.trim();
+
+        let ends_chapter = matches!(&line.tag, Some(TagType::Chapter(_) | TagType::Act(_)));
+        if ends_chapter {
+            if let Some((title, start, false)) = open_chapter.take() {
+                empty_chapters.push((title, start));
+            }
+        } else if let Some((_, _, written)) = &mut open_chapter {
+            *written |= !visible.is_empty();
+        }
+
+        let mut issue = |severity, message: String| {
+            issues.push(ValidationIssue {
+                line_number: n,
+                severity,
+                message,
+            })
+        };
+
+        if visible.contains("[[") {
+            issue(
+                Severity::Warning,
+                "Comment isn't closed with \"]]\" (it's part of the text)".to_string(),
+            );
+        }
+
+        match &line.tag {
+            Some(TagType::Unknown(raw)) => {
+                let (severity, message) = describe_malformed_tag(without_comments(raw).trim());
+                issue(severity, message);
+            }
+            // Not a tag, but opened like one
+            None | Some(TagType::Character(_) | TagType::Action(_))
+                if visible.starts_with('[')
+                    && !visible.starts_with("[[")
+                    && !visible.contains(']') =>
+            {
+                issue(Severity::Error, "Missing closing bracket \"]\"".to_string());
+            }
+            Some(TagType::Chapter(title)) => {
+                open_chapter = Some((title, n, false));
+                if let Some(first) = chapter_lines.get(title.as_str()) {
+                    issue(
+                        Severity::Warning,
+                        format!(
+                            "Duplicate chapter title \"{}\" (first on line {})",
+                            title, first
+                        ),
+                    );
+                } else {
+                    chapter_lines.insert(title, n);
+                }
+            }
+            Some(TagType::Act(_)) => seen_act = true,
+            Some(TagType::Scene(description)) => {
+                if chapter_lines.is_empty() && !seen_act {
+                    issue(
+                        Severity::Warning,
+                        format!("Scene \"{}\" comes before any chapter", description),
+                    );
+                }
+                for problem in scene_meta_of_line(&line.text).1 {
+                    issue(Severity::Warning, problem);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some((title, start, false)) = open_chapter {
+        empty_chapters.push((title, start));
+    }
+    for (title, line_number) in empty_chapters {
+        issues.push(ValidationIssue {
+            line_number,
+            severity: Severity::Warning,
+            message: format!("Chapter \"{}\" is empty", title),
+        });
+    }
+    // The empty chapters were only found further down; stable, so issues
+    // on the same line keep their order
+    issues.sort_by_key(|issue| issue.line_number);
+    issues
+}
+
+/// Explain why a (trimmed) line parsed as TagType::Unknown
+fn describe_malformed_tag(line: &str) -> (Severity, String) {
+    if let Some(caps) = TAG_RE.captures(line) {
+        let name = &caps[1];
+        if !is_known_tag_name(name) {
+            return (
+                Severity::Warning,
+                format!(
+                    "Unknown tag name \"{}\" (expected {})",
+                    name,
+                    TAG_NAMES.join(", ")
+                ),
+            );
+        }
+        return (
+            Severity::Warning,
+            format!("Empty value in [{}] tag", name.to_ascii_uppercase()),
+        );
+    }
+
+    if !line.contains(']') {
+        return (Severity::Error, "Missing closing bracket \"]\"".to_string());
+    }
+    (Severity::Error, format!("Malformed tag: {}", line))
+}
+
 // ============================================================================
-// WHY USE PLACEHOLDER MODULES?
+// CHARACTER REPORT
+// ============================================================================
+
+/// Who speaks, how much, and where
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacterStats {
+    /// Normalized name: upper case, extension dropped ("HERO (V.O.)" → "HERO")
+    pub name: String,
+    /// Number of cues (speeches)
+    pub speeches: usize,
+    /// Lines of dialogue across all speeches (parentheticals not counted)
+    pub dialogue_lines: usize,
+    /// Words of dialogue across all speeches (parentheticals not counted)
+    pub dialogue_words: usize,
+    /// 1-based line of the first cue
+    pub first_line: usize,
+    /// Titles of the chapters they speak in, in document order, no repeats
+    pub chapters: Vec<String>,
+    /// The scenes they speak in, in document order, no repeats
+    pub scenes: Vec<SceneAppearance>,
+}
+
+/// A scene someone speaks in
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneAppearance {
+    pub description: String,
+    /// 1-based line of the scene's tag
+    pub line: usize,
+}
+
+/// Count the speeches and dialogue of every character in the document
+///
+/// A speech starts at a cue - an ALL-CAPS character line or a
+/// `[CHARACTER: NAME]` tag - and runs until the next blank line or tag.
+/// A cue only counts if dialogue follows it: an all-caps line on its own
+/// ("THE END", a shouted scene description) isn't anyone speaking.
+///
+/// Characters come back in order of first appearance.
+pub fn character_report(parsed: &[ParsedLine]) -> Vec<CharacterStats> {
+    let structure = extract_structure(parsed);
+    let mut report: Vec<CharacterStats> = Vec::new();
+
+    for (i, line) in parsed.iter().enumerate() {
+        let next = parsed.get(i + 1).map(|l| l.text.as_str());
+        let Some(name) = cue_name(line, next) else {
+            continue;
+        };
+
+        // Dialogue: the lines after the cue up to a blank line or a tag
+        // (stage directions in parentheses are skipped, not stopped at)
+        let dialogue: Vec<&ParsedLine> = parsed[i + 1..]
+            .iter()
+            .take_while(|l| !l.text.trim().is_empty() && !ends_speech(l))
+            .filter(|l| !matches!(l.tag, Some(TagType::Action(_))))
+            .collect();
+        let dialogue_words: usize = dialogue
+            .iter()
+            .map(|l| count_stats(&l.text, false).words)
+            .sum();
+
+        let n = line.line_number;
+        let chapter = structure
+            .chapters
+            .iter()
+            .find(|c| c.line_start <= n && n <= c.line_end)
+            .map(|c| c.title.clone());
+        let scene = structure
+            .scenes
+            .iter()
+            .find(|s| s.line_start <= n && n <= s.line_end)
+            .map(|s| SceneAppearance {
+                description: s.description.clone(),
+                line: s.line_start,
+            });
+
+        let index = match report.iter().position(|c| c.name == name) {
+            Some(index) => index,
+            None => {
+                report.push(CharacterStats {
+                    name,
+                    speeches: 0,
+                    dialogue_lines: 0,
+                    dialogue_words: 0,
+                    first_line: n,
+                    chapters: Vec::new(),
+                    scenes: Vec::new(),
+                });
+                report.len() - 1
+            }
+        };
+        let stats = &mut report[index];
+        stats.speeches += 1;
+        stats.dialogue_lines += dialogue.len();
+        stats.dialogue_words += dialogue_words;
+        if let Some(chapter) = chapter {
+            if !stats.chapters.contains(&chapter) {
+                stats.chapters.push(chapter);
+            }
+        }
+        if let Some(scene) = scene {
+            if !stats.scenes.contains(&scene) {
+                stats.scenes.push(scene);
+            }
+        }
+    }
+
+    report
+}
+
+/// The speaker's normalized name if `line` is a cue with dialogue after it
+///
+/// `next` is the text of the following line (None at the end of the
+/// document). An explicit `[CHARACTER: ...]` tag is trusted without
+/// looking at `next`; a bare ALL-CAPS line needs a non-blank, non-tag line
+/// after it.
+pub fn cue_name(line: &ParsedLine, next: Option<&str>) -> Option<String> {
+    let Some(TagType::Character(raw)) = &line.tag else {
+        return None;
+    };
+
+    let name = normalize_cue(raw);
+
+    let explicit = line.text.trim_start().starts_with('[');
+    if !explicit {
+        let next = next.map(str::trim).unwrap_or("");
+        if next.is_empty() || TAG_RE.is_match(next) || TAG_LIKE_RE.is_match(next) {
+            return None;
+        }
+        // Names are short and don't end a sentence: "THE SUN RISES." above
+        // a paragraph of action is description, not a speaker. A final
+        // abbreviation ("JR.", "SR.") is fine.
+        let last_word = name.split_whitespace().last().unwrap_or("");
+        if name.split_whitespace().count() > MAX_CUE_WORDS
+            || (last_word.ends_with('.') && last_word.len() > 3)
+        {
+            return None;
+        }
+    }
+
+    Some(name)
+}
+
+/// Most words a bare ALL-CAPS cue can have ("MARY ANN DE LA CRUZ" is five)
+const MAX_CUE_WORDS: usize = 5;
+
+/// "Hero (V.O.)" → "HERO", "DR. SMITH (CONT'D)" → "DR. SMITH"
+pub fn normalize_cue(cue: &str) -> String {
+    let name = match cue.find('(') {
+        Some(paren) => &cue[..paren],
+        None => cue,
+    };
+    // O’BRIEN and O'BRIEN are the same character
+    name.trim().replace('\u{2019}', "'").to_uppercase()
+}
+
+/// Does this line end the speech above it? (any tag except a
+/// parenthetical, which belongs to the speech)
+fn ends_speech(line: &ParsedLine) -> bool {
+    matches!(
+        line.tag,
+        Some(TagType::Chapter(_))
+            | Some(TagType::Scene(_))
+            | Some(TagType::Act(_))
+            | Some(TagType::Character(_))
+            | Some(TagType::Unknown(_))
+    )
+}
+
+// ============================================================================
+// STATISTICS
+// ============================================================================
+
+/// Word and character counts for a piece of text
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DocStats {
+    /// Whitespace-separated words; "well-known" is one word
+    pub words: usize,
+    /// Characters including spaces (line breaks are not counted)
+    pub characters: usize,
+    /// Characters excluding all whitespace
+    pub characters_no_spaces: usize,
+}
+
+impl DocStats {
+    /// Estimated page count at `words_per_page` words per page
+    pub fn pages(&self, words_per_page: usize) -> f32 {
+        self.words as f32 / words_per_page.max(1) as f32
+    }
+}
+
+/// Count words and characters in `text`
+///
+/// WHAT COUNTS AS A WORD:
+/// Anything between whitespace that contains at least one letter or digit.
+/// So "well-known" and "don't" are one word each, runs of spaces/newlines
+/// don't produce empty words, and a lone dash ("-" or "—") isn't a word.
+///
+/// With `exclude_tags`, bracket tag lines like `[CHAPTER: 1]` are left out of
+/// all three counts - they're structure, not manuscript text. Comments
+/// (`[[note: ...]]`) are always left out.
+pub fn count_stats(text: &str, exclude_tags: bool) -> DocStats {
+    let mut stats = DocStats::default();
+
+    for line in text.lines() {
+        let line = without_comments(line);
+        if exclude_tags && is_tag_line(&line) {
+            continue;
+        }
+
+        stats.words += line_words(&line);
+        // lines() already stripped the line break (and a trailing '\r')
+        stats.characters += line.chars().count();
+        stats.characters_no_spaces += line.chars().filter(|c| !c.is_whitespace()).count();
+    }
+
+    stats
+}
+
+/// Words on one line (see count_stats for what counts as a word)
+fn line_words(line: &str) -> usize {
+    line.split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .count()
+}
+
+/// Is this line a bracket tag (well-formed or not)?
+fn is_tag_line(line: &str) -> bool {
+    let trimmed = line.trim();
+    TAG_RE.is_match(trimmed) || TAG_LIKE_RE.is_match(trimmed)
+}
+
+// ============================================================================
+// COMMENTS
 // ============================================================================
 //
-// In software development, it's good practice to:
-//
-// 1. Define interfaces/modules early (even if empty)
-// 2. Write documentation about planned features
-// 3. Implement incrementally (one feature at a time)
-//
-// This lets us:
-// - Organize code logically from the start
-// - Document our intentions for future developers
-// - Compile and test the app even when features are incomplete
-// - Avoid big-bang rewrites later
+// A comment is a note to self between double brackets, anywhere in a line
+// or on a line of its own:
 //
-// The #[allow(dead_code)] attribute tells the Rust compiler "I know this
-// code isn't used yet, don't warn me about it."
+//   She left without a word. [[note: too sudden? see chapter 3]]
 //
+// A comment ends at the first `]]` and can't cross a line break. Comments
+// aren't manuscript text: tags and cues are recognized as if they weren't
+// there, they're never counted as words, and exports leave them out unless
+// asked to keep them (Settings::export_comments).
+
+/// `[[`, anything but a line break, then the first `]]`
+static COMMENT_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[\[[^\n]*?\]\]").unwrap());
+
+/// A comment, as listed in View → Comments
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    /// 1-based line the comment is on
+    pub line: usize,
+    /// What's between the brackets, without a leading `note:`
+    pub text: String,
+}
+
+/// Byte ranges of the comments in `text` (brackets included)
+pub fn comment_ranges(text: &str) -> Vec<Range<usize>> {
+    COMMENT_RE.find_iter(text).map(|m| m.range()).collect()
+}
+
+/// Every comment in the document, in order
+pub fn find_comments(parsed_lines: &[ParsedLine]) -> Vec<Comment> {
+    let mut comments = Vec::new();
+    for line in parsed_lines {
+        for m in COMMENT_RE.find_iter(&line.text) {
+            let inner = m.as_str()[2..m.len() - 2].trim();
+            let text = match inner.split_once(':') {
+                Some((label, rest)) if label.trim().eq_ignore_ascii_case("note") => rest.trim(),
+                _ => inner,
+            };
+            comments.push(Comment {
+                line: line.line_number,
+                text: text.to_string(),
+            });
+        }
+    }
+    comments
+}
+
+/// One line with its comments cut out, for recognizing tags and counting
+/// words (spacing around them isn't tidied)
+fn without_comments(line: &str) -> Cow<'_, str> {
+    if line.contains("[[") {
+        COMMENT_RE.replace_all(line, "")
+    } else {
+        Cow::Borrowed(line)
+    }
+}
+
+/// The text without its comments, as it goes into an export
+///
+/// The space before a comment goes with it when the comment ends a
+/// sentence or a line ("word [[note]]." becomes "word."), and a line that
+/// held nothing but comments is taken out altogether rather than left
+/// blank, which would split its paragraph in two.
+pub fn strip_comments(text: &str) -> Cow<'_, str> {
+    if !COMMENT_RE.is_match(text) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let (body, ending) = match line.strip_suffix('\n') {
+            Some(body) => (body, "\n"),
+            None => (line, ""),
+        };
+        if !COMMENT_RE.is_match(body) {
+            out.push_str(line);
+            continue;
+        }
+
+        let mut stripped = String::with_capacity(body.len());
+        let mut copied_to = 0;
+        for m in COMMENT_RE.find_iter(body) {
+            let mut before = &body[copied_to..m.start()];
+            let opens_line = stripped.trim().is_empty() && before.trim().is_empty();
+            let next = body[m.end()..].chars().next();
+            if !opens_line
+                && next.is_none_or(|c| {
+                    c.is_whitespace() || matches!(c, '.' | ',' | ';' | ':' | '!' | '?' | ')')
+                })
+            {
+                before = before.trim_end_matches([' ', '\t']);
+            }
+            stripped.push_str(before);
+            copied_to = m.end();
+            // A comment opening the line takes the space after it along,
+            // leaving the indentation before it
+            if opens_line {
+                copied_to += body[copied_to..].len()
+                    - body[copied_to..].trim_start_matches([' ', '\t']).len();
+            }
+        }
+        stripped.push_str(&body[copied_to..]);
+
+        if !stripped.trim().is_empty() {
+            out.push_str(&stripped);
+            out.push_str(ending);
+        }
+    }
+    Cow::Owned(out)
+}
+
 // ============================================================================
+// FOUNTAIN IMPORT
+// ============================================================================
+
+/// Boneyard (`/* ... */`) and note (`[[ ... ]]`) spans, which may cross lines
+static FOUNTAIN_HIDDEN_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)/\*.*?\*/|\[\[.*?\]\]").unwrap());
+
+/// A scene heading: INT, EXT, EST, INT./EXT, INT/EXT or I/E, then `.` or space
+static FOUNTAIN_SLUG_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^(INT\.?/EXT|INT|EXT|EST|I/E)[. ]").unwrap());
+
+/// A section title naming an act: "Act I", "ACT TWO"
+static FOUNTAIN_ACT_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^act\s+(\S.*)$").unwrap());
+
+/// An optional scene number at the end of a heading: `INT. HOUSE - DAY #12A#`
+static FOUNTAIN_SCENE_NUMBER_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s*#[\w.\-]+#$").unwrap());
+
+/// Stands in for a removed boneyard/note span until the line is looked at,
+/// so a line that held nothing else can be dropped instead of left blank
+const HIDDEN_MARKER: char = '\u{0}';
+
+/// Convert a Fountain screenplay into BookScript text
+///
+/// MAPPING:
+/// - `# Act I`, `# ACT ONE` (one `#`)    → `[ACT: I]`, `[ACT: ONE]`
+/// - `# Heading` (any number of `#`)     → `[CHAPTER: Heading]`
+/// - `INT. BEACH - DAY`, `.FLASHBACK`    → `[SCENE: INT. BEACH - DAY]`, `[SCENE: FLASHBACK]`
+///   (scene numbers like `#12#` are dropped)
+/// - `@McCLANE` (forced character cue)   → `MCCLANE`
+/// - `BRICK ^` (dual dialogue)           → `BRICK` - both speeches are kept,
+///   one after the other
+/// - `> CUT TO:`, `>THE END<`            → `CUT TO:`, `THE END`
+/// - `!FORCED ACTION`                    → `FORCED ACTION`
+/// - boneyard `/* */`, notes `[[ ]]`, synopses (`= ...`) and page breaks
+///   (`===`) are removed
+///
+/// Character cues, parentheticals and everything else pass through as-is.
+/// A scene heading only counts after a blank line (or at the top), as in
+/// the Fountain spec - otherwise "INT. is short for interior" mid-paragraph
+/// would become a scene.
+pub fn import_fountain(text: &str) -> String {
+    // Normalise line endings first so the span regex sees plain '\n's
+    let text = text.replace("\r\n", "\n");
+    let text = FOUNTAIN_HIDDEN_RE.replace_all(&text, HIDDEN_MARKER.to_string().as_str());
+
+    let mut out = String::with_capacity(text.len());
+    let mut previous_blank = true;
+    // Set when a line is dropped between blank lines, so the blank after it
+    // is dropped too rather than leaving a double gap
+    let mut skip_blank = false;
+
+    for raw in text.lines() {
+        let line = raw.replace(HIDDEN_MARKER, "");
+        let trimmed = line.trim();
+
+        let converted = if trimmed.is_empty() {
+            // A line that held nothing but boneyard/notes disappears entirely
+            if raw.contains(HIDDEN_MARKER) || skip_blank {
+                skip_blank = previous_blank;
+                continue;
+            }
+            String::new()
+        } else {
+            // Dropped lines (synopses, page breaks) aren't content, so they
+            // don't break the "blank line before a heading" rule either
+            match convert_fountain_line(trimmed, previous_blank) {
+                Some(converted) => converted,
+                None => {
+                    skip_blank = previous_blank;
+                    continue;
+                }
+            }
+        };
+
+        skip_blank = false;
+        previous_blank = converted.is_empty();
+        out.push_str(&converted);
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Convert one non-blank, trimmed Fountain line; None drops the line
+fn convert_fountain_line(line: &str, after_blank: bool) -> Option<String> {
+    // Page break (`===`) or synopsis (`= ...`)
+    if line.starts_with('=') {
+        return None;
+    }
+
+    // Section: `# Act One`, `## Sequence`. A top-level "Act ..." section is
+    // an act (export::export_fountain writes acts that way); any other
+    // section is a chapter
+    if line.starts_with('#') {
+        let title = line.trim_start_matches('#').trim();
+        if title.is_empty() {
+            return Some(line.to_string());
+        }
+        let top_level = !line.starts_with("##");
+        return Some(match FOUNTAIN_ACT_RE.captures(title) {
+            Some(act) if top_level => format!("[ACT: {}]", &act[1]),
+            _ => format!("[CHAPTER: {}]", title),
+        });
+    }
+
+    // Forced scene heading: `.` followed by something other than another `.`
+    if let Some(rest) = line.strip_prefix('.') {
+        if rest.starts_with(|c: char| c.is_alphanumeric()) {
+            return Some(fountain_scene(rest));
+        }
+    }
+    if after_blank && FOUNTAIN_SLUG_RE.is_match(line) {
+        return Some(fountain_scene(line));
+    }
+
+    // Centered text and forced transitions
+    if let Some(rest) = line.strip_prefix('>') {
+        let rest = rest.strip_suffix('<').unwrap_or(rest);
+        return Some(rest.trim().to_string());
+    }
+
+    // Forced character cue, possibly with a dual-dialogue caret
+    if let Some(name) = line.strip_prefix('@') {
+        let name = name.trim_end_matches('^').trim_end();
+        return Some(name.to_uppercase());
+    }
+
+    // Forced action
+    if let Some(rest) = line.strip_prefix('!') {
+        return Some(rest.to_string());
+    }
+
+    // Dual dialogue: the second cue of a pair ends with `^`
+    if let Some(cue) = line.strip_suffix('^') {
+        let cue = cue.trim_end();
+        if is_character_cue(cue) {
+            return Some(cue.to_string());
+        }
+    }
+
+    Some(line.to_string())
+}
+
+/// Would Fountain read this line as a scene heading (given a blank line
+/// before it)? Used by the Fountain exporter to know when a heading needs
+/// forcing with `.`, and when an ordinary line needs protecting with `!`.
+pub fn is_fountain_scene_heading(line: &str) -> bool {
+    FOUNTAIN_SLUG_RE.is_match(line.trim())
+}
+
+/// `[SCENE: heading]`, minus any trailing scene number
+fn fountain_scene(heading: &str) -> String {
+    let heading = FOUNTAIN_SCENE_NUMBER_RE.replace(heading.trim(), "");
+    format!("[SCENE: {}]", heading)
+}
 
 // ============================================================================
-// EXAMPLE USAGE (FUTURE)
+// EXAMPLE USAGE
 // ============================================================================
 //
 // ```rust
@@ -257,3 +1644,755 @@ pub struct Scene {
 //     Scene: Cave
 //
 // ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(parser: &dyn DocumentParser, text: &str) -> Vec<Option<TagType>> {
+        text.lines()
+            .enumerate()
+            .map(|(i, line)| parser.parse_line(line, i + 1).tag)
+            .collect()
+    }
+
+    #[test]
+    fn fountain_lines_come_out_as_bookscript_tags() {
+        let text = "# Act One\n## Arrival\nINT. BEACH - DAY #4#\n.FLASHBACK\n@McCLANE\nBRICK ^\n= A synopsis\n[not a tag]\nThey walk.";
+        assert_eq!(
+            tags(parser_for(DocumentFormat::Fountain), text),
+            vec![
+                Some(TagType::Act("One".into())),
+                Some(TagType::Chapter("Arrival".into())),
+                Some(TagType::Scene("INT. BEACH - DAY".into())),
+                Some(TagType::Scene("FLASHBACK".into())),
+                Some(TagType::Character("MCCLANE".into())),
+                Some(TagType::Character("BRICK".into())),
+                None,
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn markdown_headings_are_chapters_and_scenes() {
+        let text = "# One #\n## The beach\n### Deeper\n#hashtag\n[CHAPTER: 2]\nHERO";
+        assert_eq!(
+            tags(parser_for(DocumentFormat::Markdown), text),
+            vec![
+                Some(TagType::Chapter("One".into())),
+                Some(TagType::Scene("The beach".into())),
+                None,
+                None,
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn plain_documents_have_no_tags() {
+        let text = "[CHAPTER: 1]\n# Heading\nHERO\n(beat)";
+        assert_eq!(tags(parser_for(DocumentFormat::Plain), text), vec![None; 4]);
+    }
+
+    #[test]
+    fn markdown_converts_to_bookscript_keeping_line_endings() {
+        let parser = parser_for(DocumentFormat::Markdown);
+        assert_eq!(
+            parser.to_bookscript("# One\r\nText\n## Beach ##\n"),
+            "[CHAPTER: One]\r\nText\n[SCENE: Beach]\n"
+        );
+        // Nothing to convert: the text is borrowed, not copied
+        assert!(matches!(
+            parser.to_bookscript("No headings"),
+            Cow::Borrowed(_)
+        ));
+        assert!(matches!(
+            parser_for(DocumentFormat::BookScript).to_bookscript("[CHAPTER: 1]"),
+            Cow::Borrowed(_)
+        ));
+    }
+    fn tag(line: &str) -> Option<TagType> {
+        parse_line(line, 1).tag
+    }
+
+    fn unknown(line: &str) -> Option<TagType> {
+        Some(TagType::Unknown(line.to_string()))
+    }
+
+    #[test]
+    fn each_tag_type_is_recognised() {
+        assert_eq!(
+            tag("[CHAPTER: The Beginning]"),
+            Some(TagType::Chapter("The Beginning".into()))
+        );
+        assert_eq!(tag("[SCENE: Beach]"), Some(TagType::Scene("Beach".into())));
+        assert_eq!(tag("[ACT: I]"), Some(TagType::Act("I".into())));
+        assert_eq!(
+            tag("[CHARACTER: HERO]"),
+            Some(TagType::Character("HERO".into()))
+        );
+        assert_eq!(tag("HERO"), Some(TagType::Character("HERO".into())));
+        assert_eq!(
+            tag("(She slams the door.)"),
+            Some(TagType::Action("She slams the door.".into()))
+        );
+        assert_eq!(tag("Just regular text here."), None);
+        assert_eq!(tag(""), None);
+    }
+
+    #[test]
+    fn the_keyword_ignores_case_but_the_value_is_kept_verbatim() {
+        assert_eq!(
+            tag("[chapter: the Beginning]"),
+            Some(TagType::Chapter("the Beginning".into()))
+        );
+        assert_eq!(
+            tag("[Scene: O'Brien's  BAR]"),
+            Some(TagType::Scene("O'Brien's  BAR".into()))
+        );
+    }
+
+    #[test]
+    fn malformed_tags_come_back_unknown_with_the_raw_line() {
+        for line in [
+            "[SCENE: Beach",
+            "[SCENE: ]",
+            "[SCENE:]",
+            "[CHAPTR: 1]",
+            "[SCENE]",
+            "[CHAPTER: 1] and more",
+            "[scene] Beach",
+            "[Chapter One",
+            "[sic]",
+            "  [ACT: ",
+            "[SCENE: | status=draft]",
+        ] {
+            assert_eq!(tag(line), unknown(line), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn whitespace_around_and_inside_tags_is_ignored() {
+        for line in [
+            "[ scene :  Beach ]",
+            "  [SCENE:Beach]  ",
+            "\t[SCENE:\tBeach\t]",
+            "[SCENE: Beach]\r",
+        ] {
+            assert_eq!(
+                tag(line),
+                Some(TagType::Scene("Beach".into())),
+                "{:?}",
+                line
+            );
+        }
+        // Spaces inside the value are the writer's own
+        assert_eq!(
+            tag("[CHAPTER:   Two  Words  ]"),
+            Some(TagType::Chapter("Two  Words".into()))
+        );
+        assert_eq!(tag("   "), None);
+    }
+
+    #[test]
+    fn nested_brackets_stay_in_the_value() {
+        assert_eq!(
+            tag("[SCENE: The [old] house]"),
+            Some(TagType::Scene("The [old] house".into()))
+        );
+        assert_eq!(
+            tag("[CHAPTER: [Redacted]]"),
+            Some(TagType::Chapter("[Redacted]".into()))
+        );
+        // A tag inside a tag is still one tag, named by the outer keyword
+        assert_eq!(
+            tag("[ACT: [SCENE: Beach]]"),
+            Some(TagType::Act("[SCENE: Beach]".into()))
+        );
+        // Brackets inside don't make up for a missing closing one
+        assert_eq!(
+            tag("[SCENE: The [old house"),
+            unknown("[SCENE: The [old house")
+        );
+    }
+
+    #[test]
+    fn bracketed_prose_is_not_a_tag() {
+        assert_eq!(tag("A [sic] reading"), None);
+        assert_eq!(tag("[sic] the letter read"), None);
+        assert_eq!(tag("[Aside] she said"), None);
+        assert!(validate(&parse_document("[sic] text")).is_empty());
+        assert!(!is_tag_line("[sic] text"));
+        assert_eq!(tag("[2] A footnote"), None);
+        assert_eq!(tag("He said [quietly]."), None);
+    }
+
+    #[test]
+    fn scene_metadata_is_not_part_of_the_description() {
+        assert_eq!(
+            tag("[SCENE: Beach | status=draft | pov=ANN]"),
+            Some(TagType::Scene("Beach".into()))
+        );
+    }
+
+    #[test]
+    fn cues_need_capitals_and_exclude_headings_and_transitions() {
+        for cue in ["HERO (V.O.)", "DR. SMITH", "MARY-ANN", "O’BRIEN (CONT'D)"] {
+            assert_eq!(tag(cue), Some(TagType::Character(cue.into())), "{:?}", cue);
+        }
+        for line in [
+            "INT. BEACH - DAY",
+            "EXT HOUSE",
+            "CUT TO:",
+            "I",
+            "1984",
+            "Hero",
+            "THIS LINE IS FAR TOO LONG TO BE ANYBODY'S NAME AT ALL",
+        ] {
+            assert_eq!(tag(line), None, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn comments_are_ignored_when_classifying() {
+        assert_eq!(
+            tag("HERO [[note: rename]]"),
+            Some(TagType::Character("HERO".into()))
+        );
+        assert_eq!(tag("[[just a note]]"), None);
+        assert_eq!(
+            tag("[CHAPTER: One] [[draft]]"),
+            Some(TagType::Chapter("One".into()))
+        );
+    }
+
+    #[test]
+    fn parse_document_numbers_lines_from_one() {
+        let parsed = parse_document("[ACT: I]\n\nText\r\n[SCENE: Cave]");
+        let numbers: Vec<usize> = parsed.iter().map(|l| l.line_number).collect();
+        assert_eq!(numbers, [1, 2, 3, 4]);
+        assert_eq!(parsed[2].text, "Text");
+        assert_eq!(parsed[3].tag, Some(TagType::Scene("Cave".into())));
+    }
+
+    fn structure_of(text: &str) -> DocumentStructure {
+        extract_structure(&parse_document(text))
+    }
+
+    #[test]
+    fn out_of_order_tags_get_the_right_ranges_and_parents() {
+        let structure = structure_of(
+            "[SCENE: Prologue]\n\
+             Text\n\
+             [CHAPTER: One]\n\
+             [CHAPTER: Two]\n\
+             [SCENE: Beach]\n\
+             Text\n\
+             [ACT: II]\n\
+             [SCENE: Under the act]\n\
+             [CHAPTER: One]\n\
+             [CHAPTER: Last]",
+        );
+
+        let chapters: Vec<_> = structure
+            .chapters
+            .iter()
+            .map(|c| {
+                (
+                    c.title.as_str(),
+                    c.line_start,
+                    c.line_end,
+                    c.parent_act.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            chapters,
+            [
+                ("One", 3, 3, None),
+                ("Two", 4, 6, None),
+                // A repeated title is its own entry
+                ("One", 9, 9, Some("II")),
+                // The trailing chapter ends at the end of the document
+                ("Last", 10, 10, Some("II")),
+            ]
+        );
+
+        let scenes: Vec<_> = structure
+            .scenes
+            .iter()
+            .map(|s| {
+                (
+                    s.description.as_str(),
+                    s.line_start,
+                    s.line_end,
+                    s.parent_chapter.as_deref(),
+                    s.parent_act.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            scenes,
+            [
+                ("Prologue", 1, 2, None, None),
+                ("Beach", 5, 6, Some("Two"), None),
+                ("Under the act", 8, 8, None, Some("II")),
+            ]
+        );
+
+        assert_eq!(structure.acts.len(), 1);
+        assert_eq!(
+            (structure.acts[0].line_start, structure.acts[0].line_end),
+            (7, 10)
+        );
+    }
+
+    #[test]
+    fn a_document_without_tags_has_no_structure() {
+        let structure = structure_of("Just prose.\n\nMore prose.");
+        assert!(structure.acts.is_empty());
+        assert!(structure.chapters.is_empty());
+        assert!(structure.scenes.is_empty());
+        assert!(structure_of("").chapters.is_empty());
+    }
+
+    #[test]
+    fn a_chapter_runs_to_the_last_line_including_trailing_text() {
+        let structure = structure_of("[CHAPTER: Only]\nOne\nTwo\n\n");
+        assert_eq!(structure.chapters[0].line_end, 4);
+    }
+
+    #[test]
+    fn hyphenated_words_and_contractions_are_one_word() {
+        assert_eq!(line_words("A well-known, state-of-the-art don't"), 4);
+    }
+
+    #[test]
+    fn runs_of_spaces_and_blank_lines_add_no_words() {
+        let stats = count_stats("  one   two\n\n\n\tthree  \r\n", false);
+        assert_eq!(stats.words, 3);
+        assert_eq!(stats.characters, "  one   two".len() + "\tthree  ".len());
+        assert_eq!(stats.characters_no_spaces, 11);
+        assert_eq!(count_stats("", false), DocStats::default());
+    }
+
+    #[test]
+    fn a_lone_dash_is_not_a_word() {
+        assert_eq!(line_words("wait - what — no –"), 3);
+        assert_eq!(line_words("--- * * *"), 0);
+        assert_eq!(line_words("1984 - 2001"), 2);
+    }
+
+    #[test]
+    fn tag_lines_are_left_out_only_when_asked() {
+        let text = "[CHAPTER: The Beginning]\nShe ran.\n[SCENE: Beach";
+        assert_eq!(count_stats(text, false).words, 7);
+        let stats = count_stats(text, true);
+        assert_eq!(stats.words, 2);
+        assert_eq!(stats.characters, "She ran.".len());
+        // Cues and stage directions are manuscript text either way
+        assert_eq!(count_stats("HERO\n(beat)", true).words, 2);
+    }
+
+    #[test]
+    fn comments_are_never_counted() {
+        let text = "She left. [[note: too sudden?]]\n[[a whole line]]";
+        let stats = count_stats(text, false);
+        assert_eq!(stats.words, 2);
+        assert_eq!(stats.characters, "She left. ".len());
+    }
+
+    #[test]
+    fn pages_are_words_over_words_per_page() {
+        let stats = DocStats {
+            words: 500,
+            ..DocStats::default()
+        };
+        assert_eq!(stats.pages(250), 2.0);
+        assert_eq!(stats.pages(1000), 0.5);
+        // A zero setting doesn't divide by zero
+        assert_eq!(stats.pages(0), 500.0);
+    }
+
+    /// A short screenplay using most of Fountain's markup
+    const FOUNTAIN_FIXTURE: &str = "\
+Title: The Beach
+Author: Someone
+
+# Act One
+
+## Arrival
+
+= They arrive at the beach.
+
+INT. BEACH HOUSE - DAY #1#
+
+Ann unpacks. [[Should she be alone?]]
+
+ANN
+Finally.
+
+.FLASHBACK
+
+@McCLANE
+(quietly)
+Not yet.
+
+BRICK
+Screw retirement.
+
+STEEL ^
+Screw retirement.
+
+/* Cut this scene:
+EXT. PIER - NIGHT
+*/
+
+===
+
+> CUT TO:
+
+>THE END<
+
+!INT. is short for interior.
+";
+
+    #[test]
+    fn a_fountain_screenplay_imports_as_bookscript() {
+        let expected = "\
+Title: The Beach
+Author: Someone
+
+[ACT: One]
+
+[CHAPTER: Arrival]
+
+[SCENE: INT. BEACH HOUSE - DAY]
+
+Ann unpacks.
+
+ANN
+Finally.
+
+[SCENE: FLASHBACK]
+
+MCCLANE
+(quietly)
+Not yet.
+
+BRICK
+Screw retirement.
+
+STEEL
+Screw retirement.
+
+CUT TO:
+
+THE END
+
+INT. is short for interior.
+";
+        assert_eq!(import_fountain(FOUNTAIN_FIXTURE), expected);
+    }
+
+    #[test]
+    fn imported_fountain_parses_into_acts_chapters_scenes_and_cues() {
+        let parsed = parse_document(&import_fountain(FOUNTAIN_FIXTURE));
+        let structure = extract_structure(&parsed);
+        assert_eq!(structure.acts.len(), 1);
+        assert_eq!(structure.chapters[0].title, "Arrival");
+        let scenes: Vec<&str> = structure
+            .scenes
+            .iter()
+            .map(|s| s.description.as_str())
+            .collect();
+        assert_eq!(scenes, ["INT. BEACH HOUSE - DAY", "FLASHBACK"]);
+        let cues: Vec<&str> = parsed
+            .iter()
+            .filter_map(|l| match &l.tag {
+                Some(TagType::Character(name)) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        // Both halves of the dual dialogue are kept, one after the other
+        assert_eq!(cues[..4], ["ANN", "MCCLANE", "BRICK", "STEEL"]);
+    }
+
+    #[test]
+    fn a_scene_heading_needs_a_blank_line_before_it() {
+        assert_eq!(
+            import_fountain("Some action.\nINT. is short for interior.\n"),
+            "Some action.\nINT. is short for interior.\n"
+        );
+        // Windows line endings are normalised
+        assert_eq!(
+            import_fountain("EXT. PIER - NIGHT\r\n\r\nWaves.\r\n"),
+            "[SCENE: EXT. PIER - NIGHT]\n\nWaves.\n"
+        );
+    }
+
+    #[test]
+    fn validation_reports_each_issue_on_its_line() {
+        let text = "\
+[SCENE: Prologue]
+Text
+[CHAPTER: One]
+[SCENE: Beach
+[Scene Cave
+[SCENE] extra
+[SCENE: ]
+[CHAPTR: 2]
+[CHAPTER: Two]
+Text
+[CHAPTER: One]
+Text [[note: unclosed
+[SCENE: Pier | mood=dark]
+[CHAPTER: Empty]
+";
+        let issues: Vec<(usize, Severity)> = validate(&parse_document(text))
+            .into_iter()
+            .map(|issue| (issue.line_number, issue.severity))
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                // Scene before any chapter
+                (1, Severity::Warning),
+                // Missing "]" on a known tag, and on a tag-like line
+                (4, Severity::Error),
+                (5, Severity::Error),
+                // Trailing text after the bracket
+                (6, Severity::Error),
+                // Empty value
+                (7, Severity::Warning),
+                // Unknown tag name
+                (8, Severity::Warning),
+                // Duplicate chapter title
+                (11, Severity::Warning),
+                // Unclosed comment
+                (12, Severity::Warning),
+                // Unknown scene field
+                (13, Severity::Warning),
+                // Nothing in the last chapter
+                (14, Severity::Warning),
+            ]
+        );
+    }
+
+    #[test]
+    fn validation_messages_name_the_problem() {
+        let messages: Vec<String> = validate(&parse_document(
+            "[CHAPTER: One]\nText\n[CHAPTER: One]\nText\n[CHAPTR: 2]",
+        ))
+        .into_iter()
+        .map(|issue| issue.message)
+        .collect();
+        assert_eq!(
+            messages,
+            [
+                "Duplicate chapter title \"One\" (first on line 1)",
+                "Unknown tag name \"CHAPTR\" (expected CHAPTER, SCENE, ACT, CHARACTER)",
+            ]
+        );
+    }
+
+    #[test]
+    fn scenes_under_an_act_and_well_formed_documents_are_fine() {
+        let text = "[ACT: I]\n[SCENE: Stage]\nHERO\nHello.\n[CHAPTER: One]\n\nText";
+        assert_eq!(validate(&parse_document(text)), []);
+    }
+
+    #[test]
+    fn cue_extensions_and_curly_apostrophes_are_normalized_away() {
+        assert_eq!(normalize_cue("HERO (V.O.)"), "HERO");
+        assert_eq!(normalize_cue("Dr. Smith (CONT'D)"), "DR. SMITH");
+        assert_eq!(normalize_cue("  O\u{2019}Brien "), "O'BRIEN");
+    }
+
+    #[test]
+    fn a_cue_needs_dialogue_after_it() {
+        let text = "HERO (V.O.)\nWe should go.\n\nTHE END\n\nTHE SUN RISES SLOWLY.\nBirds sing.\n";
+        let parsed = parse_document(text);
+        assert_eq!(
+            cue_name(&parsed[0], Some(&parsed[1].text)).as_deref(),
+            Some("HERO")
+        );
+        // Blank line after, and end of document
+        assert_eq!(cue_name(&parsed[3], Some(&parsed[4].text)), None);
+        assert_eq!(cue_name(&parsed[3], None), None);
+        // Ends like a sentence: description, not a speaker
+        assert_eq!(cue_name(&parsed[5], Some(&parsed[6].text)), None);
+        // Not a cue at all
+        assert_eq!(cue_name(&parsed[1], Some("")), None);
+
+        // An explicit tag is trusted even with nothing after it
+        let tagged = parse_line("[CHARACTER: Mary Ann]", 1);
+        assert_eq!(cue_name(&tagged, None).as_deref(), Some("MARY ANN"));
+    }
+
+    #[test]
+    fn character_report_counts_speeches_words_and_places() {
+        let text = "[CHAPTER: One]\n\
+                    [SCENE: Kitchen]\n\
+                    HERO\n\
+                    Pass the salt.\n\
+                    (reaching)\n\
+                    Please.\n\
+                    \n\
+                    VILLAIN\n\
+                    No.\n\
+                    \n\
+                    [CHAPTER: Two]\n\
+                    [SCENE: Garden]\n\
+                    HERO (CONT'D)\n\
+                    Fine then.\n";
+        let report = character_report(&parse_document(text));
+        let names: Vec<&str> = report.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["HERO", "VILLAIN"]);
+
+        let hero = &report[0];
+        assert_eq!(hero.speeches, 2);
+        // The parenthetical is part of the speech but not dialogue
+        assert_eq!(hero.dialogue_lines, 3);
+        assert_eq!(hero.dialogue_words, 6);
+        assert_eq!(hero.first_line, 3);
+        assert_eq!(hero.chapters, ["One", "Two"]);
+        let scenes: Vec<(&str, usize)> = hero
+            .scenes
+            .iter()
+            .map(|s| (s.description.as_str(), s.line))
+            .collect();
+        assert_eq!(scenes, [("Kitchen", 2), ("Garden", 12)]);
+
+        let villain = &report[1];
+        assert_eq!(
+            (villain.speeches, villain.dialogue_words, villain.first_line),
+            (1, 1, 8)
+        );
+        assert_eq!(villain.chapters, ["One"]);
+    }
+
+    fn annotated(text: &str, exclude_tags: bool) -> DocumentStructure {
+        let mut structure = structure_of(text);
+        annotate_structure(text, &mut structure, exclude_tags);
+        structure
+    }
+
+    #[test]
+    fn chapter_counts_include_their_scenes_and_add_up_to_the_total() {
+        let text = "Front matter here\n\
+                    [CHAPTER: One]\n\
+                    one two\n\
+                    [SCENE: Dock]\n\
+                    three four five\n\
+                    [SCENE: Ship]\n\
+                    six\n\
+                    [CHAPTER: Two]\n\
+                    seven eight\n";
+        for exclude_tags in [true, false] {
+            let structure = annotated(text, exclude_tags);
+            let chaptered: usize = structure.chapters.iter().map(|c| c.word_count).sum();
+            assert_eq!(
+                chaptered + structure.unchaptered_words,
+                count_stats(text, exclude_tags).words
+            );
+        }
+
+        let structure = annotated(text, true);
+        let chapters: Vec<usize> = structure.chapters.iter().map(|c| c.word_count).collect();
+        let scenes: Vec<usize> = structure.scenes.iter().map(|s| s.word_count).collect();
+        assert_eq!(chapters, [6, 2]);
+        assert_eq!(scenes, [3, 1]);
+        assert_eq!(structure.unchaptered_words, 3);
+    }
+
+    #[test]
+    fn empty_chapters_and_a_closing_tag_count_nothing() {
+        let text = "[CHAPTER: Empty]\n[CHAPTER: Full]\nSome words here\n[CHAPTER: Last]";
+        let structure = annotated(text, true);
+        let chapters: Vec<(&str, usize)> = structure
+            .chapters
+            .iter()
+            .map(|c| (c.title.as_str(), c.word_count))
+            .collect();
+        assert_eq!(chapters, [("Empty", 0), ("Full", 3), ("Last", 0)]);
+        assert_eq!(structure.unchaptered_words, 0);
+
+        // Counting tags, each tag line adds its own words
+        let structure = annotated(text, false);
+        let chapters: Vec<usize> = structure.chapters.iter().map(|c| c.word_count).collect();
+        assert_eq!(
+            chapters.iter().sum::<usize>(),
+            count_stats(text, false).words
+        );
+        assert!(chapters.iter().all(|&words| words > 0));
+    }
+
+    #[test]
+    fn a_document_without_chapters_is_all_unchaptered() {
+        let structure = annotated("Just a few words\n\n", true);
+        assert!(structure.chapters.is_empty());
+        assert_eq!(structure.unchaptered_words, 4);
+        assert_eq!(annotated("", true).unchaptered_words, 0);
+    }
+
+    fn jump(text: &str, from: usize, landmark: Landmark, direction: Direction) -> Option<usize> {
+        find_landmark(&parse_document(text), from, landmark, direction)
+    }
+
+    #[test]
+    fn jumps_with_no_chapters_or_only_one_go_nowhere_or_there() {
+        use Direction::{Next, Previous};
+        let none = "Just prose\nand more prose\n";
+        assert_eq!(jump(none, 1, Landmark::Chapter, Next), None);
+        assert_eq!(jump(none, 2, Landmark::Chapter, Previous), None);
+        assert_eq!(jump("", 1, Landmark::Scene, Next), None);
+
+        let one = "Intro\n[CHAPTER: Only]\nText\n";
+        assert_eq!(jump(one, 1, Landmark::Chapter, Next), Some(2));
+        assert_eq!(jump(one, 3, Landmark::Chapter, Previous), Some(2));
+        // From the tag itself there's nowhere further, and no wrapping
+        assert_eq!(jump(one, 2, Landmark::Chapter, Next), None);
+        assert_eq!(jump(one, 2, Landmark::Chapter, Previous), None);
+        assert_eq!(jump(one, 3, Landmark::Chapter, Next), None);
+    }
+
+    #[test]
+    fn jumps_step_through_tags_of_the_asked_kind() {
+        use Direction::{Next, Previous};
+        let text = "[CHAPTER: One]\n[SCENE: A]\nx\n[SCENE: B]\n[CHAPTER: Two]\n[SCENE: C]\n";
+        assert_eq!(jump(text, 1, Landmark::Chapter, Next), Some(5));
+        assert_eq!(jump(text, 1, Landmark::Scene, Next), Some(2));
+        assert_eq!(jump(text, 2, Landmark::Scene, Next), Some(4));
+        assert_eq!(jump(text, 6, Landmark::Scene, Previous), Some(4));
+        assert_eq!(jump(text, 4, Landmark::Chapter, Previous), Some(1));
+    }
+
+    #[test]
+    fn go_to_clamps_line_numbers_and_matches_names() {
+        let text = "[CHAPTER: The Journey]\n[SCENE: Beach]\nText\n";
+        let parsed = parse_document(text);
+        let structure = extract_structure(&parsed);
+        let go = |query| go_to_targets(query, &parsed, &structure);
+
+        assert_eq!(go("2")[0].line, 2);
+        assert_eq!(go("0")[0].line, 1);
+        assert_eq!(go("999")[0].label, "Line 3");
+        assert!(go("  ").is_empty());
+        assert!(go("zzz").is_empty());
+
+        let journey = go("journ");
+        assert_eq!(journey.len(), 1);
+        assert_eq!(journey[0].label, "Chapter: The Journey");
+        assert_eq!(go("beach")[0].label, "Scene: Beach (The Journey)");
+
+        // A document with no chapters still takes line numbers
+        let plain = parse_document("one\ntwo\n");
+        let empty = extract_structure(&plain);
+        assert_eq!(go_to_targets("2", &plain, &empty)[0].line, 2);
+        assert!(go_to_targets("one", &plain, &empty).is_empty());
+    }
+}
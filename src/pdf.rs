@@ -0,0 +1,712 @@
+//! FILE: src/pdf.rs
+//!
+//! File → Export → PDF: the document laid out as a screenplay.
+//!
+//! Screenplays are typeset in 12-point Courier on US Letter, which makes the
+//! layout a grid: every character is a tenth of an inch wide and every line
+//! a sixth of an inch tall, so a page is 85 columns by 66 rows. Layout works
+//! in those columns and rows - which makes "35 characters of dialogue" or
+//! "a centered cue" simple arithmetic - and only render() turns them into
+//! points.
+//!
+//! THE LAYOUT (columns from the left edge of the page):
+//!
+//! | Element                 | Column | Width | Notes                         |
+//! |-------------------------|--------|-------|-------------------------------|
+//! | `[SCENE: ...]`          | 15     | 60    | capitals, blank line before   |
+//! | action, anything else   | 15     | 60    |                               |
+//! | character cue           | center | -     | capitals, blank line before   |
+//! | dialogue                | 25     | 35    | lines after a cue             |
+//! | `(parenthetical)`       | 31     | 25    | inside a speech               |
+//! | `CUT TO:`               | right  | -     | ends at column 75             |
+//! | `[ACT: ...]`, chapters  | center | -     | start a new page              |
+//!
+//! Pages are numbered "2." in the top right corner; the first page of the
+//! script isn't, by convention, and neither is the title page. A scene
+//! heading or cue never sits alone at the bottom of a page, and a speech
+//! that doesn't fit is split with (MORE) and a "NAME (CONT'D)" cue.
+//!
+//! File → Print uses the same pages (layout()), drawn on screen by print.rs
+//! for the preview and printed from this module's PDF.
+//!
+//! PDF's built-in Courier needs no font file, but it can only show the
+//! Windows-1252 characters; anything else (emoji, most non-Latin scripts)
+//! is left out of the PDF.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Separating pure layout (testable, no PDF involved) from rendering
+//! - Using an external crate (printpdf) behind a small function
+
+use crate::parser::{self, ParsedLine, TagType};
+use anyhow::{anyhow, Result};
+use printpdf::{BuiltinFont, Mm, PdfDocument, Pt};
+use serde::{Deserialize, Serialize};
+
+// ============================================================================
+// PAGE GEOMETRY
+// ============================================================================
+
+/// Page size in columns and rows (8.5" × 11")
+pub const PAGE_COLUMNS: usize = 85;
+pub const PAGE_ROWS: usize = 66;
+
+/// First and last row of the body (1" margins top and bottom)
+pub const TOP_ROW: usize = 6;
+pub const BOTTOM_ROW: usize = 60;
+
+/// Row the page number sits on (half an inch from the top)
+const PAGE_NUMBER_ROW: usize = 3;
+
+/// Action and scene headings: 1.5" left margin, 1" right margin
+pub const ACTION_COLUMN: usize = 15;
+const ACTION_WIDTH: usize = 60;
+pub const RIGHT_EDGE: usize = ACTION_COLUMN + ACTION_WIDTH;
+
+const DIALOGUE_COLUMN: usize = 25;
+const DIALOGUE_WIDTH: usize = 35;
+
+const PARENTHETICAL_COLUMN: usize = 31;
+const PARENTHETICAL_WIDTH: usize = 25;
+
+/// Courier at this size is 10 characters per inch
+pub const FONT_SIZE: f32 = 12.0;
+/// One column and one row, in points (72 to the inch)
+pub const COLUMN_POINTS: f32 = 7.2;
+pub const ROW_POINTS: f32 = 12.0;
+
+// ============================================================================
+// OPTIONS
+// ============================================================================
+
+/// The title page's fields, saved with the settings
+///
+/// The title itself isn't here: it belongs to the document, so the export
+/// window suggests one from the file name each time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PdfOptions {
+    /// Start the PDF with a title page
+    pub title_page: bool,
+    /// The line between title and author ("Written by", "Story by", ...)
+    pub credit: String,
+    pub author: String,
+    /// Draft or date line, bottom right ("First draft, May 2025")
+    pub draft: String,
+    /// Address, phone, email: bottom left, one item per line
+    pub contact: String,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self {
+            title_page: true,
+            credit: String::from("Written by"),
+            author: String::new(),
+            draft: String::new(),
+            contact: String::new(),
+        }
+    }
+}
+
+// ============================================================================
+// LAID-OUT PAGES
+// ============================================================================
+
+/// One line of text at a fixed place on the page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageLine {
+    /// Row from the top of the page (0-65)
+    pub row: usize,
+    /// Column from the left edge (0-84)
+    pub column: usize,
+    pub text: String,
+}
+
+/// One page of the PDF
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Page {
+    pub lines: Vec<PageLine>,
+}
+
+/// The result of export_pdf()
+#[derive(Debug, Clone)]
+pub struct PdfExport {
+    /// The PDF file's contents
+    pub bytes: Vec<u8>,
+    /// Pages of script, not counting the title page
+    pub pages: usize,
+}
+
+/// Lay out and render `text` as a screenplay PDF
+pub fn export_pdf(text: &str, title: &str, options: &PdfOptions) -> Result<PdfExport> {
+    let all_pages = layout(text, title, options);
+    Ok(PdfExport {
+        bytes: render(&all_pages, title)?,
+        pages: all_pages.len() - usize::from(options.title_page),
+    })
+}
+
+/// Lay out `text` as the PDF's pages: the title page if there is one, then
+/// the script
+pub fn layout(text: &str, title: &str, options: &PdfOptions) -> Vec<Page> {
+    let script = layout_script(&parser::parse_document(text));
+    let mut pages = Vec::with_capacity(script.len() + 1);
+    if options.title_page {
+        pages.push(title_page(title, options));
+    }
+    pages.extend(script);
+    pages
+}
+
+// ============================================================================
+// SCRIPT ELEMENTS
+// ============================================================================
+
+/// What one line of the document is, in screenplay terms
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Element {
+    SceneHeading(String),
+    Action(String),
+    /// A cue, and the name to repeat as "NAME (CONT'D)" if the speech
+    /// runs onto the next page
+    Character {
+        cue: String,
+        name: String,
+    },
+    Parenthetical(String),
+    Dialogue(String),
+    Transition(String),
+    /// An act or chapter title, centered on a new page
+    Title(String),
+    Blank,
+}
+
+/// Decide what each line is
+///
+/// A speech runs from a cue to the next blank line or tag. Inside it, lines
+/// in parentheses are parentheticals and everything else is dialogue;
+/// outside it, they're action.
+fn classify(parsed: &[ParsedLine]) -> Vec<Element> {
+    let mut elements = Vec::with_capacity(parsed.len());
+    let mut in_speech = false;
+
+    for (i, line) in parsed.iter().enumerate() {
+        let trimmed = line.text.trim();
+        if trimmed.is_empty() {
+            elements.push(Element::Blank);
+            in_speech = false;
+            continue;
+        }
+
+        let next = parsed.get(i + 1).map(|line| line.text.as_str());
+        let element = match &line.tag {
+            Some(TagType::Act(title)) => Element::Title(format!("ACT {}", title.to_uppercase())),
+            Some(TagType::Chapter(title)) => Element::Title(title.to_uppercase()),
+            Some(TagType::Scene(heading)) => Element::SceneHeading(heading.to_uppercase()),
+            Some(TagType::Character(cue)) => match parser::cue_name(line, next) {
+                Some(name) => Element::Character {
+                    cue: cue.trim().to_uppercase(),
+                    name,
+                },
+                None => plain_line(trimmed),
+            },
+            Some(TagType::Unknown(_)) => plain_line(trimmed),
+            Some(TagType::Action(_)) if in_speech => Element::Parenthetical(trimmed.to_string()),
+            _ if in_speech => Element::Dialogue(trimmed.to_string()),
+            _ => plain_line(trimmed),
+        };
+
+        in_speech = matches!(
+            element,
+            Element::Character { .. } | Element::Parenthetical(_) | Element::Dialogue(_)
+        );
+        elements.push(element);
+    }
+
+    elements
+}
+
+/// Action, or a transition: an all-capitals line ending in "TO:"
+/// ("CUT TO:", "SMASH CUT TO:")
+fn plain_line(line: &str) -> Element {
+    let is_transition = line.ends_with("TO:") && !line.chars().any(char::is_lowercase);
+    if is_transition {
+        Element::Transition(line.to_string())
+    } else {
+        Element::Action(line.to_string())
+    }
+}
+
+// ============================================================================
+// PAGINATION
+// ============================================================================
+
+/// The page each act, chapter and scene heading of `text` lands on, in
+/// document order
+///
+/// Pages are counted from the first page of script, as they're numbered
+/// when printed; a title page isn't counted.
+pub fn heading_pages(text: &str) -> Vec<usize> {
+    paginate(&parser::parse_document(text)).1
+}
+
+/// Lay out the script's pages (title page not included)
+fn layout_script(parsed: &[ParsedLine]) -> Vec<Page> {
+    paginate(parsed).0
+}
+
+/// The script's pages, and the page number of each heading on them (see
+/// heading_pages)
+fn paginate(parsed: &[ParsedLine]) -> (Vec<Page>, Vec<usize>) {
+    let elements = classify(parsed);
+    let mut pager = Pager::default();
+    let mut heading_pages = Vec::new();
+    // An act title followed by a chapter title share a page
+    let mut after_title = false;
+    let mut i = 0;
+
+    while i < elements.len() {
+        match &elements[i] {
+            Element::Blank => pager.blank(),
+            Element::Title(title) => {
+                if !pager.page_is_empty() && !after_title {
+                    pager.break_page();
+                }
+                pager.line(centered_column(title), title);
+                heading_pages.push(pager.pages.len() + 1);
+                pager.blank();
+            }
+            Element::SceneHeading(heading) => {
+                let rows = wrap(heading, ACTION_WIDTH);
+                // The heading, the blank under it and a line of the scene
+                pager.blank();
+                pager.keep_together(rows.len() + 2);
+                pager.lines(ACTION_COLUMN, &rows);
+                heading_pages.push(pager.pages.len() + 1);
+                pager.blank();
+            }
+            Element::Action(text) => pager.lines(ACTION_COLUMN, &wrap(text, ACTION_WIDTH)),
+            Element::Transition(text) => {
+                pager.blank();
+                pager.line(RIGHT_EDGE.saturating_sub(width(text)), text);
+                pager.blank();
+            }
+            Element::Character { cue, name } => {
+                // Everything up to the end of the speech goes together
+                let end = elements[i + 1..]
+                    .iter()
+                    .position(|e| !matches!(e, Element::Parenthetical(_) | Element::Dialogue(_)))
+                    .map_or(elements.len(), |n| i + 1 + n);
+                pager.blank();
+                pager.speech(cue, name, &elements[i + 1..end]);
+                after_title = false;
+                i = end;
+                continue;
+            }
+            // Only reachable for a parenthetical or dialogue without a cue,
+            // which classify() never produces
+            Element::Parenthetical(text) | Element::Dialogue(text) => {
+                pager.lines(DIALOGUE_COLUMN, &wrap(text, DIALOGUE_WIDTH));
+            }
+        }
+        if elements[i] != Element::Blank {
+            after_title = matches!(elements[i], Element::Title(_));
+        }
+        i += 1;
+    }
+
+    (pager.finish(), heading_pages)
+}
+
+/// Places lines row by row, starting new pages as they fill up
+#[derive(Debug, Default)]
+struct Pager {
+    pages: Vec<Page>,
+    current: Page,
+    /// Rows used on the current page, counted from TOP_ROW
+    used: usize,
+}
+
+impl Pager {
+    fn rows_left(&self) -> usize {
+        (BOTTOM_ROW - TOP_ROW) - self.used
+    }
+
+    fn page_is_empty(&self) -> bool {
+        self.current.lines.is_empty()
+    }
+
+    fn line(&mut self, column: usize, text: &str) {
+        if self.rows_left() == 0 {
+            self.break_page();
+        }
+        self.current.lines.push(PageLine {
+            row: TOP_ROW + self.used,
+            column,
+            text: text.to_string(),
+        });
+        self.used += 1;
+    }
+
+    fn lines(&mut self, column: usize, rows: &[String]) {
+        for row in rows {
+            self.line(column, row);
+        }
+    }
+
+    /// An empty row, except at the top of a page or right after another one
+    ///
+    /// Also used before headings, cues and transitions to make sure they
+    /// have a blank line above them.
+    fn blank(&mut self) {
+        let after_text = self
+            .current
+            .lines
+            .last()
+            .is_some_and(|line| line.row + 1 == TOP_ROW + self.used);
+        if after_text && self.rows_left() > 0 {
+            self.used += 1;
+        }
+    }
+
+    /// Start a new page unless `rows` rows still fit on this one
+    fn keep_together(&mut self, rows: usize) {
+        if rows > self.rows_left() && !self.page_is_empty() {
+            self.break_page();
+        }
+    }
+
+    fn break_page(&mut self) {
+        let page = std::mem::take(&mut self.current);
+        self.pages.push(page);
+        self.used = 0;
+    }
+
+    /// A cue and its speech, split across pages if it has to be
+    fn speech(&mut self, cue: &str, name: &str, parts: &[Element]) {
+        let mut rows = Vec::new();
+        for part in parts {
+            match part {
+                Element::Parenthetical(text) => rows.extend(
+                    wrap(text, PARENTHETICAL_WIDTH)
+                        .into_iter()
+                        .map(|row| (PARENTHETICAL_COLUMN, row)),
+                ),
+                Element::Dialogue(text) => rows.extend(
+                    wrap(text, DIALOGUE_WIDTH)
+                        .into_iter()
+                        .map(|row| (DIALOGUE_COLUMN, row)),
+                ),
+                _ => {}
+            }
+        }
+
+        let mut cue = cue.to_string();
+        let mut rows = rows.as_slice();
+        loop {
+            let left = self.rows_left();
+            if rows.len() < left {
+                self.line(centered_column(&cue), &cue);
+                for (column, text) in rows {
+                    self.line(*column, text);
+                }
+                return;
+            }
+
+            // Splitting leaves the cue, at least two lines and (MORE) at the
+            // bottom of the page; when that won't fit, the speech moves on.
+            // (An empty page always has room, so this can't loop forever.)
+            if left < 4 || rows.len() < 3 {
+                self.break_page();
+                continue;
+            }
+
+            let take = left - 2;
+            self.line(centered_column(&cue), &cue);
+            for (column, text) in &rows[..take] {
+                self.line(*column, text);
+            }
+            self.line(centered_column("(MORE)"), "(MORE)");
+            rows = &rows[take..];
+            self.break_page();
+            cue = format!("{} (CONT'D)", name);
+        }
+    }
+
+    /// The finished pages, numbered
+    fn finish(mut self) -> Vec<Page> {
+        if !self.page_is_empty() || self.pages.is_empty() {
+            self.break_page();
+        }
+        for (index, page) in self.pages.iter_mut().enumerate().skip(1) {
+            let number = format!("{}.", index + 1);
+            page.lines.push(PageLine {
+                row: PAGE_NUMBER_ROW,
+                column: RIGHT_EDGE - width(&number),
+                text: number,
+            });
+        }
+        self.pages
+    }
+}
+
+// ============================================================================
+// TITLE PAGE
+// ============================================================================
+
+/// The title page: title, credit and author in the middle of the page,
+/// contact details bottom left and the draft line bottom right
+fn title_page(title: &str, options: &PdfOptions) -> Page {
+    let mut page = Page::default();
+    let mut centered = |row: usize, text: &str| {
+        if !text.is_empty() {
+            page.lines.push(PageLine {
+                row,
+                column: centered_column(text),
+                text: text.to_string(),
+            });
+        }
+    };
+
+    // A third of the way down, as on a typed title page
+    let mut row = 22;
+    for line in wrap(&title.to_uppercase(), ACTION_WIDTH) {
+        centered(row, &line);
+        row += 1;
+    }
+    row += 3;
+    centered(row, options.credit.trim());
+    centered(row + 2, options.author.trim());
+
+    let contact: Vec<&str> = options
+        .contact
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    let first_contact_row = BOTTOM_ROW.saturating_sub(contact.len());
+    for (i, line) in contact.iter().enumerate() {
+        page.lines.push(PageLine {
+            row: first_contact_row + i,
+            column: ACTION_COLUMN,
+            text: line.to_string(),
+        });
+    }
+
+    let draft = options.draft.trim();
+    if !draft.is_empty() {
+        page.lines.push(PageLine {
+            row: BOTTOM_ROW - 1,
+            column: RIGHT_EDGE.saturating_sub(width(draft)),
+            text: draft.to_string(),
+        });
+    }
+
+    page
+}
+
+// ============================================================================
+// RENDERING
+// ============================================================================
+
+/// Turn laid-out pages into a PDF file
+fn render(pages: &[Page], title: &str) -> Result<Vec<u8>> {
+    let page_width = Mm::from(Pt(PAGE_COLUMNS as f32 * COLUMN_POINTS));
+    let page_height = Mm::from(Pt(PAGE_ROWS as f32 * ROW_POINTS));
+
+    let (document, first_page, first_layer) =
+        PdfDocument::new(title, page_width, page_height, "Script");
+    let font = document
+        .add_builtin_font(BuiltinFont::Courier)
+        .map_err(|e| anyhow!("Could not load the Courier font: {}", e))?;
+
+    for (index, page) in pages.iter().enumerate() {
+        let (page_index, layer_index) = if index == 0 {
+            (first_page, first_layer)
+        } else {
+            document.add_page(page_width, page_height, "Script")
+        };
+        let layer = document.get_page(page_index).get_layer(layer_index);
+        for line in &page.lines {
+            // PDF measures from the bottom left corner, to the text baseline
+            let x = Pt(line.column as f32 * COLUMN_POINTS);
+            let y = Pt((PAGE_ROWS - line.row - 1) as f32 * ROW_POINTS + 3.0);
+            layer.use_text(line.text.as_str(), FONT_SIZE, x.into(), y.into(), &font);
+        }
+    }
+
+    document
+        .save_to_bytes()
+        .map_err(|e| anyhow!("Could not write the PDF: {}", e))
+}
+
+// ============================================================================
+// HELPERS
+// ============================================================================
+
+/// Width in columns (one per char, as Courier is monospaced)
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Column that centers `text` between the margins
+fn centered_column(text: &str) -> usize {
+    ACTION_COLUMN + ACTION_WIDTH.saturating_sub(width(text)) / 2
+}
+
+/// Break `text` into lines of at most `max` chars, at spaces where possible
+///
+/// A word longer than a whole line is cut wherever the line ends.
+fn wrap(text: &str, max: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        let needed = if line.is_empty() {
+            word.len()
+        } else {
+            width(&line) + 1 + word.len()
+        };
+        if needed > max && !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        while word.len() > max {
+            let rest = word.split_off(max);
+            lines.push(word.into_iter().collect());
+            word = rest;
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.extend(word);
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(row: usize, column: usize, text: &str) -> PageLine {
+        PageLine {
+            row,
+            column,
+            text: text.to_string(),
+        }
+    }
+
+    fn no_title_page() -> PdfOptions {
+        PdfOptions {
+            title_page: false,
+            ..PdfOptions::default()
+        }
+    }
+
+    #[test]
+    fn each_element_goes_in_its_column() {
+        let text = "[SCENE: int. house - day]\nAnn enters.\n\nANN\n(softly)\nHello.\n\nCUT TO:\n";
+        let pages = layout(text, "Play", &no_title_page());
+        assert_eq!(
+            pages,
+            [Page {
+                lines: vec![
+                    at(6, ACTION_COLUMN, "INT. HOUSE - DAY"),
+                    at(8, ACTION_COLUMN, "Ann enters."),
+                    // Centered cue
+                    at(10, 43, "ANN"),
+                    at(11, PARENTHETICAL_COLUMN, "(softly)"),
+                    at(12, DIALOGUE_COLUMN, "Hello."),
+                    // Ends at the right margin
+                    at(14, RIGHT_EDGE - 7, "CUT TO:"),
+                ]
+            }]
+        );
+    }
+
+    #[test]
+    fn a_cue_without_a_speech_is_action() {
+        let pages = layout("THE END.\n\nHERO\n", "Play", &no_title_page());
+        assert_eq!(pages[0].lines[0], at(6, ACTION_COLUMN, "THE END."));
+        assert_eq!(pages[0].lines[1], at(8, ACTION_COLUMN, "HERO"));
+    }
+
+    #[test]
+    fn the_title_page_fills_in_its_fields() {
+        let options = PdfOptions {
+            author: String::from(" Ann Smith "),
+            draft: String::from("Draft 1"),
+            contact: String::from("ann@example.com\n\n555 0100\n"),
+            ..PdfOptions::default()
+        };
+        let pages = layout("Text", "My Play", &options);
+        assert_eq!(pages.len(), 2);
+        assert_eq!(
+            pages[0].lines,
+            [
+                at(22, 41, "MY PLAY"),
+                at(26, 40, "Written by"),
+                at(28, 40, "Ann Smith"),
+                at(58, ACTION_COLUMN, "ann@example.com"),
+                at(59, ACTION_COLUMN, "555 0100"),
+                at(59, RIGHT_EDGE - 7, "Draft 1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_long_speech_is_split_with_more_and_contd() {
+        let lines: Vec<String> = (1..=60).map(|n| format!("Line {}.", n)).collect();
+        let text = format!("ANN (V.O.)\n{}\n", lines.join("\n"));
+        let pages = layout(&text, "Play", &no_title_page());
+        assert_eq!(pages.len(), 2);
+
+        let first = &pages[0].lines;
+        assert_eq!(first[0], at(TOP_ROW, 40, "ANN (V.O.)"));
+        assert_eq!(first.len(), BOTTOM_ROW - TOP_ROW);
+        assert_eq!(first[first.len() - 2].text, "Line 52.");
+        assert_eq!(first.last().unwrap(), &at(BOTTOM_ROW - 1, 42, "(MORE)"));
+
+        let second = &pages[1].lines;
+        assert_eq!(second[0], at(TOP_ROW, 39, "ANN (CONT'D)"));
+        assert_eq!(second[1].text, "Line 53.");
+        assert_eq!(second[8].text, "Line 60.");
+        // Numbered from the second page on
+        assert_eq!(
+            second.last().unwrap(),
+            &at(PAGE_NUMBER_ROW, RIGHT_EDGE - 2, "2.")
+        );
+        assert!(!first.iter().any(|line| line.row == PAGE_NUMBER_ROW));
+    }
+
+    #[test]
+    fn titles_start_pages_and_headings_know_theirs() {
+        let text = "[ACT: i]\n[CHAPTER: One]\n[SCENE: A]\nText.\n[CHAPTER: Two]\n[SCENE: B]\n";
+        // The act and first chapter share a page; the next chapter doesn't
+        assert_eq!(heading_pages(text), [1, 1, 1, 2, 2]);
+        let pages = layout(text, "Play", &no_title_page());
+        assert_eq!(pages[0].lines[0], at(TOP_ROW, 42, "ACT I"));
+        assert_eq!(pages[0].lines[1], at(TOP_ROW + 2, 43, "ONE"));
+        assert_eq!(pages[1].lines[0], at(TOP_ROW, 43, "TWO"));
+    }
+
+    #[test]
+    fn wrapping_breaks_at_spaces_and_cuts_long_words() {
+        assert_eq!(wrap("one two three", 7), ["one two", "three"]);
+        assert_eq!(wrap("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("a  abcdefgh", 4), ["a", "abcd", "efgh"]);
+        assert_eq!(wrap("", 4), [""]);
+    }
+
+    #[test]
+    fn the_export_is_a_pdf() {
+        let export =
+            export_pdf("[SCENE: Beach]\nWaves.\n", "Play", &PdfOptions::default()).unwrap();
+        assert!(export.bytes.starts_with(b"%PDF"));
+        // The title page isn't counted
+        assert_eq!(export.pages, 1);
+    }
+}
@@ -0,0 +1,138 @@
+//! FILE: src/print.rs
+//!
+//! File → Print…: a preview of the pages, then the document sent to the
+//! printer.
+//!
+//! The pages are the PDF exporter's (pdf::layout), so what's previewed,
+//! printed and exported is always the same: same page breaks, same
+//! margins, same page numbers. The preview draws them here with egui's
+//! painter; printing renders the PDF and hands it to the system.
+//!
+//! SENDING TO THE PRINTER:
+//! There is no printing API that works everywhere, so the PDF is written to
+//! the temp folder and given to the platform's own command: `lp` (CUPS, on
+//! Linux and macOS) or, on Windows, whatever program prints PDFs there
+//! (PowerShell's `Start-Process -Verb Print`). Either way it goes to the
+//! default printer. The temp file is left behind, since Windows reads it
+//! after the command returns; the next print replaces it.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - std::process::Command for running another program
+//! - cfg!() to pick a platform at compile time inside ordinary code
+
+use crate::pdf::{self, Page};
+use crate::storage;
+use anyhow::{anyhow, Result};
+use std::process::Command;
+
+/// Widest a page is drawn (points at 1:1 on a normal screen)
+const MAX_PAGE_WIDTH: f32 = 612.0;
+
+/// Draw `pages` one under another, as they'd come out of the printer
+///
+/// `title_page` says whether the first one is a title page, which isn't
+/// counted in the page numbers below each page.
+pub fn show(ui: &mut egui::Ui, pages: &[Page], title_page: bool) {
+    if pages.is_empty() {
+        ui.label("Nothing to print.");
+        return;
+    }
+
+    let script_pages = pages.len() - usize::from(title_page);
+    egui::ScrollArea::vertical()
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            for (index, page) in pages.iter().enumerate() {
+                draw_page(ui, page);
+                let caption = match index.checked_sub(usize::from(title_page)) {
+                    None => String::from("Title page"),
+                    Some(number) => format!("Page {} of {}", number + 1, script_pages),
+                };
+                ui.weak(caption);
+                ui.add_space(12.0);
+            }
+        });
+}
+
+/// One page: paper, the margins as a faint box, and the text
+fn draw_page(ui: &mut egui::Ui, page: &Page) {
+    let points_wide = pdf::PAGE_COLUMNS as f32 * pdf::COLUMN_POINTS;
+    let points_high = pdf::PAGE_ROWS as f32 * pdf::ROW_POINTS;
+    let scale = ui.available_width().min(MAX_PAGE_WIDTH) / points_wide;
+    let (rect, _) = ui.allocate_exact_size(
+        egui::vec2(points_wide * scale, points_high * scale),
+        egui::Sense::hover(),
+    );
+    // A long script has hundreds of pages; only those on screen are drawn
+    if !ui.is_rect_visible(rect) {
+        return;
+    }
+
+    // Where a column/row corner falls on screen
+    let at = |column: usize, row: usize| {
+        rect.min
+            + egui::vec2(
+                column as f32 * pdf::COLUMN_POINTS * scale,
+                row as f32 * pdf::ROW_POINTS * scale,
+            )
+    };
+
+    let painter = ui.painter_at(rect);
+    painter.rect(
+        rect,
+        0.0,
+        egui::Color32::WHITE,
+        egui::Stroke::new(1.0, egui::Color32::GRAY),
+    );
+    let margins = egui::Rect::from_min_max(
+        at(pdf::ACTION_COLUMN, pdf::TOP_ROW),
+        at(pdf::RIGHT_EDGE, pdf::BOTTOM_ROW + 1),
+    );
+    painter.rect_stroke(
+        margins,
+        0.0,
+        egui::Stroke::new(1.0, egui::Color32::from_rgb(0xd8, 0xe4, 0xf0)),
+    );
+
+    let font = egui::FontId::monospace(pdf::FONT_SIZE * scale);
+    for line in &page.lines {
+        painter.text(
+            at(line.column, line.row),
+            egui::Align2::LEFT_TOP,
+            &line.text,
+            font.clone(),
+            egui::Color32::BLACK,
+        );
+    }
+}
+
+/// Print a rendered PDF on the default printer
+///
+/// `title` names the print job where the system shows one.
+pub fn print_pdf(bytes: &[u8], title: &str) -> Result<()> {
+    let path = std::env::temp_dir().join(format!("bookscript-print-{}.pdf", std::process::id()));
+    storage::save_binary_file(&path, bytes)?;
+
+    let (program, output) = if cfg!(windows) {
+        // Single quotes are doubled inside a PowerShell string
+        let quoted = path.display().to_string().replace('\'', "''");
+        let command = format!("Start-Process -FilePath '{}' -Verb Print", quoted);
+        let output = Command::new("powershell")
+            .args(["-NoProfile", "-Command", &command])
+            .output();
+        ("PowerShell", output)
+    } else {
+        let output = Command::new("lp").arg("-t").arg(title).arg(&path).output();
+        ("lp", output)
+    };
+
+    let output = output.map_err(|e| anyhow!("Couldn't run {} to print: {}", program, e))?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        match message.trim() {
+            "" => anyhow::bail!("{} failed ({})", program, output.status),
+            message => anyhow::bail!("{}", message),
+        }
+    }
+    Ok(())
+}
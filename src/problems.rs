@@ -0,0 +1,66 @@
+//! FILE: src/problems.rs
+//!
+//! The "Problems" list: tag validation issues shown under the editor.
+//!
+//! Like outline.rs, this module only DRAWS. App owns the issue list (from
+//! parser::validate), passes it in, and gets back the line the user
+//! clicked so it can move the editor there.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Returning Option<T> from UI code to report user actions
+//! - Counting with iterator filters
+
+use crate::parser::{Severity, ValidationIssue};
+
+/// Tallest the list gets before it scrolls
+const MAX_LIST_HEIGHT: f32 = 140.0;
+
+/// Draw the issue list
+///
+/// Returns the 1-based line number of the issue the user clicked, if any.
+pub fn show(ui: &mut egui::Ui, issues: &[ValidationIssue]) -> Option<usize> {
+    if issues.is_empty() {
+        ui.label("No problems found.");
+        return None;
+    }
+
+    let mut clicked = None;
+
+    egui::ScrollArea::vertical()
+        .max_height(MAX_LIST_HEIGHT)
+        .show(ui, |ui| {
+            for issue in issues {
+                let (icon, color) = match issue.severity {
+                    Severity::Error => ("✖", ui.visuals().error_fg_color),
+                    Severity::Warning => ("⚠", ui.visuals().warn_fg_color),
+                };
+                ui.horizontal(|ui| {
+                    ui.colored_label(color, icon);
+                    let label = format!("Line {}: {}", issue.line_number, issue.message);
+                    if ui.selectable_label(false, label).clicked() {
+                        clicked = Some(issue.line_number);
+                    }
+                });
+            }
+        });
+
+    clicked
+}
+
+/// One-line summary for the status bar: "No problems", "2 warnings",
+/// "1 error, 3 warnings"
+pub fn summary(issues: &[ValidationIssue]) -> String {
+    let errors = issues
+        .iter()
+        .filter(|i| i.severity == Severity::Error)
+        .count();
+    let warnings = issues.len() - errors;
+
+    let plural = |n: usize, word: &str| format!("{} {}{}", n, word, if n == 1 { "" } else { "s" });
+    match (errors, warnings) {
+        (0, 0) => "No problems".to_string(),
+        (0, w) => plural(w, "warning"),
+        (e, 0) => plural(e, "error"),
+        (e, w) => format!("{}, {}", plural(e, "error"), plural(w, "warning")),
+    }
+}
@@ -0,0 +1,223 @@
+//! FILE: src/profile.rs
+//!
+//! Portable profiles: a single JSON file carrying the user's preferences
+//! from one machine to another.
+//!
+//! EXPORT is simple - serialize Settings with a version number.
+//!
+//! IMPORT is more careful. A profile may come from a newer (or older) version
+//! of the app, or contain a value this machine can't use. Rather than
+//! rejecting the whole file, we apply it one setting at a time: each value
+//! that produces a valid Settings is accepted, everything else is reported
+//! as skipped. The user sees a preview of both lists before anything changes,
+//! and the previous profile is backed up before the new one is applied.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - serde_json::Value for working with JSON whose shape we don't trust
+//! - Recursion over nested data
+//! - JSON pointers ("/a/b") for addressing nested values
+
+use crate::settings::Settings;
+use crate::storage;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// Version written into exported profiles
+///
+/// Bump this when the profile layout changes in a way older versions of the
+/// app can't understand. Importing a profile with a HIGHER version is refused.
+pub const PROFILE_VERSION: u64 = 1;
+
+/// File name suggested in the Export profile dialog
+pub const PROFILE_FILE_NAME: &str = "bookscript-profile.json";
+
+/// Settings sections that describe this machine rather than the user's
+/// preferences (window placement), and so never travel in a profile
+const LOCAL_ONLY_KEYS: [&str; 1] = ["window"];
+
+/// The on-disk shape of a profile
+#[derive(Debug, Serialize, Deserialize)]
+struct Profile {
+    version: u64,
+    settings: Settings,
+}
+
+/// The result of reading a profile, ready to be previewed and applied
+#[derive(Debug, Clone)]
+pub struct ProfileImport {
+    /// The settings that WOULD be in effect after applying the import
+    pub settings: Settings,
+    /// Human-readable list of values that differ from the current settings
+    /// e.g. "structure_editing.smart_enter: true → false"
+    pub changes: Vec<String>,
+    /// Values that could not be imported, with the reason
+    pub skipped: Vec<String>,
+}
+
+// ============================================================================
+// EXPORT
+// ============================================================================
+
+/// Serialize the current preferences as a profile
+///
+/// Machine-specific values (LOCAL_ONLY_KEYS) are left out, so importing a
+/// profile elsewhere keeps that machine's own values.
+pub fn export_profile(settings: &Settings) -> Result<String> {
+    let profile = Profile {
+        version: PROFILE_VERSION,
+        settings: settings.clone(),
+    };
+    let mut value = serde_json::to_value(&profile).context("Failed to serialize profile")?;
+    if let Some(settings) = value.get_mut("settings").and_then(Value::as_object_mut) {
+        for key in LOCAL_ONLY_KEYS {
+            settings.remove(key);
+        }
+    }
+    serde_json::to_string_pretty(&value).context("Failed to serialize profile")
+}
+
+/// Write the current profile into the data directory before an import
+/// replaces it, so a bad import can be undone by importing the backup
+///
+/// Backups go to `<data dir>/profile-backups/profile-<timestamp>.json`.
+pub fn write_backup(settings: &Settings) -> Result<PathBuf> {
+    let dir = storage::get_data_dir()?.join("profile-backups");
+    let path = dir.join(format!("profile-{}.json", storage::unix_now()));
+    storage::save_text_file(
+        &path,
+        &export_profile(settings)?,
+        storage::BackupSettings::OFF,
+    )?;
+    Ok(path)
+}
+
+// ============================================================================
+// IMPORT
+// ============================================================================
+
+/// Read a profile and work out what applying it would change
+///
+/// Nothing is modified here - the caller decides whether to apply
+/// `ProfileImport::settings` after showing the preview.
+///
+/// ERRORS:
+/// Only problems with the file as a whole are errors (not JSON, no version,
+/// version too new). Problems with individual values end up in `skipped`.
+pub fn prepare_import(json: &str, current: &Settings) -> Result<ProfileImport> {
+    let root: Value = serde_json::from_str(json).context("Profile is not valid JSON")?;
+
+    let Some(object) = root.as_object() else {
+        bail!("Profile must be a JSON object");
+    };
+
+    let version = object
+        .get("version")
+        .and_then(Value::as_u64)
+        .context("Not a BookScript profile (missing version)")?;
+    if version > PROFILE_VERSION {
+        bail!(
+            "Profile version {} is newer than this app supports ({})",
+            version,
+            PROFILE_VERSION
+        );
+    }
+
+    let mut skipped = Vec::new();
+
+    // Top-level sections other than "settings" come from newer versions
+    // of the app (or from hand-editing) - we can't use them
+    for key in object.keys() {
+        if key != "version" && key != "settings" {
+            skipped.push(format!("{}: not supported by this version", key));
+        }
+    }
+
+    let current_value = serde_json::to_value(current).context("Failed to serialize settings")?;
+    let mut merged = current_value.clone();
+
+    if let Some(incoming) = object.get("settings") {
+        let mut leaves = Vec::new();
+        collect_leaves(incoming, String::new(), &mut leaves);
+
+        for (pointer, value) in leaves {
+            apply_leaf(&mut merged, &pointer, value, &mut skipped);
+        }
+    } else {
+        skipped.push(String::from("settings: missing from profile"));
+    }
+
+    let settings: Settings =
+        serde_json::from_value(merged.clone()).context("Failed to build imported settings")?;
+
+    let mut changes = Vec::new();
+    describe_changes(&current_value, &merged, String::new(), &mut changes);
+
+    Ok(ProfileImport {
+        settings,
+        changes,
+        skipped,
+    })
+}
+
+/// Try to set one value, keeping it only if Settings still deserializes
+fn apply_leaf(merged: &mut Value, pointer: &str, value: &Value, skipped: &mut Vec<String>) {
+    let name = pointer_to_name(pointer);
+
+    let mut candidate = merged.clone();
+    let Some(slot) = candidate.pointer_mut(pointer) else {
+        skipped.push(format!("{}: unknown setting", name));
+        return;
+    };
+    *slot = value.clone();
+
+    match serde_json::from_value::<Settings>(candidate.clone()) {
+        Ok(_) => *merged = candidate,
+        Err(e) => skipped.push(format!("{}: {}", name, e)),
+    }
+}
+
+/// Flatten nested objects into (JSON pointer, value) pairs
+///
+/// {"a": {"b": 1, "c": 2}} → [("/a/b", 1), ("/a/c", 2)]
+fn collect_leaves<'a>(value: &'a Value, prefix: String, out: &mut Vec<(String, &'a Value)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                // JSON pointer escaping: "~" → "~0", "/" → "~1"
+                let escaped = key.replace('~', "~0").replace('/', "~1");
+                collect_leaves(child, format!("{}/{}", prefix, escaped), out);
+            }
+        }
+        _ => out.push((prefix, value)),
+    }
+}
+
+/// Compare two settings trees and list every leaf that differs
+fn describe_changes(old: &Value, new: &Value, prefix: String, out: &mut Vec<String>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, new_child) in new_map {
+                let path = format!("{}/{}", prefix, key);
+                match old_map.get(key) {
+                    Some(old_child) => describe_changes(old_child, new_child, path, out),
+                    None => out.push(format!("{}: (new) {}", pointer_to_name(&path), new_child)),
+                }
+            }
+        }
+        _ if old != new => {
+            out.push(format!("{}: {} → {}", pointer_to_name(&prefix), old, new));
+        }
+        _ => {}
+    }
+}
+
+/// "/structure_editing/smart_enter" → "structure_editing.smart_enter"
+fn pointer_to_name(pointer: &str) -> String {
+    pointer
+        .trim_start_matches('/')
+        .replace('/', ".")
+        .replace("~1", "/")
+        .replace("~0", "~")
+}
@@ -0,0 +1,407 @@
+//! FILE: src/project.rs
+//!
+//! Projects: a book kept as several documents (one per chapter, say) that
+//! belong together.
+//!
+//! A project is a small JSON file with the `.bksproj` extension:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "metadata": { "title": "The Long Way", "author": "Ann Smith" },
+//!   "files": ["chapters/01-arrival.bks", "chapters/02-the-house.bks"],
+//!   "settings": { "target_words": 80000 },
+//!   "characters": [
+//!     { "name": "ANN", "description": "The narrator", "notes": "" }
+//!   ]
+//! }
+//! ```
+//!
+//! The files are listed in reading order, relative to the folder the
+//! project file is in and with `/` separators, so a project folder can be
+//! moved, synced or shared between systems without breaking. A file
+//! outside that folder is stored with its absolute path instead.
+//!
+//! The project only lists documents - each one is still an ordinary
+//! BookScript file, opened, edited and saved on its own. While a project is
+//! open the sidebar shows its files instead of the outline; the open
+//! document's outline is nested under its entry (see show).
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Path::strip_prefix to make a path relative
+//! - Vec::swap and Vec::remove for reordering a list in place
+//! - Returning an enum from UI code to report what was clicked
+
+use crate::app::format_count;
+use crate::compile::CompileSettings;
+use crate::outline;
+use crate::parser::{self, DocumentStructure};
+use crate::storage;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Extension of project files
+pub const PROJECT_EXTENSION: &str = "bksproj";
+
+/// Version written into project files
+///
+/// Bump this when the layout changes in a way older versions of the app
+/// can't understand. Opening a project with a HIGHER version is refused.
+pub const PROJECT_VERSION: u64 = 1;
+
+// ============================================================================
+// PROJECT FILE
+// ============================================================================
+
+/// The contents of a `.bksproj` file
+///
+/// `#[serde(default)]` lets a hand-written project leave out anything but
+/// the file list.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Project {
+    pub version: u64,
+    pub metadata: ProjectMetadata,
+    /// The project's documents in reading order, relative to the project
+    /// file's folder (see module docs)
+    pub files: Vec<String>,
+    pub settings: ProjectSettings,
+    /// What the writer keeps on each character (the Characters panel)
+    pub characters: Vec<CharacterProfile>,
+}
+
+impl Default for Project {
+    fn default() -> Self {
+        Self {
+            version: PROJECT_VERSION,
+            metadata: ProjectMetadata::default(),
+            files: Vec::new(),
+            settings: ProjectSettings::default(),
+            characters: Vec::new(),
+        }
+    }
+}
+
+/// What the book is called and who wrote it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectMetadata {
+    pub title: String,
+    pub author: String,
+}
+
+/// Notes on one character
+///
+/// Who speaks where comes from the text (parser::character_report); only
+/// what can't be read from it is kept here.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CharacterProfile {
+    /// Normalized like a cue (parser::normalize_cue), so it matches the
+    /// report's names
+    pub name: String,
+    /// One line: who they are
+    pub description: String,
+    pub notes: String,
+}
+
+/// Settings that belong to the book rather than to the app
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectSettings {
+    /// Words the finished book should have, shown against the total in the
+    /// sidebar (0 = no target)
+    pub target_words: usize,
+    /// What File → Compile includes and how
+    pub compile: CompileSettings,
+    /// Commit the project folder to Git each time one of its documents is
+    /// saved (View → Git)
+    pub git_commit_on_save: bool,
+}
+
+/// Read a project file
+///
+/// Err if it can't be read, isn't a project, or was written by a newer
+/// version of the app.
+pub fn load_project(path: &Path) -> Result<Project> {
+    let json = storage::load_text_file(path)?;
+    let project: Project = serde_json::from_str(&json)
+        .context(format!("Not a valid project file: {}", path.display()))?;
+    if project.version > PROJECT_VERSION {
+        bail!(
+            "Project version {} is newer than this app supports ({})",
+            project.version,
+            PROJECT_VERSION
+        );
+    }
+    Ok(project)
+}
+
+/// Write a project file
+pub fn save_project(path: &Path, project: &Project) -> Result<()> {
+    let json = serde_json::to_string_pretty(project).context("Failed to serialize project")?;
+    storage::save_text_file(path, &json, storage::BackupSettings::OFF)
+}
+
+// ============================================================================
+// OPEN PROJECT
+// ============================================================================
+
+/// A project as the app holds it while it's open
+#[derive(Debug, Clone)]
+pub struct OpenProject {
+    /// Where the `.bksproj` file is
+    pub path: PathBuf,
+    pub project: Project,
+    /// Word count of each file, in the same order as `project.files`
+    /// (None if the file couldn't be read)
+    ///
+    /// Counted when the project opens or a file is added; the open
+    /// document's count is kept current by App as it changes.
+    pub word_counts: Vec<Option<usize>>,
+    /// Whether those counts leave out tag lines (the word count setting)
+    pub exclude_tags: bool,
+}
+
+impl OpenProject {
+    /// Wrap a loaded project, counting its files' words
+    pub fn new(path: PathBuf, project: Project, exclude_tags: bool) -> Self {
+        let mut open = Self {
+            path,
+            project,
+            word_counts: Vec::new(),
+            exclude_tags,
+        };
+        open.recount(exclude_tags);
+        open
+    }
+
+    /// Count the words of every file again
+    pub fn recount(&mut self, exclude_tags: bool) {
+        self.exclude_tags = exclude_tags;
+        self.word_counts = (0..self.project.files.len())
+            .map(|index| count_words(&self.file_path(index), exclude_tags))
+            .collect();
+    }
+
+    /// The folder the project file is in
+    pub fn dir(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new(""))
+    }
+
+    /// The project's title, or its file name if it has none
+    pub fn title(&self) -> String {
+        match self.project.metadata.title.trim() {
+            "" => self
+                .path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            title => title.to_string(),
+        }
+    }
+
+    /// Full path of the file at `index` in the list
+    pub fn file_path(&self, index: usize) -> PathBuf {
+        let entry = &self.project.files[index];
+        let path = Path::new(entry);
+        if path.is_absolute() {
+            return path.to_path_buf();
+        }
+        entry
+            .split('/')
+            .fold(self.dir().to_path_buf(), |path, part| path.join(part))
+    }
+
+    /// Where `path` is in the list, if it's there
+    pub fn index_of(&self, path: &Path) -> Option<usize> {
+        (0..self.project.files.len()).find(|&index| self.file_path(index) == path)
+    }
+
+    /// `path` as the project file lists it: relative to the project's
+    /// folder with `/` separators, or absolute if it's outside
+    pub fn entry_for(&self, path: &Path) -> String {
+        match path.strip_prefix(self.dir()) {
+            // Stored with `/` whatever the system, so the project works
+            // on other systems too
+            Ok(relative) => relative
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            Err(_) => path.to_string_lossy().into_owned(),
+        }
+    }
+
+    /// Add a document to the end of the list; false if it's already there
+    pub fn add_file(&mut self, path: &Path) -> bool {
+        if self.index_of(path).is_some() {
+            return false;
+        }
+        let entry = self.entry_for(path);
+        self.project.files.push(entry);
+        self.word_counts.push(count_words(path, self.exclude_tags));
+        true
+    }
+
+    /// Move the file at `index` one place up (-1) or down (1) the list
+    pub fn move_file(&mut self, index: usize, offset: isize) {
+        let Some(target) = index.checked_add_signed(offset) else {
+            return;
+        };
+        if target < self.project.files.len() {
+            self.project.files.swap(index, target);
+            self.word_counts.swap(index, target);
+        }
+    }
+
+    /// Take the file at `index` out of the project (the file itself stays)
+    pub fn remove_file(&mut self, index: usize) {
+        self.project.files.remove(index);
+        self.word_counts.remove(index);
+    }
+
+    /// Words in all the files that could be read
+    pub fn total_words(&self) -> usize {
+        self.word_counts.iter().flatten().sum()
+    }
+}
+
+/// A file's word count, or None if it can't be read
+fn count_words(path: &Path, exclude_tags: bool) -> Option<usize> {
+    let text = storage::load_text_file(path).ok()?;
+    Some(parser::count_stats(&text, exclude_tags).words)
+}
+
+// ============================================================================
+// SIDEBAR
+// ============================================================================
+
+/// What the user did in the project sidebar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectAction {
+    /// Open the file at this index
+    Open(usize),
+    /// Jump to this 1-based line of the open document (its outline)
+    Jump(usize),
+    /// Move the file at this index up (-1) or down (1)
+    Move(usize, isize),
+    /// Take the file at this index out of the project
+    Remove(usize),
+    /// Add the open document to the project
+    AddCurrent,
+    /// Create a new document and add it
+    NewFile,
+}
+
+/// Draw the project's file tree
+///
+/// `active` is the open document's path; its entry is highlighted and holds
+/// that document's outline. Files that can't be read are greyed out.
+/// Nothing changes here - App applies the returned action.
+pub fn show(
+    ui: &mut egui::Ui,
+    open: &OpenProject,
+    active: Option<&Path>,
+    structure: &DocumentStructure,
+) -> Option<ProjectAction> {
+    let mut action = None;
+
+    let total = format_count(open.total_words());
+    match open.project.settings.target_words {
+        0 => ui.weak(format!("{} words", total)),
+        target => ui.weak(format!("{} of {} words", total, format_count(target))),
+    };
+    ui.horizontal(|ui| {
+        if ui
+            .button("New…")
+            .on_hover_text("Create a new document in the project")
+            .clicked()
+        {
+            action = Some(ProjectAction::NewFile);
+        }
+        let can_add = active.is_some_and(|path| open.index_of(path).is_none());
+        if ui
+            .add_enabled(can_add, egui::Button::new("Add open document"))
+            .clicked()
+        {
+            action = Some(ProjectAction::AddCurrent);
+        }
+    });
+    ui.separator();
+
+    if open.project.files.is_empty() {
+        ui.label("No documents yet.");
+        return action;
+    }
+
+    let count = open.project.files.len();
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        for (index, entry) in open.project.files.iter().enumerate() {
+            let path = open.file_path(index);
+            let is_active = active == Some(path.as_path());
+            let name = entry.rsplit('/').next().unwrap_or(entry);
+            let label = match open.word_counts.get(index).copied().flatten() {
+                Some(words) => format!("{} ({} words)", name, format_count(words)),
+                None => format!("{} (missing)", name),
+            };
+
+            let header = |ui: &mut egui::Ui| {
+                let response = ui
+                    .selectable_label(is_active, label)
+                    .on_hover_text(path.display().to_string());
+                if response.clicked() {
+                    action = Some(ProjectAction::Open(index));
+                }
+                response.context_menu(|ui| {
+                    if ui
+                        .add_enabled(index > 0, egui::Button::new("Move up"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        action = Some(ProjectAction::Move(index, -1));
+                    }
+                    if ui
+                        .add_enabled(index + 1 < count, egui::Button::new("Move down"))
+                        .clicked()
+                    {
+                        ui.close_menu();
+                        action = Some(ProjectAction::Move(index, 1));
+                    }
+                    if ui.button("Remove from project").clicked() {
+                        ui.close_menu();
+                        action = Some(ProjectAction::Remove(index));
+                    }
+                });
+            };
+
+            // Only the open document has a parsed structure, so only its
+            // entry opens up into an outline
+            if is_active {
+                let id = ui.make_persistent_id("project_active_file");
+                egui::collapsing_header::CollapsingState::load_with_default_open(
+                    ui.ctx(),
+                    id,
+                    true,
+                )
+                .show_header(ui, header)
+                .body(|ui| {
+                    let mut clicked = None;
+                    outline::tree(ui, structure, &mut clicked);
+                    if let Some(line) = clicked {
+                        action = Some(ProjectAction::Jump(line));
+                    }
+                });
+            } else {
+                // Lined up with the open document's entry, past its arrow
+                ui.horizontal(|ui| {
+                    ui.add_space(ui.spacing().indent);
+                    header(ui);
+                });
+            }
+        }
+    });
+
+    action
+}
@@ -0,0 +1,526 @@
+//! FILE: src/project_search.rs
+//!
+//! Edit → Find in Project (Ctrl+Shift+F): the find bar's search run over
+//! every document of the open project, with the results listed by file in
+//! a panel. Clicking a result opens its document with the match selected.
+//!
+//! IN THE BACKGROUND:
+//! A worker thread does the reading and searching, so typing in the
+//! query field never waits on the disk. It keeps every file's text it has
+//! read with a trigram index of it (search_index.rs), so a plain-text
+//! search only looks at the lines that could match. A file is read again
+//! once its modification time changes, and only its changed lines are
+//! indexed again. Opening a project starts the worker indexing the
+//! project's files straight away. Documents open with unsaved changes are
+//! searched in full as they are in the editor, not as they are on disk.
+//!
+//! Results come back one file at a time as they're found, and the panel
+//! shows them as they arrive. Every search has an id; a new search (the
+//! query changed again) makes the worker drop the one it's on, and results
+//! still in the channel for an old id are ignored.
+//!
+//! Password-protected documents can't be searched and are counted as
+//! unreadable.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - A worker thread that checks for newer work between steps (try_recv)
+//!   and abandons what's gone stale
+//! - Caching by modification time with std::fs::metadata
+
+use crate::search::{self, SearchOptions};
+use crate::search_index::SearchIndex;
+use crate::storage;
+use regex::Regex;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::SystemTime;
+
+/// Most matching lines listed, over all files; a search stops there
+pub const MAX_LINES: usize = 2000;
+
+// ============================================================================
+// RESULTS
+// ============================================================================
+
+/// A line with at least one match, and the lines either side of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineMatch {
+    /// 1-based line number
+    pub line: usize,
+    pub text: String,
+    /// Byte ranges of the matches within `text`
+    pub matches: Vec<Range<usize>>,
+    /// Byte range of the line's first match in the whole document, for
+    /// selecting it
+    pub first: Range<usize>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// The matching lines of one document
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileMatches {
+    pub path: PathBuf,
+    pub lines: Vec<LineMatch>,
+    /// Matches in all, some lines having several
+    pub count: usize,
+}
+
+/// The matching lines of `text`, at most `limit` of them
+///
+/// Only the `candidates` lines (0-based, in order) are searched, if given.
+fn search_text(
+    text: &str,
+    regex: &Regex,
+    limit: usize,
+    candidates: Option<Vec<usize>>,
+) -> Vec<LineMatch> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let line_starts: Vec<usize> = lines
+        .iter()
+        .scan(0, |offset, line| {
+            let start = *offset;
+            *offset += line.len() + 1;
+            Some(start)
+        })
+        .collect();
+    let candidates = candidates.unwrap_or_else(|| (0..lines.len()).collect());
+    let mut found: Vec<LineMatch> = Vec::new();
+    for index in candidates {
+        let line = lines[index];
+        let line_start = line_starts[index];
+        let matches = search::find_matches(line, regex);
+        if let Some(first) = matches.first() {
+            if found.len() == limit {
+                break;
+            }
+            found.push(LineMatch {
+                line: index + 1,
+                text: line.to_string(),
+                first: line_start + first.start..line_start + first.end,
+                matches,
+                before: index.checked_sub(1).map(|before| lines[before].to_string()),
+                after: lines.get(index + 1).map(|after| after.to_string()),
+            });
+        }
+    }
+    found
+}
+
+// ============================================================================
+// WORKER
+// ============================================================================
+
+/// Work sent to the worker thread
+enum Request {
+    /// Read these files into the index ahead of the first search
+    Index(Vec<PathBuf>),
+    /// Search `files` in order; `unsaved` has the text of those open with
+    /// unsaved changes. `regex` is `query` and `options` built.
+    Search {
+        id: u64,
+        query: String,
+        options: SearchOptions,
+        regex: Regex,
+        files: Vec<PathBuf>,
+        unsaved: HashMap<PathBuf, String>,
+    },
+}
+
+/// What the worker sends back
+#[derive(Debug)]
+enum Event {
+    /// A file with matches
+    Found { id: u64, matches: FileMatches },
+    /// The search is over: how many files were searched, and which
+    /// couldn't be read
+    Done {
+        id: u64,
+        searched: usize,
+        unreadable: Vec<PathBuf>,
+    },
+}
+
+/// A file's text as last read and indexed, and the modification time it
+/// was read at
+struct Indexed {
+    modified: Option<SystemTime>,
+    /// None if it couldn't be read (or is password-protected)
+    index: Option<SearchIndex>,
+}
+
+/// The worker's copy of the files' text
+#[derive(Default)]
+struct Index {
+    files: HashMap<PathBuf, Indexed>,
+}
+
+impl Index {
+    /// The index of `path`, read again only if it has changed on disk since
+    fn get(&mut self, path: &Path) -> Option<&SearchIndex> {
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let stale = self
+            .files
+            .get(path)
+            .is_none_or(|indexed| indexed.modified != modified || modified.is_none());
+        if stale {
+            let text = storage::load_text_file(path).ok();
+            let previous = self.files.remove(path).and_then(|indexed| indexed.index);
+            let index = text.map(|text| match previous {
+                Some(mut index) => {
+                    if !index.update(&text) {
+                        index = SearchIndex::new(&text);
+                    }
+                    index
+                }
+                None => SearchIndex::new(&text),
+            });
+            self.files
+                .insert(path.to_path_buf(), Indexed { modified, index });
+        }
+        self.files.get(path)?.index.as_ref()
+    }
+}
+
+/// The worker thread: index and search until the App side goes away
+fn worker_loop(requests: Receiver<Request>, events: Sender<Event>) {
+    let mut index = Index::default();
+    let mut next = None;
+    loop {
+        let request = match next.take() {
+            Some(request) => request,
+            None => match requests.recv() {
+                Ok(request) => request,
+                Err(_) => return,
+            },
+        };
+        match request {
+            Request::Index(files) => {
+                for path in files {
+                    index.get(&path);
+                    // A search shouldn't wait for indexing to finish; files
+                    // not indexed yet are read when searched
+                    if let Ok(request) = requests.try_recv() {
+                        next = Some(request);
+                        break;
+                    }
+                }
+            }
+            Request::Search {
+                id,
+                query,
+                options,
+                regex,
+                files,
+                unsaved,
+            } => {
+                let mut searched = 0;
+                let mut unreadable = Vec::new();
+                let mut room = MAX_LINES;
+                for path in files {
+                    if let Ok(request) = requests.try_recv() {
+                        next = Some(request);
+                        break;
+                    }
+                    let lines = match unsaved.get(&path) {
+                        Some(text) => search_text(text, &regex, room, None),
+                        None => match index.get(&path) {
+                            Some(indexed) => {
+                                let candidates = indexed.candidate_lines(&query, options);
+                                search_text(indexed.text(), &regex, room, candidates)
+                            }
+                            None => {
+                                unreadable.push(path);
+                                continue;
+                            }
+                        },
+                    };
+                    searched += 1;
+                    if lines.is_empty() {
+                        continue;
+                    }
+                    room -= lines.len();
+                    let count = lines.iter().map(|l| l.matches.len()).sum();
+                    let matches = FileMatches { path, lines, count };
+                    if events.send(Event::Found { id, matches }).is_err() {
+                        return;
+                    }
+                    if room == 0 {
+                        break;
+                    }
+                }
+                if next.is_none() {
+                    let done = Event::Done {
+                        id,
+                        searched,
+                        unreadable,
+                    };
+                    if events.send(done).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ============================================================================
+// PANEL STATE
+// ============================================================================
+
+/// The Find in Project panel: its query, the search in flight, and the
+/// results so far
+#[derive(Default)]
+pub struct ProjectSearch {
+    pub query: String,
+    pub options: SearchOptions,
+    pub results: Vec<FileMatches>,
+    /// Why the query can't be searched for (a bad regex)
+    pub error: Option<String>,
+    /// The search whose results are showing, and whether it's finished
+    id: u64,
+    done: bool,
+    /// (files searched, files that couldn't be read) once done
+    searched: (usize, usize),
+    /// Move keyboard focus to the query field on the next frame
+    pub focus_field: bool,
+    worker: Option<(Sender<Request>, Receiver<Event>)>,
+}
+
+impl ProjectSearch {
+    /// The worker's channels, starting it the first time
+    fn worker(&mut self) -> &(Sender<Request>, Receiver<Event>) {
+        self.worker.get_or_insert_with(|| {
+            let (request_tx, request_rx) = mpsc::channel();
+            let (event_tx, event_rx) = mpsc::channel();
+            thread::spawn(move || worker_loop(request_rx, event_tx));
+            (request_tx, event_rx)
+        })
+    }
+
+    /// Read `files` into the worker's index now, ahead of any search
+    pub fn index(&mut self, files: Vec<PathBuf>) {
+        let _ = self.worker().0.send(Request::Index(files));
+    }
+
+    /// Search `files` for the query, replacing the results shown
+    ///
+    /// `unsaved` holds the editor text of documents with unsaved changes.
+    pub fn start(&mut self, files: Vec<PathBuf>, unsaved: HashMap<PathBuf, String>) {
+        self.results.clear();
+        self.id += 1;
+        self.done = true;
+        self.error = None;
+        if self.query.is_empty() {
+            return;
+        }
+        let regex = match search::build_regex(&self.query, self.options) {
+            Ok(regex) => regex,
+            Err(e) => {
+                self.error = Some(e);
+                return;
+            }
+        };
+        self.done = false;
+        let request = Request::Search {
+            id: self.id,
+            query: self.query.clone(),
+            options: self.options,
+            regex,
+            files,
+            unsaved,
+        };
+        let _ = self.worker().0.send(request);
+    }
+
+    /// Take in whatever results have arrived; true while the search is
+    /// still going
+    pub fn poll(&mut self) -> bool {
+        let Some((_, events)) = &self.worker else {
+            return false;
+        };
+        while let Ok(event) = events.try_recv() {
+            match event {
+                Event::Found { id, matches } if id == self.id => self.results.push(matches),
+                Event::Done {
+                    id,
+                    searched,
+                    unreadable,
+                } if id == self.id => {
+                    self.done = true;
+                    self.searched = (searched, unreadable.len());
+                }
+                // From a search since replaced
+                _ => {}
+            }
+        }
+        !self.done
+    }
+
+    /// "12 matches in 3 of 20 documents"
+    pub fn summary(&self) -> String {
+        if let Some(error) = &self.error {
+            return error.clone();
+        }
+        if self.query.is_empty() {
+            return String::new();
+        }
+        let matches: usize = self.results.iter().map(|f| f.count).sum();
+        let lines: usize = self.results.iter().map(|f| f.lines.len()).sum();
+        if !self.done {
+            return format!("Searching… {} found so far", matches);
+        }
+        let (searched, unreadable) = self.searched;
+        let mut summary = format!(
+            "{} {} in {} of {} {}",
+            matches,
+            if matches == 1 { "match" } else { "matches" },
+            self.results.len(),
+            searched,
+            if searched == 1 {
+                "document"
+            } else {
+                "documents"
+            }
+        );
+        if lines >= MAX_LINES {
+            summary.push_str(&format!(" (stopped at {} lines)", MAX_LINES));
+        }
+        if unreadable > 0 {
+            summary.push_str(&format!("; {} couldn't be read", unreadable));
+        }
+        summary
+    }
+}
+
+// ============================================================================
+// PANEL
+// ============================================================================
+
+/// What the user did in the panel
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchAction {
+    /// The query or an option changed: search again
+    Search,
+    /// Open this document with this match (a document byte range, on this
+    /// 1-based line) selected
+    Open {
+        path: PathBuf,
+        line: usize,
+        range: Range<usize>,
+    },
+    Close,
+}
+
+/// Draw the panel
+///
+/// `label` gives the name to list a document under (its project entry).
+pub fn show(
+    ui: &mut egui::Ui,
+    state: &mut ProjectSearch,
+    label: impl Fn(&Path) -> String,
+) -> Option<SearchAction> {
+    let mut action = None;
+    ui.horizontal(|ui| {
+        ui.heading("Find in Project");
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            if ui.small_button("✕").on_hover_text("Close (Esc)").clicked() {
+                action = Some(SearchAction::Close);
+            }
+        });
+    });
+
+    let field = ui.add(
+        egui::TextEdit::singleline(&mut state.query)
+            .hint_text("Search all documents")
+            .desired_width(f32::INFINITY),
+    );
+    if std::mem::take(&mut state.focus_field) {
+        field.request_focus();
+    }
+    let options_before = state.options;
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut state.options.case_sensitive, "Match case");
+        ui.checkbox(&mut state.options.whole_word, "Whole word");
+        ui.checkbox(&mut state.options.regex, "Regex");
+    });
+    if field.changed() || state.options != options_before {
+        action = Some(SearchAction::Search);
+    }
+
+    let summary = state.summary();
+    if state.error.is_some() {
+        ui.colored_label(ui.visuals().error_fg_color, summary);
+    } else if !summary.is_empty() {
+        ui.weak(summary);
+    }
+    ui.separator();
+
+    egui::ScrollArea::vertical()
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            for file in &state.results {
+                let heading = format!("{} ({})", label(&file.path), file.count);
+                egui::CollapsingHeader::new(heading)
+                    .id_salt(&file.path)
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        for (index, line) in file.lines.iter().enumerate() {
+                            // A neighbour that matches too is listed as a
+                            // result, not repeated as context
+                            let previous = index.checked_sub(1).map(|i| file.lines[i].line);
+                            let next = file.lines.get(index + 1).map(|l| l.line);
+                            if previous != Some(line.line - 1) {
+                                context_row(ui, line.before.as_deref());
+                            }
+                            if result_row(ui, line).clicked() {
+                                action = Some(SearchAction::Open {
+                                    path: file.path.clone(),
+                                    line: line.line,
+                                    range: line.first.clone(),
+                                });
+                            }
+                            if next != Some(line.line + 1) {
+                                context_row(ui, line.after.as_deref());
+                            }
+                        }
+                    });
+            }
+        });
+    action
+}
+
+/// A line next to a match, faint and without a number
+fn context_row(ui: &mut egui::Ui, text: Option<&str>) {
+    if let Some(text) = text.filter(|t| !t.trim().is_empty()) {
+        let text = egui::RichText::new(format!("       {}", text))
+            .small()
+            .weak();
+        ui.add(egui::Label::new(text).truncate());
+    }
+}
+
+/// One matching line: its number and text with the matches marked
+fn result_row(ui: &mut egui::Ui, line: &LineMatch) -> egui::Response {
+    let font = egui::TextStyle::Body.resolve(ui.style());
+    let plain = egui::TextFormat::simple(font, ui.visuals().text_color());
+    let marked = egui::TextFormat {
+        background: ui.visuals().selection.bg_fill,
+        ..plain.clone()
+    };
+
+    let mut job = egui::text::LayoutJob::default();
+    job.append(&format!("{:>5}  ", line.line), 0.0, plain.clone());
+    let mut copied_to = 0;
+    for range in &line.matches {
+        job.append(&line.text[copied_to..range.start], 0.0, plain.clone());
+        job.append(&line.text[range.clone()], 0.0, marked.clone());
+        copied_to = range.end;
+    }
+    job.append(&line.text[copied_to..], 0.0, plain);
+    ui.add(egui::Label::new(job).sense(egui::Sense::click()).truncate())
+        .on_hover_text("Open at this match")
+}
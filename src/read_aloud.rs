@@ -0,0 +1,358 @@
+//! FILE: src/read_aloud.rs
+//!
+//! Tools → Read Aloud: the selection, or the scene the caret is in, read
+//! out by the system's speech synthesizer while the sentence being read is
+//! highlighted in the editor. A toolbar above the editor has play/pause,
+//! previous/next sentence, the speed and stop.
+//!
+//! SPEAKING:
+//! As with printing, there's no speech API that works everywhere, so each
+//! sentence is handed to the platform's own program on its stdin:
+//! - macOS: `say`
+//! - Windows: PowerShell with .NET's System.Speech
+//! - Linux and the rest: `espeak-ng`, or `espeak` if that's missing
+//!
+//! One program runs per sentence, and the next starts when it exits; that's
+//! what tells us which sentence is being read. Pausing stops the program;
+//! playing again starts that sentence over. A new speed applies from the
+//! next sentence.
+//!
+//! WHAT'S READ:
+//! Tag lines (`[SCENE: ...]` and the like) and `[[comments]]` are skipped;
+//! everything else is read as written, cues included. A sentence ends at
+//! `.`, `!`, `?` or `…` (with any closing quotes or brackets) before a
+//! space, and at the end of every line.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - std::process::Child, polled with try_wait() instead of waited on
+//! - impl Drop so a reader that's thrown away stops talking
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::ops::Range;
+use std::process::{Child, Command, Stdio};
+
+/// Slowest and fastest speeds the toolbar offers, in words a minute
+pub const MIN_WPM: u32 = 80;
+pub const MAX_WPM: u32 = 400;
+
+/// Read-aloud settings (saved in Settings)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadAloudOptions {
+    /// Speaking speed, MIN_WPM to MAX_WPM
+    pub words_per_minute: u32,
+}
+
+impl Default for ReadAloudOptions {
+    fn default() -> Self {
+        Self {
+            words_per_minute: 180,
+        }
+    }
+}
+
+/// The byte ranges of the sentences to read in `range` of `text` (see
+/// module docs)
+pub fn sentences(text: &str, range: Range<usize>) -> Vec<Range<usize>> {
+    let mut sentences = Vec::new();
+    let mut line_start = range.start;
+    for line in text[range.clone()].split('\n') {
+        let line_range = line_start..line_start + line.len();
+        line_start = line_range.end + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || (trimmed.starts_with('[') && trimmed.ends_with(']')) {
+            continue;
+        }
+
+        let mut start = 0;
+        let mut chars = line.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            if !matches!(c, '.' | '!' | '?' | '…') {
+                continue;
+            }
+            let mut end = index + c.len_utf8();
+            while let Some(&(next, c)) = chars.peek() {
+                if !matches!(
+                    c,
+                    '.' | '!' | '?' | '…' | '"' | '”' | '’' | '\'' | ')' | ']' | '»'
+                ) {
+                    break;
+                }
+                end = next + c.len_utf8();
+                chars.next();
+            }
+            if chars.peek().is_some_and(|&(_, c)| c.is_whitespace()) {
+                push_sentence(&mut sentences, line, line_range.start, start..end);
+                start = end;
+            }
+        }
+        push_sentence(&mut sentences, line, line_range.start, start..line.len());
+    }
+    sentences
+}
+
+/// Add `range` of `line` (which starts at byte `offset`) as a sentence,
+/// trimmed, if it has anything to say
+fn push_sentence(
+    sentences: &mut Vec<Range<usize>>,
+    line: &str,
+    offset: usize,
+    range: Range<usize>,
+) {
+    let piece = &line[range.clone()];
+    if piece.trim().is_empty() {
+        return;
+    }
+    let start = range.start + (piece.len() - piece.trim_start().len());
+    let end = range.end - (piece.len() - piece.trim_end().len());
+    if !spoken(&line[start..end]).trim().is_empty() {
+        sentences.push(offset + start..offset + end);
+    }
+}
+
+/// A sentence as it's said: without `[[comments]]`
+fn spoken(sentence: &str) -> String {
+    let mut out = String::new();
+    let mut rest = sentence;
+    while let Some(start) = rest.find("[[") {
+        out.push_str(&rest[..start]);
+        match rest[start..].find("]]") {
+            Some(end) => rest = &rest[start + end + 2..],
+            None => rest = "",
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Start the speech program on `sentence`
+fn speak(sentence: &str, words_per_minute: u32) -> Result<Child> {
+    let rate = words_per_minute.to_string();
+    let programs: Vec<Command> = if cfg!(target_os = "macos") {
+        let mut say = Command::new("say");
+        say.args(["-r", &rate]);
+        vec![say]
+    } else if cfg!(windows) {
+        // System.Speech's Rate runs from -10 to 10, 0 being about 180 wpm
+        let rate = ((words_per_minute as i32 - 180) / 20).clamp(-10, 10);
+        let script = format!(
+            "[Console]::InputEncoding = [Text.Encoding]::UTF8; \
+             Add-Type -AssemblyName System.Speech; \
+             $voice = New-Object System.Speech.Synthesis.SpeechSynthesizer; \
+             $voice.Rate = {}; $voice.Speak([Console]::In.ReadToEnd())",
+            rate
+        );
+        let mut powershell = Command::new("powershell");
+        powershell.args(["-NoProfile", "-Command", &script]);
+        vec![powershell]
+    } else {
+        ["espeak-ng", "espeak"]
+            .iter()
+            .map(|program| {
+                let mut espeak = Command::new(program);
+                espeak.args(["-s", &rate, "--stdin"]);
+                espeak
+            })
+            .collect()
+    };
+
+    let mut tried = Vec::new();
+    for mut program in programs {
+        tried.push(program.get_program().to_string_lossy().into_owned());
+        let child = program
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        if let Ok(mut child) = child {
+            if let Some(mut stdin) = child.stdin.take() {
+                // Dropping stdin at the end of this block closes it, which
+                // tells the program the text is complete
+                stdin.write_all(spoken(sentence).as_bytes())?;
+            }
+            return Ok(child);
+        }
+    }
+    Err(anyhow!(
+        "No speech synthesizer found (tried {})",
+        tried.join(", ")
+    ))
+}
+
+/// What poll() found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    /// Still reading (or paused)
+    Reading,
+    /// The last sentence has been read
+    Finished,
+}
+
+/// Reading in progress
+pub struct ReadAloud {
+    /// Byte ranges of the sentences, in the text as it was when reading
+    /// started
+    sentences: Vec<Range<usize>>,
+    /// Their text, so editing the document can't change what's said
+    texts: Vec<String>,
+    /// The sentence being read (or to read on play)
+    current: usize,
+    /// The speech program reading it; None while paused
+    child: Option<Child>,
+    /// The editor's edit generation the ranges are for
+    generation: u64,
+    /// Set when `current` changes, until the editor has scrolled to it
+    moved: bool,
+}
+
+impl ReadAloud {
+    /// Start reading the sentences in `range` (bytes) of `text`, as it is
+    /// at edit `generation`
+    ///
+    /// Ok(None) if there's nothing there to read; Err if no speech program
+    /// could be started.
+    pub fn start(
+        text: &str,
+        range: Range<usize>,
+        generation: u64,
+        words_per_minute: u32,
+    ) -> Result<Option<Self>> {
+        let sentences = sentences(text, range);
+        let Some(first) = sentences.first() else {
+            return Ok(None);
+        };
+        let child = speak(&text[first.clone()], words_per_minute)?;
+        Ok(Some(Self {
+            texts: sentences
+                .iter()
+                .map(|r| text[r.clone()].to_string())
+                .collect(),
+            sentences,
+            current: 0,
+            child: Some(child),
+            generation,
+            moved: true,
+        }))
+    }
+
+    /// Move on to the next sentence if the current one is done; call once a
+    /// frame
+    pub fn poll(&mut self, words_per_minute: u32) -> Result<Progress> {
+        let Some(child) = &mut self.child else {
+            return Ok(Progress::Reading);
+        };
+        if child.try_wait()?.is_none() {
+            return Ok(Progress::Reading);
+        }
+        self.child = None;
+        if self.current + 1 >= self.sentences.len() {
+            return Ok(Progress::Finished);
+        }
+        self.go_to(self.current + 1, words_per_minute)?;
+        Ok(Progress::Reading)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.child.is_none()
+    }
+
+    /// Stop speaking, staying on the current sentence
+    pub fn pause(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            // Already finished is fine too
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    /// Read the current sentence from its start
+    pub fn play(&mut self, words_per_minute: u32) -> Result<()> {
+        self.go_to(self.current, words_per_minute)
+    }
+
+    /// Skip `by` sentences forward (or back, if negative) and read from
+    /// there, even if paused
+    pub fn skip(&mut self, by: isize, words_per_minute: u32) -> Result<()> {
+        let last = self.sentences.len() - 1;
+        let target = self.current.saturating_add_signed(by).min(last);
+        self.go_to(target, words_per_minute)
+    }
+
+    fn go_to(&mut self, sentence: usize, words_per_minute: u32) -> Result<()> {
+        self.pause();
+        self.moved |= sentence != self.current;
+        self.current = sentence;
+        self.child = Some(speak(&self.texts[sentence], words_per_minute)?);
+        Ok(())
+    }
+
+    /// Byte range of the sentence being read, if the text is still the one
+    /// reading started on (its edit `generation`)
+    pub fn highlight(&self, generation: u64) -> Option<Range<usize>> {
+        (generation == self.generation).then(|| self.sentences[self.current].clone())
+    }
+
+    /// "Sentence 3 of 12"
+    pub fn position(&self) -> String {
+        format!("Sentence {} of {}", self.current + 1, self.sentences.len())
+    }
+
+    /// Whether the sentence has changed since the last call
+    pub fn take_moved(&mut self) -> bool {
+        std::mem::take(&mut self.moved)
+    }
+}
+
+impl Drop for ReadAloud {
+    fn drop(&mut self) {
+        self.pause();
+    }
+}
+
+/// What the toolbar asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolbarAction {
+    Play,
+    Pause,
+    Skip(isize),
+    Stop,
+}
+
+/// Draw the toolbar's contents
+///
+/// The speed slider changes `options` directly.
+pub fn toolbar(
+    ui: &mut egui::Ui,
+    reader: &ReadAloud,
+    options: &mut ReadAloudOptions,
+) -> Option<ToolbarAction> {
+    let mut action = None;
+    ui.horizontal(|ui| {
+        ui.label("Read aloud:");
+        if ui.button("⏮").on_hover_text("Previous sentence").clicked() {
+            action = Some(ToolbarAction::Skip(-1));
+        }
+        if reader.is_paused() {
+            if ui.button("▶").on_hover_text("Play").clicked() {
+                action = Some(ToolbarAction::Play);
+            }
+        } else if ui.button("⏸").on_hover_text("Pause").clicked() {
+            action = Some(ToolbarAction::Pause);
+        }
+        if ui.button("⏭").on_hover_text("Next sentence").clicked() {
+            action = Some(ToolbarAction::Skip(1));
+        }
+        if ui.button("⏹").on_hover_text("Stop").clicked() {
+            action = Some(ToolbarAction::Stop);
+        }
+        ui.separator();
+        ui.label("Speed");
+        ui.add(egui::Slider::new(&mut options.words_per_minute, MIN_WPM..=MAX_WPM).suffix(" wpm"))
+            .on_hover_text("Applies from the next sentence");
+        ui.separator();
+        ui.weak(reader.position());
+    });
+    action
+}
@@ -0,0 +1,316 @@
+//! FILE: src/readability.rs
+//!
+//! Readability: how hard the manuscript is to read, and how long it takes,
+//! for the Tools → Statistics window.
+//!
+//! Everything comes from three counts - words, sentences and syllables -
+//! taken over what a reader reads (prose, dialogue, stage directions; not
+//! headings or cues):
+//! - Flesch reading ease:
+//!   `206.835 - 1.015 × words per sentence - 84.6 × syllables per word`.
+//!   Higher is easier; most fiction lands between 70 and 90
+//! - Flesch-Kincaid grade level:
+//!   `0.39 × words per sentence + 11.8 × syllables per word - 15.59`,
+//!   roughly the US school grade that reads it comfortably
+//! - Reading time at READING_WPM words a minute
+//!
+//! Syllables are guessed from spelling (syllables()), not looked up, so
+//! scores are close to other tools' but rarely the same to the decimal.
+//!
+//! KEEPING UP WITH TYPING:
+//! The counts are taken per paragraph or speech, and a Cache remembers
+//! them by the block's text. Rebuilding the report after an edit only
+//! counts the blocks that changed; the rest are looked up.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - impl AddAssign so counts add up with +=
+//! - std::mem::take to swap a map out and keep only what was used
+
+use crate::storage;
+use std::collections::HashMap;
+use std::ops::AddAssign;
+
+/// Words a minute an adult reads silently, the figure reading times use
+pub const READING_WPM: f32 = 238.0;
+
+/// What the scores are worked out from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub words: usize,
+    pub sentences: usize,
+    pub syllables: usize,
+}
+
+impl AddAssign for Counts {
+    fn add_assign(&mut self, other: Counts) {
+        self.words += other.words;
+        self.sentences += other.sentences;
+        self.syllables += other.syllables;
+    }
+}
+
+impl Counts {
+    /// Count one paragraph or speech
+    ///
+    /// Words are counted as parser::count_stats counts them. A sentence
+    /// ends at `.`, `!` or `?` (a run of them, like "?!" or "...", ends one
+    /// sentence) or at the end of the text. "Mr." ends a sentence too - the
+    /// scores are a guide, not a grammar check.
+    pub fn of(text: &str) -> Counts {
+        let mut counts = Counts::default();
+        for word in text.split_whitespace() {
+            if word.chars().any(char::is_alphanumeric) {
+                counts.words += 1;
+                counts.syllables += syllables(word);
+            }
+        }
+        counts.sentences = text
+            .split(['.', '!', '?'])
+            .filter(|piece| piece.chars().any(char::is_alphanumeric))
+            .count();
+        counts
+    }
+
+    /// Words per sentence and syllables per word; None without any words
+    fn ratios(&self) -> Option<(f32, f32)> {
+        (self.words > 0 && self.sentences > 0).then(|| {
+            (
+                self.words as f32 / self.sentences as f32,
+                self.syllables as f32 / self.words as f32,
+            )
+        })
+    }
+
+    /// Average words per sentence; None without any sentences
+    pub fn sentence_length(&self) -> Option<f32> {
+        self.ratios().map(|(words, _)| words)
+    }
+
+    /// Flesch reading ease (see module docs)
+    pub fn reading_ease(&self) -> Option<f32> {
+        self.ratios()
+            .map(|(words, syllables)| 206.835 - 1.015 * words - 84.6 * syllables)
+    }
+
+    /// Flesch-Kincaid grade level (see module docs), never below 0
+    pub fn grade_level(&self) -> Option<f32> {
+        self.ratios()
+            .map(|(words, syllables)| (0.39 * words + 11.8 * syllables - 15.59).max(0.0))
+    }
+
+    /// Minutes it takes to read at READING_WPM
+    pub fn reading_minutes(&self) -> f32 {
+        self.words as f32 / READING_WPM
+    }
+}
+
+/// Syllables in a word, guessed from its spelling
+///
+/// Each run of vowels (y included) is a syllable, less a silent final "e"
+/// ("make"), though not the "e" of a final consonant + "le" ("table").
+/// Every word has at least one, numbers included.
+pub fn syllables(word: &str) -> usize {
+    let letters: Vec<char> = word
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .flat_map(char::to_lowercase)
+        .collect();
+    let vowel = |c: char| matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y');
+
+    let mut count = 0;
+    let mut in_vowels = false;
+    for &c in &letters {
+        if vowel(c) && !in_vowels {
+            count += 1;
+        }
+        in_vowels = vowel(c);
+    }
+    if let [.., before, last, 'e'] = letters[..] {
+        let le = last == 'l' && !vowel(before);
+        if !vowel(last) && !le {
+            count -= 1;
+        }
+    }
+    count.max(1)
+}
+
+/// What a reading ease score means, in the usual bands
+pub fn ease_label(score: f32) -> &'static str {
+    match score {
+        s if s >= 90.0 => "very easy",
+        s if s >= 80.0 => "easy",
+        s if s >= 70.0 => "fairly easy",
+        s if s >= 60.0 => "plain",
+        s if s >= 50.0 => "fairly difficult",
+        s if s >= 30.0 => "difficult",
+        _ => "very difficult",
+    }
+}
+
+/// "under 1 min", "12 min", "2 h 05 min"
+pub fn format_reading_time(minutes: f32) -> String {
+    let minutes = minutes.round() as u64;
+    match minutes {
+        0 => String::from("under 1 min"),
+        m if m < 60 => format!("{} min", m),
+        m => format!("{} h {:02} min", m / 60, m % 60),
+    }
+}
+
+/// The counts of blocks already seen, by their text
+///
+/// Each rebuild asks for every block's counts, then calls finish(), which
+/// forgets blocks that weren't asked for (text since edited or deleted), so
+/// the cache stays the size of the document.
+#[derive(Debug, Default)]
+pub struct Cache {
+    /// Counts from the last rebuild, by content hash
+    previous: HashMap<u64, Counts>,
+    /// Counts asked for in this rebuild
+    current: HashMap<u64, Counts>,
+}
+
+impl Cache {
+    /// The counts of `text`, counted now only if it's new
+    pub fn counts(&mut self, text: &str) -> Counts {
+        let key = storage::content_hash(text);
+        let counts = self
+            .current
+            .get(&key)
+            .or_else(|| self.previous.get(&key))
+            .copied()
+            .unwrap_or_else(|| Counts::of(text));
+        self.current.insert(key, counts);
+        counts
+    }
+
+    /// End a rebuild: keep only the blocks it used
+    pub fn finish(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: Option<f32>, expected: f32) -> bool {
+        actual.is_some_and(|actual| (actual - expected).abs() < 0.01)
+    }
+
+    #[test]
+    fn scores_of_a_known_passage() {
+        // 9 words, 2 sentences, 10 syllables ("happy" has two)
+        let counts = Counts::of("The cat sat on the mat. It was happy.");
+        assert_eq!(
+            counts,
+            Counts {
+                words: 9,
+                sentences: 2,
+                syllables: 10
+            }
+        );
+        assert!(close(counts.sentence_length(), 4.5));
+        // 206.835 - 1.015 × 4.5 - 84.6 × 10/9
+        assert!(close(counts.reading_ease(), 108.2675));
+        // 0.39 × 4.5 + 11.8 × 10/9 - 15.59 is below 0
+        assert!(close(counts.grade_level(), 0.0));
+
+        // 5 words, 1 sentence, 21 syllables by spelling ("requires" is
+        // guessed at 3)
+        let counts = Counts::of("Institutional communication requires careful consideration!");
+        assert_eq!(
+            (counts.words, counts.sentences, counts.syllables),
+            (5, 1, 21)
+        );
+        assert!(close(
+            counts.reading_ease(),
+            206.835 - 1.015 * 5.0 - 84.6 * 4.2
+        ));
+        assert!(close(counts.grade_level(), 0.39 * 5.0 + 11.8 * 4.2 - 15.59));
+        assert_eq!(ease_label(counts.reading_ease().unwrap()), "very difficult");
+    }
+
+    #[test]
+    fn nothing_to_read_has_no_scores() {
+        let counts = Counts::of("  ... -- !  ");
+        assert_eq!(counts, Counts::default());
+        assert_eq!(counts.sentence_length(), None);
+        assert_eq!(counts.reading_ease(), None);
+        assert_eq!(counts.grade_level(), None);
+    }
+
+    #[test]
+    fn sentence_ends() {
+        assert_eq!(Counts::of("Wait?! What... Yes").sentences, 3);
+        assert_eq!(Counts::of("No full stop at the end").sentences, 1);
+        assert_eq!(Counts::of("Mr. Smith left.").sentences, 2);
+    }
+
+    #[test]
+    fn syllables_from_spelling() {
+        for (word, expected) in [
+            ("cat", 1),
+            ("the", 1),
+            ("make", 1),
+            ("table", 2),
+            ("people", 2),
+            ("happy", 2),
+            ("rhythm", 1),
+            ("queue", 1),
+            ("Beautiful,", 3),
+            ("42", 1),
+        ] {
+            assert_eq!(syllables(word), expected, "{word}");
+        }
+    }
+
+    #[test]
+    fn counts_add_up() {
+        let mut total = Counts::of("One two.");
+        total += Counts::of("Three four five!");
+        assert_eq!(
+            total,
+            Counts {
+                words: 5,
+                sentences: 2,
+                syllables: 5
+            }
+        );
+        assert!(
+            (Counts {
+                words: 476,
+                ..total
+            }
+            .reading_minutes()
+                - 2.0)
+                .abs()
+                < 0.001
+        );
+    }
+
+    #[test]
+    fn labels_and_times() {
+        assert_eq!(ease_label(95.0), "very easy");
+        assert_eq!(ease_label(70.0), "fairly easy");
+        assert_eq!(ease_label(29.9), "very difficult");
+        assert_eq!(format_reading_time(0.4), "under 1 min");
+        assert_eq!(format_reading_time(12.2), "12 min");
+        assert_eq!(format_reading_time(125.0), "2 h 05 min");
+    }
+
+    #[test]
+    fn the_cache_keeps_only_blocks_still_used() {
+        let mut cache = Cache::default();
+        assert_eq!(cache.counts("One two."), Counts::of("One two."));
+        cache.counts("Three.");
+        cache.finish();
+        assert_eq!(cache.previous.len(), 2);
+
+        // Same text, same counts, whether counted again or not
+        assert_eq!(cache.counts("Three."), Counts::of("Three."));
+        cache.finish();
+        assert_eq!(cache.previous.len(), 1);
+        assert!(cache.current.is_empty());
+    }
+}
@@ -0,0 +1,454 @@
+//! FILE: src/search.rs
+//!
+//! Find and replace: turning the find bar's query and options into matches,
+//! and the matches into replaced text.
+//!
+//! Every search runs through a Regex, even a plain-text one: the query is
+//! escaped first, and the options become regex settings - case-insensitive
+//! matching, `\b...\b` around the query for whole words. One code path then
+//! covers all eight combinations of the three checkboxes.
+//!
+//! Matches are byte ranges into the text. Empty matches (a regex like `^`
+//! or `x*`) are dropped: there's nothing to highlight or replace.
+//!
+//! The find bar itself is drawn by app.rs; FindState holds what it needs
+//! between frames. For a long document that includes a trigram index of
+//! it (search_index.rs), built on a background thread and then kept up to
+//! date with each edit, so a search only looks at the lines that could
+//! match.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - regex::escape, and Captures::expand for `$1` in replacements
+//! - Result<_, String> for errors meant for the user (a bad regex)
+//! - Range<usize> for byte ranges
+
+use crate::search_index::{SearchIndex, INDEX_MIN_BYTES};
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+// ============================================================================
+// QUERY
+// ============================================================================
+
+/// The find bar's checkboxes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SearchOptions {
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// Treat the query as a regular expression (and allow `$1` in the
+    /// replacement)
+    pub regex: bool,
+}
+
+/// Build the Regex a query and options stand for
+///
+/// `^` and `$` match at the start and end of every line, not just of the
+/// whole document.
+///
+/// Err holds the regex crate's message, for the find bar to show. Plain
+/// text queries are escaped, so they can't fail.
+pub fn build_regex(query: &str, options: SearchOptions) -> Result<Regex, String> {
+    let pattern = if options.regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    let pattern = if options.whole_word {
+        format!(r"\b(?:{pattern})\b")
+    } else {
+        pattern
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .multi_line(true)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Every non-empty match of `regex` in `text`, as byte ranges, in order
+pub fn find_matches(text: &str, regex: &Regex) -> Vec<Range<usize>> {
+    regex
+        .find_iter(text)
+        .filter(|m| !m.is_empty())
+        .map(|m| m.range())
+        .collect()
+}
+
+// ============================================================================
+// REPLACING
+// ============================================================================
+
+/// What the match at `range` should become
+///
+/// In regex mode `$1`, `${name}` and `$$` in `replacement` are expanded
+/// from the match's capture groups; otherwise it's used exactly as typed.
+pub fn replacement_for(
+    text: &str,
+    range: Range<usize>,
+    regex: &Regex,
+    replacement: &str,
+    options: SearchOptions,
+) -> String {
+    if !options.regex {
+        return replacement.to_string();
+    }
+    // captures_at searches from range.start but still sees the text
+    // before it, so `\b` and `^` behave as they did when the match was found
+    match regex.captures_at(text, range.start) {
+        Some(captures) if captures.get(0).is_some_and(|m| m.range() == range) => {
+            let mut expanded = String::new();
+            captures.expand(replacement, &mut expanded);
+            expanded
+        }
+        _ => replacement.to_string(),
+    }
+}
+
+/// Replace every non-empty match, returning the new text and how many
+/// matches were replaced
+pub fn replace_all(
+    text: &str,
+    regex: &Regex,
+    replacement: &str,
+    options: SearchOptions,
+) -> (String, usize) {
+    let mut result = String::with_capacity(text.len());
+    let mut copied_to = 0;
+    let mut count = 0;
+
+    for captures in regex.captures_iter(text) {
+        let whole = captures.get(0).expect("group 0 is always present");
+        if whole.is_empty() {
+            continue;
+        }
+        result.push_str(&text[copied_to..whole.start()]);
+        if options.regex {
+            captures.expand(replacement, &mut result);
+        } else {
+            result.push_str(replacement);
+        }
+        copied_to = whole.end();
+        count += 1;
+    }
+    result.push_str(&text[copied_to..]);
+
+    (result, count)
+}
+
+// ============================================================================
+// FIND BAR STATE
+// ============================================================================
+
+/// Everything the find bar remembers between frames
+#[derive(Debug, Default)]
+pub struct FindState {
+    pub query: String,
+    pub replacement: String,
+    pub options: SearchOptions,
+    /// Show the Replace row too (Ctrl+H rather than Ctrl+F)
+    pub show_replace: bool,
+
+    /// Matches in the current text
+    pub matches: Vec<Range<usize>>,
+    /// Index into `matches` of the one F3 last moved to
+    pub current: Option<usize>,
+    /// Why the query isn't a valid regex, if it isn't
+    pub error: Option<String>,
+    /// The edit generation, query and options `matches` was computed for
+    matches_key: Option<(u64, String, SearchOptions)>,
+    /// Index of the text, once it's INDEX_MIN_BYTES or more
+    index: Option<SearchIndex>,
+    /// The index being built in the background
+    indexing: Option<Receiver<SearchIndex>>,
+}
+
+impl FindState {
+    /// Recompute the matches if the text (its edit `generation`), query or
+    /// options changed
+    ///
+    /// Returns true if they were recomputed. The current match is kept if
+    /// a match still starts at the same place.
+    pub fn refresh(&mut self, text: &str, generation: u64) -> bool {
+        if let Some(indexing) = &self.indexing {
+            match indexing.try_recv() {
+                Ok(index) => {
+                    self.index = Some(index);
+                    self.indexing = None;
+                }
+                Err(TryRecvError::Disconnected) => self.indexing = None,
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+
+        let key = (generation, self.query.clone(), self.options);
+        if self.matches_key.as_ref() == Some(&key) {
+            return false;
+        }
+        self.matches_key = Some(key);
+
+        let current_start = self.current_match().map(|range| range.start);
+        self.error = None;
+        self.matches = if self.query.is_empty() {
+            Vec::new()
+        } else {
+            match build_regex(&self.query, self.options) {
+                Ok(regex) => self.search(text, &regex),
+                Err(error) => {
+                    self.error = Some(error);
+                    Vec::new()
+                }
+            }
+        };
+        self.current =
+            current_start.and_then(|start| self.matches.iter().position(|m| m.start == start));
+        true
+    }
+
+    /// The matches of `regex` (the query) in `text`, through the index if
+    /// there's one for it
+    ///
+    /// A long text without one starts it building, and is searched in full
+    /// until it's ready.
+    fn search(&mut self, text: &str, regex: &Regex) -> Vec<Range<usize>> {
+        if text.len() < INDEX_MIN_BYTES {
+            self.index = None;
+            self.indexing = None;
+            return find_matches(text, regex);
+        }
+        if let Some(index) = &mut self.index {
+            if index.update(text) {
+                return index.find_matches(&self.query, self.options, regex);
+            }
+            self.index = None;
+        }
+        if self.indexing.is_none() {
+            let (sender, receiver) = mpsc::channel();
+            let text = text.to_string();
+            thread::spawn(move || {
+                // The find state may be gone by the time it's built
+                let _ = sender.send(SearchIndex::new(&text));
+            });
+            self.indexing = Some(receiver);
+        }
+        find_matches(text, regex)
+    }
+
+    /// The match F3 last moved to, if it's still there
+    pub fn current_match(&self) -> Option<Range<usize>> {
+        self.current.and_then(|i| self.matches.get(i)).cloned()
+    }
+
+    /// Move to the first match starting at or after byte `from`, wrapping
+    /// around to the first match in the document
+    pub fn select_next(&mut self, from: usize) -> Option<Range<usize>> {
+        if self.matches.is_empty() {
+            return None;
+        }
+        let index = self
+            .matches
+            .iter()
+            .position(|m| m.start >= from)
+            .unwrap_or(0);
+        self.current = Some(index);
+        self.current_match()
+    }
+
+    /// Move to the last match starting before byte `before`, wrapping
+    /// around to the last match in the document
+    pub fn select_previous(&mut self, before: usize) -> Option<Range<usize>> {
+        if self.matches.is_empty() {
+            return None;
+        }
+        let index = self
+            .matches
+            .iter()
+            .rposition(|m| m.start < before)
+            .unwrap_or(self.matches.len() - 1);
+        self.current = Some(index);
+        self.current_match()
+    }
+
+    /// "3 of 12", "12 matches", "No matches", or the regex error
+    pub fn summary(&self) -> String {
+        if let Some(error) = &self.error {
+            // The regex crate's syntax errors run over several lines, ending
+            // with "error: <what's wrong>"
+            return match error.lines().find_map(|line| line.strip_prefix("error: ")) {
+                Some(reason) => format!("Invalid regex: {reason}"),
+                None => String::from("Invalid regex"),
+            };
+        }
+        match (self.matches.len(), self.current) {
+            (0, _) => String::from("No matches"),
+            (total, Some(i)) => format!("{} of {total}", i + 1),
+            (1, None) => String::from("1 match"),
+            (total, None) => format!("{total} matches"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(case_sensitive: bool, whole_word: bool, regex: bool) -> SearchOptions {
+        SearchOptions {
+            case_sensitive,
+            whole_word,
+            regex,
+        }
+    }
+
+    /// The matched text of each match
+    fn found<'a>(text: &'a str, query: &str, options: SearchOptions) -> Vec<&'a str> {
+        let regex = build_regex(query, options).unwrap();
+        find_matches(text, &regex)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn plain_queries_are_taken_literally() {
+        let text = "Cost: $5 (approx.) or $5.";
+        assert_eq!(found(text, "$5", SearchOptions::default()), ["$5", "$5"]);
+        assert_eq!(
+            found(text, "(approx.)", SearchOptions::default()),
+            ["(approx.)"]
+        );
+        assert!(found(text, "a.p", SearchOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn case_is_ignored_unless_asked_for() {
+        let text = "Ann met ANN and anne.";
+        assert_eq!(
+            found(text, "ann", options(false, false, false)),
+            ["Ann", "ANN", "ann"]
+        );
+        assert_eq!(found(text, "ann", options(true, false, false)), ["ann"]);
+    }
+
+    #[test]
+    fn whole_word_skips_parts_of_words() {
+        let text = "Ann met ANN and anne. Joanna said ann.";
+        assert_eq!(
+            found(text, "ann", options(false, true, false)),
+            ["Ann", "ANN", "ann"]
+        );
+        // Around a regex alternation, the whole alternation is one word
+        assert_eq!(
+            found(
+                "cat catalog dog dogma",
+                "cat|dog",
+                options(true, true, true)
+            ),
+            ["cat", "dog"]
+        );
+    }
+
+    #[test]
+    fn line_anchors_and_empty_matches() {
+        let text = "One\nTwo\n";
+        assert_eq!(found(text, "^T", options(true, false, true)), ["T"]);
+        assert_eq!(found(text, "o$", options(true, false, true)), ["o"]);
+        // Empty matches are dropped
+        assert!(found(text, "x*", options(true, false, true)).is_empty());
+    }
+
+    #[test]
+    fn regex_replacements_expand_captures() {
+        let text = "Smith, John and Doe, Jane";
+        let opts = options(true, false, true);
+        let regex = build_regex(r"(\w+), (\w+)", opts).unwrap();
+        let matches = find_matches(text, &regex);
+        assert_eq!(
+            replacement_for(text, matches[1].clone(), &regex, "$2 $1", opts),
+            "Jane Doe"
+        );
+        assert_eq!(
+            replace_all(text, &regex, "${2} $1", opts),
+            (String::from("John Smith and Jane Doe"), 2)
+        );
+        // Without regex mode `$1` is typed text
+        let plain = options(true, false, false);
+        let regex = build_regex("Doe", plain).unwrap();
+        assert_eq!(replacement_for(text, 16..19, &regex, "$1", plain), "$1");
+        assert_eq!(
+            replace_all(text, &regex, "$1", plain),
+            (String::from("Smith, John and $1, Jane"), 1)
+        );
+    }
+
+    #[test]
+    fn replace_all_keeps_the_text_between_matches() {
+        let opts = SearchOptions::default();
+        // Longer, then shorter, than what they replace
+        let regex = build_regex("ab", opts).unwrap();
+        assert_eq!(
+            replace_all("ab-ab--ab", &regex, "XYZW", opts),
+            (String::from("XYZW-XYZW--XYZW"), 3)
+        );
+        assert_eq!(
+            replace_all("ab-ab--ab", &regex, "", opts),
+            (String::from("---"), 3)
+        );
+        // Multi-byte text around the matches
+        let regex = build_regex("é", opts).unwrap();
+        assert_eq!(
+            replace_all("café é—É", &regex, "e", opts),
+            (String::from("cafe e—e"), 3)
+        );
+        // Nothing to replace
+        let regex = build_regex("zz", opts).unwrap();
+        assert_eq!(
+            replace_all("text", &regex, "y", opts),
+            (String::from("text"), 0)
+        );
+    }
+
+    #[test]
+    fn an_invalid_regex_is_reported_not_searched() {
+        assert!(build_regex("(unclosed", options(false, false, true)).is_err());
+        // The same text is fine as a plain query
+        assert!(build_regex("(unclosed", SearchOptions::default()).is_ok());
+
+        let mut find = FindState {
+            query: String::from("(unclosed"),
+            options: options(false, false, true),
+            ..FindState::default()
+        };
+        assert!(find.refresh("(unclosed", 1));
+        assert!(find.matches.is_empty());
+        assert!(find.summary().starts_with("Invalid regex"));
+        assert_ne!(find.summary(), "Invalid regex");
+    }
+
+    #[test]
+    fn find_state_moves_between_matches_and_wraps() {
+        let text = "a b a b a";
+        let mut find = FindState {
+            query: String::from("a"),
+            ..FindState::default()
+        };
+        assert!(find.refresh(text, 1));
+        assert!(!find.refresh(text, 1));
+        assert_eq!(find.summary(), "3 matches");
+
+        assert_eq!(find.select_next(1), Some(4..5));
+        assert_eq!(find.summary(), "2 of 3");
+        assert_eq!(find.select_next(9), Some(0..1));
+        assert_eq!(find.select_previous(0), Some(8..9));
+        assert_eq!(find.select_previous(8), Some(4..5));
+
+        // The current match survives an edit that leaves it in place
+        assert!(find.refresh("a b a b", 2));
+        assert_eq!(find.current_match(), Some(4..5));
+        assert_eq!(find.summary(), "2 of 2");
+        find.query.clear();
+        assert!(find.refresh("a b a b", 2));
+        assert_eq!(find.summary(), "No matches");
+    }
+}
@@ -0,0 +1,391 @@
+//! FILE: src/search_index.rs
+//!
+//! A trigram index of a text's lines, so finding plain text in a
+//! manuscript of several hundred thousand words runs the regex over the
+//! few lines that could match instead of over the whole text on every
+//! keystroke.
+//!
+//! TRIGRAMS:
+//! Every run of three characters in a line is a trigram: "the cat" has
+//! "the", "he ", "e c", " ca" and "cat". The index lists, for each
+//! trigram, the lines that have it. A line can only contain the query if
+//! it has every one of the query's trigrams, so only the lines on all of
+//! their lists are searched - and the matches come out the same as a
+//! search of everything.
+//!
+//! ASCII letters are indexed lowercased, so one index serves searches with
+//! and without Match case. So are the Kelvin sign and the long s (ſ), the
+//! two other characters a case-insensitive regex matches to ASCII letters.
+//!
+//! WHAT IT CAN'T NARROW DOWN:
+//! Regex queries, queries shorter than three characters, and queries with
+//! non-ASCII letters when Match case is off search the whole text, as if
+//! there were no index.
+//!
+//! EDITS:
+//! update() compares the new text with the one indexed. The lines before
+//! the first difference and after the last one are kept, and only the
+//! lines in between are indexed again, so typing costs one line. Lines are
+//! known by ids that never change, so adding or removing a line doesn't
+//! renumber the lists; a removed line's id stays in them and is skipped,
+//! until there are more of those than lines and they're cleared out.
+//!
+//! FindState builds the document's index on a background thread, once the
+//! document is INDEX_MIN_BYTES or more; Find in Project's worker keeps one
+//! for every file of the project.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Three chars packed into one u64, for a cheap HashMap key
+//! - Intersecting sorted Vecs with binary_search
+
+use crate::search::{self, SearchOptions};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// Texts smaller than this (in bytes) aren't indexed: the regex gets
+/// through them before an index would be built
+pub const INDEX_MIN_BYTES: usize = 100_000;
+
+/// Three characters, 21 bits each
+type Trigram = u64;
+
+/// A line of the indexed text
+#[derive(Debug, Clone, Copy)]
+struct Line {
+    id: u32,
+    /// Length in bytes, without the '\n'
+    len: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    /// The text as indexed, to compare the next version with
+    text: String,
+    lines: Vec<Line>,
+    /// Trigram → ids of the lines that have it, in ascending order (a new
+    /// line always gets a higher id than any before it)
+    postings: HashMap<Trigram, Vec<u32>>,
+    /// Ids of lines no longer in the text but still in `postings`
+    removed: HashSet<u32>,
+    next_id: u32,
+}
+
+impl SearchIndex {
+    pub fn new(text: &str) -> Self {
+        let mut index = Self {
+            text: text.to_string(),
+            ..Self::default()
+        };
+        let lines = text.split('\n').map(|line| index.add_line(line)).collect();
+        index.lines = lines;
+        index
+    }
+
+    /// The text as indexed
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Put a line's trigrams in the index under a new id
+    fn add_line(&mut self, line: &str) -> Line {
+        let id = self.next_id;
+        self.next_id += 1;
+        for trigram in trigrams(line) {
+            self.postings.entry(trigram).or_default().push(id);
+        }
+        Line {
+            id,
+            len: line.len(),
+        }
+    }
+
+    /// Bring the index up to date with `text`, indexing again only the
+    /// lines that changed (see module docs)
+    ///
+    /// Returns false, leaving the index as it was, if the changed lines are
+    /// more than half of `text` - another document, most likely, which is
+    /// better indexed afresh in the background.
+    pub fn update(&mut self, text: &str) -> bool {
+        if text == self.text {
+            return true;
+        }
+        let old = self.text.as_bytes();
+        let new = text.as_bytes();
+        let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        let room = old.len().min(new.len()) - prefix;
+        let suffix = old
+            .iter()
+            .rev()
+            .zip(new.iter().rev())
+            .take(room)
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        // The old lines the changed bytes are in: `first..=last`, from byte
+        // `start` to `old_end`
+        let mut first = 0;
+        let mut start = 0;
+        while start + self.lines[first].len < prefix {
+            start += self.lines[first].len + 1;
+            first += 1;
+        }
+        let mut last = first;
+        let mut old_end = start + self.lines[first].len;
+        while old_end < old.len() - suffix {
+            last += 1;
+            old_end += 1 + self.lines[last].len;
+        }
+        // Everything after `old_end` is the same in the new text
+        let new_end = new.len() - (old.len() - old_end);
+        if (new_end - start) * 2 > new.len() {
+            return false;
+        }
+
+        for line in self.lines.drain(first..=last) {
+            self.removed.insert(line.id);
+        }
+        let added: Vec<Line> = text[start..new_end]
+            .split('\n')
+            .map(|line| self.add_line(line))
+            .collect();
+        self.lines.splice(first..first, added);
+        self.text
+            .replace_range(start..old_end, &text[start..new_end]);
+
+        if self.removed.len() > self.lines.len() {
+            let removed = std::mem::take(&mut self.removed);
+            self.postings.retain(|_, ids| {
+                ids.retain(|id| !removed.contains(id));
+                !ids.is_empty()
+            });
+        }
+        true
+    }
+
+    /// The lines (0-based, in order) that could hold a match of `query`
+    ///
+    /// None if the index can't narrow it down (see module docs) and every
+    /// line has to be searched.
+    pub fn candidate_lines(&self, query: &str, options: SearchOptions) -> Option<Vec<usize>> {
+        if options.regex || query.contains('\n') || (!options.case_sensitive && !query.is_ascii()) {
+            return None;
+        }
+        let trigrams = trigrams(query);
+        if trigrams.is_empty() {
+            return None;
+        }
+        let mut lists = Vec::new();
+        for trigram in &trigrams {
+            match self.postings.get(trigram) {
+                Some(ids) => lists.push(ids),
+                None => return Some(Vec::new()),
+            }
+        }
+        // Check the shortest list's lines against the others
+        lists.sort_by_key(|ids| ids.len());
+        let ids: HashSet<u32> = lists[0]
+            .iter()
+            .copied()
+            .filter(|id| {
+                !self.removed.contains(id) && lists[1..].iter().all(|l| l.binary_search(id).is_ok())
+            })
+            .collect();
+        Some(
+            self.lines
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| ids.contains(&line.id))
+                .map(|(index, _)| index)
+                .collect(),
+        )
+    }
+
+    /// The matches of `regex` in the indexed text, as search::find_matches
+    /// would find them; `regex` is `query` and `options` built
+    pub fn find_matches(
+        &self,
+        query: &str,
+        options: SearchOptions,
+        regex: &Regex,
+    ) -> Vec<Range<usize>> {
+        let Some(candidates) = self.candidate_lines(query, options) else {
+            return search::find_matches(&self.text, regex);
+        };
+        let mut matches = Vec::new();
+        let mut candidates = candidates.into_iter().peekable();
+        let mut start = 0;
+        for (index, line) in self.lines.iter().enumerate() {
+            if candidates.peek().is_none() {
+                break;
+            }
+            if candidates.next_if_eq(&index).is_some() {
+                let text = &self.text[start..start + line.len];
+                matches.extend(
+                    search::find_matches(text, regex)
+                        .into_iter()
+                        .map(|m| start + m.start..start + m.end),
+                );
+            }
+            start += line.len + 1;
+        }
+        matches
+    }
+}
+
+/// A character as indexed (see module docs)
+fn fold(c: char) -> char {
+    match c {
+        '\u{212A}' => 'k',
+        'ſ' => 's',
+        _ => c.to_ascii_lowercase(),
+    }
+}
+
+/// The distinct trigrams of `text`, sorted
+fn trigrams(text: &str) -> Vec<Trigram> {
+    let chars: Vec<u64> = text.chars().map(|c| fold(c) as u64).collect();
+    let mut trigrams: Vec<Trigram> = chars
+        .windows(3)
+        .map(|w| (w[0] << 42) | (w[1] << 21) | w[2])
+        .collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "The cat sat on the mat.\n\
+                        A \u{212A}elvin thermometer read low.\n\
+                        Her ſister kept the cat.\n\
+                        \n\
+                        Nothing else here.\n\
+                        The end of the cat story";
+
+    /// Queries covering narrowed searches (both cases), ones the index
+    /// can't narrow, and the two characters folded to ASCII
+    const QUERIES: [(&str, bool, bool); 8] = [
+        ("cat", true, false),
+        ("CAT", false, false),
+        ("the", false, false),
+        ("kelvin", false, false),
+        ("sister", false, false),
+        ("sister", true, false),
+        ("ca", false, false),
+        ("c.t", true, true),
+    ];
+
+    /// The index must find exactly what a search of the whole text does
+    fn assert_same_matches(index: &SearchIndex, text: &str) {
+        assert_eq!(index.text(), text);
+        let lines: Vec<usize> = index.lines.iter().map(|line| line.len).collect();
+        let expected: Vec<usize> = text.split('\n').map(str::len).collect();
+        assert_eq!(lines, expected);
+        for (query, case_sensitive, regex) in QUERIES {
+            let options = SearchOptions {
+                case_sensitive,
+                whole_word: false,
+                regex,
+            };
+            let built = search::build_regex(query, options).unwrap();
+            assert_eq!(
+                index.find_matches(query, options, &built),
+                search::find_matches(text, &built),
+                "{:?} in {:?}",
+                query,
+                text
+            );
+        }
+    }
+
+    /// Index BASE, update it to `edited`, and check the result
+    fn check_edit(edited: &str) {
+        let mut index = SearchIndex::new(BASE);
+        assert!(index.update(edited), "{:?}", edited);
+        assert_same_matches(&index, edited);
+        // And back again
+        assert!(index.update(BASE));
+        assert_same_matches(&index, BASE);
+    }
+
+    #[test]
+    fn a_fresh_index_matches_a_full_search() {
+        assert_same_matches(&SearchIndex::new(BASE), BASE);
+        assert_same_matches(&SearchIndex::new(""), "");
+    }
+
+    #[test]
+    fn folded_characters_are_found_without_match_case() {
+        let index = SearchIndex::new(BASE);
+        let options = SearchOptions::default();
+        assert_eq!(index.candidate_lines("KELVIN", options), Some(vec![1]));
+        assert_eq!(index.candidate_lines("Sister", options), Some(vec![2]));
+        assert_eq!(index.candidate_lines("dog", options), Some(vec![]));
+        // Nothing to narrow with
+        assert_eq!(index.candidate_lines("ca", options), None);
+        assert_eq!(index.candidate_lines("señor", options), None);
+    }
+
+    #[test]
+    fn edits_within_a_line() {
+        // Start, middle and end of a line
+        check_edit(&BASE.replacen("A \u{212A}", "One \u{212A}", 1));
+        check_edit(&BASE.replacen("thermometer", "thermo cat meter", 1));
+        check_edit(&BASE.replacen("read low.", "read low, the cat said.", 1));
+        // Deleting inside a line, and at the very start of the text
+        check_edit(&BASE.replacen("kept the ", "", 1));
+        check_edit(&BASE[4..]);
+        // The last line, which has no '\n' after it
+        check_edit(&format!("{} and the mat", BASE));
+        check_edit(&BASE.replacen("cat story", "dog", 1));
+    }
+
+    #[test]
+    fn edits_that_add_or_remove_lines() {
+        check_edit(&BASE.replacen("sat on", "sat\non", 1));
+        check_edit(&BASE.replacen("mat.\n", "mat. ", 1));
+        check_edit(&BASE.replacen("\n\n", "\n", 1));
+        check_edit(&BASE.replacen("\n\n", "\ncat\n\ncat\n", 1));
+        check_edit(&format!("{}\n", BASE));
+        check_edit(&format!("{}\n\nThe cat came back.", BASE));
+        check_edit(&format!("The cat.\n{}", BASE));
+        // A multi-byte character split across the edit
+        check_edit(&BASE.replacen("ſ", "s", 1));
+    }
+
+    #[test]
+    fn a_mostly_new_text_is_left_to_a_fresh_index() {
+        let mut index = SearchIndex::new(BASE);
+        assert!(!index.update("Another document entirely, with a cat."));
+        assert_same_matches(&index, BASE);
+        assert!(!index.update(&format!("{}\n{}", "x".repeat(200), BASE)));
+        assert_same_matches(&index, BASE);
+        // An unchanged text is already up to date
+        assert!(index.update(BASE));
+        // Clearing everything leaves nothing to index, so it's kept
+        assert!(index.update(""));
+        assert_same_matches(&index, "");
+        assert!(index.postings.is_empty());
+    }
+
+    #[test]
+    fn removed_lines_are_cleared_out_eventually() {
+        let mut index = SearchIndex::new(BASE);
+        let mut text = String::from(BASE);
+        for n in 0..20 {
+            text = text.replacen("Nothing", &format!("Nothing {}", n), 1);
+            assert!(index.update(&text));
+            assert!(index.removed.len() <= index.lines.len());
+        }
+        assert_same_matches(&index, &text);
+        // Every id left in the lists belongs to a line still there
+        let live: HashSet<u32> = index.lines.iter().map(|line| line.id).collect();
+        let listed: HashSet<u32> = index.postings.values().flatten().copied().collect();
+        assert!(listed
+            .iter()
+            .all(|id| live.contains(id) || index.removed.contains(id)));
+    }
+}
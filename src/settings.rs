@@ -0,0 +1,353 @@
+//! FILE: src/settings.rs
+//!
+//! Application-wide user preferences.
+//!
+//! Everything the user can configure lives in one `Settings` struct so it
+//! can be serialized as a unit - to `settings.json` in the data directory
+//! (see storage::load_settings / save_settings) and into portable profiles.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Deriving serde's Serialize/Deserialize on nested structs
+//! - `#[serde(default)]` for forward/backward compatible formats
+
+use crate::autocorrect::AutocorrectOptions;
+use crate::docx::DocxOptions;
+use crate::editing::StructureEditing;
+use crate::fonts;
+use crate::goals::WritingGoal;
+use crate::pdf::PdfOptions;
+use crate::read_aloud::ReadAloudOptions;
+use crate::storage::{AutosaveSettings, BackupSettings, SnapshotSettings};
+use crate::style::StyleOptions;
+use crate::text_utils::CleanupOptions;
+use crate::theme;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// All user preferences
+///
+/// `#[serde(default)]` fills any field missing from the input with its
+/// Default value, so older files with fewer fields still load.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Smart Enter / bracket pairing / colon spacing toggles
+    pub structure_editing: StructureEditing,
+    /// Autosave on/off and interval
+    pub autosave: AutosaveSettings,
+    /// When version-history snapshots are taken and how many are kept
+    pub snapshots: SnapshotSettings,
+    /// Whether saving a document backs up the version it replaces, and
+    /// which backups are kept
+    pub backups: BackupSettings,
+    /// Status-bar word count options
+    pub word_count: WordCountSettings,
+    /// Session or daily word goal (Tools → Writing Goal)
+    pub goal: WritingGoal,
+    /// Editor text size and wrapping
+    pub editor: EditorSettings,
+    /// Column width and typewriter scrolling for focus mode
+    pub focus: FocusSettings,
+    /// Edit → Clean Up Document fixes, and whether pastes get them too
+    pub cleanup: CleanupOptions,
+    /// Tools → Style Check thresholds and word lists
+    pub style: StyleOptions,
+    /// Autocorrect on/off and its replacement table
+    pub autocorrect: AutocorrectOptions,
+    /// The WordNet folder for Define and Synonyms; None looks in the data
+    /// directory (storage::get_wordnet_dir)
+    pub wordnet_dir: Option<PathBuf>,
+    /// Tools → Read Aloud speed
+    pub read_aloud: ReadAloudOptions,
+    /// File → Export → PDF title page
+    pub pdf: PdfOptions,
+    /// File → Export → Word (DOCX) cover page and header
+    pub docx: DocxOptions,
+    /// Keep `[[...]]` comments in exported and compiled files (by default
+    /// they're left out)
+    pub export_comments: bool,
+    /// Dark, light, sepia or the user's own theme file
+    pub theme: Theme,
+    /// Reopen the last documents and project (each document at the same
+    /// cursor and scroll position) on launch; off starts every launch with
+    /// a blank document
+    pub restore_session: bool,
+    /// Where the window was and how big it was when the app last closed
+    /// Machine-specific, so it is left out of exported profiles.
+    pub window: WindowGeometry,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            structure_editing: StructureEditing::default(),
+            autosave: AutosaveSettings::default(),
+            snapshots: SnapshotSettings::default(),
+            backups: BackupSettings::default(),
+            word_count: WordCountSettings::default(),
+            goal: WritingGoal::default(),
+            editor: EditorSettings::default(),
+            focus: FocusSettings::default(),
+            cleanup: CleanupOptions::default(),
+            style: StyleOptions::default(),
+            autocorrect: AutocorrectOptions::default(),
+            wordnet_dir: None,
+            read_aloud: ReadAloudOptions::default(),
+            pdf: PdfOptions::default(),
+            docx: DocxOptions::default(),
+            export_comments: false,
+            theme: Theme::default(),
+            restore_session: true,
+            window: WindowGeometry::default(),
+        }
+    }
+}
+
+/// Options for the word/character/page counts in the status bar
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WordCountSettings {
+    /// Words per page for the page estimate (250 is the usual manuscript rule)
+    pub words_per_page: usize,
+    /// Leave `[CHAPTER: ...]`-style tag lines out of the counts
+    pub exclude_tags: bool,
+}
+
+impl Default for WordCountSettings {
+    fn default() -> Self {
+        Self {
+            words_per_page: 250,
+            exclude_tags: false,
+        }
+    }
+}
+
+/// Smallest and largest editor font sizes (points)
+pub const MIN_FONT_SIZE: f32 = 8.0;
+pub const MAX_FONT_SIZE: f32 = 48.0;
+
+/// Editor font size for new installs and View → Reset zoom (Ctrl+0)
+pub const DEFAULT_FONT_SIZE: f32 = 14.0;
+
+/// How much one Ctrl+= / Ctrl+- changes the editor font size (points)
+pub const FONT_SIZE_STEP: f32 = 1.0;
+
+/// Tightest and loosest editor line spacing, as a multiple of the font's
+/// own line height
+pub const MIN_LINE_SPACING: f32 = 1.0;
+pub const MAX_LINE_SPACING: f32 = 3.0;
+
+/// How the editor lays out text
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorSettings {
+    /// Editor font size in points (menus and panels keep egui's sizes)
+    pub font_size: f32,
+    /// Typeface family for the editor text
+    pub font: EditorFont,
+    /// The font file used by EditorFont::Custom (.ttf or .otf)
+    pub custom_font: Option<PathBuf>,
+    /// Line height as a multiple of the font's own (1.0 = as designed)
+    pub line_spacing: f32,
+    /// Wrap long lines at the window edge; off scrolls horizontally instead
+    pub word_wrap: bool,
+}
+
+impl Default for EditorSettings {
+    fn default() -> Self {
+        Self {
+            font_size: DEFAULT_FONT_SIZE,
+            font: EditorFont::default(),
+            custom_font: None,
+            line_spacing: MIN_LINE_SPACING,
+            word_wrap: true,
+        }
+    }
+}
+
+impl EditorSettings {
+    /// The font the editor should draw with
+    ///
+    /// `custom_ready` says whether egui has the custom font yet (see
+    /// fonts.rs); until it does, Custom draws in monospace.
+    pub fn font_id(&self, custom_ready: bool) -> egui::FontId {
+        let size = self.font_size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+        match self.font {
+            EditorFont::Custom if custom_ready => egui::FontId::new(size, fonts::custom_family()),
+            EditorFont::Monospace | EditorFont::Custom => egui::FontId::monospace(size),
+            EditorFont::Proportional => egui::FontId::proportional(size),
+        }
+    }
+
+    /// The height of one editor line for `row_height`, the font's own, or
+    /// None to leave it as designed
+    pub fn line_height(&self, row_height: f32) -> Option<f32> {
+        let spacing = self.line_spacing.clamp(MIN_LINE_SPACING, MAX_LINE_SPACING);
+        (spacing > MIN_LINE_SPACING).then_some(row_height * spacing)
+    }
+}
+
+/// Editor typeface family
+///
+/// Monospace keeps tags and cues lined up; proportional reads more like a
+/// printed page for long stretches of prose. Custom is a font file of the
+/// user's choosing (EditorSettings::custom_font).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EditorFont {
+    #[default]
+    Monospace,
+    Proportional,
+    Custom,
+}
+
+/// Narrowest and widest text column offered for focus mode (points)
+pub const MIN_FOCUS_COLUMN_WIDTH: f32 = 300.0;
+pub const MAX_FOCUS_COLUMN_WIDTH: f32 = 1600.0;
+
+/// Focus mode (View → Focus mode, F11) layout
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FocusSettings {
+    /// Width of the centered text column (points)
+    pub column_width: f32,
+    /// Keep the line being typed in the vertical middle of the window
+    pub typewriter_scrolling: bool,
+    /// Fill the screen while in focus mode
+    pub full_screen: bool,
+    /// Fade the text outside the paragraph the caret is in
+    pub dim_paragraphs: bool,
+}
+
+impl Default for FocusSettings {
+    fn default() -> Self {
+        Self {
+            column_width: 700.0,
+            typewriter_scrolling: false,
+            full_screen: true,
+            dim_paragraphs: true,
+        }
+    }
+}
+
+/// Color scheme
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+    /// Dark brown on warm paper (theme::sepia)
+    Sepia,
+    /// The user's own theme file (theme::load_custom)
+    Custom,
+}
+
+impl Theme {
+    /// Every theme, in the order menus list them
+    pub const ALL: [Theme; 4] = [Theme::Dark, Theme::Light, Theme::Sepia, Theme::Custom];
+
+    /// Name for menus
+    pub fn label(&self) -> &'static str {
+        match self {
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+            Theme::Sepia => "Sepia",
+            Theme::Custom => "Custom (theme file)",
+        }
+    }
+
+    /// The colors for this theme
+    ///
+    /// Custom comes from a file, which App reads itself so it can report
+    /// problems with it; here it is just dark.
+    pub fn visuals(&self) -> egui::Visuals {
+        match self {
+            Theme::Dark | Theme::Custom => egui::Visuals::dark(),
+            Theme::Light => egui::Visuals::light(),
+            Theme::Sepia => theme::sepia(),
+        }
+    }
+}
+
+/// Last known window size and position, in logical points
+///
+/// None means "never recorded" - the window opens at its built-in default.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowGeometry {
+    /// Inner size [width, height]
+    pub size: Option<[f32; 2]>,
+    /// Outer top-left corner [x, y]
+    pub position: Option<[f32; 2]>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_round_trip_through_json() {
+        let mut settings = Settings::default();
+        settings.editor.font_size = 18.0;
+        settings.editor.word_wrap = false;
+        settings.theme = Theme::Sepia;
+        settings.backups.count = 5;
+        settings.restore_session = false;
+        settings.window = WindowGeometry {
+            size: Some([1200.0, 800.0]),
+            position: Some([10.0, 20.0]),
+        };
+        settings.wordnet_dir = Some(PathBuf::from("/opt/wordnet"));
+
+        let json = serde_json::to_string_pretty(&settings).unwrap();
+        let back: Settings = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, settings);
+    }
+
+    #[test]
+    fn unknown_fields_from_a_newer_version_are_ignored() {
+        let json = r#"{
+            "theme": "Light",
+            "spell_check": { "language": "en_GB" },
+            "editor": { "font_size": 20.0, "ligatures": true }
+        }"#;
+        let settings: Settings = serde_json::from_str(json).unwrap();
+        assert_eq!(settings.theme, Theme::Light);
+        assert_eq!(settings.editor.font_size, 20.0);
+    }
+
+    #[test]
+    fn missing_fields_get_their_defaults() {
+        let json = r#"{ "backups": { "enabled": false } }"#;
+        let settings: Settings = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            settings,
+            Settings {
+                backups: BackupSettings {
+                    enabled: false,
+                    ..BackupSettings::default()
+                },
+                ..Settings::default()
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<Settings>("{}").unwrap(),
+            Settings::default()
+        );
+    }
+
+    #[test]
+    fn out_of_range_sizes_are_clamped_when_used() {
+        let editor = EditorSettings {
+            font_size: 500.0,
+            line_spacing: 0.2,
+            ..EditorSettings::default()
+        };
+        assert_eq!(editor.font_id(false).size, MAX_FONT_SIZE);
+        assert_eq!(editor.line_height(20.0), None);
+        let spaced = EditorSettings {
+            line_spacing: 1.5,
+            ..EditorSettings::default()
+        };
+        assert_eq!(spaced.line_height(20.0), Some(30.0));
+    }
+}
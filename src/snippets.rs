@@ -0,0 +1,280 @@
+//! FILE: src/snippets.rs
+//!
+//! Snippets: tag boilerplate put in with the Insert menu, or by typing a
+//! snippet's trigger and pressing Tab (`ch` then Tab gives `[CHAPTER: ]`
+//! with the caret inside the brackets).
+//!
+//! SNIPPETS FILE:
+//! They're read from `<data dir>/snippets.toml`, one `[[snippet]]` table
+//! each. `$0` marks where the caret goes (the end, without one):
+//!
+//! ```toml
+//! [[snippet]]
+//! name = "Chapter"
+//! trigger = "ch"
+//! body = "[CHAPTER: $0]"
+//! ```
+//!
+//! The first time it's needed, the file is written from EXAMPLE_SNIPPETS,
+//! so the defaults are there to edit. Insert → Reload Snippets reads it
+//! again; a file that can't be read is reported and the snippets already
+//! loaded stay.
+//!
+//! A trigger is the word (everything since the last space or line start)
+//! just before the caret, matched exactly, case included.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Deserializing TOML with serde, the same derive as for JSON
+//! - Returning an editing::EditCommand so the caller applies it like any
+//!   other structure-aware edit
+
+use crate::editing::{self, EditCommand};
+use crate::storage;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Where the caret goes in a snippet's body
+pub const CARET_MARKER: &str = "$0";
+
+/// One piece of boilerplate
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Snippet {
+    /// What the Insert menu lists it as
+    pub name: String,
+    /// Typed before Tab to put it in; none means menu only
+    #[serde(default)]
+    pub trigger: String,
+    /// The text, with CARET_MARKER where the caret goes
+    pub body: String,
+}
+
+/// The snippets file's layout
+#[derive(Deserialize)]
+struct SnippetFile {
+    #[serde(default, rename = "snippet")]
+    snippets: Vec<Snippet>,
+}
+
+/// What the snippets file starts out as (see module docs)
+const EXAMPLE_SNIPPETS: &str = r#"# BookScript snippets: Insert menu entries, and triggers to type before Tab.
+# $0 marks where the caret goes. Insert → Reload Snippets picks up changes.
+
+[[snippet]]
+name = "Act"
+trigger = "act"
+body = "[ACT: $0]"
+
+[[snippet]]
+name = "Chapter"
+trigger = "ch"
+body = "[CHAPTER: $0]"
+
+[[snippet]]
+name = "Scene"
+trigger = "sc"
+body = "[SCENE: $0]"
+
+[[snippet]]
+name = "Scene with status and POV"
+trigger = "scm"
+body = "[SCENE: $0 | status=draft | pov=]"
+
+[[snippet]]
+name = "Character"
+trigger = "char"
+body = "[CHARACTER: $0]"
+
+[[snippet]]
+name = "Note"
+trigger = "note"
+body = "[[note: $0]]"
+
+[[snippet]]
+name = "Chapter with first scene"
+trigger = "chs"
+body = """
+[CHAPTER: $0]
+[SCENE: ]
+"""
+"#;
+
+/// The snippets the example file has, for when the real one can't be read
+pub fn defaults() -> Vec<Snippet> {
+    parse(EXAMPLE_SNIPPETS).expect("the example snippets are valid")
+}
+
+/// Read the snippets file, writing it from EXAMPLE_SNIPPETS first if
+/// there isn't one
+pub fn load(path: &Path) -> Result<Vec<Snippet>> {
+    if !path.exists() {
+        storage::save_text_file(path, EXAMPLE_SNIPPETS, storage::BackupSettings::OFF)?;
+    }
+    let text = storage::load_text_file(path)?;
+    parse(&text).context(format!("Failed to read snippets: {}", path.display()))
+}
+
+/// Parse a snippets file
+///
+/// Err if it isn't valid TOML, or a snippet has no name, a trigger with a
+/// space in it, or the same trigger as another.
+pub fn parse(text: &str) -> Result<Vec<Snippet>> {
+    let file: SnippetFile = toml::from_str(text)?;
+    for (index, snippet) in file.snippets.iter().enumerate() {
+        if snippet.name.trim().is_empty() {
+            bail!("Snippet {} has no name", index + 1);
+        }
+        if snippet.trigger.chars().any(char::is_whitespace) {
+            bail!(
+                "Snippet \"{}\": a trigger can't contain spaces",
+                snippet.name
+            );
+        }
+        let repeated = file.snippets[..index]
+            .iter()
+            .any(|other| !snippet.trigger.is_empty() && other.trigger == snippet.trigger);
+        if repeated {
+            bail!("Trigger \"{}\" is used more than once", snippet.trigger);
+        }
+    }
+    Ok(file.snippets)
+}
+
+/// The edit that puts `snippet` in place of `range` (char indices)
+pub fn insert(snippet: &Snippet, range: std::ops::Range<usize>) -> EditCommand {
+    let (insert, caret) = match snippet.body.split_once(CARET_MARKER) {
+        Some((before, after)) => (format!("{}{}", before, after), before.chars().count()),
+        None => (snippet.body.clone(), snippet.body.chars().count()),
+    };
+    EditCommand {
+        caret: range.start + caret,
+        range,
+        insert,
+    }
+}
+
+/// The edit Tab makes with the caret at char index `caret`: the trigger
+/// just before it swapped for its snippet
+///
+/// None if the word before the caret isn't a trigger, and Tab does what
+/// it always does.
+pub fn expand(text: &str, caret: usize, snippets: &[Snippet]) -> Option<EditCommand> {
+    let end = editing::char_to_byte(text, caret);
+    let before = &text[..end];
+    let start = before.rfind(char::is_whitespace).map_or(0, |i| {
+        i + before[i..].chars().next().map_or(1, char::len_utf8)
+    });
+    let word = &before[start..];
+    if word.is_empty() {
+        return None;
+    }
+    let snippet = snippets.iter().find(|s| s.trigger == word)?;
+    Some(insert(snippet, caret - word.chars().count()..caret))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Press Tab at the end of `text`; the text afterwards with `|` at the
+    /// caret, or None if nothing expanded
+    fn tab(text: &str, snippets: &[Snippet]) -> Option<String> {
+        let mut text = text.to_string();
+        let command = expand(&text, text.chars().count(), snippets)?;
+        command.apply(&mut text);
+        text.insert(editing::char_to_byte(&text, command.caret), '|');
+        Some(text)
+    }
+
+    #[test]
+    fn a_trigger_expands_with_the_caret_at_the_marker() {
+        let snippets = defaults();
+        assert_eq!(tab("ch", &snippets).as_deref(), Some("[CHAPTER: |]"));
+        assert_eq!(
+            tab("Intro\nscm", &snippets).as_deref(),
+            Some("Intro\n[SCENE: | | status=draft | pov=]")
+        );
+        assert_eq!(
+            tab("chs", &snippets).as_deref(),
+            Some("[CHAPTER: |]\n[SCENE: ]\n")
+        );
+        // The word runs back to a space, so punctuation before it counts
+        assert_eq!(tab("Day one: “ch", &snippets), None);
+        assert_eq!(
+            tab("Day one: ch", &snippets).as_deref(),
+            Some("Day one: [CHAPTER: |]")
+        );
+    }
+
+    #[test]
+    fn only_an_exact_trigger_expands() {
+        let snippets = defaults();
+        assert_eq!(tab("CH", &snippets), None);
+        assert_eq!(tab("c", &snippets), None);
+        assert_eq!(tab("chapter", &snippets), None);
+        assert_eq!(tab("ch ", &snippets), None);
+        assert_eq!(tab("", &snippets), None);
+    }
+
+    #[test]
+    fn without_a_marker_the_caret_goes_to_the_end() {
+        let snippet = Snippet {
+            name: String::from("Break"),
+            trigger: String::from("br"),
+            body: String::from("* * *\n"),
+        };
+        assert_eq!(tab("x br", &[snippet]).as_deref(), Some("x * * *\n|"));
+    }
+
+    #[test]
+    fn the_example_file_is_valid() {
+        let snippets = defaults();
+        assert_eq!(snippets.len(), 7);
+        assert!(snippets.iter().all(|s| s.body.contains(CARET_MARKER)));
+    }
+
+    #[test]
+    fn bad_snippets_are_refused() {
+        let error = |text: &str| parse(text).unwrap_err().to_string();
+        assert_eq!(
+            error("[[snippet]]\nname = \" \"\nbody = \"x\"\n"),
+            "Snippet 1 has no name"
+        );
+        assert_eq!(
+            error("[[snippet]]\nname = \"A\"\ntrigger = \"a b\"\nbody = \"x\"\n"),
+            "Snippet \"A\": a trigger can't contain spaces"
+        );
+        assert_eq!(
+            error(
+                "[[snippet]]\nname = \"A\"\ntrigger = \"a\"\nbody = \"x\"\n\
+                 [[snippet]]\nname = \"B\"\ntrigger = \"a\"\nbody = \"y\"\n"
+            ),
+            "Trigger \"a\" is used more than once"
+        );
+        assert!(parse("[[snippet]]\nname = \"A\"\n").is_err());
+        // Menu-only snippets may share the empty trigger
+        let snippets = parse(
+            "[[snippet]]\nname = \"A\"\nbody = \"x\"\n[[snippet]]\nname = \"B\"\nbody = \"y\"\n",
+        )
+        .unwrap();
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(parse("").unwrap(), []);
+    }
+
+    #[test]
+    fn loading_writes_the_example_file_first() {
+        let dir =
+            std::env::temp_dir().join(format!("bookscript-test-{}-snippets", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("snippets.toml");
+        assert_eq!(load(&path).unwrap(), defaults());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), EXAMPLE_SNIPPETS);
+
+        std::fs::write(&path, "[[snippet]]\nname = \"Mine\"\nbody = \"$0!\"\n").unwrap();
+        assert_eq!(load(&path).unwrap()[0].name, "Mine");
+        std::fs::write(&path, "[[snippet]\n").unwrap();
+        assert!(load(&path).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,464 @@
+//! FILE: src/statistics.rs
+//!
+//! The Tools → Statistics window: how the manuscript's words are spread
+//! over its chapters and scenes, how much of it is dialogue, and how easy
+//! it is to read (readability.rs), overall and chapter by chapter.
+//!
+//! App builds the report (report()) from two things it already keeps up to
+//! date: the structure with its word counts (annotate_structure, so the
+//! exclude-tags option applies here as in the status bar), and the
+//! formatted preview's blocks, which already tell dialogue from action.
+//! show() draws it, with the words per chapter or scene as a bar chart;
+//! clicking a bar jumps to that chapter or scene. The readability counts
+//! are kept in a readability::Cache between rebuilds, so only the
+//! paragraphs and speeches an edit touched are counted again.
+//!
+//! WHAT COUNTS AS WHAT:
+//! - Dialogue: the words of speeches under a character cue
+//! - Action: everything else that's read - prose, stage directions, and
+//!   parentheticals like "(beat)" inside speeches. Headings and cues are
+//!   neither
+//! - Readability is measured over both (see readability::Counts::of for
+//!   what ends a sentence)
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Drawing a chart with egui's Painter instead of widgets
+//! - slice::partition_point to find the section a line falls in
+
+use crate::formatted::{Block, BlockKind};
+use crate::parser::{self, DocumentStructure};
+use crate::readability::{self, Counts};
+
+/// Height of one bar of the chart, the gap between bars included
+const BAR_HEIGHT: f32 = 20.0;
+
+/// Width kept for a bar's label, left of the bars
+const LABEL_WIDTH: f32 = 160.0;
+
+/// Which breakdown the chart shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Breakdown {
+    #[default]
+    Chapters,
+    Scenes,
+}
+
+/// A chapter or scene and its length
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub title: String,
+    /// 1-based line of its tag
+    pub line: usize,
+    /// 1-based last line, inclusive
+    pub line_end: usize,
+    pub words: usize,
+    /// Readability counts of its dialogue and action
+    pub reading: Counts,
+}
+
+/// Everything the Statistics window shows
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    /// Words in the whole document, as the status bar counts them
+    pub words: usize,
+    pub chapters: Vec<Section>,
+    pub scenes: Vec<Section>,
+    /// Words of dialogue (see module docs)
+    pub dialogue_words: usize,
+    /// Words of prose and stage directions (see module docs)
+    pub action_words: usize,
+    /// Readability counts of the dialogue and action
+    pub reading: Counts,
+}
+
+impl Report {
+    /// The share of dialogue and action words that are dialogue (0.0 to
+    /// 1.0); None if there are neither
+    pub fn dialogue_share(&self) -> Option<f32> {
+        let total = self.dialogue_words + self.action_words;
+        (total > 0).then(|| self.dialogue_words as f32 / total as f32)
+    }
+
+    /// The scene with the most words (the first, if several tie)
+    pub fn longest_scene(&self) -> Option<&Section> {
+        self.scenes.iter().reduce(|longest, scene| {
+            if scene.words > longest.words {
+                scene
+            } else {
+                longest
+            }
+        })
+    }
+
+    fn sections(&self, breakdown: Breakdown) -> &[Section] {
+        match breakdown {
+            Breakdown::Chapters => &self.chapters,
+            Breakdown::Scenes => &self.scenes,
+        }
+    }
+}
+
+/// Build the report from the annotated structure and the document's
+/// formatted blocks, counting for readability only blocks `cache` hasn't
+/// seen
+pub fn report(
+    structure: &DocumentStructure,
+    blocks: &[Block],
+    cache: &mut readability::Cache,
+) -> Report {
+    let section = |title: &str, line: usize, line_end: usize, words: usize| Section {
+        title: title.to_string(),
+        line,
+        line_end,
+        words,
+        reading: Counts::default(),
+    };
+    let mut report = Report {
+        words: structure.unchaptered_words
+            + structure
+                .chapters
+                .iter()
+                .map(|c| c.word_count)
+                .sum::<usize>(),
+        chapters: structure
+            .chapters
+            .iter()
+            .map(|c| section(&c.title, c.line_start, c.line_end, c.word_count))
+            .collect(),
+        scenes: structure
+            .scenes
+            .iter()
+            .map(|s| section(&s.description, s.line_start, s.line_end, s.word_count))
+            .collect(),
+        ..Report::default()
+    };
+
+    for block in blocks {
+        let words = parser::count_stats(&block.text, false).words;
+        match block.kind {
+            BlockKind::Dialogue => report.dialogue_words += words,
+            BlockKind::Paragraph | BlockKind::Direction | BlockKind::Parenthetical => {
+                report.action_words += words
+            }
+            BlockKind::Act | BlockKind::Chapter | BlockKind::Scene | BlockKind::Cue => continue,
+        }
+        let counts = cache.counts(&block.text);
+        report.reading += counts;
+        for sections in [&mut report.chapters, &mut report.scenes] {
+            if let Some(section) = section_at(sections, block.line) {
+                section.reading += counts;
+            }
+        }
+    }
+    cache.finish();
+    report
+}
+
+/// The section `line` falls in, if any; `sections` are in document order
+fn section_at(sections: &mut [Section], line: usize) -> Option<&mut Section> {
+    let after = sections.partition_point(|s| s.line <= line);
+    let section = sections.get_mut(after.checked_sub(1)?)?;
+    (line <= section.line_end).then_some(section)
+}
+
+/// The totals at the top of the window, as (label, value) rows; `bookscript
+/// stats` prints the same
+pub fn totals(report: &Report) -> Vec<(&'static str, String)> {
+    let mut rows = vec![
+        ("Words", report.words.to_string()),
+        ("Chapters", report.chapters.len().to_string()),
+        ("Scenes", report.scenes.len().to_string()),
+        ("Sentences", report.reading.sentences.to_string()),
+    ];
+    if let Some(average) = report.reading.sentence_length() {
+        rows.push(("Average sentence", format!("{:.1} words", average)));
+    }
+    if let Some(ease) = report.reading.reading_ease() {
+        rows.push((
+            "Reading ease",
+            format!("{:.0} ({})", ease, readability::ease_label(ease)),
+        ));
+    }
+    if let Some(grade) = report.reading.grade_level() {
+        rows.push(("Grade level", format!("{:.1}", grade)));
+    }
+    rows.push((
+        "Reading time",
+        readability::format_reading_time(report.reading.reading_minutes()),
+    ));
+    if let Some(share) = report.dialogue_share() {
+        rows.push((
+            "Dialogue / action",
+            format!(
+                "{:.0}% / {:.0}% ({} / {} words)",
+                share * 100.0,
+                (1.0 - share) * 100.0,
+                report.dialogue_words,
+                report.action_words
+            ),
+        ));
+    }
+    if let Some(scene) = report.longest_scene() {
+        rows.push((
+            "Longest scene",
+            format!("{} ({} words)", scene.title, scene.words),
+        ));
+    }
+    rows
+}
+
+/// Draw the report
+///
+/// Returns the 1-based line of the chapter or scene the user clicked, if
+/// any.
+pub fn show(ui: &mut egui::Ui, report: &Report, breakdown: &mut Breakdown) -> Option<usize> {
+    if report.words == 0 {
+        ui.label("Nothing to count yet.");
+        return None;
+    }
+
+    egui::Grid::new("statistics_totals")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            for (label, value) in totals(report) {
+                ui.label(label);
+                ui.label(value);
+                ui.end_row();
+            }
+        });
+
+    if let Some(share) = report.dialogue_share() {
+        ui.add(egui::ProgressBar::new(share).text("Dialogue"));
+    }
+
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.label("Words per");
+        ui.radio_value(breakdown, Breakdown::Chapters, "chapter");
+        ui.radio_value(breakdown, Breakdown::Scenes, "scene");
+    });
+
+    let sections = report.sections(*breakdown);
+    if sections.is_empty() {
+        ui.label(match breakdown {
+            Breakdown::Chapters => "No chapters found.",
+            Breakdown::Scenes => "No scenes found.",
+        });
+        return None;
+    }
+
+    let mut clicked = None;
+    egui::ScrollArea::vertical()
+        .id_salt("statistics_chart")
+        .max_height(360.0)
+        .show(ui, |ui| {
+            let most = sections.iter().map(|s| s.words).max().unwrap_or(0).max(1);
+            for section in sections {
+                if bar(ui, section, most).clicked() {
+                    clicked = Some(section.line);
+                }
+            }
+        });
+
+    let heading = match breakdown {
+        Breakdown::Chapters => "Readability by chapter",
+        Breakdown::Scenes => "Readability by scene",
+    };
+    egui::CollapsingHeader::new(heading)
+        .id_salt("statistics_readability")
+        .show(ui, |ui| {
+            if let Some(line) = readability_table(ui, sections) {
+                clicked = Some(line);
+            }
+        });
+    clicked
+}
+
+/// Reading ease, grade level and reading time of each section
+///
+/// Returns the line of the section whose title was clicked.
+fn readability_table(ui: &mut egui::Ui, sections: &[Section]) -> Option<usize> {
+    let mut clicked = None;
+    egui::ScrollArea::vertical()
+        .id_salt("statistics_readability_table")
+        .max_height(240.0)
+        .show(ui, |ui| {
+            egui::Grid::new("statistics_readability_grid")
+                .striped(true)
+                .num_columns(4)
+                .show(ui, |ui| {
+                    ui.strong("Title");
+                    ui.strong("Ease");
+                    ui.strong("Grade");
+                    ui.strong("Reading time");
+                    ui.end_row();
+
+                    let score = |value: Option<f32>, places: usize| {
+                        value.map_or_else(|| String::from("-"), |v| format!("{:.*}", places, v))
+                    };
+                    for section in sections {
+                        if ui.selectable_label(false, &section.title).clicked() {
+                            clicked = Some(section.line);
+                        }
+                        ui.label(score(section.reading.reading_ease(), 0));
+                        ui.label(score(section.reading.grade_level(), 1));
+                        ui.label(readability::format_reading_time(
+                            section.reading.reading_minutes(),
+                        ));
+                        ui.end_row();
+                    }
+                });
+        });
+    clicked
+}
+
+/// One row of the chart: the label, then a bar as long as the section is
+/// against the longest one, with its count at the end
+fn bar(ui: &mut egui::Ui, section: &Section, most: usize) -> egui::Response {
+    let size = egui::vec2(ui.available_width(), BAR_HEIGHT);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+    let visuals = ui.visuals();
+    let painter = ui.painter_at(rect);
+    let font = egui::TextStyle::Body.resolve(ui.style());
+
+    // Titles too long for their column are cut off at its edge
+    let label_rect = rect.with_max_x(rect.left() + LABEL_WIDTH - 8.0);
+    painter.with_clip_rect(label_rect).text(
+        label_rect.left_center(),
+        egui::Align2::LEFT_CENTER,
+        &section.title,
+        font.clone(),
+        visuals.text_color(),
+    );
+
+    let count = painter.layout_no_wrap(section.words.to_string(), font, visuals.text_color());
+    let room = (rect.width() - LABEL_WIDTH - count.size().x - 4.0).max(0.0);
+    let length = (section.words as f32 / most as f32 * room).max(1.0);
+    let left = rect.left() + LABEL_WIDTH;
+    let bar = egui::Rect::from_min_max(
+        egui::pos2(left, rect.top() + 3.0),
+        egui::pos2(left + length, rect.bottom() - 3.0),
+    );
+    let color = if response.hovered() {
+        visuals.selection.bg_fill
+    } else {
+        visuals.widgets.inactive.bg_fill
+    };
+    painter.rect_filled(bar, 2.0, color);
+    let count_top = rect.center().y - count.size().y / 2.0;
+    painter.galley(
+        egui::pos2(bar.right() + 4.0, count_top),
+        count,
+        visuals.text_color(),
+    );
+
+    response.on_hover_text(format!(
+        "{} words, line {}\nReading time: {}",
+        section.words,
+        section.line,
+        readability::format_reading_time(section.reading.reading_minutes())
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formatted;
+
+    const TEXT: &str = "[CHAPTER: One]\n[SCENE: Beach]\nThe sea was calm.\n\nANN\nHello there.\n(beat)\nGoodbye now.\n\
+                        [SCENE: Pier]\nGulls.\n[CHAPTER: Two]\n[SCENE: Cave]\nIt was dark. Very dark.\n";
+
+    fn build(text: &str, cache: &mut readability::Cache) -> Report {
+        let parsed = parser::parse_document(text);
+        let mut structure = parser::extract_structure(&parsed);
+        parser::annotate_structure(text, &mut structure, true);
+        report(&structure, &formatted::blocks(&parsed), cache)
+    }
+
+    #[test]
+    fn dialogue_and_action_are_told_apart() {
+        let report = build(TEXT, &mut readability::Cache::default());
+        // Speeches under the cue; the parenthetical inside is action
+        assert_eq!(report.dialogue_words, 4);
+        assert_eq!(report.action_words, 4 + 1 + 1 + 5);
+        assert_eq!(report.dialogue_share(), Some(4.0 / 15.0));
+        assert_eq!(Report::default().dialogue_share(), None);
+    }
+
+    #[test]
+    fn sections_get_their_words_and_readability() {
+        let report = build(TEXT, &mut readability::Cache::default());
+        let summary = |sections: &[Section]| {
+            sections
+                .iter()
+                .map(|s| (s.title.clone(), s.line, s.line_end, s.reading.words))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            summary(&report.chapters),
+            [
+                (String::from("One"), 1, 10, 10),
+                (String::from("Two"), 11, 13, 5),
+            ]
+        );
+        assert_eq!(
+            summary(&report.scenes),
+            [
+                (String::from("Beach"), 2, 8, 9),
+                (String::from("Pier"), 9, 10, 1),
+                (String::from("Cave"), 12, 13, 5),
+            ]
+        );
+        assert_eq!(report.reading.words, 15);
+        assert_eq!(report.reading.sentences, 7);
+        assert_eq!(report.longest_scene().unwrap().title, "Beach");
+    }
+
+    #[test]
+    fn totals_rows() {
+        let report = build(TEXT, &mut readability::Cache::default());
+        let rows = totals(&report);
+        let value = |label: &str| {
+            rows.iter()
+                .find(|(l, _)| *l == label)
+                .map(|(_, v)| v.as_str())
+        };
+        assert_eq!(value("Chapters"), Some("2"));
+        assert_eq!(value("Scenes"), Some("3"));
+        assert_eq!(value("Sentences"), Some("7"));
+        assert_eq!(value("Reading time"), Some("under 1 min"));
+        assert_eq!(value("Dialogue / action"), Some("27% / 73% (4 / 11 words)"));
+        // As the status bar counts, the cue included
+        assert_eq!(value("Longest scene"), Some("Beach (10 words)"));
+
+        // Nothing to score, nothing to share
+        let rows = totals(&Report::default());
+        let labels: Vec<_> = rows.iter().map(|(l, _)| *l).collect();
+        assert_eq!(
+            labels,
+            ["Words", "Chapters", "Scenes", "Sentences", "Reading time"]
+        );
+    }
+
+    #[test]
+    fn a_line_outside_every_section_is_in_none() {
+        let mut sections = build(TEXT, &mut readability::Cache::default()).scenes;
+        assert!(section_at(&mut sections, 1).is_none());
+        assert_eq!(section_at(&mut sections, 11), None);
+        assert_eq!(section_at(&mut sections, 9).unwrap().title, "Pier");
+        assert_eq!(section_at(&mut sections, 13).unwrap().title, "Cave");
+    }
+
+    #[test]
+    fn a_shared_cache_gives_the_same_report() {
+        let mut cache = readability::Cache::default();
+        let first = build(TEXT, &mut cache);
+        assert_eq!(build(TEXT, &mut cache), first);
+        let edited = TEXT.replace("Gulls.", "Gulls cried.");
+        assert_eq!(
+            build(&edited, &mut cache),
+            build(&edited, &mut readability::Cache::default())
+        );
+    }
+}
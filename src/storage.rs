@@ -1,19 +1,32 @@
+//! FILE: src/storage.rs
+//!
+//! This module handles all file I/O operations and autosave functionality.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - std::fs: File system operations (reading, writing files)
+//! - std::path: Cross-platform path handling
+//! - anyhow: Flexible error handling with context
+//! - std::sync::mpsc: Channels between the GUI and the autosave thread
+//! - std::time::Duration: Representing time intervals
+
+use crate::bookmarks::Bookmark;
+use crate::encoding::{self, DecodedText};
+use crate::encryption;
+use crate::format::DocumentFormat;
+use crate::settings::Settings;
+use crate::templates::Template;
 use anyhow::{Context, Result};
-/// FILE: src/storage.rs
-///
-/// This module handles all file I/O operations and autosave functionality.
-///
-/// RUST CONCEPTS DEMONSTRATED:
-/// - std::fs: File system operations (reading, writing files)
-/// - std::path: Cross-platform path handling
-/// - anyhow: Flexible error handling with context
-/// - std::thread::sleep: Pausing execution
-/// - std::time::Duration: Representing time intervals
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
 use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
 use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 // ============================================================================
 // FILE I/O FUNCTIONS
@@ -32,23 +45,46 @@ use std::time::Duration;
 ///   Success: Ok(String) containing the file contents
 ///   Failure: Err(Error) with context about what went wrong
 ///
+/// ENCODINGS:
+/// The file doesn't have to be UTF-8 - see load_document, which this wraps.
+/// Use load_document directly to find out what the file was encoded in.
+///
 /// ERROR HANDLING:
 /// The `?` operator propagates errors up the call stack. If any operation
 /// fails, we immediately return Err(...) to the caller.
 /// The `.context()` method adds human-readable context to errors.
 pub fn load_text_file<P: AsRef<Path>>(path: P) -> Result<String> {
-    // Convert the generic path parameter to a Path reference
-    let path = path.as_ref();
+    // Only the text is wanted here, not how it was stored
+    Ok(load_document(path)?.text)
+}
+
+/// Load a file as editor text, noting its encoding and line endings
+///
+/// The raw bytes are decoded by encoding::decode: a BOM is stripped,
+/// UTF-16 is converted, and anything that isn't UTF-8 is read as
+/// Windows-1252. Line endings come back as `\n`. Only an unreadable file or
+/// a password-protected one (which needs file_io's Unlock) is an error - no
+/// other byte sequence is.
+pub fn load_document<P: AsRef<Path>>(path: P) -> Result<DecodedText> {
+    load_document_from(&DiskStorage, path.as_ref())
+}
 
-    // fs::read_to_string reads the entire file into a String
+/// load_document, reading through any Storage backend
+pub fn load_document_from(storage: &dyn Storage, path: &Path) -> Result<DecodedText> {
     // The ? operator means: "if this returns Err, return that error immediately"
-    // .context() adds context so the user knows WHICH operation failed
-    let content =
-        fs::read_to_string(path).context(format!("Failed to read file: {}", path.display()))?;
+    let bytes = storage.read(path)?;
+    if encryption::is_encrypted(&bytes) {
+        anyhow::bail!("{} is password-protected", path.display());
+    }
+    Ok(encoding::decode(&bytes))
+}
 
-    // If we got here, reading succeeded
-    // Return Ok(content) wrapped in Result
-    Ok(content)
+/// Whether `path` is a password-protected document (false if it can't be
+/// read at all)
+pub fn is_password_protected(storage: &dyn Storage, path: &Path) -> bool {
+    storage
+        .read(path)
+        .is_ok_and(|bytes| encryption::is_encrypted(&bytes))
 }
 
 /// Save text content to a file on disk
@@ -58,45 +94,309 @@ pub fn load_text_file<P: AsRef<Path>>(path: P) -> Result<String> {
 /// - `content`: What to write (a string reference)
 ///   `&str` is a string slice - a view into string data
 ///   It doesn't own the string, just borrows it
+/// - `backups`: Whether the version being replaced is copied to the
+///   document's backups folder first, and how many are kept (see SAVE
+///   BACKUPS). BackupSettings::OFF keeps none, which is what autosave and
+///   small metadata files use.
 ///
 /// RETURN TYPE:
 /// - Result<()>: Success returns Ok(()), failure returns Err(Error)
 ///   The unit type `()` is like void - it means "no meaningful return value"
-pub fn save_text_file<P: AsRef<Path>>(path: P, content: &str) -> Result<()> {
+///
+/// CRASH SAFETY:
+/// Writing straight into the target would leave a half-written file if the
+/// process died or the disk filled up midway - losing BOTH versions. Instead
+/// the content goes to a temporary file in the same folder, is flushed to
+/// disk, and is then renamed over the target. A rename within one folder is
+/// atomic, so the target is always either the complete old version or the
+/// complete new one.
+pub fn save_text_file<P: AsRef<Path>>(
+    path: P,
+    content: &str,
+    backups: BackupSettings,
+) -> Result<()> {
+    DiskStorage.write(path.as_ref(), content.as_bytes(), backups)
+}
+
+/// Write a binary export (a PDF) in one go
+///
+/// No temp file or backups: an export can always be made again from the
+/// document, so a half-written one costs nothing.
+pub fn save_binary_file<P: AsRef<Path>>(path: P, content: &[u8]) -> Result<()> {
     let path = path.as_ref();
+    fs::write(path, content).context(format!("Failed to write file: {}", path.display()))
+}
 
-    // Before saving, ensure the parent directory exists
-    // Example: if path is "/foo/bar/file.txt", we need "/foo/bar" to exist
-    if let Some(parent) = path.parent() {
-        // fs::create_dir_all creates all missing parent directories
-        // Like `mkdir -p` in Unix/Linux
-        fs::create_dir_all(parent)
-            .context(format!("Failed to create directory: {}", parent.display()))?;
+// ============================================================================
+// STORAGE BACKENDS
+// ============================================================================
+// Loading, saving and autosaving the document go through the Storage trait
+// instead of calling std::fs directly. The app uses DiskStorage; tests can
+// hand the file worker and autosave thread an InMemoryStorage and check
+// what would have been written without creating any files.
+//
+// Only the document itself goes through here. Settings, sidecars, the trash
+// and version-history snapshots are separate files in the data directory
+// and still use the free functions in this module.
+
+/// Where documents are read from and written to
+///
+/// `Send + Sync` lets one backend be shared (in an Arc) by the GUI, the
+/// file worker and the autosave thread.
+pub trait Storage: Send + Sync {
+    /// A file's raw bytes, undecoded
+    fn read(&self, path: &Path) -> Result<Vec<u8>>;
+
+    /// Replace a file's contents, creating missing folders, and back up
+    /// the version replaced as `backups` says (see save_text_file)
+    fn write(&self, path: &Path, content: &[u8], backups: BackupSettings) -> Result<()>;
+
+    /// When the file was last changed (None if it doesn't exist)
+    fn modified(&self, path: &Path) -> Option<SystemTime>;
+}
+
+/// The real file system
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiskStorage;
+
+impl Storage for DiskStorage {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        // fs::read gives the raw bytes, whatever they are
+        // .context() adds context so the user knows WHICH operation failed
+        fs::read(path).context(format!("Failed to read file: {}", path.display()))
+    }
+
+    /// See save_text_file for why this writes a temp file and renames it
+    ///
+    /// PERMISSIONS:
+    /// The renamed-in temp file is a NEW file, with default permissions.
+    /// On Unix the old file's mode bits are copied onto it first, so a
+    /// document that was chmod 600 (or group-writable) stays that way.
+    fn write(&self, path: &Path, content: &[u8], backups: BackupSettings) -> Result<()> {
+        // Before saving, ensure the parent directory exists
+        // Example: if path is "/foo/bar/file.txt", we need "/foo/bar" to exist
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            // fs::create_dir_all creates all missing parent directories
+            // Like `mkdir -p` in Unix/Linux
+            fs::create_dir_all(parent)
+                .context(format!("Failed to create directory: {}", parent.display()))?;
+        }
+
+        // Keep the previous version around before it gets replaced
+        make_backup(path, &backups, unix_now(), get_trash_dir)?;
+
+        replace_file(path, content, write_and_sync)
+    }
+
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        modified_time(path)
+    }
+}
+
+/// Put `content` in place at `path` through a temp file and a rename
+///
+/// `write_temp` writes the temp file: write_and_sync, except in tests that
+/// make it fail halfway.
+fn replace_file(
+    path: &Path,
+    content: &[u8],
+    write_temp: impl FnOnce(&Path, &[u8]) -> Result<()>,
+) -> Result<()> {
+    // STEP 1: Write everything to a temporary file next to the target
+    // (same folder = same filesystem, which rename needs to be atomic)
+    let temp_path = temp_path_for(path);
+    if let Err(e) = write_temp(&temp_path, content) {
+        // Don't leave a half-written temp file lying around
+        let _ = fs::remove_file(&temp_path);
+        return Err(e.context(format!("Failed to write file: {}", path.display())));
+    }
+
+    // Keep the old file's permission bits (Unix only - elsewhere the only
+    // permission is the read-only flag, which would stop the rename)
+    #[cfg(unix)]
+    if let Err(e) = copy_permissions(path, &temp_path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e.context(format!("Failed to write file: {}", path.display())));
+    }
+
+    // STEP 2: Swap it into place
+    if let Err(e) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(
+            anyhow::Error::new(e).context(format!("Failed to replace file: {}", path.display()))
+        );
     }
 
-    // fs::write writes the entire string to a file
-    // If the file exists, it's overwritten
-    // If it doesn't exist, it's created
-    fs::write(path, content).context(format!("Failed to write file: {}", path.display()))?;
+    // STEP 3: Make the rename itself durable (Unix only - Windows can't
+    // open a directory this way). Failure here isn't worth reporting: the
+    // new content is already in place.
+    #[cfg(unix)]
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if let Ok(dir) = fs::File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
 
     // Success!
     Ok(())
 }
 
-/// Get the path to the autosave directory
+/// Give `to` the same Unix mode bits as `from`, if `from` exists
+#[cfg(unix)]
+fn copy_permissions(from: &Path, to: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let Ok(metadata) = fs::metadata(from) else {
+        // First save: the temp file's default permissions are right
+        return Ok(());
+    };
+    let mode = metadata.permissions().mode();
+    fs::set_permissions(to, fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+/// Files kept in a HashMap instead of on disk
 ///
-/// On Windows: C:\Users\USERNAME\AppData\Roaming\BookScript\projects
-/// On Linux: ~/.config/BookScript/projects
-/// On macOS: ~/Library/Application Support/BookScript/projects
+/// Modification times come from a counter that ticks once per write, so
+/// they are distinct and increasing even when two writes happen within
+/// the same clock tick. Backups are kept under the same names DiskStorage
+/// uses ("backups/name-<date>-<time>.bks"), stamped with those times. Only built for tests: the app itself always
+/// uses DiskStorage.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct InMemoryStorage {
+    files: Mutex<InMemoryFiles>,
+}
+
+#[cfg(test)]
+#[derive(Debug, Default)]
+struct InMemoryFiles {
+    contents: std::collections::HashMap<PathBuf, (Vec<u8>, SystemTime)>,
+    writes: u64,
+}
+
+#[cfg(test)]
+impl InMemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Put a file in place as if another program had written it
+    pub fn insert(&self, path: impl Into<PathBuf>, bytes: impl Into<Vec<u8>>) {
+        let mut files = self.files.lock().unwrap();
+        let modified = files.tick();
+        files.contents.insert(path.into(), (bytes.into(), modified));
+    }
+
+    /// A file's contents as text (lossily decoded), if it exists
+    pub fn text(&self, path: &Path) -> Option<String> {
+        let files = self.files.lock().unwrap();
+        let (bytes, _) = files.contents.get(path)?;
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    /// Every stored path, sorted
+    pub fn paths(&self) -> Vec<PathBuf> {
+        let files = self.files.lock().unwrap();
+        let mut paths: Vec<PathBuf> = files.contents.keys().cloned().collect();
+        paths.sort();
+        paths
+    }
+}
+
+#[cfg(test)]
+impl InMemoryFiles {
+    /// The next modification time
+    fn tick(&mut self) -> SystemTime {
+        self.writes += 1;
+        UNIX_EPOCH + Duration::from_secs(self.writes)
+    }
+}
+
+#[cfg(test)]
+impl Storage for InMemoryStorage {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let files = self.files.lock().unwrap();
+        match files.contents.get(path) {
+            Some((bytes, _)) => Ok(bytes.clone()),
+            None => anyhow::bail!("Failed to read file: {}", path.display()),
+        }
+    }
+
+    fn write(&self, path: &Path, content: &[u8], backups: BackupSettings) -> Result<()> {
+        let mut files = self.files.lock().unwrap();
+
+        // Same backups as make_backup, on the map (with nothing kept of
+        // what it trashes)
+        if let Some((current, saved)) = files.contents.get(path).cloned() {
+            if backups.keeps_any() {
+                let modified = files.tick();
+                files
+                    .contents
+                    .insert(backup_path(path, saved), (current, modified));
+            }
+            let mut listed: Vec<Snapshot> = files
+                .contents
+                .keys()
+                .filter_map(|stored| {
+                    Some(Snapshot {
+                        created: backup_time(path, stored)?,
+                        path: stored.clone(),
+                        backup: true,
+                    })
+                })
+                .collect();
+            listed.sort_by_key(|backup| std::cmp::Reverse(backup.created));
+            for expired in expired_backups(&listed, &backups, unix_now()) {
+                files.contents.remove(&expired);
+            }
+        }
+
+        let modified = files.tick();
+        files
+            .contents
+            .insert(path.to_path_buf(), (content.to_vec(), modified));
+        Ok(())
+    }
+
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        let files = self.files.lock().unwrap();
+        files.contents.get(path).map(|(_, modified)| *modified)
+    }
+}
+
+/// Write `content` to `path` and wait until it has reached the disk
+fn write_and_sync(path: &Path, content: &[u8]) -> Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(content)?;
+    // sync_all() asks the OS to flush its caches to the physical disk -
+    // without it, a power cut right after the rename could still lose data
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Temporary file used while saving `path`
 ///
-/// RETURN TYPE:
-/// - Result<PathBuf>: A newly allocated path buffer
-///   PathBuf is like String, but for file paths
-///   Path is like &str, but for file paths
+/// "chapter1.bks" → ".chapter1.bks.tmp-<process id>". The leading dot hides
+/// it on Unix, and the process id keeps two running copies of the app from
+/// writing to the same temp file.
+fn temp_path_for(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.tmp-{}", name, std::process::id()))
+}
+
+/// Get the root data directory for the application
 ///
-/// ERROR HANDLING:
-/// If we can't determine the user's data directory, we return an error
-pub fn get_autosave_dir() -> Result<PathBuf> {
+/// On Windows: C:\Users\USERNAME\AppData\Roaming\BookScript\BookScript\data
+/// On Linux: ~/.local/share/bookscript
+/// On macOS: ~/Library/Application Support/com.BookScript.BookScript
+///
+/// Other folders (autosaves, trash, ...) live underneath this one.
+/// The directory is NOT created here - callers create the subfolder they need.
+pub fn get_data_dir() -> Result<PathBuf> {
     // directories::ProjectDirs finds the appropriate directories for our app
     // "com", "BookScript", "BookScript" are:
     // - Qualifier (company/organization)
@@ -108,8 +408,25 @@ pub fn get_autosave_dir() -> Result<PathBuf> {
         .context("Could not determine user data directory")?;
 
     // data_dir() gives us the main data directory
-    // We append "projects" to store our autosave files there
-    let autosave_dir = proj_dirs.data_dir().join("projects");
+    Ok(proj_dirs.data_dir().to_path_buf())
+}
+
+/// Get the path to the autosave directory
+///
+/// On Windows: C:\Users\USERNAME\AppData\Roaming\BookScript\projects
+/// On Linux: ~/.config/BookScript/projects
+/// On macOS: ~/Library/Application Support/BookScript/projects
+///
+/// RETURN TYPE:
+/// - Result<PathBuf>: A newly allocated path buffer
+///   PathBuf is like String, but for file paths
+///   Path is like &str, but for file paths
+///
+/// ERROR HANDLING:
+/// If we can't determine the user's data directory, we return an error
+pub fn get_autosave_dir() -> Result<PathBuf> {
+    // We append "projects" to the data directory to store our autosave files
+    let autosave_dir = get_data_dir()?.join("projects");
 
     // Ensure the directory exists before returning
     fs::create_dir_all(&autosave_dir).context(format!(
@@ -121,132 +438,2458 @@ pub fn get_autosave_dir() -> Result<PathBuf> {
 }
 
 // ============================================================================
-// AUTOSAVE THREAD FUNCTION
+// SAVE BACKUPS
 // ============================================================================
+//
+// Before a save replaces a document, the version on disk is copied into a
+// `backups` folder beside it, named after the document and the time that
+// version was saved:
+//
+//   /novels/draft.bks → /novels/backups/draft-20261016-153000.bks
+//
+// The time is the replaced file's modification time, in local time, so the
+// name says when that version was written. A copy gets a modification time
+// of its own, so the time is read back from the name (backup_time), not
+// from the backup's metadata. Two versions saved within the same second
+// share a name, and the later one wins.
+//
+// Which backups are kept is up to BackupSettings: the newest N, or those
+// from the last M days (the newest is kept whatever its age, so a document
+// left alone for months still has one). The rest go to the trash as each
+// save makes a new one. With backups turned off, saving trashes them all.
 
-/// Background thread that periodically saves the document
-///
-/// This function runs in a separate thread and loops forever, waking up
-/// every 60 seconds to save the current text content.
-///
-/// PARAMETERS:
-/// - `text_content`: Arc<Mutex<String>> shared with the GUI thread
-///   Arc allows multiple threads to own the same data
-///   Mutex ensures only one thread accesses it at a time
-///
-/// THREADING SAFETY:
-/// The Mutex ensures that when we lock and read the text, the GUI thread
-/// can't modify it at the same time. This prevents data races.
-///
-/// INFINITE LOOP:
-/// This function never returns - it runs until the program exits.
-/// When the main thread (GUI) exits, all background threads are terminated.
-pub fn autosave_thread(text_content: Arc<Mutex<String>>) {
-    // This loop runs forever
-    loop {
-        // Sleep for 60 seconds
-        // Duration::from_secs(60) creates a 60-second time interval
-        // thread::sleep pauses this thread without consuming CPU
-        thread::sleep(Duration::from_secs(60));
+/// Name of the folder beside a document that holds its backups
+pub const BACKUP_DIR: &str = "backups";
 
-        // After waking up, perform the autosave
+/// The date and time in a backup's name
+const BACKUP_TIME_FORMAT: &str = "%Y%m%d-%H%M%S";
 
-        // ----------------------------------------------------------------
-        // STEP 1: Get the autosave directory path
-        // ----------------------------------------------------------------
-        let autosave_dir = match get_autosave_dir() {
-            Ok(dir) => dir,
-            Err(e) => {
-                // If we can't get the directory, print an error and skip this save
-                eprintln!("Autosave error: {}", e);
-                // `continue` jumps back to the start of the loop
-                continue;
-            }
-        };
+/// How old backups are cleared out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BackupRetention {
+    /// Keep the newest `count`
+    #[default]
+    Count,
+    /// Keep those from the last `days` days
+    Days,
+}
 
-        // ----------------------------------------------------------------
-        // STEP 2: Create the autosave file path
-        // ----------------------------------------------------------------
-        // We save to "autosave.bks" in the autosave directory
-        let autosave_path = autosave_dir.join("autosave.bks");
+/// Save backup preferences (Edit → Preferences…)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupSettings {
+    /// Copy the version on disk into the backups folder before each save
+    pub enabled: bool,
+    pub retention: BackupRetention,
+    /// Backups kept with BackupRetention::Count
+    pub count: usize,
+    /// Days backups are kept with BackupRetention::Days
+    pub days: u64,
+}
 
-        // ----------------------------------------------------------------
-        // STEP 3: Lock the mutex and clone the text content
-        // ----------------------------------------------------------------
-        // IMPORTANT: We clone the string so we can release the lock quickly
-        // Holding the lock during file I/O would block the GUI thread
-        let content = {
-            // Lock the mutex - this blocks if the GUI thread is holding the lock
-            // The lock is automatically released when `guard` goes out of scope
-            let guard = text_content.lock().unwrap();
-            // Clone the string (makes a copy of the text)
-            guard.clone()
-            // `guard` goes out of scope here, releasing the lock
-        };
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            retention: BackupRetention::Count,
+            count: 3,
+            days: 30,
+        }
+    }
+}
 
-        // ----------------------------------------------------------------
-        // STEP 4: Save to disk
-        // ----------------------------------------------------------------
-        match save_text_file(&autosave_path, &content) {
-            Ok(_) => {
-                // Success! Print a message (appears in the terminal)
-                println!("Autosaved to: {}", autosave_path.display());
-            }
-            Err(e) => {
-                // Error! Print to stderr
-                eprintln!("Autosave failed: {}", e);
-            }
+impl BackupSettings {
+    /// No backups, for autosaves and the app's own files
+    pub const OFF: BackupSettings = BackupSettings {
+        enabled: false,
+        retention: BackupRetention::Count,
+        count: 0,
+        days: 0,
+    };
+
+    /// Whether saving makes a backup (keeping none is the same as off)
+    fn keeps_any(&self) -> bool {
+        self.enabled && (self.retention == BackupRetention::Days || self.count > 0)
+    }
+}
+
+/// Largest count and age offered in Preferences
+pub const MAX_BACKUP_COUNT: usize = 100;
+pub const MAX_BACKUP_DAYS: u64 = 365;
+
+/// The folder holding `document`'s backups
+pub fn backup_dir(document: &Path) -> PathBuf {
+    document.parent().unwrap_or(Path::new("")).join(BACKUP_DIR)
+}
+
+/// Where the version of `document` saved at `saved` is backed up:
+/// "draft.bks" → "backups/draft-20261016-153000.bks"
+pub fn backup_path(document: &Path, saved: SystemTime) -> PathBuf {
+    let stamp = chrono::DateTime::<chrono::Local>::from(saved).format(BACKUP_TIME_FORMAT);
+    let stem = document
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut name = format!("{}-{}", stem, stamp);
+    if let Some(extension) = document.extension() {
+        name.push('.');
+        name.push_str(&extension.to_string_lossy());
+    }
+    backup_dir(document).join(name)
+}
+
+/// When the version in `backup` was saved (seconds since the Unix epoch),
+/// read from its name; None if it isn't a backup of `document`
+fn backup_time(document: &Path, backup: &Path) -> Option<u64> {
+    if backup.parent() != Some(backup_dir(document).as_path()) {
+        return None;
+    }
+    let stem = document.file_stem()?.to_str()?;
+    let name = backup.file_name()?.to_str()?;
+    let rest = name.strip_prefix(stem)?.strip_prefix('-')?;
+    let stamp = match document.extension() {
+        Some(extension) => rest.strip_suffix(extension.to_str()?)?.strip_suffix('.')?,
+        None => rest,
+    };
+    // Exactly a date and time: "draft-b-20261016-153000.bks" belongs to
+    // "draft-b.bks", not "draft.bks"
+    if stamp.len() != 15 {
+        return None;
+    }
+    let saved = chrono::NaiveDateTime::parse_from_str(stamp, BACKUP_TIME_FORMAT).ok()?;
+    let saved = saved.and_local_timezone(chrono::Local).earliest()?;
+    u64::try_from(saved.timestamp()).ok()
+}
+
+/// The backups of `document`, newest first
+///
+/// A backups folder that doesn't exist (or can't be read) has none.
+pub fn list_backups(document: &Path) -> Vec<Snapshot> {
+    let Ok(entries) = fs::read_dir(backup_dir(document)) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<Snapshot> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let created = backup_time(document, &path)?;
+            Some(Snapshot {
+                path,
+                created,
+                backup: true,
+            })
+        })
+        .collect();
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.created));
+    backups
+}
+
+/// Of `backups` (newest first, as list_backups gives them), the ones
+/// `settings` no longer keeps at `now`
+fn expired_backups(backups: &[Snapshot], settings: &BackupSettings, now: u64) -> Vec<PathBuf> {
+    let max_age = settings.days.saturating_mul(24 * 60 * 60);
+    backups
+        .iter()
+        .enumerate()
+        .filter(|&(index, backup)| {
+            !settings.keeps_any()
+                || match settings.retention {
+                    BackupRetention::Count => index >= settings.count,
+                    BackupRetention::Days => {
+                        index > 0 && now.saturating_sub(backup.created) > max_age
+                    }
+                }
+        })
+        .map(|(_, backup)| backup.path.clone())
+        .collect()
+}
+
+/// Copy the version of `path` on disk into its backups, then move the
+/// backups `settings` no longer keeps to the trash (see the section notes)
+///
+/// Nothing is copied on the very first save (no file yet). The file is
+/// COPIED, not moved, so it's still there if the save that follows fails.
+/// `trash_dir` is only asked for once there is a backup to trash.
+fn make_backup(
+    path: &Path,
+    settings: &BackupSettings,
+    now: u64,
+    trash_dir: impl FnOnce() -> Result<PathBuf>,
+) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    if settings.keeps_any() {
+        let saved = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .unwrap_or_else(|_| SystemTime::now());
+        let dir = backup_dir(path);
+        fs::create_dir_all(&dir)
+            .context(format!("Failed to create directory: {}", dir.display()))?;
+        let backup = backup_path(path, saved);
+        fs::copy(path, &backup)
+            .context(format!("Failed to create backup: {}", backup.display()))?;
+    }
+
+    let expired = expired_backups(&list_backups(path), settings, now);
+    if !expired.is_empty() {
+        let trash_dir = trash_dir()?;
+        for backup in expired {
+            move_to_trash(&trash_dir, &backup, now)
+                .context(format!("Failed to remove old backup: {}", backup.display()))?;
         }
+    }
+    Ok(())
+}
 
-        // Loop continues - wait another 60 seconds and repeat
+/// A backup's text, decrypted with `password` if it's a backup of a
+/// password-protected document (those are copies of the encrypted file)
+pub fn load_backup(path: &Path, password: Option<&str>) -> Result<String> {
+    let bytes = DiskStorage.read(path)?;
+    if !encryption::is_encrypted(&bytes) {
+        return Ok(encoding::decode(&bytes).text);
     }
+    let password = password.with_context(|| format!("{} is password-protected", path.display()))?;
+    let plain = encryption::decrypt(&bytes, password)?
+        .context("The document's password doesn't open this backup")?;
+    Ok(encoding::decode(&plain).text)
 }
 
 // ============================================================================
-// HOW THREADING WORKS IN THIS MODULE
+// EXTERNAL CHANGES
 // ============================================================================
 //
-// THREAD RELATIONSHIPS:
-//
-// Main Thread (GUI):                Autosave Thread:
-//   |                                    |
-//   | Creates Arc<Mutex<String>>         |
-//   |-------------------------------->---|
-//   | Clones Arc pointer                 |
-//   | Spawns thread                      |
-//   |                                    |
-//   | Editing text...                    | Sleep 60s...
-//   | (locks mutex)                      |
-//   | Modifies string                    |
-//   | (unlocks mutex)                    |
-//   |                                    | Wake up!
-//   | Drawing UI...                      | (locks mutex)
-//   |                                    | Clone string
-//   |                                    | (unlocks mutex)
-//   | Editing text...                    | Save to disk...
-//   | (locks mutex)                      |
-//   | ...                                | Sleep 60s...
-//
-// MUTEX PREVENTS SIMULTANEOUS ACCESS:
-// If both threads try to lock at the same time, one waits until the other
-// releases the lock. This prevents data corruption.
+// Another copy of the app, or a sync client like Dropbox, can rewrite the
+// document while it's open here. Saving would then silently throw their
+// version away. So App remembers the file's modification time from when it
+// last loaded or saved it, and a save first checks that it hasn't moved.
 //
+// Autosave writes to "<name>.autosave", never the document itself, so it
+// can't clobber anything and doesn't need the check.
+
+/// When a file was last modified (None if it doesn't exist or the platform
+/// can't say)
+pub fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).ok()?.modified().ok()
+}
+
+/// Whether a save can go ahead
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveCheck {
+    /// Nobody else has touched the file
+    Proceed,
+    /// The file changed since it was last loaded or saved here
+    Conflict,
+}
+
+/// Decide whether saving over a file would overwrite someone else's changes
+///
+/// - `known`: the modification time when this app last loaded or saved the
+///   file (None if it never has - e.g. the target of a Save As)
+/// - `on_disk`: the modification time now (None if the file is gone)
+///
+/// Only a file that still exists and has a different time than the one we
+/// know about is a conflict. A deleted file has nothing left to lose, and
+/// writing it again is what the user wants.
+pub fn check_for_external_change(
+    known: Option<SystemTime>,
+    on_disk: Option<SystemTime>,
+) -> SaveCheck {
+    match (known, on_disk) {
+        (Some(known), Some(on_disk)) if known != on_disk => SaveCheck::Conflict,
+        _ => SaveCheck::Proceed,
+    }
+}
+
+// ============================================================================
+// DOCUMENT SIDECAR FILES
 // ============================================================================
+// A sidecar is a small JSON file that lives next to a document and stores
+// per-document settings that don't belong in the document text itself.
+// For `chapter1.bks` the sidecar is `chapter1.bks.meta`.
+
+/// Per-document metadata stored in the sidecar file
+///
+/// `#[serde(default)]` means missing fields are filled from Default, so
+/// sidecars written by older versions (with fewer fields) still load.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocumentMeta {
+    /// Format chosen by the user in the status-bar mode selector
+    /// None means "use whatever detect_format() decides"
+    pub format_override: Option<DocumentFormat>,
+}
+
+/// Get the sidecar path for a document: the document path plus ".meta"
+pub fn sidecar_path<P: AsRef<Path>>(document_path: P) -> PathBuf {
+    // We append to the full file name (not replace the extension) so that
+    // `notes.bks` and `notes.txt` in the same folder get different sidecars
+    let mut name = document_path.as_ref().as_os_str().to_owned();
+    name.push(".meta");
+    PathBuf::from(name)
+}
+
+/// Load the sidecar for a document
+///
+/// A missing sidecar is not an error - most documents never get one - so we
+/// return the default metadata in that case. A sidecar that exists but can't
+/// be parsed IS reported, so the caller can tell the user.
+pub fn load_sidecar<P: AsRef<Path>>(document_path: P) -> Result<DocumentMeta> {
+    let path = sidecar_path(document_path);
+
+    if !path.exists() {
+        return Ok(DocumentMeta::default());
+    }
+
+    let json = load_text_file(&path)?;
+    let meta = serde_json::from_str(&json)
+        .context(format!("Failed to parse sidecar: {}", path.display()))?;
+    Ok(meta)
+}
+
+/// Write the sidecar for a document
+///
+/// If the metadata is entirely default there is nothing worth remembering,
+/// so we remove an existing sidecar instead of leaving an empty one behind.
+pub fn save_sidecar<P: AsRef<Path>>(document_path: P, meta: &DocumentMeta) -> Result<()> {
+    let path = sidecar_path(document_path);
+
+    if *meta == DocumentMeta::default() {
+        if path.exists() {
+            trash_file(&path)?;
+        }
+        return Ok(());
+    }
+
+    let json = serde_json::to_string_pretty(meta).context("Failed to serialize sidecar")?;
+    save_text_file(&path, &json, BackupSettings::OFF)
+}
 
 // ============================================================================
-// ERROR HANDLING PHILOSOPHY
+// TRASH - SAFE DELETE
 // ============================================================================
+// The app never unlinks user files directly. Anything "deleted" is moved into
+// a `trash/` folder in the data directory, next to a small `.trashinfo` JSON
+// file recording where it came from and when it was deleted. The user can
+// restore it from Tools → Recently deleted, and entries older than
+// TRASH_RETENTION are purged for real on startup.
 //
-// This module uses the `anyhow` crate for error handling:
-//
-// 1. Return Result<T, anyhow::Error> from functions that can fail
-// 2. Use the ? operator to propagate errors up the call stack
-// 3. Use .context() to add helpful error messages
-// 4. The caller can decide how to handle errors (show to user, log, etc.)
-//
-// Example error chain:
-//   Error: Failed to write file: /path/to/file.txt
-//   Caused by: Permission denied (os error 13)
-//
-// This gives users actionable information about what went wrong.
-// ============================================================================
+// Layout on disk:
+//   trash/
+//     1729080000-chapter1.bks               <- the file itself
+//     1729080000-chapter1.bks.trashinfo     <- where it came from
+//     1729080000-1-chapter1.bks             <- same name, same second
+//     1729080000-1-chapter1.bks.trashinfo
+
+/// How long trashed files are kept before being purged automatically
+pub const TRASH_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Extension of the metadata file stored next to each trashed file
+const TRASH_INFO_EXTENSION: &str = "trashinfo";
+
+/// Contents of a `.trashinfo` file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TrashInfo {
+    /// Absolute path the file had before it was trashed
+    original_path: PathBuf,
+    /// Deletion time in seconds since the Unix epoch
+    deleted_at: u64,
+}
+
+/// One item in the trash, as shown in the "Recently deleted" dialog
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrashEntry {
+    /// Where the file currently lives inside the trash folder
+    pub stored_path: PathBuf,
+    /// Where the file lived before it was deleted
+    pub original_path: PathBuf,
+    /// Deletion time in seconds since the Unix epoch
+    pub deleted_at: u64,
+}
+
+/// Get the trash directory, creating it if needed
+pub fn get_trash_dir() -> Result<PathBuf> {
+    let trash_dir = get_data_dir()?.join("trash");
+    fs::create_dir_all(&trash_dir).context(format!(
+        "Failed to create trash directory: {}",
+        trash_dir.display()
+    ))?;
+    Ok(trash_dir)
+}
+
+/// Move a file into the application trash instead of deleting it
+///
+/// This is the function every "delete" in the app should call.
+pub fn trash_file<P: AsRef<Path>>(path: P) -> Result<TrashEntry> {
+    move_to_trash(&get_trash_dir()?, path.as_ref(), unix_now())
+}
+
+/// List everything in the application trash, newest first
+pub fn list_trash() -> Result<Vec<TrashEntry>> {
+    list_trash_in(&get_trash_dir()?)
+}
+
+/// Permanently delete everything in the trash older than TRASH_RETENTION
+///
+/// Returns how many entries were purged.
+pub fn purge_expired_trash() -> Result<usize> {
+    purge_trash_older_than(&get_trash_dir()?, TRASH_RETENTION, unix_now())
+}
+
+/// Move `path` into `trash_dir`, recording its origin and deletion time
+///
+/// The trash is a flat folder, so two files with the same name (say, two
+/// `autosave.bks` files from different projects) must not collide. The
+/// stored name is prefixed with the timestamp, and a counter is added when
+/// even that is taken.
+pub fn move_to_trash(trash_dir: &Path, path: &Path, deleted_at: u64) -> Result<TrashEntry> {
+    let file_name = path
+        .file_name()
+        .context(format!(
+            "Cannot trash a path without a file name: {}",
+            path.display()
+        ))?
+        .to_string_lossy()
+        .into_owned();
+
+    // Remember the absolute location so restore works no matter what the
+    // working directory is later. canonicalize() fails if the file doesn't
+    // exist, which is exactly the error we'd want to report anyway.
+    let original_path = fs::canonicalize(path).context(format!(
+        "Failed to locate file to delete: {}",
+        path.display()
+    ))?;
+
+    // Find a free slot: "<ts>-name", then "<ts>-1-name", "<ts>-2-name", ...
+    let mut counter = 0;
+    let stored_path = loop {
+        let candidate_name = if counter == 0 {
+            format!("{}-{}", deleted_at, file_name)
+        } else {
+            format!("{}-{}-{}", deleted_at, counter, file_name)
+        };
+        let candidate = trash_dir.join(candidate_name);
+        if !candidate.exists() && !trash_info_path(&candidate).exists() {
+            break candidate;
+        }
+        counter += 1;
+    };
+
+    fs::create_dir_all(trash_dir).context(format!(
+        "Failed to create trash directory: {}",
+        trash_dir.display()
+    ))?;
+
+    // Write the metadata first: if the move then fails we only leave behind
+    // an orphaned .trashinfo (ignored by list_trash_in), never a file whose
+    // origin is unknown
+    let info = TrashInfo {
+        original_path: original_path.clone(),
+        deleted_at,
+    };
+    let info_json =
+        serde_json::to_string_pretty(&info).context("Failed to serialize trash metadata")?;
+    fs::write(trash_info_path(&stored_path), info_json)
+        .context("Failed to write trash metadata")?;
+
+    move_file(&original_path, &stored_path)?;
+
+    Ok(TrashEntry {
+        stored_path,
+        original_path,
+        deleted_at,
+    })
+}
+
+/// Read every entry in `trash_dir`, newest first
+///
+/// Entries whose metadata is unreadable, or whose file has gone missing,
+/// are skipped rather than failing the whole listing.
+pub fn list_trash_in(trash_dir: &Path) -> Result<Vec<TrashEntry>> {
+    let mut entries = Vec::new();
+
+    let dir = match fs::read_dir(trash_dir) {
+        Ok(dir) => dir,
+        // No trash folder yet means nothing has been deleted
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(entries),
+        Err(e) => return Err(e).context(format!("Failed to read trash: {}", trash_dir.display())),
+    };
+
+    for item in dir.flatten() {
+        let info_path = item.path();
+        if info_path.extension().and_then(|e| e.to_str()) != Some(TRASH_INFO_EXTENSION) {
+            continue;
+        }
+
+        // "x.bks.trashinfo" -> "x.bks"
+        let stored_path = info_path.with_extension("");
+        if !stored_path.exists() {
+            continue;
+        }
+
+        let Ok(json) = fs::read_to_string(&info_path) else {
+            continue;
+        };
+        let Ok(info) = serde_json::from_str::<TrashInfo>(&json) else {
+            continue;
+        };
+
+        entries.push(TrashEntry {
+            stored_path,
+            original_path: info.original_path,
+            deleted_at: info.deleted_at,
+        });
+    }
+
+    // Reverse wraps the key so the sort is descending (newest first)
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.deleted_at));
+    Ok(entries)
+}
+
+/// Move a trashed file back to where it came from
+///
+/// If something has been created at the original path since the file was
+/// deleted, we don't overwrite it - the file is restored next to it as
+/// "name (restored).ext" instead. Returns the path actually restored to.
+pub fn restore_from_trash(entry: &TrashEntry) -> Result<PathBuf> {
+    let target = restore_target(&entry.original_path);
+
+    // The original folder may have been removed too
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    move_file(&entry.stored_path, &target)?;
+
+    // The metadata has served its purpose; a leftover is harmless, so a
+    // failure here is not worth reporting
+    let _ = fs::remove_file(trash_info_path(&entry.stored_path));
+
+    Ok(target)
+}
+
+/// Permanently delete a trashed file and its metadata
+///
+/// This is the only place in the app that really unlinks user data.
+pub fn purge_trash_entry(entry: &TrashEntry) -> Result<()> {
+    fs::remove_file(&entry.stored_path)
+        .context(format!("Failed to delete: {}", entry.stored_path.display()))?;
+    let _ = fs::remove_file(trash_info_path(&entry.stored_path));
+    Ok(())
+}
+
+/// Purge every entry in `trash_dir` deleted more than `max_age` before `now`
+pub fn purge_trash_older_than(trash_dir: &Path, max_age: Duration, now: u64) -> Result<usize> {
+    let cutoff = now.saturating_sub(max_age.as_secs());
+    let mut purged = 0;
+
+    for entry in list_trash_in(trash_dir)? {
+        if entry.deleted_at < cutoff {
+            purge_trash_entry(&entry)?;
+            purged += 1;
+        }
+    }
+
+    Ok(purged)
+}
+
+/// Pick where a restored file should go
+///
+/// The original path if it's free, otherwise "stem (restored).ext",
+/// "stem (restored 2).ext", ... in the same folder.
+pub fn restore_target(original: &Path) -> PathBuf {
+    if !original.exists() {
+        return original.to_path_buf();
+    }
+
+    let stem = original
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = original
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    let mut counter = 1;
+    loop {
+        let name = if counter == 1 {
+            format!("{} (restored){}", stem, extension)
+        } else {
+            format!("{} (restored {}){}", stem, counter, extension)
+        };
+        let candidate = original.with_file_name(name);
+        if !candidate.exists() {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+/// Path of the metadata file that belongs to a trashed file
+fn trash_info_path(stored_path: &Path) -> PathBuf {
+    let mut name = stored_path.as_os_str().to_owned();
+    name.push(".");
+    name.push(TRASH_INFO_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// Move a file, even across filesystems
+///
+/// fs::rename is atomic and instant, but only works within one filesystem
+/// (the data dir may be on a different disk than the user's documents).
+/// When it fails we fall back to copying and then removing the source.
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    fs::copy(from, to).context(format!(
+        "Failed to move {} to {}",
+        from.display(),
+        to.display()
+    ))?;
+    fs::remove_file(from).context(format!("Failed to remove {}", from.display()))?;
+    Ok(())
+}
+
+/// Current time in seconds since the Unix epoch
+pub fn unix_now() -> u64 {
+    // duration_since only fails if the system clock is set before 1970
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// ============================================================================
+// SNAPSHOTS - VERSION HISTORY
+// ============================================================================
+//
+// Autosave keeps one file per document, so a deleted chapter is gone once
+// the next autosave fires. Snapshots are timestamped copies that pile up
+// instead, up to a per-document limit:
+//
+//   <autosave dir>/snapshots/<stem>-<path hash>/<unix seconds>.bks
+//
+// The path hash keeps "draft.bks" in two different folders apart. Untitled
+// documents share "<autosave dir>/snapshots/untitled/".
+
+/// Version history preferences (Edit → Preferences…)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnapshotSettings {
+    /// Take a snapshot after every successful manual save
+    pub on_save: bool,
+    /// Minutes between periodic snapshots while editing; 0 = never
+    pub interval_mins: u64,
+    /// Snapshots kept per document; older ones go to the trash
+    pub keep: usize,
+}
+
+impl Default for SnapshotSettings {
+    fn default() -> Self {
+        Self {
+            on_save: true,
+            interval_mins: 10,
+            keep: 20,
+        }
+    }
+}
+
+/// Largest interval and count offered in Preferences
+pub const MAX_SNAPSHOT_INTERVAL_MINS: u64 = 24 * 60;
+pub const MAX_SNAPSHOTS_KEPT: usize = 500;
+
+/// One stored snapshot, or a save backup shown alongside them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub path: PathBuf,
+    /// When it was taken (seconds since the Unix epoch, from the file name;
+    /// for a backup, when the version it holds was saved)
+    pub created: u64,
+    /// A save backup (see list_backups) rather than a snapshot
+    pub backup: bool,
+}
+
+/// Folder holding the snapshots of `document` under `snapshots_root`
+///
+/// "/novels/a/draft.bks" → "<root>/draft-<16 hex digits>", where the digits
+/// hash the whole path, so same-named files in different folders never
+/// share (or prune) each other's history.
+pub fn snapshot_dir_in(snapshots_root: &Path, document: Option<&Path>) -> PathBuf {
+    let Some(document) = document else {
+        return snapshots_root.join("untitled");
+    };
+    let stem = document
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("document"));
+    let mut hasher = DefaultHasher::new();
+    document.hash(&mut hasher);
+    snapshots_root.join(format!("{}-{:016x}", stem, hasher.finish()))
+}
+
+/// Folder holding the snapshots of `document` in the autosave directory
+pub fn snapshot_dir(document: Option<&Path>) -> Result<PathBuf> {
+    Ok(snapshot_dir_in(
+        &get_autosave_dir()?.join("snapshots"),
+        document,
+    ))
+}
+
+/// Write `content` as a snapshot taken at `now` into `dir`
+///
+/// Two snapshots in the same second overwrite each other - the later one
+/// is the more useful of the two.
+pub fn write_snapshot_in(dir: &Path, content: &str, now: u64) -> Result<PathBuf> {
+    let path = dir.join(format!("{}.bks", now));
+    save_text_file(&path, content, BackupSettings::OFF)?;
+    Ok(path)
+}
+
+/// List the snapshots in `dir`, newest first
+///
+/// A folder that doesn't exist yet simply has no snapshots. Files whose
+/// names aren't `<number>.bks` are ignored.
+pub fn list_snapshots_in(dir: &Path) -> Result<Vec<Snapshot>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(dir).context(format!("Failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("bks") {
+            continue;
+        }
+        let created = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.parse::<u64>().ok());
+        if let Some(created) = created {
+            snapshots.push(Snapshot {
+                path,
+                created,
+                backup: false,
+            });
+        }
+    }
+
+    // Newest first: Reverse flips the key's ordering
+    snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.created));
+    Ok(snapshots)
+}
+
+/// Move all but the newest `keep` snapshots in `dir` to `trash_dir`
+///
+/// Returns how many were moved. Like old backups, pruned snapshots go to
+/// the trash, so one pruned too eagerly can still be restored.
+pub fn prune_snapshots_in(dir: &Path, keep: usize, trash_dir: &Path) -> Result<usize> {
+    let snapshots = list_snapshots_in(dir)?;
+    let mut removed = 0;
+    for snapshot in snapshots.iter().skip(keep) {
+        move_to_trash(trash_dir, &snapshot.path, unix_now()).context(format!(
+            "Failed to remove old snapshot: {}",
+            snapshot.path.display()
+        ))?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Snapshot `content` for `document` now and prune down to `keep`
+pub fn take_snapshot(document: Option<&Path>, content: &str, keep: usize) -> Result<PathBuf> {
+    let dir = snapshot_dir(document)?;
+    let path = write_snapshot_in(&dir, content, unix_now())?;
+    prune_snapshots_in(&dir, keep.max(1), &get_trash_dir()?)?;
+    Ok(path)
+}
+
+// ============================================================================
+// NAMED SNAPSHOTS - MILESTONES
+// ============================================================================
+//
+// Unlike the snapshots above, which are taken on their own and pruned, a
+// named snapshot is taken on purpose ("First draft done") and kept until
+// the user deletes it. They belong to a project and live next to its file,
+// so they move and sync with it:
+//
+//   <project folder>/<project name>.snapshots/
+//       index.json            the list below, oldest first
+//       <unix seconds>.bks    one copy per snapshot
+//
+// The index records which document each copy is of, as the project lists
+// it (relative to the project folder, `/` separators).
+
+/// One named snapshot, as recorded in the index
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NamedSnapshot {
+    pub name: String,
+    /// The document it's a copy of, as listed in the project
+    pub document: String,
+    /// When it was taken (seconds since the Unix epoch)
+    pub created: u64,
+    /// The copy's file name in the snapshot folder
+    pub file: String,
+}
+
+/// The named snapshot folder of the project at `project_file`
+pub fn named_snapshot_dir(project_file: &Path) -> PathBuf {
+    let stem = project_file
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("project"));
+    project_file
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(format!("{}.snapshots", stem))
+}
+
+/// Read the index of the snapshot folder `dir`, oldest first
+///
+/// A folder that doesn't exist yet has no snapshots.
+pub fn list_named_snapshots(dir: &Path) -> Result<Vec<NamedSnapshot>> {
+    let path = dir.join("index.json");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let json = load_text_file(&path)?;
+    serde_json::from_str(&json).context(format!(
+        "Failed to parse snapshot index: {}",
+        path.display()
+    ))
+}
+
+/// Write the index of the snapshot folder `dir`
+fn save_named_snapshots(dir: &Path, snapshots: &[NamedSnapshot]) -> Result<()> {
+    let json =
+        serde_json::to_string_pretty(snapshots).context("Failed to serialize snapshot index")?;
+    save_text_file(dir.join("index.json"), &json, BackupSettings::OFF)
+}
+
+/// Copy `content` (the text of `document`) into `dir` as a snapshot named
+/// `name`, taken at `now`
+///
+/// The copy is written before the index, so the index never lists a file
+/// that isn't there.
+pub fn take_named_snapshot(
+    dir: &Path,
+    document: &str,
+    name: &str,
+    content: &str,
+    now: u64,
+) -> Result<NamedSnapshot> {
+    let mut snapshots = list_named_snapshots(dir)?;
+    // Two in the same second get "<secs>-2.bks" and so on
+    let mut file = format!("{}.bks", now);
+    let mut n = 1;
+    while snapshots.iter().any(|s| s.file == file) || dir.join(&file).exists() {
+        n += 1;
+        file = format!("{}-{}.bks", now, n);
+    }
+    save_text_file(dir.join(&file), content, BackupSettings::OFF)?;
+
+    let snapshot = NamedSnapshot {
+        name: name.to_string(),
+        document: document.to_string(),
+        created: now,
+        file,
+    };
+    snapshots.push(snapshot.clone());
+    save_named_snapshots(dir, &snapshots)?;
+    Ok(snapshot)
+}
+
+/// Read a snapshot's text
+pub fn load_named_snapshot(dir: &Path, snapshot: &NamedSnapshot) -> Result<String> {
+    load_text_file(dir.join(&snapshot.file))
+}
+
+/// Take a snapshot out of the index and move its copy to the trash
+pub fn delete_named_snapshot(dir: &Path, snapshot: &NamedSnapshot) -> Result<()> {
+    let mut snapshots = list_named_snapshots(dir)?;
+    snapshots.retain(|s| s.file != snapshot.file);
+    save_named_snapshots(dir, &snapshots)?;
+    let path = dir.join(&snapshot.file);
+    if path.exists() {
+        trash_file(&path)?;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// AUTOSAVE THREAD FUNCTION
+// ============================================================================
+
+/// Autosave preferences (Edit → Preferences…)
+///
+/// Part of Settings, so they travel with exported profiles.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutosaveSettings {
+    /// false turns autosave off entirely
+    pub enabled: bool,
+    /// Seconds between autosaves (see interval())
+    pub interval_secs: u64,
+}
+
+impl Default for AutosaveSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 60,
+        }
+    }
+}
+
+/// Shortest and longest intervals offered in Preferences
+pub const MIN_AUTOSAVE_INTERVAL_SECS: u64 = 10;
+pub const MAX_AUTOSAVE_INTERVAL_SECS: u64 = 10 * 60;
+
+impl AutosaveSettings {
+    /// Time between autosaves
+    ///
+    /// A hand-edited settings file can hold any number, so it's clamped to
+    /// the range Preferences offers: a 0 would write every second, and a
+    /// huge one would never save at all.
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(
+            self.interval_secs
+                .clamp(MIN_AUTOSAVE_INTERVAL_SECS, MAX_AUTOSAVE_INTERVAL_SECS),
+        )
+    }
+}
+
+/// What the autosave thread reports back to the GUI, over a channel
+#[derive(Debug, Clone)]
+pub enum AutosaveEvent {
+    /// The document was written to `path` at the given local time
+    Saved {
+        at: chrono::DateTime<chrono::Local>,
+        path: PathBuf,
+    },
+    /// An Autosave Now had nothing to write; the message says why
+    Skipped(String),
+    /// An autosave or periodic snapshot failed; the message is ready to
+    /// show to the user
+    Failed(String),
+}
+
+/// Requests from the GUI to the autosave thread
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AutosaveCommand {
+    /// The editor's text has changed: this is what to save from now on
+    ///
+    /// The text and its file travel together, so the thread can never
+    /// write one document's text to another's autosave file.
+    Document {
+        text: String,
+        /// The document being edited (None = unsaved new document)
+        file: Option<PathBuf>,
+    },
+    /// Run an autosave right away (File → Autosave Now), even if the
+    /// interval isn't up, autosave is switched off, or nothing changed
+    SaveNow,
+}
+
+/// Settings the GUI shares with the autosave thread
+///
+/// The GUI writes them whenever they might have changed; the thread reads
+/// them before every save, so a new interval takes effect without
+/// restarting anything. The text itself arrives as AutosaveCommands, and
+/// results go the other way as AutosaveEvents.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AutosaveState {
+    pub settings: AutosaveSettings,
+    /// Periodic snapshots are taken by the same thread
+    pub snapshots: SnapshotSettings,
+}
+
+/// Where the autosave copy of a document goes
+///
+/// - An open file autosaves next to itself: `myscript.bks` →
+///   `myscript.bks.autosave`, so two documents never share an autosave
+/// - A new, never-saved document uses `autosave.bks` in the autosave
+///   directory
+pub fn autosave_path_for(current_file: Option<&Path>) -> Result<PathBuf> {
+    match current_file {
+        Some(path) => {
+            let mut name = path.as_os_str().to_os_string();
+            name.push(".autosave");
+            Ok(PathBuf::from(name))
+        }
+        None => Ok(get_autosave_dir()?.join("autosave.bks")),
+    }
+}
+
+/// Fingerprint of the document text, used to spot unchanged content
+///
+/// DefaultHasher is not cryptographic, but we only need "did it change?",
+/// not protection against someone crafting a collision on purpose.
+pub fn content_hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Decide whether an autosave is worth writing
+///
+/// - `last_saved_hash`: content_hash of what the previous autosave wrote to
+///   the same file (None if nothing has been written there yet)
+/// - `content`: the text as it is now
+///
+/// Returns false when the text is identical to the last autosave (nothing
+/// to do), or blank - an empty editor right after launch must never
+/// overwrite a real draft with a zero-byte file.
+pub fn should_autosave(last_saved_hash: Option<u64>, content: &str) -> bool {
+    if content.trim().is_empty() {
+        return false;
+    }
+    last_saved_hash != Some(content_hash(content))
+}
+
+/// Background thread that periodically saves the document
+///
+/// This function runs in a separate thread and loops until the GUI goes
+/// away. It wakes up once a second to check the settings in `state`, and
+/// saves whenever the configured interval has passed since the previous
+/// save - or straight away when a SaveNow command arrives.
+///
+/// PARAMETERS:
+/// - `storage`: where the autosave copy is written (DiskStorage in the app;
+///   snapshots always go to the data directory on disk)
+/// - `state`: Arc<Mutex<AutosaveState>> with the settings
+///   Arc allows multiple threads to own the same data
+///   Mutex ensures only one thread accesses it at a time
+/// - `commands`: the document's text and path whenever they change, and
+///   requests from the GUI (Autosave Now)
+/// - `events`: where every save, skip and failure is reported, so the GUI
+///   can show it - a GUI app's stdout is somewhere nobody looks
+///
+/// WHY WAKE UP EVERY SECOND?
+/// If we slept for the whole interval, changing it from 10 minutes to 30
+/// seconds would only take effect after the old 10 minutes were up.
+/// Short naps let us notice the change almost immediately. The nap is a
+/// recv_timeout() on `commands` rather than thread::sleep, so a command
+/// cuts it short.
+///
+/// THREADING SAFETY:
+/// The text is never shared: the thread owns the copy it was last sent.
+/// Only the small settings struct sits behind a Mutex, and each lock is
+/// held just long enough to copy it.
+///
+/// SHUTDOWN:
+/// When the App is dropped, its ends of both channels go with it; the next
+/// recv_timeout() or send() fails and the loop ends.
+pub fn autosave_thread(
+    storage: Arc<dyn Storage>,
+    state: Arc<Mutex<AutosaveState>>,
+    commands: Receiver<AutosaveCommand>,
+    events: Sender<AutosaveEvent>,
+) {
+    // Instant is a monotonic clock - unaffected by the user changing the
+    // system time, which makes it the right tool for measuring intervals
+    let mut last_save = Instant::now();
+
+    // What we last wrote, and where. Tracking the path too means opening
+    // another file (or Save As) starts fresh instead of comparing against
+    // a different document's autosave.
+    let mut last_written: Option<(PathBuf, u64)> = None;
+
+    // Periodic snapshots keep their own clock and their own "unchanged?"
+    // check, so an idle document doesn't fill its history with copies
+    let mut last_snapshot = Instant::now();
+    let mut last_snapshot_hash: Option<u64> = None;
+
+    // The latest text the GUI sent, and the file it belongs to. This is
+    // the thread's own copy - nothing else can change it under us.
+    let mut content = String::new();
+    let mut current_file: Option<PathBuf> = None;
+
+    loop {
+        // recv_timeout pauses this thread without consuming CPU, until a
+        // command arrives or a second has passed
+        let forced = match commands.recv_timeout(Duration::from_secs(1)) {
+            Ok(AutosaveCommand::Document { text, file }) => {
+                content = text;
+                current_file = file;
+                false
+            }
+            Ok(AutosaveCommand::SaveNow) => true,
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+
+        // ----------------------------------------------------------------
+        // STEP 1: Read the current settings
+        // ----------------------------------------------------------------
+        let AutosaveState {
+            settings,
+            snapshots,
+        } = *state.lock().unwrap();
+
+        // Snapshots run on their own schedule, even with autosave off
+        if snapshots.interval_mins > 0
+            && last_snapshot.elapsed() >= Duration::from_secs(snapshots.interval_mins * 60)
+        {
+            last_snapshot = Instant::now();
+            if should_autosave(last_snapshot_hash, &content) {
+                match take_snapshot(current_file.as_deref(), &content, snapshots.keep) {
+                    Ok(_) => last_snapshot_hash = Some(content_hash(&content)),
+                    Err(e) => {
+                        let event = AutosaveEvent::Failed(format!("Snapshot failed: {}", e));
+                        if events.send(event).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+
+        if !forced {
+            if !settings.enabled {
+                // Restart the clock so re-enabling waits a full interval
+                // instead of saving immediately
+                last_save = Instant::now();
+                continue;
+            }
+            if last_save.elapsed() < settings.interval() {
+                continue;
+            }
+        }
+        last_save = Instant::now();
+
+        // ----------------------------------------------------------------
+        // STEP 2: Work out where the autosave goes
+        // ----------------------------------------------------------------
+        let autosave_path = match autosave_path_for(current_file.as_deref()) {
+            Ok(path) => path,
+            Err(e) => {
+                let event = AutosaveEvent::Failed(format!("Autosave error: {}", e));
+                if events.send(event).is_err() {
+                    return;
+                }
+                // `continue` jumps back to the start of the loop
+                continue;
+            }
+        };
+
+        // ----------------------------------------------------------------
+        // STEP 3: Skip the write if nothing changed since last time
+        // ----------------------------------------------------------------
+        // Autosave Now writes even unchanged text (the user asked for it),
+        // but never a blank document
+        let last_hash = last_written
+            .as_ref()
+            .filter(|(path, _)| *path == autosave_path && !forced)
+            .map(|(_, hash)| *hash);
+        if !should_autosave(last_hash, &content) {
+            if forced {
+                let event = AutosaveEvent::Skipped(String::from(
+                    "Nothing to autosave - the document is empty",
+                ));
+                if events.send(event).is_err() {
+                    return;
+                }
+            }
+            continue;
+        }
+
+        // ----------------------------------------------------------------
+        // STEP 4: Save to disk and report back to the GUI
+        // ----------------------------------------------------------------
+        let event = match storage.write(&autosave_path, content.as_bytes(), BackupSettings::OFF) {
+            Ok(_) => {
+                last_written = Some((autosave_path.clone(), content_hash(&content)));
+                AutosaveEvent::Saved {
+                    at: chrono::Local::now(),
+                    path: autosave_path,
+                }
+            }
+            Err(e) => AutosaveEvent::Failed(format!("Autosave failed: {}", e)),
+        };
+        if events.send(event).is_err() {
+            return;
+        }
+
+        // Loop continues - check again in a second
+    }
+}
+
+// ============================================================================
+// SESSION FILE - CRASH RECOVERY
+// ============================================================================
+//
+// While the app runs, `<data dir>/session.json` records when this session
+// started and which document is open. A graceful exit sets `clean_exit`.
+//
+// If the next launch finds a session WITHOUT clean_exit, the app crashed
+// (or was killed) - any autosave written during that session may hold work
+// that never made it into a real save, so we offer to restore it.
+//
+// The same record lets the next launch reopen the documents where the user
+// left off: the open tabs (and project) are written as they change, the
+// cursor and scroll positions on exit.
+
+/// What we know about the current (or previous) run of the app
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionInfo {
+    /// When the session started (seconds since the Unix epoch)
+    pub started_at: u64,
+    /// Set on graceful exit; still false after a crash
+    pub clean_exit: bool,
+    /// The document that was open, so a restored draft can get its path back
+    pub document: Option<PathBuf>,
+    /// Editor cursor position (char index) when the app closed
+    pub cursor: Option<usize>,
+    /// Editor vertical scroll offset (points) when the app closed
+    pub scroll_offset: Option<f32>,
+    /// Every open document that has a file, in tab order (untitled ones
+    /// can't be reopened)
+    pub tabs: Vec<SessionTab>,
+    /// Which of `tabs` is `document`, the one in front (None if that one
+    /// is untitled)
+    pub active_tab: Option<usize>,
+    /// The project that was open
+    pub project: Option<PathBuf>,
+    /// Bookmarked lines of every document that has any, open or not
+    pub bookmarks: BTreeMap<PathBuf, Vec<Bookmark>>,
+}
+
+/// One open document in the session record
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionTab {
+    pub path: PathBuf,
+    /// Caret position (char index) and scroll offset (points), as of the
+    /// last exit
+    pub cursor: usize,
+    pub scroll: f32,
+}
+
+/// An autosave that may contain unsaved work from a crashed session
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoveryCandidate {
+    /// The autosave file itself
+    pub autosave_path: PathBuf,
+    /// The document it belongs to (None for an untitled document)
+    pub document_path: Option<PathBuf>,
+    /// When the autosave was written (seconds since the Unix epoch)
+    pub modified: u64,
+}
+
+/// Location of the session file
+pub fn get_session_path() -> Result<PathBuf> {
+    Ok(get_data_dir()?.join("session.json"))
+}
+
+/// Read the session file
+///
+/// Returns Ok(None) when there is no session file (first launch ever).
+pub fn load_session(path: &Path) -> Result<Option<SessionInfo>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let json = load_text_file(path)?;
+    let session = serde_json::from_str(&json)
+        .context(format!("Failed to parse session file: {}", path.display()))?;
+    Ok(Some(session))
+}
+
+/// Write the session file
+pub fn save_session(path: &Path, session: &SessionInfo) -> Result<()> {
+    let json = serde_json::to_string_pretty(session).context("Failed to serialize session")?;
+    save_text_file(path, &json, BackupSettings::OFF)
+}
+
+/// Find autosaves worth offering after the given session
+///
+/// Returns nothing if the session ended cleanly. Otherwise each possible
+/// autosave location is checked - the open document's sibling autosave and
+/// the shared untitled one in `untitled_dir` - and kept only if:
+/// 1. it was written during the crashed session (not left over from an
+///    older one), and
+/// 2. it is newer than the document itself (otherwise a real save already
+///    captured everything it holds)
+///
+/// The newest candidate comes first.
+pub fn find_recovery_candidates(
+    session: &SessionInfo,
+    untitled_dir: &Path,
+) -> Vec<RecoveryCandidate> {
+    if session.clean_exit {
+        return Vec::new();
+    }
+
+    let mut locations = vec![(untitled_dir.join("autosave.bks"), None)];
+    if let Some(document) = &session.document {
+        let mut name = document.as_os_str().to_os_string();
+        name.push(".autosave");
+        locations.push((PathBuf::from(name), Some(document.clone())));
+    }
+
+    let mut candidates: Vec<RecoveryCandidate> = locations
+        .into_iter()
+        .filter_map(|(autosave_path, document_path)| {
+            let modified = modified_secs(&autosave_path)?;
+            if modified < session.started_at {
+                return None;
+            }
+            // An unreadable or missing document can't be newer than us
+            let document_modified = document_path.as_deref().and_then(modified_secs);
+            if document_modified.is_some_and(|doc| doc >= modified) {
+                return None;
+            }
+            Some(RecoveryCandidate {
+                autosave_path,
+                document_path,
+                modified,
+            })
+        })
+        .collect();
+
+    candidates.sort_by_key(|c| std::cmp::Reverse(c.modified));
+    candidates
+}
+
+/// A file's modification time in seconds since the Unix epoch
+/// (None if the file doesn't exist or the time can't be read)
+fn modified_secs(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    modified
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs())
+}
+
+// ============================================================================
+// SETTINGS FILE
+// ============================================================================
+//
+// Preferences live in `<data dir>/settings.json`. They are read once at
+// startup (before the window opens, so its size and position can be
+// restored) and written when the app closes or the Preferences window does.
+
+/// Location of the settings file
+pub fn get_settings_path() -> Result<PathBuf> {
+    Ok(get_data_dir()?.join("settings.json"))
+}
+
+/// Location of the user's own theme (View → Theme → Custom, see theme.rs)
+pub fn get_theme_path() -> Result<PathBuf> {
+    Ok(get_data_dir()?.join("theme.json"))
+}
+
+/// Location of the snippets file (Insert menu, see snippets.rs)
+pub fn get_snippets_path() -> Result<PathBuf> {
+    Ok(get_data_dir()?.join("snippets.toml"))
+}
+
+/// The file naming the running copy of the app (see instance.rs)
+pub fn get_instance_lock_path() -> Result<PathBuf> {
+    Ok(get_data_dir()?.join("instance.lock"))
+}
+
+/// Where WordNet's data files are looked for when no other folder has
+/// been chosen (see thesaurus.rs)
+pub fn get_wordnet_dir() -> Result<PathBuf> {
+    Ok(get_data_dir()?.join("wordnet"))
+}
+
+/// Read the settings file
+///
+/// A missing file is not an error - it just means defaults. Fields missing
+/// from the file get their defaults too, and fields this version doesn't
+/// know about (written by a newer version) are ignored.
+pub fn load_settings(path: &Path) -> Result<Settings> {
+    if !path.exists() {
+        return Ok(Settings::default());
+    }
+    let json = load_text_file(path)?;
+    serde_json::from_str(&json)
+        .context(format!("Failed to parse settings file: {}", path.display()))
+}
+
+/// Write the settings file
+pub fn save_settings(path: &Path, settings: &Settings) -> Result<()> {
+    let json = serde_json::to_string_pretty(settings).context("Failed to serialize settings")?;
+    save_text_file(path, &json, BackupSettings::OFF)
+}
+
+// ============================================================================
+// WRITING PROGRESS HISTORY
+// ============================================================================
+//
+// `<data dir>/progress.json` holds one record per day: how many words were
+// written that day (see goals.rs). Recording a day that's already in the
+// file replaces its total, so the file never holds more than one record per
+// date and a later session on the same day just raises the number.
+
+/// Words written on one day
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DailyProgress {
+    pub date: NaiveDate,
+    /// Net words written (negative if more were deleted than written)
+    pub words: i64,
+}
+
+/// Location of the progress history file
+pub fn get_progress_path() -> Result<PathBuf> {
+    Ok(get_data_dir()?.join("progress.json"))
+}
+
+/// Read the progress history, oldest day first
+///
+/// A missing file means no history yet.
+pub fn load_progress(path: &Path) -> Result<Vec<DailyProgress>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let json = load_text_file(path)?;
+    serde_json::from_str(&json).context(format!(
+        "Failed to parse progress history: {}",
+        path.display()
+    ))
+}
+
+/// Set the total for one day, adding the day if it isn't recorded yet
+pub fn record_progress(path: &Path, day: DailyProgress) -> Result<()> {
+    let mut history = load_progress(path)?;
+    match history.iter_mut().find(|d| d.date == day.date) {
+        Some(existing) => existing.words = day.words,
+        None => {
+            history.push(day);
+            history.sort_by_key(|d| d.date);
+        }
+    }
+    let json =
+        serde_json::to_string_pretty(&history).context("Failed to serialize progress history")?;
+    save_text_file(path, &json, BackupSettings::OFF)
+}
+
+/// The `days` days up to and including `today`, newest first
+///
+/// Days with no record are included with 0 words, so a chart of the result
+/// shows the gaps.
+pub fn recent_progress(
+    history: &[DailyProgress],
+    today: NaiveDate,
+    days: u64,
+) -> Vec<DailyProgress> {
+    (0..days)
+        .filter_map(|n| today.checked_sub_days(chrono::Days::new(n)))
+        .map(|date| DailyProgress {
+            date,
+            words: history
+                .iter()
+                .find(|d| d.date == date)
+                .map_or(0, |d| d.words),
+        })
+        .collect()
+}
+
+// ============================================================================
+// SPRINT HISTORY
+// ============================================================================
+//
+// `<data dir>/sprints.json` lists every finished sprint (Tools → Sprint),
+// oldest first, so the Sprint window can show past results to beat. Only
+// the last MAX_SPRINT_RECORDS are kept.
+
+/// Most sprints kept in the history file
+pub const MAX_SPRINT_RECORDS: usize = 200;
+
+/// The result of one sprint
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SprintRecord {
+    /// When it started (local time)
+    pub started: chrono::NaiveDateTime,
+    /// How long it was meant to run
+    pub minutes: u32,
+    /// How long it actually ran (less if stopped early)
+    pub seconds: u64,
+    /// Net words written during it
+    pub words: i64,
+}
+
+/// Location of the sprint history file
+pub fn get_sprints_path() -> Result<PathBuf> {
+    Ok(get_data_dir()?.join("sprints.json"))
+}
+
+/// Read the sprint history, oldest first
+///
+/// A missing file means no sprints yet.
+pub fn load_sprints(path: &Path) -> Result<Vec<SprintRecord>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let json = load_text_file(path)?;
+    serde_json::from_str(&json).context(format!(
+        "Failed to parse sprint history: {}",
+        path.display()
+    ))
+}
+
+/// Add a sprint to the history, dropping the oldest past MAX_SPRINT_RECORDS
+pub fn record_sprint(path: &Path, sprint: SprintRecord) -> Result<()> {
+    let mut history = load_sprints(path)?;
+    history.push(sprint);
+    let excess = history.len().saturating_sub(MAX_SPRINT_RECORDS);
+    history.drain(..excess);
+    let json =
+        serde_json::to_string_pretty(&history).context("Failed to serialize sprint history")?;
+    save_text_file(path, &json, BackupSettings::OFF)
+}
+
+// ============================================================================
+// RECENT FILES
+// ============================================================================
+//
+// `<data dir>/recent.json` lists the documents behind File → Open Recent,
+// most recent first. It's written whenever the list changes (every open
+// and save), not just on exit, so a crash doesn't lose it. It lives apart
+// from settings.json because it's machine-specific history, not a
+// preference, and shouldn't travel in an exported profile.
+
+/// How many documents File → Open Recent remembers
+pub const MAX_RECENT_FILES: usize = 10;
+
+/// Location of the recent files list
+pub fn get_recent_files_path() -> Result<PathBuf> {
+    Ok(get_data_dir()?.join("recent.json"))
+}
+
+/// Read the recent files list, most recent first
+///
+/// A missing file means nothing has been opened yet.
+pub fn load_recent_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let json = load_text_file(path)?;
+    let mut files: Vec<PathBuf> = serde_json::from_str(&json).context(format!(
+        "Failed to parse recent files list: {}",
+        path.display()
+    ))?;
+    // A hand-edited (or older, longer) list still gets the usual limit
+    files.truncate(MAX_RECENT_FILES);
+    Ok(files)
+}
+
+/// Write the recent files list
+pub fn save_recent_files(path: &Path, files: &[PathBuf]) -> Result<()> {
+    let json =
+        serde_json::to_string_pretty(files).context("Failed to serialize recent files list")?;
+    save_text_file(path, &json, BackupSettings::OFF)
+}
+
+/// Move `file` to the top of the list (adding it if it's new), dropping
+/// the oldest entry past MAX_RECENT_FILES
+pub fn push_recent_file(files: &mut Vec<PathBuf>, file: &Path) {
+    files.retain(|existing| existing != file);
+    files.insert(0, file.to_path_buf());
+    files.truncate(MAX_RECENT_FILES);
+}
+
+// ============================================================================
+// USER TEMPLATES
+// ============================================================================
+//
+// `<data dir>/templates.json` holds the templates saved with File → New
+// from Template → Save as Template…, in the order they were added. The
+// built-in ones (templates::built_in) aren't in it.
+
+/// Location of the user's templates
+pub fn get_templates_path() -> Result<PathBuf> {
+    Ok(get_data_dir()?.join("templates.json"))
+}
+
+/// Read the user's templates
+///
+/// A missing file means none yet.
+pub fn load_templates(path: &Path) -> Result<Vec<Template>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let json = load_text_file(path)?;
+    serde_json::from_str(&json).context(format!("Failed to parse templates: {}", path.display()))
+}
+
+/// Write the user's templates
+pub fn save_templates(path: &Path, templates: &[Template]) -> Result<()> {
+    let json = serde_json::to_string_pretty(templates).context("Failed to serialize templates")?;
+    save_text_file(path, &json, BackupSettings::OFF)
+}
+
+// ============================================================================
+// HOW THREADING WORKS IN THIS MODULE
+// ============================================================================
+//
+// THREAD RELATIONSHIPS:
+//
+// Main Thread (GUI):                Autosave Thread:
+//   |                                    |
+//   | Creates the channels               |
+//   | Spawns thread                      |
+//   |                                    |
+//   | Editing text...                    | Sleep until the interval
+//   |                                    | (from AutosaveState) is up...
+//   | Text changed, 1 s since last copy  |
+//   |-- AutosaveCommand::Document --->---| Keep the copy
+//   | Editing text...                    |
+//   | File → Autosave Now                |
+//   |-- AutosaveCommand::Document --->---| Keep the copy
+//   |-- AutosaveCommand::SaveNow ---->---| Wake up!
+//   | Drawing UI...                      | Save to disk...
+//   |---<---- AutosaveEvent::Saved ------|
+//   | Status bar: "Autosaved at ..."     | Sleep again...
+//
+// NO SHARED TEXT:
+// The GUI owns the document's String outright and sends the thread a
+// copy, instead of both sides locking one shared String. A megabyte-sized
+// manuscript used to be cloned under a lock the GUI needed every frame;
+// now the GUI never waits, and copies at most once a second while the
+// text is changing (never while it's idle).
+//
+// CHANNELS CARRY MESSAGES:
+// Commands and results travel over mpsc channels instead of shared slots,
+// so nothing is lost when two events happen between frames, and neither
+// side has to poll a mutex to find out something happened.
+//
+// ============================================================================
+
+// ============================================================================
+// ERROR HANDLING PHILOSOPHY
+// ============================================================================
+//
+// This module uses the `anyhow` crate for error handling:
+//
+// 1. Return Result<T, anyhow::Error> from functions that can fail
+// 2. Use the ? operator to propagate errors up the call stack
+// 3. Use .context() to add helpful error messages
+// 4. The caller can decide how to handle errors (show to user, log, etc.)
+//
+// Example error chain:
+//   Error: Failed to write file: /path/to/file.txt
+//   Caused by: Permission denied (os error 13)
+//
+// This gives users actionable information about what went wrong.
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty folder of the test's own under the system temp dir
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("bookscript-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn same_names_deleted_in_the_same_second_get_their_own_slots() {
+        let dir = scratch_dir("trash-collisions");
+        let trash = dir.join("trash");
+        let mut stored = Vec::new();
+        for project in ["a", "b", "c"] {
+            let file = dir.join(project).join("autosave.bks");
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(&file, project).unwrap();
+            let entry = move_to_trash(&trash, &file, 1000).unwrap();
+            assert!(!file.exists());
+            stored.push(entry.stored_path.file_name().unwrap().to_owned());
+        }
+        assert_eq!(
+            stored,
+            [
+                "1000-autosave.bks",
+                "1000-1-autosave.bks",
+                "1000-2-autosave.bks"
+            ]
+        );
+
+        let entries = list_trash_in(&trash).unwrap();
+        assert_eq!(entries.len(), 3);
+        for entry in &entries {
+            let project = entry.original_path.parent().unwrap().file_name().unwrap();
+            assert_eq!(
+                fs::read_to_string(&entry.stored_path).unwrap(),
+                project.to_string_lossy()
+            );
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restoring_over_a_recreated_file_keeps_both() {
+        let dir = scratch_dir("trash-restore");
+        let trash = dir.join("trash");
+        let file = dir.join("chapter.bks");
+        fs::write(&file, "old").unwrap();
+        let first = move_to_trash(&trash, &file, 1).unwrap();
+        fs::write(&file, "older").unwrap();
+        let second = move_to_trash(&trash, &file, 2).unwrap();
+        fs::write(&file, "new").unwrap();
+
+        let restored = restore_from_trash(&first).unwrap();
+        assert_eq!(restored, file.with_file_name("chapter (restored).bks"));
+        assert_eq!(fs::read_to_string(&restored).unwrap(), "old");
+        let restored = restore_from_trash(&second).unwrap();
+        assert_eq!(restored, file.with_file_name("chapter (restored 2).bks"));
+        assert_eq!(fs::read_to_string(&restored).unwrap(), "older");
+        assert_eq!(fs::read_to_string(&file).unwrap(), "new");
+        assert!(list_trash_in(&trash).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restoring_recreates_a_removed_folder() {
+        let dir = scratch_dir("trash-folder");
+        let file = dir.join("drafts").join("one.bks");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(&file, "text").unwrap();
+        let entry = move_to_trash(&dir.join("trash"), &file, 1).unwrap();
+        fs::remove_dir(file.parent().unwrap()).unwrap();
+
+        assert_eq!(restore_from_trash(&entry).unwrap(), entry.original_path);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "text");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_entries_past_the_retention_are_purged() {
+        let dir = scratch_dir("trash-purge");
+        let trash = dir.join("trash");
+        for (name, deleted_at) in [("old.bks", 100), ("new.bks", 900)] {
+            fs::write(dir.join(name), name).unwrap();
+            move_to_trash(&trash, &dir.join(name), deleted_at).unwrap();
+        }
+        let purged = purge_trash_older_than(&trash, Duration::from_secs(500), 1000).unwrap();
+        assert_eq!(purged, 1);
+        let left = list_trash_in(&trash).unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].deleted_at, 900);
+        // The purged file's metadata went with it
+        assert_eq!(fs::read_dir(&trash).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn autosave_only_writes_changed_non_blank_text() {
+        // Nothing written yet
+        assert!(should_autosave(None, "Draft"));
+        // Unchanged since the last autosave
+        assert!(!should_autosave(Some(content_hash("Draft")), "Draft"));
+        // Changed, even by a single character
+        assert!(should_autosave(Some(content_hash("Draft")), "Draft."));
+        // Blank text never overwrites a draft, whatever was saved before
+        assert!(!should_autosave(None, ""));
+        assert!(!should_autosave(Some(content_hash("Draft")), "  \n\t"));
+    }
+
+    #[test]
+    fn content_hash_tells_texts_apart() {
+        assert_eq!(content_hash("same"), content_hash("same"));
+        assert_ne!(content_hash("same"), content_hash("same "));
+        assert_ne!(content_hash(""), content_hash("\n"));
+    }
+
+    #[test]
+    fn saving_replaces_the_file_and_leaves_no_temp_file() {
+        let dir = scratch_dir("save-replace");
+        let file = dir.join("draft.bks");
+        fs::write(&file, "old").unwrap();
+        save_text_file(&file, "new", BackupSettings::OFF).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "new");
+        assert!(!temp_path_for(&file).exists());
+        assert!(!backup_dir(&file).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_first_save_creates_folders_and_makes_no_backup() {
+        let dir = scratch_dir("save-first");
+        let file = dir.join("new").join("folder").join("draft.bks");
+        save_text_file(&file, "text", BackupSettings::default()).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "text");
+        assert_eq!(fs::read_dir(file.parent().unwrap()).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn saving_backs_up_the_old_version_under_its_own_time() {
+        let dir = scratch_dir("save-backup");
+        let file = dir.join("draft.bks");
+        write_at(&file, "v1", 1_700_000_000);
+        save_text_file(&file, "v2", BackupSettings::default()).unwrap();
+
+        assert_eq!(fs::read_to_string(&file).unwrap(), "v2");
+        let backups = list_backups(&file);
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backups[0].created, 1_700_000_000);
+        assert!(backups[0].backup);
+        assert_eq!(
+            backups[0].path,
+            backup_path(&file, UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert_eq!(fs::read_to_string(&backups[0].path).unwrap(), "v1");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn backup_names_carry_the_stem_time_and_extension() {
+        let saved = chrono::NaiveDate::from_ymd_opt(2026, 10, 16)
+            .unwrap()
+            .and_hms_opt(15, 30, 0)
+            .unwrap()
+            .and_local_timezone(chrono::Local)
+            .unwrap();
+        let saved = SystemTime::from(saved);
+        assert_eq!(
+            backup_path(Path::new("/novels/draft.bks"), saved),
+            Path::new("/novels/backups/draft-20261016-153000.bks")
+        );
+        assert_eq!(
+            backup_path(Path::new("/novels/notes"), saved),
+            Path::new("/novels/backups/notes-20261016-153000")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saving_keeps_the_file_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch_dir("save-mode");
+        let file = dir.join("draft.bks");
+        fs::write(&file, "old").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o600)).unwrap();
+
+        DiskStorage
+            .write(&file, b"new", BackupSettings::OFF)
+            .unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "new");
+        let mode = fs::metadata(&file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Backups of `file` saved at each of `times`, oldest first
+    fn make_backups(file: &Path, times: &[u64]) {
+        fs::create_dir_all(backup_dir(file)).unwrap();
+        for (n, &secs) in times.iter().enumerate() {
+            let saved = UNIX_EPOCH + Duration::from_secs(secs);
+            fs::write(backup_path(file, saved), format!("v{}", n + 1)).unwrap();
+        }
+    }
+
+    /// What's in each of `file`'s backups, newest first
+    fn backup_texts(file: &Path) -> Vec<String> {
+        list_backups(file)
+            .iter()
+            .map(|backup| fs::read_to_string(&backup.path).unwrap())
+            .collect()
+    }
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    #[test]
+    fn keeping_a_count_trashes_the_surplus() {
+        let dir = scratch_dir("backup-count");
+        let trash = dir.join("trash");
+        let file = dir.join("draft.bks");
+        make_backups(&file, &[1_700_000_000, 1_700_000_100, 1_700_000_200]);
+        write_at(&file, "v4", 1_700_000_300);
+
+        let settings = BackupSettings {
+            count: 2,
+            ..BackupSettings::default()
+        };
+        make_backup(&file, &settings, 1_700_000_400, || Ok(trash.clone())).unwrap();
+        assert_eq!(backup_texts(&file), ["v4", "v3"]);
+        let mut trashed: Vec<String> = list_trash_in(&trash)
+            .unwrap()
+            .iter()
+            .map(|entry| fs::read_to_string(&entry.stored_path).unwrap())
+            .collect();
+        trashed.sort();
+        assert_eq!(trashed, ["v1", "v2"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeping_days_trashes_old_backups_but_never_the_newest() {
+        let dir = scratch_dir("backup-days");
+        let trash = dir.join("trash");
+        let file = dir.join("draft.bks");
+        let now = 1_700_000_000 + 100 * DAY;
+        make_backups(&file, &[1_700_000_000, now - 10 * DAY, now - 2 * DAY]);
+
+        let settings = BackupSettings {
+            retention: BackupRetention::Days,
+            days: 7,
+            ..BackupSettings::default()
+        };
+        // The document itself was last saved long ago
+        write_at(&file, "v4", now - 50 * DAY);
+        make_backup(&file, &settings, now, || Ok(trash.clone())).unwrap();
+        assert_eq!(backup_texts(&file), ["v3"]);
+        assert_eq!(list_trash_in(&trash).unwrap().len(), 3);
+
+        // Left alone for months, the newest backup is still kept
+        assert_eq!(
+            expired_backups(&list_backups(&file), &settings, now + 365 * DAY),
+            Vec::<PathBuf>::new()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn turning_backups_off_trashes_the_old_ones() {
+        let dir = scratch_dir("save-backups-off");
+        let trash = dir.join("trash");
+        let file = dir.join("draft.bks");
+        make_backups(&file, &[1_700_000_000]);
+        write_at(&file, "v2", 1_700_000_100);
+        make_backup(&file, &BackupSettings::OFF, 1_700_000_200, || {
+            Ok(trash.clone())
+        })
+        .unwrap();
+        assert_eq!(dir_listing(&backup_dir(&file)), Vec::<String>::new());
+        assert_eq!(list_trash_in(&trash).unwrap().len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn no_backups_means_no_trash() {
+        let dir = scratch_dir("save-no-backups");
+        let file = dir.join("draft.bks");
+        fs::write(&file, "v1").unwrap();
+        make_backup(&file, &BackupSettings::OFF, unix_now(), || {
+            panic!("nothing to trash")
+        })
+        .unwrap();
+        assert_eq!(dir_listing(&dir), ["draft.bks"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn list_backups_skips_other_documents_and_stray_files() {
+        let dir = scratch_dir("backup-list");
+        let file = dir.join("draft.bks");
+        make_backups(&file, &[1_700_000_000, 1_700_000_100]);
+        let backups = backup_dir(&file);
+        for stray in [
+            "draft-b-20261016-153000.bks",
+            "draft-20261016-153000.txt",
+            "draft-20261316-153000.bks",
+            "draft-2026.bks",
+            "notes.txt",
+        ] {
+            fs::write(backups.join(stray), "stray").unwrap();
+        }
+        assert_eq!(backup_texts(&file), ["v2", "v1"]);
+        assert_eq!(list_backups(&dir.join("draft-b.bks")).len(), 1);
+        assert!(list_backups(&dir.join("other.bks")).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn an_encrypted_backup_loads_with_the_password() {
+        let dir = scratch_dir("backup-encrypted");
+        let backup = dir.join("draft-20261016-153000.bks");
+        fs::write(&backup, encryption::encrypt(b"secret text", "pw").unwrap()).unwrap();
+        assert_eq!(load_backup(&backup, Some("pw")).unwrap(), "secret text");
+        assert!(load_backup(&backup, Some("wrong")).is_err());
+        assert!(load_backup(&backup, None).is_err());
+
+        let plain = dir.join("draft-20261016-153100.bks");
+        fs::write(&plain, "plain text").unwrap();
+        assert_eq!(load_backup(&plain, None).unwrap(), "plain text");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Write `path` with its modification time set to `secs`
+    fn write_at(path: &Path, content: &str, secs: u64) {
+        fs::write(path, content).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[test]
+    fn session_metadata_round_trips() {
+        let dir = scratch_dir("session-round-trip");
+        let path = dir.join("session.json");
+        let session = SessionInfo {
+            started_at: 1000,
+            document: Some(dir.join("draft.bks")),
+            cursor: Some(12),
+            tabs: vec![SessionTab {
+                path: dir.join("draft.bks"),
+                cursor: 12,
+                scroll: 40.0,
+            }],
+            active_tab: Some(0),
+            ..SessionInfo::default()
+        };
+        save_session(&path, &session).unwrap();
+        assert_eq!(load_session(&path).unwrap(), Some(session));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_clean_exit_has_nothing_to_recover() {
+        let dir = scratch_dir("recovery-clean");
+        write_at(&dir.join("autosave.bks"), "draft", 2000);
+        let session = SessionInfo {
+            started_at: 1000,
+            clean_exit: true,
+            ..SessionInfo::default()
+        };
+        assert!(find_recovery_candidates(&session, &dir).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn autosaves_from_the_crashed_session_newer_than_their_document_are_offered() {
+        let dir = scratch_dir("recovery-candidates");
+        let document = dir.join("draft.bks");
+        write_at(&document, "saved", 1500);
+        write_at(&dir.join("draft.bks.autosave"), "unsaved", 1800);
+        write_at(&dir.join("autosave.bks"), "untitled", 1900);
+        let session = SessionInfo {
+            started_at: 1000,
+            document: Some(document.clone()),
+            ..SessionInfo::default()
+        };
+
+        let candidates = find_recovery_candidates(&session, &dir);
+        assert_eq!(
+            candidates,
+            [
+                RecoveryCandidate {
+                    autosave_path: dir.join("autosave.bks"),
+                    document_path: None,
+                    modified: 1900,
+                },
+                RecoveryCandidate {
+                    autosave_path: dir.join("draft.bks.autosave"),
+                    document_path: Some(document),
+                    modified: 1800,
+                },
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stale_or_already_saved_autosaves_are_not_offered() {
+        let dir = scratch_dir("recovery-stale");
+        let document = dir.join("draft.bks");
+        // Saved after the autosave was written
+        write_at(&dir.join("draft.bks.autosave"), "old", 1500);
+        write_at(&document, "newer", 1600);
+        // Left over from an earlier session
+        write_at(&dir.join("autosave.bks"), "older", 500);
+        let session = SessionInfo {
+            started_at: 1000,
+            document: Some(document),
+            ..SessionInfo::default()
+        };
+        assert!(find_recovery_candidates(&session, &dir).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_missing_settings_file_means_defaults_and_a_corrupt_one_an_error() {
+        let dir = scratch_dir("settings-file");
+        let path = dir.join("settings.json");
+        assert_eq!(load_settings(&path).unwrap(), Settings::default());
+
+        let mut settings = Settings::default();
+        settings.backups.retention = BackupRetention::Days;
+        save_settings(&path, &settings).unwrap();
+        assert_eq!(load_settings(&path).unwrap(), settings);
+
+        // main.rs falls back to the defaults on this error
+        fs::write(&path, "{ not json").unwrap();
+        assert!(load_settings(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_missing_session_file_is_a_first_launch() {
+        let dir = scratch_dir("session-missing");
+        assert_eq!(load_session(&dir.join("session.json")).unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_malformed_session_file_is_an_error() {
+        let dir = scratch_dir("session-malformed");
+        let path = dir.join("session.json");
+        for garbage in ["", "{ \"started_at\": ", "[1, 2, 3]", "{\"tabs\": \"x\"}"] {
+            fs::write(&path, garbage).unwrap();
+            assert!(load_session(&path).is_err(), "{:?}", garbage);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn an_older_session_file_loads_with_defaults() {
+        let dir = scratch_dir("session-older");
+        let path = dir.join("session.json");
+        fs::write(
+            &path,
+            r#"{"started_at": 5, "document": "/a.bks", "theme": "x"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            load_session(&path).unwrap(),
+            Some(SessionInfo {
+                started_at: 5,
+                document: Some(PathBuf::from("/a.bks")),
+                ..SessionInfo::default()
+            })
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pruning_moves_the_oldest_snapshots_to_the_trash() {
+        let dir = scratch_dir("snapshots-prune");
+        let snapshots = dir.join("draft");
+        let trash = dir.join("trash");
+        // Written out of order, so the order has to come from the names
+        for secs in [300, 100, 500, 200, 400] {
+            write_snapshot_in(&snapshots, &format!("at {}", secs), secs).unwrap();
+        }
+        // Not a snapshot: left alone and not counted
+        fs::write(snapshots.join("notes.txt"), "keep me").unwrap();
+
+        assert_eq!(prune_snapshots_in(&snapshots, 3, &trash).unwrap(), 2);
+        let left: Vec<u64> = list_snapshots_in(&snapshots)
+            .unwrap()
+            .iter()
+            .map(|s| s.created)
+            .collect();
+        assert_eq!(left, [500, 400, 300]);
+        assert!(snapshots.join("notes.txt").exists());
+
+        let mut trashed: Vec<String> = list_trash_in(&trash)
+            .unwrap()
+            .iter()
+            .map(|entry| fs::read_to_string(&entry.stored_path).unwrap())
+            .collect();
+        trashed.sort();
+        assert_eq!(trashed, ["at 100", "at 200"]);
+
+        assert_eq!(prune_snapshots_in(&snapshots, 3, &trash).unwrap(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn same_named_documents_in_different_folders_keep_separate_histories() {
+        let root = Path::new("/snapshots");
+        let a = snapshot_dir_in(root, Some(Path::new("/novels/a/draft.bks")));
+        let b = snapshot_dir_in(root, Some(Path::new("/novels/b/draft.bks")));
+        assert_ne!(a, b);
+        for dir in [&a, &b] {
+            assert_eq!(dir.parent(), Some(root));
+            let name = dir.file_name().unwrap().to_str().unwrap();
+            let hash = name.strip_prefix("draft-").unwrap();
+            assert_eq!(hash.len(), 16);
+            assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+        }
+        // The same document always maps to the same folder
+        assert_eq!(
+            a,
+            snapshot_dir_in(root, Some(Path::new("/novels/a/draft.bks")))
+        );
+        assert_eq!(snapshot_dir_in(root, None), root.join("untitled"));
+    }
+
+    #[test]
+    fn a_missing_snapshot_folder_has_no_snapshots() {
+        let dir = scratch_dir("snapshots-missing");
+        assert!(list_snapshots_in(&dir.join("nothing")).unwrap().is_empty());
+        assert_eq!(
+            prune_snapshots_in(&dir.join("nothing"), 1, &dir.join("trash")).unwrap(),
+            0
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn progress_history_keeps_one_record_per_day_in_order() {
+        let dir = scratch_dir("progress");
+        let path = dir.join("progress.json");
+        let day = |d| NaiveDate::from_ymd_opt(2024, 11, d).unwrap();
+        assert!(load_progress(&path).unwrap().is_empty());
+
+        for (date, words) in [(day(3), 300), (day(1), 100), (day(3), -50)] {
+            record_progress(&path, DailyProgress { date, words }).unwrap();
+        }
+        let history = load_progress(&path).unwrap();
+        assert_eq!(
+            history,
+            [
+                DailyProgress {
+                    date: day(1),
+                    words: 100
+                },
+                DailyProgress {
+                    date: day(3),
+                    words: -50
+                },
+            ]
+        );
+
+        let recent: Vec<(u32, i64)> = recent_progress(&history, day(4), 4)
+            .iter()
+            .map(|d| (chrono::Datelike::day(&d.date), d.words))
+            .collect();
+        assert_eq!(recent, [(4, 0), (3, -50), (2, 0), (1, 100)]);
+
+        fs::write(&path, "not json").unwrap();
+        assert!(load_progress(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_a_file_changed_since_we_saw_it_is_a_conflict() {
+        let t = |secs| Some(UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(check_for_external_change(t(10), t(10)), SaveCheck::Proceed);
+        assert_eq!(check_for_external_change(t(10), t(20)), SaveCheck::Conflict);
+        // Moved back in time (a sync restoring an older copy) is a change too
+        assert_eq!(check_for_external_change(t(20), t(10)), SaveCheck::Conflict);
+        // Deleted since, or never seen (Save As onto an existing file)
+        assert_eq!(check_for_external_change(t(10), None), SaveCheck::Proceed);
+        assert_eq!(check_for_external_change(None, t(10)), SaveCheck::Proceed);
+        assert_eq!(check_for_external_change(None, None), SaveCheck::Proceed);
+    }
+
+    #[test]
+    fn another_writer_between_our_saves_is_caught() {
+        let dir = scratch_dir("external-change");
+        let path = dir.join("draft.bks");
+        write_at(&path, "ours", 1_000);
+        let known = modified_time(&path);
+        assert_eq!(
+            check_for_external_change(known, modified_time(&path)),
+            SaveCheck::Proceed
+        );
+
+        // Another copy of the app saves
+        write_at(&path, "theirs", 2_000);
+        assert_eq!(
+            check_for_external_change(known, modified_time(&path)),
+            SaveCheck::Conflict
+        );
+
+        // After we reload (or overwrite) the new time is the known one
+        let known = modified_time(&path);
+        assert_eq!(
+            check_for_external_change(known, modified_time(&path)),
+            SaveCheck::Proceed
+        );
+        fs::remove_file(&path).unwrap();
+        assert_eq!(modified_time(&path), None);
+        assert_eq!(
+            check_for_external_change(known, modified_time(&path)),
+            SaveCheck::Proceed
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn in_memory_storage_loads_what_it_saved_with_backups() {
+        let storage = InMemoryStorage::new();
+        let path = Path::new("/mem/draft.bks");
+        assert!(load_document_from(&storage, path).is_err());
+        assert_eq!(storage.modified(path), None);
+
+        storage.insert(path, b"caf\xE9\r\n".to_vec());
+        let loaded = load_document_from(&storage, path).unwrap();
+        assert_eq!(loaded.text, "caf\u{e9}\n");
+        assert_eq!(loaded.encoding, encoding::TextEncoding::Windows1252);
+        assert!(!is_password_protected(&storage, path));
+
+        let first = storage.modified(path).unwrap();
+        let settings = BackupSettings {
+            count: 2,
+            ..BackupSettings::default()
+        };
+        for version in ["two", "three", "four"] {
+            storage.write(path, version.as_bytes(), settings).unwrap();
+        }
+        assert!(storage.modified(path).unwrap() > first);
+        assert_eq!(storage.text(path).as_deref(), Some("four"));
+        let backups: Vec<PathBuf> = storage
+            .paths()
+            .into_iter()
+            .filter(|stored| stored.starts_with("/mem/backups"))
+            .collect();
+        assert_eq!(backups.len(), 2);
+        let texts: Vec<String> = backups
+            .iter()
+            .map(|backup| storage.text(backup).unwrap())
+            .collect();
+        assert_eq!(texts, ["two", "three"]);
+
+        // Keeping fewer drops the surplus
+        let settings = BackupSettings {
+            count: 1,
+            ..settings
+        };
+        storage.write(path, b"five", settings).unwrap();
+        assert_eq!(storage.paths().len(), 2);
+        assert!(storage
+            .paths()
+            .iter()
+            .any(|p| storage.text(p).as_deref() == Some("four")));
+    }
+
+    #[test]
+    fn autosave_writes_through_the_storage_it_is_given() {
+        let storage = Arc::new(InMemoryStorage::new());
+        let state = Arc::new(Mutex::new(AutosaveState {
+            // Only Autosave Now writes, so the test doesn't wait on a timer
+            settings: AutosaveSettings {
+                enabled: false,
+                ..AutosaveSettings::default()
+            },
+            snapshots: SnapshotSettings {
+                interval_mins: 0,
+                ..SnapshotSettings::default()
+            },
+        }));
+        let (commands, command_rx) = std::sync::mpsc::channel();
+        let (event_tx, events) = std::sync::mpsc::channel();
+        let thread = {
+            let storage: Arc<dyn Storage> = storage.clone();
+            let state = state.clone();
+            std::thread::spawn(move || autosave_thread(storage, state, command_rx, event_tx))
+        };
+        let next_event = || events.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        let draft = PathBuf::from("/mem/draft.bks");
+        commands
+            .send(AutosaveCommand::Document {
+                text: "First draft".into(),
+                file: Some(draft.clone()),
+            })
+            .unwrap();
+        commands.send(AutosaveCommand::SaveNow).unwrap();
+        let autosave = draft.with_extension("bks.autosave");
+        match next_event() {
+            AutosaveEvent::Saved { path, .. } => assert_eq!(path, autosave),
+            other => panic!("expected a save, got {:?}", other),
+        }
+        assert_eq!(storage.text(&autosave).as_deref(), Some("First draft"));
+        // The document itself is never touched
+        assert_eq!(storage.text(&draft), None);
+
+        commands
+            .send(AutosaveCommand::Document {
+                text: "  \n".into(),
+                file: Some(draft),
+            })
+            .unwrap();
+        commands.send(AutosaveCommand::SaveNow).unwrap();
+        assert!(matches!(next_event(), AutosaveEvent::Skipped(_)));
+        assert_eq!(storage.text(&autosave).as_deref(), Some("First draft"));
+
+        drop(commands);
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn the_autosave_interval_is_clamped_to_ten_seconds_to_ten_minutes() {
+        let interval = |interval_secs| {
+            AutosaveSettings {
+                interval_secs,
+                ..AutosaveSettings::default()
+            }
+            .interval()
+        };
+        assert_eq!(interval(0), Duration::from_secs(10));
+        assert_eq!(interval(9), Duration::from_secs(10));
+        assert_eq!(interval(60), Duration::from_secs(60));
+        assert_eq!(interval(600), Duration::from_secs(600));
+        assert_eq!(interval(u64::MAX), Duration::from_secs(600));
+        assert_eq!(
+            AutosaveSettings::default().interval(),
+            Duration::from_secs(60)
+        );
+    }
+
+    /// File names in `dir`, sorted
+    fn dir_listing(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn a_save_interrupted_mid_write_leaves_the_original_alone() {
+        let dir = scratch_dir("interrupted-write");
+        let path = dir.join("draft.bks");
+        save_text_file(&path, "The original draft", BackupSettings::OFF).unwrap();
+
+        // Half the bytes reach the temp file, then the disk fills up
+        let result = replace_file(&path, b"A much longer replacement", |temp, content| {
+            fs::write(temp, &content[..content.len() / 2])?;
+            anyhow::bail!("No space left on device")
+        });
+
+        let error = format!("{:#}", result.unwrap_err());
+        assert!(error.contains("No space left on device"), "{}", error);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "The original draft");
+        assert_eq!(dir_listing(&dir), ["draft.bks"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_first_save_interrupted_mid_write_leaves_nothing() {
+        let dir = scratch_dir("interrupted-first-write");
+        let path = dir.join("new.bks");
+        let result = replace_file(&path, b"content", |temp, _| {
+            fs::write(temp, b"cont")?;
+            anyhow::bail!("Input/output error")
+        });
+        assert!(result.is_err());
+        assert!(dir_listing(&dir).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_failed_rename_removes_the_temp_file() {
+        let dir = scratch_dir("failed-rename");
+        // A folder with something in it can't be replaced by a file
+        let path = dir.join("draft.bks");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("inside.txt"), "kept").unwrap();
+
+        assert!(DiskStorage
+            .write(&path, b"text", BackupSettings::OFF)
+            .is_err());
+        assert_eq!(dir_listing(&dir), ["draft.bks"]);
+        assert_eq!(fs::read_to_string(path.join("inside.txt")).unwrap(), "kept");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,633 @@
+//! FILE: src/style.rs
+//!
+//! The Tools → Style Check window: habits worth a second look.
+//! - Repeated words: the same word again within a few words of itself
+//!   ("He opened the door. The door was ...")
+//! - Overused adverbs: "-ly" words used more often than a set number of times
+//! - Filter words and phrases: "just", "very", "suddenly", "began to" ...
+//!   flagged wherever they appear
+//!
+//! Each finding is a word with its count and every place it occurs; clicking
+//! an occurrence selects it in the editor. The thresholds and both word
+//! lists are StyleOptions, saved with the other preferences.
+//!
+//! WHAT'S CHECKED:
+//! Prose, dialogue and stage directions. Act, chapter and scene tags,
+//! character cues and comments are skipped, and a repeat never reaches
+//! back past an act, chapter or scene tag: a word at the end of one scene
+//! and the start of the next is no echo.
+//!
+//! Words are compared lowercased, and a word is letters and digits with the
+//! apostrophes inside it ("don't", "Ann’s").
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Matching word sequences with slice::windows
+//! - A BTreeMap to group occurrences by word
+
+use crate::parser::{self, ParsedLine, TagType};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// Largest repeat window offered, in words
+pub const MAX_REPEAT_WINDOW: usize = 500;
+
+/// Characters of context shown either side of an occurrence
+const CONTEXT_CHARS: usize = 30;
+
+/// What the check looks for (saved in Settings)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StyleOptions {
+    /// A word used again within this many words is a repeat; 0 turns the
+    /// repeat check off
+    pub repeat_window: usize,
+    /// Words shorter than this are never repeats ("the", "and", "was")
+    pub min_repeat_length: usize,
+    /// An "-ly" adverb used at least this many times is flagged; 0 turns
+    /// the adverb check off
+    pub adverb_threshold: usize,
+    /// Flagged wherever they appear; an entry can be a phrase
+    pub filter_words: Vec<String>,
+    /// Never flagged as repeats or adverbs ("only", "family")
+    pub ignored_words: Vec<String>,
+}
+
+impl Default for StyleOptions {
+    fn default() -> Self {
+        let list = |words: &[&str]| words.iter().map(|w| w.to_string()).collect();
+        Self {
+            repeat_window: 40,
+            min_repeat_length: 5,
+            adverb_threshold: 3,
+            filter_words: list(&[
+                "just",
+                "very",
+                "really",
+                "suddenly",
+                "quite",
+                "rather",
+                "somewhat",
+                "actually",
+                "basically",
+                "literally",
+                "seemed to",
+                "began to",
+                "started to",
+                "could see",
+                "could hear",
+            ]),
+            ignored_words: list(&[
+                "only", "family", "early", "reply", "supply", "apply", "rely", "ally", "belly",
+                "jelly", "holy", "ugly", "silly", "lonely", "lovely", "friendly", "elderly",
+                "likely", "daily", "italy", "july",
+            ]),
+        }
+    }
+}
+
+/// What a finding is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Kind {
+    Filter,
+    Adverb,
+    Repeat,
+}
+
+impl Kind {
+    const ALL: [Kind; 3] = [Kind::Filter, Kind::Adverb, Kind::Repeat];
+
+    fn heading(self) -> &'static str {
+        match self {
+            Kind::Filter => "Filter words",
+            Kind::Adverb => "Overused adverbs",
+            Kind::Repeat => "Repeated words",
+        }
+    }
+}
+
+/// One place a flagged word occurs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Occurrence {
+    /// Byte range in the text
+    pub range: Range<usize>,
+    /// 1-based line
+    pub line: usize,
+    /// A little of the line around it
+    pub context: String,
+}
+
+/// A flagged word (or phrase) and where it occurs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub kind: Kind,
+    /// Lowercased
+    pub word: String,
+    pub occurrences: Vec<Occurrence>,
+}
+
+/// A word of the text
+struct Token {
+    /// Lowercased
+    word: String,
+    range: Range<usize>,
+    /// Index into the text's lines
+    line: usize,
+    /// Which run of text between tags it's in
+    section: usize,
+}
+
+/// Everything the check flags in `text`, filter words first, then adverbs,
+/// then repeats, each kind most frequent first
+///
+/// `parsed` are the text's parsed lines, which say which lines are tags.
+pub fn check(text: &str, parsed: &[ParsedLine], options: &StyleOptions) -> Vec<Finding> {
+    let tokens = tokens(text, parsed);
+    let ignored: Vec<String> = options
+        .ignored_words
+        .iter()
+        .map(|w| w.trim().to_lowercase())
+        .collect();
+    let is_ignored = |word: &str| ignored.iter().any(|w| w == word);
+
+    // (kind, word) → token ranges (first token, last token)
+    let mut found: BTreeMap<(Kind, String), Vec<(usize, usize)>> = BTreeMap::new();
+
+    for entry in &options.filter_words {
+        let phrase: Vec<String> = entry.split_whitespace().map(str::to_lowercase).collect();
+        if phrase.is_empty() {
+            continue;
+        }
+        for (start, window) in tokens.windows(phrase.len()).enumerate() {
+            let same_section = window[0].section == window[window.len() - 1].section;
+            if same_section && window.iter().zip(&phrase).all(|(t, w)| &t.word == w) {
+                found
+                    .entry((Kind::Filter, phrase.join(" ")))
+                    .or_default()
+                    .push((start, start + phrase.len() - 1));
+            }
+        }
+    }
+
+    if options.adverb_threshold > 0 {
+        let mut adverbs: BTreeMap<&str, Vec<(usize, usize)>> = BTreeMap::new();
+        for (i, token) in tokens.iter().enumerate() {
+            if token.word.chars().count() > 3
+                && token.word.ends_with("ly")
+                && !is_ignored(&token.word)
+            {
+                adverbs.entry(&token.word).or_default().push((i, i));
+            }
+        }
+        for (word, at) in adverbs {
+            if at.len() >= options.adverb_threshold {
+                found.insert((Kind::Adverb, word.to_string()), at);
+            }
+        }
+    }
+
+    if options.repeat_window > 0 {
+        let filter: Vec<String> = options
+            .filter_words
+            .iter()
+            .map(|w| w.to_lowercase())
+            .collect();
+        let mut repeated = vec![false; tokens.len()];
+        for (i, token) in tokens.iter().enumerate() {
+            let word = &token.word;
+            if word.chars().count() < options.min_repeat_length
+                || is_ignored(word)
+                || filter.contains(word)
+            {
+                continue;
+            }
+            let earlier = tokens[i.saturating_sub(options.repeat_window)..i]
+                .iter()
+                .enumerate()
+                .rev()
+                .find(|(_, t)| t.section == token.section && &t.word == word);
+            if let Some((offset, _)) = earlier {
+                repeated[i.saturating_sub(options.repeat_window) + offset] = true;
+                repeated[i] = true;
+            }
+        }
+        for (i, token) in tokens.iter().enumerate() {
+            if repeated[i] {
+                found
+                    .entry((Kind::Repeat, token.word.clone()))
+                    .or_default()
+                    .push((i, i));
+            }
+        }
+    }
+
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let line_starts: Vec<usize> = lines
+        .iter()
+        .scan(0, |offset, line| {
+            let start = *offset;
+            *offset += line.len();
+            Some(start)
+        })
+        .collect();
+    let occurrence = |first: &Token, last: &Token| {
+        let line_start = line_starts[first.line];
+        let line = lines[first.line].trim_end_matches(['\n', '\r']);
+        // A phrase over a line break is shown up to the end of its first line
+        let end = if last.line == first.line {
+            last.range.end - line_start
+        } else {
+            line.len()
+        };
+        Occurrence {
+            range: first.range.start..last.range.end,
+            line: first.line + 1,
+            context: context(line, first.range.start - line_start..end),
+        }
+    };
+
+    let mut findings: Vec<Finding> = found
+        .into_iter()
+        .map(|((kind, word), at)| Finding {
+            kind,
+            word,
+            occurrences: at
+                .into_iter()
+                .map(|(first, last)| occurrence(&tokens[first], &tokens[last]))
+                .collect(),
+        })
+        .collect();
+    findings.sort_by(|a, b| {
+        a.kind
+            .cmp(&b.kind)
+            .then(b.occurrences.len().cmp(&a.occurrences.len()))
+            .then_with(|| a.word.cmp(&b.word))
+    });
+    findings
+}
+
+/// The words of `text` that get checked (see module docs)
+fn tokens(text: &str, parsed: &[ParsedLine]) -> Vec<Token> {
+    let comments = parser::comment_ranges(text);
+    let in_comment = |at: usize| comments.iter().any(|c| c.contains(&at));
+
+    let mut tokens = Vec::new();
+    let mut section = 0;
+    let mut offset = 0;
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        let start = offset;
+        offset += line.len();
+        match parsed.get(index).and_then(|l| l.tag.as_ref()) {
+            // A cue is a name, not writing; the scene goes on after it
+            Some(TagType::Character(_)) => continue,
+            Some(TagType::Action(_)) | None => {}
+            Some(_) => {
+                section += 1;
+                continue;
+            }
+        }
+
+        let mut word_start = None;
+        for (i, c) in line.char_indices().chain([(line.len(), ' ')]) {
+            let part_of_word =
+                c.is_alphanumeric() || (matches!(c, '\'' | '’') && word_start.is_some());
+            match (part_of_word, word_start) {
+                (true, None) => word_start = Some(i),
+                (false, Some(from)) => {
+                    word_start = None;
+                    // An apostrophe closing a quote isn't part of the word
+                    let word = line[from..i].trim_end_matches(['\'', '’']);
+                    let range = start + from..start + from + word.len();
+                    if !in_comment(range.start) {
+                        tokens.push(Token {
+                            word: word.to_lowercase(),
+                            range,
+                            line: index,
+                            section,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    tokens
+}
+
+/// `line` cut down to the occurrence at `range` (bytes) and a little
+/// either side
+fn context(line: &str, range: Range<usize>) -> String {
+    let before: Vec<char> = line[..range.start].chars().collect();
+    let after: Vec<char> = line[range.end..].chars().collect();
+    let mut out = String::new();
+    if before.len() > CONTEXT_CHARS {
+        out.push('…');
+    }
+    out.extend(&before[before.len().saturating_sub(CONTEXT_CHARS)..]);
+    out.push_str(&line[range]);
+    out.extend(after.iter().take(CONTEXT_CHARS));
+    if after.len() > CONTEXT_CHARS {
+        out.push('…');
+    }
+    out.trim().to_string()
+}
+
+/// The findings, plus what the window needs between frames
+#[derive(Debug, Default)]
+pub struct StyleCheck {
+    pub findings: Vec<Finding>,
+    /// The edit generation and options the findings are for
+    checked: Option<(u64, StyleOptions)>,
+    /// The word lists as typed, one entry per line, so a line being typed
+    /// isn't tidied away under the caret
+    filter_text: Option<String>,
+    ignored_text: Option<String>,
+}
+
+impl StyleCheck {
+    /// Check the text again if it (its edit `generation`) or the options
+    /// changed since last time
+    pub fn refresh(
+        &mut self,
+        text: &str,
+        generation: u64,
+        parsed: &[ParsedLine],
+        options: &StyleOptions,
+    ) {
+        let current = self
+            .checked
+            .as_ref()
+            .is_some_and(|(g, o)| *g == generation && o == options);
+        if !current {
+            self.findings = check(text, parsed, options);
+            self.checked = Some((generation, options.clone()));
+        }
+    }
+
+    /// Forget the word lists as typed, so they're read from the options
+    /// the next time the window opens
+    pub fn close(&mut self) {
+        self.filter_text = None;
+        self.ignored_text = None;
+    }
+}
+
+/// Draw the window's contents
+///
+/// Returns the byte range of the occurrence the user clicked, if any.
+pub fn show(
+    ui: &mut egui::Ui,
+    check: &mut StyleCheck,
+    options: &mut StyleOptions,
+) -> Option<Range<usize>> {
+    egui::CollapsingHeader::new("Options")
+        .id_salt("style_check_options")
+        .show(ui, |ui| options_ui(ui, check, options));
+    ui.separator();
+
+    if check.findings.is_empty() {
+        ui.label("Nothing to flag.");
+        return None;
+    }
+
+    let mut clicked = None;
+    egui::ScrollArea::vertical()
+        .max_height(420.0)
+        .show(ui, |ui| {
+            for kind in Kind::ALL {
+                let findings: Vec<&Finding> =
+                    check.findings.iter().filter(|f| f.kind == kind).collect();
+                if findings.is_empty() {
+                    continue;
+                }
+                let total: usize = findings.iter().map(|f| f.occurrences.len()).sum();
+                egui::CollapsingHeader::new(format!("{} ({})", kind.heading(), total))
+                    .id_salt(("style_kind", kind))
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        for finding in findings {
+                            let label = format!("{} ×{}", finding.word, finding.occurrences.len());
+                            egui::CollapsingHeader::new(label)
+                                .id_salt(("style_finding", kind, &finding.word))
+                                .show(ui, |ui| {
+                                    for occurrence in &finding.occurrences {
+                                        let label = format!(
+                                            "Line {}: {}",
+                                            occurrence.line, occurrence.context
+                                        );
+                                        if ui.selectable_label(false, label).clicked() {
+                                            clicked = Some(occurrence.range.clone());
+                                        }
+                                    }
+                                });
+                        }
+                    });
+            }
+        });
+    clicked
+}
+
+/// The thresholds and word lists
+fn options_ui(ui: &mut egui::Ui, check: &mut StyleCheck, options: &mut StyleOptions) {
+    egui::Grid::new("style_check_numbers")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Repeats within");
+            ui.add(
+                egui::DragValue::new(&mut options.repeat_window)
+                    .range(0..=MAX_REPEAT_WINDOW)
+                    .suffix(" words"),
+            );
+            ui.end_row();
+            ui.label("Repeats of words of at least");
+            ui.add(
+                egui::DragValue::new(&mut options.min_repeat_length)
+                    .range(1..=20)
+                    .suffix(" letters"),
+            );
+            ui.end_row();
+            ui.label("Adverbs used at least");
+            ui.add(
+                egui::DragValue::new(&mut options.adverb_threshold)
+                    .range(0..=100)
+                    .suffix(" times"),
+            );
+            ui.end_row();
+        });
+    ui.weak("0 turns a check off.");
+
+    ui.columns(2, |columns| {
+        word_list(
+            &mut columns[0],
+            "Filter words and phrases",
+            &mut check.filter_text,
+            &mut options.filter_words,
+        );
+        word_list(
+            &mut columns[1],
+            "Never flag",
+            &mut check.ignored_text,
+            &mut options.ignored_words,
+        );
+    });
+}
+
+/// A word list edited as text, one entry per line
+fn word_list(ui: &mut egui::Ui, label: &str, text: &mut Option<String>, words: &mut Vec<String>) {
+    ui.label(label);
+    let text = text.get_or_insert_with(|| words.join("\n"));
+    let response = ui.add(
+        egui::TextEdit::multiline(text)
+            .desired_rows(6)
+            .desired_width(f32::INFINITY),
+    );
+    if response.changed() {
+        *words = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(text: &str, options: &StyleOptions) -> Vec<Finding> {
+        check(text, &parser::parse_document(text), options)
+    }
+
+    /// (kind, word, how many times) of each finding, in order
+    fn summary(text: &str, options: &StyleOptions) -> Vec<(Kind, String, usize)> {
+        run(text, options)
+            .into_iter()
+            .map(|f| (f.kind, f.word, f.occurrences.len()))
+            .collect()
+    }
+
+    fn found(kind: Kind, word: &str, count: usize) -> (Kind, String, usize) {
+        (kind, word.to_string(), count)
+    }
+
+    #[test]
+    fn a_word_again_nearby_is_a_repeat() {
+        let text = "Water everywhere.\nMore Water came, and the water rose.";
+        let findings = run(text, &StyleOptions::default());
+        assert_eq!(findings.len(), 1);
+        let finding = &findings[0];
+        assert_eq!(
+            (finding.kind, finding.word.as_str()),
+            (Kind::Repeat, "water")
+        );
+        let at: Vec<_> = finding
+            .occurrences
+            .iter()
+            .map(|o| (&text[o.range.clone()], o.line))
+            .collect();
+        assert_eq!(at, [("Water", 1), ("Water", 2), ("water", 2)]);
+        assert_eq!(
+            finding.occurrences[1].context,
+            "More Water came, and the water rose."
+        );
+    }
+
+    #[test]
+    fn repeats_stay_within_the_window_and_the_scene() {
+        let options = StyleOptions {
+            repeat_window: 3,
+            ..StyleOptions::default()
+        };
+        assert!(summary("Water one two three water", &options).is_empty());
+        assert_eq!(
+            summary("Water one two water", &options),
+            [found(Kind::Repeat, "water", 2)]
+        );
+        // Short and ignored words are never repeats
+        assert!(summary("The the. Only only.", &options).is_empty());
+
+        let options = StyleOptions::default();
+        assert!(summary("Water.\n[SCENE: Next]\nWater again.\n", &options).is_empty());
+        // A cue doesn't end the scene, and isn't a word of it
+        assert_eq!(
+            summary("Water.\n\nHELENA\nWater again, Helena.\n", &options),
+            [found(Kind::Repeat, "water", 2)]
+        );
+    }
+
+    #[test]
+    fn adverbs_used_often_are_flagged() {
+        let text = "Quickly, quickly. She ran quickly and slowly. \
+                    Only, only, only. Fly, fly, fly.";
+        assert_eq!(
+            summary(text, &StyleOptions::default()),
+            [
+                found(Kind::Adverb, "quickly", 3),
+                found(Kind::Repeat, "quickly", 3),
+            ]
+        );
+        let off = StyleOptions {
+            adverb_threshold: 0,
+            repeat_window: 0,
+            ..StyleOptions::default()
+        };
+        assert!(summary(text, &off).is_empty());
+    }
+
+    #[test]
+    fn filter_words_and_phrases_are_found_anywhere() {
+        let text = "Just then she began to run.\nShe Began\nto walk. [[note: just very]]\n";
+        let options = StyleOptions {
+            repeat_window: 0,
+            ..StyleOptions::default()
+        };
+        let findings = run(text, &options);
+        assert_eq!(
+            summary(text, &options),
+            [
+                found(Kind::Filter, "began to", 2),
+                found(Kind::Filter, "just", 1),
+            ]
+        );
+        // A phrase over a line break is one occurrence, from its first word
+        // to its last
+        let across = &findings[0].occurrences[1];
+        assert_eq!(&text[across.range.clone()], "Began\nto");
+        assert_eq!(across.line, 2);
+        assert_eq!(across.context, "She Began");
+    }
+
+    #[test]
+    fn long_lines_are_cut_to_context() {
+        let text = format!("{}very{}", "a ".repeat(30), " b".repeat(30));
+        let findings = run(&text, &StyleOptions::default());
+        let context = &findings[0].occurrences[0].context;
+        assert_eq!(
+            context,
+            &format!("…{}very{}…", "a ".repeat(15), " b".repeat(15))
+        );
+    }
+
+    #[test]
+    fn refresh_checks_again_only_after_a_change() {
+        let options = StyleOptions::default();
+        let mut style = StyleCheck::default();
+        let text = "It was very late.";
+        style.refresh(text, 1, &parser::parse_document(text), &options);
+        assert_eq!(style.findings.len(), 1);
+
+        style.refresh("", 1, &[], &options);
+        assert_eq!(style.findings.len(), 1);
+        style.refresh("", 2, &[], &options);
+        assert!(style.findings.is_empty());
+
+        let options = StyleOptions {
+            filter_words: vec![String::from("late")],
+            ..options
+        };
+        style.refresh(text, 2, &parser::parse_document(text), &options);
+        // Same generation, new options: checked again
+        assert_eq!(style.findings[0].word, "late");
+    }
+}
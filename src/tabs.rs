@@ -0,0 +1,247 @@
+//! FILE: src/tabs.rs
+//!
+//! Several documents open at once, one per tab.
+//!
+//! Only one document is edited at a time, and App keeps that one in its own
+//! fields (text_content, current_file_path, history, ...) just as it did
+//! before there were tabs, so nothing that works on "the document" needs
+//! to know about them. Every other open document waits in a DocumentTab.
+//! Switching tabs parks the active document's fields in its DocumentTab
+//! and takes the chosen tab's out (see App::switch_tab).
+//!
+//! Caches keyed by App's edit generation (structure, counts, problems,
+//! find matches) recompute because unparking a document bumps it, like
+//! any other change to the text; a generation is never reused, so a
+//! cache can't mistake one document for another.
+//!
+//! This module holds the parked state and draws the tab bar; what the
+//! clicks mean is up to App.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - std::mem::take / replace: moving a value out of a struct field
+//! - egui drag and drop payloads for reordering
+
+use crate::encoding::{LineEnding, TextEncoding};
+use crate::format::DocumentFormat;
+use crate::history::{History, HistoryLimits};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// ============================================================================
+// PARKED DOCUMENT
+// ============================================================================
+
+/// Everything that belongs to one open document rather than to the app
+///
+/// Mirrors the App fields of the same meaning; see App for what each does.
+#[derive(Debug)]
+pub struct DocumentTab {
+    pub text: String,
+    pub path: Option<PathBuf>,
+    pub dirty: bool,
+    pub history: History,
+    pub disk_modified: Option<SystemTime>,
+    pub line_ending: LineEnding,
+    pub encoding: TextEncoding,
+    pub password: Option<String>,
+    pub detected_format: DocumentFormat,
+    pub format_override: Option<DocumentFormat>,
+    /// Caret position (char index) and scroll offset to go back to
+    pub cursor: usize,
+    pub scroll: f32,
+    pub preview: Option<String>,
+}
+
+impl DocumentTab {
+    /// A new, untitled, empty document
+    pub fn empty() -> Self {
+        Self {
+            text: String::new(),
+            path: None,
+            dirty: false,
+            history: History::new("", HistoryLimits::default()),
+            disk_modified: None,
+            line_ending: LineEnding::Lf,
+            encoding: TextEncoding::Utf8,
+            password: None,
+            // New documents are BookScript
+            detected_format: DocumentFormat::BookScript,
+            format_override: None,
+            cursor: 0,
+            scroll: 0.0,
+            preview: None,
+        }
+    }
+}
+
+// ============================================================================
+// TAB BAR
+// ============================================================================
+
+/// What the tab bar shows for one tab
+#[derive(Debug, Clone)]
+pub struct TabLabel {
+    /// File name, or "Untitled"
+    pub title: String,
+    pub dirty: bool,
+    /// Full path, shown on hover (None for untitled documents)
+    pub path: Option<PathBuf>,
+}
+
+/// Something the user did in the tab bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabAction {
+    Select(usize),
+    Close(usize),
+    /// Drag `from` to where `to` is
+    Move {
+        from: usize,
+        to: usize,
+    },
+    New,
+}
+
+/// Draw the tab bar
+///
+/// A tab is selected by clicking it, closed with its × or a middle click,
+/// and reordered by dragging it onto another tab (or with its context
+/// menu). `+` opens a new, empty tab.
+pub fn tab_bar(ui: &mut egui::Ui, labels: &[TabLabel], active: usize) -> Option<TabAction> {
+    let mut action = None;
+
+    egui::ScrollArea::horizontal().show(ui, |ui| {
+        ui.horizontal(|ui| {
+            for (index, label) in labels.iter().enumerate() {
+                let text = if label.dirty {
+                    format!("*{}", label.title)
+                } else {
+                    label.title.clone()
+                };
+
+                let response = ui
+                    .add(egui::SelectableLabel::new(index == active, text))
+                    .interact(egui::Sense::drag());
+                let response = match &label.path {
+                    Some(path) => response.on_hover_text(path.display().to_string()),
+                    None => response,
+                };
+
+                // Dragging carries the tab's index; dropping it on another
+                // tab moves it there
+                response.dnd_set_drag_payload(index);
+                if let Some(from) = response.dnd_release_payload::<usize>() {
+                    if *from != index {
+                        action = Some(TabAction::Move {
+                            from: *from,
+                            to: index,
+                        });
+                    }
+                }
+
+                if response.clicked() {
+                    action = Some(TabAction::Select(index));
+                }
+                if response.middle_clicked() {
+                    action = Some(TabAction::Close(index));
+                }
+                response.context_menu(|ui| {
+                    if ui
+                        .add_enabled(index > 0, egui::Button::new("Move left"))
+                        .clicked()
+                    {
+                        action = Some(TabAction::Move {
+                            from: index,
+                            to: index - 1,
+                        });
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(index + 1 < labels.len(), egui::Button::new("Move right"))
+                        .clicked()
+                    {
+                        action = Some(TabAction::Move {
+                            from: index,
+                            to: index + 1,
+                        });
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui.button("Close").clicked() {
+                        action = Some(TabAction::Close(index));
+                        ui.close_menu();
+                    }
+                });
+
+                if ui
+                    .small_button("×")
+                    .on_hover_text("Close (Ctrl+W)")
+                    .clicked()
+                {
+                    action = Some(TabAction::Close(index));
+                }
+                ui.separator();
+            }
+
+            if ui
+                .small_button("+")
+                .on_hover_text("New tab (Ctrl+N)")
+                .clicked()
+            {
+                action = Some(TabAction::New);
+            }
+        });
+    });
+
+    action
+}
+
+/// Where the active tab ends up after the tab at `from` moves to `to`
+pub fn active_after_move(active: usize, from: usize, to: usize) -> usize {
+    if active == from {
+        to
+    } else if from < active && to >= active {
+        active - 1
+    } else if from > active && to <= active {
+        active + 1
+    } else {
+        active
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_active_tab_is_followed_through_a_move() {
+        assert_eq!(active_after_move(1, 1, 3), 3);
+        assert_eq!(active_after_move(2, 0, 3), 1);
+        assert_eq!(active_after_move(2, 4, 0), 3);
+        assert_eq!(active_after_move(2, 3, 4), 2);
+
+        // Every move among five tabs, done the way App does it
+        for from in 0..5 {
+            for to in 0..5 {
+                let mut order: Vec<usize> = (0..5).collect();
+                let tab = order.remove(from);
+                order.insert(to, tab);
+                for active in 0..5 {
+                    let after = active_after_move(active, from, to);
+                    assert_eq!(order[after], active, "{active}: {from} → {to}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn a_new_tab_is_an_untitled_bookscript_document() {
+        let tab = DocumentTab::empty();
+        assert!(tab.text.is_empty());
+        assert_eq!(tab.path, None);
+        assert!(!tab.dirty);
+        assert_eq!(tab.detected_format, DocumentFormat::BookScript);
+        assert_eq!(tab.format_override, None);
+        assert_eq!(tab.line_ending, LineEnding::Lf);
+        assert_eq!((tab.cursor, tab.scroll), (0, 0.0));
+    }
+}
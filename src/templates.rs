@@ -0,0 +1,127 @@
+//! FILE: src/templates.rs
+//!
+//! File → New from Template: documents that start with a structure already
+//! in place instead of an empty page.
+//!
+//! Three templates are built in (novel, screenplay, stage play). Any
+//! document can be kept as a template of the user's own (File → New from
+//! Template → Save as Template…); those are stored in `templates.json` in
+//! the data directory (storage::load_templates / save_templates).
+//!
+//! A template is the text to start with plus the document mode to pin, the
+//! same choice as the status bar's Mode dropdown. None leaves the mode to
+//! detection, as for any new document.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - #[serde(default)] so a hand-written template can leave fields out
+//! - Replacing an element found with iter().position()
+
+use crate::format::DocumentFormat;
+use serde::{Deserialize, Serialize};
+
+/// A document to start from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Template {
+    /// What File → New from Template lists it as
+    pub name: String,
+    /// The document mode a new document from it is set to
+    #[serde(default)]
+    pub format: Option<DocumentFormat>,
+    /// The new document's text
+    #[serde(default)]
+    pub text: String,
+}
+
+const NOVEL: &str = "\
+[CHAPTER: One]
+[SCENE: Opening]
+
+The first scene starts here. Write in paragraphs, with a blank line between them.
+
+[SCENE: Second scene]
+
+A new scene tag starts each scene; the outline lists them under their chapter.
+
+[CHAPTER: Two]
+[SCENE: Opening]
+
+";
+
+const SCREENPLAY: &str = "\
+[ACT: I]
+[SCENE: INT. KITCHEN - NIGHT]
+
+Rain against the window. ALEX (30s) stares into a cold cup of coffee.
+
+ALEX
+(quietly)
+It was never going to work.
+
+CUT TO:
+
+[SCENE: EXT. STREET - CONTINUOUS]
+
+Alex steps out into the rain.
+
+[ACT: II]
+[SCENE: INT. OFFICE - DAY]
+
+[ACT: III]
+[SCENE: EXT. ROOFTOP - DAWN]
+
+";
+
+const STAGE_PLAY: &str = "\
+[ACT: I]
+[SCENE: A drawing room. Evening.]
+
+(Lights up. MARGARET sits by the fire, a letter in her lap.)
+
+MARGARET
+He isn't coming back, is he?
+
+HENRY
+(at the door)
+Not tonight.
+
+(Blackout.)
+
+[SCENE: The same. Next morning.]
+
+[ACT: II]
+[SCENE: A railway platform.]
+
+";
+
+/// The templates every installation has
+pub fn built_in() -> Vec<Template> {
+    let template = |name: &str, text: &str| Template {
+        name: name.to_string(),
+        format: Some(DocumentFormat::BookScript),
+        text: text.to_string(),
+    };
+    vec![
+        template("Novel", NOVEL),
+        template("Screenplay", SCREENPLAY),
+        template("Stage play", STAGE_PLAY),
+    ]
+}
+
+/// Add a user template, replacing one with the same name (ignoring case)
+///
+/// Returns true if an existing template was replaced.
+pub fn add(templates: &mut Vec<Template>, template: Template) -> bool {
+    let existing = templates
+        .iter()
+        .position(|t| t.name.eq_ignore_ascii_case(&template.name));
+    match existing {
+        Some(index) => {
+            templates[index] = template;
+            true
+        }
+        None => {
+            templates.push(template);
+            false
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! FILE: src/text_utils.rs
+//!
+//! Cleaning up text pasted in from word processors and web pages.
+//!
+//! Text copied out of Word or a browser brings along things a plain-text
+//! manuscript doesn't want: curly quotes in one paragraph and straight ones
+//! in the next, non-breaking spaces that look like spaces but don't wrap,
+//! runs of empty lines, trailing spaces and tabs. clean_up() fixes these in
+//! one pass; each fix can be switched off in CleanupOptions.
+//!
+//! Tag lines (`[CHAPTER: 1]`) and character cues (`O'BRIEN`) only get the
+//! whitespace fixes. Their punctuation is left as typed, because the parser
+//! matches it exactly - a cue with a curly apostrophe is no longer a cue.
+//!
+//! Everything here is string in, string out; the app decides whether the
+//! whole document or just a paste goes through it.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Peekable char iterators for looking one character ahead
+//! - str::split('\n') to take text apart and put it back together exactly
+
+use crate::parser::{self, TagType};
+use serde::{Deserialize, Serialize};
+
+// ============================================================================
+// OPTIONS
+// ============================================================================
+
+/// Which kind of quotes, dashes and ellipses the text should end up with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PunctuationStyle {
+    /// Typographer's punctuation: “ ” ‘ ’ — …
+    #[default]
+    Smart,
+    /// Plain keyboard punctuation: " ' -- ...
+    Straight,
+}
+
+/// Which fixes Edit → Clean Up Document applies
+///
+/// Saved with the settings, so the dialog remembers the last choice and
+/// clean-on-paste uses the same fixes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CleanupOptions {
+    /// Convert quotes, dashes and ellipses to `punctuation_style`
+    pub normalize_punctuation: bool,
+    pub punctuation_style: PunctuationStyle,
+    /// Turn non-breaking and other odd spaces into plain spaces, and drop
+    /// zero-width characters
+    pub normalize_spaces: bool,
+    /// Collapse two or more empty lines in a row into one
+    pub collapse_blank_lines: bool,
+    /// Remove spaces and tabs at the ends of lines
+    pub strip_trailing_whitespace: bool,
+    /// Expand tabs to spaces, lining up with tab stops every `tab_width`
+    pub tabs_to_spaces: bool,
+    pub tab_width: usize,
+    /// Run pasted text through the same fixes before it is inserted
+    pub clean_on_paste: bool,
+}
+
+impl Default for CleanupOptions {
+    fn default() -> Self {
+        Self {
+            normalize_punctuation: true,
+            punctuation_style: PunctuationStyle::Smart,
+            normalize_spaces: true,
+            collapse_blank_lines: true,
+            strip_trailing_whitespace: true,
+            tabs_to_spaces: true,
+            tab_width: 4,
+            clean_on_paste: false,
+        }
+    }
+}
+
+/// Widest tab stop the dialog offers
+pub const MAX_TAB_WIDTH: usize = 8;
+
+// ============================================================================
+// WHOLE-TEXT CLEANUP
+// ============================================================================
+
+/// The result of clean_up()
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cleaned {
+    pub text: String,
+    /// Lines whose text changed, plus blank lines removed
+    pub lines_changed: usize,
+}
+
+/// Apply every fix switched on in `options` to `text`
+///
+/// Line breaks are kept exactly as they were (apart from removed blank
+/// lines), including whether the text ends with one.
+pub fn clean_up(text: &str, options: &CleanupOptions) -> Cleaned {
+    let mut lines = Vec::new();
+    let mut lines_changed = 0;
+    let mut previous_blank = false;
+
+    for line in text.split('\n') {
+        let cleaned = clean_line(line, options);
+        let blank = cleaned.trim().is_empty();
+
+        if options.collapse_blank_lines && blank && previous_blank {
+            lines_changed += 1;
+            continue;
+        }
+        previous_blank = blank;
+
+        if cleaned != line {
+            lines_changed += 1;
+        }
+        lines.push(cleaned);
+    }
+
+    Cleaned {
+        text: lines.join("\n"),
+        lines_changed,
+    }
+}
+
+/// Apply the per-line fixes to one line (no line breaks)
+pub fn clean_line(line: &str, options: &CleanupOptions) -> String {
+    let mut line = line.to_string();
+
+    if options.normalize_spaces {
+        line = normalize_spaces(&line);
+    }
+    if options.tabs_to_spaces {
+        line = tabs_to_spaces(&line, options.tab_width);
+    }
+    if options.strip_trailing_whitespace {
+        line.truncate(line.trim_end().len());
+    }
+    if options.normalize_punctuation && !is_protected(&line) {
+        line = match options.punctuation_style {
+            PunctuationStyle::Smart => smarten_punctuation(&line),
+            PunctuationStyle::Straight => straighten_punctuation(&line),
+        };
+    }
+
+    line
+}
+
+/// Tag lines and character cues: their punctuation is left alone
+fn is_protected(line: &str) -> bool {
+    match parser::parse_line(line, 0).tag {
+        // Bracket tags, well-formed or not, and bare ALL-CAPS cues
+        Some(TagType::Action(_)) | None => false,
+        Some(_) => true,
+    }
+}
+
+// ============================================================================
+// INDIVIDUAL FIXES
+// ============================================================================
+
+/// Replace Unicode spaces with plain spaces and drop zero-width characters
+///
+/// Tabs are left for tabs_to_spaces() to deal with.
+pub fn normalize_spaces(line: &str) -> String {
+    line.chars()
+        .filter(|c| !matches!(c, '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{FEFF}'))
+        .map(|c| {
+            if c != '\t' && c.is_whitespace() {
+                ' '
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Expand tabs to spaces, so text after a tab lines up with the next
+/// multiple of `tab_width` columns, as it would in a text editor
+pub fn tabs_to_spaces(line: &str, tab_width: usize) -> String {
+    if !line.contains('\t') {
+        return line.to_string();
+    }
+    let tab_width = tab_width.max(1);
+    let mut result = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = tab_width - column % tab_width;
+            result.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            result.push(c);
+            column += 1;
+        }
+    }
+    result
+}
+
+/// Curly quotes, dashes and ellipses to their keyboard equivalents
+///
+/// “ ” „ → "   ‘ ’ ‚ → '   — → --   – → -   … → ...
+pub fn straighten_punctuation(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    for c in line.chars() {
+        match c {
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' => result.push('"'),
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' => result.push('\''),
+            '\u{2014}' => result.push_str("--"),
+            '\u{2013}' => result.push('-'),
+            '\u{2026}' => result.push_str("..."),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Keyboard quotes, dashes and ellipses to their typographer's versions
+///
+/// A quote opens after the start of the line, a space, an opening bracket
+/// or a dash, and closes (or is an apostrophe) anywhere else. Exactly two
+/// hyphens become an em dash and exactly three dots an ellipsis; longer
+/// runs (`-----` scene breaks, `....`) are left alone.
+pub fn smarten_punctuation(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut previous: Option<char> = None;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                let opening = previous.is_none_or(|p| {
+                    p.is_whitespace() || matches!(p, '(' | '[' | '{' | '\u{2014}' | '\u{2013}')
+                });
+                let smart = match (c, opening) {
+                    ('"', true) => '\u{201C}',
+                    ('"', false) => '\u{201D}',
+                    (_, true) => '\u{2018}',
+                    (_, false) => '\u{2019}',
+                };
+                result.push(smart);
+                previous = Some(smart);
+            }
+            '-' | '.' => {
+                // Measure the whole run of this character
+                let mut run = 1;
+                while chars.peek() == Some(&c) {
+                    chars.next();
+                    run += 1;
+                }
+                match (c, run) {
+                    ('-', 2) => result.push('\u{2014}'),
+                    ('.', 3) => result.push('\u{2026}'),
+                    _ => result.extend(std::iter::repeat_n(c, run)),
+                }
+                previous = result.chars().last();
+            }
+            _ => {
+                result.push(c);
+                previous = Some(c);
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn straight() -> CleanupOptions {
+        CleanupOptions {
+            punctuation_style: PunctuationStyle::Straight,
+            ..CleanupOptions::default()
+        }
+    }
+
+    #[test]
+    fn tag_lines_and_cues_keep_their_punctuation() {
+        let options = CleanupOptions::default();
+        for line in [
+            "[CHAPTER: \"Quoted\" -- Title...]",
+            "[SCENE: O'Brien's house | pov=Ann]",
+            "O'BRIEN",
+            "[CHAPTR: Don't -- stop]",
+        ] {
+            assert_eq!(clean_line(line, &options), line);
+        }
+        // Straightening leaves curly tags alone just the same
+        let curly = "[CHAPTER: \u{201C}Home\u{201D} \u{2014} again]";
+        assert_eq!(clean_line(curly, &straight()), curly);
+        // Whitespace fixes still apply to them
+        assert_eq!(
+            clean_line("[SCENE: Beach]\u{a0}\t ", &options),
+            "[SCENE: Beach]"
+        );
+    }
+
+    #[test]
+    fn prose_gets_smart_or_straight_punctuation() {
+        let prose = "\"It's late,\" she said -- 'too late'...";
+        let smart = clean_line(prose, &CleanupOptions::default());
+        assert_eq!(
+            smart,
+            "\u{201C}It\u{2019}s late,\u{201D} she said \u{2014} \u{2018}too late\u{2019}\u{2026}"
+        );
+        assert_eq!(clean_line(&smart, &straight()), prose);
+        // Longer runs are scene breaks, not dashes or ellipses
+        assert_eq!(smarten_punctuation("-----"), "-----");
+        assert_eq!(smarten_punctuation("wait...."), "wait....");
+        assert_eq!(
+            smarten_punctuation("(\"aside\")"),
+            "(\u{201C}aside\u{201D})"
+        );
+    }
+
+    #[test]
+    fn whitespace_fixes() {
+        assert_eq!(normalize_spaces("a\u{a0}b\u{200B}c\td"), "a bc\td");
+        assert_eq!(tabs_to_spaces("\tx", 4), "    x");
+        assert_eq!(tabs_to_spaces("ab\tc", 4), "ab  c");
+        assert_eq!(tabs_to_spaces("ab\tc", 0), "ab c");
+    }
+
+    #[test]
+    fn clean_up_collapses_blank_lines_and_counts_changes() {
+        let text = "One  \n\n\n\t\nTwo\n[CHAPTER: 2]\n";
+        let cleaned = clean_up(text, &CleanupOptions::default());
+        assert_eq!(cleaned.text, "One\n\nTwo\n[CHAPTER: 2]\n");
+        // "One  " trimmed, two blank lines removed
+        assert_eq!(cleaned.lines_changed, 3);
+
+        let untouched = clean_up("Plain\n", &CleanupOptions::default());
+        assert_eq!(untouched.text, "Plain\n");
+        assert_eq!(untouched.lines_changed, 0);
+
+        let nothing = CleanupOptions {
+            normalize_punctuation: false,
+            normalize_spaces: false,
+            collapse_blank_lines: false,
+            strip_trailing_whitespace: false,
+            tabs_to_spaces: false,
+            ..CleanupOptions::default()
+        };
+        assert_eq!(clean_up(text, &nothing).text, text);
+    }
+}
@@ -0,0 +1,188 @@
+//! FILE: src/theme.rs
+//!
+//! The colors the app can be shown in, beyond egui's own dark and light:
+//! a sepia "paper" theme, and a theme of the user's own read from
+//! `<data dir>/theme.json`.
+//!
+//! THEME FILE:
+//! Every entry is optional; anything left out comes from the `base` theme.
+//! Colors are `#rrggbb` (or `#rrggbbaa`).
+//!
+//! ```json
+//! {
+//!   "base": "dark",
+//!   "background": "#1e2127",
+//!   "text": "#d7dae0",
+//!   "editor_background": "#16181d",
+//!   "caret": "#e5c07b",
+//!   "selection": "#3e4451",
+//!   "accent": "#61afef"
+//! }
+//! ```
+//!
+//! Choosing View → Theme → Custom when there is no theme file yet writes
+//! this example, so there is something to edit.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - `#[serde(rename_all = "lowercase")]` to match JSON naming
+//! - u8::from_str_radix for parsing hex digits
+
+use crate::storage;
+use anyhow::{bail, Context, Result};
+use egui::Color32;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// ============================================================================
+// SEPIA
+// ============================================================================
+
+/// Dark brown text on warm paper, easier on the eyes than white for long
+/// sessions
+pub fn sepia() -> egui::Visuals {
+    let mut visuals = egui::Visuals::light();
+    let paper = Color32::from_rgb(0xf4, 0xec, 0xd8);
+    let page = Color32::from_rgb(0xfb, 0xf6, 0xe9);
+    let ink = Color32::from_rgb(0x5b, 0x46, 0x36);
+
+    visuals.panel_fill = paper;
+    visuals.window_fill = page;
+    visuals.faint_bg_color = Color32::from_rgb(0xec, 0xe2, 0xc9);
+    visuals.extreme_bg_color = page;
+    visuals.override_text_color = Some(ink);
+    visuals.selection.bg_fill = Color32::from_rgb(0xe0, 0xcd, 0xa9);
+    visuals.selection.stroke.color = ink;
+    visuals.hyperlink_color = Color32::from_rgb(0x8b, 0x5a, 0x2b);
+    visuals.text_cursor.stroke.color = Color32::from_rgb(0x8b, 0x45, 0x13);
+
+    // Buttons and other widgets on the same paper, a shade darker
+    let widget = Color32::from_rgb(0xe8, 0xdc, 0xc0);
+    for widgets in [
+        &mut visuals.widgets.inactive,
+        &mut visuals.widgets.hovered,
+        &mut visuals.widgets.active,
+        &mut visuals.widgets.open,
+    ] {
+        widgets.weak_bg_fill = widget;
+        widgets.bg_fill = widget;
+    }
+    visuals.widgets.noninteractive.bg_fill = paper;
+    visuals.widgets.noninteractive.weak_bg_fill = paper;
+    visuals
+}
+
+/// The color `[[...]]` comments are drawn in: green, set apart from the
+/// text in every theme without shouting
+pub fn comment_color(visuals: &egui::Visuals) -> Color32 {
+    if visuals.dark_mode {
+        Color32::from_rgb(0x8f, 0xbf, 0x7f)
+    } else {
+        Color32::from_rgb(0x3d, 0x7a, 0x3a)
+    }
+}
+
+// ============================================================================
+// THEME FILE
+// ============================================================================
+
+/// Which built-in theme a theme file starts from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Base {
+    #[default]
+    Dark,
+    Light,
+    Sepia,
+}
+
+/// The contents of theme.json, colors still as text
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ThemeFile {
+    base: Base,
+    /// Panels and windows
+    background: Option<String>,
+    text: Option<String>,
+    /// Behind the text being edited (and other text fields)
+    editor_background: Option<String>,
+    caret: Option<String>,
+    selection: Option<String>,
+    /// Links and the outline of selected text
+    accent: Option<String>,
+}
+
+/// What Custom starts out as when there's no theme file (see module docs)
+const EXAMPLE_THEME: &str = r##"{
+  "base": "dark",
+  "background": "#1e2127",
+  "text": "#d7dae0",
+  "editor_background": "#16181d",
+  "caret": "#e5c07b",
+  "selection": "#3e4451",
+  "accent": "#61afef"
+}
+"##;
+
+/// Read the theme file and build its colors
+///
+/// A missing file is created from EXAMPLE_THEME first. Err if the file
+/// can't be read or written, isn't valid JSON, or has a color that can't be
+/// read - the message names the entry.
+pub fn load_custom(path: &Path) -> Result<egui::Visuals> {
+    if !path.exists() {
+        storage::save_text_file(path, EXAMPLE_THEME, storage::BackupSettings::OFF)?;
+    }
+    let json = storage::load_text_file(path)?;
+    let file: ThemeFile = serde_json::from_str(&json)
+        .context(format!("Failed to parse theme file: {}", path.display()))?;
+
+    let mut visuals = match file.base {
+        Base::Dark => egui::Visuals::dark(),
+        Base::Light => egui::Visuals::light(),
+        Base::Sepia => sepia(),
+    };
+    if let Some(color) = color(&file.background, "background")? {
+        visuals.panel_fill = color;
+        visuals.window_fill = color;
+    }
+    if let Some(color) = color(&file.text, "text")? {
+        visuals.override_text_color = Some(color);
+    }
+    if let Some(color) = color(&file.editor_background, "editor_background")? {
+        visuals.extreme_bg_color = color;
+    }
+    if let Some(color) = color(&file.caret, "caret")? {
+        visuals.text_cursor.stroke.color = color;
+    }
+    if let Some(color) = color(&file.selection, "selection")? {
+        visuals.selection.bg_fill = color;
+    }
+    if let Some(color) = color(&file.accent, "accent")? {
+        visuals.hyperlink_color = color;
+        visuals.selection.stroke.color = color;
+    }
+    Ok(visuals)
+}
+
+/// Parse one entry's color, if the file has it
+fn color(value: &Option<String>, name: &str) -> Result<Option<Color32>> {
+    value
+        .as_deref()
+        .map(|text| parse_hex(text).context(format!("Theme entry \"{}\"", name)))
+        .transpose()
+}
+
+/// "#rrggbb" or "#rrggbbaa" → a color
+fn parse_hex(text: &str) -> Result<Color32> {
+    let hex = text.trim().trim_start_matches('#');
+    if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+        bail!("\"{}\" isn't a #rrggbb color", text);
+    }
+    let byte = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16)
+            .with_context(|| format!("\"{}\" isn't a #rrggbb color", text))
+    };
+    let (r, g, b) = (byte(0)?, byte(2)?, byte(4)?);
+    let a = if hex.len() == 8 { byte(6)? } else { 255 };
+    Ok(Color32::from_rgba_unmultiplied(r, g, b, a))
+}
@@ -0,0 +1,481 @@
+//! FILE: src/thesaurus.rs
+//!
+//! Dictionary and thesaurus lookups: right-click a word in the editor and
+//! pick Define or Synonyms. Clicking a synonym puts it in place of the
+//! word, capitalized the way the word was.
+//!
+//! THE DATA:
+//! Both come from WordNet (https://wordnet.princeton.edu), read offline
+//! from its database files: data.noun, data.verb, data.adj and data.adv,
+//! found in the `dict` folder of a WordNet 3.x download. The app looks in
+//! `<data dir>/wordnet` unless another folder has been chosen (the popup
+//! offers to when there's nothing there). Each line of a data file is one
+//! sense ("synset"): the words that share it, and a definition ("gloss")
+//! with usage examples in quotes after it.
+//!
+//! Until a download is in place, a small set of common senses in WordNet's
+//! format built into the app (thesaurus_small.txt, a few words in each part
+//! of speech) is used instead, and the popup says so. A chosen folder with
+//! no data files in it is still an error, so a wrong choice shows.
+//!
+//! The files are read once, on a background thread, the first time a word
+//! is looked up; they make a map from each word to its senses.
+//!
+//! INFLECTED WORDS:
+//! "ran" isn't in WordNet, "run" is. Like WordNet's own morphy, a word with
+//! no senses of its own is looked up with common endings taken off or
+//! swapped: "cities" → "city", "hoped" → "hope", "faster" → "fast".
+//! Irregular forms ("ran", "mice") aren't worked out.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Parsing a line-based format with split_whitespace and iterators
+//! - u32 indices into a Vec in place of references, so the map and the
+//!   senses it points into can live in one struct
+
+use crate::autocorrect;
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// The built-in selection (see module docs): WordNet's line format in one
+/// file, the part of speech taken from each line's ss_type field
+const SMALL_DATA: &str = include_str!("thesaurus_small.txt");
+
+/// WordNet's data files, with the part of speech of each
+const DATA_FILES: [(&str, PartOfSpeech); 4] = [
+    ("data.noun", PartOfSpeech::Noun),
+    ("data.verb", PartOfSpeech::Verb),
+    ("data.adj", PartOfSpeech::Adjective),
+    ("data.adv", PartOfSpeech::Adverb),
+];
+
+/// Endings taken off or swapped to find an inflected word's base form
+/// (ending, replacement), as morphy does
+const DETACHMENTS: &[(&str, &str)] = &[
+    // Nouns
+    ("s", ""),
+    ("ses", "s"),
+    ("xes", "x"),
+    ("zes", "z"),
+    ("ches", "ch"),
+    ("shes", "sh"),
+    ("men", "man"),
+    ("ies", "y"),
+    // Verbs
+    ("es", "e"),
+    ("es", ""),
+    ("ed", "e"),
+    ("ed", ""),
+    ("ing", "e"),
+    ("ing", ""),
+    // Adjectives
+    ("er", ""),
+    ("est", ""),
+    ("er", "e"),
+    ("est", "e"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartOfSpeech {
+    Noun,
+    Verb,
+    Adjective,
+    Adverb,
+}
+
+impl PartOfSpeech {
+    /// Short form for the popup, as dictionaries print it
+    pub fn label(self) -> &'static str {
+        match self {
+            PartOfSpeech::Noun => "noun",
+            PartOfSpeech::Verb => "verb",
+            PartOfSpeech::Adjective => "adj.",
+            PartOfSpeech::Adverb => "adv.",
+        }
+    }
+}
+
+/// One meaning, and the words that have it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sense {
+    pub pos: PartOfSpeech,
+    /// The words, as WordNet spells them ("ice cream", "Paris")
+    pub words: Vec<String>,
+    pub definition: String,
+    /// Usage examples, without their quotes
+    pub examples: Vec<String>,
+}
+
+/// The loaded WordNet data
+#[derive(Debug, Default)]
+pub struct WordNet {
+    senses: Vec<Sense>,
+    /// Lowercased word → indices into `senses`
+    index: HashMap<String, Vec<u32>>,
+    /// Whether this is the built-in selection rather than a download
+    small: bool,
+}
+
+impl WordNet {
+    /// Whether this is the small built-in selection (see module docs)
+    pub fn is_small(&self) -> bool {
+        self.small
+    }
+
+    /// Add a sense, indexed under each of its words
+    fn add(&mut self, sense: Sense) {
+        let number = self.senses.len() as u32;
+        for word in &sense.words {
+            let senses = self.index.entry(word.to_lowercase()).or_default();
+            if senses.last() != Some(&number) {
+                senses.push(number);
+            }
+        }
+        self.senses.push(sense);
+    }
+
+    /// The senses of `word`, or of its base form (see module docs); nouns
+    /// first, then verbs, adjectives and adverbs, each in WordNet's order
+    pub fn lookup(&self, word: &str) -> Vec<&Sense> {
+        let word = word.to_lowercase().replace('’', "'");
+        let mut found: Vec<u32> = self.index.get(&word).cloned().unwrap_or_default();
+        if found.is_empty() {
+            for (ending, replacement) in DETACHMENTS {
+                if let Some(stem) = word.strip_suffix(ending).filter(|s| !s.is_empty()) {
+                    let base = format!("{}{}", stem, replacement);
+                    for &index in self.index.get(&base).into_iter().flatten() {
+                        if !found.contains(&index) {
+                            found.push(index);
+                        }
+                    }
+                }
+            }
+            found.sort_unstable();
+        }
+        found.iter().map(|&i| &self.senses[i as usize]).collect()
+    }
+}
+
+/// The folder to read WordNet from: `dict` inside `dir` if it has one (the
+/// download's top folder was chosen), otherwise `dir` itself
+pub fn data_dir(dir: &Path) -> PathBuf {
+    let dict = dir.join("dict");
+    if dict.join(DATA_FILES[0].0).exists() {
+        dict
+    } else {
+        dir.to_path_buf()
+    }
+}
+
+/// Whether `dir` (see data_dir) has any of WordNet's data files
+pub fn has_data(dir: &Path) -> bool {
+    let dir = data_dir(dir);
+    DATA_FILES.iter().any(|(name, _)| dir.join(name).exists())
+}
+
+/// The small built-in selection, for when no download is in place
+pub fn load_small() -> WordNet {
+    let mut wordnet = WordNet {
+        small: true,
+        ..WordNet::default()
+    };
+    for line in SMALL_DATA.lines() {
+        let pos = match line.split_whitespace().nth(2) {
+            Some("n") => PartOfSpeech::Noun,
+            Some("v") => PartOfSpeech::Verb,
+            // "s" is a satellite adjective, one clustered around another
+            Some("a" | "s") => PartOfSpeech::Adjective,
+            Some("r") => PartOfSpeech::Adverb,
+            _ => continue,
+        };
+        if let Some(sense) = parse_line(line, pos) {
+            wordnet.add(sense);
+        }
+    }
+    wordnet
+}
+
+/// Read WordNet's data files from `dir` (see data_dir)
+///
+/// Err if none of them is there, or one can't be read.
+pub fn load(dir: &Path) -> Result<WordNet> {
+    let dir = data_dir(dir);
+    let mut wordnet = WordNet::default();
+    let mut files = 0;
+    for (name, pos) in DATA_FILES {
+        let path = dir.join(name);
+        if !path.exists() {
+            continue;
+        }
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        files += 1;
+        for sense in text.lines().filter_map(|line| parse_line(line, pos)) {
+            wordnet.add(sense);
+        }
+    }
+    if files == 0 {
+        bail!(
+            "No WordNet data in {} (looked for data.noun, data.verb, data.adj and data.adv)",
+            dir.display()
+        );
+    }
+    Ok(wordnet)
+}
+
+/// One line of a data file: `offset lex_filenum ss_type w_cnt word lex_id
+/// [word lex_id...] ... | gloss`, w_cnt in hex
+///
+/// None for the license lines at the top (they start with a space) and
+/// anything else that doesn't fit.
+fn parse_line(line: &str, pos: PartOfSpeech) -> Option<Sense> {
+    if line.starts_with(' ') {
+        return None;
+    }
+    let (fields, gloss) = line.split_once(" | ").unwrap_or((line, ""));
+    let mut fields = fields.split_whitespace().skip(3);
+    let count = usize::from_str_radix(fields.next()?, 16).ok()?;
+    let words = (0..count)
+        .map(|_| {
+            let word = fields.next()?;
+            fields.next()?;
+            // Adjectives can carry a position marker: "galore(ip)"
+            let word = word.split_once('(').map_or(word, |(word, _)| word);
+            Some(word.replace('_', " "))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut parts = gloss.trim().split("; \"");
+    let definition = parts.next().unwrap_or("").trim().to_string();
+    let examples = parts
+        .map(|example| {
+            example
+                .trim()
+                .trim_end_matches(';')
+                .trim_matches('"')
+                .to_string()
+        })
+        .collect();
+    Some(Sense {
+        pos,
+        words,
+        definition,
+        examples,
+    })
+}
+
+/// The char range of the word at char index `index` in `text`
+///
+/// A click just past a word's last letter still counts as on it. Letters,
+/// digits, and apostrophes or hyphens between them make up a word.
+pub fn word_at(text: &str, index: usize) -> Option<Range<usize>> {
+    let chars: Vec<char> = text.chars().collect();
+    let is_word = |c: char| c.is_alphanumeric() || matches!(c, '\'' | '’' | '-');
+    let index = if chars.get(index).is_some_and(|&c| is_word(c)) {
+        index
+    } else {
+        index.checked_sub(1).filter(|&i| is_word(chars[i]))?
+    };
+    let mut start = index;
+    while start > 0 && is_word(chars[start - 1]) {
+        start -= 1;
+    }
+    let mut end = index + 1;
+    while end < chars.len() && is_word(chars[end]) {
+        end += 1;
+    }
+    // Quotes and dashes around the word aren't part of it
+    while start < end && !chars[start].is_alphanumeric() {
+        start += 1;
+    }
+    while end > start && !chars[end - 1].is_alphanumeric() {
+        end -= 1;
+    }
+    (start < end).then_some(start..end)
+}
+
+/// What the popup shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LookupKind {
+    Define,
+    Synonyms,
+}
+
+/// A word being looked up, and where it is
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lookup {
+    pub kind: LookupKind,
+    pub word: String,
+    /// Char range of the word in the text, for replacing it
+    pub range: Range<usize>,
+    /// Where the popup opens (the right-click)
+    pub pos: egui::Pos2,
+}
+
+/// Draw the popup's contents
+///
+/// Returns the synonym clicked, cased like the looked-up word.
+pub fn show(ui: &mut egui::Ui, lookup: &mut Lookup, wordnet: &WordNet) -> Option<String> {
+    ui.horizontal(|ui| {
+        ui.selectable_value(&mut lookup.kind, LookupKind::Define, "Define");
+        ui.selectable_value(&mut lookup.kind, LookupKind::Synonyms, "Synonyms");
+    });
+    ui.separator();
+
+    let senses = wordnet.lookup(&lookup.word);
+    if senses.is_empty() {
+        ui.label(format!("“{}” isn't in the dictionary.", lookup.word));
+        return None;
+    }
+
+    let mut picked = None;
+    egui::ScrollArea::vertical()
+        .max_height(320.0)
+        .show(ui, |ui| match lookup.kind {
+            LookupKind::Define => {
+                for (number, sense) in senses.iter().enumerate() {
+                    ui.label(format!(
+                        "{}. ({}) {}",
+                        number + 1,
+                        sense.pos.label(),
+                        sense.definition
+                    ));
+                    for example in &sense.examples {
+                        ui.label(
+                            egui::RichText::new(format!("“{}”", example))
+                                .italics()
+                                .weak(),
+                        );
+                    }
+                }
+            }
+            LookupKind::Synonyms => {
+                let lowercase = lookup.word.to_lowercase();
+                let mut any = false;
+                for sense in &senses {
+                    let others: Vec<&String> = sense
+                        .words
+                        .iter()
+                        .filter(|w| w.to_lowercase() != lowercase)
+                        .collect();
+                    if others.is_empty() {
+                        continue;
+                    }
+                    any = true;
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "({}) {}",
+                            sense.pos.label(),
+                            sense.definition
+                        ))
+                        .weak(),
+                    );
+                    ui.horizontal_wrapped(|ui| {
+                        for word in others {
+                            if ui
+                                .button(word)
+                                .on_hover_text("Replace the word with this")
+                                .clicked()
+                            {
+                                picked = Some(autocorrect::match_case(&lookup.word, word));
+                            }
+                        }
+                    });
+                }
+                if !any {
+                    ui.label(format!("No synonyms for “{}”.", lookup.word));
+                }
+            }
+        });
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_selection_has_every_part_of_speech() {
+        let wordnet = load_small();
+        assert!(wordnet.is_small());
+        for pos in [
+            PartOfSpeech::Noun,
+            PartOfSpeech::Verb,
+            PartOfSpeech::Adjective,
+            PartOfSpeech::Adverb,
+        ] {
+            assert!(wordnet.senses.iter().any(|s| s.pos == pos), "{:?}", pos);
+        }
+        // The license text at the top isn't read as senses
+        assert!(wordnet.lookup("wordnet").is_empty());
+        assert!(wordnet.lookup("princeton").is_empty());
+    }
+
+    #[test]
+    fn small_selection_looks_words_up() {
+        let wordnet = load_small();
+        let happy = wordnet.lookup("Happy");
+        assert_eq!(happy.len(), 1);
+        assert_eq!(happy[0].pos, PartOfSpeech::Adjective);
+        assert!(happy[0].words.contains(&String::from("glad")));
+        assert_eq!(happy[0].examples[0], "a happy smile");
+
+        // Nouns before verbs, and satellites count as adjectives
+        let end = wordnet.lookup("end");
+        assert_eq!(end[0].pos, PartOfSpeech::Noun);
+        assert_eq!(end[1].pos, PartOfSpeech::Verb);
+        assert_eq!(wordnet.lookup("quiet")[1].pos, PartOfSpeech::Adjective);
+
+        // Underscores are spaces, and inflections find their base form
+        assert!(wordnet.lookup("run away")[0]
+            .words
+            .contains(&String::from("flee")));
+        assert_eq!(
+            wordnet.lookup("whispered")[0].definition,
+            "speak softly; in a low voice"
+        );
+    }
+
+    #[test]
+    fn parses_a_data_line() {
+        let line = "01148283 00 a 02 happy 0 galore(ip) 0 001 & 01149494 a 0000 | \
+                    enjoying joy; \"a happy smile\"; \"happy days\"";
+        let sense = parse_line(line, PartOfSpeech::Adjective).unwrap();
+        assert_eq!(sense.words, ["happy", "galore"]);
+        assert_eq!(sense.definition, "enjoying joy");
+        assert_eq!(sense.examples, ["a happy smile", "happy days"]);
+        assert_eq!(parse_line("  1 This software", PartOfSpeech::Noun), None);
+    }
+
+    #[test]
+    fn loads_from_a_folder_or_says_it_cannot() {
+        let dir =
+            std::env::temp_dir().join(format!("bookscript-test-{}-wordnet", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("dict")).unwrap();
+        assert!(!has_data(&dir));
+        assert!(load(&dir).is_err());
+
+        std::fs::write(
+            dir.join("dict").join("data.noun"),
+            "07020538 10 n 02 story 0 narrative 0 000 | a message that tells of events\n",
+        )
+        .unwrap();
+        assert!(has_data(&dir));
+        let wordnet = load(&dir).unwrap();
+        assert!(!wordnet.is_small());
+        assert_eq!(wordnet.lookup("narratives")[0].pos, PartOfSpeech::Noun);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn finds_the_word_at_a_click() {
+        let text = "She said, \"don't-go\" quietly.";
+        let word = |i| word_at(text, i).map(|r| text[r].to_string());
+        assert_eq!(word(1).as_deref(), Some("She"));
+        assert_eq!(word(3).as_deref(), Some("She"));
+        assert_eq!(word(12).as_deref(), Some("don't-go"));
+        assert_eq!(word(9), None);
+    }
+}
@@ -0,0 +1,329 @@
+//! FILE: src/toc.rs
+//!
+//! Tools → Table of Contents: the document's acts, chapters and scenes
+//! listed in the text itself, each with the line (or printed page) it
+//! starts on.
+//!
+//! The list sits between two comment lines:
+//!
+//! ```text
+//! [[contents]]
+//! Contents
+//! Act I, page 1
+//!   Arrival, page 1
+//!     The beach, page 2
+//! [[/contents]]
+//! ```
+//!
+//! The markers are how running the command again finds the list and brings
+//! it up to date in place, instead of adding a second one. Being comments,
+//! they never reach an export. The entries are ordinary text: they're part
+//! of the manuscript, and every exporter but HTML and EPUB prints them.
+//! Those have linked contents of their own, so the list is taken out for
+//! them (remove).
+//!
+//! Every entry has a lowercase word in it ("line", "page"), so the parser
+//! never takes one for an all-capitals character cue.
+//!
+//! THE LIST MOVES WHAT IT LISTS:
+//! Putting twelve lines at the top moves everything else down twelve lines,
+//! and maybe onto a later page. So the references are worked out on the
+//! text with the new list already in it - and again if the new numbers
+//! changed the list's own length on the page - until they stop changing.
+//!
+//! RUST CONCEPTS DEMONSTRATED:
+//! - Iterating to a fixed point
+//! - Cow: handing back the text untouched when there's nothing to change
+
+use crate::parser;
+use crate::pdf;
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// The line that opens the list
+const OPEN: &str = "[[contents]]";
+/// The line that closes it
+const CLOSE: &str = "[[/contents]]";
+/// The list's heading, first line inside the markers
+const HEADING: &str = "Contents";
+
+/// Most times the references are worked out again (see module docs); they
+/// settle in two for line numbers and almost always two or three for pages
+const MAX_PASSES: usize = 5;
+
+/// What each entry points to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum References {
+    /// The line in the editor
+    Lines,
+    /// The page as printed or exported to PDF
+    Pages,
+}
+
+impl References {
+    fn word(self) -> &'static str {
+        match self {
+            References::Lines => "line",
+            References::Pages => "page",
+        }
+    }
+}
+
+/// One act, chapter or scene: how deep it sits and its title
+struct Entry {
+    depth: usize,
+    title: String,
+}
+
+/// The list's lines in `text`, markers included, as a 0-based line range
+///
+/// None if there isn't one, or its closing marker is missing.
+pub fn find(text: &str) -> Option<Range<usize>> {
+    let mut lines = text.lines().map(str::trim).enumerate();
+    let start = lines.find(|(_, line)| line.eq_ignore_ascii_case(OPEN))?.0;
+    let end = lines.find(|(_, line)| line.eq_ignore_ascii_case(CLOSE))?.0;
+    Some(start..end + 1)
+}
+
+/// `text` without its list, markers and all
+pub fn remove(text: &str) -> Cow<'_, str> {
+    let Some(range) = find(text) else {
+        return Cow::Borrowed(text);
+    };
+    let mut lines: Vec<&str> = text.split_inclusive('\n').collect();
+    lines.drain(range);
+    Cow::Owned(lines.concat())
+}
+
+/// `text` with an up-to-date list: the existing one replaced, or a new one
+/// put in before `line` (0-based) with blank lines around it
+///
+/// None if there are no acts, chapters or scenes to list.
+pub fn update(text: &str, line: usize, references: References) -> Option<String> {
+    let entries = entries(text);
+    if entries.is_empty() {
+        return None;
+    }
+
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let (range, before, after) = match find(text) {
+        Some(range) => (range, "", ""),
+        None => {
+            let line = line.min(lines.len());
+            let blank = |index: Option<usize>| {
+                index
+                    .and_then(|i| lines.get(i))
+                    .is_none_or(|l| l.trim().is_empty())
+            };
+            // A list added where there's text either side gets a blank line
+            // to keep it out of that paragraph (or speech)
+            let before = if blank(line.checked_sub(1)) { "" } else { "\n" };
+            let after = if blank(Some(line)) { "" } else { "\n" };
+            (line..line, before, after)
+        }
+    };
+    // Text ending without a line break needs one before a list at the end
+    let before = match lines.get(range.start.wrapping_sub(1)) {
+        Some(last) if range.start == lines.len() && !last.ends_with('\n') => "\n\n",
+        _ => before,
+    };
+
+    let with_list = |numbers: &[usize]| {
+        let mut out = String::with_capacity(text.len() + 40 * entries.len());
+        out.extend(lines[..range.start].iter().copied());
+        out.push_str(before);
+        out.push_str(&list(&entries, numbers, references));
+        out.push_str(after);
+        out.extend(lines[range.end..].iter().copied());
+        out
+    };
+
+    let mut numbers = vec![0; entries.len()];
+    for _ in 0..MAX_PASSES {
+        let candidate = with_list(&numbers);
+        let found = references_in(&candidate, references);
+        if found == numbers {
+            return Some(candidate);
+        }
+        numbers = found;
+    }
+    Some(with_list(&numbers))
+}
+
+/// The acts, chapters and scenes of `text`, in document order
+///
+/// Chapters are indented under acts when there are any, and scenes under
+/// the chapter (or act) they're in.
+fn entries(text: &str) -> Vec<Entry> {
+    let structure = parser::extract_structure(&parser::parse_document(text));
+    let acts = usize::from(!structure.acts.is_empty());
+
+    let mut entries: Vec<(usize, Entry)> = Vec::new();
+    for act in &structure.acts {
+        let title = format!("Act {}", act.title);
+        entries.push((act.line_start, Entry { depth: 0, title }));
+    }
+    for chapter in &structure.chapters {
+        let depth = usize::from(chapter.parent_act.is_some());
+        let title = chapter.title.clone();
+        entries.push((chapter.line_start, Entry { depth, title }));
+    }
+    for scene in &structure.scenes {
+        let depth = acts.min(usize::from(scene.parent_act.is_some()))
+            + usize::from(scene.parent_chapter.is_some());
+        let title = scene.description.clone();
+        entries.push((scene.line_start, Entry { depth, title }));
+    }
+    entries.sort_by_key(|(line, _)| *line);
+    entries.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// Where each act, chapter and scene of `text` is, in document order
+fn references_in(text: &str, references: References) -> Vec<usize> {
+    match references {
+        References::Lines => {
+            let structure = parser::extract_structure(&parser::parse_document(text));
+            let mut lines: Vec<usize> = structure
+                .acts
+                .iter()
+                .map(|a| a.line_start)
+                .chain(structure.chapters.iter().map(|c| c.line_start))
+                .chain(structure.scenes.iter().map(|s| s.line_start))
+                .collect();
+            lines.sort_unstable();
+            lines
+        }
+        // As printed: comments (the markers among them) are left out
+        References::Pages => pdf::heading_pages(&parser::strip_comments(text)),
+    }
+}
+
+/// The list's lines, markers included
+fn list(entries: &[Entry], numbers: &[usize], references: References) -> String {
+    let mut out = format!("{}\n{}\n", OPEN, HEADING);
+    for (entry, number) in entries.iter().zip(numbers) {
+        out.push_str(&format!(
+            "{}{}, {} {}\n",
+            "  ".repeat(entry.depth),
+            entry.title,
+            references.word(),
+            number
+        ));
+    }
+    out.push_str(CLOSE);
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (depth, title) of each entry
+    fn listed(text: &str) -> Vec<(usize, String)> {
+        entries(text)
+            .into_iter()
+            .map(|entry| (entry.depth, entry.title))
+            .collect()
+    }
+
+    fn entry(depth: usize, title: &str) -> (usize, String) {
+        (depth, title.to_string())
+    }
+
+    #[test]
+    fn acts_chapters_and_scenes_are_nested() {
+        let text = "[ACT: I]\n[CHAPTER: Arrival]\n[SCENE: The beach]\nText\n\
+                    [ACT: II]\n[SCENE: Storm]\n[CHAPTER: Home]\n[SCENE: Kitchen]\n";
+        assert_eq!(
+            listed(text),
+            [
+                entry(0, "Act I"),
+                entry(1, "Arrival"),
+                entry(2, "The beach"),
+                entry(0, "Act II"),
+                // Straight under the act
+                entry(1, "Storm"),
+                entry(1, "Home"),
+                entry(2, "Kitchen"),
+            ]
+        );
+    }
+
+    #[test]
+    fn without_acts_chapters_are_the_top_level() {
+        let text = "[SCENE: Prologue]\n[CHAPTER: One]\n[SCENE: Beach]\n[CHAPTER: Two]\n";
+        assert_eq!(
+            listed(text),
+            [
+                entry(0, "Prologue"),
+                entry(0, "One"),
+                entry(1, "Beach"),
+                entry(0, "Two"),
+            ]
+        );
+        assert!(listed("Just text.\nHERO\nHello.\n").is_empty());
+        assert_eq!(update("Just text.\n", 0, References::Lines), None);
+    }
+
+    #[test]
+    fn line_numbers_count_the_list_itself() {
+        let text = "[CHAPTER: One]\nText\n[SCENE: Beach]\nMore\n";
+        let updated = update(text, 0, References::Lines).unwrap();
+        assert_eq!(
+            updated,
+            "[[contents]]\nContents\nOne, line 7\n  Beach, line 9\n[[/contents]]\n\n\
+             [CHAPTER: One]\nText\n[SCENE: Beach]\nMore\n"
+        );
+        // Running it again changes nothing
+        assert_eq!(update(&updated, 3, References::Lines).unwrap(), updated);
+        assert_eq!(find(&updated), Some(0..5));
+        assert_eq!(remove(&updated), format!("\n{}", text));
+    }
+
+    #[test]
+    fn an_existing_list_is_replaced_in_place() {
+        let text = "Title\n\n[[contents]]\nContents\nOld, line 1\n[[/contents]]\n\n\
+                    [CHAPTER: One]\n[CHAPTER: Two]\n";
+        assert_eq!(
+            update(text, 8, References::Lines).unwrap(),
+            "Title\n\n[[contents]]\nContents\nOne, line 9\nTwo, line 10\n[[/contents]]\n\n\
+             [CHAPTER: One]\n[CHAPTER: Two]\n"
+        );
+    }
+
+    #[test]
+    fn a_list_in_text_gets_blank_lines_around_it() {
+        let text = "Before\nAfter\n[CHAPTER: One]";
+        assert_eq!(
+            update(text, 1, References::Lines).unwrap(),
+            "Before\n\n[[contents]]\nContents\nOne, line 9\n[[/contents]]\n\nAfter\n[CHAPTER: One]"
+        );
+        // At the end of text with no final line break
+        assert_eq!(
+            update(text, 9, References::Lines).unwrap(),
+            "Before\nAfter\n[CHAPTER: One]\n\n[[contents]]\nContents\nOne, line 3\n[[/contents]]\n"
+        );
+    }
+
+    #[test]
+    fn page_numbers_are_as_printed() {
+        let text = "[CHAPTER: One]\nText\n[CHAPTER: Two]\nMore\n";
+        // Each chapter starts a page, and the list takes the first
+        assert_eq!(
+            update(text, 0, References::Pages).unwrap(),
+            "[[contents]]\nContents\nOne, page 2\nTwo, page 3\n[[/contents]]\n\n\
+             [CHAPTER: One]\nText\n[CHAPTER: Two]\nMore\n"
+        );
+    }
+
+    #[test]
+    fn an_unclosed_list_is_not_found() {
+        let text = "[[contents]]\nContents\n[CHAPTER: One]\n";
+        assert_eq!(find(text), None);
+        assert!(matches!(remove(text), Cow::Borrowed(_)));
+        // Markers are matched whatever their case and indentation
+        assert_eq!(find("x\n  [[Contents]]\n[[/CONTENTS]]\n"), Some(1..3));
+        assert_eq!(remove("x\n[[contents]]\nA\n[[/contents]]\ny\n"), "x\ny\n");
+    }
+}